@@ -0,0 +1,32 @@
+//! Compares creating 100k failing `Email`s with and without
+//! capturing backtraces.
+//!
+//! Run with `cargo bench --bench backtrace_capture`.
+extern crate mail_headers;
+
+use std::time::Instant;
+
+use mail_headers::HeaderTryFrom;
+use mail_headers::error::set_backtrace_capture;
+use mail_headers::header_components::Email;
+
+const ITERATIONS: usize = 100_000;
+
+fn run(name: &str) {
+    let start = Instant::now();
+    let mut failed = 0;
+    for _ in 0..ITERATIONS {
+        if Email::try_from("no-at-sign.example.com").is_err() {
+            failed += 1;
+        }
+    }
+    println!("{:>16}: {:?} ({} failed)", name, start.elapsed(), failed);
+}
+
+fn main() {
+    set_backtrace_capture(true);
+    run("with backtrace");
+
+    set_backtrace_capture(false);
+    run("without backtrace");
+}
@@ -0,0 +1,74 @@
+//! Compares encoding the header blocks of 10k small maps one by one
+//! (allocating a new buffer for each map) with using a `EncodeSession`.
+//!
+//! Allocations are counted with a counting global allocator.
+//!
+//! Run with `cargo bench --bench encode_session`.
+#[macro_use]
+extern crate mail_headers;
+extern crate mail_internals;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::time::{Duration, Instant};
+
+use mail_internals::MailType;
+use mail_headers::HeaderMap;
+use mail_headers::headers::*;
+use mail_headers::map::{EncodeSession, InjectionGuard};
+
+const MAPS: usize = 10_000;
+
+static ALLOCATIONS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+struct CountingAlloc;
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn maps() -> Vec<HeaderMap> {
+    (0..MAPS)
+        .map(|idx| headers! {
+            _From: [("Our Newsletter", "newsletter@example.com")],
+            _To: [format!("recipient{}@example.com", idx).as_str()],
+            Subject: "The weekly newsletter with all the news of this week"
+        }.unwrap())
+        .collect()
+}
+
+fn run<F>(name: &str, mut encode: F) -> Duration
+    where F: FnMut(&HeaderMap) -> usize
+{
+    // fresh maps for each run, so that no encoded value is cached yet
+    let maps = maps();
+    let mut checksum = 0;
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for map in maps.iter() {
+        checksum += encode(map);
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+    println!("{:>10}: {:?} ({} maps, {} allocations, checksum {})",
+        name, elapsed, maps.len(), allocations, checksum);
+    elapsed
+}
+
+fn main() {
+    run("standalone", |map| {
+        map.encode_with_guard(MailType::Ascii, InjectionGuard::Disabled).unwrap().len()
+    });
+    let mut session = EncodeSession::new(MailType::Ascii);
+    run("session", |map| session.encode_map(map).unwrap().len());
+}
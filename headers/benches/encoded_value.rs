@@ -0,0 +1,69 @@
+//! Compares running several passes which need the encoded value of each
+//! header of a 30-header map once by re-encoding each header in every pass
+//! and once using the cached `HeaderObjTrait::encoded_value`.
+//!
+//! Run with `cargo bench --bench encoded_value`.
+extern crate mail_headers;
+extern crate mail_internals;
+
+use std::time::{Duration, Instant};
+
+use mail_internals::MailType;
+use mail_internals::encoder::EncodingBuffer;
+use mail_headers::{HeaderMap, HeaderKind};
+use mail_headers::headers::{_To, Cc, Comments, Subject};
+
+/// e.g. a fingerprint, a deduplication and a canonical string pass
+const PASSES: usize = 3;
+const ROUNDS: usize = 200;
+
+fn map() -> HeaderMap {
+    let mut map = HeaderMap::new();
+    map.insert(_To::auto_body(["to1@example.com", "to2@example.com", "to3@example.com"]).unwrap());
+    map.insert(Cc::auto_body(["cc1@example.com", "cc2@example.com"]).unwrap());
+    map.insert(Subject::auto_body("a subject which is long enough to be folded at some point, or not").unwrap());
+    for idx in 0..27 {
+        map.insert(Comments::auto_body(format!("comment number {} with some text", idx)).unwrap());
+    }
+    map
+}
+
+fn reencode(map: &HeaderMap) -> usize {
+    let mut total = 0;
+    for (_name, obj) in map.iter() {
+        let mut buffer = EncodingBuffer::new(MailType::Ascii);
+        {
+            let mut handle = buffer.writer();
+            obj.encode(&mut handle).unwrap();
+            handle.finish_header();
+        }
+        total += buffer.as_slice().len();
+    }
+    total
+}
+
+fn cached(map: &HeaderMap) -> usize {
+    map.iter()
+        .map(|(_name, obj)| obj.encoded_value(MailType::Ascii).unwrap().len())
+        .sum()
+}
+
+fn run(name: &str, pass: fn(&HeaderMap) -> usize) -> Duration {
+    let mut checksum = 0;
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        // a fresh map each round, so the cache starts empty
+        let map = map();
+        for _ in 0..PASSES {
+            checksum += pass(&map);
+        }
+    }
+    let elapsed = start.elapsed();
+    println!("{:>8}: {:?} ({} rounds, checksum {})", name, elapsed, ROUNDS, checksum);
+    elapsed
+}
+
+fn main() {
+    run("reencode", reencode);
+    run("cached", cached);
+}
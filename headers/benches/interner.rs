@@ -0,0 +1,50 @@
+//! Compares building maps with the same `From` and `Subject` with
+//! and without a `HeaderInterner`.
+//!
+//! Run with `cargo bench --bench interner`.
+extern crate mail_headers;
+
+use std::time::{Duration, Instant};
+
+use mail_headers::{HeaderMap, HeaderKind, HeaderTryFrom};
+use mail_headers::headers::*;
+use mail_headers::header_components::{Mailbox, MailboxList};
+use mail_headers::interner::HeaderInterner;
+
+const MESSAGES: usize = 10_000;
+
+const NAME: &str = "Our Newsletter";
+const EMAIL: &str = "newsletter@example.com";
+const SUBJECT: &str = "The weekly newsletter with all the news of this week";
+
+fn run<F>(name: &str, func: F) -> Duration
+    where F: Fn() -> HeaderMap
+{
+    let start = Instant::now();
+    let maps = (0..MESSAGES).map(|_| func()).collect::<Vec<_>>();
+    let elapsed = start.elapsed();
+    println!("{:>16}: {:?} ({} maps)", name, elapsed, maps.len());
+    elapsed
+}
+
+fn main() {
+    run("without interner", || {
+        let mut map = HeaderMap::new();
+        let from = Mailbox::try_from((NAME, EMAIL)).unwrap();
+        map.insert(_From::body(MailboxList::from_single(from)));
+        map.insert(Subject::auto_body(SUBJECT).unwrap());
+        map
+    });
+
+    let interner = HeaderInterner::new();
+    run("with interner", || {
+        let mut map = HeaderMap::new();
+        let from = interner.mailbox(Some(NAME), EMAIL).unwrap();
+        map.insert(_From::body(MailboxList::from_single(from)));
+        map.insert(interner.subject(SUBJECT));
+        map
+    });
+
+    let stats = interner.stats();
+    println!("{:>16}: {} entries, {} bytes saved", "interner stats", stats.entries, stats.bytes_saved);
+}
@@ -0,0 +1,75 @@
+//! Compares cloning a 25-header template map by deep-cloning every header
+//! body (what cloning a map did before header bodies were shared) and by
+//! cloning the map (which shares the bodies until they are mutated).
+//!
+//! Run with `cargo bench --bench map_clone`.
+#[macro_use]
+extern crate mail_headers;
+
+use std::time::{Duration, Instant};
+
+use mail_headers::{HeaderMap, HeaderKind, Header};
+use mail_headers::headers::{_From, _To, Cc, Comments, Subject};
+
+const CLONES: usize = 10_000;
+
+fn recipients(prefix: &str, count: usize) -> Vec<String> {
+    (0..count)
+        .map(|idx| format!("{}{}@example.com", prefix, idx))
+        .collect()
+}
+
+fn template() -> HeaderMap {
+    let mut map = headers! {
+        _From: [("Newsletter", "news@example.com")],
+        _To: recipients("to", 20),
+        Cc: recipients("cc", 20),
+        Subject: "the monthly newsletter of a company with a rather long name"
+    }.unwrap();
+    for idx in 0..21 {
+        map.insert(Comments::auto_body(format!("comment number {} of the template", idx)).unwrap());
+    }
+    assert_eq!(map.len(), 25);
+    map
+}
+
+fn deep_copy<H: HeaderKind>(header: &Header<H>) -> Header<H> {
+    H::body(header.body().clone())
+}
+
+/// Clones the map copying every header body.
+fn deep_clone(map: &HeaderMap) -> HeaderMap {
+    let mut clone = HeaderMap::with_capacity(map.len());
+    for (_, obj) in map.iter() {
+        if let Some(header) = obj.downcast_ref::<_From>() {
+            clone.insert(deep_copy(header));
+        } else if let Some(header) = obj.downcast_ref::<_To>() {
+            clone.insert(deep_copy(header));
+        } else if let Some(header) = obj.downcast_ref::<Cc>() {
+            clone.insert(deep_copy(header));
+        } else if let Some(header) = obj.downcast_ref::<Subject>() {
+            clone.insert(deep_copy(header));
+        } else if let Some(header) = obj.downcast_ref::<Comments>() {
+            clone.insert(deep_copy(header));
+        } else {
+            unreachable!("unexpected header in template");
+        }
+    }
+    clone
+}
+
+fn run<F>(name: &str, map: &HeaderMap, mut clone: F) -> Duration
+    where F: FnMut(&HeaderMap) -> HeaderMap
+{
+    let start = Instant::now();
+    let clones = (0..CLONES).map(|_| clone(map)).collect::<Vec<_>>();
+    let elapsed = start.elapsed();
+    println!("{:>8}: {:?} ({} clones)", name, elapsed, clones.len());
+    elapsed
+}
+
+fn main() {
+    let map = template();
+    run("deep", &map, deep_clone);
+    run("shared", &map, HeaderMap::clone);
+}
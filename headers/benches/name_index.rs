@@ -0,0 +1,67 @@
+//! Compares 30 `contains` checks per map done directly on the map
+//! with the same checks done through a `NameIndex`.
+//!
+//! Run with `cargo bench --bench name_index`.
+#[macro_use]
+extern crate mail_headers;
+
+use std::time::{Duration, Instant};
+
+use mail_headers::{HeaderMap, HeaderName, HeaderKind};
+use mail_headers::headers::*;
+
+const MESSAGES: usize = 10_000;
+
+const QUERIES: &[&str] = &[
+    "Date", "From", "Sender", "Reply-To", "To", "Cc", "Bcc", "Message-Id",
+    "In-Reply-To", "References", "Subject", "Comments", "Keywords", "Resent-Date",
+    "Resent-From", "Resent-Sender", "Resent-To", "Resent-Cc", "Resent-Bcc",
+    "Resent-Msg-Id", "Return-Path", "Received", "Content-Type", "Content-Id",
+    "Content-Transfer-Encoding", "Content-Description", "Content-Disposition",
+    "X-Mailer", "X-Spam-Status", "List-Id"
+];
+
+fn create_maps() -> Vec<HeaderMap> {
+    (0..MESSAGES).map(|idx| {
+        let mut map = headers! {
+            _From: ["sender@example.com"],
+            _To: ["receiver@example.com"],
+            Subject: "a subject",
+            Comments: "some comment"
+        }.unwrap();
+        if idx % 2 == 0 {
+            map.insert(Sender::auto_body("other@example.com").unwrap());
+        }
+        map
+    }).collect()
+}
+
+fn run<F>(name: &str, maps: &[HeaderMap], func: F) -> Duration
+    where F: Fn(&HeaderMap, &[HeaderName]) -> usize
+{
+    let names = QUERIES.iter()
+        .map(|name| HeaderName::from_ascii_unchecked(*name))
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let mut found = 0;
+    for map in maps {
+        found += func(map, &names);
+    }
+    let elapsed = start.elapsed();
+    println!("{:>12}: {:?} (found {})", name, elapsed, found);
+    elapsed
+}
+
+fn main() {
+    let maps = create_maps();
+
+    run("map", &maps, |map, names| {
+        names.iter().filter(|name| map.contains(**name)).count()
+    });
+
+    run("name index", &maps, |map, names| {
+        let index = map.build_name_index();
+        names.iter().filter(|name| index.contains(name)).count()
+    });
+}
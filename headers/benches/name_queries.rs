@@ -0,0 +1,95 @@
+//! Compares 8-name `contains_any`/`contains_all`/`which_present` queries
+//! with the same queries done as loops over `contains`.
+//!
+//! Run with `cargo bench --bench name_queries`.
+#[macro_use]
+extern crate mail_headers;
+
+use std::time::{Duration, Instant};
+
+use mail_headers::{HeaderMap, HeaderName, HeaderKind};
+use mail_headers::headers::*;
+
+const MESSAGES: usize = 10_000;
+
+const QUERIES: &[&str] = &[
+    "List-Id", "List-Unsubscribe", "Precedence", "From",
+    "Date", "Message-Id", "Subject", "Sender"
+];
+
+const QUERIES_MIXED_CASE: &[&str] = &[
+    "list-id", "LIST-UNSUBSCRIBE", "precedence", "FROM",
+    "date", "Message-ID", "subject", "SENDER"
+];
+
+fn create_maps() -> Vec<HeaderMap> {
+    (0..MESSAGES).map(|idx| {
+        let mut map = headers! {
+            _From: ["sender@example.com"],
+            _To: ["receiver@example.com"],
+            Subject: "a subject",
+            Comments: "some comment"
+        }.unwrap();
+        if idx % 2 == 0 {
+            map.insert(Sender::auto_body("other@example.com").unwrap());
+        }
+        map
+    }).collect()
+}
+
+fn run<F>(name: &str, maps: &[HeaderMap], func: F) -> Duration
+    where F: Fn(&HeaderMap, &[HeaderName]) -> usize
+{
+    let names = QUERIES.iter()
+        .map(|name| HeaderName::from_ascii_unchecked(*name))
+        .collect::<Vec<_>>();
+
+    let start = Instant::now();
+    let mut found = 0;
+    for map in maps {
+        found += func(map, &names);
+    }
+    let elapsed = start.elapsed();
+    println!("{:>20}: {:?} (found {})", name, elapsed, found);
+    elapsed
+}
+
+fn main() {
+    let maps = create_maps();
+
+    run("any loop", &maps, |map, names| {
+        names.iter().any(|name| map.contains(*name)) as usize
+    });
+
+    run("contains_any", &maps, |map, names| {
+        map.contains_any(names) as usize
+    });
+
+    run("contains_any_str", &maps, |map, _| {
+        map.contains_any_str(QUERIES_MIXED_CASE) as usize
+    });
+
+    run("all loop", &maps, |map, names| {
+        names.iter().all(|name| map.contains(*name)) as usize
+    });
+
+    run("contains_all", &maps, |map, names| {
+        map.contains_all(names) as usize
+    });
+
+    run("contains_all_str", &maps, |map, _| {
+        map.contains_all_str(QUERIES_MIXED_CASE) as usize
+    });
+
+    run("which loop", &maps, |map, names| {
+        names.iter().filter(|name| map.contains(**name)).count()
+    });
+
+    run("which_present", &maps, |map, names| {
+        map.which_present(names).into_iter().filter(|&present| present).count()
+    });
+
+    run("which_present_str", &maps, |map, _| {
+        map.which_present_str(QUERIES_MIXED_CASE).into_iter().filter(|&present| present).count()
+    });
+}
@@ -0,0 +1,48 @@
+//! Compares cloning a map containing a 500-entry `MailboxList` once
+//! owned and once as `Shared<MailboxList>`.
+//!
+//! Run with `cargo bench --bench shared`.
+#[macro_use]
+extern crate mail_headers;
+
+use std::time::{Duration, Instant};
+
+use mail_headers::{HeaderMap, HeaderKind, HeaderTryFrom};
+use mail_headers::headers::_To;
+use mail_headers::header_components::{Mailbox, MailboxList, Shared};
+
+pub type SharedMailboxList = Shared<MailboxList>;
+
+def_headers! {
+    test_name: validate_header_names,
+    scope: self,
+    SharedTo, unchecked { "To" }, SharedMailboxList, maxOne, None
+}
+
+const ENTRIES: usize = 500;
+const CLONES: usize = 1_000;
+
+fn list() -> MailboxList {
+    let mailboxes = (0..ENTRIES)
+        .map(|idx| Mailbox::try_from(format!("recipient{}@example.com", idx)).unwrap())
+        .collect::<Vec<_>>();
+    MailboxList::try_from(mailboxes).unwrap()
+}
+
+fn run(name: &str, map: &HeaderMap) -> Duration {
+    let start = Instant::now();
+    let clones = (0..CLONES).map(|_| map.clone()).collect::<Vec<_>>();
+    let elapsed = start.elapsed();
+    println!("{:>8}: {:?} ({} clones)", name, elapsed, clones.len());
+    elapsed
+}
+
+fn main() {
+    let mut owned = HeaderMap::new();
+    owned.insert(_To::body(list()));
+    run("owned", &owned);
+
+    let mut shared = HeaderMap::new();
+    shared.insert(SharedTo::body(Shared::new(list())));
+    run("shared", &shared);
+}
@@ -0,0 +1,181 @@
+//! Detection of the optional features this build of the crate was compiled with.
+//!
+//! This allows e.g. a plugin host to refuse loading a plugin which needs
+//! the `serde-impl` feature with a proper error instead of failing later on:
+//!
+//! ```
+//! use mail_headers::capabilities::{self, capabilities};
+//!
+//! let caps = capabilities();
+//! assert_eq!(caps.serde_impl, caps.features.contains(&"serde-impl"));
+//! assert_eq!(capabilities::require("serde-impl").is_ok(), caps.serde_impl);
+//! assert!(capabilities::require("no-such-feature").is_err());
+//! ```
+//!
+//! Every (public) feature of the crate has to be listed here.
+use ::error::MissingCapability;
+
+/// The names of all public features of this crate.
+pub const ALL_FEATURES: &[&str] = &[
+    "serde-impl",
+    "traceing",
+    "cheap-errors",
+    "test-helpers",
+    "compress-values"
+];
+
+/// The names of the features this build was compiled with.
+const ENABLED_FEATURES: &[&str] = &[
+    #[cfg(feature="serde-impl")] "serde-impl",
+    #[cfg(feature="traceing")] "traceing",
+    #[cfg(feature="cheap-errors")] "cheap-errors",
+    #[cfg(feature="test-helpers")] "test-helpers",
+    #[cfg(feature="compress-values")] "compress-values"
+];
+
+/// The features (and version) of this build of the crate, see `capabilities()`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Capabilities {
+    /// The `serde-impl` feature, enabling serde support and the `from_json` module.
+    pub serde_impl: bool,
+    /// The `traceing` feature of `mail-internals`.
+    pub traceing: bool,
+    /// The `cheap-errors` feature, disabling backtrace capturing by default.
+    pub cheap_errors: bool,
+    /// The `test-helpers` feature, enabling the `testing` module.
+    pub test_helpers: bool,
+    /// The `compress-values` feature, enabling `HeaderMap::compress_large_values`.
+    pub compress_values: bool,
+    /// The names of all enabled features (in the order of `ALL_FEATURES`).
+    pub features: &'static [&'static str],
+    /// The version of this crate.
+    pub version: &'static str
+}
+
+/// Returns the capabilities of this build of the crate.
+pub const fn capabilities() -> Capabilities {
+    Capabilities {
+        serde_impl: cfg!(feature="serde-impl"),
+        traceing: cfg!(feature="traceing"),
+        cheap_errors: cfg!(feature="cheap-errors"),
+        test_helpers: cfg!(feature="test-helpers"),
+        compress_values: cfg!(feature="compress-values"),
+        features: ENABLED_FEATURES,
+        version: env!("CARGO_PKG_VERSION")
+    }
+}
+
+impl Capabilities {
+
+    /// Returns `(feature name, is enabled)` for every public feature.
+    pub fn flags(&self) -> [(&'static str, bool); 5] {
+        [
+            ("serde-impl", self.serde_impl),
+            ("traceing", self.traceing),
+            ("cheap-errors", self.cheap_errors),
+            ("test-helpers", self.test_helpers),
+            ("compress-values", self.compress_values)
+        ]
+    }
+
+    /// Returns if the feature is enabled or `None` if there is no such feature.
+    pub fn is_enabled(&self, feature: &str) -> Option<bool> {
+        self.flags().iter()
+            .find(|&&(name, _)| name == feature)
+            .map(|&(_, enabled)| enabled)
+    }
+
+    /// Fails if the given feature is not enabled (or does not exist).
+    pub fn require(&self, feature: &str) -> Result<(), MissingCapability> {
+        match self.flags().iter().find(|&&(name, _)| name == feature) {
+            Some(&(_, true)) => Ok(()),
+            Some(&(name, false)) => Err(MissingCapability::Disabled { feature: name }),
+            None => Err(MissingCapability::Unknown { feature: feature.to_owned() })
+        }
+    }
+}
+
+/// Fails if this build of the crate was compiled without the given feature.
+///
+/// Shorthand for `capabilities().require(feature)`.
+pub fn require(feature: &str) -> Result<(), MissingCapability> {
+    capabilities().require(feature)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn flags_match_cfg() {
+        let caps = capabilities();
+        let expected = [
+            cfg!(feature="serde-impl"),
+            cfg!(feature="traceing"),
+            cfg!(feature="cheap-errors"),
+            cfg!(feature="test-helpers"),
+            cfg!(feature="compress-values")
+        ];
+        let flags = caps.flags();
+        assert_eq!(flags.len(), ALL_FEATURES.len());
+        for (&(name, enabled), (&known, &expected)) in flags.iter().zip(ALL_FEATURES.iter().zip(&expected)) {
+            assert_eq!(name, known);
+            assert_eq!(enabled, expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn features_lists_the_enabled_flags() {
+        let caps = capabilities();
+        let enabled = caps.flags().iter()
+            .filter(|&&(_, enabled)| enabled)
+            .map(|&(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(caps.features, &enabled[..]);
+    }
+
+    #[test]
+    fn version_is_the_crate_version() {
+        assert_eq!(capabilities().version, env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn require_enabled_feature() {
+        // tests always need `traceing`
+        assert_eq!(require("traceing"), Ok(()));
+        assert_eq!(capabilities().is_enabled("traceing"), Some(true));
+    }
+
+    #[test]
+    fn require_unknown_feature() {
+        assert_eq!(
+            require("wire-format"),
+            Err(MissingCapability::Unknown { feature: "wire-format".to_owned() })
+        );
+        assert_eq!(capabilities().is_enabled("wire-format"), None);
+    }
+
+    #[cfg(feature="serde-impl")]
+    #[test]
+    fn serde_impl_enabled() {
+        assert!(capabilities().serde_impl);
+        assert_eq!(require("serde-impl"), Ok(()));
+    }
+
+    #[cfg(not(feature="serde-impl"))]
+    #[test]
+    fn serde_impl_disabled() {
+        assert!(!capabilities().serde_impl);
+        assert!(!capabilities().features.contains(&"serde-impl"));
+        let err = assert_err!(require("serde-impl"));
+        assert_eq!(err, MissingCapability::Disabled { feature: "serde-impl" });
+        assert_eq!(err.to_string(), "mail-headers was built without the \"serde-impl\" feature");
+    }
+
+    #[cfg(all(feature="serde-impl", feature="cheap-errors", feature="test-helpers",
+        feature="compress-values"))]
+    #[test]
+    fn all_features_enabled() {
+        assert_eq!(capabilities().features, ALL_FEATURES);
+    }
+}
@@ -1,9 +1,10 @@
 use std::ops::Deref;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 use std::borrow::ToOwned;
 
 use owning_ref::OwningRef;
-use soft_ascii_string::{SoftAsciiString, SoftAsciiStr};
+use soft_ascii::{SoftAsciiString, SoftAsciiStr};
 
 #[cfg(feature="serde")]
 use serde::{Serialize, Deserialize, Serializer, Deserializer, de::Error as __Error};
@@ -12,7 +13,7 @@ use serde::{Serialize, Deserialize, Serializer, Deserializer, de::Error as __Err
 /// InnerAscii is string data container which can contain either a
 /// owned `SoftAsciiString` or a `SoftAsciiStr` reference into a shared
 /// string buffer.
-#[derive(Debug, Clone, Hash, Eq)]
+#[derive(Debug, Clone, Eq)]
 pub enum InnerAscii {
     Owned(SoftAsciiString),
     //by using String+SoftAsciiStr we can eliminate unessesary copies
@@ -51,7 +52,7 @@ impl InnerAscii {
 /// InnerUtf8 is string data container which can contain either a
 /// owned `String` or a `str` reference into a shared
 /// string buffer.
-#[derive(Debug, Clone, Hash, Eq)]
+#[derive(Debug, Clone, Eq)]
 pub enum InnerUtf8 {
     Owned(String),
     //by using String+SoftAsciiStr we can eliminate unessesary copies
@@ -134,6 +135,14 @@ macro_rules! inner_impl {
             }
         }
 
+        // must be consistent with `PartialEq`, so owned and shared data hash the same
+        impl Hash for $name {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                let me: &$borrowed_form = &*self;
+                me.hash(state)
+            }
+        }
+
         impl AsRef<str> for $name {
             fn as_ref(&self) -> &str {
                 self.as_str()
@@ -235,6 +244,25 @@ mod test {
         assert_ne!( a, b );
     }
 
+    #[test]
+    fn owned_and_shared_hash_the_same() {
+        use std::collections::hash_map::DefaultHasher;
+
+        fn hash_of<T: Hash>(val: &T) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            val.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let owned = InnerUtf8::Owned(String::from("same"));
+        let shared = owned.clone().into_shared();
+        assert_eq!(hash_of(&owned), hash_of(&shared));
+
+        let owned = InnerAscii::Owned(SoftAsciiString::from_unchecked("same"));
+        let shared = owned.clone().into_shared();
+        assert_eq!(hash_of(&owned), hash_of(&shared));
+    }
+
     #[test]
     fn has_as_str() {
         use std::borrow::ToOwned;
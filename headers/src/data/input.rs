@@ -1,7 +1,7 @@
 use std::result::{ Result as StdResult };
 use std::fmt::{self, Display};
 
-use soft_ascii_string::SoftAsciiString;
+use soft_ascii::SoftAsciiString;
 
 use ::HeaderTryFrom;
 use ::error::ComponentCreationError;
@@ -1,6 +1,6 @@
 use std::ops::Deref;
 
-use soft_ascii_string::{ SoftAsciiStr, SoftAsciiString};
+use soft_ascii::{ SoftAsciiStr, SoftAsciiString};
 
 use super::input::Input;
 use super::inner_item::{ InnerAscii, InnerUtf8 };
@@ -43,6 +43,17 @@ impl SimpleItem {
         SimpleItem::Utf8( InnerUtf8::Owned( s ) )
     }
 
+    /// converts this item into one which uses underlying shared data
+    ///
+    /// see `InnerAscii::into_shared`/`InnerUtf8::into_shared`
+    pub fn into_shared( self ) -> Self {
+        use self::SimpleItem::*;
+        match self {
+            Ascii( value ) => Ascii( value.into_shared() ),
+            Utf8( value ) => Utf8( value.into_shared() )
+        }
+    }
+
 
 }
 
@@ -1,32 +1,142 @@
 //! module contains the (new) errors emitted by this crate
 use std::fmt::{self, Display};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use failure::{Fail, Context, Error as FError, Backtrace};
+use chrono::{DateTime, Utc};
+use internals::error::EncodingError;
 
 use ::name::HeaderName;
 
+static CAPTURE_BACKTRACES: AtomicBool = AtomicBool::new(!cfg!(feature="cheap-errors"));
+
+/// Sets if errors created by this crate capture a backtrace.
+///
+/// This affects `HeaderTypeError` and `ComponentCreationError`,
+/// if disabled their `Fail::backtrace()` method will return `None`.
+///
+/// Capturing backtraces is enabled by default, except if the
+/// `cheap-errors` feature is enabled. Disabling it can be useful
+/// if a large number of errors is created and then discarded,
+/// e.g. when filtering out invalid addresses.
+pub fn set_backtrace_capture(enable: bool) {
+    CAPTURE_BACKTRACES.store(enable, Ordering::Relaxed);
+}
+
+/// Returns true if errors created by this crate capture a backtrace.
+///
+/// See `set_backtrace_capture`.
+pub fn backtrace_capture() -> bool {
+    CAPTURE_BACKTRACES.load(Ordering::Relaxed)
+}
+
+fn capture_backtrace_if(capture: bool) -> Option<Backtrace> {
+    if capture {
+        Some(Backtrace::new())
+    } else {
+        None
+    }
+}
+
 /// This error can occur if different implementations for the
 /// same header (e.g. `Subject`) where used in the same `HeaderMap`.
-#[derive(Debug, Fail)]
-#[fail(display = "cast error caused by mixing different header implementations for {}", header_name)]
+///
+/// It is also returned by typed accessors for a compressed header (see
+/// `HeaderMap::compress_large_values`) whose value can not be restored,
+/// in which case `decompress_error` returns the cause.
+#[derive(Debug)]
 pub struct HeaderTypeError {
     header_name: HeaderName,
-    backtrace: Backtrace
+    #[cfg(feature="compress-values")]
+    decompress_error: Option<DecompressError>,
+    backtrace: Option<Backtrace>
 }
 
 impl HeaderTypeError {
     pub fn new(name: HeaderName) -> Self {
+        HeaderTypeError::new_capturing(name, backtrace_capture())
+    }
+
+    fn new_capturing(name: HeaderName, capture: bool) -> Self {
         HeaderTypeError {
             header_name: name,
-            backtrace: Backtrace::new()
+            #[cfg(feature="compress-values")]
+            decompress_error: None,
+            backtrace: capture_backtrace_if(capture)
         }
     }
 
     pub fn new_with_backtrace(name: HeaderName, backtrace: Backtrace) -> Self {
         HeaderTypeError {
             header_name: name,
-            backtrace
+            #[cfg(feature="compress-values")]
+            decompress_error: None,
+            backtrace: Some(backtrace)
+        }
+    }
+
+    /// Creates the error for a compressed header whose value can not be restored.
+    #[cfg(feature="compress-values")]
+    pub fn from_decompress_error(err: DecompressError) -> Self {
+        let mut type_error = HeaderTypeError::new(err.header_name());
+        type_error.decompress_error = Some(err);
+        type_error
+    }
+
+    /// The name of the header which could not be accessed.
+    pub fn header_name(&self) -> HeaderName {
+        self.header_name
+    }
+
+    /// Returns the cause if the header is a compressed header whose value can not be restored.
+    #[cfg(feature="compress-values")]
+    pub fn decompress_error(&self) -> Option<&DecompressError> {
+        self.decompress_error.as_ref()
+    }
+}
+
+impl Fail for HeaderTypeError {
+    #[cfg(feature="compress-values")]
+    fn cause(&self) -> Option<&Fail> {
+        self.decompress_error.as_ref().map(|err| err as &Fail)
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.backtrace.as_ref()
+    }
+}
+
+impl Display for HeaderTypeError {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        #[cfg(feature="compress-values")]
+        {
+            if let Some(ref err) = self.decompress_error {
+                return write!(fter, "compressed value can not be restored: {}", err);
+            }
         }
+        write!(fter, "cast error caused by mixing different header implementations for {}",
+            self.header_name)
+    }
+}
+
+/// A header expected to be in a `HeaderMap` is missing or has a different type.
+///
+/// Returned by `HeaderMap::expect_single`.
+#[derive(Debug, Fail)]
+pub enum HeaderExpectationError {
+    /// The map contains no header with the name.
+    #[fail(display = "expected {} header is missing", name)]
+    Missing { name: HeaderName },
+
+    /// The map contains a header with the name but of a different type (see `HeaderTypeError`).
+    #[fail(display = "{}", _0)]
+    Type(#[cause] HeaderTypeError)
+}
+
+impl From<HeaderTypeError> for HeaderExpectationError {
+    fn from(err: HeaderTypeError) -> Self {
+        HeaderExpectationError::Type(err)
     }
 }
 
@@ -55,6 +165,41 @@ impl From<Context<BuildInValidationError>> for HeaderValidationError {
     }
 }
 
+/// Multiple validators failed, returned (as `HeaderValidationError::Custom`) by `HeaderMap::validate_with`.
+#[derive(Debug, Fail)]
+pub struct ValidationErrors {
+    errors: Vec<HeaderValidationError>
+}
+
+impl ValidationErrors {
+
+    pub fn new(errors: Vec<HeaderValidationError>) -> Self {
+        ValidationErrors { errors }
+    }
+
+    /// The errors in the order the failing validators were run.
+    pub fn errors(&self) -> &[HeaderValidationError] {
+        &self.errors
+    }
+
+    pub fn into_errors(self) -> Vec<HeaderValidationError> {
+        self.errors
+    }
+}
+
+impl Display for ValidationErrors {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "{} validation errors: ", self.errors.len())?;
+        for (idx, err) in self.errors.iter().enumerate() {
+            if idx > 0 {
+                fter.write_str("; ")?;
+            }
+            write!(fter, "{}", err)?;
+        }
+        Ok(())
+    }
+}
+
 /// The build-in error variants (error kinds) which can be returned
 /// when running a header map validator.
 #[derive(Copy, Clone, Debug, Fail, PartialEq, Eq, Hash)]
@@ -78,7 +223,759 @@ pub enum BuildInValidationError {
     ResentDateFieldMissing,
 
     #[fail(display = "Resent-From field in resent block without a Resent-Sender field")]
-    MultiMailboxResentFromWithoutResentSender
+    MultiMailboxResentFromWithoutResentSender,
+
+    #[fail(display = "no Date field was set")]
+    DateFieldMissing,
+
+    #[fail(display = "{} field is too far in the future: {}", header_name, date)]
+    DateTooFarInFuture { header_name: &'static str, date: DateTime<Utc> },
+
+    #[fail(display = "{} field is too far in the past: {}", header_name, date)]
+    DateTooFarInPast { header_name: &'static str, date: DateTime<Utc> },
+
+    #[fail(display = "Resent-Date field is older than the Resent-Date of the block below it: {}", date)]
+    ResentDateOutOfOrder { date: DateTime<Utc> },
+
+    /// A header checked by a build-in validator does not use the expected component.
+    ///
+    /// E.g. a `From` header which does not use a `MailboxList`. This is not
+    /// returned if the map uses lenient validator options (see `map::ValidatorOptions`).
+    #[fail(display = "{} header field does not use the expected component type", header_name)]
+    UnexpectedComponentType { header_name: &'static str }
+}
+
+/// The limit of a `HeaderMap` which would have been exceeded (see `map::MapLimits`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LimitKind {
+    /// the maximal number of headers
+    HeaderCount,
+    /// the maximal number of bytes of all (encoded) headers
+    Bytes,
+    /// the maximal number of recipients, see `HeaderMap::guard_recipient_limit`
+    Recipients
+}
+
+impl Display for LimitKind {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LimitKind::HeaderCount => fter.write_str("header count"),
+            LimitKind::Bytes => fter.write_str("header bytes"),
+            LimitKind::Recipients => fter.write_str("recipient count")
+        }
+    }
+}
+
+/// Inserting into a `HeaderMap` with limits was rejected as it would exceed a limit.
+///
+/// The header(s) causing the violation were not inserted.
+#[derive(Copy, Clone, Debug, Fail, PartialEq, Eq, Hash)]
+#[fail(display = "inserting header would exceed the maximal {} of {} (accepted {} header(s) before)",
+    kind, limit, accepted)]
+pub struct LimitViolation {
+    kind: LimitKind,
+    limit: usize,
+    accepted: usize
+}
+
+impl LimitViolation {
+
+    pub fn new(kind: LimitKind, limit: usize, accepted: usize) -> Self {
+        LimitViolation { kind, limit, accepted }
+    }
+
+    /// Which limit would have been exceeded.
+    pub fn kind(&self) -> LimitKind {
+        self.kind
+    }
+
+    /// The value of the limit which would have been exceeded.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// The number of headers inserted before the violation.
+    ///
+    /// This is only non zero for a (non atomic) `HeaderMap::try_insert_all`.
+    pub fn accepted(&self) -> usize {
+        self.accepted
+    }
+}
+
+/// `HeaderMap::guard_recipient_limit` refused a map.
+#[derive(Debug, Fail)]
+pub enum RecipientLimitError {
+    /// The map has more recipients than allowed.
+    #[fail(display = "map has {} recipients but at most {} are allowed", count, limit)]
+    Exceeded { count: usize, limit: usize },
+
+    /// A address header has a different type, so the recipients can not be counted (see `HeaderTypeError`).
+    #[fail(display = "{}", _0)]
+    Type(#[cause] HeaderTypeError)
+}
+
+impl RecipientLimitError {
+
+    /// Returns the exceeded limit as `LimitViolation` of kind `LimitKind::Recipients`.
+    ///
+    /// Returns `None` if the recipients could not be counted.
+    pub fn limit_violation(&self) -> Option<LimitViolation> {
+        match *self {
+            RecipientLimitError::Exceeded { limit, .. } => {
+                Some(LimitViolation::new(LimitKind::Recipients, limit, 0))
+            },
+            RecipientLimitError::Type(_) => None
+        }
+    }
+}
+
+impl From<HeaderTypeError> for RecipientLimitError {
+    fn from(err: HeaderTypeError) -> Self {
+        RecipientLimitError::Type(err)
+    }
+}
+
+/// A max-one header is too long to be encoded, see `MAX_ONE_HEADER_LEN`.
+///
+/// Encoding such a header fails with a `EncodingError` caused by this error
+/// (use `HeaderTooLong::find_in` to get it). Splitting the header into multiple
+/// headers of the same name is not a valid fix, as the header must appear at
+/// most once. Instead the header body has to be shortened, e.g. by dropping
+/// message ids from the middle of `References` or truncating the `Subject`.
+#[derive(Copy, Clone, Debug, Fail, PartialEq, Eq, Hash)]
+#[fail(display = "{} header is {} bytes long when encoded but may at most be {} bytes long \
+    (it must not be split into multiple headers, shorten its body instead, e.g. by \
+    dropping message ids from References or truncating the Subject)",
+    name, encoded_len, limit)]
+pub struct HeaderTooLong {
+    name: HeaderName,
+    encoded_len: usize,
+    limit: usize
+}
+
+impl HeaderTooLong {
+
+    pub fn new(name: HeaderName, encoded_len: usize, limit: usize) -> Self {
+        HeaderTooLong { name, encoded_len, limit }
+    }
+
+    /// The name of the header which is too long.
+    pub fn name(&self) -> HeaderName {
+        self.name
+    }
+
+    /// The length of the encoded header line (including name and final CRLF).
+    pub fn encoded_len(&self) -> usize {
+        self.encoded_len
+    }
+
+    /// The exceeded limit.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Returns the `HeaderTooLong` error which caused given encoding error (if any).
+    pub fn find_in(err: &EncodingError) -> Option<&HeaderTooLong> {
+        let mut current: Option<&Fail> = Some(err);
+        while let Some(fail) = current {
+            if let Some(too_long) = fail.downcast_ref::<HeaderTooLong>() {
+                return Some(too_long);
+            }
+            current = fail.cause();
+        }
+        None
+    }
+}
+
+/// The encoded output of a header contains a line break which is not a legal fold.
+///
+/// Returned (as the cause of a `EncodingError`, use `HeaderInjectionDetected::find_in`
+/// to get it) by encoding methods using a `InjectionGuard` if a component wrote a CR
+/// or LF which is not part of a CRLF followed by whitespace (or the final CRLF of the
+/// header). Such a line break would allow to inject additional headers into the mail.
+#[derive(Copy, Clone, Debug, Fail, PartialEq, Eq, Hash)]
+#[fail(display = "{} header contains a line break which is not a legal fold (at byte {})",
+    name, position)]
+pub struct HeaderInjectionDetected {
+    name: HeaderName,
+    position: usize
+}
+
+impl HeaderInjectionDetected {
+
+    pub fn new(name: HeaderName, position: usize) -> Self {
+        HeaderInjectionDetected { name, position }
+    }
+
+    /// The name of the header containing the line break.
+    pub fn name(&self) -> HeaderName {
+        self.name
+    }
+
+    /// The position of the line break in the encoded header line (starting with the name).
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the `HeaderInjectionDetected` error which caused given encoding error (if any).
+    pub fn find_in(err: &EncodingError) -> Option<&HeaderInjectionDetected> {
+        let mut current: Option<&Fail> = Some(err);
+        while let Some(fail) = current {
+            if let Some(detected) = fail.downcast_ref::<HeaderInjectionDetected>() {
+                return Some(detected);
+            }
+            current = fail.cause();
+        }
+        None
+    }
+}
+
+/// A header can not be encoded in any mail type, see `HeaderMap::required_mail_type`.
+#[derive(Copy, Clone, Debug, Fail, PartialEq, Eq, Hash)]
+#[fail(display = "{} header can not be encoded in any mail type: {}", name, reason)]
+pub struct RequirementConflict {
+    name: HeaderName,
+    reason: &'static str
+}
+
+impl RequirementConflict {
+
+    pub fn new(name: HeaderName, reason: &'static str) -> Self {
+        RequirementConflict { name, reason }
+    }
+
+    /// The name of the header which can not be encoded.
+    pub fn name(&self) -> HeaderName {
+        self.name
+    }
+
+    /// Why the header can not be encoded.
+    pub fn reason(&self) -> &'static str {
+        self.reason
+    }
+}
+
+/// A feature required by the caller is not available, see `capabilities::require`.
+#[derive(Clone, Debug, Fail, PartialEq, Eq, Hash)]
+pub enum MissingCapability {
+
+    /// The crate was built without the feature.
+    #[fail(display = "mail-headers was built without the \"{}\" feature", feature)]
+    Disabled { feature: &'static str },
+
+    /// There is no feature with this name.
+    #[fail(display = "\"{}\" is not a feature of mail-headers", feature)]
+    Unknown { feature: String }
+}
+
+impl MissingCapability {
+
+    /// The name of the missing feature.
+    pub fn feature(&self) -> &str {
+        match *self {
+            MissingCapability::Disabled { feature } => feature,
+            MissingCapability::Unknown { ref feature } => feature
+        }
+    }
+}
+
+/// What is wrong with a display name, see `DisplayNameViolation`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DisplayNameViolationKind {
+    /// The display name is not allowed for the domain of the mailbox.
+    NotAllowed,
+    /// The mailbox has no display name but the policy requires one.
+    Missing,
+    /// The policy has no entry for the domain of the mailbox.
+    UnlistedDomain,
+    /// The display name of the `Sender` mailbox is not allowed.
+    SenderNotAllowed
+}
+
+impl Display for DisplayNameViolationKind {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DisplayNameViolationKind::NotAllowed =>
+                fter.write_str("display name is not allowed for the domain"),
+            DisplayNameViolationKind::Missing =>
+                fter.write_str("display name is missing"),
+            DisplayNameViolationKind::UnlistedDomain =>
+                fter.write_str("domain is not covered by the policy"),
+            DisplayNameViolationKind::SenderNotAllowed =>
+                fter.write_str("display name is not allowed for the sender")
+        }
+    }
+}
+
+/// A mailbox violates a `DisplayNamePolicy`.
+///
+/// Returned as `HeaderValidationError::Custom` by `DisplayNamePolicy::validate`.
+#[derive(Clone, Debug, Fail, PartialEq, Eq, Hash)]
+#[fail(display = "{} mailbox {} violates the display name policy: {}", header_name, mailbox, kind)]
+pub struct DisplayNameViolation {
+    header_name: &'static str,
+    mailbox: String,
+    display_name: Option<String>,
+    kind: DisplayNameViolationKind
+}
+
+impl DisplayNameViolation {
+
+    pub fn new(
+        header_name: &'static str,
+        mailbox: String,
+        display_name: Option<String>,
+        kind: DisplayNameViolationKind
+    ) -> Self {
+        DisplayNameViolation { header_name, mailbox, display_name, kind }
+    }
+
+    /// The name of the header containing the mailbox (`From` or `Sender`).
+    pub fn header_name(&self) -> &'static str {
+        self.header_name
+    }
+
+    /// The address of the mailbox (`local-part@domain`).
+    pub fn mailbox(&self) -> &str {
+        &self.mailbox
+    }
+
+    /// The decoded display name, `None` if the mailbox has none (or it wasn't checked).
+    pub fn display_name(&self) -> Option<&str> {
+        self.display_name.as_ref().map(|name| &**name)
+    }
+
+    /// What is wrong with the display name.
+    pub fn kind(&self) -> DisplayNameViolationKind {
+        self.kind
+    }
+}
+
+/// The rule of `AddressProfile::ProviderStrict` an address violates.
+///
+/// It is the cause of the `ComponentCreationError` returned by e.g.
+/// `Email::new_with_profile`, see `ComponentCreationError::address_violation`.
+#[derive(Copy, Clone, Debug, Fail, PartialEq, Eq, Hash)]
+pub enum AddressViolation {
+    /// The local part is not a dot-atom, i.e. it would have to be quoted (e.g. `"john doe"`).
+    #[fail(display = "local part would have to be quoted")]
+    QuotedLocalPart,
+    /// The domain is a domain literal (e.g. `[127.0.0.1]`).
+    #[fail(display = "domain literals are not accepted")]
+    DomainLiteral,
+    /// The local part is longer than 64 octets.
+    #[fail(display = "local part is longer than 64 octets")]
+    LocalPartTooLong,
+    /// The address contains two consecutive dots.
+    #[fail(display = "address contains consecutive dots")]
+    ConsecutiveDots,
+    /// The domain has only one label (e.g. `localhost`).
+    #[fail(display = "domain has no top level domain")]
+    SingleLabelDomain
+}
+
+/// A domain literal (e.g. `[IPv6:::1]`) is not a valid RFC 5321 address literal.
+///
+/// It is the cause of the `ComponentCreationError` returned when creating
+/// a `Domain` (or `Email`) with such a literal, see
+/// `ComponentCreationError::invalid_domain_name`.
+#[derive(Clone, Debug, Fail, PartialEq, Eq, Hash)]
+#[fail(display = "invalid domain name: {}", name)]
+pub struct InvalidDomainName {
+    name: String
+}
+
+impl InvalidDomainName {
+
+    /// Creates a new error for the given (invalid) domain.
+    pub fn new<I>(name: I) -> Self
+        where I: Into<String>
+    {
+        InvalidDomainName { name: name.into() }
+    }
+
+    /// The invalid domain (including the brackets of the literal).
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// A domain violates the length limits or the hyphen rule of its labels.
+///
+/// The limits apply to the ACE form of the domain, i.e. internationalized
+/// labels are checked after punycoding them. It is the cause of the
+/// `ComponentCreationError` returned when creating such a `Domain` (or
+/// `Email`), see `ComponentCreationError::domain_violation`.
+#[derive(Clone, Debug, Fail, PartialEq, Eq, Hash)]
+pub enum DomainViolation {
+    /// A label is longer than 63 octets.
+    #[fail(display = "domain label {} is {} octets long but may at most be 63 octets long", label, len)]
+    LabelTooLong { label: String, len: usize },
+    /// The domain is longer than 253 octets (255 octets in the DNS wire format).
+    #[fail(display = "domain is {} octets long but may at most be 253 octets long", len)]
+    DomainTooLong { len: usize },
+    /// A label starts or ends with a `-`.
+    #[fail(display = "domain label {} starts or ends with '-'", label)]
+    HyphenAtLabelEdge { label: String }
+}
+
+/// A segment of a `FeedbackId` is empty or contains chars other than
+/// us-ascii alphanumerics, `-`, `_` and `.` (e.g. a `:`).
+///
+/// It is the cause of the `ComponentCreationError` returned when creating
+/// or parsing a `FeedbackId`, see `ComponentCreationError::feedback_id_segment`.
+#[derive(Copy, Clone, Debug, Fail, PartialEq, Eq, Hash)]
+#[fail(display = "invalid {} segment (index {}) of feedback id", name, index)]
+pub struct InvalidFeedbackIdSegment {
+    index: usize,
+    name: &'static str
+}
+
+impl InvalidFeedbackIdSegment {
+
+    /// Creates a new error for the segment at given index (0 to 3).
+    pub fn new(index: usize) -> Self {
+        let name = match index {
+            0 => "campaign",
+            1 => "customer",
+            2 => "mail type",
+            _ => "sender id"
+        };
+        InvalidFeedbackIdSegment { index, name }
+    }
+
+    /// The index of the segment (0 = campaign, 1 = customer, 2 = mail type, 3 = sender id).
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The name of the segment, e.g. `"mail type"`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+/// A `HeaderTransform` failed, see `transform::TransformPipeline`.
+#[derive(Debug, Fail)]
+#[fail(display = "{} transform failed: {}", transform, error)]
+pub struct TransformError {
+    transform: &'static str,
+    error: FError
+}
+
+impl TransformError {
+
+    pub fn new<E>(transform: &'static str, error: E) -> Self
+        where E: Into<FError>
+    {
+        TransformError { transform, error: error.into() }
+    }
+
+    /// The name of the transform which failed (see `HeaderTransform::name`).
+    pub fn transform(&self) -> &'static str {
+        self.transform
+    }
+
+    /// The error which made the transform fail.
+    pub fn error(&self) -> &FError {
+        &self.error
+    }
+}
+
+/// Converting a legacy `(header name, value)` pair failed, see `HeaderMap::from_legacy_pairs`.
+#[derive(Debug, Fail)]
+pub enum MigrationError {
+
+    /// The header name is not the name of a header defined in `headers`.
+    #[fail(display = "{} header is not known to the legacy migration", header_name)]
+    UnknownHeader { header_name: HeaderName },
+
+    /// The shape of the legacy value can not be used for the header.
+    #[fail(display = "{} header does not accept a legacy {} value (expected {})",
+        header_name, shape, expected)]
+    ShapeMismatch {
+        header_name: HeaderName,
+        shape: &'static str,
+        expected: &'static str
+    },
+
+    /// The shape fits the header, but the component could not be created from it.
+    #[fail(display = "{} header: {}", header_name, error)]
+    Component {
+        header_name: HeaderName,
+        #[cause]
+        error: ComponentCreationError
+    },
+
+    /// Inserting the header would exceed the limits of the map.
+    #[fail(display = "{} header: {}", header_name, violation)]
+    Limit {
+        header_name: HeaderName,
+        #[cause]
+        violation: LimitViolation
+    }
+}
+
+impl MigrationError {
+
+    /// The name of the header which could not be converted.
+    pub fn header_name(&self) -> HeaderName {
+        match *self {
+            MigrationError::UnknownHeader { header_name } => header_name,
+            MigrationError::ShapeMismatch { header_name, .. } => header_name,
+            MigrationError::Component { header_name, .. } => header_name,
+            MigrationError::Limit { header_name, .. } => header_name
+        }
+    }
+}
+
+/// Converting a `(name, value)` string pair failed, see `HeaderMap::from_string_pairs`.
+#[derive(Debug, Fail)]
+pub enum StringPairError {
+
+    /// No parser is registered for the header name.
+    #[fail(display = "{} header is not known to the registry", name)]
+    UnknownHeader { name: String },
+
+    /// The value could not be parsed into the component of the header.
+    #[fail(display = "{} header: {}", header_name, error)]
+    Component {
+        header_name: HeaderName,
+        #[cause]
+        error: ComponentCreationError
+    },
+
+    /// Inserting the header would exceed the limits of the map.
+    #[fail(display = "{} header: {}", header_name, violation)]
+    Limit {
+        header_name: HeaderName,
+        #[cause]
+        violation: LimitViolation
+    }
+}
+
+impl StringPairError {
+
+    /// The name of the header which could not be created.
+    pub fn name(&self) -> &str {
+        match *self {
+            StringPairError::UnknownHeader { ref name } => name,
+            StringPairError::Component { header_name, .. } => header_name.as_str(),
+            StringPairError::Limit { header_name, .. } => header_name.as_str()
+        }
+    }
+}
+
+/// Parsing a raw header block failed, see `HeaderMap::from_block_bytes`.
+///
+/// Each error contains the byte offset (in the parsed input) of the line
+/// or byte which could not be parsed.
+#[derive(Debug, Fail)]
+pub enum BlockParseError {
+
+    /// A CR not followed by a LF or a LF not preceded by a CR.
+    #[fail(display = "invalid line break at byte {}", offset)]
+    InvalidLineBreak { offset: usize },
+
+    /// A line which is neither a `name: value` line nor a continuation line.
+    #[fail(display = "malformed header line at byte {}", offset)]
+    MalformedLine { offset: usize },
+
+    /// The value of the header starting at the offset is not valid utf-8.
+    #[fail(display = "header at byte {} is not valid utf-8", offset)]
+    InvalidUtf8 { offset: usize },
+
+    /// There are bytes after the blank line ending the block (see `TrailingBytes::Reject`).
+    #[fail(display = "unexpected bytes after the header block at byte {}", offset)]
+    TrailingBytes { offset: usize },
+
+    /// The header starting at the offset could not be created.
+    #[fail(display = "header at byte {}: {}", offset, error)]
+    Header {
+        offset: usize,
+        #[cause]
+        error: StringPairError
+    },
+
+    /// Adding the header starting at the offset would exceed the limits (see `BlockParseOptions::limits`).
+    #[fail(display = "header at byte {}: {}", offset, violation)]
+    Limit {
+        offset: usize,
+        #[cause]
+        violation: LimitViolation
+    }
+}
+
+impl BlockParseError {
+
+    /// The byte offset of the failing line (or byte) in the parsed input.
+    pub fn offset(&self) -> usize {
+        use self::BlockParseError::*;
+        match *self {
+            InvalidLineBreak { offset } | MalformedLine { offset } | InvalidUtf8 { offset }
+                | TrailingBytes { offset } | Header { offset, .. } | Limit { offset, .. } => offset
+        }
+    }
+}
+
+/// Decompressing a header compressed by `HeaderMap::compress_large_values` failed.
+///
+/// See `CompressedRaw::decompressed` and `HeaderMap::decompress_values`.
+#[cfg(feature="compress-values")]
+#[derive(Debug, Fail)]
+pub enum DecompressError {
+
+    /// The compressed bytes can not be inflated into a utf-8 value.
+    #[fail(display = "{} header: compressed value is corrupted", header_name)]
+    Corrupted { header_name: HeaderName },
+
+    /// The decompressed value can not be parsed into the component of the header again.
+    #[fail(display = "{} header: decompressed value can not be parsed as {}: {}",
+        header_name, component, error)]
+    Reparse {
+        header_name: HeaderName,
+        component: &'static str,
+        #[cause]
+        error: ComponentCreationError
+    }
+}
+
+#[cfg(feature="compress-values")]
+impl DecompressError {
+
+    /// The name of the header which could not be decompressed.
+    pub fn header_name(&self) -> HeaderName {
+        match *self {
+            DecompressError::Corrupted { header_name }
+                | DecompressError::Reparse { header_name, .. } => header_name
+        }
+    }
+}
+
+/// Converting a JSON value into a header failed, see `from_json::header_from_json`.
+///
+/// Each error contains the JSON path of the value which could not be
+/// converted, e.g. `$.To[1].email`.
+#[cfg(feature="serde-impl")]
+#[derive(Debug, Fail)]
+pub enum JsonHeaderError {
+
+    /// No converter is registered for the header name.
+    #[fail(display = "{}: {} header is not known", path, name)]
+    UnknownHeader { name: String, path: String },
+
+    /// The JSON value does not have a shape accepted by the header.
+    #[fail(display = "{}: {} header does not accept {} (expected {})",
+        path, header_name, shape, expected)]
+    ShapeMismatch {
+        header_name: HeaderName,
+        path: String,
+        shape: &'static str,
+        expected: &'static str
+    },
+
+    /// The shape fits the header, but the component could not be created from it.
+    #[fail(display = "{}: {} header: {}", path, header_name, error)]
+    Component {
+        header_name: HeaderName,
+        path: String,
+        #[cause]
+        error: ComponentCreationError
+    },
+
+    /// Inserting the header would exceed the limits of the map.
+    #[fail(display = "{}: {} header: {}", path, header_name, violation)]
+    Limit {
+        header_name: HeaderName,
+        path: String,
+        #[cause]
+        violation: LimitViolation
+    }
+}
+
+#[cfg(feature="serde-impl")]
+impl JsonHeaderError {
+
+    /// The name of the header which could not be created.
+    pub fn name(&self) -> &str {
+        match *self {
+            JsonHeaderError::UnknownHeader { ref name, .. } => name,
+            JsonHeaderError::ShapeMismatch { header_name, .. } => header_name.as_str(),
+            JsonHeaderError::Component { header_name, .. } => header_name.as_str(),
+            JsonHeaderError::Limit { header_name, .. } => header_name.as_str()
+        }
+    }
+
+    /// The JSON path of the value which could not be converted.
+    pub fn path(&self) -> &str {
+        match *self {
+            JsonHeaderError::UnknownHeader { ref path, .. } => path,
+            JsonHeaderError::ShapeMismatch { ref path, .. } => path,
+            JsonHeaderError::Component { ref path, .. } => path,
+            JsonHeaderError::Limit { ref path, .. } => path
+        }
+    }
+}
+
+/// Why a entry of a `header_group::HeaderGroupSpec` is invalid, see `SpecError`.
+#[cfg(feature="serde-impl")]
+#[derive(Debug, Fail)]
+pub enum SpecErrorKind {
+
+    /// The registry doesn't know a header with this name (for the kind of the entry).
+    #[fail(display = "{} header is not known as {} header", name, kind)]
+    UnknownHeader { name: String, kind: &'static str },
+
+    /// The header is known, but only for the other kind, e.g. a raw entry for `Reply-To`.
+    #[fail(display = "{} header can not be used as {} header (use kind {})", name, kind, expected)]
+    KindMismatch { name: String, kind: &'static str, expected: &'static str },
+
+    /// The name of a raw entry is not a valid header field name.
+    #[fail(display = "{:?} is not a valid header name", name)]
+    InvalidName { name: String },
+
+    /// A "max one" header is specified by more than one entry.
+    #[fail(display = "{} header can only be specified once", name)]
+    Duplicate { name: String },
+
+    /// The value of the entry can not be converted into the header.
+    #[fail(display = "{}", _0)]
+    Value(#[cause] JsonHeaderError)
+}
+
+/// A invalid entry of a `header_group::HeaderGroupSpec`.
+///
+/// It contains the index of the entry in the spec and the field of the
+/// entry (`"name"`, `"kind"` or `"value"`) which is invalid.
+#[cfg(feature="serde-impl")]
+#[derive(Debug, Fail)]
+#[fail(display = "entry {} ({}): {}", index, field, kind)]
+pub struct SpecError {
+    index: usize,
+    field: &'static str,
+    kind: SpecErrorKind
+}
+
+#[cfg(feature="serde-impl")]
+impl SpecError {
+
+    pub fn new(index: usize, field: &'static str, kind: SpecErrorKind) -> Self {
+        SpecError { index, field, kind }
+    }
+
+    /// The index of the invalid entry.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The invalid field of the entry.
+    pub fn field(&self) -> &'static str {
+        self.field
+    }
+
+    pub fn kind(&self) -> &SpecErrorKind {
+        &self.kind
+    }
 }
 
 macro_rules! header_validation_bail {
@@ -102,6 +999,11 @@ pub enum ChainTail {
 
 impl ChainTail {
 
+    /// Returns a backtrace tail if `capture` is true, see `set_backtrace_capture`.
+    fn new_backtrace(capture: bool) -> Option<Self> {
+        capture_backtrace_if(capture).map(ChainTail::Backtrace)
+    }
+
     fn backtrace(&self) -> &Backtrace {
         match *self {
             ChainTail::Backtrace(ref trace) => trace,
@@ -125,8 +1027,11 @@ impl ChainTail {
 #[derive(Debug)]
 pub struct ComponentCreationError {
     component: &'static str,
-    backtrace: ChainTail,
-    str_context: Option<String>
+    /// `None` if no backtrace was captured, see `set_backtrace_capture`
+    backtrace: Option<ChainTail>,
+    str_context: Option<String>,
+    span: Option<Range<usize>>,
+    expected: Option<&'static str>
 }
 
 impl ComponentCreationError {
@@ -139,8 +1044,10 @@ impl ComponentCreationError {
     {
         ComponentCreationError {
             component,
-            backtrace: ChainTail::Error(parent.into()),
-            str_context: None
+            backtrace: Some(ChainTail::Error(parent.into())),
+            str_context: None,
+            span: None,
+            expected: None
         }
     }
 
@@ -148,11 +1055,7 @@ impl ComponentCreationError {
     ///
     /// The name is normally the type name, for example `Email`, `Mailbox` etc.
     pub fn new(component: &'static str) -> Self {
-        ComponentCreationError {
-            component,
-            backtrace: ChainTail::Backtrace(Backtrace::new()),
-            str_context: None
-        }
+        ComponentCreationError::new_capturing(component, None, backtrace_capture())
     }
 
     /// creates a new `ComponentCreationError` based on the components name with a str_context
@@ -164,10 +1067,16 @@ impl ComponentCreationError {
     pub fn new_with_str<I>(component: &'static str, str_context: I) -> Self
         where I: Into<String>
     {
+        ComponentCreationError::new_capturing(component, Some(str_context.into()), backtrace_capture())
+    }
+
+    fn new_capturing(component: &'static str, str_context: Option<String>, capture: bool) -> Self {
         ComponentCreationError {
             component,
-            backtrace: ChainTail::Backtrace(Backtrace::new()),
-            str_context: Some(str_context.into())
+            backtrace: ChainTail::new_backtrace(capture),
+            str_context,
+            span: None,
+            expected: None
         }
     }
 
@@ -187,19 +1096,132 @@ impl ComponentCreationError {
         self.set_str_context(ctx);
         self
     }
+
+    /// The byte offsets of the invalid part in the input (if known).
+    ///
+    /// For an unexpected end of input this is an empty range at the end of the input.
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.span.clone()
+    }
+
+    /// A short description of what was expected at `span` (if known), e.g. `"'@'"`.
+    pub fn expected(&self) -> Option<&'static str> {
+        self.expected
+    }
+
+    pub fn set_span(&mut self, span: Range<usize>, expected: Option<&'static str>) {
+        self.span = Some(span);
+        self.expected = expected;
+    }
+
+    pub fn with_span(mut self, span: Range<usize>, expected: Option<&'static str>) -> Self {
+        self.set_span(span, expected);
+        self
+    }
+
+    /// The rule of a `AddressProfile` the address violates, if this is the reason of the error.
+    pub fn address_violation(&self) -> Option<AddressViolation> {
+        self.backtrace.as_ref().and_then(ChainTail::as_fail)
+            .and_then(|cause| cause.downcast_ref::<AddressViolation>())
+            .cloned()
+    }
+
+    /// The violated domain length limit or label rule, if this is the reason of the error.
+    pub fn domain_violation(&self) -> Option<&DomainViolation> {
+        self.backtrace.as_ref().and_then(ChainTail::as_fail)
+            .and_then(|cause| cause.downcast_ref::<DomainViolation>())
+    }
+
+    /// The invalid domain literal, if this is the reason of the error.
+    pub fn invalid_domain_name(&self) -> Option<&InvalidDomainName> {
+        self.backtrace.as_ref().and_then(ChainTail::as_fail)
+            .and_then(|cause| cause.downcast_ref::<InvalidDomainName>())
+    }
+
+    /// The invalid segment of a `FeedbackId`, if this is the reason of the error.
+    pub fn feedback_id_segment(&self) -> Option<InvalidFeedbackIdSegment> {
+        self.backtrace.as_ref().and_then(ChainTail::as_fail)
+            .and_then(|cause| cause.downcast_ref::<InvalidFeedbackIdSegment>())
+            .cloned()
+    }
+
+    /// Moves the span by `offset` bytes.
+    ///
+    /// This is used if the input of the failed component is part of a larger
+    /// input, e.g. the domain of an email address.
+    pub fn shift_span(mut self, offset: usize) -> Self {
+        if let Some(span) = self.span.take() {
+            self.span = Some(span.start + offset..span.end + offset);
+        }
+        self
+    }
 }
 
 impl Fail for ComponentCreationError {
     fn cause(&self) -> Option<&Fail> {
-        self.backtrace.as_fail()
+        self.backtrace.as_ref().and_then(ChainTail::as_fail)
     }
     fn backtrace(&self) -> Option<&Backtrace> {
-        Some(self.backtrace.backtrace())
+        self.backtrace.as_ref().map(ChainTail::backtrace)
     }
 }
 
 impl Display for ComponentCreationError {
     fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
-        write!(fter, "creating component {} failed", self.component)
+        write!(fter, "creating component {} failed", self.component)?;
+        if let Some(ref span) = self.span {
+            write!(fter, " at byte {}", span.start)?;
+            if let Some(expected) = self.expected {
+                write!(fter, ", expected {}", expected)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderKind;
+    use ::headers::Subject;
+    use super::*;
+
+    // the global setting is not changed here as the tests run in parallel
+    #[test]
+    fn backtrace_capture_can_be_disabled() {
+        assert!(ComponentCreationError::new_capturing("Email", None, true).backtrace().is_some());
+        assert!(HeaderTypeError::new_capturing(Subject::name(), true).backtrace().is_some());
+
+        let err = ComponentCreationError::new_capturing("Email", Some("abc".to_owned()), false);
+        assert!(err.backtrace().is_none());
+        assert_eq!(err.str_context(), Some("abc"));
+        assert!(HeaderTypeError::new_capturing(Subject::name(), false).backtrace().is_none());
+
+        // backtraces explicitly passed in are still kept
+        let err = HeaderTypeError::new_with_backtrace(Subject::name(), Backtrace::new());
+        assert!(err.backtrace().is_some());
+    }
+
+    #[test]
+    fn constructors_use_the_global_setting() {
+        let capture = backtrace_capture();
+        assert_eq!(ComponentCreationError::new("Email").backtrace().is_some(), capture);
+        assert_eq!(ComponentCreationError::new_with_str("Email", "abc").backtrace().is_some(), capture);
+        assert_eq!(HeaderTypeError::new(Subject::name()).backtrace().is_some(), capture);
+    }
+
+    #[test]
+    fn display_includes_span_if_known() {
+        let err = ComponentCreationError::new_with_str("Email", "abc");
+        assert_eq!(err.span(), None);
+        assert_eq!(err.to_string(), "creating component Email failed");
+
+        let err = err.with_span(3..3, Some("'@'"));
+        assert_eq!(err.to_string(), "creating component Email failed at byte 3, expected '@'");
+
+        let err = ComponentCreationError::new("Domain")
+            .with_span(2..3, None)
+            .shift_span(4);
+        assert_eq!(err.span(), Some(6..7));
+        assert_eq!(err.to_string(), "creating component Domain failed at byte 6");
     }
 }
@@ -0,0 +1,828 @@
+//! Creating headers from dynamic JSON values (requires the `serde-impl` feature).
+//!
+//! This is meant for e.g. rule engines storing header overrides as JSON,
+//! like `{"Subject": "Hi", "To": ["a@example.com", {"name": "X", "email": "x@example.com"}]}`.
+//! Which JSON shapes are accepted depends on the component of the header:
+//!
+//! - `Mailbox` accepts an address string or an object `{"name": .., "email": ..}`
+//!   (the `name` is optional)
+//! - `MailboxList` accepts a `Mailbox` shape or a non empty array of them,
+//!   `OptMailboxList` additionally `null` and an empty array
+//! - `MessageIdList`, `PhraseList` and `UriList` accept a string or an array of strings
+//! - `MediaType` accepts a string (including parameters) or an object
+//!   `{"type": "text/plain", "params": {"charset": "utf-8"}}`
+//! - `Disposition` accepts `"inline"`, `"attachment"` or an object with the
+//!   `kind` and the fields of `FileMeta`, e.g. `{"kind": "attachment", "file_name": "a.txt"}`
+//! - `Path` accepts an address string or `null` (for the null path)
+//! - `DateTime` accepts a rfc2822 (or rfc3339) date string
+//! - all other components (e.g. `Unstructured`, `MessageId`, `TransferEncoding`)
+//!   accept a string, which is parsed if needed
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono;
+use serde_json::{self, Map, Value};
+use vec1::Vec1;
+
+use ::{HeaderKind, HeaderTryFrom};
+use ::name::HeaderName;
+use ::header::HeaderObj;
+use ::error::{ComponentCreationError, JsonHeaderError};
+use ::header_components as components;
+use ::headers::*;
+use ::map::{HeaderMap, MapLimits};
+
+/// Converts the JSON value at given JSON path into a header with given name.
+pub type JsonConverter = fn(HeaderName, &Value, &str) -> Result<Box<HeaderObj>, JsonHeaderError>;
+
+/// Maps header names to the `JsonConverter` used to create the header.
+///
+/// `HeaderRegistry::builtin()` (which is also the `Default`) knows all
+/// headers defined in `headers`, custom headers can be added with
+/// `register` or `register_str`.
+///
+/// Headers registered with `register_raw` are kept separately, they are
+/// only used for the `raw` entries of a `header_group::HeaderGroupSpec`.
+#[derive(Clone)]
+pub struct HeaderRegistry {
+    converters: HashMap<HeaderName, JsonConverter>,
+    raw: HashMap<HeaderName, JsonConverter>
+}
+
+impl HeaderRegistry {
+
+    /// Creates a registry without any header.
+    pub fn new() -> Self {
+        HeaderRegistry { converters: HashMap::new(), raw: HashMap::new() }
+    }
+
+    /// Creates a registry knowing all headers defined in `headers`.
+    pub fn builtin() -> Self {
+        let mut registry = HeaderRegistry::new();
+
+        macro_rules! builtin {
+            ($($header:ident => $convert:ident),*) => ($({
+                fn convert(name: HeaderName, value: &Value, json_path: &str)
+                    -> Result<Box<HeaderObj>, JsonHeaderError>
+                {
+                    Ok(Box::new($header::body($convert(name, value, json_path)?)))
+                }
+                registry.register($header::name(), convert);
+            })*);
+        }
+
+        builtin! {
+            Date => date_time,
+            _From => mailbox_list,
+            Sender => mailbox,
+            ReplyTo => mailbox_list,
+            _To => mailbox_list,
+            Cc => mailbox_list,
+            Bcc => mailbox_list,
+            MessageId => message_id,
+            InReplyTo => message_id_list,
+            References => message_id_list,
+            Subject => unstructured,
+            Comments => unstructured,
+            Keywords => phrase_list,
+            ResentDate => date_time,
+            ResentFrom => mailbox_list,
+            ResentSender => mailbox,
+            ResentTo => mailbox_list,
+            ResentCc => mailbox_list,
+            ResentBcc => opt_mailbox_list,
+            ResentMsgId => message_id,
+            ReturnPath => path,
+            Received => received_token,
+            ContentType => media_type,
+            ContentId => message_id,
+            ContentTransferEncoding => transfer_encoding,
+            ContentDescription => unstructured,
+            ContentDisposition => disposition,
+            Precedence => precedence,
+            XAutoResponseSuppress => auto_response_suppress,
+            ListId => list_id,
+            ListPost => uri_list,
+            ListArchive => uri_list,
+            ListHelp => uri_list,
+            ListSubscribe => uri_list,
+            FeedbackId => feedback_id,
+            XReportAbuse => uri_list,
+            XRedacted => unstructured,
+            XOriginalFrom => mailbox,
+            XSpamStatus => spam_status
+        }
+
+        registry
+    }
+
+    /// Registers (or replaces) the converter for given header name.
+    pub fn register(&mut self, name: HeaderName, converter: JsonConverter) {
+        self.converters.insert(name, converter);
+    }
+
+    /// Registers a header whose component is created from a JSON string.
+    pub fn register_str<H>(&mut self)
+        where H: HeaderKind, H::Component: HeaderTryFrom<String>
+    {
+        self.register(H::name(), str_converter::<H>);
+    }
+
+    /// Registers (or replaces) a raw header, i.e. a header whose body is a uninterpreted string.
+    ///
+    /// The header is created from a JSON string, normally the component
+    /// of `H` is `header_components::RawFieldBody`.
+    pub fn register_raw<H>(&mut self)
+        where H: HeaderKind, H::Component: HeaderTryFrom<String>
+    {
+        self.raw.insert(H::name(), str_converter::<H>);
+    }
+
+    /// Returns the converter for given header name.
+    pub fn get(&self, name: HeaderName) -> Option<JsonConverter> {
+        self.converters.get(&name).cloned()
+    }
+
+    /// Returns the converter for given raw header name.
+    pub fn get_raw(&self, name: HeaderName) -> Option<JsonConverter> {
+        self.raw.get(&name).cloned()
+    }
+
+    /// Finds the converter for the header name given as string (ignoring case).
+    pub(crate) fn find(&self, name: &str) -> Option<(HeaderName, JsonConverter)> {
+        find_in(&self.converters, name)
+    }
+
+    /// Like `find` but for the headers registered with `register_raw`.
+    pub(crate) fn find_raw(&self, name: &str) -> Option<(HeaderName, JsonConverter)> {
+        find_in(&self.raw, name)
+    }
+}
+
+fn find_in(converters: &HashMap<HeaderName, JsonConverter>, name: &str)
+    -> Option<(HeaderName, JsonConverter)>
+{
+    converters.iter()
+        .find(|&(known, _)| known.as_str().eq_ignore_ascii_case(name))
+        .map(|(&known, &converter)| (known, converter))
+}
+
+impl Default for HeaderRegistry {
+    fn default() -> Self {
+        HeaderRegistry::builtin()
+    }
+}
+
+impl fmt::Debug for HeaderRegistry {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.debug_set()
+            .entries(self.converters.keys())
+            .entries(self.raw.keys())
+            .finish()
+    }
+}
+
+/// Creates the header with given name from the JSON value.
+///
+/// See the module documentation for the accepted shapes.
+///
+/// # Error
+///
+/// Fails if the registry doesn't know the header, the shape of the value
+/// doesn't fit the header or the component can not be created from the
+/// value. The error names the header and the JSON path (starting with
+/// `$` for the given value) of the value which could not be converted.
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_headers;
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// # fn main() {
+/// use mail_headers::HeaderKind;
+/// use mail_headers::headers::_To;
+/// use mail_headers::from_json::{header_from_json, HeaderRegistry};
+///
+/// let registry = HeaderRegistry::builtin();
+/// let to = json!(["a@example.com", {"name": "X", "email": "x@example.com"}]);
+/// let header = header_from_json(&_To::name(), &to, &registry).unwrap();
+/// assert!(header.downcast_ref::<_To>().is_some());
+///
+/// let err = header_from_json(&_To::name(), &json!(["a@example.com", 12]), &registry).unwrap_err();
+/// assert_eq!(err.path(), "$[1]");
+/// # }
+/// ```
+pub fn header_from_json(name: &HeaderName, value: &Value, registry: &HeaderRegistry)
+    -> Result<Box<HeaderObj>, JsonHeaderError>
+{
+    match registry.get(*name) {
+        Some(converter) => converter(*name, value, "$"),
+        None => Err(JsonHeaderError::UnknownHeader { name: name.as_str().to_owned(), path: "$".to_owned() })
+    }
+}
+
+/// Creates a map from a JSON object mapping header names to values.
+///
+/// The header names are matched ignoring case, the headers are inserted
+/// in the iteration order of the object. Like `headers!` this does not
+/// run the contextual validators.
+///
+/// # Error
+///
+/// Returns the errors for all headers which could not be converted (see
+/// `header_from_json`), their paths start with `$.<name>`.
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_headers;
+/// # #[macro_use]
+/// # extern crate serde_json;
+/// # fn main() {
+/// use mail_headers::headers::*;
+/// use mail_headers::from_json::headers_from_json;
+///
+/// let value = json!({
+///     "Subject": "Hi",
+///     "From": {"name": "Me", "email": "me@example.com"},
+///     "Date": "Tue, 1 Jul 2003 10:52:37 +0200"
+/// });
+/// let map = headers_from_json(value.as_object().unwrap()).unwrap();
+/// map.use_contextual_validators().unwrap();
+/// assert_eq!(map.get_single(Subject).unwrap().unwrap().as_str(), "Hi");
+/// # }
+/// ```
+pub fn headers_from_json(object: &Map<String, Value>) -> Result<HeaderMap, Vec<JsonHeaderError>> {
+    headers_from_json_with(object, &HeaderRegistry::builtin())
+}
+
+/// Like `headers_from_json` but uses given registry.
+pub fn headers_from_json_with(object: &Map<String, Value>, registry: &HeaderRegistry)
+    -> Result<HeaderMap, Vec<JsonHeaderError>>
+{
+    headers_from_json_with_limits(object, registry, MapLimits::default())
+}
+
+/// Like `headers_from_json_with` but creates a map with given limits.
+///
+/// If a header would exceed the limits a `JsonHeaderError::Limit` is
+/// added to the returned errors and the remaining entries of the object
+/// are not converted.
+pub fn headers_from_json_with_limits(
+    object: &Map<String, Value>, registry: &HeaderRegistry, limits: MapLimits
+) -> Result<HeaderMap, Vec<JsonHeaderError>> {
+    let mut map = HeaderMap::with_limits(limits);
+    let mut errors = Vec::new();
+    for (name, value) in object {
+        let path = format!("$.{}", name);
+        match registry.find(name) {
+            Some((name, converter)) => match converter(name, value, &path) {
+                Ok(obj) => if let Err(violation) = map.try_push(name, obj) {
+                    errors.push(JsonHeaderError::Limit { header_name: name, path, violation });
+                    break;
+                },
+                Err(err) => errors.push(err)
+            },
+            None => errors.push(JsonHeaderError::UnknownHeader { name: name.clone(), path })
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(map)
+    } else {
+        Err(errors)
+    }
+}
+
+fn str_converter<H>(name: HeaderName, value: &Value, path: &str)
+    -> Result<Box<HeaderObj>, JsonHeaderError>
+    where H: HeaderKind, H::Component: HeaderTryFrom<String>
+{
+    let text = string(name, value, path, "a string")?;
+    let component = component(name, path, H::Component::try_from(text.to_owned()))?;
+    Ok(Box::new(H::body(component)))
+}
+
+/// A human readable name of the shape of a JSON value (used in `JsonHeaderError`).
+fn shape_of(value: &Value) -> &'static str {
+    match *value {
+        Value::Null => "null",
+        Value::Bool(..) => "a boolean",
+        Value::Number(..) => "a number",
+        Value::String(..) => "a string",
+        Value::Array(..) => "an array",
+        Value::Object(..) => "an object"
+    }
+}
+
+fn mismatch(name: HeaderName, value: &Value, path: &str, expected: &'static str) -> JsonHeaderError {
+    JsonHeaderError::ShapeMismatch {
+        header_name: name,
+        path: path.to_owned(),
+        shape: shape_of(value),
+        expected
+    }
+}
+
+fn component<T>(name: HeaderName, path: &str, res: Result<T, ComponentCreationError>)
+    -> Result<T, JsonHeaderError>
+{
+    res.map_err(|error| JsonHeaderError::Component {
+        header_name: name,
+        path: path.to_owned(),
+        error
+    })
+}
+
+fn string<'a>(name: HeaderName, value: &'a Value, path: &str, expected: &'static str)
+    -> Result<&'a str, JsonHeaderError>
+{
+    match *value {
+        Value::String(ref text) => Ok(text.as_str()),
+        _ => Err(mismatch(name, value, path, expected))
+    }
+}
+
+/// Returns the value as one item list if it's not an array, or the items of the array.
+fn items<'a>(value: &'a Value, path: &str) -> Vec<(String, &'a Value)> {
+    match *value {
+        Value::Array(ref items) => items.iter()
+            .enumerate()
+            .map(|(idx, item)| (format!("{}[{}]", path, idx), item))
+            .collect(),
+        _ => vec![(path.to_owned(), value)]
+    }
+}
+
+const MAILBOX: &str = "an address or an object with an `email` and an optional `name`";
+const MAILBOXES: &str = "an address, an object with an `email` and an optional `name` or a non empty array of them";
+
+fn mailbox(name: HeaderName, value: &Value, path: &str) -> Result<components::Mailbox, JsonHeaderError> {
+    let object = match *value {
+        Value::String(ref addr) => return component(name, path, components::Mailbox::try_from(addr.clone())),
+        Value::Object(ref object) => object,
+        _ => return Err(mismatch(name, value, path, MAILBOX))
+    };
+
+    let email_path = format!("{}.email", path);
+    let email = match object.get("email") {
+        Some(email) => string(name, email, &email_path, "an address string")?,
+        None => return Err(mismatch(name, value, path, MAILBOX))
+    };
+    match object.get("name") {
+        None | Some(&Value::Null) =>
+            component(name, path, components::Mailbox::try_from(email.to_owned())),
+        Some(display_name) => {
+            let display_name = string(name, display_name, &format!("{}.name", path), "a display name string")?;
+            component(name, path, components::Mailbox::try_from((display_name.to_owned(), email.to_owned())))
+        }
+    }
+}
+
+fn mailbox_list(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::MailboxList, JsonHeaderError>
+{
+    let mailboxes = items(value, path).into_iter()
+        .map(|(path, item)| match *item {
+            Value::String(..) | Value::Object(..) => mailbox(name, item, &path),
+            _ => Err(mismatch(name, item, &path, MAILBOX))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Vec1::from_vec(mailboxes)
+        .map(components::MailboxList)
+        .map_err(|_| mismatch(name, value, path, MAILBOXES))
+}
+
+fn opt_mailbox_list(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::OptMailboxList, JsonHeaderError>
+{
+    match *value {
+        Value::Null => Ok(components::OptMailboxList(Vec::new())),
+        Value::Array(ref items) if items.is_empty() => Ok(components::OptMailboxList(Vec::new())),
+        _ => mailbox_list(name, value, path)
+            .map(|list| components::OptMailboxList(list.into_iter().collect()))
+    }
+}
+
+fn message_id(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::MessageId, JsonHeaderError>
+{
+    let id = string(name, value, path, "a message id string")?;
+    component(name, path, components::MessageId::try_from(id.to_owned()))
+}
+
+fn message_id_list(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::MessageIdList, JsonHeaderError>
+{
+    let ids = items(value, path).into_iter()
+        .map(|(path, item)| message_id(name, item, &path))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Vec1::from_vec(ids)
+        .map(components::MessageIdList)
+        .map_err(|_| mismatch(name, value, path, "a message id string or a non empty array of them"))
+}
+
+fn unstructured(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::Unstructured, JsonHeaderError>
+{
+    let text = string(name, value, path, "a string")?;
+    component(name, path, components::Unstructured::try_from(text.to_owned()))
+}
+
+fn phrase_list(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::PhraseList, JsonHeaderError>
+{
+    let phrases = items(value, path).into_iter()
+        .map(|(path, item)| {
+            string(name, item, &path, "a string or an array of strings").map(str::to_owned)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    component(name, path, components::PhraseList::try_from(phrases))
+}
+
+fn date_time(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::DateTime, JsonHeaderError>
+{
+    let date = string(name, value, path, "a rfc2822 date string")?;
+    let parsed = chrono::DateTime::parse_from_rfc2822(date)
+        .or_else(|_| chrono::DateTime::parse_from_rfc3339(date))
+        .map(components::DateTime::new)
+        .map_err(|err| ComponentCreationError::from_parent(err, "DateTime")
+            .with_str_context(date));
+    component(name, path, parsed)
+}
+
+fn path(name: HeaderName, value: &Value, path: &str) -> Result<components::Path, JsonHeaderError> {
+    match *value {
+        Value::Null => Ok(components::Path(None)),
+        Value::String(ref addr) => component(name, path, components::Path::try_from(addr.clone())),
+        _ => Err(mismatch(name, value, path, "an address string or null (for the null path)"))
+    }
+}
+
+fn received_token(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::ReceivedToken, JsonHeaderError>
+{
+    use self::components::ReceivedToken::*;
+    let token = string(name, value, path, "an address or domain string")?;
+    if token.contains('@') {
+        component(name, path, components::Email::try_from(token.to_owned()).map(Address))
+    } else {
+        component(name, path, components::Domain::try_from(token.to_owned()).map(Domain))
+    }
+}
+
+const MEDIA_TYPE: &str = "a media type string or an object with a `type` and optional `params`";
+
+fn media_type(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::MediaType, JsonHeaderError>
+{
+    let object = match *value {
+        Value::String(ref media_type) =>
+            return component(name, path, components::MediaType::parse(media_type)),
+        Value::Object(ref object) => object,
+        _ => return Err(mismatch(name, value, path, MEDIA_TYPE))
+    };
+
+    let type_path = format!("{}.type", path);
+    let full_type = match object.get("type") {
+        Some(full_type) => string(name, full_type, &type_path, "a `type/subtype` string")?,
+        None => return Err(mismatch(name, value, path, MEDIA_TYPE))
+    };
+    let (type_, subtype) = match full_type.find('/') {
+        Some(idx) => (&full_type[..idx], &full_type[idx+1..]),
+        None => return component(name, &type_path,
+            Err(ComponentCreationError::new_with_str("MediaType", full_type)))
+    };
+
+    let mut params = Vec::new();
+    match object.get("params") {
+        None | Some(&Value::Null) => {},
+        Some(&Value::Object(ref object)) => {
+            for (param, param_value) in object {
+                let param_path = format!("{}.params.{}", path, param);
+                let param_value = string(name, param_value, &param_path, "a parameter value string")?;
+                params.push((param.as_str(), param_value));
+            }
+        },
+        Some(other) =>
+            return Err(mismatch(name, other, &format!("{}.params", path), "an object of parameters"))
+    }
+
+    component(name, path, components::MediaType::new_with_params(type_, subtype, params))
+}
+
+fn transfer_encoding(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::TransferEncoding, JsonHeaderError>
+{
+    use self::components::TransferEncoding::*;
+    let encoding = string(name, value, path, "a transfer encoding string")?;
+    let encoding = [_7Bit, _8Bit, Binary, QuotedPrintable, Base64].iter()
+        .find(|candidate| candidate.repr().as_str().eq_ignore_ascii_case(encoding))
+        .cloned()
+        .ok_or_else(|| ComponentCreationError::new_with_str("TransferEncoding", encoding));
+    component(name, path, encoding)
+}
+
+const DISPOSITION: &str = "\"inline\", \"attachment\" or an object with a `kind` and file meta fields";
+
+fn disposition(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::Disposition, JsonHeaderError>
+{
+    let object = match *value {
+        Value::String(ref disposition) =>
+            return component(name, path, components::Disposition::try_from(&**disposition)),
+        Value::Object(ref object) => object,
+        _ => return Err(mismatch(name, value, path, DISPOSITION))
+    };
+
+    let kind_path = format!("{}.kind", path);
+    let kind = match object.get("kind") {
+        Some(kind) => string(name, kind, &kind_path, "\"inline\" or \"attachment\"")?,
+        None => return Err(mismatch(name, value, path, DISPOSITION))
+    };
+    let kind = component(name, &kind_path, components::Disposition::try_from(kind))?.kind();
+
+    let mut file_meta = object.clone();
+    file_meta.remove("kind");
+    let file_meta = serde_json::from_value::<components::FileMeta>(Value::Object(file_meta))
+        .map_err(|err| ComponentCreationError::from_parent(err, "FileMeta"));
+    let file_meta = component(name, path, file_meta)?;
+
+    Ok(components::Disposition::new(kind, file_meta))
+}
+
+fn precedence(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::Precedence, JsonHeaderError>
+{
+    let precedence = string(name, value, path, "a precedence string")?;
+    component(name, path, components::Precedence::parse(precedence))
+}
+
+fn auto_response_suppress(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::AutoResponseSuppress, JsonHeaderError>
+{
+    let tokens = string(name, value, path, "a comma separated list of tokens")?;
+    component(name, path, components::AutoResponseSuppress::parse(tokens))
+}
+
+fn list_id(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::ListId, JsonHeaderError>
+{
+    let list_id = string(name, value, path, "a list id string")?;
+    component(name, path, components::ListId::parse(list_id))
+}
+
+fn feedback_id(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::FeedbackId, JsonHeaderError>
+{
+    let feedback_id = string(name, value, path, "a feedback id string")?;
+    component(name, path, components::FeedbackId::parse(feedback_id))
+}
+
+fn spam_status(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::SpamStatus, JsonHeaderError>
+{
+    let status = string(name, value, path, "a spam status string")?;
+    component(name, path, components::SpamStatus::parse(status))
+}
+
+fn uri_list(name: HeaderName, value: &Value, path: &str)
+    -> Result<components::UriList, JsonHeaderError>
+{
+    match *value {
+        Value::String(ref uris) => component(name, path, components::UriList::parse(uris)),
+        Value::Array(..) => {
+            let uris = items(value, path).into_iter()
+                .map(|(path, item)| string(name, item, &path, "a URI string"))
+                .collect::<Result<Vec<_>, _>>()?;
+            let uris = uris.into_iter()
+                .map(|uri| uri.trim_matches(|ch: char| ch == '<' || ch == '>'));
+            component(name, path, components::UriList::new(uris))
+        },
+        _ => Err(mismatch(name, value, path, "a URI list string or an array of URIs"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::header_components::DispositionKind;
+    use super::*;
+
+    fn convert(name: HeaderName, value: Value) -> Result<Box<HeaderObj>, JsonHeaderError> {
+        header_from_json(&name, &value, &HeaderRegistry::builtin())
+    }
+
+    fn map_of(name: HeaderName, value: Value) -> HeaderMap {
+        let obj = convert(name, value)
+            .unwrap_or_else(|err| panic!("{} rejected value: {}", name, err));
+        HeaderMap::from_vec(vec![(name, obj)])
+    }
+
+    fn assert_mismatch(name: HeaderName, value: Value, expected_path: &str) {
+        match convert(name, value) {
+            Err(JsonHeaderError::ShapeMismatch { header_name, path, .. }) => {
+                assert_eq!(header_name, name);
+                assert_eq!(path, expected_path);
+            },
+            other => panic!("{}: unexpected result: {:?}", name, other)
+        }
+    }
+
+    #[test]
+    fn string_shapes() {
+        let map = map_of(Subject::name(), json!("Hi"));
+        assert_eq!(map.get_single(Subject).unwrap().unwrap().as_str(), "Hi");
+
+        let map = map_of(MessageId::name(), json!("a@example.com"));
+        assert_eq!(map.get_single(MessageId).unwrap().unwrap().as_str(), "a@example.com");
+
+        let map = map_of(ContentTransferEncoding::name(), json!("Quoted-Printable"));
+        assert_eq!(**map.get_single(ContentTransferEncoding).unwrap().unwrap(),
+            components::TransferEncoding::QuotedPrintable);
+
+        let map = map_of(Date::name(), json!("Tue, 1 Jul 2003 10:52:37 +0200"));
+        let date = map.get_single(Date).unwrap().unwrap();
+        assert_eq!(date.timestamp(), 1057049557);
+
+        assert_mismatch(Subject::name(), json!(["Hi"]), "$");
+        assert_mismatch(Date::name(), json!(12), "$");
+    }
+
+    #[test]
+    fn mailbox_shapes() {
+        let map = map_of(Sender::name(), json!("me@example.com"));
+        assert_eq!(map.get_single(Sender).unwrap().unwrap().email(), "me@example.com");
+
+        let map = map_of(Sender::name(), json!({"name": "Me", "email": "me@example.com"}));
+        let sender = map.get_single(Sender).unwrap().unwrap();
+        assert_eq!(sender.name(), "Me");
+        assert_eq!(sender.email(), "me@example.com");
+
+        let map = map_of(Sender::name(), json!({"name": null, "email": "me@example.com"}));
+        assert_eq!(map.get_single(Sender).unwrap().unwrap().email(), "me@example.com");
+
+        assert_mismatch(Sender::name(), json!(["me@example.com"]), "$");
+        assert_mismatch(Sender::name(), json!({"name": "Me"}), "$");
+        assert_mismatch(Sender::name(), json!({"name": 12, "email": "me@example.com"}), "$.name");
+        assert_mismatch(Sender::name(), json!({"email": ["me@example.com"]}), "$.email");
+    }
+
+    #[test]
+    fn list_shapes() {
+        let map = map_of(_To::name(), json!(["a@b.example", {"name": "X", "email": "d@e.example"}]));
+        let to = map.get_single(_To).unwrap().unwrap();
+        assert_eq!(to.len(), 2);
+        assert_eq!(to[1].name(), "X");
+
+        let map = map_of(_To::name(), json!("a@b.example"));
+        assert_eq!(map.get_single(_To).unwrap().unwrap().len(), 1);
+
+        let map = map_of(References::name(), json!(["a@example.com", "b@example.com"]));
+        assert_eq!(map.get_single(References).unwrap().unwrap().0.len(), 2);
+
+        let map = map_of(Keywords::name(), json!(["a", "b c"]));
+        assert_eq!(map.get_single(Keywords).unwrap().unwrap().0.len(), 2);
+
+        let map = map_of(ResentBcc::name(), json!([]));
+        assert!(map.get_single(ResentBcc).unwrap().unwrap().0.is_empty());
+
+        assert_mismatch(_To::name(), json!([]), "$");
+        assert_mismatch(_To::name(), json!(["a@b.example", 12]), "$[1]");
+        assert_mismatch(_To::name(), json!(["a@b.example", {"email": null}]), "$[1].email");
+        assert_mismatch(References::name(), json!(["a@example.com", {}]), "$[1]");
+        assert_mismatch(Keywords::name(), json!({"a": "b"}), "$");
+    }
+
+    #[test]
+    fn object_shapes() {
+        let map = map_of(ContentType::name(), json!({
+            "type": "text/plain",
+            "params": {"charset": "utf-8"}
+        }));
+        let media_type = map.get_single(ContentType).unwrap().unwrap();
+        assert_eq!(media_type.get_param("charset"), Some("utf-8".to_owned()));
+
+        let map = map_of(ContentType::name(), json!("text/html; charset=utf-8"));
+        assert!(map.get_single(ContentType).unwrap().is_ok());
+
+        let map = map_of(ContentDisposition::name(), json!({
+            "kind": "attachment",
+            "file_name": "a.txt",
+            "size": 12
+        }));
+        let disposition = map.get_single(ContentDisposition).unwrap().unwrap();
+        assert_eq!(disposition.kind(), DispositionKind::Attachment);
+        assert_eq!(disposition.file_meta().file_name, Some("a.txt".to_owned()));
+        assert_eq!(disposition.file_meta().size, Some(12));
+
+        let map = map_of(ContentDisposition::name(), json!("inline"));
+        assert_eq!(map.get_single(ContentDisposition).unwrap().unwrap().kind(), DispositionKind::Inline);
+
+        assert_mismatch(ContentType::name(), json!({"params": {}}), "$");
+        assert_mismatch(ContentType::name(), json!({"type": "text/plain", "params": {"a": 1}}), "$.params.a");
+        assert_mismatch(ContentDisposition::name(), json!({"kind": 1}), "$.kind");
+    }
+
+    #[test]
+    fn component_errors_name_header_and_path() {
+        match convert(_To::name(), json!(["a@b.example", "not an address"])) {
+            Err(JsonHeaderError::Component { header_name, path, .. }) => {
+                assert_eq!(header_name, _To::name());
+                assert_eq!(path, "$[1]");
+            },
+            other => panic!("unexpected result: {:?}", other)
+        }
+
+        match convert(ContentDisposition::name(), json!({"kind": "sideways"})) {
+            Err(JsonHeaderError::Component { path, .. }) => assert_eq!(path, "$.kind"),
+            other => panic!("unexpected result: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn wrong_shape_error_message() {
+        let err = assert_err!(convert(Subject::name(), json!({"text": "Hi"})));
+        assert_eq!(
+            err.to_string(),
+            "$: Subject header does not accept an object (expected a string)"
+        );
+        assert_eq!(err.name(), "Subject");
+        assert_eq!(err.path(), "$");
+    }
+
+    #[test]
+    fn custom_headers_can_be_registered() {
+        let name = HeaderName::from_ascii_unchecked("X-Unknown");
+        let mut registry = HeaderRegistry::new();
+        match header_from_json(&name, &json!("value"), &registry) {
+            Err(JsonHeaderError::UnknownHeader { name, path }) => {
+                assert_eq!(name, "X-Unknown");
+                assert_eq!(path, "$");
+            },
+            other => panic!("unexpected result: {:?}", other)
+        }
+
+        registry.register_str::<XRedacted>();
+        let obj = assert_ok!(header_from_json(&XRedacted::name(), &json!("Bcc"), &registry));
+        assert!(obj.downcast_ref::<XRedacted>().is_some());
+        assert_err!(header_from_json(&Subject::name(), &json!("Hi"), &registry));
+    }
+
+    test!(end_to_end {
+        let value = json!({
+            "Subject": "Hi",
+            "from": ["a@b.example", {"name": "X", "email": "d@e.example"}],
+            "Sender": "a@b.example",
+            "To": ["c@d.example"],
+            "Date": "Tue, 1 Jul 2003 10:52:37 +0200",
+            "Content-Type": {"type": "text/plain", "params": {"charset": "utf-8"}}
+        });
+
+        let map = headers_from_json(value.as_object().unwrap())
+            .unwrap_or_else(|errs| panic!("unexpected errors: {:?}", errs));
+        assert_eq!(map.len(), 6);
+        assert_eq!(map.get_single(_From).unwrap()?.len(), 2);
+        map.use_contextual_validators()?;
+    });
+
+    #[test]
+    fn all_errors_are_returned() {
+        let value = json!({
+            "Subject": ["Hi"],
+            "To": ["a@b.example", 12],
+            "X-Unknown": "value",
+            "Comments": "fine"
+        });
+
+        let errors = assert_err!(headers_from_json(value.as_object().unwrap()));
+        let mut paths = errors.iter().map(|err| err.path()).collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(paths, vec!["$.Subject", "$.To[1]", "$.X-Unknown"]);
+    }
+
+    #[test]
+    fn limits_stop_the_conversion() {
+        let value = json!({
+            "Comments": "a",
+            "Keywords": "b"
+        });
+
+        let limits = MapLimits { max_headers: Some(1), ..Default::default() };
+        let errors = assert_err!(headers_from_json_with_limits(
+            value.as_object().unwrap(), &HeaderRegistry::builtin(), limits));
+        assert_eq!(errors.len(), 1);
+        match errors[0] {
+            JsonHeaderError::Limit { ref violation, .. } => assert_eq!(violation.accepted(), 1),
+            ref other => panic!("unexpected error: {:?}", other)
+        }
+
+        let limits = MapLimits { max_headers: Some(2), ..Default::default() };
+        let map = assert_ok!(headers_from_json_with_limits(
+            value.as_object().unwrap(), &HeaderRegistry::builtin(), limits));
+        assert_eq!(map.len(), 2);
+    }
+}
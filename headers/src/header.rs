@@ -1,18 +1,28 @@
 use std::any::TypeId;
+use std::borrow::Cow;
 use std::ops::{Deref, DerefMut};
+use std::str;
 use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use failure::Fail;
+use once_cell::sync::OnceCell;
+use soft_ascii::SoftAsciiChar;
 
 use internals::{
-    error::EncodingError,
+    MailType,
+    error::{EncodingError, EncodingErrorKind},
     encoder::{
         EncodableInHeader,
+        EncodingBuffer,
         EncodingWriter,
     }
 };
 
 
-use ::error::ComponentCreationError;
+use ::error::{ComponentCreationError, HeaderTooLong, HeaderTypeError};
 use ::convert::HeaderTryInto;
+use ::mail_type::MailTypeRequirement;
 use ::name::{HeaderName, HasHeaderName};
 //NOTE: this is a circular dependency between Header/HeaderMap
 // but putting up e.g. a GenericHeaderMap trait/interface is
@@ -63,6 +73,26 @@ pub trait HeaderKind: Clone + Default + 'static {
     /// Most headers have this set to true.
     const MAX_ONE: bool;
 
+    /// If true `HeaderObjTrait::encoded_value` caches the encoded value.
+    ///
+    /// Only the value for the first mail type it is requested for is cached,
+    /// so a header keeps at most one encoded copy of its body.
+    ///
+    /// The cache is cleared whenever the body is accessed mutable, but it can
+    /// not detect changes done through interior mutability (e.g. a component
+    /// containing a `Mutex`), header kinds with such components should set
+    /// this to false.
+    const CACHE_ENCODED_VALUE: bool = true;
+
+    /// Returns which mail type is needed to encode given body, see `HeaderMap::required_mail_type`.
+    ///
+    /// The default is that the body can be encoded in any mail type, headers
+    /// defined with `def_headers!` can use the `HasMailTypeRequirement` impl of
+    /// their component instead (see `mail_type_requirement: component` there).
+    fn mail_type_requirement(_body: &Self::Component) -> MailTypeRequirement {
+        MailTypeRequirement::ascii()
+    }
+
     /// Creates a `Header` instance automatically converting given body to the right type.
     ///
     /// # Error
@@ -95,29 +125,128 @@ impl<H> HasHeaderName for H
 
 pub trait MaxOneMarker: HeaderKind {}
 
+/// A header of the given header kind.
+///
+/// The body (and the cached encoded value) are shared between clones of
+/// a header, so cloning a header (and with it a `HeaderMap`) does not clone
+/// the component. The body is copied on write, i.e. the first mutable
+/// access to the body of a clone (e.g. through `body_mut`, `DerefMut` or
+/// the `*_mut` methods of `HeaderMap`) clones the component if it is still
+/// shared with another header.
 #[derive(Clone)]
 pub struct Header<H>
     where H: HeaderKind
 {
-    body: H::Component
+    body: Arc<H::Component>,
+    cache: Arc<EncodedValueCache>
 }
 
 impl<H> Header<H>
     where H: HeaderKind
 {
     pub fn new(body: H::Component) -> Header<H> {
-        Header { body }
+        Header { body: Arc::new(body), cache: Default::default() }
     }
 
     pub fn body(&self) -> &H::Component {
-        &self.body
+        &*self.body
     }
 
+    /// Returns a mutable reference to the body.
+    ///
+    /// This clears the cached encoded value (see `HeaderObjTrait::encoded_value`)
+    /// and clones the body if it is shared with a clone of this header.
     pub fn body_mut(&mut self) -> &mut H::Component {
-        &mut self.body
+        self.cache = Default::default();
+        Arc::make_mut(&mut self.body)
+    }
+
+    /// Returns true if this header shares its body with given header.
+    ///
+    /// This is the case for a header and its clones as long as neither of
+    /// them was accessed mutable.
+    pub fn shares_body_with(&self, other: &Header<H>) -> bool {
+        Arc::ptr_eq(&self.body, &other.body)
     }
 }
 
+/// The encoded value of a header for the mail type it was first requested for.
+#[derive(Clone, Default)]
+struct EncodedValueCache {
+    value: OnceCell<(MailType, Vec<u8>)>
+}
+
+/// The maximal length of a encoded max-one header line (including name and final CRLF).
+///
+/// rfc5322 only limits the length of each (folded) line, but receivers
+/// handle very long headers badly and a max-one header can not be split into
+/// multiple headers, so encoding a longer max-one header fails with a
+/// `HeaderTooLong` error. See `HeaderMap::oversized_headers` to find such
+/// headers before encoding.
+pub const MAX_ONE_HEADER_LEN: usize = 8 * 1024;
+
+/// Encodes the header line and returns the bytes between `"<name>: "` and the final CRLF.
+///
+/// `encode_body` is called to encode the body after the name was written.
+fn encode_value<F>(name: HeaderName, max_one: bool, mail_type: MailType, encode_body: F)
+    -> Result<Vec<u8>, EncodingError>
+    where F: FnOnce(&mut EncodingWriter) -> Result<(), EncodingError>
+{
+    fn write_name(handle: &mut EncodingWriter, name: HeaderName) -> Result<(), EncodingError> {
+        handle.write_str(name.as_wire_ascii_str())?;
+        handle.write_char(SoftAsciiChar::from_unchecked(':'))?;
+        handle.write_fws();
+        Ok(())
+    }
+
+    let mut buffer = EncodingBuffer::new(mail_type);
+    {
+        let mut handle = buffer.writer();
+        let res = write_name(&mut handle, name).and_then(|()| encode_body(&mut handle));
+        match res {
+            Ok(()) => handle.finish_header(),
+            Err(err) => {
+                handle.undo_header();
+                return Err(err);
+            }
+        }
+    }
+    let line = buffer.as_slice();
+    if max_one {
+        check_max_one_len(name, line.len())?;
+    }
+    let value = &line[name.as_wire_str().len() + 1..line.len() - 2];
+    let value = if value.first() == Some(&b' ') { &value[1..] } else { value };
+    Ok(value.to_owned())
+}
+
+/// Writes a value returned by `encode_value` after the `"<name>:"` and FWS written by the caller.
+fn write_encoded_value(handle: &mut EncodingWriter, value: &[u8]) -> Result<(), EncodingError> {
+    // a fold at the FWS after the name is redone by `handle` (at the FWS of the caller)
+    let value = if value.starts_with(b"\r\n ") { &value[3..] } else { value };
+    let value = str::from_utf8(value)
+        .expect("[BUG] the encoder only writes utf-8");
+    handle.write_str_unchecked(value)
+}
+
+/// Fails with a `HeaderTooLong` error if a max-one header line of given length is too long.
+pub(crate) fn check_max_one_len(name: HeaderName, line_len: usize) -> Result<(), EncodingError> {
+    if line_len > MAX_ONE_HEADER_LEN {
+        let too_long = HeaderTooLong::new(name, line_len, MAX_ONE_HEADER_LEN);
+        Err(EncodingError::from(
+            too_long.context(EncodingErrorKind::Other { kind: "HeaderTooLong" })))
+    } else {
+        Ok(())
+    }
+}
+
+/// Encodes the value of given header without using (or filling) the cache of `encoded_value`.
+///
+/// Unlike `encoded_value` this does not fail for max-one headers which are too long.
+pub(crate) fn uncached_value(obj: &HeaderObj, mail_type: MailType) -> Result<Vec<u8>, EncodingError> {
+    encode_value(obj.name(), false, mail_type, |handle| obj.encode_uncached(handle))
+}
+
 impl<H> Deref for Header<H>
     where H: HeaderKind
 {
@@ -143,16 +272,62 @@ impl<H> Debug for Header<H>
     }
 }
 
+impl Debug for EncodedValueCache {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str("EncodedValueCache { .. }")
+    }
+}
+
 /// Type alias for HeaderObjTrait's trait object.
 pub type HeaderObj = dyn HeaderObjTrait;
 
 pub trait HeaderObjTrait: Sync + Send + ::std::any::Any + Debug {
     fn name(&self) -> HeaderName;
+
+    /// Returns true if at most one header with this name may appear in a mail (`HeaderKind::MAX_ONE`).
+    ///
+    /// This makes it possible to check if duplicates are allowed when only
+    /// having the header object, e.g. after `HeaderMap::into_iter`.
     fn is_max_one(&self) -> bool;
     fn validator(&self) -> Option<HeaderMapValidator>;
     fn encode(&self, encoder: &mut EncodingWriter) -> Result<(), EncodingError>;
     fn boxed_clone(&self) -> Box<HeaderObj>;
 
+    /// Returns the encoded value (body) of this header for given mail type.
+    ///
+    /// This are the bytes following the `"<name>: "` prefix of the encoded
+    /// header line, excluding the final CRLF (but including any CRLF added
+    /// by folding). The result for the first requested mail type is cached
+    /// (see `HeaderKind::CACHE_ENCODED_VALUE`), so this is cheap to call
+    /// repeatedly as long as the header is not changed.
+    ///
+    /// The default implementation encodes the header (using `encode`) on
+    /// each call.
+    fn encoded_value(&self, mail_type: MailType) -> Result<Cow<[u8]>, EncodingError> {
+        let value = encode_value(self.name(), self.is_max_one(), mail_type, |handle| self.encode(handle))?;
+        Ok(Cow::Owned(value))
+    }
+
+    /// Returns which mail type is needed to encode this header, see `HeaderMap::required_mail_type`.
+    ///
+    /// The default is that the header can be encoded in any mail type.
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        MailTypeRequirement::ascii()
+    }
+
+    /// Returns the type name of the component of this header, e.g. for debugging.
+    ///
+    /// The default is the type name of the header object itself.
+    fn component_type_name(&self) -> &'static str {
+        ::std::any::type_name::<Self>()
+    }
+
+    /// Encodes the body like `encode` but without any checks or caching (used for debugging).
+    #[doc(hidden)]
+    fn encode_uncached(&self, encoder: &mut EncodingWriter) -> Result<(), EncodingError> {
+        self.encode(encoder)
+    }
+
     #[doc(hidden)]
     fn type_id(&self) -> TypeId {
         TypeId::of::<Self>()
@@ -174,10 +349,55 @@ impl<H> HeaderObjTrait for Header<H>
         H::VALIDATOR
     }
 
+    /// Encodes the body of this header.
+    ///
+    /// Max-one headers whose header line would be longer than `MAX_ONE_HEADER_LEN`
+    /// fail with a `HeaderTooLong` error (before anything is written). To know
+    /// the length such a header is encoded on its own first (or the cached value
+    /// is used if it is cached for the mail type), the produced bytes are then
+    /// written into `encoder`. This neither encodes the body twice nor fills
+    /// the cache of `encoded_value`.
     fn encode(&self, encoder: &mut EncodingWriter) -> Result<(), EncodingError> {
+        if !H::MAX_ONE {
+            return self.body.encode(encoder);
+        }
+        let mail_type = encoder.mail_type();
+        match self.cache.value.get() {
+            // the length was checked when the value was cached
+            Some(&(cached_type, ref cached)) if cached_type == mail_type => {
+                write_encoded_value(encoder, cached)
+            },
+            _ => {
+                let value = encode_value(H::name(), true, mail_type, |handle| self.body.encode(handle))?;
+                write_encoded_value(encoder, &value)
+            }
+        }
+    }
+
+    fn encode_uncached(&self, encoder: &mut EncodingWriter) -> Result<(), EncodingError> {
         self.body.encode(encoder)
     }
 
+    fn encoded_value(&self, mail_type: MailType) -> Result<Cow<[u8]>, EncodingError> {
+        let encode = || encode_value(H::name(), H::MAX_ONE, mail_type, |handle| self.body.encode(handle));
+        if H::CACHE_ENCODED_VALUE {
+            let &(cached_type, ref cached) = self.cache.value
+                .get_or_try_init(|| Ok::<_, EncodingError>((mail_type, encode()?)))?;
+            if cached_type == mail_type {
+                return Ok(Cow::Borrowed(&cached[..]));
+            }
+        }
+        Ok(Cow::Owned(encode()?))
+    }
+
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        H::mail_type_requirement(self.body())
+    }
+
+    fn component_type_name(&self) -> &'static str {
+        ::std::any::type_name::<H::Component>()
+    }
+
     fn boxed_clone(&self) -> Box<HeaderObj> {
         let cloned = self.clone();
         Box::new(cloned)
@@ -194,8 +414,20 @@ impl<H> HasHeaderName for Header<H>
 
 
 impl HeaderObj {
+    /// Returns true if this is a `Header<H>`.
+    ///
+    /// A compressed header (see `HeaderMap::compress_large_values`) is a
+    /// `Header<H>` if its value can be decompressed into one, in which case
+    /// the decompressed header is cached and used by `downcast_ref`.
     pub fn is<H>(&self) -> bool
         where H: HeaderKind
+    {
+        self.is_exactly::<H>()
+            || self.decompressed().map(|obj| obj.is_exactly::<H>()).unwrap_or(false)
+    }
+
+    fn is_exactly<H>(&self) -> bool
+        where H: HeaderKind
     {
         self.type_id() == TypeId::of::<Header<H>>()
     }
@@ -203,21 +435,90 @@ impl HeaderObj {
     pub fn downcast_ref<H>(&self) -> Option<&Header<H>>
         where H: HeaderKind
     {
-        if self.is::<H>() {
+        if self.is_exactly::<H>() {
             Some(unsafe { &*(self as *const _ as *const Header<H>) })
         } else {
-            None
+            self.decompressed().and_then(|obj| obj.downcast_ref::<H>())
         }
     }
 
     pub fn downcast_mut<H>(&mut self) -> Option<&mut Header<H>>
         where H: HeaderKind
     {
-        if self.is::<H>() {
-            Some(unsafe { &mut *(self as *mut _ as *mut Header<H>) })
-        } else {
-            None
+        if self.is_exactly::<H>() {
+            return Some(unsafe { &mut *(self as *mut _ as *mut Header<H>) });
+        }
+        if !self.is::<H>() {
+            return None;
         }
+        self.decompressed_mut().and_then(|obj| obj.downcast_mut::<H>())
+    }
+
+    /// Like `downcast_ref` but returns the error typed accessors return if this is not a `Header<H>`.
+    pub(crate) fn try_downcast_ref<H>(&self) -> Result<&Header<H>, HeaderTypeError>
+        where H: HeaderKind
+    {
+        self.downcast_ref::<H>()
+            .ok_or_else(|| self.type_error::<H>())
+    }
+
+    /// Like `downcast_mut` but returns the error typed accessors return if this is not a `Header<H>`.
+    pub(crate) fn try_downcast_mut<H>(&mut self) -> Result<&mut Header<H>, HeaderTypeError>
+        where H: HeaderKind
+    {
+        if !self.is::<H>() {
+            return Err(self.type_error::<H>());
+        }
+        self.downcast_mut::<H>()
+            .ok_or_else(|| HeaderTypeError::new(H::name()))
+    }
+
+    /// The error returned by typed accessors if this is not a `Header<H>`.
+    ///
+    /// For a compressed header whose value can not be restored the error
+    /// has the `DecompressError` as cause.
+    pub(crate) fn type_error<H>(&self) -> HeaderTypeError
+        where H: HeaderKind
+    {
+        self.decompress_error()
+            .unwrap_or_else(|| HeaderTypeError::new(H::name()))
+    }
+
+    /// Returns the error restoring the value, if this is a `CompressedRaw` header which can not be decompressed.
+    #[cfg(feature="compress-values")]
+    fn decompress_error(&self) -> Option<HeaderTypeError> {
+        ::map::CompressedRaw::from_obj(self)
+            .and_then(|compressed| compressed.decompressed().err())
+            .map(HeaderTypeError::from_decompress_error)
+    }
+
+    #[cfg(not(feature="compress-values"))]
+    fn decompress_error(&self) -> Option<HeaderTypeError> {
+        None
+    }
+
+    /// Returns the decompressed header, if this is a `CompressedRaw` header which can be decompressed.
+    #[cfg(feature="compress-values")]
+    fn decompressed(&self) -> Option<&HeaderObj> {
+        ::map::CompressedRaw::from_obj(self)
+            .and_then(|compressed| compressed.decompressed().ok())
+    }
+
+    #[cfg(not(feature="compress-values"))]
+    fn decompressed(&self) -> Option<&HeaderObj> {
+        None
+    }
+
+    /// Like `decompressed`, marking the compressed header as modified.
+    #[cfg(feature="compress-values")]
+    fn decompressed_mut(&mut self) -> Option<&mut HeaderObj> {
+        ::map::CompressedRaw::from_obj_mut(self)
+            .and_then(|compressed| compressed.decompressed_mut().ok())
+    }
+
+    #[cfg(not(feature="compress-values"))]
+    fn decompressed_mut(&mut self) -> Option<&mut HeaderObj> {
+        None
     }
 }
 
@@ -243,11 +544,153 @@ impl HeaderObjTraitBoxExt for Box<HeaderObjTrait> {
     fn downcast<H>(self) -> Result<Box<Header<H>>, Self>
         where H: HeaderKind
     {
-        if HeaderObjTrait::is::<H>(&*self) {
+        if HeaderObjTrait::is_exactly::<H>(&*self) {
             let ptr: *mut (HeaderObj) = Box::into_raw(self);
             Ok(unsafe { Box::from_raw(ptr as *mut Header<H>) })
+        } else if HeaderObjTrait::is::<H>(&*self) {
+            into_decompressed(self).downcast::<H>()
         } else {
             Err(self)
         }
     }
-}
\ No newline at end of file
+}
+
+/// Replaces a compressed header with its decompressed header (if it can be decompressed).
+#[cfg(feature="compress-values")]
+fn into_decompressed(obj: Box<HeaderObj>) -> Box<HeaderObj> {
+    ::map::CompressedRaw::into_decompressed(obj)
+}
+
+#[cfg(not(feature="compress-values"))]
+fn into_decompressed(obj: Box<HeaderObj>) -> Box<HeaderObj> {
+    obj
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use internals::MailType;
+    use ::{HeaderMap, HeaderName, HeaderTryFrom};
+    use ::header_components::Unstructured;
+    use ::headers::{Subject, _To};
+    use ::map::HeaderMapValidator;
+    use super::*;
+
+    #[derive(Default, Copy, Clone)]
+    struct XUncached;
+
+    impl HeaderKind for XUncached {
+        type Component = Unstructured;
+
+        fn name() -> HeaderName {
+            HeaderName::from_ascii_unchecked("X-Uncached")
+        }
+
+        const VALIDATOR: Option<HeaderMapValidator> = None;
+        const MAX_ONE: bool = true;
+        const CACHE_ENCODED_VALUE: bool = false;
+    }
+
+    fn is_borrowed(value: &Cow<[u8]>) -> bool {
+        match *value {
+            Cow::Borrowed(_) => true,
+            Cow::Owned(_) => false
+        }
+    }
+
+    test!(value_is_the_encoded_line_without_name {
+        let mut map = HeaderMap::new();
+        map.insert(_To::auto_body(["a@example.com", "b@example.com"])?);
+        map.insert(Subject::auto_body("hy there")?);
+
+        let text = map.display(MailType::Ascii).to_string();
+        for (name, obj) in map.iter() {
+            let value = obj.encoded_value(MailType::Ascii)?;
+            let line = format!("{}: {}\r\n", name, String::from_utf8(value.into_owned())?);
+            assert!(text.contains(&line), "{:?} not in {:?}", line, text);
+        }
+    });
+
+    test!(value_is_cached_for_the_first_mail_type {
+        let header = Subject::auto_body("hö")?;
+        let first = header.encoded_value(MailType::Internationalized)?;
+        let second = header.encoded_value(MailType::Internationalized)?;
+        assert!(is_borrowed(&second));
+        assert_eq!(first, second);
+        assert_eq!(&*first, "hö".as_bytes());
+
+        // other mail types are encoded each time
+        let ascii = header.encoded_value(MailType::Ascii)?;
+        assert!(!is_borrowed(&ascii));
+        assert_ne!(ascii, first);
+        assert!(!is_borrowed(&header.encoded_value(MailType::Ascii)?));
+        assert!(is_borrowed(&header.encoded_value(MailType::Internationalized)?));
+    });
+
+    test!(mutation_clears_the_cache {
+        let mut map = HeaderMap::new();
+        map.insert(Subject::auto_body("old")?);
+        {
+            let (_, obj) = map.iter().next().unwrap();
+            assert_eq!(&*obj.encoded_value(MailType::Ascii)?, b"old");
+        }
+
+        *map.get_single_mut(Subject).unwrap()?.body_mut() = Unstructured::try_from("new")?;
+        {
+            let (_, obj) = map.iter().next().unwrap();
+            assert_eq!(&*obj.encoded_value(MailType::Ascii)?, b"new");
+        }
+
+        let mut header = Subject::auto_body("old")?;
+        header.encoded_value(MailType::Ascii)?;
+        *header = Unstructured::try_from("through deref")?;
+        assert_eq!(&*header.encoded_value(MailType::Ascii)?, b"through deref");
+    });
+
+    test!(clones_share_the_body_until_mutated {
+        let header = Subject::auto_body("shared")?;
+        header.encoded_value(MailType::Ascii)?;
+        let mut clone = header.clone();
+        assert!(clone.shares_body_with(&header));
+        assert!(is_borrowed(&clone.encoded_value(MailType::Ascii)?));
+
+        *clone.body_mut() = Unstructured::try_from("changed")?;
+        assert!(!clone.shares_body_with(&header));
+        assert_eq!(header.as_str(), "shared");
+        assert_eq!(&*header.encoded_value(MailType::Ascii)?, b"shared");
+        assert_eq!(&*clone.encoded_value(MailType::Ascii)?, b"changed");
+
+        // a body which is not shared is not cloned
+        let before = clone.body() as *const Unstructured;
+        clone.body_mut();
+        assert_eq!(clone.body() as *const Unstructured, before);
+    });
+
+    test!(encoding_does_not_fill_the_cache {
+        let mut map = HeaderMap::new();
+        map.insert(Subject::auto_body("a subject with a lot of words so that it needs to be folded to not exceed the line limit")?);
+        let encoded = map.encode_checked(MailType::Ascii)?;
+        {
+            let subject = map.get_single(Subject).unwrap()?;
+            assert!(subject.cache.value.get().is_none());
+            // the same bytes are written as if the body would have been written directly
+            let mut expected = b"Subject: ".to_vec();
+            expected.extend(uncached_value(subject, MailType::Ascii)?);
+            expected.extend(b"\r\n");
+            assert_eq!(encoded, expected);
+            assert!(expected.windows(3).any(|window| window == b"\r\n "));
+            assert!(subject.encoded_value(MailType::Ascii).is_ok());
+        }
+        // the cached value is written as it is
+        assert_eq!(map.encode_checked(MailType::Ascii)?, encoded);
+        assert_eq!(map.encode_checked(MailType::Internationalized)?, encoded);
+    });
+
+    test!(caching_can_be_disabled {
+        let header = XUncached::auto_body("text")?;
+        let value = header.encoded_value(MailType::Ascii)?;
+        assert!(!is_borrowed(&value));
+        assert_eq!(&*value, b"text");
+    });
+}
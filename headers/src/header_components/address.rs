@@ -0,0 +1,351 @@
+use std::slice;
+use std::fmt::{self, Display};
+
+use vec1::Vec1;
+use soft_ascii::SoftAsciiChar;
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodableInHeader, EncodingWriter};
+use ::{HeaderTryFrom, HeaderTryInto};
+use ::error::ComponentCreationError;
+use ::mail_type::{HasMailTypeRequirement, MailTypeRequirement};
+
+use super::{Mailbox, MailboxList, Phrase};
+
+/// A address in a address list, i.e. a single mailbox or a group (rfc5322).
+///
+/// A group is a named (possible empty) list of mailboxes, e.g.
+/// `Team: a@x.test, b@x.test;` or `undisclosed-recipients:;`.
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+pub enum Address {
+    Mailbox(Mailbox),
+    Group {
+        display_name: Phrase,
+        mailboxes: Vec<Mailbox>
+    }
+}
+
+impl Address {
+
+    /// Creates a group with given display name and mailboxes.
+    pub fn group<N>(display_name: N, mailboxes: Vec<Mailbox>) -> Result<Self, ComponentCreationError>
+        where N: HeaderTryInto<Phrase>
+    {
+        let display_name = display_name.try_into()?;
+        Ok(Address::Group { display_name, mailboxes })
+    }
+
+    /// Creates a group without mailboxes, e.g. `undisclosed-recipients:;`.
+    pub fn empty_group<N>(display_name: N) -> Result<Self, ComponentCreationError>
+        where N: HeaderTryInto<Phrase>
+    {
+        Address::group(display_name, Vec::new())
+    }
+
+    pub fn is_group(&self) -> bool {
+        match *self {
+            Address::Mailbox(_) => false,
+            Address::Group { .. } => true
+        }
+    }
+
+    /// Returns the mailbox or the mailboxes of the group.
+    pub fn mailboxes(&self) -> &[Mailbox] {
+        match *self {
+            Address::Mailbox(ref mailbox) => slice::from_ref(mailbox),
+            Address::Group { ref mailboxes, .. } => mailboxes
+        }
+    }
+}
+
+impl From<Mailbox> for Address {
+    fn from(mailbox: Mailbox) -> Self {
+        Address::Mailbox(mailbox)
+    }
+}
+
+impl HeaderTryFrom<Mailbox> for Address {
+    fn try_from(mailbox: Mailbox) -> Result<Self, ComponentCreationError> {
+        Ok(Address::Mailbox(mailbox))
+    }
+}
+
+/// Formats the mailbox or `name: mailbox, ...;` for a group, see `Mailbox`s `Display` impl.
+impl Display for Address {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Address::Mailbox(ref mailbox) => write!(fter, "{}", mailbox),
+            Address::Group { ref display_name, ref mailboxes } => {
+                write!(fter, "{}:", display_name)?;
+                for (idx, mailbox) in mailboxes.iter().enumerate() {
+                    let sep = if idx == 0 { " " } else { ", " };
+                    write!(fter, "{}{}", sep, mailbox)?;
+                }
+                fter.write_str(";")
+            }
+        }
+    }
+}
+
+impl HasMailTypeRequirement for Address {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        let name = match *self {
+            Address::Mailbox(_) => MailTypeRequirement::ascii(),
+            Address::Group { ref display_name, .. } => display_name.mail_type_requirement()
+        };
+        self.mailboxes().iter()
+            .map(|mailbox| mailbox.mail_type_requirement())
+            .fold(name, MailTypeRequirement::combine)
+    }
+}
+
+impl EncodableInHeader for Address {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        match *self {
+            Address::Mailbox(ref mailbox) => mailbox.encode(handle),
+            Address::Group { ref display_name, ref mailboxes } => {
+                display_name.encode(handle)?;
+                handle.write_char(SoftAsciiChar::from_unchecked(':'))?;
+                sep_for!{ mailbox in mailboxes.iter();
+                    sep {
+                        handle.write_char(SoftAsciiChar::from_unchecked(','))?;
+                    };
+                    handle.write_fws();
+                    mailbox.encode(handle)?;
+                }
+                handle.write_char(SoftAsciiChar::from_unchecked(';'))
+            }
+        }
+    }
+
+    fn boxed_clone(&self) -> Box<EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+/// A non empty list of addresses (`address-list` in rfc5322).
+///
+/// The built-in `_To`, `Cc` and `Bcc` headers use `MailboxList`, which can
+/// not represent groups. Where groups are needed a header with a
+/// `AddressList` body can be defined under the same name, existing lists
+/// can be converted with `From<MailboxList>`:
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate mail_headers;
+/// use mail_headers::header_components as components;
+/// use mail_headers::header_components::{Address, AddressList, MailboxList};
+///
+/// def_headers! {
+///     test_name: validate_header_names,
+///     scope: components,
+///     mail_type_requirement: component,
+///     GroupTo, unchecked { "To" }, AddressList, maxOne, None
+/// }
+///
+/// # fn main() {
+/// let list = MailboxList::parse("a@x.test").unwrap();
+/// let mut addresses = AddressList::from(list);
+/// addresses.push(Address::empty_group("undisclosed-recipients").unwrap());
+/// assert_eq!(addresses.to_string(), "a@x.test, undisclosed-recipients:;");
+///
+/// let map = headers! {
+///     GroupTo: addresses
+/// }.unwrap();
+/// # let _ = map;
+/// # }
+/// ```
+///
+/// As both use the same header name such a header should not be mixed
+/// with the built-in one in the same `HeaderMap` (accessing it with the
+/// other header kind fails with a type error).
+#[derive(Debug, Hash, Eq, PartialEq, Clone)]
+pub struct AddressList( pub Vec1<Address> );
+
+impl AddressList {
+
+    pub fn from_single<A: Into<Address>>(address: A) -> Self {
+        AddressList(Vec1::new(address.into()))
+    }
+
+    /// Returns all mailboxes in the list, including the ones in groups.
+    pub fn mailboxes(&self) -> Vec<&Mailbox> {
+        self.0.iter()
+            .flat_map(|address| address.mailboxes())
+            .collect()
+    }
+}
+
+impl From<MailboxList> for AddressList {
+    fn from(list: MailboxList) -> Self {
+        let addresses = list.into_iter().map(Address::Mailbox).collect::<Vec<_>>();
+        //UNWRAP_SAFE: a MailboxList is never empty
+        AddressList(Vec1::from_vec(addresses).unwrap())
+    }
+}
+
+impl HeaderTryFrom<MailboxList> for AddressList {
+    fn try_from(list: MailboxList) -> Result<Self, ComponentCreationError> {
+        Ok(AddressList::from(list))
+    }
+}
+
+impl HeaderTryFrom<Vec<Address>> for AddressList {
+    fn try_from(vec: Vec<Address>) -> Result<Self, ComponentCreationError> {
+        Vec1::from_vec(vec)
+            .map(AddressList)
+            .map_err(|_| ComponentCreationError::new("AddressList"))
+    }
+}
+
+/// Formats all addresses comma separated, see `Address`s `Display` impl.
+impl Display for AddressList {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, address) in self.0.iter().enumerate() {
+            if idx > 0 {
+                fter.write_str(", ")?;
+            }
+            write!(fter, "{}", address)?;
+        }
+        Ok(())
+    }
+}
+
+impl HasMailTypeRequirement for AddressList {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        self.0.iter()
+            .map(|address| address.mail_type_requirement())
+            .fold(MailTypeRequirement::ascii(), MailTypeRequirement::combine)
+    }
+}
+
+impl EncodableInHeader for AddressList {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        sep_for!{ address in self.0.iter();
+            sep {
+                handle.write_char(SoftAsciiChar::from_unchecked(','))?;
+                handle.write_fws();
+            };
+            address.encode(handle)?;
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+deref0!{ +mut AddressList => Vec1<Address> }
+
+#[cfg(test)]
+mod test {
+    use internals::MailType;
+    use internals::encoder::EncodingBuffer;
+    use ::header_components::Email;
+    use super::*;
+
+    def_headers! {
+        test_name: validate_header_names,
+        scope: self,
+        mail_type_requirement: component,
+        GroupTo, unchecked { "To" }, AddressList, maxOne, None
+    }
+
+    fn encode(body: &EncodableInHeader) -> String {
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        {
+            let mut handle = encoder.writer();
+            body.encode(&mut handle).unwrap();
+            handle.finish_header();
+        }
+        let encoded = String::from_utf8(encoder.as_slice().to_owned()).unwrap();
+        encoded.replace("\r\n", "")
+    }
+
+    fn mailbox(email: &str) -> Mailbox {
+        Mailbox::from(Email::try_from(email).unwrap())
+    }
+
+    #[test]
+    fn encode_empty_group() {
+        let group = assert_ok!(Address::empty_group("undisclosed-recipients"));
+        assert_eq!(encode(&group), "undisclosed-recipients:;");
+        assert_eq!(encode(&AddressList::from_single(group)), "undisclosed-recipients:;");
+    }
+
+    #[test]
+    fn encode_group() {
+        let group = assert_ok!(Address::group("Team", vec![mailbox("a@x.test"), mailbox("b@x.test")]));
+        assert_eq!(encode(&group), "Team: <a@x.test>, <b@x.test>;");
+        assert_eq!(group.to_string(), "Team: a@x.test, b@x.test;");
+    }
+
+    #[test]
+    fn encode_groups_mixed_with_mailboxes() {
+        let list = assert_ok!(AddressList::try_from(vec![
+            Address::from(assert_ok!(Mailbox::parse("Jane <jane@example.com>"))),
+            assert_ok!(Address::group("Team", vec![mailbox("a@x.test"), mailbox("b@x.test")])),
+            assert_ok!(Address::empty_group("undisclosed-recipients")),
+            Address::from(mailbox("c@x.test"))
+        ]));
+
+        assert_eq!(
+            encode(&list),
+            "Jane <jane@example.com>, Team: <a@x.test>, <b@x.test>;, \
+             undisclosed-recipients:;, <c@x.test>"
+        );
+        assert_eq!(
+            list.to_string(),
+            "Jane <jane@example.com>, Team: a@x.test, b@x.test;, \
+             undisclosed-recipients:;, c@x.test"
+        );
+
+        let emails = list.mailboxes().into_iter()
+            .map(|mailbox| mailbox.email.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(emails, vec!["jane@example.com", "a@x.test", "b@x.test", "c@x.test"]);
+        assert_eq!(
+            list.iter().map(Address::is_group).collect::<Vec<_>>(),
+            vec![false, true, true, false]
+        );
+    }
+
+    #[test]
+    fn from_mailbox_list() {
+        let mailboxes = assert_ok!(MailboxList::parse("Jane <jane@example.com>, b@x.test"));
+        let list = AddressList::from(mailboxes.clone());
+        assert_eq!(list.len(), 2);
+        assert!(list.iter().all(|address| !address.is_group()));
+        assert_eq!(list.to_string(), mailboxes.to_string());
+        assert_eq!(encode(&list), encode(&mailboxes));
+    }
+
+    #[test]
+    fn empty_list_is_an_error() {
+        assert_err!(AddressList::try_from(Vec::<Address>::new()));
+    }
+
+    #[test]
+    fn mail_type_requirement_includes_group_name() {
+        let group = assert_ok!(Address::empty_group("Gäste"));
+        assert_eq!(group.mail_type_requirement(), Phrase::try_from("Gäste").unwrap().mail_type_requirement());
+        let group = assert_ok!(Address::empty_group("Team"));
+        assert_eq!(group.mail_type_requirement(), MailTypeRequirement::ascii());
+    }
+
+    test!(group_in_header_map {
+        let list = AddressList::try_from(vec![
+            Address::from(mailbox("a@x.test")),
+            Address::empty_group("undisclosed-recipients")?
+        ])?;
+        let map = headers! {
+            GroupTo: list
+        }?;
+
+        let encoded = map.encode_checked(MailType::Ascii)?;
+        assert_eq!(String::from_utf8(encoded)?, "To: <a@x.test>, undisclosed-recipients:;\r\n");
+    });
+}
@@ -0,0 +1,230 @@
+use std::collections::BTreeSet;
+use std::collections::btree_set;
+
+use soft_ascii::{SoftAsciiStr, SoftAsciiChar};
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodingWriter, EncodableInHeader};
+use ::HeaderTryFrom;
+use ::error::ComponentCreationError;
+use ::mail_type::HasMailTypeRequirement;
+
+/// A single token of the `X-Auto-Response-Suppress` header.
+///
+/// The order of the variants is the order in which they are encoded.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum AutoResponseSuppressToken {
+    /// suppress all auto responses
+    All,
+    /// suppress delivery reports
+    DR,
+    /// suppress non-delivery reports
+    NDR,
+    /// suppress read notifications
+    RN,
+    /// suppress not read notifications
+    NRN,
+    /// suppress out of office notifications
+    OOF,
+    /// suppress auto replies other than out of office notifications
+    AutoReply
+}
+
+impl AutoResponseSuppressToken {
+
+    /// Returns the canonical representation of the token.
+    pub fn as_str(&self) -> &'static str {
+        use self::AutoResponseSuppressToken::*;
+        match *self {
+            All => "All",
+            DR => "DR",
+            NDR => "NDR",
+            RN => "RN",
+            NRN => "NRN",
+            OOF => "OOF",
+            AutoReply => "AutoReply"
+        }
+    }
+
+    fn parse(token: &str) -> Option<Self> {
+        use self::AutoResponseSuppressToken::*;
+        [All, DR, NDR, RN, NRN, OOF, AutoReply].iter()
+            .find(|candidate| candidate.as_str().eq_ignore_ascii_case(token))
+            .cloned()
+    }
+}
+
+/// The component of the (Exchange specific) `X-Auto-Response-Suppress` header.
+///
+/// This is a set of `AutoResponseSuppressToken`s. The `None` token is not
+/// part of the set, instead it is represented by an empty set (and an empty
+/// set is encoded as `None`).
+///
+/// As `All` implies all other tokens, inserting `All` replaces all other
+/// tokens in the set and inserting any other token into a set containing
+/// `All` does nothing.
+///
+/// Tokens are parsed case-insensitive, duplicates are removed and they are
+/// always encoded in the same (the declaration) order.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct AutoResponseSuppress {
+    tokens: BTreeSet<AutoResponseSuppressToken>
+}
+
+impl AutoResponseSuppress {
+
+    /// Creates a empty set, i.e. the equivalent of `None`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Parses a comma separated list of tokens.
+    pub fn parse(value: &str) -> Result<Self, ComponentCreationError> {
+        let mut set = AutoResponseSuppress::new();
+        for raw_token in value.split(',') {
+            let raw_token = raw_token.trim();
+            if raw_token.eq_ignore_ascii_case("None") {
+                continue;
+            }
+            let token = AutoResponseSuppressToken::parse(raw_token)
+                .ok_or_else(|| {
+                    ComponentCreationError::new_with_str("AutoResponseSuppress", value)
+                })?;
+            set.insert(token);
+        }
+        Ok(set)
+    }
+
+    /// Returns true if given token is in the set or implied by `All`.
+    pub fn contains(&self, token: AutoResponseSuppressToken) -> bool {
+        self.tokens.contains(&token)
+            || self.tokens.contains(&AutoResponseSuppressToken::All)
+    }
+
+    /// Inserts given token, returns true if the set changed.
+    pub fn insert(&mut self, token: AutoResponseSuppressToken) -> bool {
+        if self.contains(token) {
+            return false;
+        }
+        if token == AutoResponseSuppressToken::All {
+            self.tokens.clear();
+        }
+        self.tokens.insert(token)
+    }
+
+    /// Removes given token, returns true if it was in the set.
+    ///
+    /// Note that removing e.g. `OOF` from a set containing `All` will not
+    /// do anything, as `All` is not split up into its implied tokens.
+    pub fn remove(&mut self, token: AutoResponseSuppressToken) -> bool {
+        self.tokens.remove(&token)
+    }
+
+    /// Returns true if no token is in the set (i.e. `None`).
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Iterates over all tokens in the set in encoding order.
+    pub fn iter(&self) -> btree_set::Iter<AutoResponseSuppressToken> {
+        self.tokens.iter()
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for AutoResponseSuppress {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        AutoResponseSuppress::parse(value)
+    }
+}
+
+impl HeaderTryFrom<String> for AutoResponseSuppress {
+    fn try_from(value: String) -> Result<Self, ComponentCreationError> {
+        AutoResponseSuppress::parse(&value)
+    }
+}
+
+impl HeaderTryFrom<AutoResponseSuppressToken> for AutoResponseSuppress {
+    fn try_from(token: AutoResponseSuppressToken) -> Result<Self, ComponentCreationError> {
+        let mut set = AutoResponseSuppress::new();
+        set.insert(token);
+        Ok(set)
+    }
+}
+
+impl HasMailTypeRequirement for AutoResponseSuppress {}
+
+impl EncodableInHeader for AutoResponseSuppress {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        if self.is_empty() {
+            handle.write_str(SoftAsciiStr::from_unchecked("None"))?;
+            return Ok(());
+        }
+        sep_for!{ token in self.iter();
+            sep {
+                handle.write_char(SoftAsciiChar::from_unchecked(','))?;
+                handle.write_fws();
+            };
+            handle.write_str(SoftAsciiStr::from_unchecked(token.as_str()))?;
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::AutoResponseSuppressToken::*;
+
+    #[test]
+    fn parse_mixed_case_with_duplicates() {
+        let set = assert_ok!(AutoResponseSuppress::parse("autoreply, OOF,oof , AutoReply,dr"));
+        assert_eq!(set.iter().cloned().collect::<Vec<_>>(), vec![DR, OOF, AutoReply]);
+    }
+
+    #[test]
+    fn parse_none() {
+        let set = assert_ok!(AutoResponseSuppress::parse("None"));
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn parse_unknown_token_fails() {
+        assert_err!(AutoResponseSuppress::parse("OOF, Vacation"));
+        assert_err!(AutoResponseSuppress::parse("OOF,,DR"));
+    }
+
+    #[test]
+    fn all_implies_other_tokens() {
+        let mut set = assert_ok!(AutoResponseSuppress::parse("OOF, DR"));
+        assert!(set.insert(All));
+        assert_eq!(set.iter().cloned().collect::<Vec<_>>(), vec![All]);
+        assert!(set.contains(NRN));
+        assert!(!set.insert(AutoReply));
+        assert_eq!(set.iter().cloned().collect::<Vec<_>>(), vec![All]);
+    }
+
+    #[test]
+    fn all_in_parsed_list_collapses() {
+        let set = assert_ok!(AutoResponseSuppress::parse("OOF, all, RN"));
+        assert_eq!(set.iter().cloned().collect::<Vec<_>>(), vec![All]);
+    }
+
+    ec_test!{ encode_in_stable_order, {
+        AutoResponseSuppress::try_from("AutoReply, OOF")?
+    } => ascii => [
+        Text "OOF,",
+        MarkFWS,
+        Text " AutoReply"
+    ]}
+
+    ec_test!{ encode_empty_as_none, {
+        AutoResponseSuppress::new()
+    } => ascii => [
+        Text "None"
+    ]}
+}
@@ -1,5 +1,6 @@
 use internals::error::EncodingError;
 use internals::encoder::{EncodableInHeader, EncodingWriter};
+use ::mail_type::HasMailTypeRequirement;
 
 //FEATURE_TODO(fws_controll): allow controlling the amount of WS and if a CRLF should be used in FWS
 //  this is also usefull for parsing and keeping information about FWS structure
@@ -33,6 +34,8 @@ pub enum CFWS {
 }
 
 
+impl HasMailTypeRequirement for CFWS {}
+
 impl EncodableInHeader for CFWS {
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
         match *self {
@@ -0,0 +1,406 @@
+//! Detection of confusable (lookalike) email addresses, see `Email::confusable_with`.
+use std::char;
+
+use super::email::{Email, Domain};
+
+/// Lowercase lookalikes of latin letters (sorted by code point).
+///
+/// This only covers the most common Cyrillic, Greek and Latin extended
+/// lookalikes, it is not the full Unicode confusables table (UTS #39).
+/// As chars are lowercased before they are looked up, uppercase chars
+/// are covered through their lowercase form (e.g. Cyrillic `А`).
+const CONFUSABLES: &[(char, char)] = &[
+    ('\u{0131}', 'i'), // LATIN SMALL LETTER DOTLESS I
+    ('\u{0251}', 'a'), // LATIN SMALL LETTER ALPHA
+    ('\u{0261}', 'g'), // LATIN SMALL LETTER SCRIPT G
+    ('\u{03B1}', 'a'), // GREEK SMALL LETTER ALPHA
+    ('\u{03B9}', 'i'), // GREEK SMALL LETTER IOTA
+    ('\u{03BA}', 'k'), // GREEK SMALL LETTER KAPPA
+    ('\u{03BD}', 'v'), // GREEK SMALL LETTER NU
+    ('\u{03BF}', 'o'), // GREEK SMALL LETTER OMICRON
+    ('\u{03C1}', 'p'), // GREEK SMALL LETTER RHO
+    ('\u{03C5}', 'u'), // GREEK SMALL LETTER UPSILON
+    ('\u{03C7}', 'x'), // GREEK SMALL LETTER CHI
+    ('\u{03F2}', 'c'), // GREEK LUNATE SIGMA SYMBOL
+    ('\u{03F3}', 'j'), // GREEK LETTER YOT
+    ('\u{0430}', 'a'), // CYRILLIC SMALL LETTER A
+    ('\u{0435}', 'e'), // CYRILLIC SMALL LETTER IE
+    ('\u{043E}', 'o'), // CYRILLIC SMALL LETTER O
+    ('\u{0440}', 'p'), // CYRILLIC SMALL LETTER ER
+    ('\u{0441}', 'c'), // CYRILLIC SMALL LETTER ES
+    ('\u{0443}', 'y'), // CYRILLIC SMALL LETTER U
+    ('\u{0445}', 'x'), // CYRILLIC SMALL LETTER HA
+    ('\u{0455}', 's'), // CYRILLIC SMALL LETTER DZE
+    ('\u{0456}', 'i'), // CYRILLIC SMALL LETTER BYELORUSSIAN-UKRAINIAN I
+    ('\u{0458}', 'j'), // CYRILLIC SMALL LETTER JE
+    ('\u{04BB}', 'h'), // CYRILLIC SMALL LETTER SHHA
+    ('\u{04CF}', 'l'), // CYRILLIC SMALL LETTER PALOCHKA
+    ('\u{0501}', 'd'), // CYRILLIC SMALL LETTER KOMI DE
+    ('\u{051B}', 'q'), // CYRILLIC SMALL LETTER QA
+    ('\u{051D}', 'w'), // CYRILLIC SMALL LETTER WE
+    ('\u{2113}', 'l'), // SCRIPT SMALL L
+];
+
+/// How likely it is that an address is meant to be mistaken for another one.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum ConfusableSeverity {
+    /// Nothing was found.
+    None,
+    /// Chars which look alike in many fonts were substituted (e.g. `0` for `o`).
+    Medium,
+    /// The addresses only differ in lookalike chars (e.g. a Cyrillic `а`).
+    High
+}
+
+/// The part of an address a finding is about.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum AddressPart {
+    LocalPart,
+    Domain
+}
+
+/// A digit/letter substitution which is hard to spot in many fonts.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Substitution {
+    /// `0` and `o`/`O`
+    ZeroO,
+    /// `1`, `l` and `I`
+    OneL,
+    /// `rn` and `m`
+    RnM
+}
+
+impl Substitution {
+
+    const ALL: [Substitution; 3] = [Substitution::ZeroO, Substitution::OneL, Substitution::RnM];
+
+    /// Returns a short description of the substitution, e.g. `"0/o"`.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Substitution::ZeroO => "0/o",
+            Substitution::OneL => "1/l/I",
+            Substitution::RnM => "rn/m"
+        }
+    }
+}
+
+/// A single finding of `Email::confusable_with`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ConfusableFinding {
+    /// The part differs but has the same skeleton, i.e. it uses lookalike chars.
+    Homoglyph(AddressPart),
+    /// The part only differs in the given substitution.
+    Substitution(AddressPart, Substitution),
+    /// One of the lookalike domains is written in punycode, hiding the lookalike chars.
+    PunycodeMismatch
+}
+
+impl ConfusableFinding {
+
+    /// Returns the severity of this finding.
+    pub fn severity(&self) -> ConfusableSeverity {
+        match *self {
+            ConfusableFinding::Homoglyph(..) => ConfusableSeverity::High,
+            ConfusableFinding::Substitution(..) => ConfusableSeverity::Medium,
+            ConfusableFinding::PunycodeMismatch => ConfusableSeverity::High
+        }
+    }
+}
+
+/// The result of `Email::confusable_with`.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct ConfusableReport {
+    findings: Vec<ConfusableFinding>
+}
+
+impl ConfusableReport {
+
+    /// Returns all findings, findings about the domain come first.
+    pub fn findings(&self) -> &[ConfusableFinding] {
+        &self.findings
+    }
+
+    /// Returns the highest severity of all findings (`None` if there are none).
+    pub fn severity(&self) -> ConfusableSeverity {
+        self.findings.iter()
+            .map(|finding| finding.severity())
+            .max()
+            .unwrap_or(ConfusableSeverity::None)
+    }
+
+    /// Returns true if there is any finding.
+    pub fn is_confusable(&self) -> bool {
+        !self.findings.is_empty()
+    }
+}
+
+/// Implementation of `Email::confusable_with`.
+pub(crate) fn compare(email: &Email, other: &Email) -> ConfusableReport {
+    let mut report = ConfusableReport::default();
+    let local_part = email.local_part.as_str().to_lowercase();
+    let other_local_part = other.local_part.as_str().to_lowercase();
+
+    if normalized_domain(&email.domain) != normalized_domain(&other.domain) {
+        let skeleton = email.domain.skeleton();
+        let other_skeleton = other.domain.skeleton();
+        if !compare_part(AddressPart::Domain, &skeleton, &other_skeleton, &mut report) {
+            // completely different domains, the local part doesn't matter
+            return report;
+        }
+        let is_puny = is_punycode(&email.domain);
+        if is_puny != is_punycode(&other.domain) && skeleton == other_skeleton {
+            report.findings.push(ConfusableFinding::PunycodeMismatch);
+        }
+    }
+
+    if local_part != other_local_part {
+        let skeleton = skeleton_of(&local_part);
+        let other_skeleton = skeleton_of(&other_local_part);
+        compare_part(AddressPart::LocalPart, &skeleton, &other_skeleton, &mut report);
+    }
+    report
+}
+
+/// Adds the findings for two differing parts, returns false if they are not confusable.
+fn compare_part(part: AddressPart, skeleton: &str, other: &str, report: &mut ConfusableReport)
+    -> bool
+{
+    if skeleton == other {
+        report.findings.push(ConfusableFinding::Homoglyph(part));
+        return true;
+    }
+
+    if fold(skeleton, &Substitution::ALL) != fold(other, &Substitution::ALL) {
+        return false;
+    }
+
+    // report the substitutions without which the parts would differ
+    for &substitution in Substitution::ALL.iter() {
+        let others = Substitution::ALL.iter()
+            .cloned()
+            .filter(|&other_sub| other_sub != substitution)
+            .collect::<Vec<_>>();
+        if fold(skeleton, &others) != fold(other, &others) {
+            report.findings.push(ConfusableFinding::Substitution(part, substitution));
+        }
+    }
+    true
+}
+
+fn normalized_domain(domain: &Domain) -> String {
+    match domain.to_ascii_string() {
+        Ok(ascii) => ascii.as_str().to_lowercase(),
+        Err(_) => domain.as_str().to_lowercase()
+    }
+}
+
+fn is_punycode(domain: &Domain) -> bool {
+    domain.as_str().split('.').any(|label| label.to_lowercase().starts_with("xn--"))
+}
+
+/// Computes the skeleton of a domain, see `Domain::skeleton`.
+pub(crate) fn domain_skeleton(domain: &str) -> String {
+    domain.split('.')
+        .map(|label| {
+            let lower = label.to_lowercase();
+            if lower.starts_with("xn--") {
+                if let Some(decoded) = decode_punycode(&lower[4..]) {
+                    return skeleton_of(&decoded);
+                }
+            }
+            skeleton_of(&lower)
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Lowercases the text and replaces all lookalikes from the confusables table.
+fn skeleton_of(text: &str) -> String {
+    text.chars()
+        .flat_map(|ch| ch.to_lowercase())
+        .map(|ch| {
+            CONFUSABLES.binary_search_by_key(&ch, |&(from, _)| from)
+                .map(|idx| CONFUSABLES[idx].1)
+                .unwrap_or(ch)
+        })
+        .collect()
+}
+
+/// Applies the given substitutions to a (lowercase) skeleton.
+fn fold(skeleton: &str, substitutions: &[Substitution]) -> String {
+    let folded =
+        if substitutions.contains(&Substitution::RnM) {
+            skeleton.replace("rn", "m")
+        } else {
+            skeleton.to_owned()
+        };
+    let zero_o = substitutions.contains(&Substitution::ZeroO);
+    let one_l = substitutions.contains(&Substitution::OneL);
+    folded.chars()
+        .map(|ch| match ch {
+            '0' if zero_o => 'o',
+            '1' | 'i' if one_l => 'l',
+            ch => ch
+        })
+        .collect()
+}
+
+const BASE: u32 = 36;
+const T_MIN: u32 = 1;
+const T_MAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+
+/// Decodes a punycode label (without the `xn--` prefix), see rfc3492.
+fn decode_punycode(input: &str) -> Option<String> {
+    let (basic, extended) = match input.rfind('-') {
+        Some(idx) => (&input[..idx], &input[idx+1..]),
+        None => ("", input)
+    };
+    if !basic.is_ascii() {
+        return None;
+    }
+
+    let mut output = basic.chars().collect::<Vec<_>>();
+    let mut n = 128u32;
+    let mut i = 0u32;
+    let mut bias = 72u32;
+    let mut chars = extended.chars().peekable();
+    while chars.peek().is_some() {
+        let old_i = i;
+        let mut weight = 1u32;
+        let mut k = BASE;
+        loop {
+            let digit = digit_value(chars.next()?)?;
+            i = i.checked_add(digit.checked_mul(weight)?)?;
+            let threshold =
+                if k <= bias { T_MIN }
+                else if k >= bias + T_MAX { T_MAX }
+                else { k - bias };
+            if digit < threshold {
+                break;
+            }
+            weight = weight.checked_mul(BASE - threshold)?;
+            k += BASE;
+        }
+        let len = output.len() as u32 + 1;
+        bias = adapt(i - old_i, len, old_i == 0);
+        n = n.checked_add(i / len)?;
+        i %= len;
+        output.insert(i as usize, char::from_u32(n)?);
+        i += 1;
+    }
+    Some(output.into_iter().collect())
+}
+
+fn digit_value(ch: char) -> Option<u32> {
+    match ch {
+        'a'...'z' => Some(ch as u32 - 'a' as u32),
+        'A'...'Z' => Some(ch as u32 - 'A' as u32),
+        '0'...'9' => Some(ch as u32 - '0' as u32 + 26),
+        _ => None
+    }
+}
+
+fn adapt(delta: u32, num_points: u32, first_time: bool) -> u32 {
+    let mut delta = if first_time { delta / DAMP } else { delta / 2 };
+    delta += delta / num_points;
+    let mut k = 0;
+    while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+        delta /= BASE - T_MIN;
+        k += BASE;
+    }
+    k + ((BASE - T_MIN + 1) * delta) / (delta + SKEW)
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderTryFrom;
+    use super::*;
+
+    fn report(email: &str, other: &str) -> ConfusableReport {
+        let email = Email::try_from(email).unwrap();
+        let other = Email::try_from(other).unwrap();
+        email.confusable_with(&other)
+    }
+
+    #[test]
+    fn confusables_table_is_sorted() {
+        assert!(CONFUSABLES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn decodes_punycode_labels() {
+        assert_eq!(decode_punycode("pypal-4ve").unwrap(), "p\u{0430}ypal");
+        assert_eq!(decode_punycode("bcher-kva").unwrap(), "bücher");
+        assert_eq!(decode_punycode("ggle-55da").unwrap(), "g\u{043E}\u{043E}gle");
+        assert!(decode_punycode("bcher-k!a").is_none());
+    }
+
+    #[test]
+    fn cyrillic_a_in_domain() {
+        let report = report("service@p\u{0430}ypal.com", "service@paypal.com");
+        assert_eq!(report.findings(), &[ConfusableFinding::Homoglyph(AddressPart::Domain)]);
+        assert_eq!(report.severity(), ConfusableSeverity::High);
+    }
+
+    #[test]
+    fn punycode_lookalike_domain() {
+        let report = report("service@xn--pypal-4ve.com", "service@paypal.com");
+        assert_eq!(report.findings(), &[
+            ConfusableFinding::Homoglyph(AddressPart::Domain),
+            ConfusableFinding::PunycodeMismatch
+        ]);
+    }
+
+    #[test]
+    fn rn_for_m_in_local_part() {
+        let report = report("rnartin@example.com", "martin@example.com");
+        assert_eq!(report.findings(), &[
+            ConfusableFinding::Substitution(AddressPart::LocalPart, Substitution::RnM)
+        ]);
+        assert_eq!(report.severity(), ConfusableSeverity::Medium);
+    }
+
+    #[test]
+    fn one_for_l_in_local_part() {
+        let report = report("paypa1@example.com", "paypal@example.com");
+        assert_eq!(report.findings(), &[
+            ConfusableFinding::Substitution(AddressPart::LocalPart, Substitution::OneL)
+        ]);
+    }
+
+    #[test]
+    fn zero_for_o_in_domain() {
+        let report = report("info@g00gle.com", "info@google.com");
+        assert_eq!(report.findings(), &[
+            ConfusableFinding::Substitution(AddressPart::Domain, Substitution::ZeroO)
+        ]);
+        assert_eq!(report.severity(), ConfusableSeverity::Medium);
+    }
+
+    #[test]
+    fn clean_non_match() {
+        let report = report("alice@example.com", "bob@example.org");
+        assert!(!report.is_confusable());
+        assert_eq!(report.severity(), ConfusableSeverity::None);
+
+        assert!(!report("alice@example.com", "bob@example.com").is_confusable());
+    }
+
+    #[test]
+    fn equal_normalized_addresses_are_not_confusable() {
+        assert!(!report("me@example.com", "me@example.com").is_confusable());
+        assert!(!report("Me@Example.COM", "me@example.com").is_confusable());
+        assert!(!report("me@bücher.example", "me@xn--bcher-kva.example").is_confusable());
+        assert!(!report("g\u{043E}\u{043E}gle@x.com", "g\u{043E}\u{043E}gle@x.com").is_confusable());
+    }
+
+    #[test]
+    fn domain_skeleton() {
+        let domain = Domain::try_from("P\u{0410}YPAL.com").unwrap();
+        assert_eq!(domain.skeleton(), "paypal.com");
+        let domain = Domain::try_from("xn--ggle-55da.com").unwrap();
+        assert_eq!(domain.skeleton(), "google.com");
+    }
+}
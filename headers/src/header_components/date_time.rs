@@ -1,11 +1,14 @@
+use std::fmt::{self, Display};
+
 use chrono;
-use soft_ascii_string::SoftAsciiString;
+use soft_ascii::SoftAsciiString;
 
 
 use internals::encoder::{EncodingWriter, EncodableInHeader};
 use internals::error::EncodingError;
 use ::HeaderTryFrom;
 use ::error::ComponentCreationError;
+use ::mail_type::HasMailTypeRequirement;
 
 #[cfg(feature="serde")]
 use serde::{Serialize, Deserialize};
@@ -38,6 +41,15 @@ impl DateTime {
     }
 }
 
+/// Formats the date like the wire form (rfc2822).
+impl Display for DateTime {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(&self.to_rfc2822())
+    }
+}
+
+impl HasMailTypeRequirement for DateTime {}
+
 impl EncodableInHeader for DateTime {
 
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
@@ -87,4 +99,8 @@ mod test {
         Text "Tue,  6 Aug 2013 04:11:45 +0000"
     ]}
 
-}
\ No newline at end of file
+    #[test]
+    fn display_is_rfc2822() {
+        assert_eq!(DateTime::test_time(45).to_string(), "Tue,  6 Aug 2013 04:11:45 +0000");
+    }
+}
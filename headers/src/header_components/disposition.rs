@@ -1,11 +1,11 @@
 use std::borrow::Cow;
-#[cfg(feature="serde")]
-use std::fmt;
+use std::fmt::{self, Display};
 
+use chrono::{self, Utc};
 use failure::Fail;
-use soft_ascii_string::SoftAsciiStr;
+use soft_ascii::SoftAsciiStr;
 use mime::push_params_to_buffer;
-use mime::spec::{MimeSpec, Ascii, Modern, Internationalized};
+use mime::spec::{MimeSpec, Ascii, Modern};
 
 #[cfg(feature="serde")]
 use serde::{
@@ -13,12 +13,16 @@ use serde::{
     Deserialize, Deserializer,
 };
 
+use internals::MailType;
 use internals::error::{EncodingError, EncodingErrorKind};
 use internals::encoder::{EncodableInHeader, EncodingWriter};
 use ::HeaderTryFrom;
 use ::error::ComponentCreationError;
+use ::mail_type::HasMailTypeRequirement;
+use ::params::quote_value;
 
 use super::FileMeta;
+use super::media_type_params::{scan_params, resolve};
 
 /// Disposition Component mainly used for the Content-Disposition header (rfc2183)
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -76,6 +80,38 @@ impl Disposition {
         &mut self.file_meta
     }
 
+    /// Parses a disposition including its parameters, e.g. `attachment; filename=a.png`.
+    ///
+    /// The parameters are resolved like the ones of `MediaType::parse`
+    /// (including rfc2231 encoded file names). Parameters not represented
+    /// by `FileMeta` are ignored.
+    pub fn parse( text: &str ) -> Result<Self, ComponentCreationError> {
+        let end = text.find(';').unwrap_or(text.len());
+        let mut disposition = Disposition::try_from( text[..end].trim() )?;
+
+        let invalid = |param: &str, value: &str| {
+            ComponentCreationError::new_with_str("Disposition", format!("{}={}", param, value))
+        };
+        let parse_date = |param: &str, value: &str| {
+            chrono::DateTime::parse_from_rfc2822(value.trim())
+                .map(|date| date.with_timezone(&Utc))
+                .map_err(|_| invalid(param, value))
+        };
+
+        for (name, value) in resolve(&scan_params(text)).params {
+            let meta = &mut disposition.file_meta.0;
+            match &*name.to_ascii_lowercase() {
+                "filename" => meta.file_name = Some(value),
+                "creation-date" => meta.creation_date = Some(parse_date(&name, &value)?),
+                "modification-date" => meta.modification_date = Some(parse_date(&name, &value)?),
+                "read-date" => meta.read_date = Some(parse_date(&name, &value)?),
+                "size" => meta.size = Some(value.trim().parse().map_err(|_| invalid(&name, &value))?),
+                _ => {}
+            }
+        }
+        Ok(disposition)
+    }
+
 }
 
 #[cfg(feature="serde")]
@@ -143,12 +179,10 @@ impl<'a> HeaderTryFrom<&'a str> for Disposition {
 }
 
 
-//TODO provide a gnneral way for encoding header parameter ...
-//  which follow the scheme: <mainvalue> *(";" <key>"="<value> )
-//  this are: ContentType and ContentDisposition for now
-impl EncodableInHeader for DispositionParameters {
+impl DispositionParameters {
 
-    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+    /// Returns the (unquoted) parameters in the order they are encoded in.
+    fn params(&self) -> Vec<(&'static str, Cow<str>)> {
         let mut params = Vec::<(&str, Cow<str>)>::new();
         if let Some(filename) = self.file_name.as_ref() {
             params.push(("filename", Cow::Borrowed(filename)));
@@ -165,30 +199,44 @@ impl EncodableInHeader for DispositionParameters {
         if let Some(size) = self.size.as_ref() {
             params.push(("size", Cow::Owned(size.to_string())));
         }
+        params
+    }
+}
+
+//TODO provide a gnneral way for encoding header parameter ...
+//  which follow the scheme: <mainvalue> *(";" <key>"="<value> )
+//  this are: ContentType and ContentDisposition for now
+impl EncodableInHeader for DispositionParameters {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        let params = self.params();
 
         //TODO instead do optCFWS ; spCFWS <name>=<value>
         // so that soft line brakes can be done
+        let mail_type = handle.mail_type();
         let mut buff = String::new();
-        let res =
-            if handle.mail_type().is_internationalized() {
-                push_params_to_buffer::<MimeSpec<Internationalized, Modern>, _, _, _>(
-                    &mut buff, params
-                )
-            } else {
-                push_params_to_buffer::<MimeSpec<Ascii, Modern>, _, _, _>(
-                    &mut buff, params
-                )
-            };
-
-        match res {
-            Err(err) => {
-                Err(err.context(EncodingErrorKind::Malformed).into())
-            },
-            Ok(_) => {
-                handle.write_str_unchecked(&*buff)?;
-                Ok(())
+        for (name, value) in params {
+            match quote_value(&value, mail_type) {
+                Ok(value) => {
+                    buff.push_str("; ");
+                    buff.push_str(name);
+                    buff.push('=');
+                    buff.push_str(&value);
+                },
+                // non us-ascii values in ascii mails need to be encoded (rfc2231)
+                Err(_) if !mail_type.is_internationalized() && !value.is_ascii() => {
+                    push_params_to_buffer::<MimeSpec<Ascii, Modern>, _, _, _>(
+                        &mut buff, Some((name, value))
+                    ).map_err(|err| err.context(EncodingErrorKind::Malformed))?;
+                },
+                Err(err) => {
+                    return Err(err.context(EncodingErrorKind::Malformed).into());
+                }
             }
         }
+
+        handle.write_str_unchecked(&*buff)?;
+        Ok(())
     }
 
     fn boxed_clone(&self) -> Box<EncodableInHeader> {
@@ -197,6 +245,30 @@ impl EncodableInHeader for DispositionParameters {
 }
 
 
+/// Formats the disposition like the wire form of a internationalized mail,
+/// e.g. `attachment; filename="a b.png"`.
+///
+/// Non us-ascii parameters are not encoded (rfc2231) and the output is never folded.
+impl Display for Disposition {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(match self.kind {
+            DispositionKind::Inline => "inline",
+            DispositionKind::Attachment => "attachment"
+        })?;
+        for (name, value) in self.file_meta.params() {
+            match quote_value(&value, MailType::Internationalized) {
+                Ok(quoted) => write!(fter, "; {}={}", name, quoted)?,
+                // only happens for values which can not be quoted at all
+                Err(_) => write!(fter, "; {}={:?}", name, value)?
+            }
+        }
+        Ok(())
+    }
+}
+
+// non us-ascii file names are encoded (rfc2231) in ascii mails
+impl HasMailTypeRequirement for Disposition {}
+
 impl EncodableInHeader for Disposition {
 
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
@@ -279,7 +351,25 @@ mod test {
     } => ascii => [
         Text "inline; filename=logo.png"
     ]}
-    //TODO: (1 allow FWS or so in parameters) (2 utf8 file names)
+
+    ec_test!{ file_name_with_quotes_and_backslashes, {
+        Disposition::new(DispositionKind::Attachment, FileMeta {
+            file_name: Some(r#"a "b"\c.png"#.to_owned()),
+            ..Default::default()
+        })
+    } => ascii => [
+        Text r#"attachment; filename="a \"b\"\\c.png""#
+    ]}
+
+    ec_test!{ utf8_file_name, {
+        Disposition::new(DispositionKind::Attachment, FileMeta {
+            file_name: Some("hälö.png".to_owned()),
+            ..Default::default()
+        })
+    } => utf8 => [
+        Text "attachment; filename=\"hälö.png\""
+    ]}
+    //TODO: allow FWS or so in parameters
 
     #[test]
     fn test_from_str() {
@@ -290,6 +380,30 @@ mod test {
         assert_err!( Disposition::try_from( "In line") );
     }
 
+    #[test]
+    fn parse_with_params() {
+        let disposition = assert_ok!(Disposition::parse(concat!(
+            "attachment; filename=\"a b.png\"",
+            "; creation-date=\"Tue,  6 Aug 2013 07:11:01 +0000\"",
+            "; size=4096; unknown=ignored"
+        )));
+        assert_eq!(disposition.kind(), DispositionKind::Attachment);
+        assert_eq!(disposition.file_meta(), &FileMeta {
+            file_name: Some("a b.png".to_owned()),
+            creation_date: Some(test_time(1)),
+            size: Some(4096),
+            ..Default::default()
+        });
+
+        let encoded = assert_ok!(Disposition::parse("inline; filename*=utf-8''h%C3%A4l%C3%B6.png"));
+        assert_eq!(encoded.file_meta().file_name.as_ref().unwrap(), "hälö.png");
+
+        assert_eq!(assert_ok!(Disposition::parse(" Inline ")), Disposition::inline());
+        assert_err!(Disposition::parse("attachment; size=big"));
+        assert_err!(Disposition::parse("attachment; read-date=yesterday"));
+        assert_err!(Disposition::parse("other; filename=a.png"));
+    }
+
     #[cfg(feature="serde")]
     fn assert_serialize<S: ::serde::Serialize>() {}
     #[cfg(feature="serde")]
@@ -305,4 +419,15 @@ mod test {
         assert_deserialize::<DispositionKind>();
         assert_deserialize::<DispositionParameters>();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn display() {
+        assert_eq!(Disposition::inline().to_string(), "inline");
+        let disposition = Disposition::new(DispositionKind::Attachment, FileMeta {
+            file_name: Some("hälö datei.png".to_owned()),
+            size: Some(4096),
+            ..Default::default()
+        });
+        assert_eq!(disposition.to_string(), "attachment; filename=\"hälö datei.png\"; size=4096");
+    }
+}
@@ -1,8 +1,14 @@
-use std::ops::Deref;
+// emails are parsed from untrusted input, slicing is done through
+// `str::get` so a wrong index surfaces as an error instead of a panic
+#![deny(clippy::indexing_slicing)]
+
+use std::ops::{Deref, Range};
 use std::borrow::Cow;
+use std::fmt::{self, Display};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 
 use failure::Fail;
-use soft_ascii_string::{SoftAsciiStr, SoftAsciiString, SoftAsciiChar};
+use soft_ascii::{SoftAsciiStr, SoftAsciiString, SoftAsciiChar};
 
 use mime::spec::{MimeSpec, Ascii, Internationalized, Modern};
 use quoted_string::quote_if_needed;
@@ -11,8 +17,6 @@ use internals::error::{EncodingError, EncodingErrorKind};
 use internals::grammar::{
     is_ascii,
     is_atext,
-    is_dtext,
-    is_ws,
 };
 use internals::MailType;
 use internals::encoder::{EncodingWriter, EncodableInHeader};
@@ -21,11 +25,17 @@ use internals::bind::quoted_string::UnquotedDotAtomTextValidator;
 
 use ::{HeaderTryFrom, HeaderTryInto};
 use ::data::{Input, SimpleItem, InnerUtf8 };
-use ::error::ComponentCreationError;
+use ::error::{ComponentCreationError, AddressViolation, DomainViolation, InvalidDomainName};
+use ::mail_type::{HasMailTypeRequirement, MailTypeRequirement};
+
+use super::confusable::{self, ConfusableReport};
 
 /// an email of the form `local-part@domain`
 /// corresponds to RFC5322 addr-spec, so `<`, `>` padding is _not_
 /// part of this Email type (but of the Mailbox type instead)
+///
+/// `Eq` and `Hash` compare the address as written, i.e. case sensitive,
+/// see `Email::eq_ignore_case` and `Email::to_lowercase`.
 #[derive(Debug,  Clone, Hash, PartialEq, Eq)]
 pub struct Email {
     pub local_part: LocalPart,
@@ -36,6 +46,13 @@ pub struct Email {
 #[derive(Debug,  Clone, Hash, PartialEq, Eq)]
 pub struct LocalPart( Input );
 
+/// The domain of an `Email`.
+///
+/// `Eq` and `Hash` compare the domain as written, i.e. `Example.com` and
+/// `example.com` are *not* equal. This keeps them consistent with `as_str`
+/// and the encoded form. Domains are case insensitive, so to e.g. use them
+/// as key of a `HashSet` use the key returned by `to_lowercase` and to
+/// compare two domains use `eq_ignore_case`.
 #[derive(Debug,  Clone, Hash, PartialEq, Eq)]
 pub struct Domain( SimpleItem );
 
@@ -45,6 +62,114 @@ impl Email {
         self.local_part.check_if_internationalized()
     }
 
+    /// Checks if this address could be mistaken for the other address.
+    ///
+    /// This is meant to detect addresses spoofing a known address, e.g.
+    /// `paypa1@…` or a domain using a Cyrillic `а`. It compares the
+    /// skeletons of the domains (see `Domain::skeleton`) and of the local
+    /// parts and checks for digit/letter substitutions (`0`/`o`, `1`/`l`/`I`
+    /// and `rn`/`m`). If one of two lookalike domains is written in
+    /// punycode this is reported, too.
+    ///
+    /// Addresses which are equal when normalized (i.e. ignoring case and
+    /// comparing domains in their punycode form) never have any findings,
+    /// neither do addresses with unrelated domains.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate mail_headers;
+    /// use mail_headers::HeaderTryFrom;
+    /// use mail_headers::header_components::{Email, ConfusableSeverity};
+    /// # fn main() {
+    ///
+    /// let known = Email::try_from("service@paypal.com").unwrap();
+    /// let spoof = Email::try_from("service@p\u{430}ypal.com").unwrap();
+    /// assert_eq!(spoof.confusable_with(&known).severity(), ConfusableSeverity::High);
+    /// # }
+    /// ```
+    pub fn confusable_with(&self, other: &Email) -> ConfusableReport {
+        confusable::compare(self, other)
+    }
+
+    /// Converts the local part and domain into shared data, making clones cheap.
+    pub fn into_shared(self) -> Self {
+        Email {
+            local_part: self.local_part.into_shared(),
+            domain: self.domain.into_shared()
+        }
+    }
+
+    /// Returns the us-ascii form of the address, e.g. for the SMTP envelope (`RCPT TO`).
+    ///
+    /// The local part is quoted if needed (see `LocalPart::to_ascii_string`)
+    /// and a internationalized domain is punycode encoded, like it is done
+    /// when encoding the address in a non internationalized mail.
+    ///
+    /// # Error
+    ///
+    /// Fails if the local part contains non us-ascii chars (such a address
+    /// can only be used if SMTPUTF8 is supported) or the domain can not be
+    /// punycode encoded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate mail_headers;
+    /// use mail_headers::HeaderTryFrom;
+    /// use mail_headers::header_components::Email;
+    /// # fn main() {
+    ///
+    /// let email = Email::try_from("user@dömain.de").unwrap();
+    /// assert_eq!(email.to_ascii_string().unwrap().as_str(), "user@xn--dmain-jua.de");
+    /// assert!(Email::try_from("jörn@dömain.de").unwrap().to_ascii_string().is_err());
+    /// # }
+    /// ```
+    pub fn to_ascii_string(&self) -> Result<SoftAsciiString, EncodingError> {
+        let mut ascii = self.local_part.to_ascii_string()?;
+        ascii.push(SoftAsciiChar::from_unchecked('@'));
+        ascii.push_str(&self.domain.to_ascii_string()?);
+        Ok(ascii)
+    }
+
+    /// Returns a copy of this address with the local part and domain in lowercase.
+    ///
+    /// Local parts are case sensitive by the standard but treated case
+    /// insensitive by nearly all providers, so this is the key to use when
+    /// de-duplicating addresses. To keep the local part as is use
+    /// `Domain::to_lowercase` on the domain only.
+    pub fn to_lowercase(&self) -> Email {
+        Email {
+            local_part: LocalPart(Input::from(self.local_part.as_str().to_lowercase())),
+            domain: self.domain.to_lowercase()
+        }
+    }
+
+    /// Returns true if both addresses are equal ignoring the case of the local part and domain.
+    ///
+    /// This is the same as comparing the results of `to_lowercase`, see
+    /// there for why the local part is compared case insensitive, too.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate mail_headers;
+    /// use mail_headers::HeaderTryFrom;
+    /// use mail_headers::header_components::Email;
+    /// # fn main() {
+    ///
+    /// let email = Email::try_from("John.Doe@Example.COM").unwrap();
+    /// let other = Email::try_from("john.doe@example.com").unwrap();
+    /// assert_ne!(email, other);
+    /// assert!(email.eq_ignore_case(&other));
+    /// assert_eq!(email.to_lowercase(), other);
+    /// # }
+    /// ```
+    pub fn eq_ignore_case(&self, other: &Email) -> bool {
+        eq_ignore_case(self.local_part.as_str(), other.local_part.as_str())
+            && self.domain.eq_ignore_case(&other.domain)
+    }
+
     pub fn new<T: HeaderTryInto<Input>>(email: T) -> Result<Self, ComponentCreationError> {
         let email = email.try_into()?.into_shared();
         match email {
@@ -54,25 +179,221 @@ impl Email {
                 //2. get 2 sub shares split befor/after @
                 let index = shared.find( "@" )
                     .ok_or_else(|| {
+                        let end = shared.len();
                         ComponentCreationError::new_with_str("Email", shared.to_string())
+                            .with_span(end..end, Some("'@'"))
                     })?;
 
-                let left = shared.clone().map( |all| &all[..index] );
+                let split_error = |all: &str| {
+                    ComponentCreationError::new_with_str("Email", all.to_owned())
+                        .with_span(index..index+1, Some("'@'"))
+                };
+
+                let left = shared.clone()
+                    .try_map(|all| all.get(..index).ok_or(()))
+                    .map_err(|()| split_error(&*shared))?;
                 let local_part = LocalPart::try_from( Input( InnerUtf8::Shared( left ) ) )?;
-                //index+1 is ok as '@'.utf8_len() == 1
-                let right = shared.map( |all| &all[index+1..] );
-                let domain = Domain::try_from( Input( InnerUtf8::Shared( right ) ) )?;
+                //index+1 is a char boundary as '@'.len_utf8() == 1
+                let right = shared.clone()
+                    .try_map(|all| all.get(index+1..).ok_or(()))
+                    .map_err(|()| split_error(&*shared))?;
+                let domain = Domain::try_from( Input( InnerUtf8::Shared( right ) ) )
+                    .map_err(|err| err.shift_span(index+1))?;
                 Ok( Email { local_part, domain } )
             }
         }
     }
+
+    /// Creates the email like `new`, but also accepts it in angle brackets (e.g. `<a@b.example>`).
+    ///
+    /// This is meant for addresses pasted by users, which are often copied
+    /// from a mailbox (`Name <a@b.example>`) or a message id. If the input
+    /// starts with `<` and ends with `>` the brackets are stripped and the
+    /// inside is parsed with `new`, in which case whitespace inside of the
+    /// brackets is rejected (e.g. `< a@b.example >`). A single `<` or `>`
+    /// at the start or end without its counterpart is rejected, too.
+    ///
+    /// Spans of errors are relative to the given input (including the brackets).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate mail_headers;
+    /// use mail_headers::header_components::Email;
+    /// # fn main() {
+    ///
+    /// let email = Email::parse_lenient("<user@example.com>").unwrap();
+    /// assert_eq!(email, Email::new("user@example.com").unwrap());
+    /// assert!(Email::parse_lenient("<user@example.com").is_err());
+    /// # }
+    /// ```
+    pub fn parse_lenient(email: &str) -> Result<Self, ComponentCreationError> {
+        let opening = email.starts_with('<');
+        let closing = email.ends_with('>');
+        if !opening && !closing {
+            return Email::new(email);
+        }
+
+        let end = email.len();
+        let inner = match email.get(1..end.saturating_sub(1)) {
+            Some(inner) if opening && closing => inner,
+            _ => {
+                let span = if opening { 0..1 } else { end-1..end };
+                return Err(ComponentCreationError::new_with_str("Email", email)
+                    .with_span(span, Some("matching '<' and '>'")));
+            }
+        };
+        if let Some((idx, ch)) = inner.char_indices().find(|&(_, ch)| is_ws(ch)) {
+            return Err(ComponentCreationError::new_with_str("Email", email)
+                .with_span(idx+1..idx+1+ch.len_utf8(), Some("no whitespace in angle brackets")));
+        }
+        Email::new(inner)
+            .map_err(|err| err.shift_span(1).with_str_context(email))
+    }
+
+    /// Creates the email like `new` and checks it with given profile.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate mail_headers;
+    /// use mail_headers::header_components::{Email, AddressProfile};
+    /// use mail_headers::error::AddressViolation;
+    /// # fn main() {
+    ///
+    /// assert!(Email::new_with_profile("john..doe@example.com", AddressProfile::Rfc5322).is_ok());
+    ///
+    /// let err = Email::new_with_profile("john..doe@example.com", AddressProfile::provider_strict())
+    ///     .unwrap_err();
+    /// assert_eq!(err.address_violation(), Some(AddressViolation::ConsecutiveDots));
+    /// # }
+    /// ```
+    pub fn new_with_profile<T>(email: T, profile: AddressProfile) -> Result<Self, ComponentCreationError>
+        where T: HeaderTryInto<Input>
+    {
+        let email = Email::new(email)?;
+        profile.check(&email)?;
+        Ok(email)
+    }
+}
+
+/// The rules an address has to follow in addition to being syntactically valid.
+///
+/// `Rfc5322` (the default) accepts every address rfc5322 accepts.
+/// `ProviderStrict` additionally applies the rules many large mail
+/// providers enforce, i.e. it rejects addresses with:
+///
+/// - a local part which would have to be quoted (e.g. `"john doe"@example.com`)
+/// - a domain literal (e.g. `abc@[127.0.0.1]`)
+/// - a local part longer than 64 octets
+/// - consecutive dots (e.g. `john..doe@example.com`)
+/// - a domain with only one label (e.g. `abc@localhost`), unless
+///   `allow_single_label_domain` is true
+///
+/// Each of the rules fails with a distinct `AddressViolation`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AddressProfile {
+    Rfc5322,
+    ProviderStrict { allow_single_label_domain: bool }
+}
+
+impl AddressProfile {
+
+    /// `ProviderStrict` rejecting domains with only one label.
+    pub fn provider_strict() -> Self {
+        AddressProfile::ProviderStrict { allow_single_label_domain: false }
+    }
+
+    /// Checks if the (syntactically valid) email follows the rules of this profile.
+    ///
+    /// # Error
+    ///
+    /// The error is caused by the `AddressViolation` of the first violated
+    /// rule (see `ComponentCreationError::address_violation`), its span
+    /// points to the violating part of the address.
+    pub fn check(&self, email: &Email) -> Result<(), ComponentCreationError> {
+        let allow_single_label_domain = match *self {
+            AddressProfile::Rfc5322 => return Ok(()),
+            AddressProfile::ProviderStrict { allow_single_label_domain } => allow_single_label_domain
+        };
+
+        let local_part = email.local_part.as_str();
+        let domain = email.domain.as_str();
+        let domain_start = local_part.len() + 1;
+        let fail = |violation, span| {
+            ComponentCreationError::from_parent(violation, "Email")
+                .with_str_context(email.to_string())
+                .with_span(span, None)
+        };
+
+        if local_part.len() > 64 {
+            return Err(fail(AddressViolation::LocalPartTooLong, 0..local_part.len()));
+        }
+        if let Some(idx) = local_part.find("..") {
+            return Err(fail(AddressViolation::ConsecutiveDots, idx..idx+2));
+        }
+        let needs_quoting = local_part.is_empty()
+            || local_part.starts_with('.')
+            || local_part.ends_with('.')
+            || !local_part.chars().all(|ch| ch == '.' || is_atext(ch, MailType::Internationalized));
+        if needs_quoting {
+            return Err(fail(AddressViolation::QuotedLocalPart, 0..local_part.len()));
+        }
+        let domain_span = domain_start..domain_start+domain.len();
+        if domain.starts_with('[') {
+            return Err(fail(AddressViolation::DomainLiteral, domain_span));
+        }
+        if !allow_single_label_domain && !domain.contains('.') {
+            return Err(fail(AddressViolation::SingleLabelDomain, domain_span));
+        }
+        Ok(())
+    }
+}
+
+impl Default for AddressProfile {
+    fn default() -> Self {
+        AddressProfile::Rfc5322
+    }
 }
 
 impl LocalPart {
 
+    /// Converts the underlying text into shared data, making clones cheap.
+    pub fn into_shared(self) -> Self {
+        LocalPart(self.0.into_shared())
+    }
+
     pub fn check_if_internationalized(&self) -> bool {
         self.0.as_str().bytes().any(|b| b > 0x7f)
     }
+
+    /// Returns the local part as `dot-atom-text`, or as `quoted-string` if it has to be quoted.
+    ///
+    /// # Error
+    ///
+    /// Fails if the local part contains non us-ascii chars, as they can
+    /// not be represented in a us-ascii local part (there is no
+    /// equivalent of punycode for local parts).
+    pub fn to_ascii_string(&self) -> Result<SoftAsciiString, EncodingError> {
+        let quoted = self.quoted(MailType::Ascii)?;
+        // quoting for `Ascii` fails for non us-ascii chars
+        Ok(SoftAsciiString::from_unchecked(quoted.into_owned()))
+    }
+
+    /// Quotes the local part if needed for given mail type.
+    fn quoted(&self, mail_type: MailType) -> Result<Cow<str>, EncodingError> {
+        let input: &str = &*self.0;
+        let mut validator = UnquotedDotAtomTextValidator::new(mail_type);
+
+        if mail_type.is_internationalized() {
+            quote_if_needed::<MimeSpec<Internationalized, Modern>, _>(input, &mut validator)
+        } else {
+            quote_if_needed::<MimeSpec<Ascii, Modern>, _>(input, &mut validator)
+        }.map_err(|err| EncodingError
+            ::from(err.context(EncodingErrorKind::Malformed))
+            .with_str_context(input)
+        )
+    }
 }
 
 impl<'a> HeaderTryFrom<&'a str> for Email {
@@ -94,6 +415,23 @@ impl HeaderTryFrom<Input> for Email {
 }
 
 
+/// Formats the email as `local-part@domain`.
+///
+/// This is not the wire form if the local part needs to be quoted
+/// or the domain is internationalized (it is not punycode encoded).
+impl Display for Email {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "{}@{}", self.local_part, self.domain)
+    }
+}
+
+impl HasMailTypeRequirement for Email {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        self.local_part.mail_type_requirement()
+            .combine(self.domain.mail_type_requirement())
+    }
+}
+
 impl EncodableInHeader for  Email {
 
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
@@ -118,24 +456,27 @@ impl<T> HeaderTryFrom<T> for LocalPart
 
 }
 
-impl EncodableInHeader for LocalPart {
-
-    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
-        let input: &str = &*self.0;
-        let mail_type = handle.mail_type();
+/// Formats the local part as is, i.e. without quoting it.
+impl Display for LocalPart {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(self.0.as_str())
+    }
+}
 
-        let mut validator = UnquotedDotAtomTextValidator::new(mail_type);
+impl HasMailTypeRequirement for LocalPart {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        if self.check_if_internationalized() {
+            MailTypeRequirement::internationalized()
+        } else {
+            MailTypeRequirement::ascii()
+        }
+    }
+}
 
-        let res =
-            if mail_type.is_internationalized() {
-                quote_if_needed::<MimeSpec<Internationalized, Modern>, _>(input, &mut validator)
-            } else {
-                quote_if_needed::<MimeSpec<Ascii, Modern>, _>(input, &mut validator)
-            }.map_err(|err| EncodingError
-                ::from(err.context(EncodingErrorKind::Malformed))
-                .with_str_context(input)
-            )?;
+impl EncodableInHeader for LocalPart {
 
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        let res = self.quoted(handle.mail_type())?;
 
         handle.mark_fws_pos();
         // if mail_type == Ascii quote_if_needed already made sure it's ascii
@@ -182,6 +523,10 @@ impl<T> HeaderTryFrom<T> for Domain
 impl Domain {
 
     /// creates a domain from a string without checking for validity
+    ///
+    /// This also skips the length limits and label rules checked when
+    /// creating a domain with `try_from`, e.g. for domains only used
+    /// internally which do not need to be accepted by other MTAs.
     pub fn from_unchecked(string: String) -> Self {
         let item =
             match SoftAsciiString::from_string(string) {
@@ -197,30 +542,31 @@ impl Domain {
     //  if the domain is actually ascii
     fn check_domain( domain: &str ) -> Result<MailType, ComponentCreationError> {
         if domain.starts_with("[") && domain.ends_with("]") {
-            //TODO improved support for domain literals, e.g. internationalized ones? CRLF? etc.
-            for ch in domain.chars() {
-                if !(is_dtext(ch, MailType::Ascii) || is_ws(ch)) {
-                    let mut err = ComponentCreationError::new("Domain");
-                    err.set_str_context(domain);
-                    return Err(err);
-                }
-            }
+            //TODO improved support for domain literals, e.g. internationalized ones?
+            Domain::check_address_literal(domain)?;
             Ok(MailType::Ascii)
         } else {
             let mut ascii = true;
             let mut dot_alowed = false;
-            for char in domain.chars() {
+            for (idx, char) in domain.char_indices() {
                 if ascii { ascii = is_ascii( char ) }
                 if char == '.' && dot_alowed {
                     dot_alowed = false;
                 } else if !is_atext( char, MailType::Internationalized ) {
-                    let mut err = ComponentCreationError::new("Domain");
-                    err.set_str_context(domain);
-                    return Err(err);
+                    let expected = if dot_alowed { "atext or '.'" } else { "atext" };
+                    return Err(ComponentCreationError::new_with_str("Domain", domain)
+                        .with_span(idx..idx+char.len_utf8(), Some(expected)));
                 } else {
                     dot_alowed = true;
                 }
             }
+            // dot_alowed is only false if the domain is empty or ends with a '.'
+            if !dot_alowed {
+                let end = domain.len();
+                return Err(ComponentCreationError::new_with_str("Domain", domain)
+                    .with_span(end..end, Some("atext")));
+            }
+            Domain::check_labels(domain)?;
             Ok(if ascii {
                 MailType::Ascii
             } else {
@@ -229,10 +575,183 @@ impl Domain {
         }
     }
 
+    /// Checks the length limits (RFC 1035) and hyphen rule (RFC 5891) of the labels.
+    ///
+    /// The limits apply to the ACE form, so internationalized labels are
+    /// punycoded before checking their length. The domain has to be
+    /// syntactically valid, i.e. it has no empty labels.
+    fn check_labels(domain: &str) -> Result<(), ComponentCreationError> {
+        let fail = |violation, span: Range<usize>, expected| {
+            ComponentCreationError::from_parent(violation, "Domain")
+                .with_str_context(domain)
+                .with_span(span, Some(expected))
+        };
+
+        // the dots between the labels
+        let mut total_len = domain.split('.').count() - 1;
+        let mut start = 0;
+        for label in domain.split('.') {
+            let span = start..start+label.len();
+            if label.starts_with('-') || label.ends_with('-') {
+                let violation = DomainViolation::HyphenAtLabelEdge { label: label.to_owned() };
+                return Err(fail(violation, span, "label not starting or ending with '-'"));
+            }
+            let len = ace_len(label);
+            if len > MAX_LABEL_LEN {
+                let violation = DomainViolation::LabelTooLong { label: label.to_owned(), len };
+                return Err(fail(violation, span, "label of at most 63 octets"));
+            }
+            total_len += len;
+            start = span.end + 1;
+        }
+
+        if total_len > MAX_DOMAIN_LEN {
+            let violation = DomainViolation::DomainTooLong { len: total_len };
+            return Err(fail(violation, 0..domain.len(), "domain of at most 253 octets"));
+        }
+        Ok(())
+    }
+
+    /// Checks a domain literal (including the brackets) as RFC 5321 address literal.
+    ///
+    /// Accepted are IPv4 addresses (e.g. `[192.0.2.1]`), IPv6 addresses with
+    /// the `IPv6:` tag (e.g. `[IPv6:2001:db8::1]`) and general address
+    /// literals with a standardized tag (e.g. `[x-tag:content]`).
+    fn check_address_literal(domain: &str) -> Result<(), ComponentCreationError> {
+        let invalid = |span: Range<usize>, expected| {
+            ComponentCreationError::from_parent(InvalidDomainName::new(domain), "Domain")
+                .with_str_context(domain)
+                .with_span(span, Some(expected))
+        };
+        // the brackets are one byte each and the domain starts and ends
+        // with one, so this can't fail, it's still checked to not panic
+        let end = domain.len().saturating_sub(1);
+        let content = domain.get(1..end)
+            .ok_or_else(|| invalid(0..domain.len(), "address literal"))?;
+
+        match split_literal_tag(content) {
+            None => {
+                if content.parse::<Ipv4Addr>().is_err() {
+                    return Err(invalid(1..end, "IPv4 address or tagged address literal"));
+                }
+            },
+            Some((tag, address)) => {
+                // the address starts after the `[`, the tag and the `:`
+                let start = tag.len() + 2;
+                if tag.eq_ignore_ascii_case(IPV6_TAG) {
+                    if address.parse::<Ipv6Addr>().is_err() {
+                        return Err(invalid(start..end, "IPv6 address"));
+                    }
+                } else if !is_ldh_str(tag) {
+                    return Err(invalid(1..start-1, "standardized tag"));
+                } else if address.is_empty() {
+                    return Err(invalid(start..start, "dcontent"));
+                } else if let Some((idx, ch)) = address.char_indices().find(|&(_, ch)| !is_dcontent(ch)) {
+                    let idx = start + idx;
+                    return Err(invalid(idx..idx+ch.len_utf8(), "dcontent"));
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn as_str(&self) -> &str {
         self.0.as_str()
     }
 
+    /// Returns true if this is a domain literal (e.g. `[192.0.2.1]`).
+    pub fn is_literal(&self) -> bool {
+        let domain = self.as_str();
+        domain.starts_with('[') && domain.ends_with(']')
+    }
+
+    /// Returns the ip address of a IPv4 or IPv6 address literal.
+    ///
+    /// Returns `None` if the domain is not a literal or a general address
+    /// literal (e.g. `[x-tag:content]`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate mail_headers;
+    /// use std::net::{IpAddr, Ipv6Addr};
+    /// use mail_headers::HeaderTryFrom;
+    /// use mail_headers::header_components::Domain;
+    /// # fn main() {
+    ///
+    /// let domain = Domain::try_from("[IPv6:2001:db8::1]").unwrap();
+    /// assert!(domain.is_literal());
+    /// let expected = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    /// assert_eq!(domain.as_ip_addr(), Some(IpAddr::V6(expected)));
+    ///
+    /// assert_eq!(Domain::try_from("example.com").unwrap().as_ip_addr(), None);
+    /// # }
+    /// ```
+    pub fn as_ip_addr(&self) -> Option<IpAddr> {
+        if !self.is_literal() {
+            return None;
+        }
+        let domain = self.as_str();
+        let content = domain.get(1..domain.len()-1)?;
+        match split_literal_tag(content) {
+            None => content.parse::<Ipv4Addr>().ok().map(IpAddr::V4),
+            Some((tag, address)) if tag.eq_ignore_ascii_case(IPV6_TAG) => {
+                address.parse::<Ipv6Addr>().ok().map(IpAddr::V6)
+            },
+            Some(_) => None
+        }
+    }
+
+    /// Returns the skeleton of the domain, used to detect lookalike domains.
+    ///
+    /// The skeleton is the lowercased domain in which punycode labels are
+    /// decoded and common Cyrillic, Greek and Latin extended lookalikes of
+    /// latin letters are replaced by the letter (e.g. the Cyrillic `а`
+    /// becomes `a`). Only a small table of the most common lookalikes is
+    /// used, not the full Unicode confusables data.
+    pub fn skeleton(&self) -> String {
+        confusable::domain_skeleton(self.as_str())
+    }
+
+    /// Converts the underlying text into shared data, making clones cheap.
+    pub fn into_shared(self) -> Self {
+        Domain(self.0.into_shared())
+    }
+
+    /// Returns a copy of this domain in lowercase.
+    ///
+    /// Us-ascii domains (including punycode labels) are lowercased as
+    /// us-ascii, internationalized domains using the Unicode lowercase
+    /// mapping. The further mappings of UTS #46 (e.g. of full width chars)
+    /// are not applied, neither are punycode labels decoded, so the
+    /// punycode and the Unicode form of a domain still differ. Compare
+    /// the results of `to_ascii_string` (lowercased) for this.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate mail_headers;
+    /// use mail_headers::HeaderTryFrom;
+    /// use mail_headers::header_components::Domain;
+    /// # fn main() {
+    ///
+    /// let domain = Domain::try_from("Bücher.Example").unwrap();
+    /// assert_eq!(domain.to_lowercase().as_str(), "bücher.example");
+    /// assert!(domain.eq_ignore_case(&Domain::try_from("BÜCHER.example").unwrap()));
+    /// # }
+    /// ```
+    pub fn to_lowercase(&self) -> Domain {
+        // lowercasing never makes a valid domain invalid
+        Domain::from_unchecked(self.as_str().to_lowercase())
+    }
+
+    /// Returns true if both domains are equal ignoring case.
+    ///
+    /// This is the same as comparing the results of `to_lowercase`.
+    pub fn eq_ignore_case(&self, other: &Domain) -> bool {
+        eq_ignore_case(self.as_str(), other.as_str())
+    }
+
     pub fn into_ascii_string(self) -> Result<SoftAsciiString, EncodingError> {
         match self.0 {
             SimpleItem::Ascii(ascii) => Ok(ascii.into()),
@@ -252,6 +771,77 @@ impl Domain {
     }
 }
 
+/// Compares both strings ignoring case, like comparing the results of `str::to_lowercase`.
+fn eq_ignore_case(left: &str, right: &str) -> bool {
+    if left.is_ascii() && right.is_ascii() {
+        left.eq_ignore_ascii_case(right)
+    } else {
+        left.to_lowercase() == right.to_lowercase()
+    }
+}
+
+/// The maximal length of a label (in its ACE form).
+const MAX_LABEL_LEN: usize = 63;
+
+/// The maximal length of a domain (in its ACE form), 255 octets in the DNS wire format.
+const MAX_DOMAIN_LEN: usize = 253;
+
+/// Returns the length of the ACE form of the label.
+///
+/// If the label can't be punycoded (which fails when encoding it anyway)
+/// the length of the label is returned.
+fn ace_len(label: &str) -> usize {
+    if label.is_ascii() {
+        label.len()
+    } else {
+        idna::puny_code_domain(label)
+            .map(|ace| ace.len())
+            .unwrap_or_else(|_| label.len())
+    }
+}
+
+/// The tag of IPv6 address literals (compared case insensitive).
+const IPV6_TAG: &str = "IPv6";
+
+/// Splits the content of a tagged address literal (`tag:address`) at the first `:`.
+///
+/// Returns `None` for untagged literals (i.e. IPv4 addresses).
+fn split_literal_tag(content: &str) -> Option<(&str, &str)> {
+    let colon = content.find(':')?;
+    Some((content.get(..colon)?, content.get(colon+1..)?))
+}
+
+/// Returns true if the tag is a RFC 5321 `Ldh-str`.
+fn is_ldh_str(tag: &str) -> bool {
+    !tag.is_empty()
+        && !tag.ends_with('-')
+        && tag.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '-')
+}
+
+/// Returns true if the char is RFC 5321 `dcontent` (printable us-ascii except `[`, `\` and `]`).
+fn is_dcontent(ch: char) -> bool {
+    match ch {
+        '!'...'Z' | '^'...'~' => true,
+        _ => false
+    }
+}
+
+/// Formats the domain as is, i.e. internationalized domains are not punycode encoded.
+impl Display for Domain {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(self.as_str())
+    }
+}
+
+impl HasMailTypeRequirement for Domain {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        match self.0 {
+            SimpleItem::Ascii(..) => MailTypeRequirement::ascii(),
+            SimpleItem::Utf8(..) => MailTypeRequirement::punycode()
+        }
+    }
+}
+
 impl EncodableInHeader for  Domain {
 
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
@@ -382,6 +972,195 @@ mod test {
         assert_eq!(lp.as_str(), "hello")
     }
 
+    #[test]
+    fn domain_rejects_empty_and_trailing_dot() {
+        assert_err!(Domain::try_from(""));
+        assert_err!(Domain::try_from("example."));
+        assert_err!(Email::try_from("abc@"));
+        assert_err!(Email::try_from("abc@example.com."));
+    }
+
+    #[test]
+    fn domain_label_length_limit() {
+        let label = "a".repeat(63);
+        assert_ok!(Domain::try_from(format!("{}.example", label)));
+
+        let label = "a".repeat(64);
+        let err = assert_err!(Domain::try_from(format!("www.{}.example", label)));
+        assert_eq!(err.span(), Some(4..68));
+        assert_eq!(err.expected(), Some("label of at most 63 octets"));
+        assert_eq!(err.domain_violation(), Some(&DomainViolation::LabelTooLong { label, len: 64 }));
+
+        let err = assert_err!(Email::try_from(format!("abc@{}.example", "b".repeat(70))));
+        assert_eq!(err.span(), Some(4..74));
+    }
+
+    #[test]
+    fn domain_label_length_limit_applies_to_ace_form() {
+        // 62 octets as utf-8, but longer when punycoded
+        let label = format!("{}ü", "a".repeat(60));
+        let err = assert_err!(Domain::try_from(format!("{}.example", label)));
+        match err.domain_violation() {
+            Some(&DomainViolation::LabelTooLong { label: ref too_long, len }) => {
+                assert_eq!(too_long, &label);
+                assert!(len > 63);
+            },
+            other => panic!("unexpected violation: {:?}", other)
+        }
+        assert_ok!(Domain::try_from("dömain.example"));
+    }
+
+    #[test]
+    fn domain_total_length_limit() {
+        let label = "a".repeat(63);
+        let domain = format!("{0}.{0}.{0}.{1}", label, "b".repeat(61));
+        assert_eq!(domain.len(), 253);
+        assert_ok!(Domain::try_from(&*domain));
+
+        let domain = format!("{0}.{0}.{0}.{0}", label);
+        let err = assert_err!(Domain::try_from(&*domain));
+        assert_eq!(err.span(), Some(0..255));
+        assert_eq!(err.domain_violation(), Some(&DomainViolation::DomainTooLong { len: 255 }));
+    }
+
+    #[test]
+    fn domain_labels_do_not_start_or_end_with_hyphen() {
+        assert_ok!(Domain::try_from("a-b.example"));
+        assert_ok!(Domain::try_from("xn--tda.example"));
+
+        let err = assert_err!(Domain::try_from("-abc.example"));
+        assert_eq!(err.span(), Some(0..4));
+        let violation = DomainViolation::HyphenAtLabelEdge { label: "-abc".to_owned() };
+        assert_eq!(err.domain_violation(), Some(&violation));
+
+        let err = assert_err!(Domain::try_from("www.abc-.example"));
+        assert_eq!(err.span(), Some(4..8));
+    }
+
+    #[test]
+    fn from_unchecked_skips_label_rules() {
+        let domain = Domain::from_unchecked(format!("-{}.example", "a".repeat(70)));
+        assert!(domain.as_str().starts_with("-a"));
+    }
+
+    #[test]
+    fn domain_literal() {
+        let domain = assert_ok!(Domain::try_from("[127.0.0.1]"));
+        assert_eq!(domain.as_str(), "[127.0.0.1]");
+        assert!(domain.is_literal());
+        assert_eq!(domain.as_ip_addr(), Some(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))));
+        assert_err!(Domain::try_from("[a[b]"));
+
+        let domain = assert_ok!(Domain::try_from("example.com"));
+        assert!(!domain.is_literal());
+        assert_eq!(domain.as_ip_addr(), None);
+    }
+
+    #[test]
+    fn domain_literal_ipv6() {
+        let expected = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
+        let domain = assert_ok!(Domain::try_from("[IPv6:2001:db8::1]"));
+        assert_eq!(domain.as_ip_addr(), Some(expected));
+        let domain = assert_ok!(Domain::try_from("[ipv6:2001:DB8::1]"));
+        assert_eq!(domain.as_ip_addr(), Some(expected));
+
+        let domain = assert_ok!(Domain::try_from("[IPv6:::ffff:192.0.2.1]"));
+        assert_eq!(domain.as_ip_addr(), Some(IpAddr::V6(Ipv4Addr::new(192, 0, 2, 1).to_ipv6_mapped())));
+
+        let email = assert_ok!(Email::try_from("abc@[IPv6:::1]"));
+        assert_eq!(email.domain.as_ip_addr(), Some(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1))));
+    }
+
+    #[test]
+    fn domain_literal_general() {
+        let domain = assert_ok!(Domain::try_from("[x-tag:some!content]"));
+        assert!(domain.is_literal());
+        assert_eq!(domain.as_ip_addr(), None);
+    }
+
+    #[test]
+    fn invalid_domain_literals() {
+        let cases: &[(&str, Range<usize>, &str)] = &[
+            ("[]", 1..1, "IPv4 address or tagged address literal"),
+            ("[192.0.2]", 1..8, "IPv4 address or tagged address literal"),
+            ("[192.0.2.256]", 1..12, "IPv4 address or tagged address literal"),
+            ("[IPv6:2001:db8::1::2]", 6..20, "IPv6 address"),
+            ("[IPv6:192.0.2.1]", 6..15, "IPv6 address"),
+            ("[::1]", 1..1, "standardized tag"),
+            ("[x tag:content]", 1..6, "standardized tag"),
+            ("[x-tag:]", 7..7, "dcontent"),
+            ("[x-tag:con tent]", 10..11, "dcontent"),
+        ];
+        for &(input, ref span, expected) in cases {
+            let err = assert_err!(Domain::try_from(input));
+            assert_eq!(err.invalid_domain_name(), Some(&InvalidDomainName::new(input)), "{}", input);
+            assert_eq!(err.span(), Some(span.clone()), "{}", input);
+            assert_eq!(err.expected(), Some(expected), "{}", input);
+        }
+
+        let err = assert_err!(Email::try_from("abc@[IPv6:nope]"));
+        assert_eq!(err.invalid_domain_name().map(|err| err.name()), Some("[IPv6:nope]"));
+    }
+
+    #[test]
+    fn bad_char_in_domain_reports_offset() {
+        let err = assert_err!(Email::try_from("abc@exa mple.com"));
+        assert_eq!(err.span(), Some(7..8));
+        assert_eq!(err.expected(), Some("atext or '.'"));
+
+        let err = assert_err!(Domain::try_from("[x-tag:a[b]"));
+        assert_eq!(err.span(), Some(8..9));
+        assert_eq!(err.expected(), Some("dcontent"));
+    }
+
+    #[test]
+    fn truncated_email_reports_end_of_input() {
+        let err = assert_err!(Email::try_from("abc@example."));
+        assert_eq!(err.span(), Some(12..12));
+
+        let err = assert_err!(Email::try_from("abc"));
+        assert_eq!(err.span(), Some(3..3));
+        assert_eq!(err.expected(), Some("'@'"));
+    }
+
+    #[test]
+    fn parse_lenient_strips_angle_brackets() {
+        let email = Email::try_from("a@b.c").unwrap();
+        assert_eq!(assert_ok!(Email::parse_lenient("<a@b.c>")), email);
+        assert_eq!(assert_ok!(Email::parse_lenient("a@b.c")), email);
+    }
+
+    #[test]
+    fn parse_lenient_rejects_stray_brackets() {
+        let err = assert_err!(Email::parse_lenient("<a@b.c"));
+        assert_eq!(err.span(), Some(0..1));
+        assert_eq!(err.str_context(), Some("<a@b.c"));
+
+        let err = assert_err!(Email::parse_lenient("a@b.c>"));
+        assert_eq!(err.span(), Some(5..6));
+
+        let err = assert_err!(Email::parse_lenient("<"));
+        assert_eq!(err.span(), Some(0..1));
+        assert_err!(Email::parse_lenient("<>"));
+    }
+
+    #[test]
+    fn parse_lenient_rejects_whitespace_in_brackets() {
+        let err = assert_err!(Email::parse_lenient("< a@b.c >"));
+        assert_eq!(err.span(), Some(1..2));
+        assert_eq!(err.expected(), Some("no whitespace in angle brackets"));
+
+        let err = assert_err!(Email::parse_lenient("<a b@c.d>"));
+        assert_eq!(err.span(), Some(2..3));
+    }
+
+    #[test]
+    fn parse_lenient_reports_spans_in_the_input() {
+        let err = assert_err!(Email::parse_lenient("<abc@exa,mple.com>"));
+        assert_eq!(err.span(), Some(8..9));
+        assert_eq!(err.str_context(), Some("<abc@exa,mple.com>"));
+    }
+
     #[test]
     fn domain_as_str() {
         let domain = Domain::try_from("hello").unwrap();
@@ -401,4 +1180,144 @@ mod test {
         let stringified = domain.into_ascii_string().unwrap();
         assert_eq!(&*stringified, "xn--h-1ga.test")
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn email_to_ascii_string_puny_encodes_domain() {
+        let email = Email::try_from("user@dömain.de").unwrap();
+        assert_eq!(assert_ok!(email.to_ascii_string()).as_str(), "user@xn--dmain-jua.de");
+
+        let email = Email::try_from("user@example.com").unwrap();
+        assert_eq!(assert_ok!(email.to_ascii_string()).as_str(), "user@example.com");
+    }
+
+    #[test]
+    fn email_to_ascii_string_quotes_local_part() {
+        let email = Email::try_from("ha ns@example.com").unwrap();
+        assert_eq!(assert_ok!(email.to_ascii_string()).as_str(), "\"ha ns\"@example.com");
+    }
+
+    #[test]
+    fn email_to_ascii_string_rejects_non_ascii_local_part() {
+        let email = Email::try_from("jörn@dömain.de").unwrap();
+        assert_err!(email.to_ascii_string());
+        assert_err!(email.local_part.to_ascii_string());
+    }
+
+    #[test]
+    fn display_email() {
+        let email = Email::try_from("abc@de.fg").unwrap();
+        assert_eq!(email.to_string(), "abc@de.fg");
+        assert_eq!(email.local_part.to_string(), "abc");
+        assert_eq!(email.domain.to_string(), "de.fg");
+    }
+
+    #[test]
+    fn display_does_not_punycode_domain() {
+        let email = Email::try_from("abc@bücher.example").unwrap();
+        assert_eq!(email.to_string(), "abc@bücher.example");
+    }
+
+    #[test]
+    fn address_profiles() {
+        use ::error::AddressViolation::*;
+        let long_local_part = format!("{}@example.com", "a".repeat(65));
+        let max_local_part = format!("{}@example.com", "a".repeat(64));
+
+        // address, accepted by Rfc5322, result with provider_strict
+        let table: Vec<(&str, bool, Option<AddressViolation>)> = vec![
+            ("john.doe@example.com", true, None),
+            ("john+tag@mail.example.com", true, None),
+            ("jörn@bücher.example", true, None),
+            (max_local_part.as_str(), true, None),
+            ("john doe@example.com", true, Some(QuotedLocalPart)),
+            ("\"john doe\"@example.com", true, Some(QuotedLocalPart)),
+            (".john@example.com", true, Some(QuotedLocalPart)),
+            ("john.@example.com", true, Some(QuotedLocalPart)),
+            ("@example.com", true, Some(QuotedLocalPart)),
+            ("john@[127.0.0.1]", true, Some(DomainLiteral)),
+            (long_local_part.as_str(), true, Some(LocalPartTooLong)),
+            ("john..doe@example.com", true, Some(ConsecutiveDots)),
+            ("john@example..com", false, None),
+            ("john@localhost", true, Some(SingleLabelDomain)),
+            ("john@", false, None),
+        ];
+
+        for &(address, rfc_ok, strict) in table.iter() {
+            let rfc = Email::new_with_profile(address, AddressProfile::Rfc5322);
+            assert_eq!(rfc.is_ok(), rfc_ok, "Rfc5322: {}", address);
+            if !rfc_ok {
+                assert_err!(Email::new_with_profile(address, AddressProfile::provider_strict()));
+                continue;
+            }
+            match (Email::new_with_profile(address, AddressProfile::provider_strict()), strict) {
+                (Ok(_), None) => {},
+                (Err(err), Some(expected)) =>
+                    assert_eq!(err.address_violation(), Some(expected), "strict: {}", address),
+                (res, _) => panic!("strict: {}: unexpected result {:?}", address, res)
+            }
+        }
+    }
+
+    #[test]
+    fn single_label_domains_can_be_allowed() {
+        let profile = AddressProfile::ProviderStrict { allow_single_label_domain: true };
+        assert_ok!(Email::new_with_profile("john@localhost", profile));
+        let err = assert_err!(Email::new_with_profile("john..doe@localhost", profile));
+        assert_eq!(err.address_violation(), Some(AddressViolation::ConsecutiveDots));
+    }
+
+    #[test]
+    fn profile_violation_reports_span() {
+        let profile = AddressProfile::provider_strict();
+        let err = assert_err!(Email::new_with_profile("john..doe@example.com", profile));
+        assert_eq!(err.span(), Some(4..6));
+        let err = assert_err!(Email::new_with_profile("john@[127.0.0.1]", profile));
+        assert_eq!(err.span(), Some(5..16));
+        assert_eq!(err.str_context(), Some("john@[127.0.0.1]"));
+
+        // other errors have no violation
+        let err = assert_err!(Email::new_with_profile("john", profile));
+        assert_eq!(err.address_violation(), None);
+    }
+
+    #[test]
+    fn default_profile_is_rfc5322() {
+        assert_eq!(AddressProfile::default(), AddressProfile::Rfc5322);
+    }
+
+    #[test]
+    fn domains_compare_ignoring_case() {
+        let domain = Domain::try_from("Example.COM").unwrap();
+        let other = Domain::try_from("example.com").unwrap();
+        assert_ne!(domain, other);
+        assert!(domain.eq_ignore_case(&other));
+        assert_eq!(domain.to_lowercase(), other);
+        assert!(domain.to_lowercase().is_ascii());
+
+        let domain = Domain::try_from("BÜCHER.example").unwrap();
+        assert_eq!(domain.to_lowercase().as_str(), "bücher.example");
+        assert!(domain.eq_ignore_case(&Domain::try_from("bücher.EXAMPLE").unwrap()));
+        assert!(!domain.eq_ignore_case(&Domain::try_from("xn--bcher-kva.example").unwrap()));
+
+        let literal = Domain::try_from("[IPv6:2001:DB8::1]").unwrap();
+        assert_eq!(literal.to_lowercase().as_ip_addr(), literal.as_ip_addr());
+    }
+
+    #[test]
+    fn emails_compare_ignoring_case() {
+        let email = Email::try_from("John.Doe@Example.COM").unwrap();
+        let other = Email::try_from("john.doe@example.com").unwrap();
+        assert!(email.eq_ignore_case(&other));
+        assert_eq!(email.to_lowercase(), other);
+        assert_eq!(email.to_lowercase().to_string(), "john.doe@example.com");
+        assert!(!email.eq_ignore_case(&Email::try_from("jane.doe@example.com").unwrap()));
+        assert!(Email::try_from("JÖRN@x.example").unwrap()
+            .eq_ignore_case(&Email::try_from("jörn@X.example").unwrap()));
+    }
+
+    #[test]
+    fn display_does_not_quote_local_part() {
+        let local_part = LocalPart::try_from("ha ns").unwrap();
+        assert_eq!(local_part.to_string(), "ha ns");
+    }
+}
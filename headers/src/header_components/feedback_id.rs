@@ -0,0 +1,282 @@
+use std::fmt::{self, Display};
+
+use soft_ascii::{SoftAsciiStr, SoftAsciiString};
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodingWriter, EncodableInHeader};
+use ::HeaderTryFrom;
+use ::error::{ComponentCreationError, InvalidFeedbackIdSegment};
+use ::mail_type::HasMailTypeRequirement;
+
+const CAMPAIGN: usize = 0;
+const CUSTOMER: usize = 1;
+const MAIL_TYPE: usize = 2;
+const SENDER_ID: usize = 3;
+
+/// The component of the `Feedback-ID` header (non standard, used by e.g. Gmail's postmaster tools).
+///
+/// It consists of up to four colon separated identifiers, in order the
+/// campaign, customer, mail type and sender id, e.g.
+/// `Feedback-ID: summer-sale:customer42:newsletter:esp1`. Segments which
+/// are not set are encoded as empty, trailing unset segments are omitted
+/// (e.g. `summer-sale:::esp1` or `summer-sale`).
+///
+/// Each segment has to be a non empty string of us-ascii alphanumerics,
+/// `-`, `_` and `.`. Other chars (especially `:`) are rejected when
+/// creating or parsing the component, the cause of the error is a
+/// `InvalidFeedbackIdSegment` naming the segment (see
+/// `ComponentCreationError::feedback_id_segment`).
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_headers;
+/// use mail_headers::header_components::FeedbackId;
+/// # fn main() {
+///
+/// let id = FeedbackId::new("summer-sale").unwrap()
+///     .with_customer("customer42").unwrap()
+///     .with_sender_id("esp1").unwrap();
+/// assert_eq!(id.to_string(), "summer-sale:customer42::esp1");
+/// assert_eq!(FeedbackId::parse("summer-sale:customer42::esp1").unwrap(), id);
+///
+/// let err = FeedbackId::new("summer-sale").unwrap().with_mail_type("news:letter").unwrap_err();
+/// assert_eq!(err.feedback_id_segment().unwrap().index(), 2);
+/// # }
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct FeedbackId {
+    segments: [Option<SoftAsciiString>; 4]
+}
+
+impl FeedbackId {
+
+    /// Creates a new feedback id only containing the campaign identifier.
+    pub fn new(campaign: &str) -> Result<Self, ComponentCreationError> {
+        FeedbackId { segments: Default::default() }.with_segment(CAMPAIGN, campaign)
+    }
+
+    /// Sets the customer identifier (the second segment).
+    pub fn with_customer(self, customer: &str) -> Result<Self, ComponentCreationError> {
+        self.with_segment(CUSTOMER, customer)
+    }
+
+    /// Sets the mail type identifier (the third segment).
+    pub fn with_mail_type(self, mail_type: &str) -> Result<Self, ComponentCreationError> {
+        self.with_segment(MAIL_TYPE, mail_type)
+    }
+
+    /// Sets the sender identifier (the fourth segment).
+    pub fn with_sender_id(self, sender_id: &str) -> Result<Self, ComponentCreationError> {
+        self.with_segment(SENDER_ID, sender_id)
+    }
+
+    fn with_segment(mut self, index: usize, segment: &str) -> Result<Self, ComponentCreationError> {
+        check_segment(index, segment, segment)?;
+        self.segments[index] = Some(SoftAsciiString::from_unchecked(segment));
+        Ok(self)
+    }
+
+    /// Parses up to four colon separated segments, empty segments are not set.
+    ///
+    /// At least one segment has to be set.
+    pub fn parse(value: &str) -> Result<Self, ComponentCreationError> {
+        let trimmed = value.trim();
+        let offset = value.len() - value.trim_start().len();
+        let mut segments: [Option<SoftAsciiString>; 4] = Default::default();
+        let mut start = 0;
+        for (index, segment) in trimmed.split(':').enumerate() {
+            if index >= segments.len() {
+                // the colon before the fifth segment
+                let colon = offset + start - 1;
+                return Err(ComponentCreationError::new_with_str("FeedbackId", value)
+                    .with_span(colon..colon+1, Some("at most four segments")));
+            }
+            if !segment.is_empty() {
+                check_segment(index, segment, value)
+                    .map_err(|err| err.shift_span(offset + start))?;
+                segments[index] = Some(SoftAsciiString::from_unchecked(segment));
+            }
+            start += segment.len() + 1;
+        }
+
+        if segments.iter().all(Option::is_none) {
+            return Err(ComponentCreationError::new_with_str("FeedbackId", value));
+        }
+        Ok(FeedbackId { segments })
+    }
+
+    /// Returns the campaign identifier, if set.
+    pub fn campaign(&self) -> Option<&str> {
+        self.segment(CAMPAIGN)
+    }
+
+    /// Returns the customer identifier, if set.
+    pub fn customer(&self) -> Option<&str> {
+        self.segment(CUSTOMER)
+    }
+
+    /// Returns the mail type identifier, if set.
+    pub fn mail_type(&self) -> Option<&str> {
+        self.segment(MAIL_TYPE)
+    }
+
+    /// Returns the sender identifier, if set.
+    pub fn sender_id(&self) -> Option<&str> {
+        self.segment(SENDER_ID)
+    }
+
+    fn segment(&self, index: usize) -> Option<&str> {
+        self.segments[index].as_ref().map(|segment| segment.as_str())
+    }
+
+    /// Returns the value as written into the header, e.g. `campaign::mail-type`.
+    pub fn as_string(&self) -> String {
+        let used = self.segments.iter()
+            .rposition(Option::is_some)
+            .map(|last| last + 1)
+            .unwrap_or(0);
+        self.segments[..used].iter()
+            .map(|segment| segment.as_ref().map(|segment| segment.as_str()).unwrap_or(""))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+}
+
+/// Returns true for the chars allowed in a segment.
+fn is_segment_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' || ch == '.'
+}
+
+/// Checks the segment, the span of the error is relative to the segment.
+fn check_segment(index: usize, segment: &str, context: &str) -> Result<(), ComponentCreationError> {
+    let invalid = |span, expected| {
+        ComponentCreationError::from_parent(InvalidFeedbackIdSegment::new(index), "FeedbackId")
+            .with_str_context(context)
+            .with_span(span, Some(expected))
+    };
+    if segment.is_empty() {
+        return Err(invalid(0..0, "a identifier"));
+    }
+    if let Some((idx, ch)) = segment.char_indices().find(|&(_, ch)| !is_segment_char(ch)) {
+        return Err(invalid(idx..idx+ch.len_utf8(), "alphanumeric, '-', '_' or '.'"));
+    }
+    Ok(())
+}
+
+impl<'a> HeaderTryFrom<&'a str> for FeedbackId {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        FeedbackId::parse(value)
+    }
+}
+
+impl HeaderTryFrom<String> for FeedbackId {
+    fn try_from(value: String) -> Result<Self, ComponentCreationError> {
+        FeedbackId::parse(&value)
+    }
+}
+
+impl Display for FeedbackId {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(&self.as_string())
+    }
+}
+
+impl HasMailTypeRequirement for FeedbackId {}
+
+impl EncodableInHeader for FeedbackId {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        // the segments are validated to only contain us-ascii chars
+        handle.write_str(SoftAsciiStr::from_unchecked(&self.as_string()))?;
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn full() -> FeedbackId {
+        FeedbackId::new("campaign-1").unwrap()
+            .with_customer("customer_2").unwrap()
+            .with_mail_type("news.letter").unwrap()
+            .with_sender_id("Sender4").unwrap()
+    }
+
+    #[test]
+    fn full_round_trip() {
+        let id = full();
+        assert_eq!(id.as_string(), "campaign-1:customer_2:news.letter:Sender4");
+        assert_eq!(assert_ok!(FeedbackId::parse(&id.as_string())), id);
+        assert_eq!(id.campaign(), Some("campaign-1"));
+        assert_eq!(id.customer(), Some("customer_2"));
+        assert_eq!(id.mail_type(), Some("news.letter"));
+        assert_eq!(id.sender_id(), Some("Sender4"));
+    }
+
+    #[test]
+    fn partial_segments() {
+        let id = assert_ok!(FeedbackId::new("campaign"));
+        assert_eq!(id.to_string(), "campaign");
+        assert_eq!(id.sender_id(), None);
+
+        let id = assert_ok!(id.with_sender_id("sender"));
+        assert_eq!(id.to_string(), "campaign:::sender");
+        assert_eq!(assert_ok!(FeedbackId::parse("campaign:::sender")), id);
+
+        let parsed = assert_ok!(FeedbackId::parse(" :customer "));
+        assert_eq!(parsed.campaign(), None);
+        assert_eq!(parsed.customer(), Some("customer"));
+        assert_eq!(parsed.to_string(), ":customer");
+
+        // trailing empty segments are dropped
+        assert_eq!(assert_ok!(FeedbackId::parse("campaign::")).to_string(), "campaign");
+    }
+
+    #[test]
+    fn invalid_chars_name_the_segment() {
+        let err = assert_err!(FeedbackId::new("camp aign"));
+        assert_eq!(err.feedback_id_segment(), Some(InvalidFeedbackIdSegment::new(0)));
+        assert_eq!(err.span(), Some(4..5));
+
+        let err = assert_err!(full().with_mail_type("news:letter"));
+        let segment = err.feedback_id_segment().unwrap();
+        assert_eq!(segment.index(), 2);
+        assert_eq!(segment.name(), "mail type");
+        assert_eq!(err.str_context(), Some("news:letter"));
+
+        let err = assert_err!(full().with_customer("kündin"));
+        assert_eq!(err.feedback_id_segment().unwrap().index(), 1);
+        assert_eq!(err.span(), Some(1..3));
+
+        let err = assert_err!(full().with_sender_id(""));
+        assert_eq!(err.feedback_id_segment().unwrap().index(), 3);
+    }
+
+    #[test]
+    fn parse_rejects_invalid_values() {
+        let err = assert_err!(FeedbackId::parse("a:b:c/d:e"));
+        assert_eq!(err.feedback_id_segment().unwrap().index(), 2);
+        assert_eq!(err.span(), Some(5..6));
+        assert_eq!(err.str_context(), Some("a:b:c/d:e"));
+
+        let err = assert_err!(FeedbackId::parse("a:b:c:d:e"));
+        assert_eq!(err.feedback_id_segment(), None);
+        assert_eq!(err.span(), Some(7..8));
+
+        assert_err!(FeedbackId::parse(""));
+        assert_err!(FeedbackId::parse(":::"));
+        assert_err!(FeedbackId::parse("a b"));
+    }
+
+    ec_test!{ encoded_colon_joined, {
+        FeedbackId::try_from("campaign::newsletter:esp")?
+    } => ascii => [
+        Text "campaign::newsletter:esp"
+    ]}
+}
@@ -0,0 +1,188 @@
+use std::fmt::{self, Display};
+
+use soft_ascii::{SoftAsciiChar, SoftAsciiStr, SoftAsciiString};
+
+use internals::MailType;
+use internals::grammar::is_atext;
+use internals::error::EncodingError;
+use internals::encoder::{EncodingWriter, EncodableInHeader};
+use ::HeaderTryFrom;
+use ::error::ComponentCreationError;
+use ::mail_type::{HasMailTypeRequirement, MailTypeRequirement};
+
+use super::Phrase;
+
+/// The component of the `List-Id` header (rfc2919).
+///
+/// It consists of the list identifier (a dot-atom like `list-id.example.com`)
+/// and an optional display phrase, e.g. `Friendly Name <list-id.example.com>`.
+///
+/// The identifier has to be us-ascii and has to contain at least one dot,
+/// as it consists of a list label and a namespace.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ListId {
+    phrase: Option<Phrase>,
+    id: SoftAsciiString
+}
+
+impl ListId {
+
+    /// Creates a new list id from an optional phrase and the (bare) identifier.
+    pub fn new(phrase: Option<Phrase>, id: &str) -> Result<Self, ComponentCreationError> {
+        if !is_list_id(id) {
+            return Err(ComponentCreationError::new_with_str("ListId", id));
+        }
+        let id = SoftAsciiString::from_unchecked(id);
+        Ok(ListId { phrase, id })
+    }
+
+    /// Parses `phrase <id>`, `<id>` or a bare `id`.
+    pub fn parse(value: &str) -> Result<Self, ComponentCreationError> {
+        let value = value.trim();
+        if !value.ends_with('>') {
+            return ListId::new(None, value);
+        }
+
+        let start = value.rfind('<')
+            .ok_or_else(|| ComponentCreationError::new_with_str("ListId", value))?;
+        let id = &value[start+1..value.len()-1];
+        let phrase = value[..start].trim();
+        let phrase =
+            if phrase.is_empty() {
+                None
+            } else {
+                Some(Phrase::new(phrase)?)
+            };
+
+        ListId::new(phrase, id)
+            .map_err(|_| ComponentCreationError::new_with_str("ListId", value))
+    }
+
+    /// Returns the (bare) list identifier, e.g. `list-id.example.com`.
+    pub fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    /// Returns the display phrase if there is one.
+    pub fn phrase(&self) -> Option<&Phrase> {
+        self.phrase.as_ref()
+    }
+}
+
+/// dot-atom-text (us-ascii only) with at least two atoms
+fn is_list_id(id: &str) -> bool {
+    let mut atoms = 0;
+    for atom in id.split('.') {
+        if atom.is_empty() || !atom.chars().all(|ch| is_atext(ch, MailType::Ascii)) {
+            return false;
+        }
+        atoms += 1;
+    }
+    atoms > 1
+}
+
+impl<'a> HeaderTryFrom<&'a str> for ListId {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        ListId::parse(value)
+    }
+}
+
+impl HeaderTryFrom<String> for ListId {
+    fn try_from(value: String) -> Result<Self, ComponentCreationError> {
+        ListId::parse(&value)
+    }
+}
+
+/// Formats the list id as `phrase <id>` or `<id>` if there is no phrase.
+///
+/// Like for `Phrase` the words are neither quoted nor encoded.
+impl Display for ListId {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(phrase) = self.phrase.as_ref() {
+            write!(fter, "{} ", phrase)?;
+        }
+        write!(fter, "<{}>", self.id)
+    }
+}
+
+impl HasMailTypeRequirement for ListId {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        self.phrase.as_ref()
+            .map(|phrase| phrase.mail_type_requirement())
+            .unwrap_or_else(MailTypeRequirement::ascii)
+    }
+}
+
+impl EncodableInHeader for ListId {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        if let Some(phrase) = self.phrase.as_ref() {
+            phrase.encode(handle)?;
+            handle.write_fws();
+        }
+        handle.write_char(SoftAsciiChar::from_unchecked('<'))?;
+        handle.write_str(SoftAsciiStr::from_unchecked(self.id.as_str()))?;
+        handle.write_char(SoftAsciiChar::from_unchecked('>'))?;
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_with_phrase() {
+        let list_id = assert_ok!(ListId::parse("Friendly Name <list-id.example.com>"));
+        assert_eq!(list_id.id(), "list-id.example.com");
+        assert_eq!(list_id.phrase().unwrap().to_string(), "Friendly Name");
+    }
+
+    #[test]
+    fn parse_without_phrase() {
+        let list_id = assert_ok!(ListId::parse(" <list-id.example.com> "));
+        assert_eq!(list_id.id(), "list-id.example.com");
+        assert!(list_id.phrase().is_none());
+
+        let bare = assert_ok!(ListId::parse("list-id.example.com"));
+        assert_eq!(bare, list_id);
+    }
+
+    #[test]
+    fn parse_rejects_invalid_ids() {
+        assert_err!(ListId::parse("<localhost>"));
+        assert_err!(ListId::parse("Name <list..example.com>"));
+        assert_err!(ListId::parse("Name <list id.example.com>"));
+        assert_err!(ListId::parse("Name <lüst.example.com>"));
+        assert_err!(ListId::parse("Name list.example.com>"));
+        assert_err!(ListId::parse(""));
+    }
+
+    #[test]
+    fn display() {
+        let list_id = assert_ok!(ListId::parse("Friendly Name <list-id.example.com>"));
+        assert_eq!(list_id.to_string(), "Friendly Name <list-id.example.com>");
+        let list_id = assert_ok!(ListId::parse("list-id.example.com"));
+        assert_eq!(list_id.to_string(), "<list-id.example.com>");
+    }
+
+    ec_test!{ with_phrase, {
+        ListId::try_from("Friendly Name <list-id.example.com>")?
+    } => ascii => [
+        Text "Friendly",
+        MarkFWS,
+        Text " Name",
+        MarkFWS,
+        Text " <list-id.example.com>"
+    ]}
+
+    ec_test!{ without_phrase, {
+        ListId::try_from("list-id.example.com")?
+    } => ascii => [
+        Text "<list-id.example.com>"
+    ]}
+}
@@ -1,13 +1,19 @@
-use soft_ascii_string::SoftAsciiChar;
+use std::fmt::{self, Display};
+
+use soft_ascii::SoftAsciiChar;
 
 use internals::error::EncodingError;
 use internals::encoder::{EncodableInHeader, EncodingWriter};
 use ::{HeaderTryFrom, HeaderTryInto};
 use ::error::ComponentCreationError;
+use ::mail_type::{HasMailTypeRequirement, MailTypeRequirement};
+use ::data::Input;
 
 use super::Phrase;
-use super::Email;
+use super::{Email, LocalPart, Domain, AddressProfile};
 
+/// Marker used to create a `Mailbox` without display name from a `(NoDisplayName, email)` pair.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
 pub struct NoDisplayName;
 
 #[derive(Debug, Hash, Eq, PartialEq, Clone)]
@@ -18,6 +24,62 @@ pub struct Mailbox {
 
 impl Mailbox {
 
+    /// Parses a mailbox in the form it has in a header, e.g. `Jane Doe <jane@example.com>`.
+    ///
+    /// Accepted are a bare address (`jane@example.com`), an address in angle
+    /// brackets (`<jane@example.com>`) and an address in angle brackets
+    /// preceded by a display name, which can contain quoted strings with
+    /// escaped quotes (`"Doe, Jane \"JD\"" <jane@example.com>`). Encoded
+    /// words in the display name are decoded and a quoted local part is
+    /// unquoted. Comments are accepted where whitespace is allowed, but they
+    /// are not kept.
+    ///
+    /// This is used by `HeaderTryFrom<&str>` (and `String`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate mail_headers;
+    /// use mail_headers::header_components::Mailbox;
+    /// # fn main() {
+    ///
+    /// let mailbox = Mailbox::parse(r#""Doe, Jane" <jane@example.com>"#).unwrap();
+    /// assert_eq!(mailbox.to_string(), "Doe, Jane <jane@example.com>");
+    ///
+    /// let mailbox = Mailbox::parse("jane@example.com (Jane)").unwrap();
+    /// assert!(mailbox.display_name.is_none());
+    /// assert!(Mailbox::parse("Jane Doe jane@example.com").is_err());
+    /// # }
+    /// ```
+    pub fn parse(input: &str) -> Result<Self, ComponentCreationError> {
+        use self::parser_parts::{parse_mailbox, creation_error, offset};
+
+        let parsed = parse_mailbox(input)
+            .map_err(|(rest, expected)| creation_error(input, rest, expected))?;
+
+        let domain_offset = offset(input, parsed.domain_rest);
+        let domain = Domain::try_from(parsed.domain)
+            .map_err(|err| err.shift_span(domain_offset).with_str_context(input))?;
+        let local_part = LocalPart::try_from(parsed.local_part)?;
+        let display_name = match parsed.display_name {
+            Some(display_name) => Some(Phrase::try_from(display_name)?),
+            None => None
+        };
+
+        Ok(Mailbox { display_name, email: Email { local_part, domain } })
+    }
+
+    /// Creates the mailbox like `try_from` and checks its email with given profile.
+    ///
+    /// See `AddressProfile::check`.
+    pub fn try_from_with_profile<T>(input: T, profile: AddressProfile) -> Result<Self, ComponentCreationError>
+        where Mailbox: HeaderTryFrom<T>
+    {
+        let mailbox = <Mailbox as HeaderTryFrom<T>>::try_from(input)?;
+        profile.check(&mailbox.email)?;
+        Ok(mailbox)
+    }
+
     pub fn auto_gen_name<F>(&mut self, default_fn: F) -> Result<(), ComponentCreationError>
         where F: FnOnce(&Email) -> Result<Option<Phrase>, ComponentCreationError>
     {
@@ -34,6 +96,82 @@ impl Mailbox {
         self.auto_gen_name(default_fn)?;
         Ok(self)
     }
+
+    /// Converts the display name and email into shared data, making clones cheap.
+    pub fn into_shared(self) -> Self {
+        Mailbox {
+            display_name: self.display_name.map(Phrase::into_shared),
+            email: self.email.into_shared()
+        }
+    }
+
+    /// Returns a key for sorting mailboxes "by name, then by address" e.g. in a UI.
+    ///
+    /// The name part is the display name with surrounding quotes and
+    /// quoted-pair escapes removed, or the local part of the email if there
+    /// is no display name. As a `Phrase` stores the unencoded text (encoded
+    /// words are only created when encoding) there is nothing to decode.
+    ///
+    /// Both parts are lowercased using `str::to_lowercase`, i.e. a simple
+    /// unicode aware lowercasing but no full case folding or locale specific
+    /// collation.
+    pub fn sort_key(&self) -> MailboxSortKey {
+        let name = match self.display_name {
+            Some(ref phrase) => normalize_display_name(phrase),
+            None => self.email.local_part.as_str().to_owned()
+        };
+        let email = format!("{}@{}", self.email.local_part.as_str(), self.email.domain.as_str());
+        MailboxSortKey {
+            name: name.to_lowercase(),
+            email: email.to_lowercase()
+        }
+    }
+}
+
+/// A key for ordering mailboxes by (normalized) name and then email, see `Mailbox::sort_key`.
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct MailboxSortKey {
+    name: String,
+    email: String
+}
+
+impl MailboxSortKey {
+
+    /// The normalized display name (or local part if there was no display name).
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The normalized email address.
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+}
+
+fn normalize_display_name(phrase: &Phrase) -> String {
+    let joined = phrase.0.iter()
+        .map(|word| word.input.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let unquoted =
+        if joined.len() >= 2 && joined.starts_with('"') && joined.ends_with('"') {
+            &joined[1..joined.len()-1]
+        } else {
+            &joined[..]
+        };
+
+    let mut out = String::with_capacity(unquoted.len());
+    let mut escaped = false;
+    for ch in unquoted.chars() {
+        if !escaped && ch == '\\' {
+            escaped = true;
+        } else {
+            escaped = false;
+            out.push(ch);
+        }
+    }
+    out
 }
 
 impl From<Email> for Mailbox {
@@ -53,11 +191,27 @@ impl From<(Option<Phrase>, Email)> for Mailbox {
     }
 }
 
-impl<E> HeaderTryFrom<E> for Mailbox
-    where E: HeaderTryInto<Email>
-{
-    fn try_from(email: E) -> Result<Self, ComponentCreationError> {
-        Ok( Mailbox::from( email.try_into()? ) )
+impl<'a> HeaderTryFrom<&'a str> for Mailbox {
+    fn try_from(input: &'a str) -> Result<Self, ComponentCreationError> {
+        Mailbox::parse(input)
+    }
+}
+
+impl HeaderTryFrom<String> for Mailbox {
+    fn try_from(input: String) -> Result<Self, ComponentCreationError> {
+        Mailbox::parse(&input)
+    }
+}
+
+impl HeaderTryFrom<Input> for Mailbox {
+    fn try_from(input: Input) -> Result<Self, ComponentCreationError> {
+        Mailbox::parse(input.as_str())
+    }
+}
+
+impl HeaderTryFrom<Email> for Mailbox {
+    fn try_from(email: Email) -> Result<Self, ComponentCreationError> {
+        Ok(Mailbox::from(email))
     }
 }
 
@@ -92,6 +246,28 @@ impl<P, E> HeaderTryFrom<(P, E)> for Mailbox
 }
 
 
+/// Formats the mailbox as `Display Name <local-part@domain>` or `local-part@domain`.
+///
+/// This is not the wire form, e.g. the display name is neither quoted nor
+/// encoded and a mailbox without display name is not put in `<>`.
+impl Display for Mailbox {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        match self.display_name {
+            Some(ref display_name) => write!(fter, "{} <{}>", display_name, self.email),
+            None => write!(fter, "{}", self.email)
+        }
+    }
+}
+
+impl HasMailTypeRequirement for Mailbox {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        let name = self.display_name.as_ref()
+            .map(|name| name.mail_type_requirement())
+            .unwrap_or_default();
+        name.combine(self.email.mail_type_requirement())
+    }
+}
+
 impl EncodableInHeader for  Mailbox {
 
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
@@ -112,11 +288,340 @@ impl EncodableInHeader for  Mailbox {
 }
 
 
+mod parser_parts {
+    use std::ops::Range;
+    use nom::IResult;
+    use internals::grammar::is_atext;
+    use internals::MailType;
+    use ::error::ComponentCreationError;
+    use super::super::message_id::parser_parts::{id_left, id_right};
+    use super::super::utils::cfws::skip_cfws;
+    use super::super::utils::encoded_word::decode_encoded_words;
+
+    /// The parts of a parsed mailbox.
+    pub struct ParsedMailbox<'a> {
+        /// The display name with quoted strings unquoted and encoded words decoded.
+        pub display_name: Option<String>,
+        /// The unquoted local part.
+        pub local_part: String,
+        pub domain: &'a str,
+        /// The input starting with the domain, used to locate it in the input.
+        pub domain_rest: &'a str
+    }
+
+    /// The input remaining where parsing failed and what was expected there.
+    pub type ParseError<'a> = (&'a str, &'static str);
+
+    /// Creates the error for an invalid mailbox, including the span of the invalid part.
+    pub fn creation_error(input: &str, rest: &str, expected: &'static str) -> ComponentCreationError {
+        ComponentCreationError::new_with_str("Mailbox", input)
+            .with_span(span_at(input, rest), Some(expected))
+    }
+
+    /// Returns the position of `rest` in `input`, `rest` has to be a suffix of `input`.
+    pub fn offset(input: &str, rest: &str) -> usize {
+        input.len() - rest.len()
+    }
+
+    /// The span of the first char of `rest`, which is empty at the end of the input.
+    fn span_at(input: &str, rest: &str) -> Range<usize> {
+        let start = offset(input, rest);
+        let len = rest.chars().next().map(|ch| ch.len_utf8()).unwrap_or(0);
+        start..start+len
+    }
+
+    /// Parses a bare `addr-spec` or a `name-addr`, both with optional surrounding CFWS.
+    ///
+    /// If neither can be parsed the error of the one which got further is returned.
+    pub fn parse_mailbox(input: &str) -> Result<ParsedMailbox, ParseError> {
+        let start = cfws(input)?;
+        let bare_error = match addr_spec(start) {
+            Ok((parsed, rest)) => match cfws(rest)? {
+                "" => return Ok(parsed),
+                rest => (rest, "end of input")
+            },
+            Err(err) => err
+        };
+
+        match name_addr(start) {
+            Ok(parsed) => Ok(parsed),
+            Err(err) => {
+                if err.0.len() < bare_error.0.len() {
+                    Err(err)
+                } else {
+                    Err(bare_error)
+                }
+            }
+        }
+    }
+
+    /// Parses `[display-name] "<" addr-spec ">"` followed by optional CFWS.
+    fn name_addr(input: &str) -> Result<ParsedMailbox, ParseError> {
+        let mut words = Vec::new();
+        let mut atoms = Vec::new();
+        let mut rest = input;
+        while !rest.starts_with('<') {
+            if rest.starts_with('"') {
+                let (text, tail) = quoted_string(rest)?;
+                push_atoms(&mut atoms, &mut words);
+                words.push(text);
+                rest = cfws(tail)?;
+            } else {
+                let (atom, tail) = done(phrase_atom(rest))
+                    .ok_or((rest, "display name or '<'"))?;
+                atoms.push(atom);
+                rest = cfws(tail)?;
+            }
+        }
+        push_atoms(&mut atoms, &mut words);
+
+        let (mut parsed, rest) = addr_spec(cfws(&rest[1..])?)?;
+        let rest = cfws(rest)?;
+        if !rest.starts_with('>') {
+            return Err((rest, "'>'"));
+        }
+        let rest = cfws(&rest[1..])?;
+        if !rest.is_empty() {
+            return Err((rest, "end of input"));
+        }
+
+        let display_name = words.join(" ");
+        if !display_name.trim().is_empty() {
+            parsed.display_name = Some(display_name);
+        }
+        Ok(parsed)
+    }
+
+    /// Parses `local-part "@" domain`, returns the remaining input.
+    fn addr_spec(input: &str) -> Result<(ParsedMailbox, &str), ParseError> {
+        let (local_part, rest) =
+            if input.starts_with('"') {
+                quoted_string(input)?
+            } else {
+                let (local_part, rest) = done(id_left(input))
+                    .ok_or((input, "dot-atom-text or quoted-string"))?;
+                (local_part.to_owned(), rest)
+            };
+
+        let rest = cfws(rest)?;
+        if !rest.starts_with('@') {
+            return Err((rest, "'@'"));
+        }
+
+        let domain_rest = cfws(&rest[1..])?;
+        let (domain, rest) = done(id_right(domain_rest))
+            .ok_or((domain_rest, "dot-atom-text or domain literal"))?;
+
+        let parsed = ParsedMailbox {
+            display_name: None,
+            local_part,
+            domain,
+            domain_rest
+        };
+        Ok((parsed, rest))
+    }
+
+    /// Parses a `quoted-string`, returns its content without quotes and escapes.
+    ///
+    /// Line breaks (of folding whitespace) are removed from the content.
+    fn quoted_string(input: &str) -> Result<(String, &str), ParseError> {
+        let mut content = String::new();
+        let mut chars = input.char_indices().skip(1);
+        while let Some((idx, ch)) = chars.next() {
+            match ch {
+                '"' => return Ok((content, &input[idx+1..])),
+                '\\' => match chars.next() {
+                    Some((_, escaped)) => content.push(escaped),
+                    None => break
+                },
+                '\r' | '\n' => {},
+                ch => content.push(ch)
+            }
+        }
+        Err((&input[input.len()..], "'\"'"))
+    }
+
+    /// Adds the pending atoms as one word, decoding the encoded words in them.
+    fn push_atoms(atoms: &mut Vec<&str>, words: &mut Vec<String>) {
+        if !atoms.is_empty() {
+            words.push(decode_encoded_words(&atoms.join(" ")));
+            atoms.clear();
+        }
+    }
+
+    /// Skips CFWS, fails for unclosed comments.
+    fn cfws(input: &str) -> Result<&str, ParseError> {
+        skip_cfws(input).ok_or((&input[input.len()..], "')'"))
+    }
+
+    /// Turns a nom result into `(output, rest)`, treating incomplete input as error.
+    fn done<'a>(result: IResult<&'a str, &'a str>) -> Option<(&'a str, &'a str)> {
+        match result {
+            IResult::Done(rest, output) => Some((output, rest)),
+            _ => None
+        }
+    }
+
+    fn phrase_atom(input: &str) -> IResult<&str, &str> {
+        take_while1!(input, is_phrase_char)
+    }
+
+    /// `atext` or `.`, which is allowed in a phrase by the obsolete syntax (e.g. `John Q. Public`).
+    fn is_phrase_char(ch: char) -> bool {
+        ch == '.' || is_atext(ch, MailType::Internationalized)
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use internals::MailType;
+    use internals::encoder::EncodingBuffer;
     use ::header_components::{ Email, Phrase };
+    use ::error::AddressViolation;
     use super::*;
 
+    fn encode(mailbox: &Mailbox) -> String {
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        {
+            let mut handle = encoder.writer();
+            mailbox.encode(&mut handle).unwrap();
+            handle.finish_header();
+        }
+        let encoded = String::from_utf8(encoder.as_slice().to_owned()).unwrap();
+        encoded.trim_end_matches("\r\n").to_owned()
+    }
+
+    fn display_name(mailbox: &Mailbox) -> Option<String> {
+        mailbox.display_name.as_ref().map(|name| name.to_string())
+    }
+
+    #[test]
+    fn parse_bare_and_angle_addr() {
+        let expected = Mailbox::from(Email::try_from("jane@example.com").unwrap());
+        assert_eq!(assert_ok!(Mailbox::parse("jane@example.com")), expected);
+        assert_eq!(assert_ok!(Mailbox::parse("<jane@example.com>")), expected);
+        assert_eq!(assert_ok!(Mailbox::parse("  < jane@example.com >  ")), expected);
+        assert_eq!(assert_ok!(Mailbox::try_from("jane@example.com".to_owned())), expected);
+    }
+
+    #[test]
+    fn parse_display_name() {
+        let mailbox = assert_ok!(Mailbox::parse("Jane Doe <jane@example.com>"));
+        assert_eq!(display_name(&mailbox), Some("Jane Doe".to_owned()));
+        assert_eq!(mailbox.email.to_string(), "jane@example.com");
+
+        let mailbox = assert_ok!(Mailbox::try_from("John Q. Public <john@example.com>"));
+        assert_eq!(display_name(&mailbox), Some("John Q. Public".to_owned()));
+
+        let mailbox = assert_ok!(Mailbox::parse("=?utf-8?q?J=C3=B6rg?= <j@example.com>"));
+        assert_eq!(display_name(&mailbox), Some("Jörg".to_owned()));
+    }
+
+    #[test]
+    fn parse_quoted_display_name() {
+        let mailbox = assert_ok!(Mailbox::parse(r#""Doe, Jane \"JD\"" <jane@example.com>"#));
+        assert_eq!(display_name(&mailbox), Some(r#"Doe, Jane "JD""#.to_owned()));
+
+        let mailbox = assert_ok!(Mailbox::parse(r#"Jane "the \\ one" Doe <jane@example.com>"#));
+        assert_eq!(display_name(&mailbox), Some(r#"Jane the \ one Doe"#.to_owned()));
+
+        let mailbox = assert_ok!(Mailbox::parse(r#""" <jane@example.com>"#));
+        assert_eq!(mailbox.display_name, None);
+    }
+
+    #[test]
+    fn parse_quoted_local_part_and_domain_literal() {
+        let mailbox = assert_ok!(Mailbox::parse(r#"Jane <"jane doe"@example.com>"#));
+        assert_eq!(mailbox.email.local_part.as_str(), "jane doe");
+
+        let mailbox = assert_ok!(Mailbox::parse(r#""a@b"@example.com"#));
+        assert_eq!(mailbox.email.local_part.as_str(), "a@b");
+
+        let mailbox = assert_ok!(Mailbox::parse("jane@[127.0.0.1]"));
+        assert!(mailbox.email.domain.is_literal());
+    }
+
+    #[test]
+    fn parse_skips_comments() {
+        let mailbox = assert_ok!(Mailbox::parse(
+            "(the boss) Jane (nested (comment)) Doe <(c1) jane (c2) @ example.com (c3)> (trailing)"
+        ));
+        assert_eq!(display_name(&mailbox), Some("Jane Doe".to_owned()));
+        assert_eq!(mailbox.email.to_string(), "jane@example.com");
+
+        let mailbox = assert_ok!(Mailbox::parse("jane@example.com (Jane Doe)"));
+        assert_eq!(mailbox.display_name, None);
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_problem() {
+        let cases: &[(&str, ::std::ops::Range<usize>)] = &[
+            ("", 0..0),
+            ("Jane Doe jane@example.com", 13..14),
+            ("jane@exa mple.com", 9..10),
+            ("Jane <jane@example.com", 22..22),
+            ("Jane <jane@example.com> x", 24..25),
+            ("\"Jane <jane@example.com>", 24..24),
+            ("Jane <jane@example.com> (open", 29..29),
+            ("Jane <jane>", 10..11),
+            ("Jane <jane@exa-.com>", 11..15),
+        ];
+        for &(input, ref span) in cases {
+            let err = assert_err!(Mailbox::parse(input));
+            assert_eq!(err.span(), Some(span.clone()), "{}", input);
+        }
+    }
+
+    #[test]
+    fn parse_round_trips_through_encoding() {
+        let inputs = &[
+            "jane@example.com",
+            "<jane@example.com>",
+            "Jane Doe <jane@example.com>",
+            r#""Doe, Jane" <jane@example.com>"#,
+            r#"Jane "\"JD\"" Doe <jane@example.com>"#,
+            r#"Jane <"jane doe"@example.com>"#,
+            "Jane (comment) <jane@[127.0.0.1]>",
+        ];
+        for input in inputs {
+            let parsed = assert_ok!(Mailbox::parse(input));
+            let encoded = encode(&parsed);
+            let reparsed = assert_ok!(Mailbox::parse(&encoded));
+            assert_eq!(reparsed, parsed, "{} => {}", input, encoded);
+            assert_eq!(encode(&reparsed), encoded);
+        }
+        assert_eq!(encode(&assert_ok!(Mailbox::parse("Jane Doe <jane@example.com>"))),
+            "Jane Doe <jane@example.com>");
+    }
+
+    #[test]
+    fn try_from_with_profile() {
+        let strict = AddressProfile::provider_strict();
+        let mailbox = assert_ok!(Mailbox::try_from_with_profile(("John", "john@example.com"), strict));
+        assert_eq!(mailbox.email.to_string(), "john@example.com");
+
+        assert_ok!(Mailbox::try_from_with_profile("john@localhost", AddressProfile::Rfc5322));
+        let err = assert_err!(Mailbox::try_from_with_profile(("John", "john@localhost"), strict));
+        assert_eq!(err.address_violation(), Some(AddressViolation::SingleLabelDomain));
+    }
+
+    #[test]
+    fn sort_key_removes_quotes_and_case() {
+        let quoted = assert_ok!(Mailbox::try_from(("\"Doe, John\"", "a@example.com")));
+        let plain = assert_ok!(Mailbox::try_from(("doe, JOHN", "b@example.com")));
+        assert_eq!(quoted.sort_key().name(), "doe, john");
+        assert_eq!(quoted.sort_key().name(), plain.sort_key().name());
+        assert!(quoted.sort_key() < plain.sort_key());
+    }
+
+    #[test]
+    fn sort_key_falls_back_to_local_part() {
+        let mailbox = assert_ok!(Mailbox::try_from("Tom@Example.com"));
+        let key = mailbox.sort_key();
+        assert_eq!(key.name(), "tom");
+        assert_eq!(key.email(), "tom@example.com");
+    }
+
     ec_test!{ email_only, {
         let email = Email::try_from( "affen@haus" )?;
         Mailbox::from(email)
@@ -208,5 +713,22 @@ mod test {
             assert_eq!(err.to_string(), "creating component DisplayName failed");
         }
     }
-}
 
+    #[test]
+    fn display_with_name() {
+        let mailbox = assert_ok!(Mailbox::try_from(("Tom Tailor", "tom@example.com")));
+        assert_eq!(mailbox.to_string(), "Tom Tailor <tom@example.com>");
+    }
+
+    #[test]
+    fn display_with_utf8_name_and_idn_domain() {
+        let mailbox = assert_ok!(Mailbox::try_from(("Jörg Müller", "jm@bücher.example")));
+        assert_eq!(mailbox.to_string(), "Jörg Müller <jm@bücher.example>");
+    }
+
+    #[test]
+    fn display_without_name() {
+        let mailbox = assert_ok!(Mailbox::try_from("tom@example.com"));
+        assert_eq!(mailbox.to_string(), "tom@example.com");
+    }
+}
@@ -1,13 +1,16 @@
 use std::iter::IntoIterator;
+use std::fmt::{self, Display};
 use vec1::Vec1;
-use soft_ascii_string::SoftAsciiChar;
+use soft_ascii::SoftAsciiChar;
 
 use internals::error::EncodingError;
 use internals::encoder::{EncodableInHeader, EncodingWriter};
 use ::{ HeaderTryFrom, HeaderTryInto};
 use ::error::ComponentCreationError;
+use ::mail_type::{HasMailTypeRequirement, MailTypeRequirement};
 
-use super::Mailbox;
+use super::{Mailbox, AddressProfile};
+use super::utils::cfws::skip_cfws;
 
 #[derive(Debug, Hash, Eq, PartialEq, Clone)]
 pub struct OptMailboxList( pub Vec<Mailbox> );
@@ -15,10 +18,135 @@ pub struct OptMailboxList( pub Vec<Mailbox> );
 #[derive(Debug, Hash, Eq, PartialEq, Clone)]
 pub struct MailboxList( pub Vec1<Mailbox> );
 
+impl OptMailboxList {
+
+    /// Parses a comma separated list of mailboxes like `MailboxList::parse`.
+    ///
+    /// Unlike for `MailboxList` a empty input (or one only consisting of
+    /// CFWS and commas) is accepted and results in the empty list.
+    pub fn parse(input: &str) -> Result<Self, ComponentCreationError> {
+        parse_mailboxes(input).map(OptMailboxList)
+    }
+}
+
 impl MailboxList {
+
+    /// Parses a comma separated list of mailboxes in the form it has in a header.
+    ///
+    /// The input is split at all commas which are not part of a quoted
+    /// string, a comment, a angle address or a domain literal and each part
+    /// is parsed with `Mailbox::parse`, so e.g. display names containing
+    /// commas have to be quoted. As the obsolete syntax allows it empty
+    /// list elements (e.g. from a trailing comma) are skipped. Groups
+    /// (`undisclosed-recipients:;`) are not supported.
+    ///
+    /// If a mailbox is invalid its error is returned, it has the invalid
+    /// part of the list as `str_context` and its span is relative to it.
+    /// If the list contains no mailbox at all a error with the input as
+    /// `str_context` is returned.
+    ///
+    /// This is used by `HeaderTryFrom<&str>` (and `String`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate mail_headers;
+    /// use mail_headers::header_components::MailboxList;
+    /// # fn main() {
+    ///
+    /// let list = MailboxList::parse(r#""A" <a@x.test>, b@y.test, "C, Inc." <c@z.test>"#).unwrap();
+    /// assert_eq!(list.len(), 3);
+    /// assert_eq!(list.to_string(), "A <a@x.test>, b@y.test, C, Inc. <c@z.test>");
+    ///
+    /// let err = MailboxList::parse("a@x.test, C, Inc. <c@z.test>").unwrap_err();
+    /// assert_eq!(err.str_context(), Some("C"));
+    /// # }
+    /// ```
+    pub fn parse(input: &str) -> Result<Self, ComponentCreationError> {
+        let mailboxes = parse_mailboxes(input)?;
+        Vec1::from_vec(mailboxes)
+            .map(MailboxList)
+            .map_err(|_| ComponentCreationError::new_with_str("MailboxList", input)
+                .with_span(input.len()..input.len(), Some("mailbox")))
+    }
+
     pub fn from_single( m: Mailbox ) -> Self {
         MailboxList( Vec1::new( m ) )
     }
+
+    /// Creates the list like `try_from` and checks the email of each mailbox with given profile.
+    ///
+    /// See `AddressProfile::check`.
+    pub fn try_from_with_profile<T>(input: T, profile: AddressProfile) -> Result<Self, ComponentCreationError>
+        where MailboxList: HeaderTryFrom<T>
+    {
+        let list = <MailboxList as HeaderTryFrom<T>>::try_from(input)?;
+        for mailbox in list.0.iter() {
+            profile.check(&mailbox.email)?;
+        }
+        Ok(list)
+    }
+
+    /// Returns the mailboxes sorted by `Mailbox::sort_key`.
+    ///
+    /// The sort is stable, mailboxes with equal keys keep their order. The
+    /// key of each mailbox is only computed once.
+    pub fn sorted(&self) -> Vec<&Mailbox> {
+        let mut mailboxes = self.0.iter().collect::<Vec<_>>();
+        mailboxes.sort_by_cached_key(|mailbox| mailbox.sort_key());
+        mailboxes
+    }
+
+    /// Sorts the mailboxes in place by `Mailbox::sort_key` (stable).
+    ///
+    /// Note that this changes the order in which the mailboxes are encoded.
+    pub fn sort(&mut self) {
+        self.0.sort_by_cached_key(|mailbox| mailbox.sort_key());
+    }
+}
+
+/// Parses each non empty element of the list with `Mailbox::parse`.
+fn parse_mailboxes(input: &str) -> Result<Vec<Mailbox>, ComponentCreationError> {
+    split_list(input).into_iter()
+        .filter(|element| skip_cfws(element) != Some(""))
+        .map(|element| Mailbox::parse(element.trim()))
+        .collect()
+}
+
+/// Splits the list at all `','` not in a quoted string, comment, angle address or domain literal.
+fn split_list(input: &str) -> Vec<&str> {
+    let mut elements = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut comment_depth = 0usize;
+    let mut in_angle = false;
+    let mut in_literal = false;
+    for (idx, ch) in input.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match ch {
+            '\\' if quoted || comment_depth > 0 => escaped = true,
+            '"' if comment_depth == 0 => quoted = !quoted,
+            _ if quoted => {},
+            '(' => comment_depth += 1,
+            ')' if comment_depth > 0 => comment_depth -= 1,
+            _ if comment_depth > 0 => {},
+            '<' => in_angle = true,
+            '>' => in_angle = false,
+            '[' => in_literal = true,
+            ']' => in_literal = false,
+            ',' if !in_angle && !in_literal => {
+                elements.push(&input[start..idx]);
+                start = idx + 1;
+            },
+            _ => {}
+        }
+    }
+    elements.push(&input[start..]);
+    elements
 }
 
 impl IntoIterator for MailboxList {
@@ -32,6 +160,14 @@ impl IntoIterator for MailboxList {
 
 
 
+impl HasMailTypeRequirement for OptMailboxList {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        self.0.iter()
+            .map(|mailbox| mailbox.mail_type_requirement())
+            .fold(MailTypeRequirement::ascii(), MailTypeRequirement::combine)
+    }
+}
+
 impl EncodableInHeader for  OptMailboxList {
 
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
@@ -199,6 +335,51 @@ impl<T> HeaderTryFrom<Vec<T>> for OptMailboxList
     }
 }
 
+impl<'a> HeaderTryFrom<&'a str> for MailboxList {
+    fn try_from(input: &'a str) -> Result<Self, ComponentCreationError> {
+        MailboxList::parse(input)
+    }
+}
+
+impl HeaderTryFrom<String> for MailboxList {
+    fn try_from(input: String) -> Result<Self, ComponentCreationError> {
+        MailboxList::parse(&input)
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for OptMailboxList {
+    fn try_from(input: &'a str) -> Result<Self, ComponentCreationError> {
+        OptMailboxList::parse(input)
+    }
+}
+
+impl HeaderTryFrom<String> for OptMailboxList {
+    fn try_from(input: String) -> Result<Self, ComponentCreationError> {
+        OptMailboxList::parse(&input)
+    }
+}
+
+/// Formats all mailboxes comma separated, see `Mailbox`s `Display` impl.
+impl Display for MailboxList {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, mailbox) in self.0.iter().enumerate() {
+            if idx > 0 {
+                fter.write_str(", ")?;
+            }
+            write!(fter, "{}", mailbox)?;
+        }
+        Ok(())
+    }
+}
+
+impl HasMailTypeRequirement for MailboxList {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        self.0.iter()
+            .map(|mailbox| mailbox.mail_type_requirement())
+            .fold(MailTypeRequirement::ascii(), MailTypeRequirement::combine)
+    }
+}
+
 impl EncodableInHeader for  MailboxList {
 
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
@@ -229,8 +410,51 @@ deref0!{ +mut MailboxList => Vec1<Mailbox> }
 #[cfg(test)]
 mod test {
     use ::header_components::{ Mailbox, Email, Phrase };
+    use ::error::AddressViolation;
     use super::*;
 
+    #[test]
+    fn try_from_with_profile_checks_all_mailboxes() {
+        let strict = AddressProfile::provider_strict();
+        let list = assert_ok!(MailboxList::try_from_with_profile(
+            vec!["a@example.com", "b@example.com"], strict));
+        assert_eq!(list.0.len(), 2);
+
+        let mailboxes = vec!["a@example.com", "b..c@example.com"];
+        assert_ok!(MailboxList::try_from_with_profile(mailboxes.clone(), AddressProfile::Rfc5322));
+        let err = assert_err!(MailboxList::try_from_with_profile(mailboxes, strict));
+        assert_eq!(err.address_violation(), Some(AddressViolation::ConsecutiveDots));
+    }
+
+    #[test]
+    fn sorted_by_name_then_email() {
+        let list = assert_ok!(MailboxList::try_from(vec![
+            ("zoe", "z@example.com"),
+            ("\"Adam\"", "b@example.com"),
+            ("adam", "a@example.com")
+        ]));
+
+        let emails = list.sorted().into_iter()
+            .map(|mailbox| mailbox.sort_key().email().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(emails, vec!["a@example.com", "b@example.com", "z@example.com"]);
+    }
+
+    #[test]
+    fn sort_is_stable() {
+        let mut list = assert_ok!(MailboxList::try_from(vec![
+            ("Same", "same@example.com"),
+            ("other", "other@example.com"),
+            ("same", "Same@example.com")
+        ]));
+
+        list.sort();
+        let names = list.iter()
+            .map(|mailbox| mailbox.display_name.as_ref().unwrap().0.first().input.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["other", "Same", "same"]);
+    }
+
 
     ec_test! { empty_list, {
         OptMailboxList( Vec::new() )
@@ -297,4 +521,79 @@ mod test {
         MarkFWS,
         Text ">"
     ]}
-}
\ No newline at end of file
+
+    fn emails(list: &[Mailbox]) -> Vec<String> {
+        list.iter().map(|mailbox| mailbox.email.to_string()).collect()
+    }
+
+    fn names(list: &[Mailbox]) -> Vec<Option<String>> {
+        list.iter()
+            .map(|mailbox| mailbox.display_name.as_ref().map(|name| name.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_keeps_commas_in_quoted_names() {
+        let list = assert_ok!(MailboxList::parse(
+            r#""A" <a@x.test>, b@y.test, "C, Inc." <c@z.test>"#));
+        assert_eq!(emails(&list), vec!["a@x.test", "b@y.test", "c@z.test"]);
+        assert_eq!(names(&list), vec![Some("A".to_owned()), None, Some("C, Inc.".to_owned())]);
+    }
+
+    #[test]
+    fn parse_handles_cfws_and_empty_elements() {
+        let list = assert_ok!(MailboxList::parse(
+            " (team, all) Tom (the, cat) <tom@example.com> ,\r\n jerry@example.com (mouse, too), ,"));
+        assert_eq!(emails(&list), vec!["tom@example.com", "jerry@example.com"]);
+        assert_eq!(names(&list), vec![Some("Tom".to_owned()), None]);
+
+        let list = assert_ok!(MailboxList::parse(r#""a\", b" <a@x.test>, <b@c>"#));
+        assert_eq!(emails(&list), vec!["a@x.test", "b@c"]);
+        assert_eq!(names(&list), vec![Some("a\", b".to_owned()), None]);
+    }
+
+    #[test]
+    fn parse_empty_list() {
+        for input in &["", "  ", " , ", "(nobody)"] {
+            let err = assert_err!(MailboxList::parse(input));
+            assert_eq!(err.str_context(), Some(*input));
+            assert_eq!(err.span(), Some(input.len()..input.len()));
+
+            let list = assert_ok!(OptMailboxList::parse(input));
+            assert!(list.is_empty());
+        }
+    }
+
+    #[test]
+    fn parse_error_has_invalid_fragment_as_context() {
+        let err = assert_err!(MailboxList::parse("a@x.test, C, Inc. <c@z.test>"));
+        assert_eq!(err.str_context(), Some("C"));
+
+        let err = assert_err!(MailboxList::parse("a@x.test,  Bob <bob@example.com, c@z.test"));
+        assert_eq!(err.str_context(), Some("Bob <bob@example.com, c@z.test"));
+
+        let err = assert_err!(OptMailboxList::parse("a@x.test, \"unclosed <b@y.test>"));
+        assert_eq!(err.str_context(), Some("\"unclosed <b@y.test>"));
+    }
+
+    #[test]
+    fn try_from_str_and_string() {
+        let input = "Tom <tom@example.com>, jerry@example.com";
+        let list = assert_ok!(MailboxList::parse(input));
+        assert_eq!(assert_ok!(MailboxList::try_from(input)), list);
+        assert_eq!(assert_ok!(MailboxList::try_from(input.to_owned())), list);
+
+        let opt = assert_ok!(OptMailboxList::try_from(input));
+        assert_eq!(opt.0, list.0.to_vec());
+        assert_eq!(assert_ok!(OptMailboxList::try_from(String::new())).len(), 0);
+    }
+
+    #[test]
+    fn display_is_comma_separated() {
+        let list = assert_ok!(MailboxList::try_from(vec![
+            ("Tom", "tom@example.com"),
+            ("Jörg", "jm@bücher.example")
+        ]));
+        assert_eq!(list.to_string(), "Tom <tom@example.com>, Jörg <jm@bücher.example>");
+    }
+}
@@ -1,48 +1,99 @@
 use std::{
+    fmt::{self, Display},
     ops::Deref,
     str::FromStr
 };
-#[cfg(feature="serde")]
-use std::fmt;
 
 #[cfg(feature="serde")]
 use serde::{Serialize, Serializer, Deserialize, Deserializer, de::{self, Visitor}};
-use soft_ascii_string::{SoftAsciiStr,SoftAsciiChar};
+use soft_ascii::{SoftAsciiStr,SoftAsciiChar};
 
+use failure::Fail;
 use mime::{
     MediaType as _MediaType,
-    Name, AnyMediaType,
+    Name, AnyMediaType, push_params_to_buffer,
     spec::{MimeSpec, Ascii, Internationalized, Modern}
 };
 use internals::{
-    error::EncodingError,
+    error::{EncodingError, EncodingErrorKind},
     encoder::{EncodingWriter, EncodableInHeader}
 };
 
 use crate::{
     HeaderTryFrom,
-    error::ComponentCreationError
+    error::ComponentCreationError,
+    mail_type::HasMailTypeRequirement,
+    params::unquote_value,
+    rand_source::{RandSource, random_string, use_current_source}
 };
 
-
+use super::media_type_params::{RawParam, ParamIssue, scan_params, resolve};
+
+/// The chars used for generated boundaries (all are bchars and token chars).
+const BOUNDARY_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// A media type (e.g. `text/plain; charset=utf-8`), the component of `Content-Type`.
+///
+/// `MediaType` does not implement `PartialEq`, as it is not clear if the
+/// raw parameters (including duplicates and the way they were split or
+/// encoded) should be compared or just the resolved ones. Compare the
+/// parts you care about (e.g. using `get_param`) instead:
+///
+/// ```compile_fail
+/// # extern crate mail_headers;
+/// use mail_headers::header_components::MediaType;
+///
+/// # fn main() {
+/// assert!(MediaType::parse("text/plain").unwrap() == MediaType::parse("text/plain").unwrap());
+/// # }
+/// ```
 #[derive(Debug, Clone)]
 pub struct MediaType {
     media_type: InternationalizedMediaType,
-    might_need_utf8: bool
+    might_need_utf8: bool,
+    raw_params: Vec<RawParam>
 }
 
 impl MediaType {
 
+    /// Parses a media type including its parameters.
+    ///
+    /// Duplicate parameters are handled as described in `get_param`, i.e.
+    /// later duplicates are ignored (and reported by `param_issues`).
     pub fn parse(media_type: &str) -> Result<Self, ComponentCreationError> {
-        let media_type = InternationalizedMediaType
-            ::parse(media_type)
+        let raw_params = scan_params(media_type);
+        let duplicates = resolve(&raw_params).duplicates;
+
+        let deduplicated;
+        let input = if duplicates.is_empty() {
+            media_type
+        } else {
+            let end = media_type.find(';').unwrap_or(media_type.len());
+            let mut input = media_type[..end].to_owned();
+            for (idx, param) in raw_params.iter().enumerate() {
+                if !duplicates.contains(&idx) {
+                    input.push_str("; ");
+                    input.push_str(&param.name);
+                    input.push('=');
+                    input.push_str(&param.value);
+                }
+            }
+            deduplicated = input;
+            &*deduplicated
+        };
+
+        let parsed = InternationalizedMediaType
+            ::parse(input)
             .map_err(|e|
                 ComponentCreationError
                 ::from_parent(e.to_owned(), "MediaType")
                 .with_str_context(media_type)
             )?;
 
-        Ok(media_type.into())
+        let mut media_type: MediaType = parsed.into();
+        media_type.raw_params = raw_params;
+        Ok(media_type)
     }
 
     pub fn new<T, ST>(type_: T, subtype: ST) -> Result<Self, ComponentCreationError>
@@ -77,19 +128,84 @@ impl MediaType {
         Ok(media_type.into())
     }
 
+    /// Creates a `multipart/mixed` media type with a random boundary.
+    ///
+    /// The boundary only consists of token chars, so it never needs to be quoted.
+    pub fn multipart_mixed_with_boundary_using(source: &mut RandSource) -> Self {
+        let boundary = random_string(source, BOUNDARY_ALPHABET, 40);
+        MediaType::new_with_params("multipart", "mixed", vec![("boundary", boundary)])
+            .expect("[BUG] generated boundary is not valid")
+    }
+
+    /// Like `multipart_mixed_with_boundary_using` but uses the current source of randomness.
+    ///
+    /// See `rand_source::with_rand_source` for making this deterministic.
+    pub fn multipart_mixed_with_boundary() -> Self {
+        use_current_source(MediaType::multipart_mixed_with_boundary_using)
+    }
+
+    /// Removes a parameter.
+    ///
+    /// Afterwards `raw_params` only contains the current parameters, i.e.
+    /// ignored duplicates are no longer listed.
     pub fn remove_param<N>(&mut self, name: N) -> bool
         where N: for<'a> PartialEq<Name<'a>>
     {
-        self.media_type.remove_param(name)
+        let removed = self.media_type.remove_param(name);
+        self.raw_params = raw_params_of(&self.media_type);
+        removed
     }
 
+    /// Sets a parameter.
+    ///
+    /// Afterwards `raw_params` only contains the current parameters, i.e.
+    /// ignored duplicates are no longer listed.
     pub fn set_param<N, V>(&mut self, name: N, value: V)
         where N: AsRef<str>, V: AsRef<str>
     {
-        self.media_type.set_param(name, value)
+        self.media_type.set_param(name, value);
+        self.raw_params = raw_params_of(&self.media_type);
+    }
+
+    /// Returns the value of a parameter (unquoted and decoded).
+    ///
+    /// The name is matched case-insensitive and the following precedence
+    /// rules apply if the parameters contain a parameter multiple times:
+    ///
+    /// - the extended form (`name*=` or sections like `name*0*=`, rfc2231)
+    ///   takes precedence over the plain form (`name=`)
+    /// - later duplicates of a parameter (or of a section of it) are ignored
+    /// - sections are reassembled in numeric order, independent of the order
+    ///   in which they appeared
+    ///
+    /// Everything ignored by this rules is listed by `param_issues`.
+    pub fn get_param(&self, name: &str) -> Option<String> {
+        resolve(&self.raw_params).params
+            .into_iter()
+            .find(|&(ref other, _)| other.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Returns all parameters as they appeared (including ignored duplicates).
+    pub fn raw_params(&self) -> &[RawParam] {
+        &self.raw_params
+    }
+
+    /// Returns everything ignored when applying the precedence rules of `get_param`.
+    pub fn param_issues(&self) -> Vec<ParamIssue> {
+        resolve(&self.raw_params).issues
     }
 }
 
+fn raw_params_of(media_type: &InternationalizedMediaType) -> Vec<RawParam> {
+    media_type.params()
+        .map(|(name, value)| RawParam {
+            name: AsRef::<str>::as_ref(&name).to_owned(),
+            value: value.as_str_repr().to_owned()
+        })
+        .collect()
+}
+
 impl FromStr for MediaType {
     type Err = ComponentCreationError;
     fn from_str(inp: &str) -> Result<Self, Self::Err> {
@@ -111,8 +227,10 @@ type InternationalizedMediaType = _MediaType<MimeSpec<Internationalized, Modern>
 
 impl From<AsciiMediaType> for MediaType {
     fn from(media_type: AsciiMediaType) -> Self {
+        let media_type: InternationalizedMediaType = media_type.into();
         MediaType {
-            media_type: media_type.into(),
+            raw_params: raw_params_of(&media_type),
+            media_type: media_type,
             might_need_utf8: false
         }
     }
@@ -121,6 +239,7 @@ impl From<AsciiMediaType> for MediaType {
 impl From<InternationalizedMediaType> for MediaType {
     fn from(media_type: InternationalizedMediaType) -> Self{
         MediaType {
+            raw_params: raw_params_of(&media_type),
             media_type: media_type,
             might_need_utf8: true
         }
@@ -158,6 +277,9 @@ impl<'a> HeaderTryFrom<&'a str> for MediaType {
 }
 
 
+// non us-ascii parameters are encoded (rfc2231) in ascii mails
+impl HasMailTypeRequirement for MediaType {}
+
 impl EncodableInHeader for  MediaType {
 
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
@@ -172,20 +294,24 @@ impl EncodableInHeader for  MediaType {
             handle.mark_fws_pos();
             handle.write_char(SoftAsciiChar::from_unchecked(';'))?;
             handle.write_fws();
-            //names are always ascii
-            handle.write_str(SoftAsciiStr::from_unchecked(name.as_ref()))?;
 
-            handle.write_char(SoftAsciiChar::from_unchecked('='))?;
-            if no_recheck_needed {
-                handle.write_str_unchecked(value.as_str_repr())?;
+            let repr = value.as_str_repr();
+            if no_recheck_needed || repr.is_ascii() {
+                //names are always ascii
+                handle.write_str(SoftAsciiStr::from_unchecked(name.as_ref()))?;
+                handle.write_char(SoftAsciiChar::from_unchecked('='))?;
+                handle.write_str_unchecked(repr)?;
             } else {
-                match SoftAsciiStr::from_str(value.as_str_repr()) {
-                    Ok(soa) => handle.write_str(soa)?,
-                    Err(_) => {
-                        //TODO encode value ! then write it
-                        unimplemented!();
-                    }
-                }
+                // non us-ascii values in ascii mails need to be encoded (rfc2231)
+                let content = unquote_value(repr)
+                    .map_err(|err| err.context(EncodingErrorKind::Malformed))?;
+                let mut buff = String::new();
+                push_params_to_buffer::<MimeSpec<Ascii, Modern>, _, _, _>(
+                    &mut buff, Some((name.as_ref(), content))
+                ).map_err(|err| err.context(EncodingErrorKind::Malformed))?;
+                // `;` and the following space where already written
+                let encoded = buff.trim_start_matches(|ch: char| ch == ';' || ch == ' ');
+                handle.write_str(SoftAsciiStr::from_unchecked(encoded))?;
             }
         }
         Ok(())
@@ -196,6 +322,20 @@ impl EncodableInHeader for  MediaType {
     }
 }
 
+/// Formats the media type like the wire form of a internationalized mail,
+/// e.g. `text/plain; charset=utf-8`.
+///
+/// Parameter values are written as they are stored (i.e. quoted if needed)
+/// and the output is never folded.
+impl Display for MediaType {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "{}/{}", self.type_().as_ref(), self.subtype().as_ref())?;
+        for (name, value) in self.params() {
+            write!(fter, "; {}={}", name.as_ref(), value.as_str_repr())?;
+        }
+        Ok(())
+    }
+}
 
 #[cfg(feature="serde")]
 impl Serialize for MediaType {
@@ -507,7 +647,55 @@ mod test {
         Text " a*1*=%E2%86%93"
     ]}
 
-
-
-
-}
\ No newline at end of file
+    test!(duplicate_charset_keeps_first {
+        let media_type = MediaType::parse("text/plain; charset=utf-8; charset=iso-8859-1")?;
+        assert_eq!(media_type.get_param("charset"), Some("utf-8".to_owned()));
+        assert_eq!(media_type.get_param("CHARSET"), Some("utf-8".to_owned()));
+        assert_eq!(media_type.raw_params().len(), 2);
+        assert_eq!(media_type.param_issues(), vec![ParamIssue::DuplicateIgnored {
+            name: "charset".to_owned(),
+            value: "iso-8859-1".to_owned()
+        }]);
+    });
+
+    test!(out_of_order_continuations_are_reassembled {
+        let media_type = MediaType::parse(
+            "application/x-stuff; title*1=\" is \"; title*2=nice; title*0=this")?;
+        assert_eq!(media_type.get_param("title"), Some("this is nice".to_owned()));
+        assert_eq!(media_type.raw_params()[0].name, "title*1");
+        assert!(media_type.param_issues().is_empty());
+    });
+
+    test!(extended_form_takes_precedence {
+        let media_type = MediaType::parse(
+            "text/plain; name=\"hallo.txt\"; name*=utf-8''h%C3%A4ll%C3%B6.txt")?;
+        assert_eq!(media_type.get_param("name"), Some("hällö.txt".to_owned()));
+        assert_eq!(media_type.param_issues(), vec![ParamIssue::PlainShadowed {
+            name: "name".to_owned(),
+            value: "\"hallo.txt\"".to_owned()
+        }]);
+    });
+
+    test!(raw_params_follow_modifications {
+        let mut media_type = MediaType::parse("text/plain; charset=utf-8; charset=us-ascii")?;
+        media_type.set_param("format", "flowed");
+        assert_eq!(media_type.raw_params().len(), 2);
+        assert!(media_type.param_issues().is_empty());
+        assert_eq!(media_type.get_param("format"), Some("flowed".to_owned()));
+    });
+
+    test!(constructed_media_types_have_raw_params {
+        let media_type = MediaType::new_with_params("text", "plain", vec![("charset", "utf-8")])?;
+        assert_eq!(media_type.raw_params(), &[RawParam {
+            name: "charset".to_owned(),
+            value: "utf-8".to_owned()
+        }][..]);
+        assert_eq!(media_type.get_param("charset"), Some("utf-8".to_owned()));
+    });
+
+    #[test]
+    fn display() {
+        let media_type = MediaType::try_from("text/plain; a=\"abc def\"; charset=utf-8").unwrap();
+        assert_eq!(media_type.to_string(), "text/plain; a=\"abc def\"; charset=utf-8");
+    }
+}
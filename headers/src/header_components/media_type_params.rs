@@ -0,0 +1,418 @@
+//! Precedence rules for the parameters of a `MediaType` as found in inbound mails.
+//!
+//! Parameters are scanned from the raw header text (so duplicates are not
+//! lost) and then resolved into one value per parameter:
+//!
+//! - extended parameters (`name*`, `name*0*`, rfc2231) take precedence over a plain `name`
+//! - later duplicates of a parameter (or of one section of it) are ignored
+//! - sections (`name*0`, `name*1*`, ...) are reassembled in numeric order,
+//!   independent of the order in which they appear
+//!
+//! Everything which was ignored (or could not be decoded) is reported as `ParamIssue`.
+use std::collections::BTreeMap;
+
+use ::params::unquote_value;
+
+/// A parameter of a media type as it appeared in the header.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RawParam {
+    /// The name including any rfc2231 section/encoding suffix (e.g. `title*0*`).
+    pub name: String,
+    /// The value as written, i.e. including quotes and percent encoding.
+    pub value: String
+}
+
+/// A problem with the parameters of a media type which was resolved by ignoring something.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ParamIssue {
+    /// The parameter (or section of a parameter) appeared more than once, the later one was ignored.
+    DuplicateIgnored { name: String, value: String },
+    /// The plain parameter was ignored because a extended (rfc2231) form of it exists.
+    PlainShadowed { name: String, value: String },
+    /// A section of a parameter is missing, all sections after it were ignored.
+    MissingSection { name: String, section: u32 },
+    /// The extended form of the parameter could not be decoded and was ignored.
+    UndecodableValue { name: String }
+}
+
+/// How a raw parameter name relates to the parameter it's part of.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum NameKind {
+    Plain,
+    Extended,
+    Section { section: u32, encoded: bool }
+}
+
+fn split_name(name: &str) -> (&str, NameKind) {
+    let star = match name.find('*') {
+        Some(star) => star,
+        None => return (name, NameKind::Plain)
+    };
+    let (base, suffix) = (&name[..star], &name[star+1..]);
+    if suffix.is_empty() {
+        return (base, NameKind::Extended);
+    }
+    let (number, encoded) =
+        if suffix.ends_with('*') { (&suffix[..suffix.len()-1], true) } else { (suffix, false) };
+    match number.parse() {
+        Ok(section) if number.bytes().all(|bch| bch.is_ascii_digit()) =>
+            (base, NameKind::Section { section, encoded }),
+        _ => (name, NameKind::Plain)
+    }
+}
+
+/// Scans all parameters of a media type (`type/subtype *(";" name "=" value)`).
+///
+/// This is tolerant, i.e. parameters without a `=` are skipped and a
+/// unterminated quoted string extends to the end of the input.
+pub fn scan_params(input: &str) -> Vec<RawParam> {
+    let mut params = Vec::new();
+    let mut rest = match find_unquoted(input, ';') {
+        Some(idx) => &input[idx+1..],
+        None => return params
+    };
+
+    while !rest.is_empty() {
+        let end = find_unquoted(rest, ';').unwrap_or(rest.len());
+        let param = &rest[..end];
+        if let Some(eq) = param.find('=') {
+            let name = param[..eq].trim();
+            if !name.is_empty() {
+                params.push(RawParam {
+                    name: name.to_owned(),
+                    value: param[eq+1..].trim().to_owned()
+                });
+            }
+        }
+        rest = if end < rest.len() { &rest[end+1..] } else { "" };
+    }
+    params
+}
+
+/// Finds the first `needle` which is not inside of a quoted string.
+fn find_unquoted(input: &str, needle: char) -> Option<usize> {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (idx, ch) in input.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if in_quotes && ch == '\\' {
+            escaped = true;
+        } else if ch == '"' {
+            in_quotes = !in_quotes;
+        } else if !in_quotes && ch == needle {
+            return Some(idx);
+        }
+    }
+    None
+}
+
+#[derive(Default)]
+struct Collected<'a> {
+    name: &'a str,
+    plain: Option<&'a str>,
+    extended: Option<&'a str>,
+    sections: BTreeMap<u32, (bool, &'a str)>
+}
+
+/// The parameters after applying the precedence rules.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Resolved {
+    /// The (decoded) parameters in order of their first appearance.
+    pub params: Vec<(String, String)>,
+    pub issues: Vec<ParamIssue>,
+    /// Indices of the raw params which are duplicates (and where ignored).
+    pub duplicates: Vec<usize>
+}
+
+/// Applies the precedence rules to given raw parameters.
+pub fn resolve(raw: &[RawParam]) -> Resolved {
+    let mut resolved = Resolved::default();
+    let mut collected: Vec<Collected> = Vec::new();
+
+    for (idx, param) in raw.iter().enumerate() {
+        let (base, kind) = split_name(&param.name);
+        let pos = match collected.iter().position(|other| other.name.eq_ignore_ascii_case(base)) {
+            Some(pos) => pos,
+            None => {
+                collected.push(Collected { name: base, ..Default::default() });
+                collected.len() - 1
+            }
+        };
+        let entry = &mut collected[pos];
+        let is_new = match kind {
+            NameKind::Plain => set_once(&mut entry.plain, &param.value),
+            NameKind::Extended => set_once(&mut entry.extended, &param.value),
+            NameKind::Section { section, encoded } => {
+                if entry.sections.contains_key(&section) {
+                    false
+                } else {
+                    entry.sections.insert(section, (encoded, &param.value));
+                    true
+                }
+            }
+        };
+        if !is_new {
+            resolved.duplicates.push(idx);
+            resolved.issues.push(ParamIssue::DuplicateIgnored {
+                name: param.name.clone(),
+                value: param.value.clone()
+            });
+        }
+    }
+
+    for entry in collected {
+        let name = entry.name.to_owned();
+        let mut extended = None;
+        if !entry.sections.is_empty() {
+            extended = reassemble(&entry.name, &entry.sections, &mut resolved.issues);
+        } else if let Some(value) = entry.extended {
+            extended = decode_extended(value);
+            if extended.is_none() {
+                resolved.issues.push(ParamIssue::UndecodableValue { name: name.clone() });
+            }
+        }
+
+        let value = match (extended, entry.plain) {
+            (Some(value), plain) => {
+                if let Some(plain) = plain {
+                    resolved.issues.push(ParamIssue::PlainShadowed {
+                        name: name.clone(),
+                        value: plain.to_owned()
+                    });
+                }
+                value
+            },
+            (None, Some(plain)) => match unquote_value(plain) {
+                Ok(value) => value.into_owned(),
+                Err(_) => plain.to_owned()
+            },
+            (None, None) => continue
+        };
+        resolved.params.push((name, value));
+    }
+    resolved
+}
+
+fn set_once<'a>(slot: &mut Option<&'a str>, value: &'a str) -> bool {
+    if slot.is_some() {
+        false
+    } else {
+        *slot = Some(value);
+        true
+    }
+}
+
+/// Reassembles the sections of a parameter, returns `None` if they can not be decoded.
+fn reassemble(name: &str, sections: &BTreeMap<u32, (bool, &str)>, issues: &mut Vec<ParamIssue>)
+    -> Option<String>
+{
+    let mut charset = None;
+    let mut bytes = Vec::new();
+    let mut decodable = true;
+    for (expected, (&section, &(encoded, value))) in sections.iter().enumerate() {
+        if section != expected as u32 {
+            issues.push(ParamIssue::MissingSection { name: name.to_owned(), section: expected as u32 });
+            if expected == 0 {
+                return None;
+            }
+            break;
+        }
+        let ok = if encoded {
+            let value = if section == 0 {
+                match split_charset(value) {
+                    Some((cs, rest)) => { charset = Some(cs); rest },
+                    None => { decodable = false; break; }
+                }
+            } else {
+                value
+            };
+            percent_decode(value, &mut bytes)
+        } else {
+            match unquote_value(value) {
+                Ok(value) => { bytes.extend_from_slice(value.as_bytes()); true },
+                Err(_) => false
+            }
+        };
+        if !ok {
+            decodable = false;
+            break;
+        }
+    }
+
+    let decoded = if decodable { decode_charset(charset.unwrap_or("utf-8"), bytes) } else { None };
+    if decoded.is_none() {
+        issues.push(ParamIssue::UndecodableValue { name: name.to_owned() });
+    }
+    decoded
+}
+
+/// Decodes a extended value (`charset'language'percent-encoded`).
+fn decode_extended(value: &str) -> Option<String> {
+    let (charset, rest) = split_charset(value)?;
+    let mut bytes = Vec::new();
+    if !percent_decode(rest, &mut bytes) {
+        return None;
+    }
+    decode_charset(charset, bytes)
+}
+
+/// Splits `charset'language'rest` into the charset and the rest.
+fn split_charset(value: &str) -> Option<(&str, &str)> {
+    let mut parts = value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let rest = parts.next()?;
+    Some((charset, rest))
+}
+
+fn percent_decode(value: &str, out: &mut Vec<u8>) -> bool {
+    let mut iter = value.bytes();
+    while let Some(bch) = iter.next() {
+        if bch == b'%' {
+            let high = iter.next().and_then(|bch| (bch as char).to_digit(16));
+            let low = iter.next().and_then(|bch| (bch as char).to_digit(16));
+            match (high, low) {
+                (Some(high), Some(low)) => out.push((high * 16 + low) as u8),
+                _ => return false
+            }
+        } else if bch.is_ascii() {
+            out.push(bch);
+        } else {
+            return false;
+        }
+    }
+    true
+}
+
+pub(crate) fn decode_charset(charset: &str, bytes: Vec<u8>) -> Option<String> {
+    let charset = charset.to_ascii_lowercase();
+    match &*charset {
+        "utf-8" | "utf8" | "" => String::from_utf8(bytes).ok(),
+        "us-ascii" | "ascii" => {
+            if bytes.is_ascii() { String::from_utf8(bytes).ok() } else { None }
+        },
+        "iso-8859-1" | "latin1" | "latin-1" => {
+            Some(bytes.into_iter().map(|bch| bch as char).collect())
+        },
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn resolved(input: &str) -> Resolved {
+        resolve(&scan_params(input))
+    }
+
+    fn param<'a>(resolved: &'a Resolved, name: &str) -> Option<&'a str> {
+        resolved.params.iter()
+            .find(|&&(ref other, _)| other.eq_ignore_ascii_case(name))
+            .map(|&(_, ref value)| &**value)
+    }
+
+    #[test]
+    fn scanning_keeps_duplicates_and_quotes() {
+        let raw = scan_params("text/plain; charset=utf-8; a=\"x;y\"; charset=iso-8859-1");
+        assert_eq!(raw, vec![
+            RawParam { name: "charset".to_owned(), value: "utf-8".to_owned() },
+            RawParam { name: "a".to_owned(), value: "\"x;y\"".to_owned() },
+            RawParam { name: "charset".to_owned(), value: "iso-8859-1".to_owned() }
+        ]);
+    }
+
+    #[test]
+    fn duplicate_charset_keeps_first() {
+        let res = resolved("text/plain; charset=utf-8; charset=iso-8859-1");
+        assert_eq!(res.params, vec![("charset".to_owned(), "utf-8".to_owned())]);
+        assert_eq!(res.issues, vec![ParamIssue::DuplicateIgnored {
+            name: "charset".to_owned(),
+            value: "iso-8859-1".to_owned()
+        }]);
+        assert_eq!(res.duplicates, vec![1]);
+    }
+
+    #[test]
+    fn duplicates_are_case_insensitive() {
+        let res = resolved("text/plain; charset=utf-8; CHARSET=us-ascii");
+        assert_eq!(param(&res, "charset"), Some("utf-8"));
+        assert_eq!(res.issues.len(), 1);
+    }
+
+    #[test]
+    fn out_of_order_sections_are_reassembled() {
+        let res = resolved("text/plain; title*2=\" three\"; title*0=one; title*1=\" two\"");
+        assert_eq!(param(&res, "title"), Some("one two three"));
+        assert!(res.issues.is_empty());
+    }
+
+    #[test]
+    fn encoded_sections_are_decoded() {
+        let res = resolved("text/plain; title*1*=%C3%B6; title*0*=utf-8'de'h%C3%A4l");
+        assert_eq!(param(&res, "title"), Some("hälö"));
+        assert!(res.issues.is_empty());
+    }
+
+    #[test]
+    fn missing_section_truncates() {
+        let res = resolved("text/plain; title*0=one; title*2=three");
+        assert_eq!(param(&res, "title"), Some("one"));
+        assert_eq!(res.issues, vec![ParamIssue::MissingSection {
+            name: "title".to_owned(),
+            section: 1
+        }]);
+    }
+
+    #[test]
+    fn missing_first_section_falls_back_to_plain() {
+        let res = resolved("text/plain; title=plain; title*1=one");
+        assert_eq!(param(&res, "title"), Some("plain"));
+        assert_eq!(res.issues, vec![ParamIssue::MissingSection {
+            name: "title".to_owned(),
+            section: 0
+        }]);
+    }
+
+    #[test]
+    fn duplicate_section_keeps_first() {
+        let res = resolved("text/plain; title*0=one; title*1=\" two\"; title*1=\" other\"");
+        assert_eq!(param(&res, "title"), Some("one two"));
+        assert_eq!(res.issues, vec![ParamIssue::DuplicateIgnored {
+            name: "title*1".to_owned(),
+            value: "\" other\"".to_owned()
+        }]);
+    }
+
+    #[test]
+    fn extended_shadows_plain() {
+        let res = resolved("text/plain; name=\"fallback.txt\"; name*=utf-8''f%C3%BC%C3%9F.txt");
+        assert_eq!(param(&res, "name"), Some("füß.txt"));
+        assert_eq!(res.issues, vec![ParamIssue::PlainShadowed {
+            name: "name".to_owned(),
+            value: "\"fallback.txt\"".to_owned()
+        }]);
+    }
+
+    #[test]
+    fn extended_shadows_plain_independent_of_order() {
+        let res = resolved("text/plain; name*=iso-8859-1''f%FC.txt; name=f.txt");
+        assert_eq!(param(&res, "name"), Some("fü.txt"));
+        assert_eq!(res.issues.len(), 1);
+    }
+
+    #[test]
+    fn undecodable_extended_falls_back_to_plain() {
+        let res = resolved("text/plain; name=f.txt; name*=x-unknown''f%FC.txt");
+        assert_eq!(param(&res, "name"), Some("f.txt"));
+        assert_eq!(res.issues, vec![ParamIssue::UndecodableValue { name: "name".to_owned() }]);
+    }
+
+    #[test]
+    fn plain_values_are_unquoted() {
+        let res = resolved("text/plain; a=\"abc\\\" def\"; b=token");
+        assert_eq!(param(&res, "a"), Some("abc\" def"));
+        assert_eq!(param(&res, "b"), Some("token"));
+    }
+}
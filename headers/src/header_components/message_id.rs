@@ -1,7 +1,7 @@
 use std::fmt::{self, Display};
 use nom::IResult;
 
-use soft_ascii_string::{SoftAsciiChar, SoftAsciiStr, SoftAsciiString};
+use soft_ascii::{SoftAsciiChar, SoftAsciiStr, SoftAsciiString};
 use vec1::Vec1;
 #[cfg(feature="serde")]
 use serde::{
@@ -14,7 +14,13 @@ use internals::error::EncodingError;
 use internals::encoder::{EncodingWriter, EncodableInHeader};
 use ::{HeaderTryFrom, HeaderTryInto};
 use ::error::ComponentCreationError;
+use ::mail_type::{HasMailTypeRequirement, MailTypeRequirement};
 use ::data::{ Input, SimpleItem };
+use ::rand_source::{RandSource, random_string, use_current_source};
+
+/// The chars used for generated id-left parts (all are atext).
+const ID_LEFT_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
 
 /// # Implementation Details
 ///
@@ -51,20 +57,14 @@ impl MessageId {
     {
         use self::{parser_parts as parser};
 
-        match parser::id_left(left_part.as_str()) {
-            IResult::Done( "", _part ) => {},
-            _other => {
-                return Err(ComponentCreationError::new_with_str(
-                    "MessageId", format!("{}@{}", left_part, right_part)));
-            }
-        }
+        let valid =
+            match (parser::id_left(left_part.as_str()), parser::id_right(right_part.as_str())) {
+                (IResult::Done( "", _ ), IResult::Done( "", _ )) => true,
+                _other => false
+            };
 
-        match parser::id_right(right_part.as_str()) {
-            IResult::Done( "", _part ) => {},
-            _other => {
-                return Err(ComponentCreationError::new_with_str(
-                    "MessageId", format!("{}@{}", left_part, right_part)));
-            }
+        if !valid {
+            return Err(parser::creation_error(&format!("{}@{}", left_part, right_part)));
         }
 
         let id = SoftAsciiString::from_unchecked(
@@ -73,6 +73,24 @@ impl MessageId {
         Ok(MessageId { message_id: item })
     }
 
+    /// Generates a new message id with a random left part and given domain as right part.
+    ///
+    /// The left part only consists of chars valid in a dot-atom-text, so this
+    /// only fails if the domain is not a valid id-right.
+    pub fn generate_using(domain: &SoftAsciiStr, source: &mut RandSource)
+        -> Result<Self, ComponentCreationError>
+    {
+        let left = random_string(source, ID_LEFT_ALPHABET, 24);
+        MessageId::new(SoftAsciiStr::from_unchecked(&left), domain)
+    }
+
+    /// Like `generate_using` but uses the current source of randomness.
+    ///
+    /// See `rand_source::with_rand_source` for making this deterministic.
+    pub fn generate(domain: &SoftAsciiStr) -> Result<Self, ComponentCreationError> {
+        use_current_source(|source| MessageId::generate_using(domain, source))
+    }
+
     //FIXME make into AsRef<str> for MessageId
     pub fn as_str( &self ) -> &str {
         self.message_id.as_str()
@@ -118,6 +136,7 @@ impl<'de> Deserialize<'de> for MessageId {
     }
 }
 
+/// Formats the bare id, i.e. without the surrounding `<>` of the wire form.
 impl Display for MessageId {
     fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
         fter.write_str(self.as_str())
@@ -128,14 +147,14 @@ impl<T> HeaderTryFrom<T> for MessageId
     where T: HeaderTryInto<Input>
 {
     fn try_from( input: T ) ->  Result<Self, ComponentCreationError> {
-        use self::parser_parts::parse_message_id;
+        use self::parser_parts::{parse_message_id, creation_error};
 
         let input = input.try_into()?;
 
         match parse_message_id(input.as_str()) {
             IResult::Done( "", _msg_id ) => {},
             _other => {
-                return Err(ComponentCreationError::new_with_str("MessageId", input.as_str()));
+                return Err(creation_error(input.as_str()));
             }
         }
 
@@ -144,6 +163,15 @@ impl<T> HeaderTryFrom<T> for MessageId
     }
 }
 
+impl HasMailTypeRequirement for MessageId {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        match self.message_id {
+            SimpleItem::Ascii(..) => MailTypeRequirement::ascii(),
+            SimpleItem::Utf8(..) => MailTypeRequirement::internationalized()
+        }
+    }
+}
+
 impl EncodableInHeader for  MessageId {
 
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
@@ -163,12 +191,33 @@ impl EncodableInHeader for  MessageId {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
 #[cfg_attr(feature="serde", derive(Serialize, Deserialize))]
 pub struct MessageIdList( pub Vec1<MessageId> );
 
 deref0!{ +mut MessageIdList => Vec1<MessageId> }
 
+/// Formats the ids like the wire form, i.e. as `<id1> <id2>` (but never folded).
+impl Display for MessageIdList {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, id) in self.0.iter().enumerate() {
+            if idx > 0 {
+                fter.write_str(" ")?;
+            }
+            write!(fter, "<{}>", id)?;
+        }
+        Ok(())
+    }
+}
+
+impl HasMailTypeRequirement for MessageIdList {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        self.0.iter()
+            .map(|id| id.mail_type_requirement())
+            .fold(MailTypeRequirement::ascii(), MailTypeRequirement::combine)
+    }
+}
+
 impl EncodableInHeader for  MessageIdList {
 
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
@@ -184,10 +233,48 @@ impl EncodableInHeader for  MessageIdList {
 }
 
 
-mod parser_parts {
+pub(crate) mod parser_parts {
+    use std::ops::Range;
     use nom::IResult;
     use internals::grammar::{is_atext, is_dtext};
     use internals::MailType;
+    use ::error::ComponentCreationError;
+
+    /// Creates the error for an invalid message id, including the span of the invalid part.
+    pub fn creation_error(input: &str) -> ComponentCreationError {
+        let (span, expected) = locate_error(input);
+        ComponentCreationError::new_with_str("MessageId", input)
+            .with_span(span, Some(expected))
+    }
+
+    /// Re-runs the parser step by step to find where `input` stops being a valid message id.
+    ///
+    /// An unexpected end of input is reported as empty span at the end of the input.
+    fn locate_error(input: &str) -> (Range<usize>, &'static str) {
+        let end = input.len();
+        let char_at = |rest: &str| {
+            let start = end - rest.len();
+            let len = rest.chars().next().map(|ch| ch.len_utf8()).unwrap_or(0);
+            start..start+len
+        };
+
+        let rest = match id_left(input) {
+            IResult::Done(rest, _) => rest,
+            IResult::Error(_) => return (char_at(input), "dot-atom-text"),
+            IResult::Incomplete(_) => return (end..end, "dot-atom-text")
+        };
+
+        if !rest.starts_with('@') {
+            return (char_at(rest), "'@'");
+        }
+
+        let right = &rest[1..];
+        match id_right(right) {
+            IResult::Done(rest, _) => (char_at(rest), "end of input"),
+            IResult::Error(_) => (char_at(right), "dot-atom-text or domain literal"),
+            IResult::Incomplete(_) => (end..end, "dot-atom-text or domain literal")
+        }
+    }
 
     pub fn parse_message_id( input: &str) -> IResult<&str, (&str, &str)> {
         do_parse!( input,
@@ -348,6 +435,45 @@ mod test {
         Text "<obst@salat>",
         MarkFWS,
     ]}
-}
 
+    #[test]
+    fn bad_char_in_domain_reports_offset() {
+        let err = assert_err!(MessageId::try_from("abc@exa mple.com"));
+        assert_eq!(err.span(), Some(7..8));
+        assert_eq!(err.expected(), Some("end of input"));
+
+        let err = assert_err!(MessageId::try_from("abc@.com"));
+        assert_eq!(err.span(), Some(4..5));
 
+        let err = assert_err!(MessageId::try_from("a b@example.com"));
+        assert_eq!(err.span(), Some(1..2));
+        assert_eq!(err.expected(), Some("'@'"));
+    }
+
+    #[test]
+    fn truncated_id_reports_end_of_input() {
+        for input in &["abc", "abc@", "abc@example.", "abc@[127.0"] {
+            let err = assert_err!(MessageId::try_from(*input), input);
+            assert_eq!(err.span(), Some(input.len()..input.len()), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn new_reports_span_in_joined_id() {
+        let err = assert_err!(MessageId::new(
+            SoftAsciiStr::from_unchecked("abc"),
+            SoftAsciiStr::from_unchecked("exa mple.com")
+        ));
+        assert_eq!(err.span(), Some(7..8));
+    }
+
+    #[test]
+    fn display_message_id_and_list() {
+        let id = MessageId::try_from("a@example.com").unwrap();
+        assert_eq!(id.to_string(), "a@example.com");
+
+        let other = MessageId::try_from("b@example.com").unwrap();
+        let list = MessageIdList(vec1![id, other]);
+        assert_eq!(list.to_string(), "<a@example.com> <b@example.com>");
+    }
+}
@@ -16,14 +16,23 @@ mod date_time;
 pub use self::date_time::DateTime;
 
 mod email;
-pub use self::email::{ Email, Domain, LocalPart };
+pub use self::email::{ Email, Domain, LocalPart, AddressProfile };
+
+mod confusable;
+pub use self::confusable::{
+    ConfusableReport, ConfusableFinding, ConfusableSeverity,
+    AddressPart, Substitution
+};
 
 mod mailbox;
-pub use self::mailbox::{Mailbox, NoDisplayName};
+pub use self::mailbox::{Mailbox, MailboxSortKey, NoDisplayName};
 
 mod mailbox_list;
 pub use self::mailbox_list::{MailboxList, OptMailboxList };
 
+mod address;
+pub use self::address::{Address, AddressList};
+
 mod transfer_encoding;
 pub use self::transfer_encoding::TransferEncoding;
 
@@ -42,6 +51,9 @@ pub use self::cfws::{ CFWS, FWS };
 mod media_type;
 pub use self::media_type::*;
 
+mod media_type_params;
+pub use self::media_type_params::{RawParam, ParamIssue};
+
 pub type ContentType = MediaType;
 
 mod path;
@@ -64,3 +76,27 @@ pub use self::disposition::*;
 
 mod raw_unstructured;
 pub use self::raw_unstructured::*;
+
+mod raw_field_body;
+pub use self::raw_field_body::{RawFieldBody, RawValidation};
+
+mod shared;
+pub use self::shared::Shared;
+
+mod precedence;
+pub use self::precedence::Precedence;
+
+mod auto_response_suppress;
+pub use self::auto_response_suppress::{AutoResponseSuppress, AutoResponseSuppressToken};
+
+mod list_id;
+pub use self::list_id::ListId;
+
+mod uri_list;
+pub use self::uri_list::UriList;
+
+mod feedback_id;
+pub use self::feedback_id::FeedbackId;
+
+mod spam_status;
+pub use self::spam_status::SpamStatus;
@@ -1,9 +1,10 @@
-use soft_ascii_string::SoftAsciiChar;
+use soft_ascii::SoftAsciiChar;
 
 use internals::error::EncodingError;
 use internals::encoder::{EncodingWriter, EncodableInHeader};
 use ::{HeaderTryFrom, HeaderTryInto};
 use ::error::ComponentCreationError;
+use ::mail_type::{HasMailTypeRequirement, MailTypeRequirement};
 use super::Email;
 
 
@@ -24,6 +25,14 @@ impl<T> HeaderTryFrom<T> for Path
     }
 }
 
+impl HasMailTypeRequirement for Path {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        self.0.as_ref()
+            .map(|email| email.mail_type_requirement())
+            .unwrap_or_default()
+    }
+}
+
 impl EncodableInHeader for  Path {
 
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
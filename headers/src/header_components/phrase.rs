@@ -1,3 +1,5 @@
+use std::fmt::{self, Display};
+
 use vec1::{Vec1, Size0Error};
 
 use internals::grammar::encoded_word::EncodedWordContext;
@@ -6,9 +8,11 @@ use internals::encoder::{EncodingWriter, EncodableInHeader};
 
 use ::{HeaderTryFrom, HeaderTryInto};
 use ::error::ComponentCreationError;
+use ::mail_type::{HasMailTypeRequirement, MailTypeRequirement};
 use ::data::Input;
 
 use super::utils::text_partition::{ Partition, partition };
+use super::utils::encoded_word::decode_encoded_words;
 use super::word::{ Word, do_encode_word };
 use super::{ CFWS, FWS };
 
@@ -59,6 +63,47 @@ impl Phrase {
 
         Ok( Phrase( words ) )
     }
+
+    /// Returns the text of the phrase as it is displayed by mail clients.
+    ///
+    /// Like `Display` this joins the words with a single space, additionally
+    /// it removes surrounding quotes and quoted-pair escapes and decodes
+    /// encoded words (e.g. text given as
+    /// `=?utf-8?q?Caf=C3=A9?=` is returned as `Café`). Encoded words are also
+    /// decoded inside of quotes, as some mail clients do, so this should be
+    /// used for any check on what a reader gets to see (e.g. the display
+    /// name of a `From` mailbox).
+    pub fn to_display_string(&self) -> String {
+        let joined = self.to_string();
+        let unquoted =
+            if joined.len() >= 2 && joined.starts_with('"') && joined.ends_with('"') {
+                &joined[1..joined.len()-1]
+            } else {
+                &joined[..]
+            };
+
+        let mut unescaped = String::with_capacity(unquoted.len());
+        let mut escaped = false;
+        for ch in unquoted.chars() {
+            if !escaped && ch == '\\' {
+                escaped = true;
+            } else {
+                escaped = false;
+                unescaped.push(ch);
+            }
+        }
+        decode_encoded_words(&unescaped)
+    }
+
+    /// Converts the text of all words into shared data, making clones cheap.
+    pub fn into_shared(self) -> Self {
+        let Phrase(mut words) = self;
+        for word in words.iter_mut() {
+            let input = ::std::mem::replace(&mut word.input, Input::from(""));
+            word.input = input.into_shared();
+        }
+        Phrase(words)
+    }
 }
 
 impl<'a> HeaderTryFrom<&'a str> for Phrase {
@@ -81,6 +126,31 @@ impl HeaderTryFrom<Input> for Phrase {
 
 
 
+/// Formats the (decoded) words separated by a single space.
+///
+/// This is not the wire form, words are neither quoted nor encoded.
+impl Display for Phrase {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, word) in self.0.iter().enumerate() {
+            if idx > 0 {
+                fter.write_str(" ")?;
+            }
+            fter.write_str(word.input.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+impl HasMailTypeRequirement for Phrase {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        if self.0.iter().all(|word| word.input.as_str().is_ascii()) {
+            MailTypeRequirement::ascii()
+        } else {
+            MailTypeRequirement::encoded_words()
+        }
+    }
+}
+
 impl EncodableInHeader for  Phrase {
 
     //FEATURE_TODO(warn_on_bad_phrase): warn if the phrase contains chars it should not
@@ -121,7 +191,19 @@ mod test {
         MarkFWS,
         Text " encoding"
     ]}
-}
 
+    #[test]
+    fn display_joins_words() {
+        let phrase = Phrase::try_from(" hm nääds encoding").unwrap();
+        assert_eq!(phrase.to_string(), "hm nääds encoding");
+    }
 
+    #[test]
+    fn display_string_decodes_encoded_words() {
+        let phrase = Phrase::try_from("=?utf-8?q?Caf=C3=A9?= =?utf-8?b?QmFy?= ok").unwrap();
+        assert_eq!(phrase.to_display_string(), "CaféBar ok");
 
+        let phrase = Phrase::try_from("\"Quoted \\\"Name\\\"\"").unwrap();
+        assert_eq!(phrase.to_display_string(), "Quoted \"Name\"");
+    }
+}
@@ -1,4 +1,4 @@
-use soft_ascii_string::SoftAsciiChar;
+use soft_ascii::SoftAsciiChar;
 
 use vec1::{Vec1, Size0Error};
 
@@ -6,6 +6,7 @@ use internals::error::EncodingError;
 use internals::encoder::{EncodingWriter, EncodableInHeader};
 use ::{HeaderTryFrom, HeaderTryInto};
 use ::error::ComponentCreationError;
+use ::mail_type::{HasMailTypeRequirement, MailTypeRequirement};
 
 
 use super::Phrase;
@@ -24,6 +25,14 @@ impl IntoIterator for PhraseList {
 }
 
 
+impl HasMailTypeRequirement for PhraseList {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        self.0.iter()
+            .map(|phrase| phrase.mail_type_requirement())
+            .fold(MailTypeRequirement::ascii(), MailTypeRequirement::combine)
+    }
+}
+
 impl EncodableInHeader for  PhraseList {
 
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
@@ -0,0 +1,142 @@
+use std::fmt::{self, Display};
+
+use soft_ascii::{SoftAsciiStr, SoftAsciiString};
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodingWriter, EncodableInHeader};
+use ::HeaderTryFrom;
+use ::error::ComponentCreationError;
+use ::mail_type::HasMailTypeRequirement;
+
+/// The Precedence header component (non standard, but widely used)
+///
+/// It is mainly used to tell auto-responders that they should not
+/// respond to a given mail, e.g. because it was send to a mailing list.
+///
+/// Values are parsed case-insensitive and always encoded in lowercase.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Precedence {
+    /// `bulk`, e.g. for newsletters
+    Bulk,
+    /// `list`, for mails send through a mailing list
+    List,
+    /// `junk`, for mails which are not worth responding to at all
+    Junk,
+    /// `first-class`
+    FirstClass,
+    /// any other token (stored in lowercase)
+    Other(SoftAsciiString)
+}
+
+impl Precedence {
+
+    /// Parses a precedence value ignoring case.
+    pub fn parse(value: &str) -> Result<Self, ComponentCreationError> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("bulk") {
+            Ok(Precedence::Bulk)
+        } else if value.eq_ignore_ascii_case("list") {
+            Ok(Precedence::List)
+        } else if value.eq_ignore_ascii_case("junk") {
+            Ok(Precedence::Junk)
+        } else if value.eq_ignore_ascii_case("first-class") {
+            Ok(Precedence::FirstClass)
+        } else if !value.is_empty() && value.chars().all(is_token_char) {
+            let token = SoftAsciiString::from_unchecked(value.to_ascii_lowercase());
+            Ok(Precedence::Other(token))
+        } else {
+            Err(ComponentCreationError::new_with_str("Precedence", value))
+        }
+    }
+
+    /// Returns the (lowercase) representation of the value.
+    pub fn as_str(&self) -> &str {
+        match *self {
+            Precedence::Bulk => "bulk",
+            Precedence::List => "list",
+            Precedence::Junk => "junk",
+            Precedence::FirstClass => "first-class",
+            Precedence::Other(ref token) => token.as_str()
+        }
+    }
+}
+
+/// token as defined in rfc2045 (any printable us-ascii char except tspecials)
+fn is_token_char(ch: char) -> bool {
+    match ch {
+        '(' | ')' | '<' | '>' | '@' | ',' | ';' | ':' |
+        '\\' | '"' | '/' | '[' | ']' | '?' | '=' => false,
+        '!'...'~' => true,
+        _ => false
+    }
+}
+
+impl<'a> HeaderTryFrom<&'a str> for Precedence {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        Precedence::parse(value)
+    }
+}
+
+impl HeaderTryFrom<String> for Precedence {
+    fn try_from(value: String) -> Result<Self, ComponentCreationError> {
+        Precedence::parse(&value)
+    }
+}
+
+impl Display for Precedence {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(self.as_str())
+    }
+}
+
+impl HasMailTypeRequirement for Precedence {}
+
+impl EncodableInHeader for Precedence {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        // either a fixed value or a already validated token
+        handle.write_str(SoftAsciiStr::from_unchecked(self.as_str()))?;
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_ignores_case() {
+        assert_eq!(assert_ok!(Precedence::parse("Bulk")), Precedence::Bulk);
+        assert_eq!(assert_ok!(Precedence::parse("LIST")), Precedence::List);
+        assert_eq!(assert_ok!(Precedence::parse(" junk ")), Precedence::Junk);
+        assert_eq!(assert_ok!(Precedence::parse("First-Class")), Precedence::FirstClass);
+        assert_eq!(
+            assert_ok!(Precedence::parse("Special")),
+            Precedence::Other(SoftAsciiString::from_unchecked("special"))
+        );
+    }
+
+    #[test]
+    fn parse_rejects_non_tokens() {
+        assert_err!(Precedence::parse(""));
+        assert_err!(Precedence::parse("bu lk"));
+        assert_err!(Precedence::parse("a;b"));
+        assert_err!(Precedence::parse("bülk"));
+    }
+
+    ec_test!{ bulk, {
+        Precedence::try_from("BULK")?
+    } => ascii => [
+        Text "bulk"
+    ]}
+
+    ec_test!{ other, {
+        Precedence::try_from("Special-Token")?
+    } => ascii => [
+        Text "special-token"
+    ]}
+}
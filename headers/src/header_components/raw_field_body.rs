@@ -0,0 +1,298 @@
+use soft_ascii::SoftAsciiStr;
+
+use internals::MailType;
+use internals::grammar::{is_vchar, is_ws};
+use internals::error::{EncodingError, EncodingErrorKind};
+use internals::encoder::{EncodingWriter, EncodableInHeader};
+use ::{HeaderTryFrom, HeaderTryInto};
+use ::error::ComponentCreationError;
+use ::mail_type::{HasMailTypeRequirement, MailTypeRequirement};
+use ::data::Input;
+
+/// The validation level of a `RawFieldBody`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum RawValidation {
+    /// Only visible chars and whitespace (space, tab), no CR or LF.
+    ///
+    /// This behaves like `RawUnstructured`, i.e. the value is written as is
+    /// without any folding.
+    Checked,
+
+    /// Like `Checked` but the value may contain CRLF if it's immediately followed by WSP.
+    ///
+    /// The folds are passed to the encoder as the only FWS positions of the
+    /// value, i.e. the encoder breaks the line at them if it would be too long
+    /// (and only there), but doesn't keep folds where the line is short enough.
+    /// A value whose lines are filled up to the line length limit is written
+    /// verbatim, the caller is responsible for providing enough fold positions.
+    PreFolded
+}
+
+/// A raw header field body for experimental/not (yet) modeled header fields.
+///
+/// Like `RawUnstructured` this does neither encode anything (non us-ascii
+/// chars are only allowed in internationalized mails) nor does it choose
+/// where to fold the value. Unlike `RawUnstructured` the validation level is
+/// chosen on construction and `RawValidation::PreFolded` allows values which
+/// already have been folded by the caller.
+///
+/// For a one-off header using this component use `def_headers!`, e.g.:
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate mail_headers;
+/// use mail_headers::header_components as components;
+/// use mail_headers::header_components::RawFieldBody;
+///
+/// def_headers! {
+///     test_name: validate_header_names,
+///     scope: components,
+///     ArcSeal, unchecked { "Arc-Seal" }, RawFieldBody, multi, None
+/// }
+///
+/// # fn main() {
+/// let map = headers! {
+///     ArcSeal: RawFieldBody::pre_folded("i=1; a=rsa-sha256;\r\n\tb=Zm9v").unwrap()
+/// }.unwrap();
+/// # let _ = map;
+/// # }
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RawFieldBody {
+    text: Input,
+    validation: RawValidation
+}
+
+impl RawFieldBody {
+
+    /// Creates a new body using the `RawValidation::Checked` level.
+    pub fn checked<T>(text: T) -> Result<Self, ComponentCreationError>
+        where T: HeaderTryInto<Input>
+    {
+        RawFieldBody::new(text, RawValidation::Checked)
+    }
+
+    /// Creates a new body using the `RawValidation::PreFolded` level.
+    pub fn pre_folded<T>(text: T) -> Result<Self, ComponentCreationError>
+        where T: HeaderTryInto<Input>
+    {
+        RawFieldBody::new(text, RawValidation::PreFolded)
+    }
+
+    /// Creates a new body validated with given level.
+    pub fn new<T>(text: T, validation: RawValidation) -> Result<Self, ComponentCreationError>
+        where T: HeaderTryInto<Input>
+    {
+        let text = text.try_into()?;
+        if !is_valid(text.as_str(), validation, MailType::Internationalized) {
+            return Err(ComponentCreationError::new_with_str("RawFieldBody", text.as_str()));
+        }
+        Ok(RawFieldBody { text, validation })
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.text.as_str()
+    }
+
+    pub fn validation(&self) -> RawValidation {
+        self.validation
+    }
+}
+
+fn is_valid(text: &str, validation: RawValidation, mail_type: MailType) -> bool {
+    let is_valid_line = |line: &str| {
+        line.chars().all(|ch| is_ws(ch) || is_vchar(ch, mail_type))
+    };
+
+    match validation {
+        RawValidation::Checked => is_valid_line(text),
+        RawValidation::PreFolded => {
+            let mut lines = text.split("\r\n");
+            // split always returns at last one element
+            let first = lines.next().unwrap();
+            is_valid_line(first) && lines.all(|line| {
+                // the line has to start with WSP and may not only contain WSP
+                line.starts_with(is_ws)
+                    && !line.chars().all(is_ws)
+                    && is_valid_line(line)
+            })
+        }
+    }
+}
+
+impl<T> HeaderTryFrom<T> for RawFieldBody
+    where T: HeaderTryInto<Input>
+{
+    /// Creates a `RawValidation::Checked` body.
+    fn try_from(text: T) -> Result<Self, ComponentCreationError> {
+        RawFieldBody::checked(text)
+    }
+}
+
+impl AsRef<str> for RawFieldBody {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl HasMailTypeRequirement for RawFieldBody {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        if is_valid(&self.text, self.validation, MailType::Ascii) {
+            MailTypeRequirement::ascii()
+        } else if is_valid(&self.text, self.validation, MailType::Internationalized) {
+            MailTypeRequirement::internationalized()
+        } else {
+            MailTypeRequirement::unencodable("contains chars or line breaks which are not allowed")
+        }
+    }
+}
+
+impl EncodableInHeader for RawFieldBody {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        let mail_type = handle.mail_type();
+
+        // revalidate as non us-ascii chars are only valid in internationalized mails
+        if !is_valid(self.text.as_str(), self.validation, mail_type) {
+            return Err(
+                EncodingError::from(EncodingErrorKind::Malformed)
+                    .with_str_context(self.text.as_str())
+            );
+        }
+
+        // a `Checked` value can not contain CRLF, so this is just one line for it
+        sep_for!{ line in self.text.as_str().split("\r\n");
+            sep {
+                // the line starts with WSP, so the fold becomes a FWS position
+                // at which the encoder breaks the line if needed (like for
+                // the whitespace in `Unstructured`)
+                handle.mark_fws_pos();
+            };
+            if mail_type.is_internationalized() {
+                handle.write_utf8(line)?;
+            } else {
+                handle.write_str(SoftAsciiStr::from_unchecked(line))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use soft_ascii::SoftAsciiChar;
+
+    use internals::MailType;
+    use internals::encoder::EncodingBuffer;
+    use ::HeaderKind;
+    use ::header_components::RawUnstructured;
+    use super::*;
+
+    def_headers! {
+        test_name: validate_header_names,
+        scope: self,
+        ArcSeal, unchecked { "Arc-Seal" }, RawFieldBody, multi, None
+    }
+
+    fn encode_line(name: &'static str, body: &EncodableInHeader, mail_type: MailType)
+        -> Result<Vec<u8>, EncodingError>
+    {
+        fn write_line(handle: &mut EncodingWriter, name: &'static str, body: &EncodableInHeader)
+            -> Result<(), EncodingError>
+        {
+            handle.write_str(SoftAsciiStr::from_unchecked(name))?;
+            handle.write_char(SoftAsciiChar::from_unchecked(':'))?;
+            handle.write_fws();
+            body.encode(handle)
+        }
+
+        let mut encoder = EncodingBuffer::new(mail_type);
+        {
+            let mut handle = encoder.writer();
+            match write_line(&mut handle, name, body) {
+                Ok(()) => handle.finish_header(),
+                Err(err) => {
+                    handle.undo_header();
+                    return Err(err);
+                }
+            }
+        }
+        Ok(encoder.as_slice().to_owned())
+    }
+
+    test!(pre_folded_is_emitted_verbatim {
+        let value = "i=1; a=rsa-sha256; t=1526029282; cv=none; d=example.org; s=arc;\r\n\
+                     \tb=dGhpcyBpcyBub3QgYSByZWFsIHNpZ25hdHVyZSwgaXQncyBqdXN0IGZvciB0ZXN0aW5n\r\n\
+                     \t MTIzNDU2Nzg5MA==";
+        let header = ArcSeal::body(RawFieldBody::pre_folded(value)?);
+        let encoded = encode_line("Arc-Seal", header.body(), MailType::Ascii)?;
+        assert_eq!(
+            String::from_utf8(encoded)?,
+            format!("Arc-Seal: {}\r\n", value)
+        );
+    });
+
+    test!(pre_folded_folds_only_at_given_positions {
+        let words = (0..30).map(|idx| format!("tag{:02}=value;", idx)).collect::<Vec<_>>();
+        let value = words.join("\r\n ");
+        let header = ArcSeal::body(RawFieldBody::pre_folded(value.as_str())?);
+        let encoded = String::from_utf8(encode_line("Arc-Seal", header.body(), MailType::Ascii)?)?;
+
+        assert_eq!(encoded.replace("\r\n", ""), format!("Arc-Seal: {}", value.replace("\r\n", "")));
+        let lines = encoded.trim_end_matches("\r\n").split("\r\n").collect::<Vec<_>>();
+        assert!(lines.len() > 1);
+        for line in &lines {
+            assert!(line.len() <= 78, "line too long: {:?}", line);
+        }
+        // lines are only broken between the given words
+        for line in &lines[1..] {
+            assert!(line.starts_with(" tag"), "unexpected fold: {:?}", line);
+        }
+    });
+
+    #[test]
+    fn pre_folded_rejects_bare_line_breaks() {
+        assert_err!(RawFieldBody::pre_folded("a=1;\nb=2"));
+        assert_err!(RawFieldBody::pre_folded("a=1;\rb=2"));
+        assert_err!(RawFieldBody::pre_folded("a=1;\r\n\nb=2"));
+    }
+
+    #[test]
+    fn pre_folded_requires_wsp_after_crlf() {
+        assert_err!(RawFieldBody::pre_folded("a=1;\r\nb=2"));
+        assert_err!(RawFieldBody::pre_folded("a=1;\r\n \t"));
+        assert_err!(RawFieldBody::pre_folded("a=1;\r\n"));
+        assert_ok!(RawFieldBody::pre_folded("a=1;\r\n b=2"));
+    }
+
+    #[test]
+    fn checked_rejects_crlf() {
+        assert_err!(RawFieldBody::checked("a=1;\r\n b=2"));
+        assert_err!(RawFieldBody::checked("a\x07b"));
+        assert_ok!(RawFieldBody::checked("a=1; b=2"));
+    }
+
+    test!(checked_behaves_like_raw_unstructured {
+        let value = "some-raw-token";
+        let raw_body = RawFieldBody::try_from(value)?;
+        assert_eq!(raw_body.validation(), RawValidation::Checked);
+        let raw_unstructured = RawUnstructured::try_from(value)?;
+
+        assert_eq!(
+            encode_line("X-Test", &raw_body, MailType::Ascii)?,
+            encode_line("X-Test", &raw_unstructured, MailType::Ascii)?
+        );
+    });
+
+    #[test]
+    fn utf8_only_in_internationalized_mails() {
+        let body = assert_ok!(RawFieldBody::checked("töken"));
+        assert_err!(encode_line("X-Test", &body, MailType::Ascii));
+        let encoded = assert_ok!(encode_line("X-Test", &body, MailType::Internationalized));
+        assert_eq!(encoded, "X-Test: töken\r\n".as_bytes().to_vec());
+    }
+}
@@ -1,13 +1,15 @@
 //! mail-internals does not ship with any predefined headers and components
 //! except `RawUnstructured`, `TransferEncoding` and `DateTime`
 
-use soft_ascii_string::SoftAsciiStr;
+use soft_ascii::SoftAsciiStr;
 
+use internals::MailType;
 use internals::grammar::is_vchar;
 use internals::error::{EncodingError, EncodingErrorKind};
 use internals::encoder::{EncodingWriter, EncodableInHeader};
 use ::{HeaderTryFrom, HeaderTryInto};
 use ::error::ComponentCreationError;
+use ::mail_type::{HasMailTypeRequirement, MailTypeRequirement};
 use ::data::Input;
 
 
@@ -61,6 +63,18 @@ impl AsRef<str> for RawUnstructured {
     }
 }
 
+impl HasMailTypeRequirement for RawUnstructured {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        if self.text.chars().all(|ch| is_vchar(ch, MailType::Ascii)) {
+            MailTypeRequirement::ascii()
+        } else if self.text.chars().all(|ch| is_vchar(ch, MailType::Internationalized)) {
+            MailTypeRequirement::internationalized()
+        } else {
+            MailTypeRequirement::unencodable("contains chars which are not vchar")
+        }
+    }
+}
+
 impl EncodableInHeader for RawUnstructured {
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
         let mail_type = handle.mail_type();
@@ -1,7 +1,8 @@
-use soft_ascii_string::SoftAsciiChar;
+use soft_ascii::SoftAsciiChar;
 
 use internals::error::EncodingError;
 use internals::encoder::{EncodingWriter, EncodableInHeader};
+use ::mail_type::{HasMailTypeRequirement, MailTypeRequirement};
 
 use super::word::{ Word, do_encode_word };
 use super::{ Email, Domain };
@@ -14,6 +15,16 @@ pub enum ReceivedToken {
     Domain( Domain )
 }
 
+impl HasMailTypeRequirement for ReceivedToken {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        match *self {
+            ReceivedToken::Word(ref word) => word.mail_type_requirement(),
+            ReceivedToken::Address(ref email) => email.mail_type_requirement(),
+            ReceivedToken::Domain(ref domain) => domain.mail_type_requirement()
+        }
+    }
+}
+
 impl EncodableInHeader for  ReceivedToken {
 
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
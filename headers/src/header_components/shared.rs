@@ -0,0 +1,242 @@
+use std::ops::Deref;
+use std::sync::Arc;
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodingWriter, EncodableInHeader};
+use ::HeaderTryFrom;
+use ::error::ComponentCreationError;
+use ::mail_type::{HasMailTypeRequirement, MailTypeRequirement};
+
+/// A component shared (through an `Arc`) between multiple headers.
+///
+/// Cloning a `Shared` only clones the `Arc`, never the component. Clones
+/// of a header (e.g. made when cloning a `HeaderMap`) already share their
+/// body (see `Header`), but headers created independently do not. So this
+/// is useful for large components (e.g. a `MailboxList` with hundreds of
+/// entries) which are used in multiple independently created headers or maps.
+///
+/// The shared component can not be mutated through a `Shared`, i.e. it
+/// only implements `Deref` but not `DerefMut`. To change it create a new
+/// `Shared` (or use `Arc::make_mut` on the result of `into_arc`).
+///
+/// Encoding a `Shared` produces exactly the same output as encoding the
+/// component itself.
+///
+/// (A `impl EncodableInHeader for Arc<T>` is not possible in this crate
+/// as both the trait and `Arc` are foreign to it, so this wrapper is
+/// needed.)
+///
+/// # Example
+///
+/// As `def_headers!` needs the component to be a single identifier, a
+/// type alias has to be used for defining headers with a `Shared` component:
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate mail_headers;
+/// use mail_headers::HeaderTryFrom;
+/// use mail_headers::header_components::{Shared, MailboxList};
+///
+/// pub type SharedMailboxList = Shared<MailboxList>;
+///
+/// def_headers! {
+///     test_name: validate_header_names,
+///     scope: self,
+///     mail_type_requirement: component,
+///     XOriginalTo, unchecked { "X-Original-To" }, SharedMailboxList, maxOne, None
+/// }
+///
+/// # fn main() {
+/// let list = Shared::new(MailboxList::try_from(vec!["a@example.com", "b@example.com"]).unwrap());
+///
+/// let map = headers! {
+///     XOriginalTo: list.clone()
+/// }.unwrap();
+/// # let _ = map;
+/// # }
+/// ```
+///
+/// The shared component can not be modified:
+///
+/// ```compile_fail
+/// # extern crate mail_headers;
+/// use mail_headers::HeaderTryFrom;
+/// use mail_headers::header_components::{Shared, MailboxList};
+///
+/// # fn main() {
+/// let mut list = Shared::new(MailboxList::try_from(vec!["a@example.com"]).unwrap());
+/// list.push(HeaderTryFrom::try_from("b@example.com").unwrap());
+/// # }
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Shared<C>(Arc<C>);
+
+impl<C> Shared<C>
+    where C: EncodableInHeader
+{
+    /// Wraps the component into a new `Arc`.
+    pub fn new(component: C) -> Self {
+        Shared(Arc::new(component))
+    }
+
+    /// Creates a `Shared` from an already existing `Arc`.
+    pub fn from_arc(component: Arc<C>) -> Self {
+        Shared(component)
+    }
+
+    /// Returns a reference to the underlying `Arc`.
+    pub fn as_arc(&self) -> &Arc<C> {
+        &self.0
+    }
+
+    /// Returns the underlying `Arc`.
+    pub fn into_arc(self) -> Arc<C> {
+        self.0
+    }
+
+    /// Returns true if both share the same component instance.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<C> Clone for Shared<C> {
+    /// Clones the `Arc`, not the component.
+    fn clone(&self) -> Self {
+        Shared(self.0.clone())
+    }
+}
+
+impl<C> Deref for Shared<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        &self.0
+    }
+}
+
+impl<C> AsRef<C> for Shared<C> {
+    fn as_ref(&self) -> &C {
+        &self.0
+    }
+}
+
+impl<C> From<Arc<C>> for Shared<C>
+    where C: EncodableInHeader
+{
+    fn from(component: Arc<C>) -> Self {
+        Shared::from_arc(component)
+    }
+}
+
+// Note: a general `impl<T, C: HeaderTryFrom<T>> HeaderTryFrom<T> for Shared<C>`
+// would overlap with the `impl<T> HeaderTryFrom<T> for T` implementation.
+impl<C> HeaderTryFrom<C> for Shared<C>
+    where C: EncodableInHeader
+{
+    fn try_from(component: C) -> Result<Self, ComponentCreationError> {
+        Ok(Shared::new(component))
+    }
+}
+
+impl<C> HeaderTryFrom<Arc<C>> for Shared<C>
+    where C: EncodableInHeader
+{
+    fn try_from(component: Arc<C>) -> Result<Self, ComponentCreationError> {
+        Ok(Shared::from_arc(component))
+    }
+}
+
+impl<C> HasMailTypeRequirement for Shared<C>
+    where C: HasMailTypeRequirement
+{
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        self.0.mail_type_requirement()
+    }
+}
+
+impl<C> EncodableInHeader for Shared<C>
+    where C: EncodableInHeader
+{
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        self.0.encode(handle)
+    }
+
+    fn boxed_clone(&self) -> Box<EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use internals::MailType;
+    use internals::encoder::{EncodingBuffer, EncodableInHeader};
+    use ::{HeaderKind, HeaderMap, HeaderTryFrom};
+    use ::header_components::MailboxList;
+    use super::Shared;
+
+    type SharedMailboxList = Shared<MailboxList>;
+
+    def_headers! {
+        test_name: validate_header_names,
+        scope: self,
+        SharedTo, unchecked { "To" }, SharedMailboxList, maxOne, None,
+        XOriginalTo, unchecked { "X-Original-To" }, SharedMailboxList, maxOne, None
+    }
+
+    fn list() -> MailboxList {
+        MailboxList::try_from(vec!["a@example.com", "b@example.com"]).unwrap()
+    }
+
+    fn encode(value: &EncodableInHeader) -> Vec<u8> {
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        {
+            let mut handle = encoder.writer();
+            value.encode(&mut handle).unwrap();
+            handle.finish_header();
+        }
+        encoder.as_slice().to_owned()
+    }
+
+    test!(two_headers_share_one_list {
+        let shared = Shared::new(list());
+        let mut map = HeaderMap::new();
+        map.insert(SharedTo::body(shared.clone()));
+        map.insert(XOriginalTo::body(shared.clone()));
+
+        let to = map.get_single(SharedTo).unwrap()?;
+        let original_to = map.get_single(XOriginalTo).unwrap()?;
+        assert!(to.body().ptr_eq(original_to.body()));
+        assert!(to.body().ptr_eq(&shared));
+        // the local `shared` and the two headers
+        assert_eq!(Arc::strong_count(shared.as_arc()), 3);
+    });
+
+    test!(cloning_the_map_does_not_copy_the_list {
+        let shared = Shared::new(list());
+        let mut map = HeaderMap::new();
+        map.insert(SharedTo::body(shared.clone()));
+
+        // the local `shared`, the original and the cloned map
+        let cloned = map.clone();
+        let to = cloned.get_single(SharedTo).unwrap()?;
+        assert!(to.body().ptr_eq(&shared));
+        assert_eq!(Arc::strong_count(shared.as_arc()), 3);
+    });
+
+    test!(encodes_like_the_owned_component {
+        let owned = list();
+        let shared = Shared::new(list());
+        assert_eq!(encode(&shared), encode(&owned));
+        assert_eq!(encode(&*shared.boxed_clone()), encode(&owned));
+    });
+
+    test!(can_be_created_from_arc {
+        let arc = Arc::new(list());
+        let shared = Shared::<MailboxList>::try_from(arc.clone())?;
+        assert!(Arc::ptr_eq(shared.as_arc(), &arc));
+        assert_eq!(shared.len(), 2);
+    });
+}
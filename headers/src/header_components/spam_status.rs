@@ -0,0 +1,595 @@
+use std::fmt::{self, Display};
+use std::ops::Range;
+
+use soft_ascii::{SoftAsciiChar, SoftAsciiStr, SoftAsciiString};
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodingWriter, EncodableInHeader};
+use ::HeaderTryFrom;
+use ::error::ComponentCreationError;
+use ::mail_type::HasMailTypeRequirement;
+
+const KNOWN_KEYS: &[&str] = &["score", "hits", "required", "tests"];
+
+/// The component of SpamAssassin style `X-Spam-Status` headers (non standard).
+///
+/// It consists of the verdict, the score, the required score (threshold)
+/// and the names of the tests which matched, optionally followed by other
+/// `key=value` pairs, e.g.
+/// `Yes, score=7.2 required=5.0 tests=BAYES_99,HTML_MESSAGE autolearn=no`.
+///
+/// Parsing is tolerant: keys are case insensitive, `hits` is accepted
+/// instead of `score` (as written by old SpamAssassin versions), the test
+/// list can be folded and unknown pairs are kept (in order) and written
+/// back after the known ones. A list without tests is written as
+/// `tests=none`.
+///
+/// Scores keep the text they were parsed from, so they are written back
+/// exactly as read (`7.20` stays `7.20`). Scores given as `f64` are written
+/// with one decimal place.
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_headers;
+/// use mail_headers::header_components::SpamStatus;
+/// # fn main() {
+///
+/// let status = SpamStatus::parse("Yes, score=7.2 required=5.0 tests=BAYES_99,HTML_MESSAGE").unwrap();
+/// assert!(status.is_spam());
+/// assert_eq!(status.score(), 7.2);
+/// assert!(status.exceeds(5.0));
+/// assert_eq!(status.tests()[0].as_str(), "BAYES_99");
+///
+/// let status = SpamStatus::new(false, 2.46).unwrap().with_required(5.0).unwrap();
+/// assert_eq!(status.to_string(), "No, score=2.5 required=5.0");
+/// # }
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SpamStatus {
+    is_spam: bool,
+    score: SoftAsciiString,
+    required: Option<SoftAsciiString>,
+    tests: Option<Vec<SoftAsciiString>>,
+    other_pairs: Vec<(SoftAsciiString, SoftAsciiString)>
+}
+
+impl SpamStatus {
+
+    /// Creates a new status with given verdict and score, without threshold and tests.
+    ///
+    /// Fails if the score is `NaN` or infinite.
+    pub fn new(is_spam: bool, score: f64) -> Result<Self, ComponentCreationError> {
+        Ok(SpamStatus {
+            is_spam,
+            score: format_score(score)?,
+            required: None,
+            tests: None,
+            other_pairs: Vec::new()
+        })
+    }
+
+    /// Sets the required score, i.e. the threshold the score was compared with.
+    pub fn with_required(mut self, required: f64) -> Result<Self, ComponentCreationError> {
+        self.required = Some(format_score(required)?);
+        Ok(self)
+    }
+
+    /// Sets the names of the tests which matched.
+    ///
+    /// Test names have to be non empty and consist of printable us-ascii
+    /// chars other than `,`.
+    pub fn with_tests<I, T>(mut self, tests: I) -> Result<Self, ComponentCreationError>
+        where I: IntoIterator<Item=T>, T: AsRef<str>
+    {
+        let mut checked = Vec::new();
+        for test in tests {
+            let test = test.as_ref();
+            check_chars(test, is_test_char, "printable us-ascii except ','")?;
+            checked.push(SoftAsciiString::from_unchecked(test));
+        }
+        self.tests = Some(checked);
+        Ok(self)
+    }
+
+    /// Adds a `key=value` pair which is written after the known pairs.
+    ///
+    /// The key has to consist of us-ascii alphanumerics, `-` and `_` and
+    /// can not be one of the known keys (`score`, `hits`, `required` or
+    /// `tests`), the value of printable us-ascii chars.
+    pub fn with_pair(mut self, key: &str, value: &str) -> Result<Self, ComponentCreationError> {
+        check_key(key)?;
+        if !value.is_empty() {
+            check_chars(value, is_value_char, "printable us-ascii")?;
+        }
+        self.other_pairs.push((SoftAsciiString::from_unchecked(key), SoftAsciiString::from_unchecked(value)));
+        Ok(self)
+    }
+
+    /// Parses the header value, e.g. `No, score=-0.1 required=5.0 tests=NONE autolearn=ham`.
+    ///
+    /// The verdict (`Yes` or `No`, case insensitive) and a `score` are
+    /// required, everything else is optional.
+    pub fn parse(value: &str) -> Result<Self, ComponentCreationError> {
+        let invalid = |span: Range<usize>, expected| {
+            ComponentCreationError::new_with_str("SpamStatus", value)
+                .with_span(span, Some(expected))
+        };
+        let end = value.len();
+
+        let mut tokens = tokens(value);
+        if tokens.is_empty() {
+            return Err(invalid(end..end, "'Yes' or 'No'"));
+        }
+        let (start, first) = tokens.remove(0);
+        let word_end = first.find(',').unwrap_or(first.len());
+        let word = &first[..word_end];
+        let is_spam =
+            if word.eq_ignore_ascii_case("yes") { true }
+            else if word.eq_ignore_ascii_case("no") { false }
+            else { return Err(invalid(start..start+word_end, "'Yes' or 'No'")); };
+
+        // the comma after the verdict can be followed by the first pair
+        // without whitespace or be separated from the verdict by whitespace
+        if word_end < first.len() {
+            if word_end + 1 < first.len() {
+                tokens.insert(0, (start + word_end + 1, &first[word_end+1..]));
+            }
+        } else if tokens.first().map(|&(_, next)| next.starts_with(',')).unwrap_or(false) {
+            let (next_start, next) = tokens.remove(0);
+            if next.len() > 1 {
+                tokens.insert(0, (next_start + 1, &next[1..]));
+            }
+        }
+
+        // (span of the key, key, value) where the value of a folded list is joined
+        let mut pairs: Vec<(Range<usize>, &str, String)> = Vec::new();
+        for (start, token) in tokens {
+            if let Some(last) = pairs.last_mut() {
+                if last.2.ends_with(',') {
+                    last.2.push_str(token);
+                    continue;
+                }
+            }
+            match token.find('=') {
+                Some(eq) if eq > 0 => pairs.push((start..start+eq, &token[..eq], token[eq+1..].to_owned())),
+                _ => return Err(invalid(start..start+token.len(), "key=value pair"))
+            }
+        }
+
+        let mut score = None;
+        let mut status = SpamStatus::new(is_spam, 0.0)?;
+        for (span, key, pair_value) in pairs {
+            let value_span = span.end+1..span.end+1+pair_value.len();
+            let duplicate = || invalid(span.clone(), "each key at most once");
+            if key.eq_ignore_ascii_case("score") || key.eq_ignore_ascii_case("hits") {
+                if score.is_some() {
+                    return Err(duplicate());
+                }
+                score = Some(parse_score(&pair_value)
+                    .ok_or_else(|| invalid(value_span, "a decimal number"))?);
+            } else if key.eq_ignore_ascii_case("required") {
+                if status.required.is_some() {
+                    return Err(duplicate());
+                }
+                status.required = Some(parse_score(&pair_value)
+                    .ok_or_else(|| invalid(value_span, "a decimal number"))?);
+            } else if key.eq_ignore_ascii_case("tests") {
+                if status.tests.is_some() {
+                    return Err(duplicate());
+                }
+                let tests = parse_tests(&pair_value)
+                    .ok_or_else(|| invalid(value_span, "comma separated test names"))?;
+                status.tests = Some(tests);
+            } else {
+                if !key.chars().all(is_key_char) {
+                    return Err(invalid(span.clone(), "alphanumeric, '-' or '_'"));
+                }
+                if !pair_value.chars().all(is_value_char) {
+                    return Err(invalid(value_span, "printable us-ascii"));
+                }
+                status.other_pairs.push((
+                    SoftAsciiString::from_unchecked(key),
+                    SoftAsciiString::from_unchecked(pair_value)
+                ));
+            }
+        }
+
+        status.score = score.ok_or_else(|| invalid(end..end, "a score=<number> pair"))?;
+        Ok(status)
+    }
+
+    /// Returns true if the message was classified as spam (`Yes`).
+    pub fn is_spam(&self) -> bool {
+        self.is_spam
+    }
+
+    /// Returns the score.
+    pub fn score(&self) -> f64 {
+        score_value(&self.score)
+    }
+
+    /// Returns the score as written in the header.
+    pub fn score_str(&self) -> &str {
+        self.score.as_str()
+    }
+
+    /// Returns the required score (threshold), if given.
+    pub fn required(&self) -> Option<f64> {
+        self.required.as_ref().map(score_value)
+    }
+
+    /// Returns the required score as written in the header, if given.
+    pub fn required_str(&self) -> Option<&str> {
+        self.required.as_ref().map(|required| required.as_str())
+    }
+
+    /// Returns true if the score is at or above the given threshold.
+    ///
+    /// This is the comparison SpamAssassin does with the required score,
+    /// i.e. a score of `5.0` exceeds a threshold of `5.0`.
+    pub fn exceeds(&self, threshold: f64) -> bool {
+        self.score() >= threshold
+    }
+
+    /// Returns the names of the tests which matched (empty for `tests=none`).
+    pub fn tests(&self) -> &[SoftAsciiString] {
+        self.tests.as_ref().map(|tests| &tests[..]).unwrap_or(&[])
+    }
+
+    /// Returns the `key=value` pairs other than the known ones, in order.
+    pub fn other_pairs(&self) -> &[(SoftAsciiString, SoftAsciiString)] {
+        &self.other_pairs
+    }
+
+    /// Returns the value of the first other pair with given key (ignoring case).
+    pub fn get_pair(&self, key: &str) -> Option<&str> {
+        self.other_pairs.iter()
+            .find(|&&(ref other, _)| other.as_str().eq_ignore_ascii_case(key))
+            .map(|&(_, ref value)| value.as_str())
+    }
+
+    /// Returns the value as written into the header (but without folding).
+    pub fn as_string(&self) -> String {
+        self.parts().join(" ")
+    }
+
+    /// The verdict and the pairs as they are written, separated by (folding) whitespace.
+    fn parts(&self) -> Vec<String> {
+        let mut parts = vec![
+            (if self.is_spam { "Yes," } else { "No," }).to_owned(),
+            format!("score={}", self.score)
+        ];
+        if let Some(ref required) = self.required {
+            parts.push(format!("required={}", required));
+        }
+        if let Some(ref tests) = self.tests {
+            if tests.is_empty() {
+                parts.push("tests=none".to_owned());
+            } else {
+                let tests = tests.iter().map(|test| test.as_str()).collect::<Vec<_>>();
+                parts.push(format!("tests={}", tests.join(",")));
+            }
+        }
+        for &(ref key, ref value) in self.other_pairs.iter() {
+            parts.push(format!("{}={}", key, value));
+        }
+        parts
+    }
+}
+
+/// Splits the value at whitespace, returning each token with its start index.
+fn tokens(value: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (idx, ch) in value.char_indices() {
+        match (ch.is_whitespace(), start) {
+            (true, Some(token_start)) => {
+                tokens.push((token_start, &value[token_start..idx]));
+                start = None;
+            },
+            (false, None) => start = Some(idx),
+            _ => {}
+        }
+    }
+    if let Some(token_start) = start {
+        tokens.push((token_start, &value[token_start..]));
+    }
+    tokens
+}
+
+fn format_score(score: f64) -> Result<SoftAsciiString, ComponentCreationError> {
+    if !score.is_finite() {
+        return Err(ComponentCreationError::new_with_str("SpamStatus", score.to_string()));
+    }
+    Ok(SoftAsciiString::from_unchecked(format!("{:.1}", score)))
+}
+
+/// Parses a decimal number with optional sign and fraction, keeping the text.
+fn parse_score(score: &str) -> Option<SoftAsciiString> {
+    let digits = score.trim_start_matches(|ch: char| ch == '-' || ch == '+');
+    if score.len() - digits.len() > 1 {
+        return None;
+    }
+    let mut parts = digits.splitn(2, '.');
+    let integer = parts.next().unwrap_or("");
+    let fraction = parts.next();
+    let is_digits = |part: &str| !part.is_empty() && part.chars().all(|ch| ch.is_ascii_digit());
+    if is_digits(integer) && fraction.map(is_digits).unwrap_or(true) {
+        Some(SoftAsciiString::from_unchecked(score))
+    } else {
+        None
+    }
+}
+
+fn score_value(score: &SoftAsciiString) -> f64 {
+    score.as_str().parse().expect("[BUG] scores are validated to be decimal numbers")
+}
+
+/// Parses a comma separated list of test names, `none` is the empty list.
+fn parse_tests(tests: &str) -> Option<Vec<SoftAsciiString>> {
+    if tests.eq_ignore_ascii_case("none") {
+        return Some(Vec::new());
+    }
+    let mut parsed = Vec::new();
+    // empty entries (e.g. a trailing comma) are ignored
+    for test in tests.split(',').filter(|test| !test.is_empty()) {
+        if !test.chars().all(is_test_char) {
+            return None;
+        }
+        parsed.push(SoftAsciiString::from_unchecked(test));
+    }
+    Some(parsed)
+}
+
+fn is_test_char(ch: char) -> bool {
+    ch.is_ascii_graphic() && ch != ','
+}
+
+fn is_key_char(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '-' || ch == '_'
+}
+
+fn is_value_char(ch: char) -> bool {
+    ch.is_ascii_graphic()
+}
+
+fn check_key(key: &str) -> Result<(), ComponentCreationError> {
+    check_chars(key, is_key_char, "alphanumeric, '-' or '_'")?;
+    if KNOWN_KEYS.iter().any(|known| known.eq_ignore_ascii_case(key)) {
+        return Err(ComponentCreationError::new_with_str("SpamStatus", key)
+            .with_span(0..key.len(), Some("a key other than score, hits, required or tests")));
+    }
+    Ok(())
+}
+
+/// Checks that `text` is non empty and only contains chars accepted by `is_valid`.
+fn check_chars<F>(text: &str, is_valid: F, expected: &'static str) -> Result<(), ComponentCreationError>
+    where F: Fn(char) -> bool
+{
+    let invalid = |span| {
+        ComponentCreationError::new_with_str("SpamStatus", text)
+            .with_span(span, Some(expected))
+    };
+    if text.is_empty() {
+        return Err(invalid(0..0));
+    }
+    if let Some((idx, ch)) = text.char_indices().find(|&(_, ch)| !is_valid(ch)) {
+        return Err(invalid(idx..idx+ch.len_utf8()));
+    }
+    Ok(())
+}
+
+impl<'a> HeaderTryFrom<&'a str> for SpamStatus {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        SpamStatus::parse(value)
+    }
+}
+
+impl HeaderTryFrom<String> for SpamStatus {
+    fn try_from(value: String) -> Result<Self, ComponentCreationError> {
+        SpamStatus::parse(&value)
+    }
+}
+
+impl Display for SpamStatus {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(&self.as_string())
+    }
+}
+
+impl HasMailTypeRequirement for SpamStatus {}
+
+impl EncodableInHeader for SpamStatus {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        // all parts are validated to only contain us-ascii chars
+        for (idx, part) in self.parts().iter().enumerate() {
+            if idx > 0 {
+                handle.write_fws();
+            }
+            // allow folding a long test list after each comma
+            for (piece_idx, piece) in part.split(',').enumerate() {
+                if piece_idx > 0 {
+                    handle.write_char(SoftAsciiChar::from_unchecked(','))?;
+                    handle.mark_fws_pos();
+                }
+                if !piece.is_empty() {
+                    handle.write_str(SoftAsciiStr::from_unchecked(piece))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use internals::MailType;
+    use internals::encoder::EncodingBuffer;
+    use super::*;
+
+    const EXAMPLES: &[&str] = &[
+        "Yes, score=7.2 required=5.0 tests=BAYES_99,HTML_MESSAGE autolearn=no version=3.4.2",
+        "No, score=-0.1 required=5.0 tests=DKIM_SIGNED,DKIM_VALID,SPF_PASS autolearn=ham autolearn_force=no version=3.4.1",
+        "No, score=0.0 required=6.31 tests=none",
+        "Yes, score=15.20 required=5.0 tests=URIBL_BLACK",
+        "No, score=1.3"
+    ];
+
+    fn encode(status: &SpamStatus) -> String {
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        {
+            let mut handle = encoder.writer();
+            status.encode(&mut handle).unwrap();
+            handle.finish_header();
+        }
+        let encoded = String::from_utf8(encoder.as_slice().to_owned()).unwrap();
+        encoded.trim_end_matches("\r\n").to_owned()
+    }
+
+    fn tests(status: &SpamStatus) -> Vec<&str> {
+        status.tests().iter().map(|test| test.as_str()).collect()
+    }
+
+    #[test]
+    fn real_world_examples_round_trip() {
+        for example in EXAMPLES {
+            let status = assert_ok!(SpamStatus::parse(example));
+            assert_eq!(status.as_string(), *example);
+            // longer examples are folded when encoded
+            assert_eq!(assert_ok!(SpamStatus::parse(&encode(&status))), status);
+        }
+        assert_eq!(encode(&assert_ok!(SpamStatus::parse(EXAMPLES[3]))), EXAMPLES[3]);
+
+        let status = assert_ok!(SpamStatus::parse(EXAMPLES[0]));
+        assert!(status.is_spam());
+        assert_eq!(status.score(), 7.2);
+        assert_eq!(status.required(), Some(5.0));
+        assert_eq!(tests(&status), vec!["BAYES_99", "HTML_MESSAGE"]);
+
+        let status = assert_ok!(SpamStatus::parse(EXAMPLES[2]));
+        assert!(!status.is_spam());
+        assert!(status.tests().is_empty());
+        assert_eq!(status.required(), Some(6.31));
+
+        let status = assert_ok!(SpamStatus::parse(EXAMPLES[4]));
+        assert_eq!(status.required(), None);
+        assert!(status.tests().is_empty());
+    }
+
+    #[test]
+    fn parsing_is_tolerant() {
+        let status = assert_ok!(SpamStatus::parse(
+            "  yes , HITS=7.2  Required=5.0 tests=BAYES_99,\r\n\tHTML_MESSAGE,\r\n RDNS_NONE "
+        ));
+        assert!(status.is_spam());
+        assert_eq!(status.score_str(), "7.2");
+        assert_eq!(tests(&status), vec!["BAYES_99", "HTML_MESSAGE", "RDNS_NONE"]);
+        assert_eq!(status.as_string(), "Yes, score=7.2 required=5.0 tests=BAYES_99,HTML_MESSAGE,RDNS_NONE");
+
+        let status = assert_ok!(SpamStatus::parse("No,score=1.0"));
+        assert_eq!(status.as_string(), "No, score=1.0");
+        let status = assert_ok!(SpamStatus::parse("No ,score=1.0 tests=A"));
+        assert_eq!(tests(&status), vec!["A"]);
+        assert_ok!(SpamStatus::parse("NO score=1.0"));
+    }
+
+    #[test]
+    fn unknown_pairs_are_preserved() {
+        let status = assert_ok!(SpamStatus::parse(
+            "No, autolearn=ham score=-1.9 shortcircuit=no required=5.0 version=3.4.2"
+        ));
+        let pairs = status.other_pairs().iter()
+            .map(|&(ref key, ref value)| (key.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, vec![("autolearn", "ham"), ("shortcircuit", "no"), ("version", "3.4.2")]);
+        assert_eq!(status.get_pair("AutoLearn"), Some("ham"));
+        assert_eq!(status.get_pair("score"), None);
+        assert_eq!(
+            status.to_string(),
+            "No, score=-1.9 required=5.0 autolearn=ham shortcircuit=no version=3.4.2"
+        );
+
+        let created = SpamStatus::new(false, -1.9)
+            .and_then(|status| status.with_required(5.0))
+            .and_then(|status| status.with_pair("autolearn", "ham"))
+            .and_then(|status| status.with_pair("shortcircuit", "no"))
+            .and_then(|status| status.with_pair("version", "3.4.2"))
+            .unwrap();
+        assert_eq!(created, status);
+        assert_err!(assert_ok!(SpamStatus::new(false, 1.0)).with_pair("Tests", "A"));
+        assert_err!(assert_ok!(SpamStatus::new(false, 1.0)).with_pair("a key", "b"));
+    }
+
+    #[test]
+    fn scores_keep_their_text() {
+        let status = assert_ok!(SpamStatus::parse("Yes, score=7.20 required=+5"));
+        assert_eq!(status.score_str(), "7.20");
+        assert_eq!(status.required_str(), Some("+5"));
+        assert_eq!(status.as_string(), "Yes, score=7.20 required=+5");
+
+        // 7.2 is not exactly representable, but is written as parsed
+        let status = assert_ok!(SpamStatus::new(true, 7.199999999999999));
+        assert_eq!(status.score_str(), "7.2");
+        let status = assert_ok!(SpamStatus::new(true, 0.1 + 0.2));
+        assert_eq!(status.as_string(), "Yes, score=0.3");
+        assert_eq!(assert_ok!(SpamStatus::parse(&status.as_string())), status);
+
+        assert_err!(SpamStatus::new(true, ::std::f64::NAN));
+        assert_err!(SpamStatus::new(true, ::std::f64::INFINITY));
+        assert_err!(assert_ok!(SpamStatus::new(true, 1.0)).with_required(::std::f64::NEG_INFINITY));
+    }
+
+    #[test]
+    fn exceeds_compares_like_spamassassin() {
+        let status = assert_ok!(SpamStatus::parse("Yes, score=5.0 required=5.0"));
+        assert!(status.exceeds(5.0));
+        assert!(status.exceeds(4.9));
+        assert!(!status.exceeds(5.1));
+        assert!(status.exceeds(status.required().unwrap()));
+
+        let status = assert_ok!(SpamStatus::parse("No, score=-2.5"));
+        assert!(status.exceeds(-3.0));
+        assert!(!status.exceeds(0.0));
+    }
+
+    #[test]
+    fn long_test_lists_are_folded() {
+        let tests = (0..20).map(|idx| format!("SOME_LONG_TEST_NAME_{}", idx)).collect::<Vec<_>>();
+        let status = assert_ok!(assert_ok!(SpamStatus::new(true, 12.5)).with_tests(&tests));
+        let encoded = encode(&status);
+        assert!(encoded.contains("\r\n"));
+        for line in encoded.split("\r\n") {
+            assert!(line.len() <= 78, "{:?}", line);
+        }
+        assert_eq!(assert_ok!(SpamStatus::parse(&encoded)), status);
+    }
+
+    #[test]
+    fn invalid_values() {
+        let cases: &[(&str, Range<usize>)] = &[
+            ("", 0..0),
+            ("Maybe, score=1.0", 0..5),
+            ("Yes, required=5.0", 18..18),
+            ("Yes, score=1.0 oops", 15..19),
+            ("Yes, score=1.0.0", 11..16),
+            ("Yes, score=abc", 11..14),
+            ("Yes, score=1.0 score=2.0", 15..20),
+            ("Yes, score=1.0 ke y=2", 15..17),
+            ("Yes, score=1.0 k?y=2", 15..18),
+            ("Yes, score=1.0 tests=ä", 21..23),
+            ("Yes, score=1.0 x=ä", 17..19),
+        ];
+        for &(input, ref span) in cases {
+            let err = assert_err!(SpamStatus::parse(input));
+            assert_eq!(err.span(), Some(span.clone()), "{}", input);
+        }
+        assert_err!(assert_ok!(SpamStatus::new(true, 1.0)).with_tests(vec!["A,B"]));
+        assert_err!(assert_ok!(SpamStatus::new(true, 1.0)).with_tests(vec![""]));
+    }
+}
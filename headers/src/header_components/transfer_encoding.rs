@@ -1,7 +1,10 @@
-use soft_ascii_string::SoftAsciiStr;
+use std::fmt::{self, Display};
+
+use soft_ascii::SoftAsciiStr;
 
 use internals::error::EncodingError;
 use internals::encoder::{EncodingWriter, EncodableInHeader};
+use ::mail_type::HasMailTypeRequirement;
 
 #[cfg(feature="serde")]
 use serde::{Serialize, Deserialize};
@@ -36,6 +39,15 @@ impl TransferEncoding {
     }
 }
 
+/// Formats the encoding like the wire form, e.g. `quoted-printable`.
+impl Display for TransferEncoding {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(self.repr().as_str())
+    }
+}
+
+
+impl HasMailTypeRequirement for TransferEncoding {}
 
 impl EncodableInHeader for  TransferEncoding {
 
@@ -112,6 +124,10 @@ mod test {
         }
 
     }
-}
-
 
+    #[test]
+    fn display() {
+        assert_eq!(TransferEncoding::QuotedPrintable.to_string(), "quoted-printable");
+        assert_eq!(TransferEncoding::Base64.to_string(), "base64");
+    }
+}
@@ -2,7 +2,7 @@ use std::ops::{ Deref, DerefMut};
 use std::fmt::{self, Display};
 
 use failure::Fail;
-use soft_ascii_string::SoftAsciiChar;
+use soft_ascii::SoftAsciiChar;
 
 use internals::grammar::is_vchar;
 use internals::error::{EncodingError, EncodingErrorKind};
@@ -10,6 +10,7 @@ use internals::encoder::{EncodingWriter, EncodableInHeader};
 use internals::bind::encoded_word::{EncodedWordEncoding, WriterWrapper};
 use ::{HeaderTryFrom, HeaderTryInto};
 use ::error::ComponentCreationError;
+use ::mail_type::{HasMailTypeRequirement, MailTypeRequirement};
 use ::data::Input;
 
 use super::utils::text_partition::{partition, Partition};
@@ -20,6 +21,15 @@ pub struct Unstructured {
     text: Input,
 }
 
+impl Unstructured {
+
+    /// Converts the underlying text into shared data, making clones cheap.
+    pub fn into_shared(self) -> Self {
+        Unstructured { text: self.text.into_shared() }
+    }
+}
+
+/// Formats the (decoded) text, i.e. without encoded words.
 impl Display for Unstructured {
     fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
         fter.write_str(self.as_str())
@@ -40,6 +50,14 @@ impl DerefMut for Unstructured {
     }
 }
 
+impl<T> From<T> for Unstructured
+    where Input: From<T>
+{
+    fn from(val: T) -> Self {
+        Unstructured { text: val.into() }
+    }
+}
+
 impl<T> HeaderTryFrom<T> for Unstructured
     where T: HeaderTryInto<Input>
 {
@@ -51,6 +69,16 @@ impl<T> HeaderTryFrom<T> for Unstructured
 
 
 
+impl HasMailTypeRequirement for Unstructured {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        if self.text.as_str().is_ascii() {
+            MailTypeRequirement::ascii()
+        } else {
+            MailTypeRequirement::encoded_words()
+        }
+    }
+}
+
 impl EncodableInHeader for  Unstructured {
 
     fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
@@ -191,4 +219,10 @@ mod test {
         MarkFWS,
         Text " \t "
     ]}
-}
\ No newline at end of file
+
+    #[test]
+    fn display_is_not_encoded() {
+        let text = Unstructured::try_from("↑ ↓ ←→ bA").unwrap();
+        assert_eq!(text.to_string(), "↑ ↓ ←→ bA");
+    }
+}
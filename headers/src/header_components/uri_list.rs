@@ -0,0 +1,249 @@
+use std::fmt::{self, Display};
+
+use soft_ascii::{SoftAsciiChar, SoftAsciiStr, SoftAsciiString};
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodingWriter, EncodableInHeader};
+use ::HeaderTryFrom;
+use ::error::ComponentCreationError;
+use ::mail_type::HasMailTypeRequirement;
+
+use super::utils::cfws::skip_cfws;
+
+/// The component of the URL list style mailing list headers (rfc2369).
+///
+/// This are e.g. `List-Post`, `List-Archive`, `List-Help` and
+/// `List-Subscribe` which all have the form `<URI>(, <URI>)*`, where
+/// each URI can be followed by a `(comment)`. Comments are accepted
+/// when parsing but are not kept.
+///
+/// Additionally `List-Post` can have the special value `NO` to state
+/// that posting to the list is not allowed, it's represented by a list
+/// without any URIs (and a list without URIs is encoded as `NO`).
+///
+/// URIs have to be us-ascii, must have a scheme and can not contain
+/// whitespace, `<`, `>` or `"`.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct UriList {
+    uris: Vec<SoftAsciiString>
+}
+
+impl UriList {
+
+    /// Creates the special `NO` value.
+    pub fn no() -> Self {
+        Default::default()
+    }
+
+    /// Creates a list from the given (bare) URIs.
+    ///
+    /// # Error
+    ///
+    /// Fails if any URI is invalid or if no URI is given (use `UriList::no`
+    /// for that).
+    pub fn new<I, S>(uris: I) -> Result<Self, ComponentCreationError>
+        where I: IntoIterator<Item=S>, S: AsRef<str>
+    {
+        let mut list = UriList::no();
+        for uri in uris {
+            let uri = uri.as_ref();
+            if !is_uri(uri) {
+                return Err(ComponentCreationError::new_with_str("UriList", uri));
+            }
+            list.uris.push(SoftAsciiString::from_unchecked(uri));
+        }
+        if list.is_no() {
+            return Err(ComponentCreationError::new("UriList"));
+        }
+        Ok(list)
+    }
+
+    /// Parses `<URI>(, <URI>)*` (with optional comments) or `NO` (ignoring case).
+    pub fn parse(value: &str) -> Result<Self, ComponentCreationError> {
+        parse_list(value)
+            .ok_or_else(|| ComponentCreationError::new_with_str("UriList", value))
+    }
+
+    /// Returns the (bare) URIs in the list, for `NO` this is empty.
+    pub fn uris(&self) -> &[SoftAsciiString] {
+        &self.uris
+    }
+
+    /// Returns true if this is the special `NO` value.
+    pub fn is_no(&self) -> bool {
+        self.uris.is_empty()
+    }
+}
+
+fn parse_list(value: &str) -> Option<UriList> {
+    let mut rest = skip_cfws(value)?;
+    if rest.get(..2).map(|no| no.eq_ignore_ascii_case("NO")).unwrap_or(false) {
+        return match skip_cfws(&rest[2..]) {
+            Some("") => Some(UriList::no()),
+            _ => None
+        };
+    }
+
+    let mut list = UriList::no();
+    loop {
+        if !rest.starts_with('<') {
+            return None;
+        }
+        let end = rest.find('>')?;
+        let uri = rest[1..end].trim();
+        if !is_uri(uri) {
+            return None;
+        }
+        list.uris.push(SoftAsciiString::from_unchecked(uri));
+
+        rest = skip_cfws(&rest[end+1..])?;
+        if rest.is_empty() {
+            return Some(list);
+        }
+        if !rest.starts_with(',') {
+            return None;
+        }
+        rest = skip_cfws(&rest[1..])?;
+    }
+}
+
+/// A us-ascii URI with a scheme, without whitespace and without the chars used as delimiters.
+fn is_uri(uri: &str) -> bool {
+    let scheme_end = match uri.find(':') {
+        Some(idx) => idx,
+        None => return false
+    };
+    let scheme = &uri[..scheme_end];
+    let valid_scheme = scheme.chars().next().map(|ch| ch.is_ascii_alphabetic()).unwrap_or(false)
+        && scheme.chars().all(|ch| ch.is_ascii_alphanumeric() || "+-.".contains(ch));
+
+    valid_scheme && uri.chars().all(|ch| match ch {
+        '<' | '>' | '"' => false,
+        '!'...'~' => true,
+        _ => false
+    })
+}
+
+impl<'a> HeaderTryFrom<&'a str> for UriList {
+    fn try_from(value: &'a str) -> Result<Self, ComponentCreationError> {
+        UriList::parse(value)
+    }
+}
+
+impl HeaderTryFrom<String> for UriList {
+    fn try_from(value: String) -> Result<Self, ComponentCreationError> {
+        UriList::parse(&value)
+    }
+}
+
+/// Formats the list like the wire form, i.e. as `<uri1>, <uri2>` or `NO` (but never folded).
+impl Display for UriList {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_no() {
+            return fter.write_str("NO");
+        }
+        for (idx, uri) in self.uris.iter().enumerate() {
+            if idx > 0 {
+                fter.write_str(", ")?;
+            }
+            write!(fter, "<{}>", uri)?;
+        }
+        Ok(())
+    }
+}
+
+impl HasMailTypeRequirement for UriList {}
+
+impl EncodableInHeader for UriList {
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        if self.is_no() {
+            handle.write_str(SoftAsciiStr::from_unchecked("NO"))?;
+            return Ok(());
+        }
+        sep_for!{ uri in self.uris.iter();
+            sep {
+                handle.write_char(SoftAsciiChar::from_unchecked(','))?;
+                handle.write_fws();
+            };
+            handle.write_char(SoftAsciiChar::from_unchecked('<'))?;
+            handle.write_str(uri)?;
+            handle.write_char(SoftAsciiChar::from_unchecked('>'))?;
+        }
+        Ok(())
+    }
+
+    fn boxed_clone(&self) -> Box<EncodableInHeader> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn uris(list: &UriList) -> Vec<&str> {
+        list.uris().iter().map(|uri| uri.as_str()).collect()
+    }
+
+    #[test]
+    fn parse_list_with_comments() {
+        let list = assert_ok!(UriList::parse(
+            "<mailto:list@example.com> (Use this (or the web form)), \
+             <https://example.com/post?list=a> (web form)"));
+        assert_eq!(uris(&list), vec!["mailto:list@example.com", "https://example.com/post?list=a"]);
+        assert!(!list.is_no());
+    }
+
+    #[test]
+    fn parse_no() {
+        for value in &["NO", "no", " NO (posting not allowed on this list)"] {
+            let list = assert_ok!(UriList::parse(value), value);
+            assert!(list.is_no(), "{:?}", value);
+            assert!(list.uris().is_empty());
+        }
+        assert_err!(UriList::parse("NO, <mailto:list@example.com>"));
+        assert_err!(UriList::parse("NOPE"));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_lists() {
+        assert_err!(UriList::parse(""));
+        assert_err!(UriList::parse("mailto:list@example.com"));
+        assert_err!(UriList::parse("<mailto:list@example.com>,"));
+        assert_err!(UriList::parse("<mailto:list@example.com> <mailto:b@example.com>"));
+        assert_err!(UriList::parse("<list@example.com>"));
+        assert_err!(UriList::parse("<mailto:li st@example.com>"));
+        assert_err!(UriList::parse("<mailto:lüst@example.com>"));
+        assert_err!(UriList::parse("<mailto:list@example.com> (unclosed"));
+    }
+
+    #[test]
+    fn new_requires_a_uri() {
+        assert_err!(UriList::new(Vec::<&str>::new()));
+        assert_err!(UriList::new(vec!["not a uri"]));
+        let list = assert_ok!(UriList::new(vec!["mailto:list@example.com"]));
+        assert_eq!(uris(&list), vec!["mailto:list@example.com"]);
+    }
+
+    #[test]
+    fn display() {
+        let list = assert_ok!(UriList::parse("<mailto:a@example.com>,<https://example.com>"));
+        assert_eq!(list.to_string(), "<mailto:a@example.com>, <https://example.com>");
+        assert_eq!(UriList::no().to_string(), "NO");
+    }
+
+    ec_test!{ multiple, {
+        UriList::try_from("<mailto:a@example.com> (comment), <https://example.com/a>")?
+    } => ascii => [
+        Text "<mailto:a@example.com>,",
+        MarkFWS,
+        Text " <https://example.com/a>"
+    ]}
+
+    ec_test!{ no, {
+        UriList::try_from("no")?
+    } => ascii => [
+        Text "NO"
+    ]}
+}
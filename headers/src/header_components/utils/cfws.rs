@@ -0,0 +1,29 @@
+/// Skips whitespace and (possible nested) comments, returns `None` for unclosed comments.
+pub(crate) fn skip_cfws(input: &str) -> Option<&str> {
+    let mut rest = input.trim_start();
+    while rest.starts_with('(') {
+        let mut depth = 0;
+        let mut escaped = false;
+        let mut end = None;
+        for (idx, ch) in rest.char_indices() {
+            if escaped {
+                escaped = false;
+                continue;
+            }
+            match ch {
+                '\\' => escaped = true,
+                '(' => depth += 1,
+                ')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        end = Some(idx);
+                        break;
+                    }
+                },
+                _ => {}
+            }
+        }
+        rest = rest[end? + 1..].trim_start();
+    }
+    Some(rest)
+}
@@ -0,0 +1,135 @@
+//! Decoding of encoded words (rfc2047) for displaying header text.
+use super::super::media_type_params::decode_charset;
+
+/// Decodes all encoded words in given text.
+///
+/// Whitespace between two adjacent encoded words is removed (as required by
+/// rfc2047). Encoded words which can not be decoded (e.g. because of a
+/// unsupported charset) are kept as they are. Supported charsets are the
+/// ones supported for rfc2231 parameters (`utf-8`, `us-ascii` and `iso-8859-1`).
+pub fn decode_encoded_words(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last_was_encoded = false;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let ws_end = rest.find(|ch: char| !ch.is_whitespace()).unwrap_or(rest.len());
+        let (ws, tail) = rest.split_at(ws_end);
+        let word_end = tail.find(char::is_whitespace).unwrap_or(tail.len());
+        let (word, tail) = tail.split_at(word_end);
+        rest = tail;
+
+        match decode_word(word) {
+            Some(decoded) => {
+                if !last_was_encoded {
+                    out.push_str(ws);
+                }
+                out.push_str(&decoded);
+                last_was_encoded = true;
+            },
+            None => {
+                out.push_str(ws);
+                out.push_str(word);
+                last_was_encoded = false;
+            }
+        }
+    }
+    out
+}
+
+/// Decodes a single encoded word (`=?charset?encoding?text?=`).
+fn decode_word(word: &str) -> Option<String> {
+    if !(word.len() >= 8 && word.starts_with("=?") && word.ends_with("?=")) {
+        return None;
+    }
+    let mut parts = word[2..word.len()-2].split('?');
+    let charset = parts.next()?;
+    let encoding = parts.next()?;
+    let text = parts.next()?;
+    if parts.next().is_some() {
+        return None;
+    }
+
+    // rfc2231 allows a language after the charset, e.g. `utf-8*en`
+    let charset = charset.split('*').next()?;
+    let bytes = match encoding {
+        "q" | "Q" => decode_q(text)?,
+        "b" | "B" => decode_base64(text)?,
+        _ => return None
+    };
+    decode_charset(charset, bytes)
+}
+
+fn decode_q(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len());
+    let mut iter = text.bytes();
+    while let Some(bch) = iter.next() {
+        match bch {
+            b'_' => out.push(b' '),
+            b'=' => {
+                let high = (iter.next()? as char).to_digit(16)?;
+                let low = (iter.next()? as char).to_digit(16)?;
+                out.push((high * 16 + low) as u8);
+            },
+            bch if bch.is_ascii() => out.push(bch),
+            _ => return None
+        }
+    }
+    Some(out)
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    fn value(bch: u8) -> Option<u32> {
+        let value = match bch {
+            b'A'...b'Z' => bch - b'A',
+            b'a'...b'z' => bch - b'a' + 26,
+            b'0'...b'9' => bch - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None
+        };
+        Some(value as u32)
+    }
+
+    let text = text.trim_end_matches('=');
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for bch in text.bytes() {
+        buffer = (buffer << 6) | value(bch)?;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decodes_q_and_b_words() {
+        assert_eq!(decode_encoded_words("=?utf-8?q?Caf=C3=A9_Bar?="), "Café Bar");
+        assert_eq!(decode_encoded_words("=?UTF-8?B?Q2Fmw6kgQmFy?="), "Café Bar");
+        assert_eq!(decode_encoded_words("=?iso-8859-1?Q?Caf=E9?="), "Café");
+        assert_eq!(decode_encoded_words("=?utf-8*en?q?hy?="), "hy");
+    }
+
+    #[test]
+    fn joins_adjacent_encoded_words() {
+        assert_eq!(decode_encoded_words("=?utf-8?q?a?= =?utf-8?q?b?="), "ab");
+        assert_eq!(decode_encoded_words("x =?utf-8?q?a?=  y"), "x a  y");
+    }
+
+    #[test]
+    fn keeps_undecodable_words() {
+        assert_eq!(decode_encoded_words("no words here"), "no words here");
+        assert_eq!(decode_encoded_words("=?koi8-r?q?a?="), "=?koi8-r?q?a?=");
+        assert_eq!(decode_encoded_words("=?utf-8?x?a?="), "=?utf-8?x?a?=");
+        assert_eq!(decode_encoded_words("=?utf-8?q?=Z1?="), "=?utf-8?q?=Z1?=");
+        assert_eq!(decode_encoded_words("=??="), "=??=");
+    }
+}
@@ -1,6 +1,8 @@
 
 
 pub mod text_partition;
+pub(crate) mod encoded_word;
+pub(crate) mod cfws;
 
 
 #[cfg(feature="serde")]
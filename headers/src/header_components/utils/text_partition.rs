@@ -1,3 +1,7 @@
+// partition is called with arbitrary user input, slicing is done through
+// `str::get` so a wrong index surfaces as an error instead of a panic
+#![deny(clippy::indexing_slicing)]
+
 use internals::grammar::{is_vchar, is_ws};
 use internals::MailType;
 
@@ -6,7 +10,7 @@ use internals::MailType;
 #[fail(display = "text contained control characters")]
 pub struct PartitionError;
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Partition<'a> {
     //from -> to the start of the next block
     SPACE(&'a str),
@@ -19,12 +23,10 @@ enum Type { SPACE, VCHAR }
 pub fn partition<'a>( text: &'a str ) -> Result<Vec<Partition<'a>>, PartitionError> {
     use self::Type::*;
 
-    if text.len() == 0 {
-        return Ok( Vec::new() );
-    }
-
-    // unwrap is ok, as we return earlier if len == 0
-    let start_with_vchar = is_vchar( text.chars().next().unwrap(), MailType::Internationalized);
+    let start_with_vchar = match text.chars().next() {
+        Some(first) => is_vchar(first, MailType::Internationalized),
+        None => return Ok( Vec::new() )
+    };
 
     let mut partitions =  Vec::new();
     let mut current_type = if start_with_vchar { VCHAR } else { SPACE };
@@ -36,13 +38,13 @@ pub fn partition<'a>( text: &'a str ) -> Result<Vec<Partition<'a>>, PartitionErr
                 // idx is the start index of the current char, with is the
                 // (exclusive) end index of the previous char which is the
                 // last char of the Partition we want to push
-                partitions.push(Partition::SPACE(&text[start_of_current..idx]));
+                partitions.push(Partition::SPACE(slice(text, start_of_current, idx)?));
                 start_of_current = idx;
                 current_type = VCHAR
             }
         } else if is_ws(ch) || ch == '\r' || ch == '\n' {
             if current_type == VCHAR {
-                partitions.push(Partition::VCHAR(&text[start_of_current..idx]));
+                partitions.push(Partition::VCHAR(slice(text, start_of_current, idx)?));
                 start_of_current = idx;
                 current_type = SPACE
             }
@@ -53,10 +55,50 @@ pub fn partition<'a>( text: &'a str ) -> Result<Vec<Partition<'a>>, PartitionErr
     }
 
 
+    let rest = slice(text, start_of_current, text.len())?;
     partitions.push( match current_type {
-        SPACE => Partition::SPACE(&text[start_of_current..]),
-        VCHAR => Partition::VCHAR(&text[start_of_current..])
+        SPACE => Partition::SPACE(rest),
+        VCHAR => Partition::VCHAR(rest)
     } );
 
     Ok( partitions )
-}
\ No newline at end of file
+}
+
+/// `&text[start..end]` but returning an error instead of panicking
+///
+/// The indices are always char boundaries obtained from `char_indices`,
+/// so this only errors if there is a bug in `partition`.
+fn slice(text: &str, start: usize, end: usize) -> Result<&str, PartitionError> {
+    text.get(start..end).ok_or(PartitionError)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn empty_text_has_no_partitions() {
+        assert_eq!(partition(""), Ok(vec![]));
+    }
+
+    #[test]
+    fn multi_byte_chars_at_partition_boundaries() {
+        assert_eq!(partition("ä 𝄞\t€"), Ok(vec![
+            Partition::VCHAR("ä"),
+            Partition::SPACE(" "),
+            Partition::VCHAR("𝄞"),
+            Partition::SPACE("\t"),
+            Partition::VCHAR("€")
+        ]));
+        assert_eq!(partition(" 𝄞"), Ok(vec![
+            Partition::SPACE(" "),
+            Partition::VCHAR("𝄞")
+        ]));
+    }
+
+    #[test]
+    fn control_chars_are_an_error() {
+        assert_eq!(partition("a\x00b"), Err(PartitionError));
+        assert_eq!(partition("\x7f"), Err(PartitionError));
+    }
+}
@@ -8,6 +8,7 @@ use internals::bind::encoded_word::{EncodedWordEncoding, WriterWrapper};
 use internals::bind::quoted_string::{MailQsSpec, InternationalizedMailQsSpec};
 use ::{HeaderTryFrom, HeaderTryInto};
 use ::error::ComponentCreationError;
+use ::mail_type::{HasMailTypeRequirement, MailTypeRequirement};
 use ::data::Input;
 
 
@@ -61,6 +62,17 @@ impl Word {
 /// word does appear, which changes some properties of the encoded word.
 ///
 /// NOTE: != encoded-word, through it might create an encoded-word
+// outside of a phrase a word can not be encoded as encoded word
+impl HasMailTypeRequirement for Word {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        if self.input.as_str().is_ascii() {
+            MailTypeRequirement::ascii()
+        } else {
+            MailTypeRequirement::internationalized()
+        }
+    }
+}
+
 pub fn do_encode_word<'a,'b: 'a>(
     word: &'a Word,
     handle: &'a mut EncodingWriter<'b>,
@@ -0,0 +1,382 @@
+//! Declarative groups of static headers (requires the `serde-impl` feature).
+//!
+//! A `HeaderGroupSpec` describes a set of headers, e.g. the static headers
+//! a tenant configured for all of its mails, as a list of entries with a
+//! header name, a kind and a JSON value. It is meant to be stored as
+//! configuration and to be `check`ed when the configuration is saved, so
+//! that invalid configuration is rejected long before a mail is sent.
+//!
+//! The kind of an entry is either `known` (the default) for headers known
+//! through `HeaderRegistry::register`/`register_str` (including all
+//! builtin headers) or `raw` for headers registered with
+//! `HeaderRegistry::register_raw`. The value is converted like with
+//! `from_json::header_from_json`, see `from_json` for the accepted shapes.
+//! As header names are `'static` only registered headers can be used,
+//! i.e. a new raw header has to be defined (e.g. with `def_headers!`) and
+//! registered before it can be used in a spec.
+//!
+//! The map created by `materialize` is normally merged onto each outgoing
+//! mail with `HeaderMap::combine_with` using `MergePolicy::SelfWins`, so
+//! that headers set by the mail itself take precedence.
+//!
+//! # Example
+//!
+//! ```
+//! # #[macro_use]
+//! # extern crate mail_headers;
+//! # #[macro_use]
+//! # extern crate serde_json;
+//! # fn main() {
+//! use mail_headers::headers::*;
+//! use mail_headers::map::MergePolicy;
+//! use mail_headers::from_json::HeaderRegistry;
+//! use mail_headers::header_group::HeaderGroupSpec;
+//!
+//! let spec: HeaderGroupSpec = serde_json::from_value(json!({
+//!     "entries": [
+//!         {"name": "Reply-To", "value": ["support@example.com"]},
+//!         {"name": "Comments", "kind": "known", "value": "sent by example.com"}
+//!     ]
+//! })).unwrap();
+//!
+//! let registry = HeaderRegistry::builtin();
+//! spec.check(&registry).unwrap();
+//!
+//! let mut mail = headers! {
+//!     _From: ["news@example.com"],
+//!     Subject: "Hi"
+//! }.unwrap();
+//! mail.combine_with(spec.materialize(&registry).unwrap(), MergePolicy::SelfWins);
+//! assert_eq!(mail.len(), 4);
+//! # }
+//! ```
+use std::collections::HashSet;
+
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use internals::grammar::is_ftext;
+use ::name::HeaderName;
+use ::header::HeaderObj;
+use ::error::{SpecError, SpecErrorKind};
+use ::from_json::{HeaderRegistry, JsonConverter};
+use ::map::HeaderMap;
+
+/// A declarative description of a group of headers.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct HeaderGroupSpec {
+    pub entries: Vec<HeaderGroupEntry>
+}
+
+/// A entry of a `HeaderGroupSpec`, i.e. one header.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HeaderGroupEntry {
+    /// The name of the header, matched ignoring case.
+    pub name: String,
+    /// The kind of the header, `known` if not specified.
+    #[serde(default)]
+    pub kind: EntryKind,
+    /// The value, the accepted shapes depend on the header.
+    pub value: Value
+}
+
+/// Whether a `HeaderGroupEntry` is a known (typed) header or a raw header.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all="lowercase")]
+pub enum EntryKind {
+    /// A header registered with `HeaderRegistry::register` or `register_str`.
+    Known,
+    /// A header registered with `HeaderRegistry::register_raw`.
+    Raw
+}
+
+impl EntryKind {
+
+    /// The name of the kind as used in the serialized spec.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            EntryKind::Known => "known",
+            EntryKind::Raw => "raw"
+        }
+    }
+}
+
+impl Default for EntryKind {
+    fn default() -> Self {
+        EntryKind::Known
+    }
+}
+
+impl HeaderGroupSpec {
+
+    /// Checks if the spec can be materialized with given registry.
+    ///
+    /// # Error
+    ///
+    /// Returns the errors of all invalid entries, see `materialize`.
+    pub fn check(&self, registry: &HeaderRegistry) -> Result<(), Vec<SpecError>> {
+        self.materialize(registry).map(|_| ())
+    }
+
+    /// Creates a map containing the headers of all entries (in order).
+    ///
+    /// Like `headers!` this does not run the contextual validators.
+    ///
+    /// # Error
+    ///
+    /// Returns the errors of all invalid entries, each error contains the
+    /// index and the invalid field of the entry. An entry is invalid if:
+    ///
+    /// - the name is not known for the kind of the entry (field `name`)
+    /// - the name is only known for the other kind (field `kind`)
+    /// - the name of a `raw` entry is not a valid header name (field `name`)
+    /// - a "max one" header was already specified by a previous entry (field `name`)
+    /// - the value can not be converted into the header (field `value`)
+    pub fn materialize(&self, registry: &HeaderRegistry) -> Result<HeaderMap, Vec<SpecError>> {
+        let mut headers = Vec::new();
+        let mut errors = Vec::new();
+        let mut max_one = HashSet::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            match entry.header(index, registry) {
+                Ok((name, obj)) => {
+                    if obj.is_max_one() && !max_one.insert(name) {
+                        errors.push(SpecError::new(index, "name",
+                            SpecErrorKind::Duplicate { name: name.as_str().to_owned() }));
+                    } else {
+                        headers.push((name, obj));
+                    }
+                },
+                Err(err) => errors.push(err)
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(HeaderMap::from_vec(headers))
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl HeaderGroupEntry {
+
+    fn header(&self, index: usize, registry: &HeaderRegistry)
+        -> Result<(HeaderName, Box<HeaderObj>), SpecError>
+    {
+        let (name, converter) = self.converter(index, registry)?;
+        let obj = converter(name, &self.value, "$")
+            .map_err(|err| SpecError::new(index, "value", SpecErrorKind::Value(err)))?;
+        Ok((name, obj))
+    }
+
+    fn converter(&self, index: usize, registry: &HeaderRegistry)
+        -> Result<(HeaderName, JsonConverter), SpecError>
+    {
+        let (found, other_kind) = match self.kind {
+            EntryKind::Known => (registry.find(&self.name), EntryKind::Raw),
+            EntryKind::Raw => {
+                if self.name.is_empty() || !self.name.chars().all(is_ftext) {
+                    return Err(SpecError::new(index, "name",
+                        SpecErrorKind::InvalidName { name: self.name.clone() }));
+                }
+                (registry.find_raw(&self.name), EntryKind::Known)
+            }
+        };
+
+        if let Some(found) = found {
+            return Ok(found);
+        }
+
+        let in_other_kind = match other_kind {
+            EntryKind::Known => registry.find(&self.name),
+            EntryKind::Raw => registry.find_raw(&self.name)
+        };
+        let name = self.name.clone();
+        let kind = self.kind.as_str();
+        if in_other_kind.is_some() {
+            Err(SpecError::new(index, "kind",
+                SpecErrorKind::KindMismatch { name, kind, expected: other_kind.as_str() }))
+        } else {
+            Err(SpecError::new(index, "name", SpecErrorKind::UnknownHeader { name, kind }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json;
+
+    use ::headers::*;
+    use ::error::JsonHeaderError;
+    use ::map::MergePolicy;
+    use super::*;
+
+    mod raw_headers {
+        use ::header_components;
+        def_headers! {
+            test_name: validate_header_names,
+            scope: header_components,
+            XTenantId, unchecked { "X-Tenant-Id" }, RawFieldBody, maxOne, None,
+            ListUnsubscribe, unchecked { "List-Unsubscribe" }, RawFieldBody, maxOne, None
+        }
+    }
+    use self::raw_headers::{XTenantId, ListUnsubscribe};
+
+    fn registry() -> HeaderRegistry {
+        let mut registry = HeaderRegistry::builtin();
+        registry.register_raw::<XTenantId>();
+        registry.register_raw::<ListUnsubscribe>();
+        registry
+    }
+
+    fn spec(value: Value) -> HeaderGroupSpec {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn tenant_spec() -> HeaderGroupSpec {
+        spec(json!({
+            "entries": [
+                {"name": "X-Tenant-Id", "kind": "raw", "value": "tenant-42"},
+                {"name": "list-unsubscribe", "kind": "raw", "value": "<mailto:unsubscribe@example.com>"},
+                {"name": "Reply-To", "kind": "known", "value": {"name": "Support", "email": "support@example.com"}},
+                {"name": "Comments", "value": "static header"}
+            ]
+        }))
+    }
+
+    fn single_error(spec: &HeaderGroupSpec) -> SpecError {
+        let mut errors = assert_err!(spec.check(&registry()));
+        assert_eq!(errors.len(), 1);
+        errors.pop().unwrap()
+    }
+
+    test!(valid_spec_materializes {
+        let spec = tenant_spec();
+        assert_eq!(spec.entries[3].kind, EntryKind::Known);
+        assert_ok!(spec.check(&registry()));
+
+        let map = spec.materialize(&registry())
+            .unwrap_or_else(|errs| panic!("unexpected errors: {:?}", errs));
+        let names = map.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["X-Tenant-Id", "List-Unsubscribe", "Reply-To", "Comments"]);
+
+        assert_eq!(map.get_single(XTenantId).unwrap()?.as_str(), "tenant-42");
+        let reply_to = map.get_single(ReplyTo).unwrap()?;
+        assert_eq!(reply_to[0].name(), "Support");
+        assert_eq!(map.get_single(Comments).unwrap()?.as_str(), "static header");
+    });
+
+    test!(spec_round_trips_through_json {
+        let spec = tenant_spec();
+        let value = serde_json::to_value(&spec)?;
+        assert_eq!(value["entries"][3]["kind"], json!("known"));
+        assert_eq!(serde_json::from_value::<HeaderGroupSpec>(value)?, spec);
+    });
+
+    #[test]
+    fn unknown_header() {
+        let err = single_error(&spec(json!({"entries": [
+            {"name": "Subject", "value": "Hi"},
+            {"name": "X-Unknown", "kind": "raw", "value": "value"}
+        ]})));
+        assert_eq!(err.index(), 1);
+        assert_eq!(err.field(), "name");
+        match *err.kind() {
+            SpecErrorKind::UnknownHeader { ref name, kind } => {
+                assert_eq!(name, "X-Unknown");
+                assert_eq!(kind, "raw");
+            },
+            ref other => panic!("unexpected error: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn kind_mismatch() {
+        let err = single_error(&spec(json!({"entries": [
+            {"name": "Reply-To", "kind": "raw", "value": "support@example.com"}
+        ]})));
+        assert_eq!(err.index(), 0);
+        assert_eq!(err.field(), "kind");
+        assert_eq!(
+            err.to_string(),
+            "entry 0 (kind): Reply-To header can not be used as raw header (use kind known)"
+        );
+
+        let err = single_error(&spec(json!({"entries": [
+            {"name": "X-Tenant-Id", "value": "tenant-42"}
+        ]})));
+        match *err.kind() {
+            SpecErrorKind::KindMismatch { kind, expected, .. } => {
+                assert_eq!(kind, "known");
+                assert_eq!(expected, "raw");
+            },
+            ref other => panic!("unexpected error: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn bad_value_shape() {
+        let err = single_error(&spec(json!({"entries": [
+            {"name": "X-Tenant-Id", "kind": "raw", "value": "tenant-42"},
+            {"name": "Reply-To", "value": ["support@example.com", 12]}
+        ]})));
+        assert_eq!(err.index(), 1);
+        assert_eq!(err.field(), "value");
+        match *err.kind() {
+            SpecErrorKind::Value(JsonHeaderError::ShapeMismatch { ref path, .. }) => assert_eq!(path, "$[1]"),
+            ref other => panic!("unexpected error: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn invalid_raw_name() {
+        for name in &["", "X Tenant", "X-Tenant:Id", "X-Tenänt"] {
+            let err = single_error(&spec(json!({"entries": [
+                {"name": name, "kind": "raw", "value": "tenant-42"}
+            ]})));
+            assert_eq!(err.index(), 0);
+            assert_eq!(err.field(), "name");
+            match *err.kind() {
+                SpecErrorKind::InvalidName { name: ref invalid } => assert_eq!(invalid, name),
+                ref other => panic!("unexpected error: {:?}", other)
+            }
+        }
+    }
+
+    #[test]
+    fn duplicate_max_one_header() {
+        let errors = assert_err!(spec(json!({"entries": [
+            {"name": "Reply-To", "value": "a@example.com"},
+            {"name": "Comments", "value": "first"},
+            {"name": "Comments", "value": "second"},
+            {"name": "reply-to", "value": "b@example.com"},
+            {"name": "X-Unknown", "kind": "raw", "value": "value"}
+        ]})).materialize(&registry()));
+
+        let errors = errors.iter()
+            .map(|err| (err.index(), err.field()))
+            .collect::<Vec<_>>();
+        assert_eq!(errors, vec![(3, "name"), (4, "name")]);
+    }
+
+    test!(end_to_end_merge_onto_message {
+        let group = tenant_spec().materialize(&registry())
+            .unwrap_or_else(|errs| panic!("unexpected errors: {:?}", errs));
+
+        let mut mail = headers! {
+            _From: ["news@example.com"],
+            ReplyTo: ["campaign@example.com"],
+            Subject: "Hi"
+        }?;
+        mail.combine_with(group, MergePolicy::SelfWins);
+
+        let names = mail.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec![
+            "From", "Reply-To", "Subject", "X-Tenant-Id", "List-Unsubscribe", "Comments"
+        ]);
+        // the Reply-To of the message wins
+        let reply_to = mail.get_single(ReplyTo).unwrap()?;
+        assert_eq!(reply_to[0].email(), "campaign@example.com");
+        mail.use_contextual_validators()?;
+    });
+}
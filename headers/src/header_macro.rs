@@ -1,4 +1,7 @@
-pub use soft_ascii_string::{ SoftAsciiStr as _SoftAsciiStr };
+/// `SoftAsciiStr` from the `soft-ascii-string` crate, use it directly instead.
+#[deprecated(since = "0.6.0", note = "use `SoftAsciiStr` from the `soft-ascii-string` crate")]
+#[doc(hidden)]
+pub type _SoftAsciiStr = ::soft_ascii::SoftAsciiStr;
 
 
 /// Defines a new header types with given type name, filed name and component
@@ -6,7 +9,9 @@ pub use soft_ascii_string::{ SoftAsciiStr as _SoftAsciiStr };
 /// Note that the name is not checked/validated, it has to be ascii, a valid
 /// header field name AND has to comply with the naming schema (each word
 /// separated by `'-'` starts with a capital letter and no capital letter
-/// follow, e.g. "Message-Id" is ok **but "Message-ID" isn't**).
+/// follow, e.g. "Message-Id" is ok **but "Message-ID" isn't**). If a
+/// different spelling should be used when encoding the header it can be
+/// given as wire spelling (see below).
 ///
 /// This macro will create a test which will check if the used field names
 /// are actually valid and appears only once (_per def_header macro call_)
@@ -36,9 +41,14 @@ pub use soft_ascii_string::{ SoftAsciiStr as _SoftAsciiStr };
 ///       case, but by enforcing a specific case in the encoder equality checks can be
 ///       done on byte level, which is especially usefull for e.g. placing them as keys
 ///       into a HashMap or for performance reasons.
+///       Optionally this can be followed by `, wire: "<wire_name>"` to use a different
+///       spelling when encoding the header, e.g. `unchecked { "Message-Id", wire: "Message-ID" }`.
+///       The wire spelling has to be a valid header field name which matches the header
+///       name ignoring case, but does not need to follow the capitalization scheme. It
+///       is only used for encoding, all lookups/comparisons use the header name.
 ///    5. `<component>` the name of the type to use ing `scope` a the component type of
 ///       the header. E.g. `Unstructured` for an unstructured header field (which still
-///       support Utf8 through encoded words)
+///       support Utf8 through encoded words).
 ///    6. `None`/`maxOne`/`<name>`, None, maxOne or the name of a validator function.
 ///       The validator function is used to validate some contextual limitations a header field
 ///       might have, like that it can appear at most one time, or that if a `From` with multiple
@@ -51,6 +61,11 @@ pub use soft_ascii_string::{ SoftAsciiStr as _SoftAsciiStr };
 ///       the header it should validate or if it finds it but it has an unexpected type it _must not_
 ///       create an error.
 ///
+/// Optionally `scope` can be followed by `mail_type_requirement: component,` in which case
+/// `HeaderKind::mail_type_requirement` uses the `HasMailTypeRequirement` impl of the
+/// components (which then all have to implement it). Without it the headers are assumed
+/// to be encodable in any mail type, see `HeaderMap::required_mail_type`.
+///
 /// # Example
 ///
 /// ```norun
@@ -60,11 +75,14 @@ pub use soft_ascii_string::{ SoftAsciiStr as _SoftAsciiStr };
 ///     // the scope from which all components should be imported
 ///     // E.g. `DateTime` refers to `components::DateTime`.
 ///     scope: components,
+///     // (optional) use `HasMailTypeRequirement` of the components
+///     mail_type_requirement: component,
 ///     // definitions of the headers or the form
 ///     // <type_name>, unchecked { <struct_name> }, <component>, <validator>
 ///     Date,     unchecked { "Date"          },  DateTime,       maxOne,
 ///     From,     unchecked { "From"          },  MailboxList,    validator_from,
 ///     Subject,  unchecked { "Subject"       },  Unstructured,   maxOne,
+///     MessageId, unchecked { "Message-Id", wire: "Message-ID" }, MessageId, maxOne,
 ///     Comments, unchecked { "Comments"      },  Unstructured,   None,
 /// }
 /// ```
@@ -73,15 +91,32 @@ macro_rules! def_headers {
     (
         test_name: $tn:ident,
         scope: $scope:ident,
+        mail_type_requirement: component,
+        $($rest:tt)+
+    ) => (
+        def_headers!{ _PRIV_impl test_name: $tn, scope: $scope, requirement: component, $($rest)+ }
+    );
+    (
+        test_name: $tn:ident,
+        scope: $scope:ident,
+        $($rest:tt)+
+    ) => (
+        def_headers!{ _PRIV_impl test_name: $tn, scope: $scope, requirement: default, $($rest)+ }
+    );
+    (
+        _PRIV_impl
+        test_name: $tn:ident,
+        scope: $scope:ident,
+        requirement: $requirement:ident,
         $(
             $(#[$attr:meta])*
-            $name:ident, unchecked { $hname:tt }, $component:ident,
+            $name:ident, unchecked { $hname:tt $(, wire: $wname:tt)* }, $component:ident,
               $maxOne:ident, $validator:ident
         ),+
     ) => (
         $(
             $(#[$attr])*
-            #[derive(Default, Copy, Clone)]
+            #[derive(Debug, Default, Copy, Clone)]
             pub struct $name;
 
             impl $crate::HeaderKind for $name {
@@ -89,13 +124,14 @@ macro_rules! def_headers {
                 type Component = $scope::$component;
 
                 fn name() -> $crate::HeaderName {
-                    let as_str: &'static str = $hname;
-                    $crate::HeaderName::from_ascii_unchecked( as_str )
+                    def_headers!{ _PRIV_mk_name $hname $(, $wname)* }
                 }
 
                 const MAX_ONE: bool = def_headers!{ _PRIV_mk_max_one $maxOne };
                 const VALIDATOR: ::std::option::Option<$crate::map::HeaderMapValidator> =
                         def_headers!{ _PRIV_mk_validator $validator };
+
+                def_headers!{ _PRIV_mk_requirement $requirement }
             }
 
             def_headers!{ _PRIV_mk_marker_impl $name, $maxOne }
@@ -116,7 +152,7 @@ macro_rules! def_headers {
         #[test]
         fn $tn() {
             use std::collections::HashSet;
-            use $crate::__internals::encoder::EncodableInHeader;
+            use $crate::__internal::EncodableInHeader;
 
             let mut name_set = HashSet::new();
             for name in HEADER_NAMES {
@@ -132,19 +168,42 @@ macro_rules! def_headers {
             )+
             for name in HEADER_NAMES {
                 let res = $crate::HeaderName::new(
-                    $crate::soft_ascii_string::SoftAsciiStr::from_str(name).unwrap()
+                    $crate::__internal::SoftAsciiStr::from_str(name).unwrap()
                 );
                 if res.is_err() {
                     panic!( "invalid header name: {:?} ({:?})", name, res.unwrap_err() );
                 }
             }
+            $(
+                let name = <$name as $crate::HeaderKind>::name();
+                let res = $crate::HeaderName::new(name.as_ascii_str())
+                    .and_then(|checked| checked.with_wire_case(name.as_wire_ascii_str()));
+                if res.is_err() {
+                    panic!( "invalid wire name: {:?} ({:?})", name.as_wire_str(), res.unwrap_err() );
+                }
+            )+
         }
     );
+    (_PRIV_mk_name $hname:tt) => ({
+        let as_str: &'static str = $hname;
+        $crate::HeaderName::from_ascii_unchecked( as_str )
+    });
+    (_PRIV_mk_name $hname:tt, $wname:tt) => ({
+        let as_str: &'static str = $hname;
+        let wire_str: &'static str = $wname;
+        $crate::HeaderName::from_ascii_unchecked_with_wire_case( as_str, wire_str )
+    });
     (_PRIV_mk_marker_impl $name:ident, multi) => ();
     (_PRIV_mk_marker_impl $name:ident, maxOne) => (
         impl $crate::MaxOneMarker for $name {}
     );
     (_PRIV_mk_marker_impl $name:ident, $other:ident) => (def_headers!{ _PRIV_max_one_err $other });
+    (_PRIV_mk_requirement default) => ();
+    (_PRIV_mk_requirement component) => (
+        fn mail_type_requirement(body: &Self::Component) -> $crate::mail_type::MailTypeRequirement {
+            $crate::mail_type::HasMailTypeRequirement::mail_type_requirement(body)
+        }
+    );
     (_PRIV_mk_validator None) => ({ None });
     (_PRIV_mk_validator $validator:ident) => ({ Some($validator) });
     (_PRIV_mk_max_one multi) => ({ false });
@@ -10,6 +10,7 @@ use self::validators::{
 def_headers! {
     test_name: validate_header_names,
     scope: header_components,
+    mail_type_requirement: component,
     /// (rfc5322)
     Date,         unchecked { "Date"          },  DateTime,       maxOne,   None,
     /// (rfc5322)
@@ -18,14 +19,14 @@ def_headers! {
     Sender,       unchecked { "Sender"        },  Mailbox,        maxOne,   None,
     /// (rfc5322)
     ReplyTo,      unchecked { "Reply-To"      },  MailboxList,    maxOne,   None,
-    /// (rfc5322)
+    /// (rfc5322, see `AddressList` for groups)
     _To,          unchecked { "To"            },  MailboxList,    maxOne,   None,
-    /// (rfc5322)
+    /// (rfc5322, see `AddressList` for groups)
     Cc,           unchecked { "Cc"            },  MailboxList,    maxOne,   None,
-    /// (rfc5322)
+    /// (rfc5322, see `AddressList` for groups)
     Bcc,          unchecked { "Bcc"           },  MailboxList,    maxOne,   None,
     /// (rfc5322)
-    MessageId,    unchecked { "Message-Id"    },  MessageId,      maxOne,   None,
+    MessageId,    unchecked { "Message-Id", wire: "Message-ID" }, MessageId, maxOne, None,
     /// (rfc5322)
     InReplyTo,    unchecked { "In-Reply-To"   },  MessageIdList,  maxOne,   None,
     /// (rfc5322)
@@ -111,13 +112,50 @@ def_headers! {
     /// - `read-date`: when the resource this body is based on was read (to create the body)
     /// - `size`: the size this resource should have, note that `Content-Size` is NOT a mail
     ///           related header but specific to http.
-    ContentDisposition, unchecked { "Content-Disposition"       }, Disposition, maxOne, None
-}
+    ContentDisposition, unchecked { "Content-Disposition"       }, Disposition, maxOne, None,
+
+    /// Tells auto-responders how to treat the mail, e.g. `bulk` or `list` (non standard)
+    Precedence, unchecked { "Precedence" }, Precedence, maxOne, None,
+
+    /// Tells (Exchange) auto-responders which kind of responses to suppress (non standard)
+    XAutoResponseSuppress, unchecked { "X-Auto-Response-Suppress" }, AutoResponseSuppress, maxOne, None,
+
+    /// Identifies the mailing list the mail was send through (rfc2919)
+    ListId,        unchecked { "List-Id"        }, ListId,  maxOne, None,
+    /// How to post to the mailing list, can be `NO` (rfc2369)
+    ListPost,      unchecked { "List-Post"      }, UriList, maxOne, None,
+    /// Where the archive of the mailing list can be found (rfc2369)
+    ListArchive,   unchecked { "List-Archive"   }, UriList, maxOne, None,
+    /// Where help about the mailing list can be found (rfc2369)
+    ListHelp,      unchecked { "List-Help"      }, UriList, maxOne, None,
+    /// How to subscribe to the mailing list (rfc2369)
+    ListSubscribe, unchecked { "List-Subscribe" }, UriList, maxOne, None,
+
+    /// Identifies the campaign, customer, mail type and sender for feedback loops (non standard)
+    ///
+    /// Used e.g. by Gmail's postmaster tools to attribute spam reports.
+    FeedbackId, unchecked { "Feedback-Id", wire: "Feedback-ID" }, FeedbackId, maxOne, None,
+    /// Where abuse of the sender can be reported, normally a single URI (non standard)
+    XReportAbuse, unchecked { "X-Report-Abuse" }, UriList, maxOne, None,
+
+    /// Marker left behind by `HeaderMap::redact_for_storage` for each redacted header
+    ///
+    /// The body names the redacted header and contains a short summary
+    /// (e.g. `Bcc (2 recipients)`) but none of the redacted values.
+    XRedacted, unchecked { "X-Redacted" }, Unstructured, multi, None,
 
-mod validators {
-    use std::collections::HashMap;
+    /// The original author of a mail whose `From` was rewritten by a mailing list (non standard)
+    ///
+    /// See `map::rewrite_from_for_list`, there is one header per original author.
+    XOriginalFrom, unchecked { "X-Original-From" }, Mailbox, multi, None,
+
+    /// The verdict, score and matched tests of a SpamAssassin style spam filter (non standard)
+    XSpamStatus, unchecked { "X-Spam-Status" }, SpamStatus, maxOne, None
+}
 
-    use ::{ HeaderMap, HeaderKind, HeaderName, HeaderObj };
+pub(crate) mod validators {
+    use ::{ HeaderMap, HeaderName, Header, HeaderKind };
+    use ::map::ResentBlock;
     use ::error::HeaderValidationError;
 
     use super::{ _From, ResentFrom, Sender, ResentSender, ResentDate };
@@ -125,15 +163,18 @@ mod validators {
 
     pub fn from(map: &HeaderMap) -> Result<(), HeaderValidationError> {
         // Note: we do not care about the quantity of From bodies,
-        // nor "other" From bodies
-        // (which do not use a MailboxList and we could
-        //  therefore not cast to it,
-        // whatever header put them in has also put in
-        // this bit of validation )
-        let needs_sender =
-            map.get(_From)
-                .filter_map(|res| res.ok())
-                .any(|list| list.len() > 1);
+        // but "other" From bodies (which do not use a MailboxList)
+        // are only skipped with lenient validator options
+        let lenient = map.validator_options().lenient_component_types;
+        let mut needs_sender = false;
+        for res in map.get(_From) {
+            match res {
+                Ok(list) => needs_sender |= list.len() > 1,
+                Err(_) if lenient => {},
+                Err(_) => header_validation_bail!(
+                    kind: UnexpectedComponentType { header_name: _From::name().as_str() })
+            }
+        }
 
         if needs_sender && !map.contains(Sender) {
             //this is the wrong bail...
@@ -142,43 +183,74 @@ mod validators {
         Ok(())
     }
 
-    fn validate_resent_block<'a>(
-            block: &HashMap<HeaderName, &'a HeaderObj>
-    ) -> Result<(), HeaderValidationError> {
-        if !block.contains_key(&ResentDate::name()) {
+    /// Returns the header with the name of `H` in the block (if any).
+    ///
+    /// If it can not be casted to `H` this fails, or with lenient
+    /// validator options returns `None`.
+    fn typed_in_block<'a, H>(
+            block: &ResentBlock<'a>,
+            lenient: bool
+    ) -> Result<Option<&'a Header<H>>, HeaderValidationError>
+        where H: HeaderKind
+    {
+        match block._get::<H>() {
+            None => Ok(None),
+            Some(Ok(header)) => Ok(Some(header)),
+            Some(Err(_)) if lenient => Ok(None),
+            Some(Err(_)) => header_validation_bail!(
+                kind: UnexpectedComponentType { header_name: H::name().as_str() })
+        }
+    }
+
+    fn validate_resent_block(block: &ResentBlock, lenient: bool) -> Result<(), HeaderValidationError> {
+        if !block.contains(ResentDate::name()) {
             //this is the wrong bail...
             header_validation_bail!(kind: ResentDateFieldMissing);
         }
+        typed_in_block::<ResentDate>(block, lenient)?;
+        typed_in_block::<ResentSender>(block, lenient)?;
         let needs_sender =
             //no Resend-From? => no problem
-            block.get(&ResentFrom::name())
-                //can't cast? => not my problem/responsibility
-                .and_then(|tobj| tobj.downcast_ref::<ResentFrom>())
+            typed_in_block::<ResentFrom>(block, lenient)?
                 .map(|list| list.len() > 1)
                 .unwrap_or(false);
 
-        if needs_sender && !block.contains_key(&ResentSender::name()) {
+        if needs_sender && !block.contains(ResentSender::name()) {
             //this is the wrong bail...
             header_validation_bail!(kind: MultiMailboxResentFromWithoutResentSender)
         }
         Ok(())
     }
 
+    /// Validates each block returned by `HeaderMap::resent_blocks`.
     pub fn resent_any(map: &HeaderMap) -> Result<(), HeaderValidationError> {
-        let resents = map
-            .iter()
-            .filter(|&(name, _)| name.as_str().starts_with("Resent-"));
-
-        let mut block = HashMap::new();
-        for (name, content) in resents {
-            if block.contains_key(&name) {
-                validate_resent_block(&block)?;
-                //create new block
-                block = HashMap::new();
-            }
-            block.insert(name, content);
+        let lenient = map.validator_options().lenient_component_types;
+        for block in map.resent_blocks() {
+            validate_resent_block(&block, lenient)?;
         }
-        validate_resent_block(&block)
+        Ok(())
+    }
+
+    /// Checks that the headers with given name use the component the validators above expect.
+    ///
+    /// This is the structural part of the validators, which is run instead
+    /// of them when validating a draft (see `map::ValidationStage`). Names
+    /// not checked by the validators are ignored.
+    pub fn component_type(map: &HeaderMap, name: HeaderName) -> Result<(), HeaderValidationError> {
+        if map.validator_options().lenient_component_types {
+            return Ok(());
+        }
+        let mismatch =
+            if name == _From::name() { map.kind_mismatch::<_From>() }
+            else if name == ResentDate::name() { map.kind_mismatch::<ResentDate>() }
+            else if name == ResentFrom::name() { map.kind_mismatch::<ResentFrom>() }
+            else if name == ResentSender::name() { map.kind_mismatch::<ResentSender>() }
+            else { None };
+
+        if mismatch.is_some() {
+            header_validation_bail!(kind: UnexpectedComponentType { header_name: name.as_str() });
+        }
+        Ok(())
     }
 }
 
@@ -188,8 +260,26 @@ mod test {
     use ::{HeaderMap, HeaderKind};
     use ::headers::{
         _From, ResentFrom, ResentTo, ResentDate,
-        Sender, ResentSender, Subject
+        Sender, ResentSender, Subject,
+        Precedence, XAutoResponseSuppress,
+        ListId, ListPost, ListArchive, ListHelp, ListSubscribe,
+        FeedbackId, XReportAbuse, XSpamStatus
+    };
+    use ::header_components::{
+        Precedence as PrecedenceComponent,
+        AutoResponseSuppressToken
     };
+    use ::header_components as components;
+    use ::map::MboxOptions;
+    use internals::MailType;
+    use super::MessageId;
+
+    def_headers! {
+        test_name: validate_test_header_names,
+        scope: components,
+        MimeVersion, unchecked { "Mime-Version", wire: "MIME-Version" }, Unstructured, maxOne, None,
+        OtherMimeVersion, unchecked { "Mime-Version" }, Unstructured, maxOne, None
+    }
 
     test!(from_validation_normal {
         let mut map = HeaderMap::new();
@@ -273,4 +363,144 @@ mod test {
         assert_ok!(map.use_contextual_validators());
     });
 
-}
\ No newline at end of file
+    test!(auto_response_headers {
+        let map = headers! {
+            Precedence: "Bulk",
+            XAutoResponseSuppress: "OOF, AutoReply"
+        }?;
+
+        let precedence = map.get_single(Precedence).unwrap()?;
+        assert_eq!(precedence.body(), &PrecedenceComponent::Bulk);
+
+        let suppress = map.get_single(XAutoResponseSuppress).unwrap()?;
+        assert!(suppress.contains(AutoResponseSuppressToken::OOF));
+        assert!(suppress.contains(AutoResponseSuppressToken::AutoReply));
+        assert!(!suppress.contains(AutoResponseSuppressToken::DR));
+    });
+
+    test!(list_headers_round_trip {
+        let map = headers! {
+            ListId: "Friendly Name <list-id.example.com>",
+            ListPost: "<mailto:list@example.com>",
+            ListArchive: "<https://example.com/archive>, <mailto:archive@example.com>",
+            ListHelp: "<mailto:list-request@example.com?subject=help> (List Instructions)",
+            ListSubscribe: "<mailto:list-request@example.com?subject=subscribe>"
+        }?;
+
+        let block = String::from_utf8(map.encode_checked(MailType::Ascii)?)?;
+        assert_eq!(block, concat!(
+            "List-Id: Friendly Name <list-id.example.com>\r\n",
+            "List-Post: <mailto:list@example.com>\r\n",
+            "List-Archive: <https://example.com/archive>, <mailto:archive@example.com>\r\n",
+            "List-Help: <mailto:list-request@example.com?subject=help>\r\n",
+            "List-Subscribe: <mailto:list-request@example.com?subject=subscribe>\r\n"
+        ));
+
+        for line in block.lines() {
+            let (name, value) = line.split_at(line.find(": ").unwrap());
+            let value = &value[2..];
+            match name {
+                "List-Id" => assert_eq!(ListId::auto_body(value)?.to_string(), value),
+                _ => assert_eq!(ListPost::auto_body(value)?.to_string(), value)
+            }
+        }
+
+        let archive = map.get_single(ListArchive).unwrap()?;
+        assert_eq!(archive.uris().len(), 2);
+        assert_eq!(map.get_single(ListId).unwrap()?.id(), "list-id.example.com");
+    });
+
+    test!(list_post_no {
+        let map = headers! {
+            ListPost: "NO (posting not allowed on this list)"
+        }?;
+        assert!(map.get_single(ListPost).unwrap()?.is_no());
+
+        let block = String::from_utf8(map.encode_checked(MailType::Ascii)?)?;
+        assert_eq!(block, "List-Post: NO\r\n");
+    });
+
+    test!(list_id_without_phrase {
+        let map = headers! {
+            ListId: "<list-id.example.com>"
+        }?;
+        let list_id = map.get_single(ListId).unwrap()?;
+        assert!(list_id.phrase().is_none());
+
+        let block = String::from_utf8(map.encode_checked(MailType::Ascii)?)?;
+        assert_eq!(block, "List-Id: <list-id.example.com>\r\n");
+    });
+
+    test!(feedback_headers {
+        let feedback_id = components::FeedbackId::new("summer-sale")?
+            .with_mail_type("newsletter")?;
+        let map = headers! {
+            FeedbackId: feedback_id.clone(),
+            XReportAbuse: "<https://example.com/report-abuse>"
+        }?;
+
+        assert_eq!(map.get_single(FeedbackId).unwrap()?.body(), &feedback_id);
+        let block = String::from_utf8(map.encode_checked(MailType::Ascii)?)?;
+        assert_eq!(block, concat!(
+            "Feedback-ID: summer-sale::newsletter\r\n",
+            "X-Report-Abuse: <https://example.com/report-abuse>\r\n"
+        ));
+
+        let map = headers! {
+            FeedbackId: "summer-sale:customer42:newsletter:esp1"
+        }?;
+        let parsed = map.get_single(FeedbackId).unwrap()?;
+        assert_eq!(parsed.customer(), Some("customer42"));
+        assert_eq!(parsed.sender_id(), Some("esp1"));
+
+        let err = assert_err!(FeedbackId::auto_body("summer:sale:news:letter:esp1"));
+        assert_eq!(err.span(), Some(23..24));
+        assert_err!(headers! { FeedbackId: "summer sale" });
+    });
+
+    test!(spam_status_header {
+        let map = headers! {
+            XSpamStatus: "yes,score=7.2  required=5.0 tests=BAYES_99,HTML_MESSAGE"
+        }?;
+        let status = map.get_single(XSpamStatus).unwrap()?;
+        assert!(status.is_spam());
+        assert!(status.exceeds(status.required().unwrap()));
+
+        let block = String::from_utf8(map.encode_checked(MailType::Ascii)?)?;
+        assert_eq!(block, "X-Spam-Status: Yes, score=7.2 required=5.0 tests=BAYES_99,HTML_MESSAGE\r\n");
+
+        assert_err!(headers! { XSpamStatus: "score=7.2" });
+    });
+
+    test!(names_differing_in_wire_case_are_the_same_key {
+        let mut map = headers! {
+            MimeVersion: "1.0"
+        }?;
+        assert!(map.contains(OtherMimeVersion));
+        map.insert(OtherMimeVersion::auto_body("1.0")?);
+        assert_eq!(map.len(), 1);
+    });
+
+    test!(lookups_ignore_wire_case {
+        let map = headers! {
+            MimeVersion: "1.0"
+        }?;
+        let index = map.build_name_index();
+        assert!(index.contains_str_ignore_case("MIME-Version"));
+        assert!(index.contains_str_ignore_case("mime-version"));
+        assert!(index.contains_str_ignore_case("Mime-Version"));
+        assert!(index.contains(&MimeVersion::name()));
+    });
+
+    test!(encoding_uses_wire_case {
+        let map = headers! {
+            MimeVersion: "1.0",
+            MessageId: "abc@example.com"
+        }?;
+        let block = map.encode_for_mbox(MailType::Ascii, MboxOptions::default())?;
+        assert_eq!(
+            String::from_utf8(block)?,
+            "MIME-Version: 1.0\nMessage-ID: <abc@example.com>\n\n"
+        );
+    });
+}
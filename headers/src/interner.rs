@@ -0,0 +1,340 @@
+//! Provides a pool for sharing the text of repeatedly used header values.
+//!
+//! When creating a large number of mails (e.g. for a campaign) many of
+//! them will have the exact same `From`, `Reply-To`, `Subject` etc. By
+//! creating these components through a `HeaderInterner` all of them
+//! share the same underlying text buffers instead of each owning a copy.
+//!
+//! Components created by the interner are normal components, they compare,
+//! hash and encode exactly like components created without it. The shared
+//! text is immutable, so modifying a header in one map (e.g. replacing the
+//! `Subject`) never affects another map.
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use ::header::{HeaderKind, Header};
+use ::error::ComponentCreationError;
+use ::header_components::{Unstructured, Mailbox, Email, Phrase};
+use ::headers::Subject;
+use ::data::Input;
+
+/// Statistics about a `HeaderInterner`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct InternerStats {
+    /// the number of unique values in the pool
+    pub entries: usize,
+    /// how often a value was taken from the pool instead of being created
+    pub hits: u64,
+    /// the number of text bytes which did not need to be allocated due to hits
+    pub bytes_saved: u64,
+    /// how often a value was not put into the pool as it was full
+    pub rejected: u64
+}
+
+/// A thread-safe pool for sharing the text of identical header values.
+///
+/// To not grow unboundedly the pool can be cleared explicitly using `clear`
+/// and/or created with a maximal number of entries (`with_max_entries`).
+/// Once the pool is full new values are still created, but they are not
+/// added to the pool (and as such do not share their text).
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate mail_headers;
+/// # fn main() {
+/// use mail_headers::{HeaderMap, HeaderKind};
+/// use mail_headers::headers::*;
+/// use mail_headers::header_components::MailboxList;
+/// use mail_headers::interner::HeaderInterner;
+///
+/// let interner = HeaderInterner::new();
+///
+/// let maps = (0..10).map(|_| {
+///     let from = interner.mailbox(Some("Newsletter"), "news@example.com").unwrap();
+///     let mut map = HeaderMap::new();
+///     map.insert(_From::body(MailboxList::from_single(from)));
+///     map.insert(interner.subject("Our weekly news"));
+///     map
+/// }).collect::<Vec<_>>();
+///
+/// assert_eq!(maps.len(), 10);
+/// assert_eq!(interner.stats().entries, 2);
+/// assert_eq!(interner.stats().hits, 18);
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct HeaderInterner {
+    max_entries: Option<usize>,
+    pool: Mutex<Pool>
+}
+
+#[derive(Debug, Default)]
+struct Pool {
+    unstructured: HashMap<SharedText, Unstructured>,
+    /// mailboxes by email, with the display name they were created with
+    mailboxes: HashMap<SharedText, Vec<(Option<SharedText>, Mailbox)>>,
+    stats: InternerStats
+}
+
+/// A pool key using the same shared text buffer as the pooled value.
+///
+/// This way the pooled text is stored only once, lookups with a `&str`
+/// work through `Borrow<str>` (`Input` hashes and compares like a `str`).
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+struct SharedText(Input);
+
+impl SharedText {
+    fn new(text: &str) -> Self {
+        SharedText(Input::from(text).into_shared())
+    }
+}
+
+impl Borrow<str> for SharedText {
+    fn borrow(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Pool {
+    fn is_full(&self, max_entries: Option<usize>) -> bool {
+        max_entries.map(|max| self.stats.entries >= max).unwrap_or(false)
+    }
+}
+
+impl HeaderInterner {
+
+    /// Creates a new interner with an unbounded pool.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates a new interner which pools at most `max_entries` values.
+    pub fn with_max_entries(max_entries: usize) -> Self {
+        HeaderInterner {
+            max_entries: Some(max_entries),
+            pool: Default::default()
+        }
+    }
+
+    /// Returns a `Unstructured` component with given text, sharing the text if possible.
+    pub fn unstructured(&self, text: &str) -> Unstructured {
+        let mut pool = self.lock();
+        if let Some(found) = pool.unstructured.get(text).cloned() {
+            pool.stats.hits += 1;
+            pool.stats.bytes_saved += text.len() as u64;
+            return found;
+        }
+
+        let key = SharedText::new(text);
+        let value = Unstructured::from(key.0.clone());
+        if pool.is_full(self.max_entries) {
+            pool.stats.rejected += 1;
+        } else {
+            pool.unstructured.insert(key, value.clone());
+            pool.stats.entries += 1;
+        }
+        value
+    }
+
+    /// Returns a `Subject` header with given text, sharing the text if possible.
+    pub fn subject(&self, text: &str) -> Header<Subject> {
+        Subject::body(self.unstructured(text))
+    }
+
+    /// Returns a `Mailbox` with given display name and email, sharing the text if possible.
+    ///
+    /// The mailbox is created the same way `Mailbox::try_from((name, email))`
+    /// (or `Mailbox::try_from((NoDisplayName, email))` if there is no name)
+    /// would, so it fails for the same inputs.
+    pub fn mailbox(&self, display_name: Option<&str>, email: &str)
+        -> Result<Mailbox, ComponentCreationError>
+    {
+        let mut pool = self.lock();
+        let found = pool.mailboxes.get(email)
+            .and_then(|mailboxes| mailboxes.iter().find(|&&(ref name, _)| {
+                name.as_ref().map(|name| name.0.as_str()) == display_name
+            }))
+            .map(|&(_, ref mailbox)| mailbox.clone());
+        if let Some(found) = found {
+            pool.stats.hits += 1;
+            pool.stats.bytes_saved += (display_name.map(str::len).unwrap_or(0) + email.len()) as u64;
+            return Ok(found);
+        }
+
+        let email_key = SharedText::new(email);
+        let name_key = display_name.map(SharedText::new);
+        let display_name = match name_key {
+            Some(ref name) => Some(Phrase::new(name.0.clone())?),
+            None => None
+        };
+        // the local part shares the text of the key
+        let value = Mailbox::from((display_name, Email::new(email_key.0.clone())?)).into_shared();
+        if pool.is_full(self.max_entries) {
+            pool.stats.rejected += 1;
+        } else {
+            pool.mailboxes.entry(email_key)
+                .or_insert_with(Vec::new)
+                .push((name_key, value.clone()));
+            pool.stats.entries += 1;
+        }
+        Ok(value)
+    }
+
+    /// Returns the current statistics of this interner.
+    pub fn stats(&self) -> InternerStats {
+        self.lock().stats
+    }
+
+    /// Removes all values from the pool and resets the statistics.
+    ///
+    /// Already created components are not affected.
+    pub fn clear(&self) {
+        let mut pool = self.lock();
+        pool.unstructured.clear();
+        pool.mailboxes.clear();
+        pool.stats = InternerStats::default();
+    }
+
+    fn lock(&self) -> ::std::sync::MutexGuard<Pool> {
+        // the pool is never left in a inconsistent state, so we can ignore poisoning
+        match self.pool.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+    use std::sync::Arc;
+
+    use internals::MailType;
+    use internals::encoder::{EncodingBuffer, EncodableInHeader};
+    use ::header_components::MailboxList;
+    use ::headers::{_From, Subject};
+    use ::{HeaderKind, HeaderMap, HeaderTryFrom};
+    use super::*;
+
+    fn encode(value: &EncodableInHeader) -> Vec<u8> {
+        let mut encoder = EncodingBuffer::new(MailType::Ascii);
+        {
+            let mut handle = encoder.writer();
+            value.encode(&mut handle).unwrap();
+            handle.finish_header();
+        }
+        encoder.as_slice().to_owned()
+    }
+
+    #[test]
+    fn interned_values_behave_like_normal_ones() {
+        let interner = HeaderInterner::new();
+
+        let interned = interner.unstructured("a subject");
+        let normal = Unstructured::from("a subject");
+        assert_eq!(interned, normal);
+        assert_eq!(encode(&interned), encode(&normal));
+
+        let interned = interner.mailbox(Some("Me"), "me@example.com").unwrap();
+        let normal = Mailbox::try_from(("Me", "me@example.com")).unwrap();
+        assert_eq!(interned, normal);
+        assert_eq!(encode(&interned), encode(&normal));
+    }
+
+    #[test]
+    fn identical_values_are_pooled() {
+        let interner = HeaderInterner::new();
+        for _ in 0..3 {
+            interner.unstructured("abc");
+            interner.mailbox(None, "me@example.com").unwrap();
+        }
+        interner.mailbox(Some("Me"), "me@example.com").unwrap();
+
+        assert_eq!(interner.stats(), InternerStats {
+            entries: 3,
+            hits: 4,
+            bytes_saved: 2 * 3 + 2 * 14,
+            rejected: 0
+        });
+
+        interner.clear();
+        assert_eq!(interner.stats(), InternerStats::default());
+    }
+
+    #[test]
+    fn max_entries_is_respected() {
+        let interner = HeaderInterner::with_max_entries(1);
+        interner.unstructured("abc");
+        let not_pooled = interner.unstructured("def");
+        assert_eq!(not_pooled.as_str(), "def");
+        interner.unstructured("def");
+
+        let stats = interner.stats();
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.rejected, 2);
+        assert_eq!(stats.hits, 0);
+    }
+
+    #[test]
+    fn invalid_mailboxes_fail() {
+        let interner = HeaderInterner::new();
+        assert_err!(interner.mailbox(None, "no at sign"));
+        assert_eq!(interner.stats().entries, 0);
+    }
+
+    test!(mutating_one_map_does_not_affect_others {
+        let interner = HeaderInterner::new();
+        let mut maps = (0..2).map(|_| {
+            let mut map = HeaderMap::new();
+            map.insert(interner.subject("shared subject"));
+            let from = interner.mailbox(Some("Me"), "me@example.com").unwrap();
+            map.insert(_From::body(MailboxList::from_single(from)));
+            map
+        }).collect::<Vec<_>>();
+
+        {
+            let subject = maps[0].get_single_mut(Subject).unwrap()?;
+            *subject.body_mut() = Unstructured::from("changed subject");
+        }
+        {
+            let from = maps[0].get_single_mut(_From).unwrap()?;
+            from.body_mut()[0].email = Email::new("other@example.com")?;
+        }
+
+        assert_eq!(maps[0].get_single(Subject).unwrap()?.as_str(), "changed subject");
+        assert_eq!(maps[1].get_single(Subject).unwrap()?.as_str(), "shared subject");
+        assert_eq!(
+            maps[1].get_single(_From).unwrap()?[0].email.domain.as_str(),
+            "example.com"
+        );
+        assert_eq!(
+            maps[1].get_single(_From).unwrap()?[0].email.local_part.as_str(),
+            "me"
+        );
+        assert_eq!(interner.subject("shared subject").as_str(), "shared subject");
+    });
+
+    #[test]
+    fn can_be_shared_between_threads() {
+        let interner = Arc::new(HeaderInterner::new());
+        let handles = (0..4).map(|_| {
+            let interner = interner.clone();
+            thread::spawn(move || {
+                for _ in 0..10 {
+                    interner.unstructured("from many threads");
+                }
+            })
+        }).collect::<Vec<_>>();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let stats = interner.stats();
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.hits, 39);
+    }
+}
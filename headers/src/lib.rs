@@ -17,6 +17,7 @@
 //! - a `headers!` macro for making the creation of an `HeaderMap`
 //!   with a number of headers easier.
 //! - a `def_headers!` macro for defining new custom headers
+//! - a `prelude` module re-exporting the most commonly used items
 //!
 //! ## Example (HeaderMap)
 //!
@@ -115,8 +116,7 @@
 //! ```
 
 extern crate mime;
-#[doc(hidden)]
-pub extern crate soft_ascii_string;
+extern crate soft_ascii_string as soft_ascii;
 extern crate quoted_string;
 #[macro_use]
 extern crate failure;
@@ -127,15 +127,19 @@ extern crate chrono;
 #[cfg_attr(test, macro_use)]
 extern crate vec1;
 extern crate total_order_multi_map;
-//FIXME[rust/macros use private] remove pub re-export
+extern crate once_cell;
 #[cfg_attr(test, macro_use)]
-#[doc(hidden)]
-pub extern crate mail_internals as __internals;
+extern crate mail_internals as internals;
 
 #[cfg(feature="serde")]
 extern crate serde;
+#[cfg(feature="serde-impl")]
+#[cfg_attr(test, macro_use)]
+extern crate serde_json;
 #[cfg(all(test, feature="serde"))]
 extern crate serde_test;
+#[cfg(feature="compress-values")]
+extern crate flate2;
 
 #[cfg(all(test, not(feature="traceing")))]
 compile_error! { "testing needs feature `traceing` to be enabled" }
@@ -157,6 +161,24 @@ mod header_macro;
 pub mod map;
 pub mod header_components;
 pub mod headers;
+pub mod interner;
+pub mod mail_type;
+pub mod params;
+pub mod rand_source;
+pub mod related;
+pub mod transform;
+pub mod prelude;
+pub mod capabilities;
+pub mod lint;
+#[cfg(feature="serde-impl")]
+pub mod from_json;
+#[cfg(feature="serde-impl")]
+pub mod header_group;
+#[cfg(feature="test-helpers")]
+#[macro_use]
+pub mod testing;
+#[cfg(test)]
+mod trait_impls;
 
 pub use self::name::*;
 pub use self::header::*;
@@ -165,6 +187,25 @@ pub use self::header_macro::*;
 pub use self::map::HeaderMap;
 
 
-// I can not reexport a private think anymore, so I need to reexport the
-// extern crate and then make the normal name available, too
-use __internals as internals;
\ No newline at end of file
+/// Items used by the macros exported by this crate.
+///
+/// **This is not part of the public API and can change at any time.**
+#[doc(hidden)]
+pub mod __internal {
+    pub use soft_ascii::SoftAsciiStr;
+    pub use internals::encoder::EncodableInHeader;
+}
+
+/// The `soft-ascii-string` crate, use it directly instead.
+#[deprecated(since = "0.6.0", note = "depend on the `soft-ascii-string` crate directly")]
+#[doc(hidden)]
+pub mod soft_ascii_string {
+    pub use soft_ascii::*;
+}
+
+/// The `mail-internals` crate, use it directly instead.
+#[deprecated(since = "0.6.0", note = "depend on the `mail-internals` crate directly")]
+#[doc(hidden)]
+pub mod __internals {
+    pub use internals::*;
+}
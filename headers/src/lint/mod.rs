@@ -0,0 +1,427 @@
+//! Advisory linting of outbound headers.
+//!
+//! Unlike validation (see `HeaderMap::use_contextual_validators`) lints do
+//! not reject a header map, they point out headers which are valid but
+//! likely to cause problems, e.g. a all-caps `Subject` which hurts
+//! deliverability. Each lint rule has a id (e.g. `subject-too-long`) and
+//! a default severity, `lint_headers` runs all enabled rules of a `LintSet`
+//! and collects their findings in a `LintReport`.
+//!
+//! # Example
+//!
+//! ```
+//! # #[macro_use]
+//! # extern crate mail_headers;
+//! # fn main() {
+//! use mail_headers::headers::*;
+//! use mail_headers::lint::{lint_headers, LintSet, Severity};
+//!
+//! let map = headers! {
+//!     _From: ["news@example.com"],
+//!     Subject: "BUY NOW"
+//! }.unwrap();
+//!
+//! let mut set = LintSet::builtin();
+//! set.override_severity("subject-all-caps", Severity::Error);
+//! let report = lint_headers(&map, &set);
+//!
+//! assert_eq!(report.findings().len(), 1);
+//! assert_eq!(report.findings()[0].rule_id, "subject-all-caps");
+//! assert_eq!(report.max_severity(), Some(Severity::Error));
+//! # }
+//! ```
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+
+#[cfg(feature="serde")]
+use serde::Serialize;
+
+use ::map::HeaderMap;
+
+mod rules;
+pub use self::rules::{
+    SubjectTooLong, SubjectAllCaps, BulkWithoutListUnsubscribe,
+    RedundantReplyTo, TooManyToRecipients, ReceivedChainTooLong
+};
+
+/// How severe a lint finding is.
+///
+/// The severities are ordered, i.e. `Info < Warning < Error`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature="serde", derive(Serialize))]
+#[cfg_attr(feature="serde", serde(rename_all="lowercase"))]
+pub enum Severity {
+    /// The header is pointless but harmless.
+    Info,
+    /// The header is likely to cause problems, e.g. with spam filters.
+    Warning,
+    /// The header is very likely to cause problems, e.g. the mail being rejected.
+    Error
+}
+
+impl Severity {
+
+    /// The lowercase name of the severity, e.g. `warning`.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error"
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(self.as_str())
+    }
+}
+
+/// A lint rule, see the module documentation.
+pub trait Lint: Send + Sync {
+
+    /// The id of the rule, e.g. `subject-too-long`.
+    fn id(&self) -> &'static str;
+
+    /// The default severity of findings of this rule.
+    fn severity(&self) -> Severity;
+
+    /// Checks the headers, returning one finding per problem.
+    ///
+    /// The findings should be created with `LintFinding::new` so that
+    /// they carry the id and severity of this rule.
+    fn check(&self, map: &HeaderMap) -> Vec<LintFinding>;
+}
+
+/// A problem found by a lint rule.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature="serde", derive(Serialize))]
+pub struct LintFinding {
+    /// The id of the rule which found the problem.
+    pub rule_id: &'static str,
+    /// The severity of the finding (after applying severity overrides).
+    pub severity: Severity,
+    /// The name of the header the finding is about.
+    pub header_name: &'static str,
+    /// A human readable description of the problem.
+    pub message: String
+}
+
+impl LintFinding {
+
+    /// Creates a finding with the id and (default) severity of given rule.
+    pub fn new<L, M>(rule: &L, header_name: &'static str, message: M) -> Self
+        where L: Lint + ?Sized, M: Into<String>
+    {
+        LintFinding {
+            rule_id: rule.id(),
+            severity: rule.severity(),
+            header_name,
+            message: message.into()
+        }
+    }
+}
+
+impl Display for LintFinding {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        write!(fter, "[{}] {}: {}", self.rule_id, self.header_name, self.message)
+    }
+}
+
+/// A set of lint rules together with per-rule settings.
+///
+/// Rules can be disabled (and enabled again) and the severity of their
+/// findings can be overridden by rule id. Settings for ids of rules which
+/// are not in the set are kept, so they can be configured before the
+/// rule is added.
+pub struct LintSet {
+    rules: Vec<Box<Lint>>,
+    disabled: HashSet<&'static str>,
+    severity_overrides: HashMap<&'static str, Severity>
+}
+
+impl LintSet {
+
+    /// Creates a empty set.
+    pub fn new() -> Self {
+        LintSet {
+            rules: Vec::new(),
+            disabled: HashSet::new(),
+            severity_overrides: HashMap::new()
+        }
+    }
+
+    /// Creates a set with all built-in rules (with their default settings).
+    pub fn builtin() -> Self {
+        let mut set = LintSet::new();
+        set.add(SubjectTooLong::default());
+        set.add(SubjectAllCaps);
+        set.add(BulkWithoutListUnsubscribe);
+        set.add(RedundantReplyTo);
+        set.add(TooManyToRecipients::default());
+        set.add(ReceivedChainTooLong::default());
+        set
+    }
+
+    /// Adds a rule, replacing any rule with the same id.
+    pub fn add<L>(&mut self, rule: L)
+        where L: Lint + 'static
+    {
+        let id = rule.id();
+        self.rules.retain(|other| other.id() != id);
+        self.rules.push(Box::new(rule));
+    }
+
+    /// Disables the rule with given id.
+    pub fn disable(&mut self, id: &'static str) {
+        self.disabled.insert(id);
+    }
+
+    /// Enables the rule with given id again.
+    pub fn enable(&mut self, id: &'static str) {
+        self.disabled.remove(id);
+    }
+
+    /// Returns true if the rule with given id is in the set and enabled.
+    pub fn is_enabled(&self, id: &str) -> bool {
+        !self.disabled.contains(id) && self.rules.iter().any(|rule| rule.id() == id)
+    }
+
+    /// Overrides the severity of all findings of the rule with given id.
+    pub fn override_severity(&mut self, id: &'static str, severity: Severity) {
+        self.severity_overrides.insert(id, severity);
+    }
+
+    /// Returns the severity findings of the rule with given id have.
+    ///
+    /// Returns `None` if the set doesn't contain a rule with that id.
+    pub fn severity_of(&self, id: &str) -> Option<Severity> {
+        let rule = self.rules.iter().find(|rule| rule.id() == id)?;
+        Some(self.severity_overrides.get(id).cloned().unwrap_or_else(|| rule.severity()))
+    }
+
+    /// Iterates over the ids of all rules in the set (including disabled ones).
+    pub fn rule_ids<'a>(&'a self) -> impl Iterator<Item=&'static str> + 'a {
+        self.rules.iter().map(|rule| rule.id())
+    }
+}
+
+impl Default for LintSet {
+    fn default() -> Self {
+        LintSet::builtin()
+    }
+}
+
+impl fmt::Debug for LintSet {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.debug_struct("LintSet")
+            .field("rules", &self.rule_ids().collect::<Vec<_>>())
+            .field("disabled", &self.disabled)
+            .field("severity_overrides", &self.severity_overrides)
+            .finish()
+    }
+}
+
+/// The findings of all rules run by `lint_headers`.
+///
+/// The `Display` implementation groups the findings by severity (most
+/// severe first), with one finding per line.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature="serde", derive(Serialize))]
+pub struct LintReport {
+    findings: Vec<LintFinding>
+}
+
+impl LintReport {
+
+    /// All findings, in the order of the rules in the set.
+    pub fn findings(&self) -> &[LintFinding] {
+        &self.findings
+    }
+
+    /// Returns true if there are no findings.
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Iterates over all findings with given severity.
+    pub fn with_severity<'a>(&'a self, severity: Severity) -> impl Iterator<Item=&'a LintFinding> + 'a {
+        self.findings.iter().filter(move |finding| finding.severity == severity)
+    }
+
+    /// Returns the highest severity of all findings.
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.findings.iter().map(|finding| finding.severity).max()
+    }
+
+    /// Consumes the report returning the findings.
+    pub fn into_findings(self) -> Vec<LintFinding> {
+        self.findings
+    }
+}
+
+impl Display for LintReport {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        if self.findings.is_empty() {
+            return fter.write_str("no lint findings\n");
+        }
+        for &severity in &[Severity::Error, Severity::Warning, Severity::Info] {
+            let count = self.with_severity(severity).count();
+            if count == 0 {
+                continue;
+            }
+            writeln!(fter, "{} ({}):", severity, count)?;
+            for finding in self.with_severity(severity) {
+                writeln!(fter, "  {}", finding)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs all enabled rules of the set on the headers.
+///
+/// This is advisory only, it neither modifies the map nor interacts with
+/// the validators of the map.
+pub fn lint_headers(map: &HeaderMap, set: &LintSet) -> LintReport {
+    let mut findings = Vec::new();
+    for rule in set.rules.iter() {
+        let id = rule.id();
+        if set.disabled.contains(id) {
+            continue;
+        }
+        let severity = set.severity_overrides.get(id).cloned();
+        for mut finding in rule.check(map) {
+            if let Some(severity) = severity {
+                finding.severity = severity;
+            }
+            findings.push(finding);
+        }
+    }
+    LintReport { findings }
+}
+
+#[cfg(test)]
+mod test {
+    use ::headers::*;
+    use super::*;
+
+    fn noisy_map() -> HeaderMap {
+        headers! {
+            _From: ["news@example.com"],
+            ReplyTo: ["news@example.com"],
+            Subject: "HUGE SALE, ONLY TODAY AND ONLY FOR YOU, DON'T MISS OUT ON THE OFFER OF THE YEAR",
+            Precedence: "bulk"
+        }.unwrap()
+    }
+
+    fn rule_ids(report: &LintReport) -> Vec<&'static str> {
+        report.findings().iter().map(|finding| finding.rule_id).collect()
+    }
+
+    test!(runs_all_rules_of_the_set {
+        let report = lint_headers(&noisy_map(), &LintSet::builtin());
+        assert_eq!(rule_ids(&report), vec![
+            "subject-too-long",
+            "subject-all-caps",
+            "bulk-without-list-unsubscribe",
+            "redundant-reply-to"
+        ]);
+        assert_eq!(report.max_severity(), Some(Severity::Error));
+    });
+
+    test!(clean_map_has_no_findings {
+        let map = headers! {
+            _From: ["news@example.com"],
+            Subject: "hy there"
+        }?;
+        let report = lint_headers(&map, &LintSet::builtin());
+        assert!(report.is_empty());
+        assert_eq!(report.max_severity(), None);
+        assert_eq!(report.to_string(), "no lint findings\n");
+    });
+
+    test!(rules_can_be_disabled_and_enabled {
+        let mut set = LintSet::builtin();
+        set.disable("subject-all-caps");
+        set.disable("redundant-reply-to");
+        assert!(!set.is_enabled("subject-all-caps"));
+
+        let report = lint_headers(&noisy_map(), &set);
+        assert_eq!(rule_ids(&report), vec!["subject-too-long", "bulk-without-list-unsubscribe"]);
+
+        set.enable("subject-all-caps");
+        assert!(set.is_enabled("subject-all-caps"));
+        let report = lint_headers(&noisy_map(), &set);
+        assert_eq!(rule_ids(&report), vec![
+            "subject-too-long",
+            "subject-all-caps",
+            "bulk-without-list-unsubscribe"
+        ]);
+    });
+
+    test!(severity_can_be_overridden {
+        let mut set = LintSet::builtin();
+        assert_eq!(set.severity_of("redundant-reply-to"), Some(Severity::Info));
+        set.override_severity("redundant-reply-to", Severity::Error);
+        assert_eq!(set.severity_of("redundant-reply-to"), Some(Severity::Error));
+        assert_eq!(set.severity_of("no-such-rule"), None);
+
+        let report = lint_headers(&noisy_map(), &set);
+        let finding = report.findings().iter()
+            .find(|finding| finding.rule_id == "redundant-reply-to")
+            .unwrap();
+        assert_eq!(finding.severity, Severity::Error);
+    });
+
+    test!(adding_a_rule_replaces_one_with_the_same_id {
+        let mut set = LintSet::builtin();
+        let count = set.rule_ids().count();
+        set.add(SubjectTooLong { max_chars: 200 });
+        assert_eq!(set.rule_ids().count(), count);
+
+        let report = lint_headers(&noisy_map(), &set);
+        assert!(!rule_ids(&report).contains(&"subject-too-long"));
+    });
+
+    test!(display_groups_by_severity {
+        let mut set = LintSet::builtin();
+        set.override_severity("subject-all-caps", Severity::Info);
+        let report = lint_headers(&noisy_map(), &set);
+
+        let text = report.to_string();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "error (1):");
+        assert!(lines[1].starts_with("  [bulk-without-list-unsubscribe] List-Unsubscribe: "), "{:?}", lines[1]);
+        assert_eq!(lines[2], "warning (1):");
+        assert!(lines[3].starts_with("  [subject-too-long] Subject: "), "{:?}", lines[3]);
+        assert_eq!(lines[4], "info (2):");
+        assert!(lines[5].starts_with("  [subject-all-caps] Subject: "), "{:?}", lines[5]);
+        assert!(lines[6].starts_with("  [redundant-reply-to] Reply-To: "), "{:?}", lines[6]);
+        assert_eq!(lines.len(), 7);
+    });
+
+    test!(does_not_interact_with_validators {
+        let map = noisy_map();
+        lint_headers(&map, &LintSet::builtin());
+        assert_ok!(map.use_contextual_validators());
+        assert!(map.validators().is_empty());
+    });
+
+    #[cfg(feature="serde")]
+    test!(report_can_be_serialized {
+        let mut set = LintSet::new();
+        set.add(RedundantReplyTo);
+        let report = lint_headers(&noisy_map(), &set);
+
+        let value = ::serde_json::to_value(&report)?;
+        assert_eq!(value, json!({
+            "findings": [{
+                "rule_id": "redundant-reply-to",
+                "severity": "info",
+                "header_name": "Reply-To",
+                "message": report.findings()[0].message.clone()
+            }]
+        }));
+    });
+}
@@ -0,0 +1,330 @@
+//! The built-in lint rules.
+use std::collections::HashSet;
+
+use ::map::HeaderMap;
+use ::headers::{Subject, Precedence, _From, ReplyTo, _To, Received};
+use ::header_components::{self as components, MailboxList};
+
+use super::{Lint, LintFinding, Severity};
+
+/// `subject-too-long`: the `Subject` has more than `max_chars` characters.
+///
+/// Long subjects are cut off by most mail clients and are rated
+/// negatively by some spam filters. The default maximum is 78
+/// characters (the recommended line length of rfc5322).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SubjectTooLong {
+    pub max_chars: usize
+}
+
+impl Default for SubjectTooLong {
+    fn default() -> Self {
+        SubjectTooLong { max_chars: 78 }
+    }
+}
+
+impl Lint for SubjectTooLong {
+    fn id(&self) -> &'static str {
+        "subject-too-long"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn check(&self, map: &HeaderMap) -> Vec<LintFinding> {
+        let mut findings = Vec::new();
+        if let Some(Ok(subject)) = map.get_single(Subject) {
+            let len = subject.as_str().chars().count();
+            if len > self.max_chars {
+                findings.push(LintFinding::new(self, "Subject", format!(
+                    "subject has {} characters, more than {}", len, self.max_chars)));
+            }
+        }
+        findings
+    }
+}
+
+/// `subject-all-caps`: the `Subject` is written in upper case only.
+///
+/// Subjects with less than 4 letters (e.g. `OK`) are not reported.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SubjectAllCaps;
+
+impl Lint for SubjectAllCaps {
+    fn id(&self) -> &'static str {
+        "subject-all-caps"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn check(&self, map: &HeaderMap) -> Vec<LintFinding> {
+        let mut findings = Vec::new();
+        if let Some(Ok(subject)) = map.get_single(Subject) {
+            let letters = subject.as_str().chars()
+                .filter(|ch| ch.is_alphabetic())
+                .collect::<Vec<_>>();
+            if letters.len() >= 4 && !letters.iter().any(|ch| ch.is_lowercase()) {
+                findings.push(LintFinding::new(self, "Subject", "subject is written in all caps"));
+            }
+        }
+        findings
+    }
+}
+
+/// `bulk-without-list-unsubscribe`: bulk mail without a `List-Unsubscribe` header.
+///
+/// A mail is considered to be bulk mail if it has a `Precedence` of
+/// `bulk` or `list`. Major mail providers expect such mails to have a
+/// `List-Unsubscribe` header. As there is no built-in header kind for it
+/// the header is looked up by name, so any header kind named
+/// `List-Unsubscribe` satisfies this rule.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct BulkWithoutListUnsubscribe;
+
+impl Lint for BulkWithoutListUnsubscribe {
+    fn id(&self) -> &'static str {
+        "bulk-without-list-unsubscribe"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, map: &HeaderMap) -> Vec<LintFinding> {
+        let mut findings = Vec::new();
+        let is_bulk = match map.get_single(Precedence) {
+            Some(Ok(precedence)) => match *precedence.body() {
+                components::Precedence::Bulk | components::Precedence::List => true,
+                _ => false
+            },
+            _ => false
+        };
+        let has_unsubscribe = map.iter()
+            .any(|(name, _)| name.as_str().eq_ignore_ascii_case("List-Unsubscribe"));
+        if is_bulk && !has_unsubscribe {
+            findings.push(LintFinding::new(self, "List-Unsubscribe",
+                "bulk mail (by Precedence) has no List-Unsubscribe header"));
+        }
+        findings
+    }
+}
+
+/// `redundant-reply-to`: the `Reply-To` contains exactly the addresses of `From`.
+///
+/// Such a `Reply-To` has no effect. Addresses are compared ignoring
+/// case and display names.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RedundantReplyTo;
+
+fn addresses(list: &MailboxList) -> HashSet<String> {
+    list.0.iter()
+        .map(|mailbox| mailbox.email.to_string().to_lowercase())
+        .collect()
+}
+
+impl Lint for RedundantReplyTo {
+    fn id(&self) -> &'static str {
+        "redundant-reply-to"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Info
+    }
+
+    fn check(&self, map: &HeaderMap) -> Vec<LintFinding> {
+        let mut findings = Vec::new();
+        if let (Some(Ok(from)), Some(Ok(reply_to))) = (map.get_single(_From), map.get_single(ReplyTo)) {
+            if addresses(from.body()) == addresses(reply_to.body()) {
+                findings.push(LintFinding::new(self, "Reply-To", "reply-to is the same as from"));
+            }
+        }
+        findings
+    }
+}
+
+/// `too-many-to-recipients`: the `To` header has more than `max` recipients.
+///
+/// All recipients can see each other, for larger groups of recipients
+/// `Bcc` should be used instead. The default maximum is 10.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TooManyToRecipients {
+    pub max: usize
+}
+
+impl Default for TooManyToRecipients {
+    fn default() -> Self {
+        TooManyToRecipients { max: 10 }
+    }
+}
+
+impl Lint for TooManyToRecipients {
+    fn id(&self) -> &'static str {
+        "too-many-to-recipients"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn check(&self, map: &HeaderMap) -> Vec<LintFinding> {
+        let mut findings = Vec::new();
+        if let Some(Ok(to)) = map.get_single(_To) {
+            let count = to.0.len();
+            if count > self.max {
+                findings.push(LintFinding::new(self, "To", format!(
+                    "{} visible recipients, more than {} (consider using Bcc)", count, self.max)));
+            }
+        }
+        findings
+    }
+}
+
+/// `received-chain-too-long`: there are more than `max` `Received` headers.
+///
+/// A long `Received` chain indicates a mail loop and many mail servers
+/// reject such mails. The default maximum is 25.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ReceivedChainTooLong {
+    pub max: usize
+}
+
+impl Default for ReceivedChainTooLong {
+    fn default() -> Self {
+        ReceivedChainTooLong { max: 25 }
+    }
+}
+
+impl Lint for ReceivedChainTooLong {
+    fn id(&self) -> &'static str {
+        "received-chain-too-long"
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, map: &HeaderMap) -> Vec<LintFinding> {
+        let mut findings = Vec::new();
+        let count = map.get_untyped(Received).len();
+        if count > self.max {
+            findings.push(LintFinding::new(self, "Received", format!(
+                "{} Received headers, more than {} (mail loop?)", count, self.max)));
+        }
+        findings
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::{HeaderKind, HeaderTryFrom};
+    use ::headers::*;
+    use ::header_components::{ReceivedToken, Domain};
+    use super::*;
+
+    mod raw_headers {
+        use ::header_components;
+        def_headers! {
+            test_name: validate_header_names,
+            scope: header_components,
+            ListUnsubscribe, unchecked { "List-Unsubscribe" }, RawFieldBody, maxOne, None
+        }
+    }
+    use self::raw_headers::ListUnsubscribe;
+
+    fn fires<L: Lint>(rule: &L, map: &HeaderMap) -> bool {
+        let findings = rule.check(map);
+        for finding in &findings {
+            assert_eq!(finding.rule_id, rule.id());
+            assert_eq!(finding.severity, rule.severity());
+        }
+        !findings.is_empty()
+    }
+
+    test!(subject_too_long {
+        let rule = SubjectTooLong::default();
+        let ok = headers! { Subject: "a".repeat(78) }?;
+        assert!(!fires(&rule, &ok));
+        // characters not bytes are counted
+        let ok = headers! { Subject: "ä".repeat(78) }?;
+        assert!(!fires(&rule, &ok));
+        assert!(!fires(&rule, &HeaderMap::new()));
+
+        let too_long = headers! { Subject: "a".repeat(79) }?;
+        let findings = rule.check(&too_long);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].header_name, "Subject");
+        assert_eq!(findings[0].message, "subject has 79 characters, more than 78");
+
+        assert!(!fires(&SubjectTooLong { max_chars: 100 }, &too_long));
+    });
+
+    test!(subject_all_caps {
+        let rule = SubjectAllCaps;
+        assert!(fires(&rule, &headers! { Subject: "BUY NOW!!!" }?));
+        assert!(fires(&rule, &headers! { Subject: "ÜBER 50% RABATT" }?));
+
+        assert!(!fires(&rule, &headers! { Subject: "Buy now" }?));
+        assert!(!fires(&rule, &headers! { Subject: "OK?" }?));
+        assert!(!fires(&rule, &headers! { Subject: "1234 5678" }?));
+        assert!(!fires(&rule, &HeaderMap::new()));
+    });
+
+    test!(bulk_without_list_unsubscribe {
+        let rule = BulkWithoutListUnsubscribe;
+        assert!(fires(&rule, &headers! { Precedence: "bulk" }?));
+        assert!(fires(&rule, &headers! { Precedence: "list" }?));
+
+        assert!(!fires(&rule, &headers! { Precedence: "junk" }?));
+        assert!(!fires(&rule, &headers! { Subject: "no precedence" }?));
+
+        let with_unsubscribe = headers! {
+            Precedence: "bulk",
+            ListUnsubscribe: "<mailto:unsubscribe@example.com>"
+        }?;
+        assert!(!fires(&rule, &with_unsubscribe));
+    });
+
+    test!(redundant_reply_to {
+        let rule = RedundantReplyTo;
+        assert!(fires(&rule, &headers! {
+            _From: [("News", "news@example.com")],
+            ReplyTo: ["NEWS@example.com"]
+        }?));
+
+        assert!(!fires(&rule, &headers! {
+            _From: ["news@example.com"],
+            ReplyTo: ["support@example.com"]
+        }?));
+        assert!(!fires(&rule, &headers! {
+            _From: ["news@example.com"],
+            ReplyTo: ["news@example.com", "support@example.com"]
+        }?));
+        assert!(!fires(&rule, &headers! { _From: ["news@example.com"] }?));
+    });
+
+    test!(too_many_to_recipients {
+        let rule = TooManyToRecipients::default();
+        let recipients = |count: usize| (0..count)
+            .map(|idx| format!("r{}@example.com", idx))
+            .collect::<Vec<_>>();
+
+        assert!(!fires(&rule, &headers! { _To: recipients(10) }?));
+        assert!(!fires(&rule, &headers! { Bcc: recipients(50) }?));
+        assert!(fires(&rule, &headers! { _To: recipients(11) }?));
+    });
+
+    test!(received_chain_too_long {
+        let rule = ReceivedChainTooLong::default();
+        let mut map = HeaderMap::new();
+        for _ in 0..25 {
+            map.insert(Received::body(ReceivedToken::Domain(Domain::try_from("mx.example.com")?)));
+        }
+        assert!(!fires(&rule, &map));
+
+        map.insert(Received::body(ReceivedToken::Domain(Domain::try_from("mx.example.com")?)));
+        assert!(fires(&rule, &map));
+    });
+}
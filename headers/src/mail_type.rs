@@ -0,0 +1,299 @@
+//! Deriving the mail type needed to encode a `HeaderMap`, see `HeaderMap::required_mail_type`.
+//!
+//! Each header reports a `MailTypeRequirement` (see `HeaderKind::mail_type_requirement`),
+//! which are folded into the minimal `MailType` which can encode all headers.
+use internals::MailType;
+
+use ::error::RequirementConflict;
+use ::map::HeaderMap;
+
+/// What a component needs to be encoded, see `HasMailTypeRequirement`.
+///
+/// Requirements of compound components are combined with `combine`, so a
+/// mailbox with a non us-ascii display name and a internationalized domain
+/// both needs encoded words and prefers a internationalized mail.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MailTypeRequirement {
+    minimal: MailType,
+    encoded_words: bool,
+    punycode: bool,
+    unencodable: Option<&'static str>
+}
+
+impl MailTypeRequirement {
+
+    /// Can be encoded in any mail type.
+    pub fn ascii() -> Self {
+        MailTypeRequirement {
+            minimal: MailType::Ascii,
+            encoded_words: false,
+            punycode: false,
+            unencodable: None
+        }
+    }
+
+    /// Can be encoded in ascii mails only by using encoded words (rfc2047).
+    pub fn encoded_words() -> Self {
+        MailTypeRequirement { encoded_words: true, ..Self::ascii() }
+    }
+
+    /// Can be encoded in ascii mails using punycode, but a internationalized mail is preferred.
+    pub fn punycode() -> Self {
+        MailTypeRequirement { punycode: true, ..Self::ascii() }
+    }
+
+    /// Needs at least given mail type.
+    pub fn at_least(mail_type: MailType) -> Self {
+        MailTypeRequirement { minimal: mail_type, ..Self::ascii() }
+    }
+
+    /// Needs a internationalized mail.
+    pub fn internationalized() -> Self {
+        Self::at_least(MailType::Internationalized)
+    }
+
+    /// Can not be encoded in any mail type.
+    pub fn unencodable(reason: &'static str) -> Self {
+        MailTypeRequirement { unencodable: Some(reason), ..Self::ascii() }
+    }
+
+    /// Returns a requirement which is met iff both requirements are met.
+    pub fn combine(self, other: Self) -> Self {
+        MailTypeRequirement {
+            minimal: max_mail_type(self.minimal, other.minimal),
+            encoded_words: self.encoded_words || other.encoded_words,
+            punycode: self.punycode || other.punycode,
+            unencodable: self.unencodable.or(other.unencodable)
+        }
+    }
+
+    /// Returns the minimal mail type meeting this requirement with given options.
+    ///
+    /// Fails with the reason why the component can not be encoded if it is unencodable.
+    pub fn mail_type(&self, options: MailTypeOptions) -> Result<MailType, &'static str> {
+        if let Some(reason) = self.unencodable {
+            return Err(reason);
+        }
+        let mut mail_type = self.minimal;
+        if (self.encoded_words && !options.allow_encoded_words)
+            || (self.punycode && options.prefer_internationalized)
+        {
+            mail_type = MailType::Internationalized;
+        }
+        Ok(mail_type)
+    }
+}
+
+impl Default for MailTypeRequirement {
+    fn default() -> Self {
+        MailTypeRequirement::ascii()
+    }
+}
+
+pub(crate) fn rank(mail_type: MailType) -> u8 {
+    match mail_type {
+        MailType::Ascii => 0,
+        MailType::Mime8BitEnabled => 1,
+        MailType::Internationalized => 2
+    }
+}
+
+fn max_mail_type(left: MailType, right: MailType) -> MailType {
+    if rank(left) >= rank(right) { left } else { right }
+}
+
+/// Implemented by header components to tell which mail type they need.
+///
+/// The default is that the component can be encoded in any mail type, so
+/// components which might contain non us-ascii text should override it.
+///
+/// (This is not a method of `EncodableInHeader` as that trait is defined in
+/// `mail-internals`. It is not required for header components, headers
+/// defined with `mail_type_requirement: component` in `def_headers!` use it
+/// for `HeaderKind::mail_type_requirement`.)
+pub trait HasMailTypeRequirement {
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        MailTypeRequirement::ascii()
+    }
+}
+
+/// Options for `HeaderMap::required_mail_type`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MailTypeOptions {
+    /// Allows encoding non us-ascii text (e.g. in the `Subject`) as encoded words in ascii mails.
+    ///
+    /// If not set such text requires a internationalized mail. Default: `true`.
+    pub allow_encoded_words: bool,
+
+    /// Uses internationalized mails for internationalized domain names instead of punycode.
+    ///
+    /// Default: `false`.
+    pub prefer_internationalized: bool
+}
+
+impl Default for MailTypeOptions {
+    fn default() -> Self {
+        MailTypeOptions {
+            allow_encoded_words: true,
+            prefer_internationalized: false
+        }
+    }
+}
+
+impl HeaderMap {
+
+    /// Returns the minimal mail type which can be used to encode all headers in this map.
+    ///
+    /// Guessing `Ascii` fails late when e.g. a address has a non us-ascii local part,
+    /// while guessing `Internationalized` produces mails some receivers reject even
+    /// if not necessary. This uses the `MailTypeRequirement` of each header body and
+    /// the given options to decide it instead.
+    ///
+    /// # Error
+    ///
+    /// Fails if a header can not be encoded in any mail type.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # extern crate mail_internals;
+    /// # fn main() {
+    /// use mail_internals::MailType;
+    /// use mail_headers::headers::*;
+    /// use mail_headers::mail_type::MailTypeOptions;
+    ///
+    /// let map = headers! {
+    ///     _From: ["me@example.com"],
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    /// assert_eq!(map.required_mail_type(MailTypeOptions::default()).unwrap(), MailType::Ascii);
+    ///
+    /// let map = headers! {
+    ///     _From: ["thé@example.com"]
+    /// }.unwrap();
+    /// assert_eq!(map.required_mail_type(MailTypeOptions::default()).unwrap(), MailType::Internationalized);
+    /// # }
+    /// ```
+    pub fn required_mail_type(&self, options: MailTypeOptions) -> Result<MailType, RequirementConflict> {
+        let mut required = MailType::Ascii;
+        for (name, obj) in self.iter() {
+            let mail_type = obj.mail_type_requirement()
+                .mail_type(options)
+                .map_err(|reason| RequirementConflict::new(name, reason))?;
+            required = max_mail_type(required, mail_type);
+        }
+        Ok(required)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderKind;
+    use ::headers::*;
+    use ::header_components::{self as components, RawUnstructured};
+    use super::*;
+
+    def_headers! {
+        test_name: validate_header_names,
+        scope: components,
+        mail_type_requirement: component,
+        XRaw, unchecked { "X-Raw" }, RawUnstructured, maxOne, None
+    }
+
+    mod default_requirement {
+        use ::header_components as components;
+        def_headers! {
+            test_name: validate_header_names,
+            scope: components,
+            XRawDefault, unchecked { "X-Raw-Default" }, RawUnstructured, maxOne, None
+        }
+    }
+    use self::default_requirement::XRawDefault;
+
+    fn required(map: &HeaderMap, options: MailTypeOptions) -> MailType {
+        let mail_type = map.required_mail_type(options).unwrap();
+        // encoding under the derived mail type has to work
+        assert!(map.try_display(mail_type).is_ok(), "can not encode map as {:?}", mail_type);
+        mail_type
+    }
+
+    fn no_encoded_words() -> MailTypeOptions {
+        MailTypeOptions { allow_encoded_words: false, ..Default::default() }
+    }
+
+    fn prefer_internationalized() -> MailTypeOptions {
+        MailTypeOptions { prefer_internationalized: true, ..Default::default() }
+    }
+
+    test!(pure_ascii {
+        let map = headers! {
+            _From: ["me@example.com"],
+            _To: ["you@example.com"],
+            Subject: "hy there"
+        }?;
+        assert_eq!(required(&map, Default::default()), MailType::Ascii);
+        assert_eq!(required(&map, no_encoded_words()), MailType::Ascii);
+        assert_eq!(required(&map, prefer_internationalized()), MailType::Ascii);
+    });
+
+    test!(idn_only {
+        let map = headers! {
+            _From: ["me@bücher.example"],
+            Subject: "hy there"
+        }?;
+        assert_eq!(required(&map, Default::default()), MailType::Ascii);
+        assert_eq!(required(&map, no_encoded_words()), MailType::Ascii);
+        assert_eq!(required(&map, prefer_internationalized()), MailType::Internationalized);
+    });
+
+    test!(utf8_local_part {
+        let map = headers! {
+            _From: ["thé@example.com"],
+            Subject: "hy there"
+        }?;
+        assert_eq!(required(&map, Default::default()), MailType::Internationalized);
+        assert_eq!(required(&map, no_encoded_words()), MailType::Internationalized);
+    });
+
+    test!(utf8_subject_only {
+        let map = headers! {
+            _From: ["me@example.com"],
+            Subject: "hö there"
+        }?;
+        assert_eq!(required(&map, Default::default()), MailType::Ascii);
+        assert_eq!(required(&map, no_encoded_words()), MailType::Internationalized);
+    });
+
+    test!(unencodable_header_is_a_conflict {
+        let mut map = HeaderMap::new();
+        map.insert(Subject::auto_body("hy there")?);
+        map.insert(XRaw::body(RawUnstructured::from("a\x07b")));
+        let err = assert_err!(map.required_mail_type(Default::default()));
+        assert_eq!(err.name(), XRaw::name());
+    });
+
+    test!(component_requirement_is_opt_in {
+        let body = RawUnstructured::from("a\x07b");
+        assert_eq!(XRaw::mail_type_requirement(&body), body.mail_type_requirement());
+        assert_eq!(XRawDefault::mail_type_requirement(&body), MailTypeRequirement::ascii());
+
+        let mut map = HeaderMap::new();
+        map.insert(XRawDefault::body(body));
+        assert_eq!(map.required_mail_type(Default::default())?, MailType::Ascii);
+    });
+
+    #[test]
+    fn combining_keeps_all_needs() {
+        let requirement = MailTypeRequirement::encoded_words()
+            .combine(MailTypeRequirement::punycode());
+
+        assert_eq!(requirement.mail_type(Default::default()), Ok(MailType::Ascii));
+        assert_eq!(requirement.mail_type(no_encoded_words()), Ok(MailType::Internationalized));
+        assert_eq!(requirement.mail_type(prefer_internationalized()), Ok(MailType::Internationalized));
+
+        let requirement = requirement.combine(MailTypeRequirement::unencodable("bad"));
+        assert_eq!(requirement.mail_type(Default::default()), Err("bad"));
+    }
+}
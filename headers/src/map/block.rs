@@ -0,0 +1,385 @@
+use std::str;
+
+use internals::MailType;
+use internals::error::EncodingError;
+
+use ::header::HeaderObj;
+use ::name::HeaderName;
+use ::error::BlockParseError;
+
+use super::{HeaderMap, MapLimits};
+use super::string_pairs::PairRegistry;
+
+/// How the header block returned by `HeaderMap::to_block_bytes` ends.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum BlockTerminator {
+    /// The last header line has no CRLF, e.g. `"A: b\r\nC: d"`.
+    None,
+    /// Each header line (including the last one) ends with CRLF, e.g. `"A: b\r\nC: d\r\n"`.
+    ///
+    /// This is the output of `encode_checked`.
+    FinalCrlf,
+    /// Like `FinalCrlf` followed by the blank line separating the block
+    /// from the body, e.g. `"A: b\r\nC: d\r\n\r\n"`.
+    CrlfBlankLine
+}
+
+impl Default for BlockTerminator {
+    /// `FinalCrlf`, like all other encoding methods of the map.
+    fn default() -> Self {
+        BlockTerminator::FinalCrlf
+    }
+}
+
+/// What `HeaderMap::from_block_bytes` does with bytes after the blank line ending the block.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum TrailingBytes {
+    /// Fail with `BlockParseError::TrailingBytes`.
+    Reject,
+    /// Stop parsing at the blank line, the bytes after it (e.g. the body)
+    /// start at the returned consumed length.
+    Return
+}
+
+/// Options for `HeaderMap::from_block_bytes`.
+#[derive(Debug, Clone)]
+pub struct BlockParseOptions {
+    /// The parsers used to create the headers from the unfolded values.
+    pub registry: PairRegistry,
+
+    /// What to do with bytes after the blank line ending the block.
+    pub trailing: TrailingBytes,
+
+    /// The limits of the created map.
+    ///
+    /// Parsing stops at the first header which would exceed them, so
+    /// untrusted input can be rejected as soon as it is too large.
+    pub limits: MapLimits
+}
+
+impl Default for BlockParseOptions {
+    /// Uses the built-in registry, rejects trailing bytes and doesn't limit the map.
+    fn default() -> Self {
+        BlockParseOptions {
+            registry: PairRegistry::builtin(),
+            trailing: TrailingBytes::Reject,
+            limits: MapLimits::default()
+        }
+    }
+}
+
+impl HeaderMap {
+
+    /// Encodes the header block of this map ending it as given by `terminator`.
+    ///
+    /// The headers are encoded like with `encode_checked` (i.e. the
+    /// contextual validators are not run, but each header is checked for
+    /// injected line breaks). For a empty map the block is empty, or only
+    /// the blank line (`"\r\n"`) with `BlockTerminator::CrlfBlankLine`.
+    ///
+    /// The returned bytes are parsed back by `from_block_bytes` with any
+    /// terminator.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # extern crate mail_internals;
+    /// # fn main() {
+    /// use mail_internals::MailType;
+    /// use mail_headers::HeaderMap;
+    /// use mail_headers::headers::*;
+    /// use mail_headers::map::{BlockTerminator, BlockParseOptions, TrailingBytes};
+    ///
+    /// let map = headers! {
+    ///     _From: ["me@example.com"],
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// let mut mail = map.to_block_bytes(MailType::Ascii, BlockTerminator::CrlfBlankLine).unwrap();
+    /// assert_eq!(mail, b"From: <me@example.com>\r\nSubject: hy there\r\n\r\n".to_vec());
+    ///
+    /// mail.extend_from_slice(b"the body");
+    /// let opts = BlockParseOptions { trailing: TrailingBytes::Return, ..Default::default() };
+    /// let (parsed, consumed) = HeaderMap::from_block_bytes(&mail, &opts).unwrap();
+    /// assert_eq!(parsed.len(), 2);
+    /// assert_eq!(&mail[consumed..], b"the body");
+    /// # }
+    /// ```
+    pub fn to_block_bytes(&self, mail_type: MailType, terminator: BlockTerminator)
+        -> Result<Vec<u8>, EncodingError>
+    {
+        let mut block = self.encode_checked(mail_type)?;
+        match terminator {
+            BlockTerminator::None => {
+                let len = block.len().saturating_sub(2);
+                block.truncate(len);
+            },
+            BlockTerminator::FinalCrlf => {},
+            BlockTerminator::CrlfBlankLine => block.extend_from_slice(b"\r\n")
+        }
+        Ok(block)
+    }
+
+    /// Parses a raw header block, returning the map and the number of consumed bytes.
+    ///
+    /// The block consists of `name: value` lines separated by CRLF, lines
+    /// starting with whitespace continue the previous header (they are
+    /// unfolded by removing the CRLF). The headers are created by the
+    /// parsers of `opts.registry` in the same way as `from_string_pairs`
+    /// does, i.e. header names are matched ignoring case, headers with
+    /// unknown names (e.g. `X-Mailer` or `DKIM-Signature`) are kept as
+    /// `RawHeader` and "max one" headers are not replaced. The returned
+    /// map has the limits given by `opts.limits`.
+    ///
+    /// The block ends either
+    ///
+    /// - at the end of the input, the CRLF of the last line is optional
+    ///   (i.e. all `BlockTerminator` variants are accepted), or
+    /// - at a blank line (a empty line), which is consumed. If there
+    ///   are bytes after it (e.g. a body) they are rejected or returned
+    ///   as given by `opts.trailing`.
+    ///
+    /// The returned length is the number of bytes belonging to the block
+    /// (including the blank line), with `TrailingBytes::Return` the bytes
+    /// after the block are `&block[consumed..]`.
+    ///
+    /// # Error
+    ///
+    /// Fails with a `BlockParseError` containing the offset of the first
+    /// line which could not be parsed: for bare CR or LF line breaks,
+    /// lines without a `:` or starting with a continuation, values which
+    /// are not utf-8, values which can not be parsed, unknown headers (only
+    /// if the raw fallback of the registry is disabled, see `RawHeader`)
+    /// and (with `TrailingBytes::Reject`) bytes after the blank line. If
+    /// a header would exceed `opts.limits` it fails with `BlockParseError::Limit`.
+    pub fn from_block_bytes(block: &[u8], opts: &BlockParseOptions)
+        -> Result<(HeaderMap, usize), BlockParseError>
+    {
+        let mut map = HeaderMap::with_limits(opts.limits);
+        // the offset and the unfolded line of the header which is parsed
+        let mut current: Option<(usize, Vec<u8>)> = None;
+        let mut pos = 0;
+        while pos < block.len() {
+            let (line, next) = next_line(block, pos)?;
+            if line.is_empty() {
+                pos = next;
+                break;
+            }
+            if line[0] == b' ' || line[0] == b'\t' {
+                match current {
+                    Some((_, ref mut unfolded)) => unfolded.extend_from_slice(line),
+                    None => return Err(BlockParseError::MalformedLine { offset: pos })
+                }
+            } else {
+                if let Some((offset, unfolded)) = current.take() {
+                    insert_header(&mut map, offset, &unfolded, &opts.registry)?;
+                }
+                current = Some((pos, line.to_owned()));
+            }
+            pos = next;
+        }
+        if let Some((offset, unfolded)) = current.take() {
+            insert_header(&mut map, offset, &unfolded, &opts.registry)?;
+        }
+
+        if pos < block.len() && opts.trailing == TrailingBytes::Reject {
+            return Err(BlockParseError::TrailingBytes { offset: pos });
+        }
+        Ok((map, pos))
+    }
+}
+
+/// Returns the line starting at `start` (without its CRLF) and the start of the next line.
+///
+/// The last line of the input doesn't need to end with CRLF.
+fn next_line(block: &[u8], start: usize) -> Result<(&[u8], usize), BlockParseError> {
+    let rest = &block[start..];
+    match rest.iter().position(|&bch| bch == b'\r' || bch == b'\n') {
+        None => Ok((rest, block.len())),
+        Some(idx) => {
+            if rest[idx] == b'\r' && rest.get(idx + 1) == Some(&b'\n') {
+                Ok((&rest[..idx], start + idx + 2))
+            } else {
+                Err(BlockParseError::InvalidLineBreak { offset: start + idx })
+            }
+        }
+    }
+}
+
+/// Creates the header from a unfolded line and adds it to the map (without replacing max one headers).
+fn insert_header(map: &mut HeaderMap, offset: usize, line: &[u8], registry: &PairRegistry)
+    -> Result<(), BlockParseError>
+{
+    let (name, obj) = parse_header(offset, line, registry)?;
+    map.try_push(name, obj)
+        .map_err(|violation| BlockParseError::Limit { offset, violation })
+}
+
+/// Creates the header from a unfolded `name: value` line starting at `offset`.
+fn parse_header(offset: usize, line: &[u8], registry: &PairRegistry)
+    -> Result<(HeaderName, Box<HeaderObj>), BlockParseError>
+{
+    let colon = line.iter().position(|&bch| bch == b':')
+        .ok_or(BlockParseError::MalformedLine { offset })?;
+    let name = str::from_utf8(&line[..colon])
+        .map_err(|_| BlockParseError::MalformedLine { offset })?;
+    if name.is_empty() {
+        return Err(BlockParseError::MalformedLine { offset });
+    }
+    let value = str::from_utf8(&line[colon+1..])
+        .map_err(|_| BlockParseError::InvalidUtf8 { offset })?;
+    let value = value.trim_start_matches(|ch: char| ch == ' ' || ch == '\t');
+    registry.parse_pair(name, value)
+        .map_err(|error| BlockParseError::Header { offset, error })
+}
+
+#[cfg(test)]
+mod test {
+    use internals::MailType;
+    use ::headers::*;
+    use ::HeaderKind;
+    use ::error::{StringPairError, LimitKind};
+    use ::map::RawHeader;
+    use super::*;
+
+    fn example_map() -> HeaderMap {
+        let text = "this is a long subject which will need to be folded at some \
+                    point as it is longer than the soft line length limit";
+        headers! {
+            _From: ["me@example.com"],
+            Subject: text,
+            MessageId: "id@example.com"
+        }.unwrap()
+    }
+
+    fn returning() -> BlockParseOptions {
+        BlockParseOptions { trailing: TrailingBytes::Return, ..Default::default() }
+    }
+
+    test!(round_trip_with_each_terminator {
+        let map = example_map();
+        let encoded = map.encode_checked(MailType::Ascii)?;
+        assert!(encoded.windows(3).any(|window| window == b"\r\n "), "subject is not folded");
+
+        for &terminator in &[
+            BlockTerminator::None, BlockTerminator::FinalCrlf, BlockTerminator::CrlfBlankLine
+        ] {
+            let block = map.to_block_bytes(MailType::Ascii, terminator)?;
+            let (parsed, consumed) = HeaderMap::from_block_bytes(&block, &Default::default())?;
+            assert_eq!(consumed, block.len());
+            assert!(parsed.eq_by_encoding(&map, MailType::Ascii)?);
+            assert_eq!(parsed.to_block_bytes(MailType::Ascii, terminator)?, block);
+        }
+    });
+
+    test!(terminators {
+        let map = headers! { Subject: "hy" }?;
+        let block = |terminator| map.to_block_bytes(MailType::Ascii, terminator);
+        assert_eq!(block(BlockTerminator::None)?, b"Subject: hy".to_vec());
+        assert_eq!(block(BlockTerminator::FinalCrlf)?, b"Subject: hy\r\n".to_vec());
+        assert_eq!(block(BlockTerminator::CrlfBlankLine)?, b"Subject: hy\r\n\r\n".to_vec());
+
+        let empty = HeaderMap::new();
+        assert_eq!(empty.to_block_bytes(MailType::Ascii, BlockTerminator::None)?, b"".to_vec());
+        assert_eq!(empty.to_block_bytes(MailType::Ascii, BlockTerminator::CrlfBlankLine)?, b"\r\n".to_vec());
+        let (parsed, consumed) = HeaderMap::from_block_bytes(b"\r\n", &Default::default())?;
+        assert!(parsed.is_empty());
+        assert_eq!(consumed, 2);
+    });
+
+    test!(body_after_blank_line {
+        let mail = b"Subject: hy\r\nComments: a\r\n  comment\r\n\r\nthe body\r\n\r\nmore\nbody";
+        let (parsed, consumed) = HeaderMap::from_block_bytes(mail, &returning())?;
+        assert_eq!(consumed, 39);
+        assert_eq!(&mail[consumed..], &b"the body\r\n\r\nmore\nbody"[..]);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed.get_single(Comments).unwrap()?.as_str(), "a  comment");
+
+        let err = assert_err!(HeaderMap::from_block_bytes(mail, &Default::default()));
+        match err {
+            BlockParseError::TrailingBytes { offset } => assert_eq!(offset, 39),
+            other => panic!("unexpected error: {:?}", other)
+        }
+    });
+
+    test!(missing_final_crlf {
+        let block = b"Subject: hy\r\nMessage-ID: <id@example.com>";
+        let (parsed, consumed) = HeaderMap::from_block_bytes(block, &returning())?;
+        assert_eq!(consumed, block.len());
+        assert_eq!(parsed.get_single(MessageId).unwrap()?.as_str(), "id@example.com");
+        assert_eq!(
+            parsed.to_block_bytes(MailType::Ascii, BlockTerminator::None)?,
+            block.to_vec()
+        );
+    });
+
+    test!(unknown_headers_are_kept {
+        let block = b"Subject: hy\r\nX-Mailer: some mailer 1.0\r\nDKIM-Signature: v=1; a=rsa-sha256;\r\n d=example.com; b=Zm9v\r\n";
+        let (parsed, consumed) = HeaderMap::from_block_bytes(block, &Default::default())?;
+        assert_eq!(consumed, block.len());
+        assert_eq!(parsed.len(), 3);
+
+        let names = parsed.iter().map(|(name, _)| name.as_wire_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Subject", "X-Mailer", "DKIM-Signature"]);
+        let (_, signature) = parsed.iter().nth(2).unwrap();
+        assert_eq!(RawHeader::from_obj(signature).unwrap().as_str(), "v=1; a=rsa-sha256; d=example.com; b=Zm9v");
+
+        let encoded = parsed.to_block_bytes(MailType::Ascii, BlockTerminator::FinalCrlf)?;
+        assert_eq!(
+            String::from_utf8(encoded)?,
+            "Subject: hy\r\nX-Mailer: some mailer 1.0\r\nDKIM-Signature: v=1; a=rsa-sha256; d=example.com; b=Zm9v\r\n"
+        );
+    });
+
+    test!(limits_stop_parsing {
+        let block = b"Subject: hy\r\nComments: a\r\nComments: b\r\n";
+        let opts = BlockParseOptions {
+            limits: MapLimits { max_headers: Some(2), ..Default::default() },
+            ..Default::default()
+        };
+        match assert_err!(HeaderMap::from_block_bytes(block, &opts)) {
+            BlockParseError::Limit { offset, violation } => {
+                assert_eq!(offset, 26);
+                assert_eq!(violation.kind(), LimitKind::HeaderCount);
+                assert_eq!(violation.accepted(), 2);
+            },
+            other => panic!("unexpected error: {:?}", other)
+        }
+
+        let opts = BlockParseOptions {
+            limits: MapLimits { max_headers: Some(3), ..Default::default() },
+            ..Default::default()
+        };
+        let (parsed, _) = HeaderMap::from_block_bytes(block, &opts)?;
+        assert_eq!(parsed.limits(), opts.limits);
+        assert_err!(parsed.clone().try_insert(Comments::auto_body("c")?));
+    });
+
+    test!(errors_name_the_offset {
+        let opts = BlockParseOptions::default();
+        let parse = |block: &[u8]| HeaderMap::from_block_bytes(block, &opts).map(|_| ());
+
+        let err = assert_err!(parse(b"Subject: a\nb\r\n"));
+        assert!(match err { BlockParseError::InvalidLineBreak { offset: 10 } => true, _ => false });
+
+        let err = assert_err!(parse(b"Subject: a\r\n no header\r\nNo colon here\r\n"));
+        assert!(match err { BlockParseError::MalformedLine { offset: 24 } => true, _ => false });
+
+        let err = assert_err!(parse(b" Subject: a\r\n"));
+        assert!(match err { BlockParseError::MalformedLine { offset: 0 } => true, _ => false });
+
+        let err = assert_err!(parse(b"Subject: \xff\r\n"));
+        assert!(match err { BlockParseError::InvalidUtf8 { offset: 0 } => true, _ => false });
+
+        let mut strict = BlockParseOptions::default();
+        strict.registry.set_raw_fallback(false);
+        let err = assert_err!(HeaderMap::from_block_bytes(b"Subject: a\r\nX-Unknown: b\r\n", &strict));
+        assert_eq!(err.offset(), 12);
+        assert!(match err {
+            BlockParseError::Header { error: StringPairError::UnknownHeader { .. }, .. } => true,
+            _ => false
+        });
+    });
+}
@@ -0,0 +1,595 @@
+use std::any::TypeId;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{Read, Write};
+use std::mem;
+use std::str;
+
+use failure::Fail;
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use once_cell::sync::OnceCell;
+use soft_ascii::SoftAsciiStr;
+
+use internals::MailType;
+use internals::error::{EncodingError, EncodingErrorKind};
+use internals::encoder::EncodingWriter;
+
+use ::name::HeaderName;
+use ::header::{HeaderObj, HeaderObjTrait, uncached_value, check_max_one_len};
+use ::error::DecompressError;
+use ::mail_type::MailTypeRequirement;
+
+use super::{HeaderMap, HeaderMapValidator};
+use super::limits::total_bytes;
+use super::string_pairs::{PairParser, PairRegistry};
+
+/// A header whose value is stored deflate compressed, see `HeaderMap::compress_large_values`.
+///
+/// It stores the us-ascii encoded value of the original header, the
+/// parser (from a `PairRegistry`) to create the original header from
+/// it again and the type name of the original component.
+///
+/// Typed access to the header (e.g. `HeaderMap::get`, `get_single` or
+/// `take`) decompresses and parses the value on first access and caches
+/// the parsed header, so accessors behave the same as for the original
+/// header (apart from the latency of the first access). As long as the
+/// header is not accessed mutable it encodes exactly the stored bytes,
+/// which are the same for all mail types as only headers which encode the
+/// same for all mail types are compressed. After mutable access the parsed
+/// (and maybe changed) header is encoded instead.
+///
+/// Like the original header a max-one header fails to encode with a
+/// `HeaderTooLong` error if its header line is longer than `MAX_ONE_HEADER_LEN`.
+#[derive(Clone)]
+pub struct CompressedRaw {
+    name: HeaderName,
+    max_one: bool,
+    validator: Option<HeaderMapValidator>,
+    requirement: MailTypeRequirement,
+    component: &'static str,
+    parser: PairParser,
+    original_len: usize,
+    deflated: Vec<u8>,
+    decompressed: OnceCell<Box<HeaderObj>>,
+    modified: bool
+}
+
+impl CompressedRaw {
+
+    /// Compresses the header if its us-ascii encoded value is longer than `threshold_bytes`.
+    ///
+    /// Returns `None` if the value is not long enough, the header can not
+    /// be encoded as us-ascii, it is encoded differently for internationalized
+    /// mails (e.g. non us-ascii text written as encoded words in ascii mails),
+    /// `registry` has no parser for the header or the parser doesn't create a
+    /// header of the same type from the value. Compressed headers are not
+    /// compressed again.
+    pub fn compress(obj: &HeaderObj, threshold_bytes: usize, registry: &PairRegistry) -> Option<Self> {
+        if CompressedRaw::from_obj(obj).is_some() {
+            return None;
+        }
+        let name = obj.name();
+        let parser = registry.get(name)?;
+        // not `encoded_value`, which fails for max-one headers which are too long
+        let value = uncached_value(obj, MailType::Ascii).ok()?;
+        if value.len() <= threshold_bytes {
+            return None;
+        }
+        // only one form is stored, so it has to be the right one for all mail types
+        if uncached_value(obj, MailType::Internationalized).ok()? != value {
+            return None;
+        }
+        // only compress values which are restored as the same kind of header
+        let restored = parser(&unfold(str::from_utf8(&value).ok()?)).ok()?;
+        if HeaderObjTrait::type_id(&*restored) != HeaderObjTrait::type_id(obj) {
+            return None;
+        }
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&value).ok()?;
+        let deflated = encoder.finish().ok()?;
+
+        Some(CompressedRaw {
+            name,
+            max_one: obj.is_max_one(),
+            validator: obj.validator(),
+            requirement: obj.mail_type_requirement(),
+            component: obj.component_type_name(),
+            parser,
+            original_len: value.len(),
+            deflated,
+            decompressed: OnceCell::new(),
+            modified: false
+        })
+    }
+
+    /// Returns the compressed header if the header object is one.
+    pub fn from_obj(obj: &HeaderObj) -> Option<&CompressedRaw> {
+        if HeaderObjTrait::type_id(obj) == TypeId::of::<CompressedRaw>() {
+            Some(unsafe { &*(obj as *const HeaderObj as *const CompressedRaw) })
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn from_obj_mut(obj: &mut HeaderObj) -> Option<&mut CompressedRaw> {
+        if HeaderObjTrait::type_id(obj) == TypeId::of::<CompressedRaw>() {
+            Some(unsafe { &mut *(obj as *mut HeaderObj as *mut CompressedRaw) })
+        } else {
+            None
+        }
+    }
+
+    /// Replaces a compressed header by its decompressed header.
+    ///
+    /// Returns the header unchanged if it is not compressed or can not
+    /// be decompressed.
+    pub(crate) fn into_decompressed(obj: Box<HeaderObj>) -> Box<HeaderObj> {
+        let can_decompress = CompressedRaw::from_obj(&*obj)
+            .map(|compressed| compressed.decompressed().is_ok())
+            .unwrap_or(false);
+        if !can_decompress {
+            return obj;
+        }
+        let compressed = unsafe { Box::from_raw(Box::into_raw(obj) as *mut CompressedRaw) };
+        let compressed = *compressed;
+        compressed.decompressed.into_inner()
+            .expect("[BUG] decompressed header is cached")
+    }
+
+    /// Returns the decompressed header, decompressing and parsing it on first access.
+    ///
+    /// # Error
+    ///
+    /// Fails if the stored bytes are corrupted or the parser fails to
+    /// create the header from the decompressed value. Nothing is cached
+    /// in this case and typed accessors return a `HeaderTypeError` with
+    /// the `DecompressError` as cause (see `HeaderTypeError::decompress_error`).
+    pub fn decompressed(&self) -> Result<&HeaderObj, DecompressError> {
+        let obj = self.decompressed.get_or_try_init(|| {
+            let value = self.original_value()?;
+            (self.parser)(&unfold(&value))
+                .map_err(|error| DecompressError::Reparse {
+                    header_name: self.name,
+                    component: self.component,
+                    error
+                })
+        })?;
+        Ok(&**obj)
+    }
+
+    /// Like `decompressed` but marks the header as modified, so the decompressed header is encoded.
+    pub fn decompressed_mut(&mut self) -> Result<&mut HeaderObj, DecompressError> {
+        self.decompressed()?;
+        self.modified = true;
+        let obj = self.decompressed.get_mut()
+            .expect("[BUG] decompressed header is cached");
+        Ok(&mut **obj)
+    }
+
+    /// Returns the (folded) us-ascii encoded value of the original header.
+    pub fn original_value(&self) -> Result<String, DecompressError> {
+        let mut value = String::with_capacity(self.original_len);
+        DeflateDecoder::new(&self.deflated[..])
+            .read_to_string(&mut value)
+            .map_err(|_| DecompressError::Corrupted { header_name: self.name })?;
+        Ok(value)
+    }
+
+    /// The length of the encoded value of the original header.
+    pub fn original_len(&self) -> usize {
+        self.original_len
+    }
+
+    /// The length of the stored (compressed) value.
+    pub fn compressed_len(&self) -> usize {
+        self.deflated.len()
+    }
+
+    /// Returns true if the header was accessed mutable, see `decompressed_mut`.
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Fails with `HeaderTooLong` if this is a max-one header whose stored header line is too long.
+    fn check_len(&self) -> Result<(), EncodingError> {
+        if self.max_one {
+            // `"<name>: <value>\r\n"`
+            check_max_one_len(self.name, self.name.as_wire_str().len() + self.original_len + 4)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the decompressed header if it is encoded instead of the stored value.
+    fn modified_obj(&self) -> Option<&HeaderObj> {
+        if self.modified {
+            self.decompressed.get().map(|obj| &**obj)
+        } else {
+            None
+        }
+    }
+}
+
+/// Removes the CRLF of all folds (keeping the whitespace following them).
+fn unfold(value: &str) -> String {
+    value.replace("\r\n", "")
+}
+
+fn decompress_error(err: DecompressError) -> EncodingError {
+    EncodingError::from(err.context(EncodingErrorKind::Other { kind: "DecompressError" }))
+}
+
+impl HeaderObjTrait for CompressedRaw {
+    fn name(&self) -> HeaderName {
+        self.name
+    }
+
+    fn is_max_one(&self) -> bool {
+        self.max_one
+    }
+
+    fn validator(&self) -> Option<HeaderMapValidator> {
+        self.validator
+    }
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        if let Some(obj) = self.modified_obj() {
+            return obj.encode(handle);
+        }
+        self.check_len()?;
+        let value = self.original_value().map_err(decompress_error)?;
+        sep_for!{ line in value.split("\r\n");
+            sep {
+                // the line starts with WSP, so the fold becomes a FWS position
+                // at which the encoder breaks the line again (like `RawFieldBody`)
+                handle.mark_fws_pos();
+            };
+            let line = SoftAsciiStr::from_str(line)
+                .map_err(|_| EncodingError::from(EncodingErrorKind::Malformed))?;
+            handle.write_str(line)?;
+        }
+        Ok(())
+    }
+
+    fn encoded_value(&self, mail_type: MailType) -> Result<Cow<[u8]>, EncodingError> {
+        if let Some(obj) = self.modified_obj() {
+            return obj.encoded_value(mail_type);
+        }
+        self.check_len()?;
+        let value = self.original_value().map_err(decompress_error)?;
+        Ok(Cow::Owned(value.into_bytes()))
+    }
+
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        match self.modified_obj() {
+            Some(obj) => obj.mail_type_requirement(),
+            None => self.requirement
+        }
+    }
+
+    fn component_type_name(&self) -> &'static str {
+        self.component
+    }
+
+    fn boxed_clone(&self) -> Box<HeaderObj> {
+        Box::new(self.clone())
+    }
+}
+
+impl fmt::Debug for CompressedRaw {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.debug_struct("CompressedRaw")
+            .field("name", &self.name)
+            .field("component", &self.component)
+            .field("original_len", &self.original_len)
+            .field("compressed_len", &self.deflated.len())
+            .field("modified", &self.modified)
+            .finish()
+    }
+}
+
+impl HeaderMap {
+
+    /// Compresses the stored values of all headers whose encoded value is longer than `threshold_bytes`.
+    ///
+    /// This is a storage optimization for maps which are kept around for
+    /// a long time but rarely accessed (e.g. archived maps of received
+    /// mails with long `Received` or `References` headers). Each such
+    /// header is replaced by a `CompressedRaw` header, which is transparently
+    /// decompressed on typed access and encodes the original bytes.
+    ///
+    /// Only headers for which the built-in `PairRegistry` has a parser are
+    /// compressed, see `compress_large_values_with` to use another registry.
+    /// Returns the number of compressed headers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # extern crate mail_internals;
+    /// # fn main() {
+    /// use mail_internals::MailType;
+    /// use mail_headers::headers::*;
+    ///
+    /// let mut map = headers! {
+    ///     Subject: "hy there",
+    ///     Comments: "a comment which is long enough to be compressed"
+    /// }.unwrap();
+    /// let encoded = map.encode_checked(MailType::Ascii).unwrap();
+    ///
+    /// assert_eq!(map.compress_large_values(20), 1);
+    /// assert_eq!(map.encode_checked(MailType::Ascii).unwrap(), encoded);
+    /// let comment = map.get(Comments).next().unwrap().unwrap();
+    /// assert_eq!(comment.as_str(), "a comment which is long enough to be compressed");
+    /// # }
+    /// ```
+    pub fn compress_large_values(&mut self, threshold_bytes: usize) -> usize {
+        self.compress_large_values_with(threshold_bytes, &PairRegistry::builtin())
+    }
+
+    /// Like `compress_large_values` but uses given registry to restore the headers.
+    pub fn compress_large_values_with(&mut self, threshold_bytes: usize, registry: &PairRegistry) -> usize {
+        let inner_map = mem::replace(&mut self.inner_map, Default::default());
+        let mut compressed = 0;
+        for (name, obj) in inner_map.into_iter() {
+            let obj = match CompressedRaw::compress(&*obj, threshold_bytes, registry) {
+                Some(raw) => {
+                    compressed += 1;
+                    Box::new(raw) as Box<HeaderObj>
+                },
+                None => obj
+            };
+            self.inner_map.add(name, obj);
+        }
+        if self.limits.max_bytes.is_some() {
+            self.used_bytes = total_bytes(self);
+        }
+        compressed
+    }
+
+    /// Replaces all compressed headers by their decompressed headers.
+    ///
+    /// Returns the number of decompressed headers. Afterwards the headers
+    /// are encoded from their components again (instead of the stored
+    /// bytes), which can normalize their encoding.
+    ///
+    /// # Error
+    ///
+    /// Fails with the error of the first header which can not be
+    /// decompressed, in which case the map is not changed.
+    pub fn decompress_values(&mut self) -> Result<usize, DecompressError> {
+        let mut count = 0;
+        for obj in self.values() {
+            if let Some(compressed) = CompressedRaw::from_obj(obj) {
+                compressed.decompressed()?;
+                count += 1;
+            }
+        }
+
+        let inner_map = mem::replace(&mut self.inner_map, Default::default());
+        for (name, obj) in inner_map.into_iter() {
+            self.inner_map.add(name, CompressedRaw::into_decompressed(obj));
+        }
+        if self.limits.max_bytes.is_some() {
+            self.used_bytes = total_bytes(self);
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use vec1::Vec1;
+
+    use ::HeaderTryFrom;
+    use ::error::{ComponentCreationError, HeaderTooLong};
+    use ::header_components::{MessageId, MessageIdList, Unstructured};
+    use ::headers::*;
+    use super::*;
+
+    /// `count` message ids with 30 bytes each (including the separating space)
+    fn references(count: usize) -> HeaderMap {
+        let ids = (0..count)
+            .map(|idx| MessageId::try_from(format!("msg{:04}.abcdef@example.com", idx)).unwrap())
+            .collect::<Vec<_>>();
+        let mut map = headers! {
+            Subject: "hy there"
+        }.unwrap();
+        map.insert(References::body(MessageIdList(Vec1::from_vec(ids).unwrap())));
+        map
+    }
+
+    fn long_references() -> HeaderMap {
+        // ~7 KiB of message ids (References is max one, so it has to stay below 8 KiB)
+        references(240)
+    }
+
+    fn compressed(map: &HeaderMap, name: HeaderName) -> &CompressedRaw {
+        let obj = map.get_untyped(name).next().unwrap();
+        CompressedRaw::from_obj(obj).expect("compressed header")
+    }
+
+    test!(large_references_round_trip {
+        let mut map = long_references();
+        let encoded = map.encode_checked(MailType::Ascii)?;
+        assert!(encoded.len() > 7_000);
+
+        assert_eq!(map.compress_large_values(1024), 1);
+        assert!(CompressedRaw::from_obj(map.get_untyped(Subject).next().unwrap()).is_none());
+        assert!(compressed(&map, References::name()).decompressed.get().is_none());
+
+        // encoding doesn't need to decompress the header
+        assert_eq!(map.encode_checked(MailType::Ascii)?, encoded);
+        assert!(compressed(&map, References::name()).decompressed.get().is_none());
+
+        let references = map.get_single(References).unwrap()?;
+        assert_eq!(references.0.len(), 240);
+        assert_eq!(references.0[239].as_str(), "msg0239.abcdef@example.com");
+        assert!(compressed(&map, References::name()).decompressed.get().is_some());
+        assert!(map.contains_kind::<References>());
+        assert_eq!(map.encode_checked(MailType::Ascii)?, encoded);
+        assert_eq!(map.encode_checked(MailType::Internationalized)?, encoded);
+
+        let taken = map.take::<References>()?;
+        assert_eq!(taken.len(), 1);
+        assert_eq!(taken[0].0.len(), 240);
+    });
+
+    test!(too_long_max_one_values_are_compressed {
+        // ~10 KiB of message ids, more than `MAX_ONE_HEADER_LEN`
+        let mut map = references(340);
+        let err = assert_err!(map.encode_checked(MailType::Ascii));
+        assert_eq!(HeaderTooLong::find_in(&err).unwrap().name(), References::name());
+        let value = uncached_value(map.get_untyped(References).next().unwrap(), MailType::Ascii)?;
+        assert!(value.len() > 10_000);
+
+        assert_eq!(map.compress_large_values(1024), 1);
+        let compressed_refs = compressed(&map, References::name());
+        assert_eq!(compressed_refs.original_len(), value.len());
+        assert_eq!(compressed_refs.original_value()?.into_bytes(), value);
+
+        // still too long to be encoded, but typed access works
+        let err = assert_err!(map.encode_checked(MailType::Ascii));
+        let too_long = HeaderTooLong::find_in(&err).unwrap();
+        assert_eq!(too_long.name(), References::name());
+        assert_eq!(too_long.encoded_len(), "References: ".len() + value.len() + 2);
+        let references = map.get_single(References).unwrap()?;
+        assert_eq!(references.0.len(), 340);
+        assert_eq!(references.0[339].as_str(), "msg0339.abcdef@example.com");
+    });
+
+    test!(large_multi_values_round_trip {
+        // ~10 KiB of text, `Comments` can appear multiple times so it has no length limit
+        let text = (0..1000).map(|idx| format!("word{:05}", idx)).collect::<Vec<_>>().join(" ");
+        let mut map = headers! {
+            Comments: text.as_str(),
+            Comments: "short"
+        }?;
+        let encoded = map.encode_checked(MailType::Ascii)?;
+        assert!(encoded.len() > 10_000);
+
+        assert_eq!(map.compress_large_values(1024), 1);
+        assert_eq!(map.encode_checked(MailType::Ascii)?, encoded);
+        let comments = map.get(Comments).collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(comments[0].as_str(), text);
+        assert_eq!(comments[1].as_str(), "short");
+        assert_eq!(map.decompress_values()?, 1);
+        assert_eq!(map.encode_checked(MailType::Ascii)?, encoded);
+    });
+
+    test!(values_encoded_differently_by_mail_type_are_not_compressed {
+        let mut map = headers! {
+            Subject: "hö there",
+            Comments: "ascii only",
+            Comments: "nön ascii"
+        }?;
+        let ascii = map.encode_checked(MailType::Ascii)?;
+        let internationalized = map.encode_checked(MailType::Internationalized)?;
+        assert_ne!(ascii, internationalized);
+
+        assert_eq!(map.compress_large_values(0), 1);
+        assert!(CompressedRaw::from_obj(map.get_untyped(Subject).next().unwrap()).is_none());
+        assert_eq!(map.encode_checked(MailType::Ascii)?, ascii);
+        assert_eq!(map.encode_checked(MailType::Internationalized)?, internationalized);
+
+        let comments = map.get(Comments).collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(comments[0].as_str(), "ascii only");
+        assert_eq!(comments[1].as_str(), "nön ascii");
+        assert_eq!(map.get_single(Subject).unwrap()?.as_str(), "hö there");
+    });
+
+    test!(threshold_is_exclusive {
+        let map = headers! {
+            Subject: "hy there"
+        }?;
+        let len = map.get_untyped(Subject).next().unwrap()
+            .encoded_value(MailType::Ascii)?
+            .len();
+
+        let mut at_threshold = map.clone();
+        assert_eq!(at_threshold.compress_large_values(len), 0);
+        let mut below_threshold = map.clone();
+        assert_eq!(below_threshold.compress_large_values(len - 1), 1);
+        assert_eq!(below_threshold.compress_large_values(0), 0);
+        assert_eq!(below_threshold.get_single(Subject).unwrap()?.as_str(), "hy there");
+    });
+
+    test!(compressed_values_are_smaller {
+        let mut map = long_references();
+        map.compress_large_values(1024);
+        let compressed = compressed(&map, References::name());
+        assert!(compressed.original_len() > 7_000);
+        assert!(compressed.compressed_len() * 4 < compressed.original_len());
+        assert!(compressed.component_type_name().ends_with("MessageIdList"));
+    });
+
+    test!(mutable_access_encodes_the_changed_header {
+        let mut map = long_references();
+        map.compress_large_values(0);
+        for subject in map.get_mut(Subject) {
+            *subject?.body_mut() = Unstructured::try_from("changed")?;
+        }
+        assert!(compressed(&map, Subject::name()).is_modified());
+        assert!(!compressed(&map, References::name()).is_modified());
+
+        let encoded = String::from_utf8(map.encode_checked(MailType::Ascii)?)?;
+        assert!(encoded.starts_with("Subject: changed\r\nReferences: <msg0000.abcdef@example.com>"));
+
+        assert_eq!(map.decompress_values()?, 2);
+        assert!(map.values().all(|obj| CompressedRaw::from_obj(obj).is_none()));
+        assert_eq!(String::from_utf8(map.encode_checked(MailType::Ascii)?)?, encoded);
+    });
+
+    test!(failing_reparse_is_a_error_not_a_panic {
+        fn fail(value: &str) -> Result<Box<HeaderObj>, ComponentCreationError> {
+            Err(ComponentCreationError::new_with_str("Unstructured", value))
+        }
+
+        let mut map = headers! {
+            Subject: "hy there"
+        }?;
+        let encoded = map.encode_checked(MailType::Ascii)?;
+        map.compress_large_values(0);
+        {
+            let obj = map.get_untyped_mut(Subject).next().unwrap();
+            CompressedRaw::from_obj_mut(obj).unwrap().parser = fail;
+        }
+
+        let err = assert_err!(map.get_single(Subject).unwrap());
+        assert_eq!(err.header_name(), Subject::name());
+        match err.decompress_error() {
+            Some(&DecompressError::Reparse { .. }) => {},
+            other => panic!("unexpected cause: {:?}", other)
+        }
+        assert!(!map.contains_kind::<Subject>());
+        assert!(map.kind_mismatch::<Subject>().unwrap().decompress_error().is_some());
+        assert!(assert_err!(map.take::<Subject>()).decompress_error().is_some());
+
+        let err = assert_err!(map.decompress_values());
+        assert_eq!(err.header_name(), Subject::name());
+        assert!(err.to_string().contains("can not be parsed"));
+
+        // the stored bytes are still encoded as they are
+        assert_eq!(map.encode_checked(MailType::Ascii)?, encoded);
+    });
+
+    test!(corrupted_bytes_are_a_error {
+        let mut map = headers! {
+            Subject: "hy there"
+        }?;
+        map.compress_large_values(0);
+        {
+            let obj = map.get_untyped_mut(Subject).next().unwrap();
+            CompressedRaw::from_obj_mut(obj).unwrap().deflated = vec![0xff; 4];
+        }
+        assert_err!(map.get_single(Subject).unwrap());
+        match assert_err!(map.decompress_values()) {
+            DecompressError::Corrupted { header_name } => assert_eq!(header_name, Subject::name()),
+            other => panic!("unexpected error: {:?}", other)
+        }
+        assert_err!(map.encode_checked(MailType::Ascii));
+    });
+}
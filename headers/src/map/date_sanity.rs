@@ -0,0 +1,227 @@
+use chrono::{self, Utc};
+
+use ::error::{HeaderValidationError, BuildInValidationError};
+use ::header::HeaderKind;
+use ::headers::{Date, ResentDate};
+
+use super::HeaderMap;
+use super::validator_options::typed_headers;
+
+/// Options for `validate_date_sanity`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DateSanityOptions {
+    /// How far a date can be in the future (wrt. `now`) before it's considered invalid.
+    pub max_future: chrono::Duration,
+
+    /// How far a date can be in the past (wrt. `now`) before it's considered invalid.
+    pub max_past: chrono::Duration,
+
+    /// The time the dates are compared with, if `None` the current time is used.
+    pub now: Option<chrono::DateTime<Utc>>,
+
+    /// If true a map without a `Date` field is invalid.
+    pub require_date: bool,
+
+    /// If true each `Resent-Date` has to be at last as new as the `Resent-Date` below it.
+    ///
+    /// Resent blocks are prepended to the header, so the first `Resent-Date` in
+    /// the map belongs to the newest resent block.
+    pub check_resent_order: bool
+}
+
+impl Default for DateSanityOptions {
+    /// Allows dates up to one day in the future and up to one year in the past,
+    /// doesn't require a `Date` field, but checks the order of `Resent-Date` fields.
+    fn default() -> Self {
+        DateSanityOptions {
+            max_future: chrono::Duration::days(1),
+            max_past: chrono::Duration::days(365),
+            now: None,
+            require_date: false,
+            check_resent_order: true
+        }
+    }
+}
+
+/// Checks that the `Date` and all `Resent-Date` fields have a sensible value.
+///
+/// This is meant to be used by relays and similar which should not forward
+/// mails with absurd dates. Fields which can not be cast to the `Date`/`ResentDate`
+/// header kinds of this crate fail with `UnexpectedComponentType`, or are ignored
+/// if the map has lenient validator options (see `ValidatorOptions`).
+///
+/// Note that this is not a `HeaderMapValidator` (as it needs options), so it is not
+/// run by `HeaderMap::use_contextual_validators` and has to be called explicitly.
+pub fn validate_date_sanity(map: &HeaderMap, opts: DateSanityOptions)
+    -> Result<(), HeaderValidationError>
+{
+    let now = opts.now.unwrap_or_else(Utc::now);
+    let latest = now + opts.max_future;
+    let earliest = now - opts.max_past;
+
+    let check_range = |header_name: &'static str, date: chrono::DateTime<Utc>| {
+        if date > latest {
+            Err(HeaderValidationError::from(
+                BuildInValidationError::DateTooFarInFuture { header_name, date }))
+        } else if date < earliest {
+            Err(HeaderValidationError::from(
+                BuildInValidationError::DateTooFarInPast { header_name, date }))
+        } else {
+            Ok(())
+        }
+    };
+
+    let mut had_date = false;
+    for date in typed_headers::<Date>(map)? {
+        had_date = true;
+        check_range(Date::name().as_str(), **date.body())?;
+    }
+
+    if opts.require_date && !had_date {
+        header_validation_bail!(kind: DateFieldMissing);
+    }
+
+    let mut above: Option<chrono::DateTime<Utc>> = None;
+    for date in typed_headers::<ResentDate>(map)? {
+        let date = **date.body();
+        check_range(ResentDate::name().as_str(), date)?;
+        if opts.check_resent_order {
+            if let Some(above) = above {
+                if above < date {
+                    header_validation_bail!(kind: ResentDateOutOfOrder { date: above });
+                }
+            }
+            above = Some(date);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{self, TimeZone, Utc};
+
+    use ::error::{HeaderValidationError, BuildInValidationError};
+    use ::headers::{Date, ResentDate, Subject};
+    use ::HeaderMap;
+    use ::map::ValidatorOptions;
+    use super::*;
+
+    mod raw_headers {
+        use ::header_components;
+        def_headers! {
+            test_name: validate_header_names,
+            scope: header_components,
+            RawResentDate, unchecked { "Resent-Date" }, RawUnstructured, multi, None
+        }
+    }
+    use self::raw_headers::RawResentDate;
+
+    fn now() -> chrono::DateTime<Utc> {
+        Utc.ymd(2018, 10, 1).and_hms(12, 0, 0)
+    }
+
+    fn opts() -> DateSanityOptions {
+        DateSanityOptions {
+            now: Some(now()),
+            ..Default::default()
+        }
+    }
+
+    fn build_in_error(res: Result<(), HeaderValidationError>) -> BuildInValidationError {
+        match assert_err!(res) {
+            HeaderValidationError::BuildIn(ctx) => *ctx.get_context(),
+            other => panic!("unexpected error: {:?}", other)
+        }
+    }
+
+    test!(future_date_beyond_threshold_fails {
+        let date = now() + chrono::Duration::days(3);
+        let map = headers! {
+            Date: date
+        }?;
+
+        assert_eq!(
+            build_in_error(validate_date_sanity(&map, opts())),
+            BuildInValidationError::DateTooFarInFuture { header_name: "Date", date }
+        );
+    });
+
+    test!(old_but_allowed_date_passes {
+        let map = headers! {
+            Date: now() - chrono::Duration::days(300)
+        }?;
+
+        assert_ok!(validate_date_sanity(&map, opts()));
+    });
+
+    test!(too_old_date_fails {
+        let date = now() - chrono::Duration::days(400);
+        let map = headers! {
+            Date: date
+        }?;
+
+        assert_eq!(
+            build_in_error(validate_date_sanity(&map, opts())),
+            BuildInValidationError::DateTooFarInPast { header_name: "Date", date }
+        );
+    });
+
+    test!(resent_date_order {
+        let older = now() - chrono::Duration::days(2);
+        let newer = now() - chrono::Duration::days(1);
+        let map = headers! {
+            ResentDate: older,
+            ResentDate: newer
+        }?;
+
+        assert_eq!(
+            build_in_error(validate_date_sanity(&map, opts())),
+            BuildInValidationError::ResentDateOutOfOrder { date: older }
+        );
+
+        let no_order_check = DateSanityOptions { check_resent_order: false, ..opts() };
+        assert_ok!(validate_date_sanity(&map, no_order_check));
+
+        let map = headers! {
+            ResentDate: newer,
+            ResentDate: older
+        }?;
+        assert_ok!(validate_date_sanity(&map, opts()));
+    });
+
+    test!(missing_date {
+        let map = headers! {
+            Subject: "no date"
+        }?;
+
+        assert_ok!(validate_date_sanity(&map, opts()));
+
+        let require_date = DateSanityOptions { require_date: true, ..opts() };
+        assert_eq!(
+            build_in_error(validate_date_sanity(&map, require_date)),
+            BuildInValidationError::DateFieldMissing
+        );
+
+        let empty = HeaderMap::new();
+        assert_err!(validate_date_sanity(&empty, require_date));
+    });
+
+    test!(wrong_typed_resent_date_fails_unless_lenient {
+        let mut map = headers! {
+            Date: now(),
+            RawResentDate: "Mon, 1 Oct 2018 12:00:00 +0000"
+        }?;
+
+        match build_in_error(validate_date_sanity(&map, opts())) {
+            BuildInValidationError::UnexpectedComponentType { header_name } => {
+                assert_eq!(header_name, "Resent-Date")
+            },
+            other => panic!("unexpected error: {:?}", other)
+        }
+
+        map.set_validator_options(ValidatorOptions::lenient());
+        assert_ok!(validate_date_sanity(&map, opts()));
+    });
+}
@@ -0,0 +1,209 @@
+use std::fmt::{self, Display};
+use std::cell::{Ref, RefCell};
+
+use internals::MailType;
+use internals::error::EncodingError;
+
+use ::name::HeaderName;
+use ::header::HeaderObj;
+
+use super::HeaderMap;
+
+/// A `Display` adapter for the header block of a `HeaderMap`, see `HeaderMap::display`.
+///
+/// The adapter collects the encoding errors while it is formatted, so it
+/// is `Send` but not `Sync`:
+///
+/// ```compile_fail
+/// # extern crate mail_headers;
+/// # extern crate mail_internals;
+/// use mail_internals::MailType;
+/// use mail_headers::HeaderMap;
+/// use mail_headers::map::HeaderMapDisplay;
+///
+/// fn assert_sync<T: Sync>() {}
+///
+/// # fn main() {
+/// assert_sync::<HeaderMapDisplay<'static>>();
+/// # let _ = (HeaderMap::new(), MailType::Ascii);
+/// # }
+/// ```
+pub struct HeaderMapDisplay<'a> {
+    map: &'a HeaderMap,
+    mail_type: MailType,
+    errors: RefCell<Vec<(HeaderName, EncodingError)>>
+}
+
+impl HeaderMap {
+
+    /// Returns a adapter implementing `Display` by encoding this map.
+    ///
+    /// Headers are encoded in map order, each line ends with CRLF. If
+    /// encoding a header fails a placeholder line of the form
+    /// `X-Encoding-Error: <header name>: <error>` is written instead and
+    /// the error is recorded, so that it can be retrieved afterwards
+    /// using `HeaderMapDisplay::errors`. Use `try_display` to make
+    /// sure all headers can be encoded before displaying the map.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # extern crate mail_internals;
+    /// # fn main() {
+    /// use mail_internals::MailType;
+    /// use mail_headers::headers::*;
+    ///
+    /// let map = headers! {
+    ///     _From: ["me@example.com"],
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// let display = map.display(MailType::Ascii);
+    /// let text = format!("{}", display);
+    /// assert_eq!(text, "From: <me@example.com>\r\nSubject: hy there\r\n");
+    /// assert!(display.errors().is_empty());
+    /// # }
+    /// ```
+    pub fn display(&self, mail_type: MailType) -> HeaderMapDisplay {
+        HeaderMapDisplay {
+            map: self,
+            mail_type,
+            errors: RefCell::new(Vec::new())
+        }
+    }
+
+    /// Like `display` but fails if any header can not be encoded.
+    ///
+    /// Displaying the returned adapter will not write any placeholder lines
+    /// (as long as the map is not changed in between, which the borrow prevents).
+    pub fn try_display(&self, mail_type: MailType) -> Result<HeaderMapDisplay, EncodingError> {
+        for (name, obj) in self.iter() {
+            encode_header(name, obj, mail_type)?;
+        }
+        Ok(self.display(mail_type))
+    }
+}
+
+impl<'a> HeaderMapDisplay<'a> {
+
+    /// Returns the errors which happened when this adapter was displayed the last time.
+    pub fn errors(&self) -> Ref<[(HeaderName, EncodingError)]> {
+        Ref::map(self.errors.borrow(), |errors| &errors[..])
+    }
+
+    /// Returns the mail type used for encoding.
+    pub fn mail_type(&self) -> MailType {
+        self.mail_type
+    }
+}
+
+impl<'a> Display for HeaderMapDisplay<'a> {
+
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        let mut errors = self.errors.borrow_mut();
+        errors.clear();
+        for (name, obj) in self.map.iter() {
+            match encode_header(name, obj, self.mail_type) {
+                Ok(line) => fter.write_str(&line)?,
+                Err(err) => {
+                    let message = err.to_string().replace(|ch: char| ch.is_control(), " ");
+                    write!(fter, "X-Encoding-Error: {}: {}\r\n", name, message)?;
+                    errors.push((name, err));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Debug for HeaderMapDisplay<'a> {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.debug_struct("HeaderMapDisplay")
+            .field("mail_type", &self.mail_type)
+            .field("errors", &self.errors.borrow().len())
+            .finish()
+    }
+}
+
+/// Encodes a single header (including the final CRLF).
+pub fn encode_header(name: HeaderName, obj: &HeaderObj, mail_type: MailType)
+    -> Result<String, EncodingError>
+{
+    let value = obj.encoded_value(mail_type)?;
+    // the encoder only writes us-ascii or (internationalized mails) utf8
+    Ok(format!("{}: {}\r\n", name.as_wire_str(), String::from_utf8_lossy(&value)))
+}
+
+#[cfg(test)]
+mod test {
+    use internals::MailType;
+    use ::HeaderKind;
+    use ::header_components as components;
+    use ::headers::{_From, Subject, Comments};
+    use ::map::MboxOptions;
+
+    def_headers! {
+        test_name: validate_header_names,
+        scope: components,
+        XRaw, unchecked { "X-Raw" }, RawFieldBody, multi, None
+    }
+
+    test!(clean_map_renders_like_encoded_map {
+        let map = headers! {
+            _From: ["me@example.com"],
+            Subject: "hy there",
+            Comments: "some comment"
+        }?;
+
+        let display = map.display(MailType::Ascii);
+        let text = format!("{}", display);
+        let encoded = map.encode_for_mbox(MailType::Ascii, MboxOptions { escape_from_lines: false, ..Default::default() })?;
+        assert_eq!(text.replace("\r\n", "\n") + "\n", String::from_utf8(encoded)?);
+        assert!(display.errors().is_empty());
+    });
+
+    test!(failing_header_renders_placeholder {
+        let map = headers! {
+            Subject: "hy there",
+            XRaw: "töken",
+            Comments: "some comment"
+        }?;
+
+        let display = map.display(MailType::Ascii);
+        let text = format!("{}", display);
+        let lines = text.split("\r\n").collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "Subject: hy there");
+        assert!(lines[1].starts_with("X-Encoding-Error: X-Raw: "), "{:?}", lines[1]);
+        assert_eq!(lines[2], "Comments: some comment");
+        assert_eq!(lines[3], "");
+
+        {
+            let errors = display.errors();
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].0, XRaw::name());
+        }
+
+        // displaying again does not duplicate errors
+        let _ = format!("{}", display);
+        assert_eq!(display.errors().len(), 1);
+
+        // but it works with internationalized mails
+        let display = map.display(MailType::Internationalized);
+        assert!(format!("{}", display).contains("X-Raw: töken\r\n"));
+        assert!(display.errors().is_empty());
+    });
+
+    test!(try_display_refuses_upfront {
+        let map = headers! {
+            Subject: "hy there",
+            XRaw: "töken"
+        }?;
+
+        assert_err!(map.try_display(MailType::Ascii));
+        let display = assert_ok!(map.try_display(MailType::Internationalized));
+        assert_eq!(format!("{}", display), "Subject: hy there\r\nX-Raw: töken\r\n");
+    });
+}
@@ -0,0 +1,394 @@
+use std::collections::BTreeMap;
+
+use ::error::{HeaderValidationError, DisplayNameViolation, DisplayNameViolationKind};
+use ::header::HeaderKind;
+use ::header_components::Mailbox;
+use ::headers::{_From, Sender};
+
+use super::HeaderMap;
+use super::validator_options::typed_headers;
+
+/// A pattern a (decoded) display name is matched against.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum NamePattern {
+    /// The display name has to be exactly this string.
+    Exact(String),
+    /// The display name has to start with this string.
+    Prefix(String),
+    /// The display name has to end with this string.
+    Suffix(String)
+}
+
+impl NamePattern {
+
+    /// Returns true if given display name matches this pattern.
+    ///
+    /// Matching is case sensitive and done on the text as displayed, i.e.
+    /// on the output of `Phrase::to_display_string`.
+    pub fn matches(&self, display_name: &str) -> bool {
+        match *self {
+            NamePattern::Exact(ref exact) => display_name == exact,
+            NamePattern::Prefix(ref prefix) => display_name.starts_with(&**prefix),
+            NamePattern::Suffix(ref suffix) => display_name.ends_with(&**suffix)
+        }
+    }
+}
+
+/// The display names allowed for `From` mailboxes of one domain.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct DomainNamePolicy {
+    /// A display name has to match at last one of the patterns.
+    pub allowed: Vec<NamePattern>,
+    /// If true a mailbox without display name is invalid.
+    pub require_display_name: bool
+}
+
+/// The rule for the display name of the `Sender` mailbox.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SenderNames {
+    /// Any (or no) display name is allowed.
+    Any,
+    /// The `Sender` mailbox must not have a display name.
+    Forbidden,
+    /// If the `Sender` mailbox has a display name it has to match one of the patterns.
+    Allowed(Vec<NamePattern>)
+}
+
+/// A policy for the display names of the `From` and `Sender` mailboxes.
+///
+/// Display names are decoded before they are checked (see
+/// `Phrase::to_display_string`), so a name given as encoded word can not be
+/// used to bypass the policy.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate mail_headers;
+/// # fn main() {
+/// use mail_headers::headers::*;
+/// use mail_headers::map::{DisplayNamePolicy, NamePattern, make_display_name_policy_validator};
+///
+/// let policy = DisplayNamePolicy::new()
+///     .with_domain("example.com", vec![
+///         NamePattern::Exact("Example Support".to_owned()),
+///         NamePattern::Suffix("(via Example)".to_owned())
+///     ]);
+/// let validator = make_display_name_policy_validator(policy);
+///
+/// let map = headers! {
+///     _From: [("Example Support", "support@example.com")]
+/// }.unwrap();
+/// assert!(validator(&map).is_ok());
+///
+/// let map = headers! {
+///     _From: [("Example Billing", "billing@example.com")]
+/// }.unwrap();
+/// assert!(validator(&map).is_err());
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DisplayNamePolicy {
+    domains: BTreeMap<String, DomainNamePolicy>,
+    allow_unlisted_domains: bool,
+    sender: SenderNames
+}
+
+impl Default for DisplayNamePolicy {
+    fn default() -> Self {
+        DisplayNamePolicy::new()
+    }
+}
+
+impl DisplayNamePolicy {
+
+    /// Creates a policy without any domains which allows any `Sender` display name.
+    ///
+    /// `From` mailboxes of domains which are not added to the policy are invalid.
+    pub fn new() -> Self {
+        DisplayNamePolicy {
+            domains: BTreeMap::new(),
+            allow_unlisted_domains: false,
+            sender: SenderNames::Any
+        }
+    }
+
+    /// Adds the allowed display names for a domain (replacing any previous ones).
+    ///
+    /// Domains are compared case insensitive. A display name is not required.
+    pub fn with_domain(self, domain: &str, allowed: Vec<NamePattern>) -> Self {
+        self.with_domain_policy(domain, DomainNamePolicy { allowed, require_display_name: false })
+    }
+
+    /// Adds the policy for a domain (replacing any previous one).
+    pub fn with_domain_policy(mut self, domain: &str, policy: DomainNamePolicy) -> Self {
+        self.domains.insert(domain.to_lowercase(), policy);
+        self
+    }
+
+    /// If true `From` mailboxes of domains not added to the policy are not checked.
+    pub fn allow_unlisted_domains(mut self, allow: bool) -> Self {
+        self.allow_unlisted_domains = allow;
+        self
+    }
+
+    /// Sets the rule for the display name of the `Sender` mailbox.
+    pub fn with_sender_names(mut self, sender: SenderNames) -> Self {
+        self.sender = sender;
+        self
+    }
+
+    /// Returns the policy used for `From` mailboxes of given domain.
+    pub fn domain_policy(&self, domain: &str) -> Option<&DomainNamePolicy> {
+        self.domains.get(&domain.to_lowercase())
+    }
+
+    /// Checks the `From` and `Sender` mailboxes of the map.
+    ///
+    /// The error is a `HeaderValidationError::Custom` with a
+    /// `DisplayNameViolation`. Fields which can not be cast to the
+    /// `_From`/`Sender` header kinds of this crate fail with
+    /// `BuildInValidationError::UnexpectedComponentType`, or are ignored
+    /// if the map has lenient validator options (see `ValidatorOptions`).
+    pub fn validate(&self, map: &HeaderMap) -> Result<(), HeaderValidationError> {
+        for from in typed_headers::<_From>(map)? {
+            for mailbox in from.body().0.iter() {
+                self.check_from(mailbox)?;
+            }
+        }
+
+        for sender in typed_headers::<Sender>(map)? {
+            self.check_sender(sender.body())?;
+        }
+        Ok(())
+    }
+
+    fn check_from(&self, mailbox: &Mailbox) -> Result<(), HeaderValidationError> {
+        let policy = match self.domain_policy(mailbox.email.domain.as_str()) {
+            Some(policy) => policy,
+            None if self.allow_unlisted_domains => return Ok(()),
+            None => return violation(_From::name().as_str(), mailbox, None,
+                DisplayNameViolationKind::UnlistedDomain)
+        };
+
+        match mailbox.display_name {
+            Some(ref phrase) => {
+                let name = phrase.to_display_string();
+                if policy.allowed.iter().any(|pattern| pattern.matches(&name)) {
+                    Ok(())
+                } else {
+                    violation(_From::name().as_str(), mailbox, Some(name),
+                        DisplayNameViolationKind::NotAllowed)
+                }
+            },
+            None if policy.require_display_name => violation(
+                _From::name().as_str(), mailbox, None, DisplayNameViolationKind::Missing),
+            None => Ok(())
+        }
+    }
+
+    fn check_sender(&self, mailbox: &Mailbox) -> Result<(), HeaderValidationError> {
+        let name = match mailbox.display_name {
+            Some(ref phrase) => phrase.to_display_string(),
+            None => return Ok(())
+        };
+        let allowed = match self.sender {
+            SenderNames::Any => true,
+            SenderNames::Forbidden => false,
+            SenderNames::Allowed(ref patterns) => patterns.iter().any(|pattern| pattern.matches(&name))
+        };
+
+        if allowed {
+            Ok(())
+        } else {
+            violation(Sender::name().as_str(), mailbox, Some(name),
+                DisplayNameViolationKind::SenderNotAllowed)
+        }
+    }
+}
+
+fn violation(
+    header_name: &'static str,
+    mailbox: &Mailbox,
+    display_name: Option<String>,
+    kind: DisplayNameViolationKind
+) -> Result<(), HeaderValidationError> {
+    let err = DisplayNameViolation::new(header_name, mailbox.email.to_string(), display_name, kind);
+    Err(HeaderValidationError::Custom(err.into()))
+}
+
+/// Creates a validator checking the `From` and `Sender` display names with given policy.
+///
+/// This is a shorthand for `DisplayNamePolicy::validate`. As the returned
+/// closure captures the policy it can not be passed to `HeaderMap::add_validator`
+/// (which takes a `HeaderMapValidator` function), instead it has to be called
+/// explicitly, e.g. before encoding an outgoing mail.
+pub fn make_display_name_policy_validator(policy: DisplayNamePolicy)
+    -> impl Fn(&HeaderMap) -> Result<(), HeaderValidationError> + Send + Sync
+{
+    move |map| policy.validate(map)
+}
+
+#[cfg(test)]
+mod test {
+    use ::error::{
+        HeaderValidationError, BuildInValidationError,
+        DisplayNameViolation, DisplayNameViolationKind
+    };
+    use ::headers::{_From, Sender};
+    use ::HeaderMap;
+    use ::map::ValidatorOptions;
+    use super::*;
+
+    mod raw_headers {
+        use ::header_components;
+        def_headers! {
+            test_name: validate_header_names,
+            scope: header_components,
+            RawFrom, unchecked { "From" }, RawUnstructured, maxOne, None
+        }
+    }
+    use self::raw_headers::RawFrom;
+
+    fn policy() -> DisplayNamePolicy {
+        DisplayNamePolicy::new()
+            .with_domain("example.com", vec![
+                NamePattern::Exact("Example Support".to_owned()),
+                NamePattern::Prefix("Example ".to_owned())
+            ])
+            .with_domain_policy("shop.example", DomainNamePolicy {
+                allowed: vec![NamePattern::Suffix("| Shop".to_owned())],
+                require_display_name: true
+            })
+    }
+
+    fn violation(res: Result<(), HeaderValidationError>) -> DisplayNameViolation {
+        match assert_err!(res) {
+            HeaderValidationError::Custom(err) => {
+                err.downcast::<DisplayNameViolation>().expect("unexpected custom error")
+            },
+            other => panic!("unexpected error: {:?}", other)
+        }
+    }
+
+    test!(allowed_name_passes {
+        let validator = make_display_name_policy_validator(policy());
+        let map = headers! {
+            _From: [("Example Support", "support@example.com"), ("Example Sales", "sales@EXAMPLE.com")]
+        }?;
+
+        assert_ok!(validator(&map));
+    });
+
+    test!(disallowed_name_fails {
+        let map = headers! {
+            _From: [("Example Support", "support@example.com"), ("Evil Corp", "evil@example.com")]
+        }?;
+
+        let err = violation(policy().validate(&map));
+        assert_eq!(err.kind(), DisplayNameViolationKind::NotAllowed);
+        assert_eq!(err.header_name(), "From");
+        assert_eq!(err.mailbox(), "evil@example.com");
+        assert_eq!(err.display_name(), Some("Evil Corp"));
+    });
+
+    test!(encoded_word_name_is_decoded {
+        let map = headers! {
+            _From: [("=?utf-8?b?RXZpbA==?=", "support@example.com")]
+        }?;
+
+        let err = violation(policy().validate(&map));
+        assert_eq!(err.kind(), DisplayNameViolationKind::NotAllowed);
+        assert_eq!(err.display_name(), Some("Evil"));
+
+        let map = headers! {
+            _From: [("=?utf-8?q?Example_Support?=", "support@example.com")]
+        }?;
+        let policy = DisplayNamePolicy::new()
+            .with_domain("example.com", vec![NamePattern::Exact("Example Support".to_owned())]);
+        assert_ok!(policy.validate(&map));
+    });
+
+    test!(policy_is_selected_by_domain {
+        let map = headers! {
+            _From: [("Deals | Shop", "deals@shop.example")]
+        }?;
+        assert_ok!(policy().validate(&map));
+
+        // allowed for shop.example but not for example.com
+        let map = headers! {
+            _From: [("Deals | Shop", "deals@example.com")]
+        }?;
+        let err = violation(policy().validate(&map));
+        assert_eq!(err.mailbox(), "deals@example.com");
+
+        let map = headers! {
+            _From: [("Example Support", "support@other.example")]
+        }?;
+        let err = violation(policy().validate(&map));
+        assert_eq!(err.kind(), DisplayNameViolationKind::UnlistedDomain);
+        assert_ok!(policy().allow_unlisted_domains(true).validate(&map));
+    });
+
+    test!(missing_display_name {
+        let map = headers! {
+            _From: ["support@example.com"]
+        }?;
+        assert_ok!(policy().validate(&map));
+
+        let map = headers! {
+            _From: ["deals@shop.example"]
+        }?;
+        let err = violation(policy().validate(&map));
+        assert_eq!(err.kind(), DisplayNameViolationKind::Missing);
+        assert_eq!(err.display_name(), None);
+    });
+
+    test!(sender_names {
+        let map = headers! {
+            _From: [("Example Support", "support@example.com")],
+            Sender: ("Mailer", "mailer@relay.example")
+        }?;
+        assert_ok!(policy().validate(&map));
+
+        let forbidden = policy().with_sender_names(SenderNames::Forbidden);
+        let err = violation(forbidden.validate(&map));
+        assert_eq!(err.kind(), DisplayNameViolationKind::SenderNotAllowed);
+        assert_eq!(err.header_name(), "Sender");
+        assert_eq!(err.mailbox(), "mailer@relay.example");
+
+        let allowed = policy().with_sender_names(
+            SenderNames::Allowed(vec![NamePattern::Exact("Mailer".to_owned())]));
+        assert_ok!(allowed.validate(&map));
+
+        let map = headers! {
+            _From: [("Example Support", "support@example.com")],
+            Sender: "mailer@relay.example"
+        }?;
+        assert_ok!(forbidden.validate(&map));
+
+        let empty = HeaderMap::new();
+        assert_ok!(forbidden.validate(&empty));
+    });
+
+    test!(wrong_typed_from_fails_unless_lenient {
+        let mut map = headers! {
+            RawFrom: "Evil Corp <evil@example.com>"
+        }?;
+
+        match assert_err!(policy().validate(&map)) {
+            HeaderValidationError::BuildIn(berr) => {
+                match *berr.get_context() {
+                    BuildInValidationError::UnexpectedComponentType { header_name } => {
+                        assert_eq!(header_name, "From")
+                    },
+                    ref other => panic!("unexpected error: {:?}", other)
+                }
+            },
+            other => panic!("unexpected error: {:?}", other)
+        }
+
+        map.set_validator_options(ValidatorOptions::lenient());
+        assert_ok!(policy().validate(&map));
+    });
+}
@@ -0,0 +1,387 @@
+use std::fmt::{self, Display};
+
+#[cfg(feature="serde")]
+use serde::{Serialize, Serializer, ser::SerializeStruct};
+
+use internals::MailType;
+
+use ::name::HeaderName;
+use ::header::HeaderObj;
+use ::mail_type::{MailTypeOptions, rank};
+
+use super::HeaderMap;
+
+/// The mechanism used to encode a header differently when downgrading, see `HeaderDowngrade`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature="serde", derive(Serialize))]
+pub enum DowngradeMechanism {
+    /// Non us-ascii text is encoded as encoded words (rfc2047).
+    EncodedWord,
+    /// Internationalized domain names are encoded with punycode (rfc3492).
+    Punycode,
+    /// Non us-ascii parameters are encoded using rfc2231.
+    Rfc2231
+}
+
+impl DowngradeMechanism {
+
+    /// A short human readable name, e.g. `encoded-word`.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            DowngradeMechanism::EncodedWord => "encoded-word",
+            DowngradeMechanism::Punycode => "punycode",
+            DowngradeMechanism::Rfc2231 => "rfc2231"
+        }
+    }
+
+    /// The marker the mechanism leaves in the encoded value.
+    fn marker(&self) -> &'static str {
+        match *self {
+            DowngradeMechanism::EncodedWord => "=?",
+            DowngradeMechanism::Punycode => "xn--",
+            DowngradeMechanism::Rfc2231 => "*="
+        }
+    }
+}
+
+/// How downgrading the mail type affects a single header, see `HeaderMap::downgrade_report`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature="serde", derive(Serialize))]
+pub enum HeaderDowngrade {
+    /// The header is encoded byte-identical.
+    Unchanged,
+    /// The header is encoded differently.
+    ///
+    /// The lengths are the lengths of the encoded values (excluding the
+    /// header name). The mechanisms are derived from the encoded values
+    /// and might be empty if the change was done in some other way.
+    Altered {
+        from_len: usize,
+        to_len: usize,
+        mechanisms: Vec<DowngradeMechanism>
+    },
+    /// The header can not be encoded at all, `reason` tells why.
+    Lost { reason: String }
+}
+
+/// The downgrade outcome of a single header.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct DowngradeEntry {
+    pub name: HeaderName,
+    pub outcome: HeaderDowngrade
+}
+
+/// Describes how the headers of a map change when encoded with a "lower" mail type.
+///
+/// The `Display` implementation renders it as a table with one row per header.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DowngradeReport {
+    from: MailType,
+    to: MailType,
+    entries: Vec<DowngradeEntry>
+}
+
+impl DowngradeReport {
+
+    /// The mail type the map would normally be encoded with.
+    pub fn from_mail_type(&self) -> MailType {
+        self.from
+    }
+
+    /// The mail type the map would be downgraded to.
+    pub fn to_mail_type(&self) -> MailType {
+        self.to
+    }
+
+    /// One entry per header, in map order.
+    pub fn entries(&self) -> &[DowngradeEntry] {
+        &self.entries
+    }
+
+    /// Returns true if no header is lost.
+    pub fn is_lossless(&self) -> bool {
+        self.lost().next().is_none()
+    }
+
+    /// Returns true if all headers are unchanged.
+    pub fn is_unchanged(&self) -> bool {
+        self.entries.iter().all(|entry| entry.outcome == HeaderDowngrade::Unchanged)
+    }
+
+    /// Iterates over the entries of all altered headers.
+    pub fn altered<'a>(&'a self) -> impl Iterator<Item=&'a DowngradeEntry> + 'a {
+        self.entries.iter().filter(|entry| match entry.outcome {
+            HeaderDowngrade::Altered { .. } => true,
+            _ => false
+        })
+    }
+
+    /// Iterates over the entries of all lost headers.
+    pub fn lost<'a>(&'a self) -> impl Iterator<Item=&'a DowngradeEntry> + 'a {
+        self.entries.iter().filter(|entry| match entry.outcome {
+            HeaderDowngrade::Lost { .. } => true,
+            _ => false
+        })
+    }
+}
+
+impl HeaderMap {
+
+    /// Reports how the headers would change if encoded with `to` instead of `from`.
+    ///
+    /// Every header is encoded (dry-run) with both mail types and classified as
+    /// `Unchanged` if both encodings are byte-identical, `Altered` if they differ
+    /// (e.g. because non us-ascii text needs encoded words or a domain needs
+    /// punycode) or `Lost` if it can not be encoded with `to` at all (e.g. a
+    /// address with a non us-ascii local part in a ascii mail).
+    ///
+    /// The map is not modified (through the encoded values are cached, see
+    /// `HeaderObjTrait::encoded_value`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # extern crate mail_internals;
+    /// # fn main() {
+    /// use mail_internals::MailType;
+    /// use mail_headers::headers::*;
+    /// use mail_headers::map::HeaderDowngrade;
+    ///
+    /// let map = headers! {
+    ///     _From: ["me@example.com"],
+    ///     Subject: "hö there"
+    /// }.unwrap();
+    ///
+    /// let report = map.downgrade_report(MailType::Internationalized, MailType::Ascii);
+    /// assert!(report.is_lossless());
+    /// assert_eq!(report.entries()[0].outcome, HeaderDowngrade::Unchanged);
+    /// assert_eq!(report.altered().count(), 1);
+    /// # }
+    /// ```
+    pub fn downgrade_report(&self, from: MailType, to: MailType) -> DowngradeReport {
+        let entries = self.iter()
+            .map(|(name, obj)| DowngradeEntry {
+                name,
+                outcome: downgrade_header(&**obj, from, to)
+            })
+            .collect();
+
+        DowngradeReport { from, to, entries }
+    }
+}
+
+fn downgrade_header(obj: &HeaderObj, from: MailType, to: MailType) -> HeaderDowngrade {
+    let options = MailTypeOptions::default();
+    match obj.mail_type_requirement().mail_type(options) {
+        Err(reason) => return HeaderDowngrade::Lost { reason: reason.to_owned() },
+        Ok(needed) if rank(needed) > rank(to) => return HeaderDowngrade::Lost {
+            reason: format!("needs a {} mail", mail_type_str(needed))
+        },
+        Ok(_) => {}
+    }
+
+    let encoded = obj.encoded_value(from)
+        .and_then(|from_value| Ok((from_value, obj.encoded_value(to)?)));
+    let (from_value, to_value) = match encoded {
+        Ok(values) => values,
+        Err(err) => return HeaderDowngrade::Lost { reason: err.to_string() }
+    };
+
+    if from_value == to_value {
+        return HeaderDowngrade::Unchanged;
+    }
+
+    let mechanisms = [
+        DowngradeMechanism::EncodedWord,
+        DowngradeMechanism::Punycode,
+        DowngradeMechanism::Rfc2231
+    ].iter()
+        .filter(|mechanism| {
+            let marker = mechanism.marker();
+            count_marker(&to_value, marker) > count_marker(&from_value, marker)
+        })
+        .cloned()
+        .collect();
+
+    HeaderDowngrade::Altered {
+        from_len: from_value.len(),
+        to_len: to_value.len(),
+        mechanisms
+    }
+}
+
+/// Counts the (non overlapping, ascii case-insensitive) occurrences of marker.
+fn count_marker(value: &[u8], marker: &str) -> usize {
+    let marker = marker.as_bytes();
+    let mut count = 0;
+    let mut idx = 0;
+    while idx + marker.len() <= value.len() {
+        if value[idx..idx + marker.len()].eq_ignore_ascii_case(marker) {
+            count += 1;
+            idx += marker.len();
+        } else {
+            idx += 1;
+        }
+    }
+    count
+}
+
+fn mail_type_str(mail_type: MailType) -> &'static str {
+    match mail_type {
+        MailType::Ascii => "ascii",
+        MailType::Mime8BitEnabled => "mime8bit",
+        MailType::Internationalized => "internationalized"
+    }
+}
+
+impl Display for HeaderDowngrade {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HeaderDowngrade::Unchanged => fter.write_str("unchanged"),
+            HeaderDowngrade::Altered { from_len, to_len, ref mechanisms } => {
+                write!(fter, "altered ({} -> {} bytes", from_len, to_len)?;
+                for (idx, mechanism) in mechanisms.iter().enumerate() {
+                    let sep = if idx == 0 { ", " } else { " + " };
+                    write!(fter, "{}{}", sep, mechanism.as_str())?;
+                }
+                fter.write_str(")")
+            },
+            HeaderDowngrade::Lost { ref reason } => write!(fter, "lost ({})", reason)
+        }
+    }
+}
+
+/// Renders the report as table, e.g.:
+///
+/// ```text
+/// internationalized -> ascii
+/// From     lost (needs a internationalized mail)
+/// Subject  altered (8 -> 24 bytes, encoded-word)
+/// Date     unchanged
+/// ```
+impl Display for DowngradeReport {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(fter, "{} -> {}", mail_type_str(self.from), mail_type_str(self.to))?;
+        let width = self.entries.iter()
+            .map(|entry| entry.name.as_str().len())
+            .max()
+            .unwrap_or(0);
+        for entry in &self.entries {
+            writeln!(fter, "{:width$}  {}", entry.name.as_str(), entry.outcome, width = width)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature="serde")]
+impl Serialize for DowngradeEntry {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        let mut state = serializer.serialize_struct("DowngradeEntry", 2)?;
+        state.serialize_field("name", self.name.as_str())?;
+        state.serialize_field("outcome", &self.outcome)?;
+        state.end()
+    }
+}
+
+#[cfg(feature="serde")]
+impl Serialize for DowngradeReport {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where S: Serializer
+    {
+        let mut state = serializer.serialize_struct("DowngradeReport", 3)?;
+        state.serialize_field("from", mail_type_str(self.from))?;
+        state.serialize_field("to", mail_type_str(self.to))?;
+        state.serialize_field("entries", &self.entries)?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderKind;
+    use ::headers::*;
+    use super::*;
+
+    fn outcome(report: &DowngradeReport, name: HeaderName) -> &HeaderDowngrade {
+        &report.entries().iter()
+            .find(|entry| entry.name == name)
+            .expect("header missing in report")
+            .outcome
+    }
+
+    fn assert_altered(report: &DowngradeReport, name: HeaderName, expected: &[DowngradeMechanism]) {
+        match *outcome(report, name) {
+            HeaderDowngrade::Altered { from_len, to_len, ref mechanisms } => {
+                assert_eq!(&mechanisms[..], expected, "{}", name);
+                assert!(to_len > from_len, "{}: {} -> {}", name, from_len, to_len);
+            },
+            ref other => panic!("{}: unexpected outcome: {:?}", name, other)
+        }
+    }
+
+    test!(classifies_headers {
+        let map = headers! {
+            _From: ["thé@example.com"],
+            _To: ["me@bücher.example"],
+            Subject: "hö there",
+            Comments: "plain text"
+        }?;
+
+        let report = map.downgrade_report(MailType::Internationalized, MailType::Ascii);
+        assert_eq!(report.entries().len(), 4);
+        assert_altered(&report, Subject::name(), &[DowngradeMechanism::EncodedWord]);
+        assert_altered(&report, _To::name(), &[DowngradeMechanism::Punycode]);
+        assert_eq!(*outcome(&report, Comments::name()), HeaderDowngrade::Unchanged);
+        match *outcome(&report, _From::name()) {
+            HeaderDowngrade::Lost { .. } => {},
+            ref other => panic!("unexpected outcome: {:?}", other)
+        }
+
+        assert!(!report.is_lossless());
+        assert!(!report.is_unchanged());
+        assert_eq!(report.altered().count(), 2);
+        assert_eq!(report.lost().map(|entry| entry.name).collect::<Vec<_>>(), vec![_From::name()]);
+    });
+
+    test!(same_mail_type_is_unchanged {
+        let map = headers! {
+            _To: ["me@bücher.example"],
+            Subject: "hö there"
+        }?;
+
+        let report = map.downgrade_report(MailType::Internationalized, MailType::Internationalized);
+        assert!(report.is_unchanged());
+        assert!(report.is_lossless());
+    });
+
+    test!(map_is_not_modified {
+        let map = headers! {
+            _From: ["thé@example.com"],
+            Subject: "hö there"
+        }?;
+        let before = map.encode_checked(MailType::Internationalized)?;
+
+        map.downgrade_report(MailType::Internationalized, MailType::Ascii);
+        assert_eq!(map.encode_checked(MailType::Internationalized)?, before);
+        assert_eq!(map.len(), 2);
+    });
+
+    test!(display_as_table {
+        let map = headers! {
+            Subject: "hö there",
+            Comments: "plain text"
+        }?;
+
+        let report = map.downgrade_report(MailType::Internationalized, MailType::Ascii);
+        let table = report.to_string();
+        let lines = table.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "internationalized -> ascii");
+        assert!(lines[1].starts_with("Subject   altered ("), "{:?}", lines[1]);
+        assert!(lines[1].ends_with(", encoded-word)"), "{:?}", lines[1]);
+        assert_eq!(lines[2], "Comments  unchanged");
+    });
+}
@@ -0,0 +1,170 @@
+use std::marker::PhantomData;
+use std::{mem, vec};
+
+use ::name::{HeaderName, HasHeaderName};
+use ::header::HeaderObj;
+
+use super::HeaderMap;
+use super::limits::total_bytes;
+
+/// Iterator over the headers removed from a map, see `HeaderMap::drain` and `HeaderMap::drain_name`.
+///
+/// The headers are removed from the map when the iterator is created, so
+/// dropping it before it is exhausted drops the remaining headers.
+#[derive(Debug)]
+pub struct Drain<'a> {
+    iter: vec::IntoIter<(HeaderName, Box<HeaderObj>)>,
+    _map: PhantomData<&'a mut HeaderMap>
+}
+
+impl HeaderMap {
+
+    /// Removes all headers from the map and returns them in insertion order.
+    ///
+    /// Like `clear` this leaves the map empty (but keeps its limits), unlike
+    /// `clear` the headers can be moved out of the map without cloning them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    ///
+    /// let mut map = headers! {
+    ///     _From: ["me@example.com"],
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// let names = map.drain().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+    /// assert_eq!(names, vec!["From", "Subject"]);
+    /// assert_eq!(map.len(), 0);
+    /// # }
+    /// ```
+    pub fn drain(&mut self) -> Drain {
+        let inner_map = mem::replace(&mut self.inner_map, Default::default());
+        self.seqs.clear();
+        self.used_bytes = 0;
+        self.labels.clear();
+        Drain {
+            iter: inner_map.into_iter(),
+            _map: PhantomData
+        }
+    }
+
+    /// Removes all headers with given name and returns them in insertion order.
+    ///
+    /// This is like `remove` but returns the removed headers instead of
+    /// only if there had been any. All other headers keep their order.
+    pub fn drain_name<H: HasHeaderName>(&mut self, name: H) -> Drain {
+        let name = name.get_name();
+        let inner_map = mem::replace(&mut self.inner_map, Default::default());
+        let seqs = mem::replace(&mut self.seqs, Vec::new());
+
+        let mut drained = Vec::new();
+        for ((other, obj), seq) in inner_map.into_iter().zip(seqs) {
+            if other == name {
+                drained.push((other, obj));
+                self.labels.remove(&seq);
+            } else {
+                self.inner_map.add(other, obj);
+                self.seqs.push(seq);
+            }
+        }
+        if self.limits.max_bytes.is_some() {
+            self.used_bytes = total_bytes(self);
+        }
+
+        Drain {
+            iter: drained.into_iter(),
+            _map: PhantomData
+        }
+    }
+}
+
+impl<'a> Iterator for Drain<'a> {
+    type Item = (HeaderName, Box<HeaderObj>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for Drain<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back()
+    }
+}
+
+impl<'a> ExactSizeIterator for Drain<'a> {
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderKind;
+    use ::headers::*;
+    use ::map::MapLimits;
+    use super::*;
+
+    fn names(map: &HeaderMap) -> Vec<&'static str> {
+        map.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    test!(drain_empties_map {
+        let mut map = headers! {
+            Comments: "a",
+            _From: ["me@example.com"],
+            Comments: "b"
+        }?;
+
+        let drained = map.drain().collect::<Vec<_>>();
+        assert_eq!(map.len(), 0);
+        let names = drained.iter().map(|&(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Comments", "From", "Comments"]);
+
+        // the drained headers can be inserted into another map without cloning
+        let mut other = HeaderMap::new();
+        for (_, obj) in drained {
+            other.insert_untyped(obj);
+        }
+        assert_eq!(other.len(), 3);
+    });
+
+    test!(drain_name_only_removes_given_name {
+        let mut map = headers! {
+            Comments: "a",
+            _From: ["me@example.com"],
+            Comments: "b",
+            Subject: "hy there"
+        }?;
+
+        let comments = map.drain_name(Comments)
+            .map(|(_, obj)| obj.downcast_ref::<Comments>().unwrap().as_str().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(comments, vec!["a", "b"]);
+        assert_eq!(names(&map), vec!["From", "Subject"]);
+        assert!(!map.contains(Comments));
+
+        assert_eq!(map.drain_name(Comments).len(), 0);
+    });
+
+    test!(drain_name_frees_limited_bytes {
+        let mut map = HeaderMap::with_limits(MapLimits {
+            max_bytes: Some(40),
+            ..Default::default()
+        });
+        map.try_insert(Comments::auto_body("some comment")?)?;
+        assert_err!(map.try_insert(Comments::auto_body("some comment")?));
+
+        assert_eq!(map.drain_name(Comments).len(), 1);
+        map.try_insert(Comments::auto_body("some comment")?)?;
+    });
+}
@@ -0,0 +1,324 @@
+use failure::Fail;
+
+use internals::MailType;
+use internals::error::{EncodingError, EncodingErrorKind};
+use internals::encoder::EncodingBuffer;
+
+use ::name::HeaderName;
+use ::error::HeaderTooLong;
+
+use super::HeaderMap;
+use super::injection_guard::{InjectionGuard, encode_guarded};
+
+/// A header which can not be encoded, see `HeaderMap::can_encode`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EncodeIssue {
+    /// The name of the header.
+    pub name: HeaderName,
+
+    /// Why the header can not be encoded.
+    pub kind: EncodeIssueKind
+}
+
+/// Why a header can not be encoded, see `EncodeIssue`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EncodeIssueKind {
+    /// A max-one header is longer than `MAX_ONE_HEADER_LEN` (see `HeaderTooLong`).
+    TooLong(HeaderTooLong),
+
+    /// Encoding the header failed for any other reason, e.g. a non us-ascii
+    /// local part in a ascii mail.
+    Failed { message: String }
+}
+
+impl HeaderMap {
+
+    /// Validates the map and writes the whole header block into given buffer.
+    ///
+    /// First `use_contextual_validators` is run, then each header is written
+    /// as `Name: <encoded body>\r\n` in map order (folding the bodies where
+    /// they contain FWS marks if a line would be too long). No blank line is
+    /// appended after the last header.
+    ///
+    /// # Error
+    ///
+    /// Fails if the map is not valid (the cause of the error is the
+    /// `HeaderValidationError`) or if any header can not be encoded. In the
+    /// later case the buffer still contains the headers written before the
+    /// failing header.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # extern crate mail_internals;
+    /// # fn main() {
+    /// use mail_internals::MailType;
+    /// use mail_internals::encoder::EncodingBuffer;
+    /// use mail_headers::headers::*;
+    ///
+    /// let map = headers! {
+    ///     _From: ["me@example.com"],
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// let mut buffer = EncodingBuffer::new(MailType::Ascii);
+    /// map.encode(&mut buffer).unwrap();
+    /// assert_eq!(buffer.as_slice(), &b"From: <me@example.com>\r\nSubject: hy there\r\n"[..]);
+    /// # }
+    /// ```
+    pub fn encode(&self, buffer: &mut EncodingBuffer) -> Result<(), EncodingError> {
+        self.use_contextual_validators()
+            .map_err(|err| EncodingError::from(
+                err.context(EncodingErrorKind::Other { kind: "HeaderValidationError" })))?;
+
+        self.encode_unchecked(buffer)
+    }
+
+    /// Like `encode` but does not run the contextual validators.
+    pub fn encode_unchecked(&self, buffer: &mut EncodingBuffer) -> Result<(), EncodingError> {
+        encode_guarded(self, buffer, InjectionGuard::Disabled)
+    }
+
+    /// Validates and encodes the map into a string, see `encode`.
+    ///
+    /// This is mainly meant for tests and debugging.
+    pub fn to_string(&self, mail_type: MailType) -> Result<String, EncodingError> {
+        let mut buffer = EncodingBuffer::new(mail_type);
+        self.encode(&mut buffer)?;
+        let block = String::from_utf8(buffer.as_slice().to_owned())
+            .expect("[BUG] the encoder only writes utf-8");
+        Ok(block)
+    }
+
+    /// Checks if all headers can be encoded for given mail type without encoding the map.
+    ///
+    /// This does not run the contextual validators. The encoded values are
+    /// cached (see `HeaderObjTrait::encoded_value`), so encoding the map
+    /// afterwards does not encode the headers again.
+    ///
+    /// # Error
+    ///
+    /// Returns all headers which can not be encoded. Max-one headers which
+    /// are too long are reported with `EncodeIssueKind::TooLong`.
+    pub fn can_encode(&self, mail_type: MailType) -> Result<(), Vec<EncodeIssue>> {
+        let mut issues = Vec::new();
+        for (name, obj) in self.iter() {
+            if let Err(err) = obj.encoded_value(mail_type) {
+                let kind = match HeaderTooLong::find_in(&err) {
+                    Some(too_long) => EncodeIssueKind::TooLong(*too_long),
+                    None => EncodeIssueKind::Failed { message: err.to_string() }
+                };
+                issues.push(EncodeIssue { name, kind });
+            }
+        }
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+
+    /// Lists all headers whose encoded header line is longer than `limit` bytes.
+    ///
+    /// The length is the length of the header line (including name and final
+    /// CRLF) when encoded for a ascii mail, which is the longest encoding. Max-one
+    /// headers longer than `MAX_ONE_HEADER_LEN` are always listed (with that
+    /// limit) as they can not be encoded at all. This allows finding (and
+    /// fixing) such headers before encoding the map.
+    pub fn oversized_headers(&self, limit: usize) -> Vec<HeaderTooLong> {
+        let mut oversized = Vec::new();
+        for (name, obj) in self.iter() {
+            match obj.encoded_value(MailType::Ascii) {
+                Ok(value) => {
+                    let encoded_len = name.as_wire_str().len() + value.len() + 4;
+                    if encoded_len > limit {
+                        oversized.push(HeaderTooLong::new(name, encoded_len, limit));
+                    }
+                },
+                Err(err) => {
+                    if let Some(too_long) = HeaderTooLong::find_in(&err) {
+                        oversized.push(*too_long);
+                    }
+                }
+            }
+        }
+        oversized
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use vec1::Vec1;
+
+    use internals::MailType;
+    use internals::error::EncodingError;
+    use internals::encoder::EncodingBuffer;
+
+    use ::{HeaderKind, HeaderTryFrom, MAX_ONE_HEADER_LEN};
+    use ::error::HeaderTooLong;
+    use ::headers::*;
+    use ::header_components::{MessageId, MessageIdList};
+    use ::map::{MboxOptions, InjectionGuard, EncodeIssueKind};
+    use ::HeaderMap;
+
+    test!(encode_writes_the_header_block {
+        let map = headers! {
+            _From: ["me@example.com"],
+            _To: ["a@example.com", "b@example.com"],
+            Subject: "hy there"
+        }?;
+
+        let mut buffer = EncodingBuffer::new(MailType::Ascii);
+        map.encode(&mut buffer)?;
+        assert_eq!(buffer.as_slice(), &map.encode_checked(MailType::Ascii)?[..]);
+        assert_eq!(
+            map.to_string(MailType::Ascii)?,
+            "From: <me@example.com>\r\nTo: <a@example.com>, <b@example.com>\r\nSubject: hy there\r\n"
+        );
+    });
+
+    test!(encode_appends_to_the_buffer {
+        let map = headers! {
+            Subject: "hy there"
+        }?;
+
+        let mut buffer = EncodingBuffer::new(MailType::Ascii);
+        map.encode(&mut buffer)?;
+        map.encode(&mut buffer)?;
+        assert_eq!(buffer.as_slice(), &b"Subject: hy there\r\nSubject: hy there\r\n"[..]);
+    });
+
+    test!(encode_folds_long_headers {
+        let map = headers! {
+            Subject: "a subject with a lot of words so that it needs to be folded to not exceed the line limit"
+        }?;
+
+        let block = map.to_string(MailType::Ascii)?;
+        assert!(block.contains("\r\n "));
+        assert!(block.lines().all(|line| line.len() <= 78));
+        assert!(block.ends_with("\r\n"));
+    });
+
+    test!(encode_runs_the_contextual_validators {
+        let map = headers! {
+            _From: ["a@example.com", "b@example.com"],
+            Subject: "hy there"
+        }?;
+
+        assert_err!(map.use_contextual_validators());
+        assert_err!(map.encode(&mut EncodingBuffer::new(MailType::Ascii)));
+        assert_err!(map.to_string(MailType::Ascii));
+
+        let mut buffer = EncodingBuffer::new(MailType::Ascii);
+        map.encode_unchecked(&mut buffer)?;
+        assert_eq!(buffer.as_slice(), &map.encode_checked(MailType::Ascii)?[..]);
+    });
+
+    test!(empty_map_encodes_to_nothing {
+        assert_eq!(HeaderMap::new().to_string(MailType::Ascii)?, "");
+    });
+
+    fn long_references() -> HeaderMap {
+        // ~12 KiB of message ids
+        let ids = (0..400)
+            .map(|idx| MessageId::try_from(format!("msg{:04}.abcdef@example.com", idx)).unwrap())
+            .collect::<Vec<_>>();
+        let mut map = HeaderMap::new();
+        map.insert(References::body(MessageIdList(Vec1::from_vec(ids).unwrap())));
+        map
+    }
+
+    fn assert_too_long(err: &EncodingError) {
+        let too_long = HeaderTooLong::find_in(err).expect("HeaderTooLong cause");
+        assert_eq!(too_long.name(), References::name());
+        assert_eq!(too_long.limit(), MAX_ONE_HEADER_LEN);
+        assert!(too_long.encoded_len() > 12_000);
+    }
+
+    test!(too_long_max_one_header_fails_with_specific_error {
+        let map = long_references();
+        let err = assert_err!(map.try_display(MailType::Ascii));
+        assert_too_long(&err);
+        let too_long = HeaderTooLong::find_in(&err).unwrap();
+        assert!(too_long.to_string().contains("References"));
+    });
+
+    test!(too_long_max_one_header_fails_on_all_encode_paths {
+        let map = long_references();
+
+        assert_too_long(&assert_err!(map.encode(&mut EncodingBuffer::new(MailType::Ascii))));
+        assert_too_long(&assert_err!(map.encode_unchecked(&mut EncodingBuffer::new(MailType::Ascii))));
+        assert_too_long(&assert_err!(map.to_string(MailType::Ascii)));
+        assert_too_long(&assert_err!(map.encode_checked(MailType::Ascii)));
+        assert_too_long(&assert_err!(map.encode_with_guard(MailType::Ascii, InjectionGuard::Disabled)));
+        assert_too_long(&assert_err!(map.encode_for_mbox(MailType::Ascii, MboxOptions::default())));
+
+        // nothing of the header is written into the buffer
+        let mut buffer = EncodingBuffer::new(MailType::Ascii);
+        assert_err!(map.encode_unchecked(&mut buffer));
+        assert!(buffer.as_slice().is_empty());
+    });
+
+    #[cfg(feature="test-helpers")]
+    #[test]
+    fn too_long_max_one_header_fails_in_test_helpers() {
+        use ::testing::encode_header_line;
+
+        let map = long_references();
+        let (name, obj) = map.iter().next().unwrap();
+        assert_too_long(&assert_err!(encode_header_line(name, obj, MailType::Internationalized)));
+    }
+
+    test!(can_encode_reports_too_long_headers {
+        let mut map = long_references();
+        map.insert(_From::auto_body(["thé@example.com"])?);
+        map.insert(Subject::auto_body("hy there")?);
+
+        let issues = assert_err!(map.can_encode(MailType::Ascii));
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[0].name, References::name());
+        match issues[0].kind {
+            EncodeIssueKind::TooLong(too_long) => assert!(too_long.encoded_len() > 12_000),
+            ref other => panic!("unexpected issue: {:?}", other)
+        }
+        assert_eq!(issues[1].name, _From::name());
+        match issues[1].kind {
+            EncodeIssueKind::Failed { .. } => {},
+            ref other => panic!("unexpected issue: {:?}", other)
+        }
+
+        let issues = assert_err!(map.can_encode(MailType::Internationalized));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].name, References::name());
+
+        assert_ok!(headers! { Subject: "hy there" }?.can_encode(MailType::Ascii));
+    });
+
+    test!(oversized_headers_are_listed_beforehand {
+        let map = long_references();
+        let oversized = map.oversized_headers(MAX_ONE_HEADER_LEN);
+        assert_eq!(oversized.len(), 1);
+        assert_eq!(oversized[0].name(), References::name());
+        assert!(oversized[0].encoded_len() > 12_000);
+    });
+
+    test!(foldable_long_to_is_not_oversized {
+        let addresses = (0..40)
+            .map(|idx| format!("recipient{}@example.com", idx))
+            .collect::<Vec<_>>();
+        let mut map = HeaderMap::new();
+        map.insert(_To::auto_body(addresses)?);
+        map.insert(Subject::auto_body("hy there")?);
+
+        assert!(map.oversized_headers(MAX_ONE_HEADER_LEN).is_empty());
+        assert_ok!(map.can_encode(MailType::Ascii));
+        let text = map.to_string(MailType::Ascii)?;
+        assert!(text.len() > 998);
+
+        let small = map.oversized_headers(100);
+        assert_eq!(small.len(), 1);
+        assert_eq!(small[0].name(), _To::name());
+    });
+}
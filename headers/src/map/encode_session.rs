@@ -0,0 +1,246 @@
+use internals::MailType;
+use internals::error::EncodingError;
+
+use super::HeaderMap;
+use super::injection_guard::{InjectionGuard, injection_error};
+
+/// Encodes many header maps reusing the same output buffer.
+///
+/// Encoding a map with `encode_with_guard` allocates a new buffer for each
+/// map. A session instead keeps its buffer between calls (clearing but not
+/// shrinking it), which e.g. amortizes the allocations when rendering the
+/// headers of a large number of mails for a campaign.
+///
+/// Each header line is assembled from the (cached) `encoded_value` of the
+/// header, so the output is byte-identical to `encode_with_guard` with the
+/// same mail type and guard. The only difference is that like
+/// `encoded_value` a session fails for max one headers which are longer
+/// than `MAX_ONE_HEADER_LEN`.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate mail_headers;
+/// # extern crate mail_internals;
+/// # fn main() {
+/// use mail_internals::MailType;
+/// use mail_headers::headers::*;
+/// use mail_headers::map::EncodeSession;
+///
+/// let mut session = EncodeSession::new(MailType::Ascii);
+/// for recipient in &["a@example.com", "b@example.com"] {
+///     let map = headers! {
+///         _To: [*recipient],
+///         Subject: "hy there"
+///     }.unwrap();
+///
+///     let block = session.encode_map(&map).unwrap();
+///     assert_eq!(block, &map.encode_checked(MailType::Ascii).unwrap()[..]);
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct EncodeSession {
+    mail_type: MailType,
+    guard: InjectionGuard,
+    buffer: Vec<u8>
+}
+
+impl EncodeSession {
+
+    /// Creates a new session for given mail type with the injection guard disabled.
+    pub fn new(mail_type: MailType) -> Self {
+        EncodeSession::with_guard(mail_type, InjectionGuard::Disabled)
+    }
+
+    /// Creates a new session for given mail type checking each header with given guard.
+    pub fn with_guard(mail_type: MailType, guard: InjectionGuard) -> Self {
+        EncodeSession {
+            mail_type,
+            guard,
+            buffer: Vec::new()
+        }
+    }
+
+    /// Returns the mail type used by this session.
+    pub fn mail_type(&self) -> MailType {
+        self.mail_type
+    }
+
+    /// Encodes the header block of given map into the session's buffer.
+    ///
+    /// The returned slice is only valid until the next call, use
+    /// `encode_map_to` to write into a buffer owned by the caller.
+    ///
+    /// # Error
+    ///
+    /// Fails like `HeaderMap::encode_with_guard`. A failed call does not
+    /// affect later calls.
+    pub fn encode_map(&mut self, map: &HeaderMap) -> Result<&[u8], EncodingError> {
+        self.buffer.clear();
+        if let Err(err) = encode_lines(map, self.mail_type, self.guard, &mut self.buffer) {
+            self.buffer.clear();
+            return Err(err);
+        }
+        Ok(&self.buffer)
+    }
+
+    /// Appends the header block of given map to `out`.
+    ///
+    /// If encoding fails `out` is truncated to its original length.
+    pub fn encode_map_to(&self, map: &HeaderMap, out: &mut Vec<u8>) -> Result<(), EncodingError> {
+        let start = out.len();
+        let res = encode_lines(map, self.mail_type, self.guard, out);
+        if res.is_err() {
+            out.truncate(start);
+        }
+        res
+    }
+}
+
+fn encode_lines(map: &HeaderMap, mail_type: MailType, guard: InjectionGuard, out: &mut Vec<u8>)
+    -> Result<(), EncodingError>
+{
+    for (name, obj) in map.iter() {
+        let start = out.len();
+        let value = obj.encoded_value(mail_type)?;
+        out.extend_from_slice(name.as_wire_str().as_bytes());
+        out.push(b':');
+        // the space after the colon is a fws, so it is missing if the encoder folded there
+        if !value.starts_with(b"\r\n") {
+            out.push(b' ');
+        }
+        out.extend_from_slice(&value);
+        out.extend_from_slice(b"\r\n");
+        guard.check(name, &out[start..])
+            .map_err(injection_error)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use internals::encoder::{EncodableInHeader, EncodingWriter};
+    use internals::error::EncodingErrorKind;
+
+    use ::HeaderKind;
+    use ::headers::*;
+    use ::error::HeaderInjectionDetected;
+    use super::*;
+
+    /// Writes the text without any checks, fails to encode if there is no text.
+    #[derive(Debug, Clone)]
+    pub struct Unchecked(Option<&'static str>);
+
+    impl EncodableInHeader for Unchecked {
+        fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+            match self.0 {
+                Some(text) => {
+                    handle.write_str_unchecked(text)?;
+                    Ok(())
+                },
+                None => Err(EncodingError::from(
+                    EncodingErrorKind::Other { kind: "no text" }))
+            }
+        }
+
+        fn boxed_clone(&self) -> Box<EncodableInHeader> {
+            Box::new(self.clone())
+        }
+    }
+
+    mod unchecked_headers {
+        def_headers! {
+            test_name: validate_header_names,
+            scope: super,
+            XUnchecked, unchecked { "X-Unchecked" }, Unchecked, multi, None
+        }
+    }
+    use self::unchecked_headers::XUnchecked;
+
+    fn small_map() -> HeaderMap {
+        headers! {
+            _From: ["me@example.com"],
+            Subject: "hy there"
+        }.unwrap()
+    }
+
+    fn large_map() -> HeaderMap {
+        let mut map = headers! {
+            _From: [("Jörg Müller", "jm@bücher.example")],
+            _To: ["a@example.com", "b@example.com", "c@example.com"],
+            Subject: "a subject with ünicode and a lot of words so that it needs to be folded",
+            ContentType: "text/plain; charset=utf-8"
+        }.unwrap();
+        for idx in 0..20 {
+            map.insert(Comments::auto_body(format!("comment number {}", idx)).unwrap());
+        }
+        map
+    }
+
+    fn failing_map() -> HeaderMap {
+        let mut map = small_map();
+        map.insert(XUnchecked::body(Unchecked(None)));
+        map
+    }
+
+    fn standalone(map: &HeaderMap, mail_type: MailType) -> Vec<u8> {
+        map.encode_with_guard(mail_type, InjectionGuard::Disabled).unwrap()
+    }
+
+    test!(output_equals_standalone_encoding {
+        for &mail_type in &[MailType::Ascii, MailType::Internationalized] {
+            let mut session = EncodeSession::new(mail_type);
+            for map in vec![small_map(), large_map(), HeaderMap::new()] {
+                let expected = standalone(&map, mail_type);
+                assert_eq!(session.encode_map(&map)?, &expected[..]);
+
+                let mut out = b"prefix".to_vec();
+                session.encode_map_to(&map, &mut out)?;
+                assert_eq!(&out[..6], b"prefix");
+                assert_eq!(&out[6..], &expected[..]);
+            }
+        }
+    });
+
+    test!(buffer_is_reused_for_growing_and_shrinking_maps {
+        let mut session = EncodeSession::new(MailType::Ascii);
+        let small = small_map();
+        let large = large_map();
+
+        for map in &[&small, &large, &small, &small, &large] {
+            let expected = standalone(map, MailType::Ascii);
+            assert_eq!(session.encode_map(map)?, &expected[..]);
+        }
+        assert!(session.buffer.capacity() >= standalone(&large, MailType::Ascii).len());
+    });
+
+    test!(failed_map_does_not_poison_the_session {
+        let mut session = EncodeSession::new(MailType::Ascii);
+        let small = small_map();
+        let large = large_map();
+
+        session.encode_map(&large)?;
+        assert_err!(session.encode_map(&failing_map()));
+        assert_eq!(session.encode_map(&small)?, &standalone(&small, MailType::Ascii)[..]);
+
+        let mut out = b"prefix".to_vec();
+        assert_err!(session.encode_map_to(&failing_map(), &mut out));
+        assert_eq!(out, b"prefix".to_vec());
+        session.encode_map_to(&small, &mut out)?;
+        assert_eq!(&out[6..], &standalone(&small, MailType::Ascii)[..]);
+    });
+
+    test!(guard_is_applied {
+        let mut map = small_map();
+        map.insert(XUnchecked::body(Unchecked(Some("x\r\nBcc: evil"))));
+
+        let mut session = EncodeSession::with_guard(MailType::Ascii, InjectionGuard::Enabled);
+        let err = assert_err!(session.encode_map(&map));
+        assert_eq!(HeaderInjectionDetected::find_in(&err).unwrap().name(), XUnchecked::name());
+
+        let mut session = EncodeSession::new(MailType::Ascii);
+        assert_eq!(session.encode_map(&map)?, &standalone(&map, MailType::Ascii)[..]);
+    });
+}
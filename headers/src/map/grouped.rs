@@ -0,0 +1,305 @@
+use std::vec;
+
+use internals::MailType;
+use internals::error::EncodingError;
+
+use ::name::HeaderName;
+use ::header::HeaderObj;
+
+use super::{HeaderMap, Iter, Names, UntypedBodies};
+
+/// Iterator over the headers of a `HeaderMap` grouped by name, see `HeaderMap::grouped`.
+#[derive(Debug)]
+pub struct Grouped<'a> {
+    map: &'a HeaderMap,
+    /// (name, position of first header, number of headers) in first-appearance order
+    groups: vec::IntoIter<(HeaderName, usize, usize)>
+}
+
+/// Iterator over the headers of a `HeaderMap` grouped by name, see `HeaderMap::iter_grouped`.
+#[derive(Debug)]
+pub struct IterGrouped<'a> {
+    map: &'a HeaderMap,
+    names: Names<'a>
+}
+
+/// Iterator over the bodies of all headers with the same name, see `HeaderMap::grouped`.
+#[derive(Debug)]
+pub struct GroupedValues<'a> {
+    name: HeaderName,
+    iter: Iter<'a>,
+    remaining: usize
+}
+
+impl HeaderMap {
+
+    /// Returns a iterator over the headers grouped by their name.
+    ///
+    /// The names are returned in the order in which they first appear in the
+    /// map, the bodies of each name in the order in which they appear in the
+    /// map. This is e.g. useful for templates rendering a "Received (3)"
+    /// heading followed by each value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    ///
+    /// let map = headers! {
+    ///     Comments: "a",
+    ///     Subject: "hy there",
+    ///     Comments: "b"
+    /// }.unwrap();
+    ///
+    /// let grouped = map.grouped()
+    ///     .map(|(name, values)| (name.as_str(), values.len()))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(grouped, vec![("Comments", 2), ("Subject", 1)]);
+    /// # }
+    /// ```
+    pub fn grouped(&self) -> Grouped {
+        let mut groups: Vec<(HeaderName, usize, usize)> = Vec::new();
+        for (idx, (name, _)) in self.iter().enumerate() {
+            match groups.iter_mut().find(|group| group.0 == name) {
+                Some(group) => group.2 += 1,
+                None => groups.push((name, idx, 1))
+            }
+        }
+        Grouped {
+            map: self,
+            groups: groups.into_iter()
+        }
+    }
+}
+
+impl HeaderMap {
+
+    /// Returns a iterator over the header names and the bodies for each name.
+    ///
+    /// Like `grouped` the names are returned in the order in which they first
+    /// appear in the map and the bodies in the order in which they appear in
+    /// the map. But instead of precomputing all groups this looks up the
+    /// bodies of each name lazily in the inner multimap, returning the same
+    /// `UntypedBodies` as `get_untyped`. This is e.g. useful when encoding
+    /// all `Comments` together.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    ///
+    /// let map = headers! {
+    ///     Comments: "a",
+    ///     Subject: "hy there",
+    ///     Comments: "b"
+    /// }.unwrap();
+    ///
+    /// let grouped = map.iter_grouped()
+    ///     .map(|(name, bodies)| (name.as_str(), bodies.count()))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(grouped, vec![("Comments", 2), ("Subject", 1)]);
+    /// # }
+    /// ```
+    pub fn iter_grouped(&self) -> IterGrouped {
+        IterGrouped {
+            map: self,
+            names: self.names()
+        }
+    }
+}
+
+impl<'a> Iterator for IterGrouped<'a> {
+    type Item = (HeaderName, UntypedBodies<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let map = self.map;
+        self.names.next().map(|name| (name, map.get_untyped(name)))
+    }
+}
+
+impl<'a> Grouped<'a> {
+
+    /// Returns the (remaining) groups with each value decoded for display.
+    ///
+    /// The name is the wire spelling of the header name (e.g. `Message-ID`),
+    /// the values are the header values as they would be encoded in a
+    /// internationalized mail, i.e. without encoded words and unfolded.
+    ///
+    /// # Error
+    ///
+    /// Fails if any of the values can not be encoded.
+    pub fn to_pairs_decoded(self) -> Result<Vec<(String, Vec<String>)>, EncodingError> {
+        let mut pairs = Vec::with_capacity(self.len());
+        for (name, values) in self {
+            let values = values
+                .map(display_decoded)
+                .collect::<Result<Vec<_>, _>>()?;
+            pairs.push((name.as_wire_str().to_owned(), values));
+        }
+        Ok(pairs)
+    }
+}
+
+fn display_decoded(obj: &HeaderObj) -> Result<String, EncodingError> {
+    let value = obj.encoded_value(MailType::Internationalized)?;
+    let value = String::from_utf8_lossy(&value);
+    // unfold, i.e. remove the CRLF of folded lines
+    Ok(value.replace("\r\n", ""))
+}
+
+impl<'a> Iterator for Grouped<'a> {
+    type Item = (HeaderName, GroupedValues<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let map = self.map;
+        self.groups.next().map(|(name, first, count)| {
+            let mut iter = map.iter();
+            if first > 0 {
+                iter.nth(first - 1);
+            }
+            (name, GroupedValues { name, iter, remaining: count })
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.groups.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for Grouped<'a> {
+    fn len(&self) -> usize {
+        self.groups.len()
+    }
+}
+
+impl<'a> Iterator for GroupedValues<'a> {
+    type Item = &'a HeaderObj;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let name = self.name;
+        let found = self.iter.find(|&(other, _)| other == name);
+        let (_, obj) = found.expect("[BUG] less headers with name in map then counted");
+        self.remaining -= 1;
+        Some(&**obj)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a> ExactSizeIterator for GroupedValues<'a> {
+    fn len(&self) -> usize {
+        self.remaining
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderKind;
+    use ::headers::*;
+    use super::*;
+
+    test!(groups_in_first_appearance_order {
+        let map = headers! {
+            Comments: "a",
+            _From: ["me@example.com"],
+            Comments: "b",
+            Subject: "hy there",
+            _To: ["you@example.com"],
+            Comments: "c"
+        }?;
+
+        let names = map.grouped()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![Comments::name(), _From::name(), Subject::name(), _To::name()]);
+    });
+
+    test!(values_are_in_insertion_order {
+        let map = headers! {
+            Comments: "a",
+            Subject: "hy there",
+            Comments: "b",
+            Comments: "c"
+        }?;
+
+        let (name, values) = map.grouped().next().unwrap();
+        assert_eq!(name, Comments::name());
+        let values = values
+            .map(|obj| obj.downcast_ref::<Comments>().unwrap().as_str().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec!["a", "b", "c"]);
+    });
+
+    test!(counts {
+        let map = headers! {
+            Comments: "a",
+            Subject: "hy there",
+            Comments: "b"
+        }?;
+
+        let mut grouped = map.grouped();
+        assert_eq!(grouped.len(), 2);
+        let (_, comments) = grouped.next().unwrap();
+        assert_eq!(comments.len(), 2);
+        assert_eq!(grouped.len(), 1);
+        let (_, subjects) = grouped.next().unwrap();
+        assert_eq!(subjects.len(), 1);
+        assert!(grouped.next().is_none());
+
+        assert_eq!(HeaderMap::new().grouped().len(), 0);
+    });
+
+    test!(iter_grouped_keeps_orders {
+        let map = headers! {
+            Comments: "a",
+            _From: ["me@example.com"],
+            Comments: "b",
+            Subject: "hy there",
+            Comments: "c"
+        }?;
+
+        let groups = map.iter_grouped()
+            .map(|(name, bodies)| {
+                let texts = bodies
+                    .filter_map(|obj| obj.downcast_ref::<Comments>())
+                    .map(|comment| comment.as_str().to_owned())
+                    .collect::<Vec<_>>();
+                (name, texts)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(groups, vec![
+            (Comments::name(), vec!["a".to_owned(), "b".to_owned(), "c".to_owned()]),
+            (_From::name(), vec![]),
+            (Subject::name(), vec![])
+        ]);
+        assert_eq!(HeaderMap::new().iter_grouped().count(), 0);
+    });
+
+    test!(pairs_are_decoded {
+        let map = headers! {
+            Subject: "hö there",
+            Comments: "a",
+            Comments: "b"
+        }?;
+        let encoded = map.display(MailType::Ascii).to_string();
+        assert!(encoded.contains("=?utf8?"));
+
+        let pairs = map.grouped().to_pairs_decoded()?;
+        assert_eq!(pairs, vec![
+            ("Subject".to_owned(), vec!["hö there".to_owned()]),
+            ("Comments".to_owned(), vec!["a".to_owned(), "b".to_owned()])
+        ]);
+    });
+}
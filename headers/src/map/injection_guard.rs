@@ -0,0 +1,289 @@
+use failure::Fail;
+
+use internals::MailType;
+use internals::error::{EncodingError, EncodingErrorKind};
+use internals::encoder::EncodingBuffer;
+
+use ::name::HeaderName;
+use ::error::HeaderInjectionDetected;
+
+use super::HeaderMap;
+use super::mbox::encode_header_line;
+
+/// Whether the encoded output of each header is checked for injected line breaks.
+///
+/// Components are expected to only write line breaks through the encoder's
+/// folding, but a buggy component (e.g. misusing `write_str_unchecked`) could
+/// write a CRLF followed by arbitrary text and by this inject a header.
+/// With the guard enabled the bytes written for each header are scanned
+/// (once, after the header was written) and every CR has to be part of a
+/// CRLF which is followed by whitespace (i.e. is a legal fold) or which ends
+/// the header. A LF not preceded by a CR is rejected, too.
+///
+/// The guard is enabled by `HeaderMap::encode_checked`, other encoding
+/// methods (e.g. `encode_for_mbox`) can opt-in to it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum InjectionGuard {
+    /// Trust the components to only write legal folds.
+    Disabled,
+    /// Fail with `HeaderInjectionDetected` if a header contains a illegal line break.
+    Enabled
+}
+
+impl Default for InjectionGuard {
+    /// Disabled, as the built-in components never write line breaks themselves.
+    fn default() -> Self {
+        InjectionGuard::Disabled
+    }
+}
+
+impl InjectionGuard {
+
+    /// Checks the encoded header line (including the final CRLF) written for given header.
+    ///
+    /// Always succeeds if the guard is disabled.
+    pub fn check(self, name: HeaderName, line: &[u8]) -> Result<(), HeaderInjectionDetected> {
+        if self == InjectionGuard::Disabled {
+            return Ok(());
+        }
+        let mut idx = 0;
+        while idx < line.len() {
+            match line[idx] {
+                b'\r' => {
+                    let is_crlf = line.get(idx + 1) == Some(&b'\n');
+                    let ends_header = idx + 2 == line.len();
+                    let is_fold = match line.get(idx + 2) {
+                        Some(&b' ') | Some(&b'\t') => true,
+                        _ => false
+                    };
+                    if !is_crlf || !(ends_header || is_fold) {
+                        return Err(HeaderInjectionDetected::new(name, idx));
+                    }
+                    // skip the LF
+                    idx += 2;
+                },
+                b'\n' => return Err(HeaderInjectionDetected::new(name, idx)),
+                _ => idx += 1
+            }
+        }
+        Ok(())
+    }
+}
+
+impl HeaderMap {
+
+    /// Encodes the header block of this map, checking each header for injected line breaks.
+    ///
+    /// This is the same as `encode_with_guard(mail_type, InjectionGuard::Enabled)`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # extern crate mail_internals;
+    /// # fn main() {
+    /// use mail_internals::MailType;
+    /// use mail_headers::headers::*;
+    ///
+    /// let map = headers! {
+    ///     _From: ["me@example.com"],
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// let block = map.encode_checked(MailType::Ascii).unwrap();
+    /// assert_eq!(block, b"From: <me@example.com>\r\nSubject: hy there\r\n".to_vec());
+    /// # }
+    /// ```
+    pub fn encode_checked(&self, mail_type: MailType) -> Result<Vec<u8>, EncodingError> {
+        self.encode_with_guard(mail_type, InjectionGuard::Enabled)
+    }
+
+    /// Encodes the header block of this map using given injection guard.
+    ///
+    /// Headers are encoded in map order, each line ends with CRLF. No blank
+    /// line is appended after the last header.
+    ///
+    /// # Error
+    ///
+    /// Fails if any header can not be encoded. If the guard is enabled
+    /// this includes headers with illegal line breaks, in which case the
+    /// cause of the error is a `HeaderInjectionDetected` naming the header.
+    pub fn encode_with_guard(&self, mail_type: MailType, guard: InjectionGuard)
+        -> Result<Vec<u8>, EncodingError>
+    {
+        let mut buffer = EncodingBuffer::new(mail_type);
+        encode_guarded(self, &mut buffer, guard)?;
+        Ok(buffer.as_slice().to_owned())
+    }
+}
+
+/// Encodes all headers of the map into the buffer, checking each one with the guard.
+///
+/// (`pub` so that it can be used by the other `map` submodules.)
+pub fn encode_guarded(map: &HeaderMap, buffer: &mut EncodingBuffer, guard: InjectionGuard)
+    -> Result<(), EncodingError>
+{
+    for (name, obj) in map.iter() {
+        let start = buffer.as_slice().len();
+        {
+            let mut handle = buffer.writer();
+            match encode_header_line(&mut handle, name, obj) {
+                Ok(()) => handle.finish_header(),
+                Err(err) => {
+                    handle.undo_header();
+                    return Err(err);
+                }
+            }
+        }
+        guard.check(name, &buffer.as_slice()[start..])
+            .map_err(injection_error)?;
+    }
+    Ok(())
+}
+
+/// Wraps a detected injection into an `EncodingError` (see `HeaderInjectionDetected::find_in`).
+pub fn injection_error(detected: HeaderInjectionDetected) -> EncodingError {
+    EncodingError::from(
+        detected.context(EncodingErrorKind::Other { kind: "HeaderInjectionDetected" }))
+}
+
+#[cfg(test)]
+mod test {
+    use internals::encoder::{EncodableInHeader, EncodingWriter};
+
+    use ::{HeaderKind, HeaderTryFrom};
+    use ::header_components::{self as components, MessageId, MessageIdList};
+    use ::headers::*;
+    use ::map::MboxOptions;
+    use super::*;
+
+    /// A component which (wrongly) writes its text without any checks.
+    #[derive(Debug, Clone)]
+    pub struct Smuggler(&'static str);
+
+    impl EncodableInHeader for Smuggler {
+        fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+            handle.write_str_unchecked(self.0)?;
+            Ok(())
+        }
+
+        fn boxed_clone(&self) -> Box<EncodableInHeader> {
+            Box::new(self.clone())
+        }
+    }
+
+    mod smuggler_headers {
+        def_headers! {
+            test_name: validate_header_names,
+            scope: super,
+            XSmuggled, unchecked { "X-Smuggled" }, Smuggler, multi, None
+        }
+    }
+    use self::smuggler_headers::XSmuggled;
+
+    fn smuggling_map(text: &'static str) -> HeaderMap {
+        let mut map = headers! {
+            Subject: "hy there"
+        }.unwrap();
+        map.insert(XSmuggled::body(Smuggler(text)));
+        map
+    }
+
+    test!(injected_header_is_detected_and_named {
+        let map = smuggling_map("x\r\nBcc: evil");
+
+        let err = assert_err!(map.encode_checked(MailType::Ascii));
+        let detected = HeaderInjectionDetected::find_in(&err).unwrap();
+        assert_eq!(detected.name(), XSmuggled::name());
+        assert_eq!(detected.position(), "X-Smuggled: x".len());
+    });
+
+    test!(bare_cr_and_lf_are_detected {
+        for &text in &["x\nBcc: evil", "x\rBcc: evil", "x\r\n"] {
+            let map = smuggling_map(text);
+            let err = assert_err!(map.encode_checked(MailType::Ascii));
+            assert!(HeaderInjectionDetected::find_in(&err).is_some(), "{:?}", text);
+        }
+    });
+
+    test!(guard_is_opt_in_elsewhere {
+        let map = smuggling_map("x\r\nBcc: evil");
+
+        let unchecked = map.encode_with_guard(MailType::Ascii, InjectionGuard::Disabled)?;
+        assert!(String::from_utf8(unchecked)?.contains("\r\nBcc: evil"));
+        assert_ok!(map.encode_for_mbox(MailType::Ascii, MboxOptions::default()));
+
+        let opts = MboxOptions { injection_guard: InjectionGuard::Enabled, ..Default::default() };
+        assert_err!(map.encode_for_mbox(MailType::Ascii, opts));
+    });
+
+    test!(legal_folding_is_not_rejected {
+        let map = smuggling_map("x\r\n\tcontinued\r\n more");
+        assert_ok!(map.encode_checked(MailType::Ascii));
+
+        let text = "this is a long subject which will need to be folded at some \
+                    point by the encoder as it is longer than a line should be";
+        let map = headers! {
+            Subject: text
+        }?;
+        let block = String::from_utf8(map.encode_checked(MailType::Ascii)?)?;
+        assert!(block.matches("\r\n").count() > 1, "subject was not folded: {:?}", block);
+    });
+
+    test!(builtin_components_pass_the_guard {
+        let mut map = headers! {
+            Date: components::DateTime::test_time(45),
+            _From: [("Jörg Müller", "jm@bücher.example"), ("Tom", "tom@example.com")],
+            Sender: ("Tom", "tom@example.com"),
+            ReplyTo: ["reply@example.com"],
+            _To: ["you@example.com"],
+            Cc: [("A Very Long Display Name Which Needs Encoding Äh", "cc@example.com")],
+            Bcc: ["secret@example.com"],
+            MessageId: "a@example.com",
+            Subject: "a subject with ünicode and a lot of words so that it needs to be folded",
+            Comments: "some comment",
+            Keywords: ["a", "b", "c"],
+            ContentType: "text/plain; charset=utf-8",
+            ContentTransferEncoding: components::TransferEncoding::QuotedPrintable,
+            ContentDisposition: components::Disposition::attachment()
+        }?;
+        let ids = vec1![
+            MessageId::try_from("b@example.com")?,
+            MessageId::try_from("c@example.com")?
+        ];
+        map.insert(InReplyTo::body(MessageIdList(ids.clone())));
+        map.insert(References::body(MessageIdList(ids)));
+
+        for &mail_type in &[MailType::Ascii, MailType::Mime8BitEnabled, MailType::Internationalized] {
+            assert_ok!(map.encode_checked(mail_type));
+        }
+
+        let raw = headers! {
+            XRaw: "raw text"
+        }?;
+        assert_ok!(raw.encode_checked(MailType::Ascii));
+    });
+
+    mod raw_headers {
+        use ::header_components;
+        def_headers! {
+            test_name: validate_header_names,
+            scope: header_components,
+            XRaw, unchecked { "X-Raw" }, RawUnstructured, multi, None
+        }
+    }
+    use self::raw_headers::XRaw;
+
+    #[test]
+    fn check_scans_each_line_break() {
+        let name = Subject::name();
+        let guard = InjectionGuard::Enabled;
+        assert_ok!(guard.check(name, b"Subject: a\r\n"));
+        assert_ok!(guard.check(name, b"Subject: a\r\n b\r\n\tc\r\n"));
+        assert_ok!(guard.check(name, b"Subject: a"));
+        assert_eq!(assert_err!(guard.check(name, b"Subject: a\r\nb\r\n")).position(), 10);
+        assert_eq!(assert_err!(guard.check(name, b"Subject: a\r\n\r\n")).position(), 10);
+        assert_ok!(InjectionGuard::Disabled.check(name, b"Subject: a\r\nb\r\n"));
+    }
+}
@@ -1,9 +1,12 @@
 use std::vec;
+use std::iter::FromIterator;
 
 use ::HeaderName;
 use ::header::HeaderObj;
+use ::error::LimitViolation;
 
 use super::HeaderMap;
+use super::seq::next_seq;
 
 pub type IntoIter = vec::IntoIter<(HeaderName, Box<HeaderObj>)>;
 
@@ -15,4 +18,317 @@ impl IntoIterator for HeaderMap {
     fn into_iter(self) -> Self::IntoIter {
         self.inner_map.into_iter()
     }
-}
\ No newline at end of file
+}
+
+/// Inserts the headers in iteration order, like `HeaderMap::insert_all`.
+///
+/// The given name is used as the name of the header (not `HeaderObj::name`).
+///
+/// Like with `insert_all` no header is inserted if this would exceed the
+/// limits of the map (see `HeaderMap::take_limit_violation`). Use
+/// `HeaderMap::try_extend` to get the `LimitViolation` directly.
+impl Extend<(HeaderName, Box<HeaderObj>)> for HeaderMap {
+    fn extend<I>(&mut self, iter: I)
+        where I: IntoIterator<Item=(HeaderName, Box<HeaderObj>)>
+    {
+        let res = self.insert_atomically(|map| map.extend_until_violation(iter));
+        self.record_violation(res);
+    }
+}
+
+/// Inserts the headers in iteration order, like `HeaderMap::insert_untyped`.
+///
+/// Like the other `Extend` implementation no header is inserted if this
+/// would exceed the limits of the map.
+impl Extend<Box<HeaderObj>> for HeaderMap {
+    fn extend<I>(&mut self, iter: I)
+        where I: IntoIterator<Item=Box<HeaderObj>>
+    {
+        self.extend(iter.into_iter().map(|obj| (obj.name(), obj)))
+    }
+}
+
+/// Creates a map from the headers in iteration order.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate mail_headers;
+/// # fn main() {
+/// use mail_headers::HeaderMap;
+/// use mail_headers::headers::*;
+///
+/// let map = headers! {
+///     _From: ["me@example.com"],
+///     Comments: "internal",
+///     Subject: "hy there"
+/// }.unwrap();
+///
+/// let map = map.into_iter()
+///     .filter(|&(name, _)| name.as_str() != "Comments")
+///     .collect::<HeaderMap>();
+/// assert_eq!(map.len(), 2);
+/// # }
+/// ```
+impl FromIterator<(HeaderName, Box<HeaderObj>)> for HeaderMap {
+    fn from_iter<I>(iter: I) -> Self
+        where I: IntoIterator<Item=(HeaderName, Box<HeaderObj>)>
+    {
+        let mut map = HeaderMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+/// Creates a map from the headers in iteration order.
+impl FromIterator<Box<HeaderObj>> for HeaderMap {
+    fn from_iter<I>(iter: I) -> Self
+        where I: IntoIterator<Item=Box<HeaderObj>>
+    {
+        let mut map = HeaderMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl HeaderMap {
+
+    /// Like `extend` but fails instead of exceeding the limits of this map.
+    ///
+    /// This behaves like `try_insert_all`: headers are inserted until one
+    /// would exceed a limit (the remaining ones are dropped), or none are
+    /// inserted if the limits have `atomic_insert_all` set. On success the
+    /// number of inserted headers is returned.
+    pub fn try_extend<I>(&mut self, iter: I) -> Result<usize, LimitViolation>
+        where I: IntoIterator<Item=(HeaderName, Box<HeaderObj>)>
+    {
+        if self.limits.atomic_insert_all {
+            self.insert_atomically(|map| map.extend_until_violation(iter))
+        } else {
+            self.extend_until_violation(iter)
+        }
+    }
+
+    /// The non atomic `try_extend`.
+    fn extend_until_violation<I>(&mut self, iter: I) -> Result<usize, LimitViolation>
+        where I: IntoIterator<Item=(HeaderName, Box<HeaderObj>)>
+    {
+        let mut accepted = 0;
+        for (name, obj) in iter {
+            let max_one = obj.is_max_one();
+            self._try_insert_with_seq(name, max_one, obj, next_seq(), accepted)?;
+            accepted += 1;
+        }
+        Ok(accepted)
+    }
+
+    /// Returns all headers as (header name, boxed body) pairs in map order.
+    ///
+    /// The bodies are cloned (using `boxed_clone`), so unlike `into_iter`
+    /// this doesn't consume the map. Together with `from_vec` this is the
+    /// stable interchange format of a map, e.g. for snapshots, so that
+    /// such features don't need to touch the inner multimap.
+    ///
+    /// Note that the limits, validator options and header sequence numbers
+    /// of the map are not part of the returned vector.
+    pub fn to_vec(&self) -> Vec<(HeaderName, Box<HeaderObj>)> {
+        self.iter()
+            .map(|(name, obj)| (name, obj.boxed_clone()))
+            .collect()
+    }
+
+    /// Creates a map from (header name, boxed body) pairs, the inverse of `to_vec`.
+    ///
+    /// All pairs are added in order. Unlike `extend`/`collect` no max one
+    /// header replaces a previous header with the same name, so for any map
+    /// `HeaderMap::from_vec(map.to_vec())` looks up, iterates, validates and
+    /// encodes exactly like `map.clone()` (if the map uses the default
+    /// limits and validator options).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::HeaderMap;
+    /// use mail_headers::headers::*;
+    ///
+    /// let map = headers! {
+    ///     _From: ["me@example.com"],
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// let snapshot = map.to_vec();
+    /// let restored = HeaderMap::from_vec(snapshot);
+    /// assert_eq!(restored.len(), 2);
+    /// assert_eq!(restored.get_single(Subject).unwrap().unwrap().as_str(), "hy there");
+    /// # }
+    /// ```
+    pub fn from_vec(headers: Vec<(HeaderName, Box<HeaderObj>)>) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, obj) in headers {
+            map._insert_with_seq(name, false, obj, next_seq());
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use internals::MailType;
+    use ::HeaderKind;
+    use ::headers::*;
+    use super::*;
+
+    fn names(map: &HeaderMap) -> Vec<&'static str> {
+        map.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    test!(collect_filtered_map {
+        let map = headers! {
+            Comments: "a",
+            _From: ["me@example.com"],
+            Comments: "b",
+            Subject: "hy there"
+        }?;
+
+        let map = map.into_iter()
+            .filter(|&(name, _)| name != Comments::name())
+            .collect::<HeaderMap>();
+        assert_eq!(names(&map), vec!["From", "Subject"]);
+    });
+
+    test!(collect_boxed_headers_keeps_order {
+        let headers: Vec<Box<HeaderObj>> = vec![
+            Box::new(Comments::auto_body("a")?),
+            Box::new(Subject::auto_body("hy there")?),
+            Box::new(Comments::auto_body("b")?)
+        ];
+
+        let map = headers.into_iter().collect::<HeaderMap>();
+        assert_eq!(names(&map), vec!["Comments", "Subject", "Comments"]);
+    });
+
+    mod raw_headers {
+        use ::header_components;
+        def_headers! {
+            test_name: validate_header_names,
+            scope: header_components,
+            Subject, unchecked { "Subject" }, RawUnstructured, multi, None
+        }
+    }
+    use self::raw_headers::Subject as RawSubject;
+
+    /// Maps with different kinds of headers, including some which don't validate.
+    fn corpus() -> Vec<HeaderMap> {
+        let mut mixed = headers! {
+            Subject: "typed",
+            Comments: "a"
+        }.unwrap();
+        mixed.insert(RawSubject::auto_body("raw").unwrap());
+
+        vec![
+            HeaderMap::new(),
+            headers! {
+                _From: [("Jörg Müller", "jm@bücher.example")],
+                _To: ["you@example.com", "other@example.com"],
+                Subject: "hy there",
+                Comments: "a",
+                Comments: "b",
+                ContentType: "text/plain; charset=utf-8"
+            }.unwrap(),
+            headers! {
+                _From: ["a@example.com", "b@example.com"],
+                Subject: "needs a sender"
+            }.unwrap(),
+            headers! {
+                ResentFrom: ["a@example.com"]
+            }.unwrap(),
+            mixed
+        ]
+    }
+
+    test!(from_vec_of_to_vec_behaves_like_clone {
+        for map in corpus() {
+            let cloned = map.clone();
+            let restored = HeaderMap::from_vec(map.to_vec());
+
+            assert_eq!(names(&restored), names(&cloned));
+            assert_eq!(restored.get(Comments).count(), cloned.get(Comments).count());
+            assert_eq!(
+                restored.get_single(Subject).map(|res| res.is_ok()),
+                cloned.get_single(Subject).map(|res| res.is_ok())
+            );
+            assert_eq!(
+                restored.use_contextual_validators().is_ok(),
+                cloned.use_contextual_validators().is_ok()
+            );
+            for &mail_type in &[MailType::Ascii, MailType::Internationalized] {
+                let expected = cloned.encode_checked(mail_type).ok();
+                assert_eq!(restored.encode_checked(mail_type).ok(), expected);
+            }
+        }
+    });
+
+    test!(to_vec_does_not_consume_or_change_the_map {
+        let map = headers! {
+            Comments: "a",
+            Subject: "hy there"
+        }?;
+
+        let snapshot = map.to_vec();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(names(&map), vec!["Comments", "Subject"]);
+        let snapshot_names = snapshot.iter().map(|&(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(snapshot_names, names(&map));
+    });
+
+    test!(from_vec_keeps_max_one_headers_sharing_a_name {
+        let map = corpus().pop().unwrap();
+        assert_eq!(names(&map), vec!["Subject", "Comments", "Subject"]);
+
+        let restored = HeaderMap::from_vec(map.to_vec());
+        assert_eq!(names(&restored), vec!["Subject", "Comments", "Subject"]);
+        assert_eq!(restored.get_untyped(Subject).count(), 2);
+    });
+
+    test!(extend_appends_and_replaces_max_one {
+        let mut map = headers! {
+            Subject: "old",
+            Comments: "a"
+        }?;
+
+        map.extend(headers! {
+            Comments: "b",
+            Subject: "new"
+        }?);
+        assert_eq!(names(&map), vec!["Comments", "Comments", "Subject"]);
+        assert_eq!(map.get_single(Subject).unwrap()?.as_str(), "new");
+
+        map.extend(vec![Box::new(Comments::auto_body("c")?) as Box<HeaderObj>]);
+        assert_eq!(map.get(Comments).count(), 3);
+    });
+
+    test!(try_extend_stops_at_the_limits {
+        use ::map::MapLimits;
+
+        let limits = MapLimits { max_headers: Some(2), ..Default::default() };
+        let mut map = HeaderMap::with_limits(limits);
+        let err = assert_err!(map.try_extend(headers! {
+            Comments: "a",
+            Comments: "b",
+            Comments: "c"
+        }?));
+        assert_eq!(err.accepted(), 2);
+        assert_eq!(map.len(), 2);
+
+        let mut map = HeaderMap::with_limits(MapLimits { atomic_insert_all: true, ..limits });
+        map.insert(Comments::auto_body("a")?);
+        assert_err!(map.try_extend(headers! { Comments: "b", Comments: "c" }?));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.try_extend(headers! { Subject: "hy there" }?)?, 1);
+        assert_eq!(map.len(), 2);
+    });
+}
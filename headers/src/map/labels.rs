@@ -0,0 +1,260 @@
+use std::collections::{HashMap, HashSet};
+
+use ::name::HeaderName;
+use ::header::HeaderObj;
+
+use super::{HeaderMap, HeaderSeq, IterWithSeq};
+
+impl HeaderMap {
+
+    /// Inserts all headers of the other map like `insert_all`, labeling them with `label`.
+    ///
+    /// This is meant for maps which are combined in layers (e.g. a global
+    /// template, a brand template, a campaign template and the headers of
+    /// the mail itself) to find out which layer contributed a header, see
+    /// `label_of` and `iter_with_labels`.
+    ///
+    /// Headers of `other` which already have a label (because `other` was
+    /// itself created with `combine_labeled`) keep their label, all other
+    /// headers of `other` get `label`. Labels are kept by `clone`,
+    /// `insert_all` and `combine_with`. Headers which are replaced (e.g. a
+    /// "max one" header replaced by one from a later layer) lose their
+    /// label together with the header. Labels do not affect encoding and
+    /// are ignored by `eq_by_encoding`.
+    ///
+    /// Like with `insert_all` no header is inserted if this would exceed
+    /// the limits of the map, see `take_limit_violation`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::HeaderMap;
+    /// use mail_headers::headers::*;
+    ///
+    /// let mut map = HeaderMap::new();
+    /// map.combine_labeled(headers! {
+    ///     _From: ["news@example.com"],
+    ///     Subject: "default subject"
+    /// }.unwrap(), "global");
+    /// map.combine_labeled(headers! {
+    ///     Subject: "campaign subject"
+    /// }.unwrap(), "campaign");
+    ///
+    /// let labels = map.iter_with_labels()
+    ///     .map(|(label, name, _)| (name.as_str(), label.unwrap()))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(labels, vec![("From", "global"), ("Subject", "campaign")]);
+    /// # }
+    /// ```
+    pub fn combine_labeled(&mut self, mut other: HeaderMap, label: &'static str) {
+        for &seq in other.seqs.iter() {
+            other.labels.entry(seq).or_insert(label);
+        }
+        self.insert_all(other);
+        // if inserting failed (see `take_limit_violation`) the labels of `other` are dropped with it
+        self.prune_labels();
+    }
+
+    /// Returns the label of the header at given position (in map order).
+    ///
+    /// Returns `None` if there is no header at the position or if the
+    /// header was not added through `combine_labeled`.
+    pub fn label_of(&self, position: usize) -> Option<&'static str> {
+        let seq = self.seq_of(position)?;
+        self.labels.get(&seq).cloned()
+    }
+
+    /// Iterates over all headers in map order together with their labels.
+    ///
+    /// The label is `None` for headers which were not added through `combine_labeled`.
+    pub fn iter_with_labels(&self) -> IterWithLabels {
+        IterWithLabels {
+            inner: self.iter_with_seq(),
+            labels: &self.labels
+        }
+    }
+
+    /// Drops the labels of all headers which are no longer in the map.
+    ///
+    /// Methods which know which headers they removed drop their labels directly.
+    pub(super) fn prune_labels(&mut self) {
+        if self.labels.is_empty() {
+            return;
+        }
+        let seqs = self.seqs.iter().cloned().collect::<HashSet<_>>();
+        self.labels.retain(|seq, _| seqs.contains(seq));
+    }
+
+    /// Copies the label of the header with given sequence number from `labels`.
+    pub(super) fn copy_label(&mut self, labels: &HashMap<HeaderSeq, &'static str>, seq: HeaderSeq) {
+        if let Some(&label) = labels.get(&seq) {
+            self.labels.insert(seq, label);
+        }
+    }
+}
+
+/// Iterator returned by `HeaderMap::iter_with_labels`.
+#[derive(Debug)]
+pub struct IterWithLabels<'a> {
+    inner: IterWithSeq<'a>,
+    labels: &'a HashMap<HeaderSeq, &'static str>
+}
+
+impl<'a> Iterator for IterWithLabels<'a> {
+    type Item = (Option<&'static str>, HeaderName, &'a Box<HeaderObj>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (seq, name, obj) = self.inner.next()?;
+        Some((self.labels.get(&seq).cloned(), name, obj))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for IterWithLabels<'a> {
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use internals::MailType;
+    use ::{HeaderKind, HeaderMap};
+    use ::headers::*;
+
+    fn global() -> HeaderMap {
+        headers! {
+            _From: ["news@example.com"],
+            ReplyTo: ["global@example.com"],
+            Comments: "global"
+        }.unwrap()
+    }
+
+    fn brand() -> HeaderMap {
+        headers! {
+            ReplyTo: ["brand@example.com"],
+            Subject: "brand subject"
+        }.unwrap()
+    }
+
+    fn campaign() -> HeaderMap {
+        headers! {
+            Subject: "campaign subject",
+            Comments: "campaign"
+        }.unwrap()
+    }
+
+    fn layered() -> HeaderMap {
+        let mut map = HeaderMap::new();
+        map.combine_labeled(global(), "global");
+        map.combine_labeled(brand(), "brand");
+        map.combine_labeled(campaign(), "campaign");
+        map
+    }
+
+    fn labels(map: &HeaderMap) -> Vec<(&'static str, Option<&'static str>)> {
+        map.iter_with_labels()
+            .map(|(label, name, _)| (name.as_str(), label))
+            .collect()
+    }
+
+    test!(layers_are_labeled {
+        let map = layered();
+
+        assert_eq!(labels(&map), vec![
+            ("From", Some("global")),
+            ("Comments", Some("global")),
+            // the Reply-To of the global layer was replaced
+            ("Reply-To", Some("brand")),
+            // the Subject of the brand layer was replaced
+            ("Subject", Some("campaign")),
+            ("Comments", Some("campaign"))
+        ]);
+
+        let reply_to = map.get_single(ReplyTo).unwrap()?;
+        assert_eq!(reply_to.0.iter().next().unwrap().email.to_string(), "brand@example.com");
+        assert_eq!(map.label_of(2), Some("brand"));
+        assert_eq!(map.label_of(5), None);
+    });
+
+    test!(unlabeled_headers_have_no_label {
+        let mut map = headers! {
+            Subject: "own subject"
+        }?;
+        map.combine_labeled(global(), "global");
+        map.insert(Comments::auto_body("added later")?);
+
+        assert_eq!(labels(&map), vec![
+            ("Subject", None),
+            ("From", Some("global")),
+            ("Reply-To", Some("global")),
+            ("Comments", Some("global")),
+            ("Comments", None)
+        ]);
+    });
+
+    test!(labels_survive_clone_and_further_combination {
+        let map = layered();
+        let expected = labels(&map);
+        assert_eq!(labels(&map.clone()), expected);
+
+        let mut message = HeaderMap::new();
+        message.combine_labeled(map.clone(), "message");
+        assert_eq!(labels(&message), expected);
+
+        let mut message = HeaderMap::new();
+        message.insert_all(map);
+        assert_eq!(labels(&message), expected);
+    });
+
+    test!(replacing_a_header_drops_its_label {
+        let mut map = layered();
+        map.insert(ReplyTo::auto_body(["message@example.com"])?);
+
+        assert_eq!(map.label_of(map.len() - 1), None);
+        assert!(!map.iter_with_labels().any(|(label, _, _)| label == Some("brand")));
+    });
+
+    test!(removing_headers_drops_their_labels {
+        let mut map = layered();
+        assert_eq!(map.labels.len(), 5);
+
+        assert!(map.remove(Comments));
+        assert_eq!(map.labels.len(), 3);
+        assert!(map.remove_at(Subject, 0).is_some());
+        map.retain(|name, _| name != ReplyTo::name());
+        assert_eq!(map.labels.len(), 1);
+
+        let mut map = layered();
+        let split = map.split_off(|name, _| name == Comments::name());
+        assert_eq!(split.labels.len(), 2);
+        assert_eq!(map.labels.len(), 3);
+        assert_eq!(map.drain_name(Subject).len(), 1);
+        assert_eq!(map.labels.len(), 2);
+        assert_eq!(map.drain().count(), 2);
+        assert!(map.labels.is_empty());
+
+        // repeated combining and removing doesn't accumulate labels
+        let mut map = HeaderMap::new();
+        for _ in 0..10 {
+            map.combine_labeled(campaign(), "campaign");
+            map.remove(Comments);
+        }
+        assert_eq!(map.labels.len(), map.len());
+    });
+
+    test!(labels_do_not_affect_encoding {
+        let mut unlabeled = HeaderMap::new();
+        unlabeled.insert_all(global());
+        let mut labeled = HeaderMap::new();
+        labeled.combine_labeled(global(), "global");
+
+        assert!(unlabeled.eq_by_encoding(&labeled, MailType::Ascii)?);
+    });
+}
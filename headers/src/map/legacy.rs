@@ -0,0 +1,478 @@
+use vec1::Vec1;
+
+use ::{HeaderKind, HeaderTryFrom};
+use ::name::HeaderName;
+use ::error::{ComponentCreationError, MigrationError, LimitViolation};
+use ::header_components as components;
+use ::headers::*;
+
+use super::{HeaderMap, MapLimits};
+use super::string_pairs::PairRegistry;
+
+/// The value shapes accepted by the old `map.insert(Header, value)` API.
+///
+/// This is only meant to be used to migrate code using the old API, see
+/// `HeaderMap::from_legacy_pairs`. It will be deprecated once the migration
+/// is done.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum LegacyValue {
+    /// No value, e.g. the null path `<>` of `Return-Path`.
+    Unit,
+    /// A single string, e.g. a subject or a single address.
+    Str(String),
+    /// A `(display name, address)` tuple.
+    Pair(String, String),
+    /// A list of strings, e.g. addresses or message ids.
+    List(Vec<String>),
+    /// A list of `(display name, address)` tuples.
+    PairList(Vec<(String, String)>)
+}
+
+impl LegacyValue {
+
+    /// A human readable name of the shape of this value (used in `MigrationError`).
+    pub fn shape(&self) -> &'static str {
+        match *self {
+            LegacyValue::Unit => "unit",
+            LegacyValue::Str(..) => "string",
+            LegacyValue::Pair(..) => "(name, address) tuple",
+            LegacyValue::List(..) => "list of strings",
+            LegacyValue::PairList(..) => "list of (name, address) tuples"
+        }
+    }
+}
+
+impl HeaderMap {
+
+    /// Creates a map from `(header name, value)` pairs recorded from the old insert API.
+    ///
+    /// The pairs are inserted in order (so a later max-one header replaces
+    /// a earlier one). Each value is converted into the component of the
+    /// built-in header with the given name:
+    ///
+    /// - address list headers (e.g. `From`, `To`, `Resent-Cc`) accept a
+    ///   string, a tuple or a list of either (`Resent-Bcc` also a unit)
+    /// - single mailbox headers (`Sender`, `Resent-Sender`, `X-Original-From`)
+    ///   accept a string or a tuple
+    /// - `In-Reply-To`, `References` and `Keywords` accept a string or a list of strings
+    /// - the `List-*` URI headers and `X-Report-Abuse` accept a string or a list of URIs
+    /// - `Return-Path` accepts a string or a unit (the null path)
+    /// - unstructured headers (e.g. `Subject`) accept a string, which is used as it is
+    /// - all other headers accept a string, which is parsed by the parser
+    ///   of the built-in `PairRegistry` (e.g. a rfc2822 date for `Date` or
+    ///   a media type for `Content-Type`)
+    ///
+    /// This is a transitional API which will be deprecated once the migration
+    /// to the typed insert API is done.
+    ///
+    /// # Error
+    ///
+    /// Fails with a `MigrationError` naming the header if the name is not the
+    /// name of a built-in header, the shape of the value does not fit the
+    /// header or the component can not be created from the value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::HeaderKind;
+    /// use mail_headers::headers::*;
+    /// use mail_headers::map::{HeaderMap, LegacyValue};
+    ///
+    /// let map = HeaderMap::from_legacy_pairs(vec![
+    ///     (_From::name(), LegacyValue::Pair("Me".to_owned(), "me@example.com".to_owned())),
+    ///     (Subject::name(), LegacyValue::Str("hy there".to_owned()))
+    /// ]).unwrap();
+    /// assert_eq!(map.len(), 2);
+    ///
+    /// let err = HeaderMap::from_legacy_pairs(vec![
+    ///     (Subject::name(), LegacyValue::Pair("Me".to_owned(), "me@example.com".to_owned()))
+    /// ]).unwrap_err();
+    /// assert_eq!(err.header_name(), Subject::name());
+    /// # }
+    /// ```
+    pub fn from_legacy_pairs(pairs: Vec<(HeaderName, LegacyValue)>)
+        -> Result<HeaderMap, MigrationError>
+    {
+        HeaderMap::from_legacy_pairs_with_limits(pairs, MapLimits::default())
+    }
+
+    /// Like `from_legacy_pairs` but creates a map with given limits.
+    ///
+    /// Fails with `MigrationError::Limit` as soon as a header would exceed
+    /// the limits.
+    pub fn from_legacy_pairs_with_limits(pairs: Vec<(HeaderName, LegacyValue)>, limits: MapLimits)
+        -> Result<HeaderMap, MigrationError>
+    {
+        let registry = PairRegistry::builtin();
+        let mut map = HeaderMap::with_limits(limits);
+        for (name, value) in pairs {
+            insert_legacy(&mut map, &registry, name, value)?;
+        }
+        Ok(map)
+    }
+}
+
+fn insert_legacy(map: &mut HeaderMap, registry: &PairRegistry, name: HeaderName, value: LegacyValue)
+    -> Result<(), MigrationError>
+{
+    macro_rules! dispatch {
+        ($($header:ident => $convert:ident),*) => ($(
+            if name == $header::name() {
+                return map.try_insert($header::body($convert(name, value)?))
+                    .map_err(|violation| limit(name, violation));
+            }
+        )*);
+    }
+
+    // headers accepting other shapes than a string or not parsing the string
+    dispatch! {
+        _From => mailbox_list,
+        Sender => mailbox,
+        ReplyTo => mailbox_list,
+        _To => mailbox_list,
+        Cc => mailbox_list,
+        Bcc => mailbox_list,
+        InReplyTo => message_id_list,
+        References => message_id_list,
+        Subject => unstructured,
+        Comments => unstructured,
+        Keywords => phrase_list,
+        ResentFrom => mailbox_list,
+        ResentSender => mailbox,
+        ResentTo => mailbox_list,
+        ResentCc => mailbox_list,
+        ResentBcc => opt_mailbox_list,
+        ReturnPath => path,
+        ContentDescription => unstructured,
+        ListPost => uri_list,
+        ListArchive => uri_list,
+        ListHelp => uri_list,
+        ListSubscribe => uri_list,
+        XReportAbuse => uri_list,
+        XRedacted => unstructured,
+        XOriginalFrom => mailbox
+    }
+
+    let parser = registry.get(name)
+        .ok_or(MigrationError::UnknownHeader { header_name: name })?;
+    match value {
+        LegacyValue::Str(text) => {
+            map.try_insert_untyped(component(name, parser(&text))?)
+                .map_err(|violation| limit(name, violation))
+        },
+        other => Err(mismatch(name, &other, "a string"))
+    }
+}
+
+fn limit(name: HeaderName, violation: LimitViolation) -> MigrationError {
+    MigrationError::Limit { header_name: name, violation }
+}
+
+fn mismatch(name: HeaderName, value: &LegacyValue, expected: &'static str) -> MigrationError {
+    MigrationError::ShapeMismatch {
+        header_name: name,
+        shape: value.shape(),
+        expected
+    }
+}
+
+fn component<T>(name: HeaderName, res: Result<T, ComponentCreationError>)
+    -> Result<T, MigrationError>
+{
+    res.map_err(|error| MigrationError::Component { header_name: name, error })
+}
+
+const ADDRESSES: &str = "a address, a (name, address) tuple or a list of them";
+
+fn mailbox_list(name: HeaderName, value: LegacyValue)
+    -> Result<components::MailboxList, MigrationError>
+{
+    match value {
+        LegacyValue::Str(addr) =>
+            component(name, components::MailboxList::try_from(vec![addr])),
+        LegacyValue::Pair(display_name, addr) =>
+            component(name, components::MailboxList::try_from(vec![(display_name, addr)])),
+        LegacyValue::List(addrs) => component(name, components::MailboxList::try_from(addrs)),
+        LegacyValue::PairList(pairs) =>
+            component(name, components::MailboxList::try_from(pairs)),
+        other => Err(mismatch(name, &other, ADDRESSES))
+    }
+}
+
+fn opt_mailbox_list(name: HeaderName, value: LegacyValue)
+    -> Result<components::OptMailboxList, MigrationError>
+{
+    let is_empty = match value {
+        LegacyValue::Unit => true,
+        LegacyValue::List(ref addrs) => addrs.is_empty(),
+        LegacyValue::PairList(ref pairs) => pairs.is_empty(),
+        _ => false
+    };
+    if is_empty {
+        return Ok(components::OptMailboxList(Vec::new()));
+    }
+    mailbox_list(name, value).map(|list| components::OptMailboxList(list.into_iter().collect()))
+}
+
+fn mailbox(name: HeaderName, value: LegacyValue) -> Result<components::Mailbox, MigrationError> {
+    match value {
+        LegacyValue::Str(addr) => component(name, components::Mailbox::try_from(addr)),
+        LegacyValue::Pair(display_name, addr) =>
+            component(name, components::Mailbox::try_from((display_name, addr))),
+        other => Err(mismatch(name, &other, "a address or a (name, address) tuple"))
+    }
+}
+
+fn message_id_list(name: HeaderName, value: LegacyValue)
+    -> Result<components::MessageIdList, MigrationError>
+{
+    let ids = match value {
+        LegacyValue::Str(id) => vec![id],
+        LegacyValue::List(ids) => ids,
+        other => return Err(mismatch(name, &other, "a message id or a list of message ids"))
+    };
+    let ids = ids.into_iter()
+        .map(|id| component(name, components::MessageId::try_from(id)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let ids = component(name, Vec1::from_vec(ids)
+        .map_err(|_| ComponentCreationError::new("MessageIdList")))?;
+    Ok(components::MessageIdList(ids))
+}
+
+fn unstructured(name: HeaderName, value: LegacyValue)
+    -> Result<components::Unstructured, MigrationError>
+{
+    match value {
+        LegacyValue::Str(text) => component(name, components::Unstructured::try_from(text)),
+        other => Err(mismatch(name, &other, "a string"))
+    }
+}
+
+fn phrase_list(name: HeaderName, value: LegacyValue)
+    -> Result<components::PhraseList, MigrationError>
+{
+    match value {
+        LegacyValue::Str(phrase) => component(name, components::PhraseList::try_from(phrase)),
+        LegacyValue::List(phrases) =>
+            component(name, components::PhraseList::try_from(phrases)),
+        other => Err(mismatch(name, &other, "a string or a list of strings"))
+    }
+}
+
+fn path(name: HeaderName, value: LegacyValue) -> Result<components::Path, MigrationError> {
+    match value {
+        LegacyValue::Unit => Ok(components::Path(None)),
+        LegacyValue::Str(addr) => component(name, components::Path::try_from(addr)),
+        other => Err(mismatch(name, &other, "a address or unit (for the null path)"))
+    }
+}
+
+fn uri_list(name: HeaderName, value: LegacyValue)
+    -> Result<components::UriList, MigrationError>
+{
+    match value {
+        LegacyValue::Str(uris) =>
+            component(name, components::UriList::parse(&uris)),
+        LegacyValue::List(uris) => {
+            let uris = uris.iter().map(|uri| uri.trim_matches(|ch: char| ch == '<' || ch == '>'));
+            component(name, components::UriList::new(uris))
+        },
+        other => Err(mismatch(name, &other, "a URI list string or a list of URIs"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn s(text: &str) -> LegacyValue {
+        LegacyValue::Str(text.to_owned())
+    }
+
+    fn pair(display_name: &str, addr: &str) -> LegacyValue {
+        LegacyValue::Pair(display_name.to_owned(), addr.to_owned())
+    }
+
+    fn list(items: &[&str]) -> LegacyValue {
+        LegacyValue::List(items.iter().map(|item| item.to_string()).collect())
+    }
+
+    fn pair_list(items: &[(&str, &str)]) -> LegacyValue {
+        LegacyValue::PairList(items.iter()
+            .map(|&(display_name, addr)| (display_name.to_owned(), addr.to_owned()))
+            .collect())
+    }
+
+    const UNIT: LegacyValue = LegacyValue::Unit;
+
+    /// (header name, a valid legacy value, a legacy value with a shape not fitting the header)
+    fn cases() -> Vec<(HeaderName, LegacyValue, LegacyValue)> {
+        vec![
+            (Date::name(), s("Tue, 6 Aug 2013 04:11:45 +0000"), list(&["a"])),
+            (_From::name(), pair("Me", "me@example.com"), UNIT),
+            (Sender::name(), s("me@example.com"), list(&["me@example.com"])),
+            (ReplyTo::name(), list(&["a@example.com", "b@example.com"]), UNIT),
+            (_To::name(), pair_list(&[("A", "a@example.com"), ("B", "b@example.com")]), UNIT),
+            (Cc::name(), s("cc@example.com"), UNIT),
+            (Bcc::name(), s("bcc@example.com"), UNIT),
+            (MessageId::name(), s("a@example.com"), pair("a", "a@example.com")),
+            (InReplyTo::name(), s("a@example.com"), pair("a", "a@example.com")),
+            (References::name(), list(&["a@example.com", "b@example.com"]), UNIT),
+            (Subject::name(), s("hy there"), pair("Me", "me@example.com")),
+            (Comments::name(), s("a comment"), list(&["a comment"])),
+            (Keywords::name(), list(&["a", "b"]), pair("a", "b")),
+            (ResentDate::name(), s("Tue, 6 Aug 2013 04:11:45 +0000"), UNIT),
+            (ResentFrom::name(), s("me@example.com"), UNIT),
+            (ResentSender::name(), pair("Me", "me@example.com"), pair_list(&[("Me", "me@example.com")])),
+            (ResentTo::name(), s("you@example.com"), UNIT),
+            (ResentCc::name(), s("cc@example.com"), UNIT),
+            (ResentMsgId::name(), s("a@example.com"), list(&["a@example.com"])),
+            (ReturnPath::name(), UNIT, pair("Me", "me@example.com")),
+            (Received::name(), s("mail.example.com"), UNIT),
+            (ContentType::name(), s("text/plain; charset=utf-8"), list(&["text/plain"])),
+            (ContentId::name(), s("part1@example.com"), UNIT),
+            (ContentTransferEncoding::name(), s("quoted-printable"), UNIT),
+            (ContentDescription::name(), s("a picture"), UNIT),
+            (ContentDisposition::name(), s("attachment"), UNIT),
+            (Precedence::name(), s("bulk"), UNIT),
+            (XAutoResponseSuppress::name(), s("OOF, AutoReply"), UNIT),
+            (ListId::name(), s("Friendly Name <list-id.example.com>"), list(&["a.example.com"])),
+            (ListPost::name(), s("NO"), UNIT),
+            (ListArchive::name(), list(&["https://example.com/archive"]), UNIT),
+            (ListHelp::name(), s("<mailto:help@example.com>"), pair("a", "b")),
+            (ListSubscribe::name(), s("<mailto:sub@example.com>"), UNIT),
+            (FeedbackId::name(), s("summer-sale:customer42:newsletter:esp1"), list(&["a"])),
+            (XReportAbuse::name(), s("<https://example.com/report-abuse>"), pair("a", "b")),
+            (XRedacted::name(), s("Bcc"), UNIT),
+            (XOriginalFrom::name(), s("me@example.com"), UNIT),
+            (XSpamStatus::name(), s("yes, score=7.2 required=5.0"), UNIT)
+        ]
+    }
+
+    #[test]
+    fn cases_cover_every_header_of_the_builtin_registry() {
+        let covered = cases().into_iter().map(|(name, _, _)| name).collect::<Vec<_>>();
+        let registry = PairRegistry::builtin();
+        let mut names = registry.names().collect::<Vec<_>>();
+        names.sort_by_key(|name| name.as_str());
+        // `Resent-Bcc` accepts all shapes, see `resent_bcc_accepts_all_shapes`
+        names.retain(|&name| name != ResentBcc::name());
+        for name in names {
+            assert!(covered.contains(&name), "no test case for {}", name);
+        }
+    }
+
+    #[test]
+    fn every_builtin_header_accepts_a_valid_shape() {
+        for (name, valid, _) in cases() {
+            let map = HeaderMap::from_legacy_pairs(vec![(name, valid.clone())])
+                .unwrap_or_else(|err| panic!("{} rejected {:?}: {}", name, valid, err));
+            assert!(map.contains(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn every_builtin_header_rejects_a_invalid_shape() {
+        for (name, _, invalid) in cases() {
+            match HeaderMap::from_legacy_pairs(vec![(name, invalid.clone())]) {
+                Err(MigrationError::ShapeMismatch { header_name, shape, .. }) => {
+                    assert_eq!(header_name, name);
+                    assert_eq!(shape, invalid.shape());
+                },
+                other => panic!("{} did not reject {:?}: {:?}", name, invalid, other)
+            }
+        }
+    }
+
+    #[test]
+    fn resent_bcc_accepts_all_shapes() {
+        let name = ResentBcc::name();
+        for value in vec![UNIT, s("a@example.com"), pair("A", "a@example.com"),
+            list(&[]), list(&["a@example.com"]), pair_list(&[("A", "a@example.com")])]
+        {
+            let map = HeaderMap::from_legacy_pairs(vec![(name, value.clone())])
+                .unwrap_or_else(|err| panic!("rejected {:?}: {}", value, err));
+            assert!(map.contains(name));
+        }
+
+        match HeaderMap::from_legacy_pairs(vec![(name, s("not a address"))]) {
+            Err(MigrationError::Component { header_name, .. }) => assert_eq!(header_name, name),
+            other => panic!("unexpected result: {:?}", other)
+        }
+    }
+
+    test!(pairs_are_inserted_in_order {
+        let map = HeaderMap::from_legacy_pairs(vec![
+            (Comments::name(), s("a")),
+            (_From::name(), pair_list(&[("A", "a@example.com"), ("B", "b@example.com")])),
+            (Sender::name(), s("a@example.com")),
+            (Comments::name(), s("b"))
+        ])?;
+
+        let names = map.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Comments", "From", "Sender", "Comments"]);
+        assert_eq!(map.get_single(_From).unwrap()?.len(), 2);
+        map.use_contextual_validators()?;
+    });
+
+    #[test]
+    fn limits_stop_the_migration() {
+        let pairs = vec![
+            (Subject::name(), s("a")),
+            (Comments::name(), s("b")),
+            (Date::name(), s("Tue, 1 Jul 2003 10:52:37 +0200"))
+        ];
+        let limits = MapLimits { max_headers: Some(2), ..Default::default() };
+        let err = assert_err!(HeaderMap::from_legacy_pairs_with_limits(pairs.clone(), limits));
+        assert_eq!(err.header_name(), Date::name());
+        match err {
+            MigrationError::Limit { violation, .. } => assert_eq!(violation.accepted(), 0),
+            other => panic!("unexpected error: {:?}", other)
+        }
+
+        let limits = MapLimits { max_headers: Some(3), ..Default::default() };
+        let map = assert_ok!(HeaderMap::from_legacy_pairs_with_limits(pairs, limits));
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.limits(), limits);
+    }
+
+    #[test]
+    fn invalid_values_are_component_errors() {
+        let err = assert_err!(HeaderMap::from_legacy_pairs(vec![
+            (Subject::name(), s("fine")),
+            (Date::name(), s("yesterday"))
+        ]));
+        assert_eq!(err.header_name(), Date::name());
+        match err {
+            MigrationError::Component { .. } => {},
+            other => panic!("unexpected error: {:?}", other)
+        }
+
+        let err = assert_err!(HeaderMap::from_legacy_pairs(vec![
+            (ContentTransferEncoding::name(), s("uuencode"))
+        ]));
+        assert_eq!(err.header_name(), ContentTransferEncoding::name());
+    }
+
+    #[test]
+    fn unknown_headers_are_rejected() {
+        let name = HeaderName::from_ascii_unchecked("X-Unknown");
+        match HeaderMap::from_legacy_pairs(vec![(name, s("value"))]) {
+            Err(MigrationError::UnknownHeader { header_name }) => assert_eq!(header_name, name),
+            other => panic!("unexpected result: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn shape_mismatch_message_names_header_and_shape() {
+        let err = assert_err!(HeaderMap::from_legacy_pairs(vec![
+            (Subject::name(), pair("Me", "me@example.com"))
+        ]));
+        assert_eq!(
+            err.to_string(),
+            "Subject header does not accept a legacy (name, address) tuple value (expected a string)"
+        );
+    }
+}
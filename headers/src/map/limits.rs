@@ -0,0 +1,412 @@
+use std::cmp;
+
+use internals::MailType;
+
+use ::name::HeaderName;
+use ::header::{Header, HeaderKind, HeaderObj};
+use ::error::{LimitViolation, LimitKind};
+
+use super::HeaderMap;
+
+/// Limits enforced by a `HeaderMap` when inserting headers, see `HeaderMap::with_limits`.
+///
+/// The default is to not limit anything.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct MapLimits {
+    /// The maximal number of headers in the map.
+    pub max_headers: Option<usize>,
+
+    /// The maximal number of bytes of all headers in the map.
+    ///
+    /// The size of a header is the size of the header line (including
+    /// the name, the `": "` separator and the final CRLF) when encoded
+    /// for an internationalized mail. Headers which can not be encoded
+    /// at all are treated as exceeding this limit.
+    ///
+    /// Changes done to a header body through mutable references (e.g.
+    /// `get_single_mut`) are not accounted for.
+    pub max_bytes: Option<usize>,
+
+    /// Makes `try_insert_all` all-or-nothing.
+    ///
+    /// By default `try_insert_all` inserts headers until one would exceed
+    /// a limit and reports how many were inserted. With this option set no
+    /// header is inserted in that case (the same applies to `try_combine_with`
+    /// and `try_extend`). The insertion methods which can not fail, e.g.
+    /// `insert_all`, are always all-or-nothing.
+    pub atomic_insert_all: bool
+}
+
+impl MapLimits {
+
+    /// Returns true if no limit is set.
+    pub fn is_unlimited(&self) -> bool {
+        self.max_headers.is_none() && self.max_bytes.is_none()
+    }
+}
+
+impl HeaderMap {
+
+    /// Creates a new empty header map which enforces given limits.
+    ///
+    /// Any insertion which would exceed the limits is rejected (without
+    /// inserting anything), which makes it possible to stop processing
+    /// untrusted input as soon as it becomes too large instead of checking
+    /// the size afterwards. The limits are kept when cloning the map.
+    ///
+    /// The `try_*` insertion methods return the `LimitViolation`. The other
+    /// insertion methods (e.g. `insert`, `insert_all` or `extend`) can not
+    /// fail, they leave the map unchanged and record the violation instead,
+    /// see `take_limit_violation`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::{HeaderMap, HeaderKind};
+    /// use mail_headers::headers::*;
+    /// use mail_headers::map::MapLimits;
+    /// use mail_headers::error::LimitKind;
+    ///
+    /// let mut map = HeaderMap::with_limits(MapLimits {
+    ///     max_headers: Some(2),
+    ///     ..Default::default()
+    /// });
+    ///
+    /// map.try_insert(Comments::auto_body("one").unwrap()).unwrap();
+    /// map.try_insert(Comments::auto_body("two").unwrap()).unwrap();
+    /// let err = map.try_insert(Comments::auto_body("three").unwrap()).unwrap_err();
+    /// assert_eq!(err.kind(), LimitKind::HeaderCount);
+    /// assert_eq!(map.len(), 2);
+    /// # }
+    /// ```
+    pub fn with_limits(limits: MapLimits) -> Self {
+        HeaderMap { limits, ..Default::default() }
+    }
+
+    /// Returns the limits of this map.
+    pub fn limits(&self) -> MapLimits {
+        self.limits
+    }
+
+    /// Returns (and clears) the last limit violation of a insertion method which can not fail.
+    ///
+    /// Insertion methods like `insert`, `insert_all`, `combine_with` or
+    /// `extend` don't insert anything if this would exceed the limits of the
+    /// map (see `with_limits`), in which case the `LimitViolation` is
+    /// recorded and can be retrieved with this method. The `try_*` variants
+    /// of these methods return the violation instead of recording it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::{HeaderMap, HeaderKind};
+    /// use mail_headers::headers::*;
+    /// use mail_headers::map::MapLimits;
+    ///
+    /// let mut map = HeaderMap::with_limits(MapLimits {
+    ///     max_headers: Some(1),
+    ///     ..Default::default()
+    /// });
+    ///
+    /// map.insert(Comments::auto_body("one").unwrap());
+    /// assert!(map.take_limit_violation().is_none());
+    /// map.insert(Comments::auto_body("two").unwrap());
+    /// assert_eq!(map.len(), 1);
+    /// assert!(map.take_limit_violation().is_some());
+    /// assert!(map.take_limit_violation().is_none());
+    /// # }
+    /// ```
+    pub fn take_limit_violation(&mut self) -> Option<LimitViolation> {
+        self.rejected.take()
+    }
+
+    /// Records the violation of a insertion method which can not fail, see `take_limit_violation`.
+    pub(crate) fn record_violation<R>(&mut self, result: Result<R, LimitViolation>) {
+        if let Err(violation) = result {
+            self.rejected = Some(violation);
+        }
+    }
+
+    /// Applies `insert` to this map, keeping the changes only if it succeeded.
+    ///
+    /// If the map has limits `insert` is applied to a clone of the map, as
+    /// a map without limits can not fail no clone is needed otherwise. The
+    /// `accepted` count of a returned `LimitViolation` is `0`.
+    pub(crate) fn insert_atomically<R, F>(&mut self, insert: F) -> Result<R, LimitViolation>
+        where F: FnOnce(&mut HeaderMap) -> Result<R, LimitViolation>
+    {
+        if self.limits.is_unlimited() {
+            return insert(self);
+        }
+        let mut probe = self.clone();
+        match insert(&mut probe) {
+            Ok(res) => {
+                *self = probe;
+                Ok(res)
+            },
+            Err(err) => Err(LimitViolation::new(err.kind(), err.limit(), 0))
+        }
+    }
+
+    /// Removes all limits from this map.
+    pub fn into_unlimited(mut self) -> Self {
+        self.limits = MapLimits::default();
+        self.used_bytes = 0;
+        self
+    }
+
+    /// Like `insert` but fails instead of exceeding the limits of this map.
+    pub fn try_insert<H>(&mut self, header: Header<H>) -> Result<(), LimitViolation>
+        where H: HeaderKind
+    {
+        let name = header.name();
+        let obj: Box<HeaderObj> = Box::new(header);
+        self._try_insert_with_seq(name, H::MAX_ONE, obj, super::next_seq(), 0)
+    }
+
+    /// Like `insert_untyped` but fails instead of exceeding the limits of this map.
+    #[doc(hidden)]
+    pub fn try_insert_untyped(&mut self, obj: Box<HeaderObj>) -> Result<(), LimitViolation> {
+        let (name, max_one) = (obj.name(), obj.is_max_one());
+        self._try_insert_with_seq(name, max_one, obj, super::next_seq(), 0)
+    }
+
+    /// Adds the header without replacing "max one" headers (like `from_vec`),
+    /// failing instead of exceeding the limits of this map.
+    ///
+    /// The `accepted` count of a `LimitViolation` is the number of headers in the map.
+    pub(crate) fn try_push(&mut self, name: HeaderName, obj: Box<HeaderObj>) -> Result<(), LimitViolation> {
+        let accepted = self.len();
+        self._try_insert_with_seq(name, false, obj, super::next_seq(), accepted)
+    }
+
+    /// Like `insert_all` but fails instead of exceeding the limits of this map.
+    ///
+    /// Headers are inserted in order until inserting one would exceed a limit,
+    /// in which case the remaining headers are dropped and the returned
+    /// `LimitViolation` reports how many headers were inserted. If the
+    /// limits have `atomic_insert_all` set no header is inserted instead.
+    ///
+    /// On success the number of inserted headers is returned.
+    pub fn try_insert_all(&mut self, other: HeaderMap) -> Result<usize, LimitViolation> {
+        if self.limits.atomic_insert_all {
+            self.insert_atomically(|map| map.insert_all_until_violation(other))
+        } else {
+            self.insert_all_until_violation(other)
+        }
+    }
+
+    /// Inserts the headers of `other` until one would exceed a limit (the non atomic `try_insert_all`).
+    pub(crate) fn insert_all_until_violation(&mut self, other: HeaderMap) -> Result<usize, LimitViolation> {
+        let HeaderMap { inner_map, seqs, max_seq, validators, validator_stages, labels, .. } = other;
+        for (validator, stages) in validators.into_iter().zip(validator_stages) {
+            self.add_staged_validator(validator, stages);
+        }
+        let mut accepted = 0;
+        for ((_name, header), seq) in inner_map.into_iter().zip(seqs) {
+            let (name, max_one) = (header.name(), header.is_max_one());
+            self._try_insert_with_seq(name, max_one, header, seq, accepted)?;
+            self.copy_label(&labels, seq);
+            accepted += 1;
+        }
+        self.max_seq = cmp::max(self.max_seq, max_seq);
+        Ok(accepted)
+    }
+}
+
+/// The size of given header used for `MapLimits::max_bytes`.
+fn header_size(name: HeaderName, obj: &HeaderObj) -> Option<usize> {
+    // `"<name>: <value>\r\n"`
+    obj.encoded_value(MailType::Internationalized)
+        .ok()
+        .map(|value| name.as_wire_str().len() + value.len() + 4)
+}
+
+/// Returns the size of all headers with given name.
+pub fn bytes_of(map: &HeaderMap, name: HeaderName) -> usize {
+    map.iter()
+        .filter(|&(other, _)| other == name)
+        .map(|(other, obj)| header_size(other, &**obj).unwrap_or(0))
+        .sum()
+}
+
+/// Returns the size of all headers in the map.
+pub fn total_bytes(map: &HeaderMap) -> usize {
+    map.iter()
+        .map(|(name, obj)| header_size(name, &**obj).unwrap_or(0))
+        .sum()
+}
+
+/// Checks if inserting given header into the map would exceed its limits.
+///
+/// Returns the number of bytes used by the map after inserting the
+/// header (`0` if `max_bytes` is not set).
+pub fn check_insert(map: &HeaderMap, name: HeaderName, max_one: bool, obj: &HeaderObj, accepted: usize)
+    -> Result<usize, LimitViolation>
+{
+    let limits = map.limits;
+    if limits.is_unlimited() {
+        return Ok(0);
+    }
+
+    if let Some(max) = limits.max_headers {
+        let replaced = if max_one { map.get_untyped(name).len() } else { 0 };
+        if map.len() - replaced + 1 > max {
+            return Err(LimitViolation::new(LimitKind::HeaderCount, max, accepted));
+        }
+    }
+
+    match limits.max_bytes {
+        None => Ok(0),
+        Some(max) => {
+            let violation = LimitViolation::new(LimitKind::Bytes, max, accepted);
+            let size = header_size(name, obj).ok_or(violation)?;
+            let replaced = if max_one { bytes_of(map, name) } else { 0 };
+            let used_bytes = map.used_bytes.saturating_sub(replaced) + size;
+            if used_bytes > max {
+                Err(violation)
+            } else {
+                Ok(used_bytes)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderKind;
+    use ::error::LimitKind;
+    use ::headers::{_From, Subject, Comments, Received};
+    use ::header_components::{ReceivedToken, Domain};
+    use ::HeaderTryFrom;
+    use super::*;
+
+    fn count_limited(max: usize) -> HeaderMap {
+        HeaderMap::with_limits(MapLimits { max_headers: Some(max), ..Default::default() })
+    }
+
+    test!(count_cap {
+        let mut map = count_limited(2);
+        map.try_insert(Comments::auto_body("one")?)?;
+        map.try_insert(Subject::auto_body("first")?)?;
+
+        let err = assert_err!(map.try_insert(Comments::auto_body("two")?));
+        assert_eq!(err.kind(), LimitKind::HeaderCount);
+        assert_eq!(err.limit(), 2);
+        assert_eq!(map.len(), 2);
+
+        // replacing a max one header does not increase the count
+        map.try_insert(Subject::auto_body("second")?)?;
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get_single(Subject).unwrap()?.as_str(), "second");
+    });
+
+    test!(byte_cap {
+        // "Comments: 1234\r\n" is 16 bytes
+        let mut map = HeaderMap::with_limits(MapLimits { max_bytes: Some(40), ..Default::default() });
+        map.try_insert(Comments::auto_body("1234")?)?;
+        map.try_insert(Comments::auto_body("5678")?)?;
+
+        let err = assert_err!(map.try_insert(Comments::auto_body("9")?));
+        assert_eq!(err.kind(), LimitKind::Bytes);
+        assert_eq!(map.len(), 2);
+
+        // removing headers frees the budget again
+        assert!(map.remove(Comments));
+        map.try_insert(Comments::auto_body("a longer comment")?)?;
+        assert_eq!(map.len(), 1);
+    });
+
+    fn received_map(count: usize) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for _ in 0..count {
+            let token = ReceivedToken::Domain(Domain::try_from("mx.example.com").unwrap());
+            map.insert(Received::auto_body(token).unwrap());
+        }
+        map
+    }
+
+    test!(combine_partial {
+        let mut map = count_limited(3);
+        map.try_insert(_From::auto_body(["me@example.com"])?)?;
+
+        let err = assert_err!(map.try_insert_all(received_map(4)));
+        assert_eq!(err.accepted(), 2);
+        assert_eq!(map.len(), 3);
+
+        let mut map = count_limited(3);
+        assert_eq!(map.try_insert_all(received_map(3))?, 3);
+    });
+
+    test!(combine_atomic {
+        let mut map = HeaderMap::with_limits(MapLimits {
+            max_headers: Some(3),
+            atomic_insert_all: true,
+            ..Default::default()
+        });
+        map.try_insert(_From::auto_body(["me@example.com"])?)?;
+
+        let err = assert_err!(map.try_insert_all(received_map(4)));
+        assert_eq!(err.accepted(), 0);
+        assert_eq!(map.len(), 1);
+
+        assert_eq!(map.try_insert_all(received_map(2))?, 2);
+        assert_eq!(map.len(), 3);
+        assert!(map.limits().atomic_insert_all);
+    });
+
+    test!(limits_travel_with_clone_but_can_be_lifted {
+        let mut map = count_limited(1);
+        map.try_insert(Comments::auto_body("one")?)?;
+
+        let mut cloned = map.clone();
+        assert_err!(cloned.try_insert(Comments::auto_body("two")?));
+
+        let mut unlimited = map.into_unlimited();
+        assert!(unlimited.limits().is_unlimited());
+        unlimited.try_insert(Comments::auto_body("two")?)?;
+        assert_eq!(unlimited.len(), 2);
+    });
+
+    test!(infallible_inserts_reject_instead_of_panicking {
+        let mut map = count_limited(2);
+        map.insert(Comments::auto_body("one")?);
+        map.insert(Comments::auto_body("two")?);
+        assert!(map.take_limit_violation().is_none());
+
+        map.insert(Comments::auto_body("three")?);
+        map.insert_untyped(Box::new(Comments::auto_body("three")?));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.take_limit_violation().unwrap().kind(), LimitKind::HeaderCount);
+        assert!(map.take_limit_violation().is_none());
+
+        // bulk insertions insert all or nothing, even if `atomic_insert_all` is not set
+        let mut map = count_limited(3);
+        map.insert(_From::auto_body(["me@example.com"])?);
+        map.insert_all(received_map(3));
+        assert_eq!(map.len(), 1);
+        let violation = map.take_limit_violation().unwrap();
+        assert_eq!(violation.accepted(), 0);
+
+        map.extend(received_map(3));
+        assert_eq!(map.len(), 1);
+        assert!(map.take_limit_violation().is_some());
+
+        map.insert_all(received_map(2));
+        assert_eq!(map.len(), 3);
+        assert!(map.take_limit_violation().is_none());
+    });
+
+    test!(default_maps_are_unlimited {
+        let mut map = HeaderMap::new();
+        assert!(map.limits().is_unlimited());
+        map.insert_all(received_map(100));
+        map.try_insert_all(received_map(100))?;
+        assert_eq!(map.len(), 200);
+        assert_eq!(map.used_bytes, 0);
+    });
+}
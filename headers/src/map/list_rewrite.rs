@@ -0,0 +1,367 @@
+use ::error::{ComponentCreationError, LimitViolation};
+use ::header::HeaderKind;
+use ::header_components::{Mailbox, MailboxList, Phrase};
+use ::headers::{_From, ReplyTo, Sender, XOriginalFrom};
+
+use super::HeaderMap;
+
+/// How `rewrite_from_for_list` handles an already existing `Reply-To` header.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ReplyToMode {
+    /// Keep the existing `Reply-To` header as it is.
+    KeepExisting,
+    /// Add all original authors which are not yet in the existing `Reply-To` header.
+    Merge
+}
+
+/// Options for `rewrite_from_for_list`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ListRewriteOptions {
+    /// Template for the display name of the new `From` mailbox.
+    ///
+    /// `{name}` is replaced by the display name(s) of the original author(s)
+    /// (their email if they have none) and `{list}` by the display name of the
+    /// list (its local part if it has none).
+    pub display_name_template: String,
+
+    /// What to do if there already is a `Reply-To` header.
+    pub reply_to: ReplyToMode
+}
+
+impl Default for ListRewriteOptions {
+    /// Uses `"{name} via {list}"` as template and keeps existing `Reply-To` headers.
+    fn default() -> Self {
+        ListRewriteOptions {
+            display_name_template: "{name} via {list}".to_owned(),
+            reply_to: ReplyToMode::KeepExisting
+        }
+    }
+}
+
+/// A single change done by `rewrite_from_for_list`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum RewriteChange {
+    /// The `From` header was replaced by the list address.
+    FromRewritten { original: Vec<Mailbox>, rewritten: Mailbox },
+    /// A `Reply-To` header with the original authors was added.
+    ReplyToSet { authors: Vec<Mailbox> },
+    /// The original authors in `added` were added to the existing `Reply-To` header.
+    ReplyToMerged { added: Vec<Mailbox> },
+    /// The existing `Reply-To` header was kept as it is.
+    ReplyToKept,
+    /// A `X-Original-From` header was added for given author.
+    OriginalFromPreserved { author: Mailbox },
+    /// The `Sender` header was removed, as the new `From` only contains the list address.
+    SenderRemoved { original: Mailbox }
+}
+
+/// Describes all changes done by `rewrite_from_for_list` in the order they were applied.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct RewriteReport {
+    pub changes: Vec<RewriteChange>
+}
+
+impl RewriteReport {
+
+    /// Returns true if nothing was changed (i.e. there was no `From` header).
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Rewrites the `From` header the way mailing lists do it to not break DMARC.
+///
+/// E.g. `From: Alice <alice@strict-dmarc.example>` becomes
+/// `From: Alice via List <list@ourdomain.example>`. Additionally:
+///
+/// - the original author(s) are set as `Reply-To` if there is no `Reply-To`
+///   header (or merged into it, see `ReplyToMode`)
+/// - for each original author a `X-Original-From` header is added
+/// - a `Sender` header is removed, as the new `From` has exactly one mailbox
+///
+/// The `From`, `Reply-To` and `Sender` headers keep their position in the map,
+/// new headers are added at the end.
+///
+/// Either all changes are applied or (if an error is returned) none of them.
+/// Errors are returned if the new display name is not a valid `Phrase`, if
+/// any of the involved headers has an unexpected type or if the added headers
+/// would exceed the limits of the map (see `HeaderMap::with_limits`, this is
+/// reported as a `ComponentCreationError` for `"HeaderMap"` caused by the
+/// `LimitViolation`). If there is no `From` header nothing is done.
+pub fn rewrite_from_for_list(map: &mut HeaderMap, list_addr: Mailbox, opts: ListRewriteOptions)
+    -> Result<RewriteReport, ComponentCreationError>
+{
+    // 1. create everything needed without modifying the map
+    let original = match map.get_single(_From) {
+        Some(from) => {
+            from.map_err(|err| ComponentCreationError::from_parent(err, "MailboxList"))?
+                .body().clone()
+        },
+        None => return Ok(RewriteReport::default())
+    };
+    let authors = original.iter().cloned().collect::<Vec<_>>();
+
+    let names = authors.iter().map(author_name).collect::<Vec<_>>().join(", ");
+    let list_name = match list_addr.display_name {
+        Some(ref phrase) => phrase_text(phrase),
+        None => list_addr.email.local_part.as_str().to_owned()
+    };
+    let display_name = opts.display_name_template
+        .replace("{name}", &names)
+        .replace("{list}", &list_name);
+    let rewritten = Mailbox {
+        display_name: Some(Phrase::new(display_name)?),
+        email: list_addr.email
+    };
+
+    let mut changes = vec![RewriteChange::FromRewritten {
+        original: authors.clone(),
+        rewritten: rewritten.clone()
+    }];
+
+    let existing_reply_to = match map.get_single(ReplyTo) {
+        Some(res) => Some(
+            res.map_err(|err| ComponentCreationError::from_parent(err, "MailboxList"))?
+                .body().clone()
+        ),
+        None => None
+    };
+    let new_reply_to = match existing_reply_to {
+        None => {
+            changes.push(RewriteChange::ReplyToSet { authors: authors.clone() });
+            Some(original)
+        },
+        Some(mut reply_to) => {
+            let added = match opts.reply_to {
+                ReplyToMode::KeepExisting => Vec::new(),
+                ReplyToMode::Merge => authors.iter()
+                    .filter(|author| !reply_to.iter().any(|mbox| mbox.email == author.email))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            };
+            if added.is_empty() {
+                changes.push(RewriteChange::ReplyToKept);
+                None
+            } else {
+                for author in added.iter() {
+                    reply_to.push(author.clone());
+                }
+                changes.push(RewriteChange::ReplyToMerged { added });
+                Some(reply_to)
+            }
+        }
+    };
+
+    for author in authors.iter() {
+        changes.push(RewriteChange::OriginalFromPreserved { author: author.clone() });
+    }
+
+    if let Some(sender) = map.get_single(Sender) {
+        let sender = sender.map_err(|err| ComponentCreationError::from_parent(err, "Mailbox"))?;
+        changes.push(RewriteChange::SenderRemoved { original: sender.body().clone() });
+    }
+
+    // 2. apply the changes to a copy, so that exceeding the limits
+    //    of the map leaves the map unchanged
+    let mut updated = map.clone();
+    {
+        let from = updated.get_single_mut(_From)
+            .expect("[BUG] From header was removed")
+            .expect("[BUG] From header changed its type");
+        *from.body_mut() = MailboxList::from_single(rewritten);
+    }
+
+    // removed first so that it doesn't count against the limits
+    updated.remove(Sender);
+
+    let limit_error = |violation: LimitViolation| ComponentCreationError::from_parent(violation, "HeaderMap");
+    if let Some(reply_to) = new_reply_to {
+        if updated.contains(ReplyTo) {
+            let existing = updated.get_single_mut(ReplyTo)
+                .expect("[BUG] Reply-To header was removed")
+                .expect("[BUG] Reply-To header changed its type");
+            *existing.body_mut() = reply_to;
+        } else {
+            updated.try_insert(ReplyTo::body(reply_to)).map_err(limit_error)?;
+        }
+    }
+
+    for author in authors {
+        updated.try_insert(XOriginalFrom::body(author)).map_err(limit_error)?;
+    }
+
+    *map = updated;
+    Ok(RewriteReport { changes })
+}
+
+fn author_name(author: &Mailbox) -> String {
+    match author.display_name {
+        Some(ref phrase) => phrase_text(phrase),
+        None => format!("{}@{}", author.email.local_part.as_str(), author.email.domain.as_str())
+    }
+}
+
+fn phrase_text(phrase: &Phrase) -> String {
+    phrase.0.iter()
+        .map(|word| word.input.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderTryFrom;
+    use ::header_components::{Mailbox, Phrase};
+    use ::headers::{_From, ReplyTo, Sender, Subject, XOriginalFrom};
+    use ::map::MapLimits;
+    use super::*;
+
+    fn list_addr() -> Mailbox {
+        Mailbox::try_from(("List", "list@ourdomain.example")).unwrap()
+    }
+
+    fn alice() -> Mailbox {
+        Mailbox::try_from(("Alice", "alice@strict-dmarc.example")).unwrap()
+    }
+
+    fn bob() -> Mailbox {
+        Mailbox::try_from("bob@example.com").unwrap()
+    }
+
+    test!(basic_rewrite {
+        let mut map = headers! {
+            _From: [("Alice", "alice@strict-dmarc.example")],
+            Subject: "hy"
+        }?;
+
+        let report = rewrite_from_for_list(&mut map, list_addr(), Default::default())?;
+
+        let from = map.get_single(_From).unwrap()?;
+        assert_eq!(from.len(), 1);
+        assert_eq!(from[0].display_name, Some(Phrase::new("Alice via List")?));
+        assert_eq!(from[0].email, list_addr().email);
+
+        let reply_to = map.get_single(ReplyTo).unwrap()?;
+        assert_eq!(reply_to.iter().collect::<Vec<_>>(), vec![&alice()]);
+
+        let originals = map.get(XOriginalFrom).map(|res| res.unwrap().body().clone()).collect::<Vec<_>>();
+        assert_eq!(originals, vec![alice()]);
+
+        assert_eq!(
+            map.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(),
+            vec!["From", "Subject", "Reply-To", "X-Original-From"]
+        );
+        assert_ok!(map.use_contextual_validators());
+
+        let rewritten = from[0].clone();
+        assert_eq!(report.changes, vec![
+            RewriteChange::FromRewritten { original: vec![alice()], rewritten },
+            RewriteChange::ReplyToSet { authors: vec![alice()] },
+            RewriteChange::OriginalFromPreserved { author: alice() }
+        ]);
+    });
+
+    test!(existing_reply_to_is_preserved {
+        let mut map = headers! {
+            _From: [("Alice", "alice@strict-dmarc.example")],
+            ReplyTo: ["replies@example.com"]
+        }?;
+
+        let report = rewrite_from_for_list(&mut map, list_addr(), Default::default())?;
+
+        let reply_to = map.get_single(ReplyTo).unwrap()?;
+        assert_eq!(reply_to.iter().collect::<Vec<_>>(), vec![&Mailbox::try_from("replies@example.com")?]);
+        assert_eq!(report.changes[1], RewriteChange::ReplyToKept);
+    });
+
+    test!(existing_reply_to_can_be_merged {
+        let mut map = headers! {
+            _From: [("Alice", "alice@strict-dmarc.example")],
+            ReplyTo: ["replies@example.com"]
+        }?;
+
+        let opts = ListRewriteOptions { reply_to: ReplyToMode::Merge, ..Default::default() };
+        let report = rewrite_from_for_list(&mut map, list_addr(), opts)?;
+
+        let reply_to = map.get_single(ReplyTo).unwrap()?;
+        assert_eq!(
+            reply_to.iter().collect::<Vec<_>>(),
+            vec![&Mailbox::try_from("replies@example.com")?, &alice()]
+        );
+        assert_eq!(report.changes[1], RewriteChange::ReplyToMerged { added: vec![alice()] });
+    });
+
+    test!(multi_mailbox_from {
+        let mut map = headers! {
+            _From: [("Alice", "alice@strict-dmarc.example"), "bob@example.com"],
+            Sender: ("Alice", "alice@strict-dmarc.example")
+        }?;
+
+        let report = rewrite_from_for_list(&mut map, list_addr(), Default::default())?;
+
+        let from = map.get_single(_From).unwrap()?;
+        assert_eq!(from.len(), 1);
+        assert_eq!(
+            from[0].display_name,
+            Some(Phrase::new("Alice, bob@example.com via List")?)
+        );
+
+        let reply_to = map.get_single(ReplyTo).unwrap()?;
+        assert_eq!(reply_to.iter().collect::<Vec<_>>(), vec![&alice(), &bob()]);
+
+        assert_eq!(map.get(XOriginalFrom).len(), 2);
+        assert!(!map.contains(Sender));
+        assert_ok!(map.use_contextual_validators());
+
+        assert_eq!(report.changes.len(), 5);
+        assert_eq!(report.changes[4], RewriteChange::SenderRemoved { original: alice() });
+    });
+
+    test!(no_from_does_nothing {
+        let mut map = headers! {
+            Subject: "hy"
+        }?;
+
+        let report = rewrite_from_for_list(&mut map, list_addr(), Default::default())?;
+        assert!(report.is_empty());
+        assert_eq!(map.len(), 1);
+    });
+
+    test!(invalid_template_changes_nothing {
+        let mut map = headers! {
+            _From: [("Alice", "alice@strict-dmarc.example")]
+        }?;
+
+        let opts = ListRewriteOptions {
+            display_name_template: "{name}\u{7} via {list}".to_owned(),
+            ..Default::default()
+        };
+        assert_err!(rewrite_from_for_list(&mut map, list_addr(), opts));
+
+        let from = map.get_single(_From).unwrap()?;
+        assert_eq!(from.iter().collect::<Vec<_>>(), vec![&alice()]);
+        assert_eq!(map.len(), 1);
+    });
+
+    test!(exceeding_the_limits_changes_nothing {
+        let mut map = HeaderMap::with_limits(MapLimits { max_headers: Some(2), ..Default::default() });
+        map.try_insert(_From::body(MailboxList::from_single(alice())))?;
+        map.try_insert(Sender::body(alice()))?;
+
+        // Sender is removed, but Reply-To and X-Original-From don't fit
+        let err = assert_err!(rewrite_from_for_list(&mut map, list_addr(), Default::default()));
+        assert!(err.to_string().contains("HeaderMap"));
+
+        let from = map.get_single(_From).unwrap()?;
+        assert_eq!(from.iter().collect::<Vec<_>>(), vec![&alice()]);
+        assert!(map.contains(Sender));
+        assert_eq!(map.len(), 2);
+
+        let mut map = HeaderMap::with_limits(MapLimits { max_headers: Some(3), ..Default::default() });
+        map.try_insert(_From::body(MailboxList::from_single(alice())))?;
+        map.try_insert(Sender::body(alice()))?;
+        rewrite_from_for_list(&mut map, list_addr(), Default::default())?;
+        assert_eq!(map.len(), 3);
+        assert!(!map.contains(Sender));
+    });
+}
@@ -0,0 +1,235 @@
+use soft_ascii::SoftAsciiChar;
+
+use internals::MailType;
+use internals::error::EncodingError;
+use internals::encoder::{EncodingBuffer, EncodingWriter};
+
+use ::name::HeaderName;
+use ::header::HeaderObj;
+
+use super::HeaderMap;
+use super::injection_guard::{InjectionGuard, encode_guarded};
+
+/// Options for `HeaderMap::encode_for_mbox`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct MboxOptions {
+    /// If true any physical line starting with `"From "` is escaped as `">From "`.
+    ///
+    /// Continuation lines created by folding start with whitespace, so this
+    /// only affects values containing line breaks which were written by a
+    /// component, but mbox readers would treat such lines as the start of a
+    /// new message.
+    pub escape_from_lines: bool,
+
+    /// Checks each header for injected line breaks, see `InjectionGuard`.
+    pub injection_guard: InjectionGuard
+}
+
+impl Default for MboxOptions {
+    /// Escapes `"From "` lines, but doesn't use the injection guard.
+    fn default() -> Self {
+        MboxOptions {
+            escape_from_lines: true,
+            injection_guard: InjectionGuard::Disabled
+        }
+    }
+}
+
+impl HeaderMap {
+
+    /// Encodes the header block of this map for storing it in a mbox file.
+    ///
+    /// Headers are encoded in map order, as mbox files use LF line endings
+    /// all CRLF are converted to LF. The returned block always ends with
+    /// exactly one blank line separating it from the (not included) body.
+    ///
+    /// The `"From "` line which precedes each message in a mbox file is not
+    /// part of the output.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # extern crate mail_internals;
+    /// # fn main() {
+    /// use mail_internals::MailType;
+    /// use mail_headers::headers::*;
+    /// use mail_headers::map::MboxOptions;
+    ///
+    /// let map = headers! {
+    ///     _From: ["me@example.com"],
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// let block = map.encode_for_mbox(MailType::Ascii, MboxOptions::default()).unwrap();
+    /// assert_eq!(block, b"From: <me@example.com>\nSubject: hy there\n\n".to_vec());
+    /// # }
+    /// ```
+    pub fn encode_for_mbox(&self, mail_type: MailType, opts: MboxOptions)
+        -> Result<Vec<u8>, EncodingError>
+    {
+        let mut buffer = EncodingBuffer::new(mail_type);
+        encode_guarded(self, &mut buffer, opts.injection_guard)?;
+        Ok(to_mbox_block(buffer.as_slice(), opts))
+    }
+}
+
+/// Writes `Name: body` for given header (without finishing the header).
+///
+/// (`pub` so that it can be used by the other `map` submodules.)
+pub fn encode_header_line(handle: &mut EncodingWriter, name: HeaderName, obj: &HeaderObj)
+    -> Result<(), EncodingError>
+{
+    handle.write_str(name.as_wire_ascii_str())?;
+    handle.write_char(SoftAsciiChar::from_unchecked(':'))?;
+    handle.write_fws();
+    obj.encode(handle)
+}
+
+/// Converts a CRLF header block into a LF block ending with exactly one blank line.
+///
+/// Line breaks at the end of the block are replaced by the blank line, empty
+/// lines inside of the block are kept.
+fn to_mbox_block(block: &[u8], opts: MboxOptions) -> Vec<u8> {
+    let end = block.iter()
+        .rposition(|&bch| bch != b'\r' && bch != b'\n')
+        .map(|idx| idx + 1)
+        .unwrap_or(0);
+    let block = &block[..end];
+
+    let mut out = Vec::with_capacity(block.len() + 2);
+    if !block.is_empty() {
+        for line in block.split(|bch| *bch == b'\n') {
+            let line = if line.ends_with(b"\r") { &line[..line.len() - 1] } else { line };
+            if opts.escape_from_lines && line.starts_with(b"From ") {
+                out.push(b'>');
+            }
+            out.extend_from_slice(line);
+            out.push(b'\n');
+        }
+    }
+    out.push(b'\n');
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use internals::MailType;
+    use internals::encoder::EncodableInHeader;
+    use ::HeaderKind;
+    use ::headers::{_From, Subject, Comments};
+    use ::HeaderMap;
+    use super::*;
+
+    fn no_escape() -> MboxOptions {
+        MboxOptions { escape_from_lines: false, ..Default::default() }
+    }
+
+    test!(normal_map_only_changes_line_endings {
+        let map = headers! {
+            _From: ["me@example.com"],
+            Subject: "hy there",
+            Comments: "some comment"
+        }?;
+
+        let block = map.encode_for_mbox(MailType::Ascii, MboxOptions::default())?;
+        assert_eq!(
+            String::from_utf8(block)?,
+            "From: <me@example.com>\nSubject: hy there\nComments: some comment\n\n"
+        );
+    });
+
+    test!(folded_continuation_lines_are_safe {
+        let text = "this is a long subject which will need to be folded at some \
+                    point From somebody who writes way to long subjects";
+        let map = headers! {
+            Subject: text
+        }?;
+
+        let block = String::from_utf8(map.encode_for_mbox(MailType::Ascii, no_escape())?)?;
+        let lines = block.lines().collect::<Vec<_>>();
+        assert!(lines.len() > 2, "subject was not folded: {:?}", block);
+        for line in lines {
+            assert!(!line.starts_with("From "), "unsafe line: {:?}", line);
+        }
+    });
+
+    #[test]
+    fn from_lines_are_escaped() {
+        let block = b"Subject: a\r\nFrom b\r\n >From c\r\n";
+        assert_eq!(
+            to_mbox_block(block, MboxOptions::default()),
+            b"Subject: a\n>From b\n >From c\n\n".to_vec()
+        );
+        assert_eq!(
+            to_mbox_block(block, no_escape()),
+            b"Subject: a\nFrom b\n >From c\n\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn exactly_one_trailing_blank_line() {
+        assert_eq!(to_mbox_block(b"A: b\r\n", no_escape()), b"A: b\n\n".to_vec());
+        assert_eq!(to_mbox_block(b"A: b\r\n\r\n", no_escape()), b"A: b\n\n".to_vec());
+        assert_eq!(to_mbox_block(b"A: b", no_escape()), b"A: b\n\n".to_vec());
+        assert_eq!(to_mbox_block(b"", no_escape()), b"\n".to_vec());
+    }
+
+    #[test]
+    fn empty_lines_are_kept() {
+        assert_eq!(
+            to_mbox_block(b"A: b\r\n\r\nFrom c\r\n\r\n", MboxOptions::default()),
+            b"A: b\n\n>From c\n\n".to_vec()
+        );
+        assert_eq!(to_mbox_block(b"A: b\r\n\r\n\r\nC: d\r\n", no_escape()), b"A: b\n\n\nC: d\n\n".to_vec());
+    }
+
+    /// A component which (wrongly) writes its text without any checks.
+    #[derive(Debug, Clone)]
+    pub struct Unchecked(&'static str);
+
+    impl EncodableInHeader for Unchecked {
+        fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+            handle.write_str_unchecked(self.0)?;
+            Ok(())
+        }
+
+        fn boxed_clone(&self) -> Box<EncodableInHeader> {
+            Box::new(self.clone())
+        }
+    }
+
+    mod unchecked_headers {
+        def_headers! {
+            test_name: validate_header_names,
+            scope: super,
+            XUnchecked, unchecked { "X-Unchecked" }, Unchecked, multi, None
+        }
+    }
+    use self::unchecked_headers::XUnchecked;
+
+    test!(encode_for_mbox_escapes_from_lines_and_keeps_empty_lines {
+        let mut map = headers! {
+            Subject: "hy there"
+        }?;
+        map.insert(XUnchecked::body(Unchecked("a\r\nFrom evil\r\n\r\nb")));
+
+        let block = map.encode_for_mbox(MailType::Ascii, MboxOptions::default())?;
+        assert_eq!(
+            String::from_utf8(block)?,
+            "Subject: hy there\nX-Unchecked: a\n>From evil\n\nb\n\n"
+        );
+
+        let block = map.encode_for_mbox(MailType::Ascii, no_escape())?;
+        assert_eq!(
+            String::from_utf8(block)?,
+            "Subject: hy there\nX-Unchecked: a\nFrom evil\n\nb\n\n"
+        );
+    });
+
+    test!(empty_map {
+        let block = HeaderMap::new().encode_for_mbox(MailType::Ascii, MboxOptions::default())?;
+        assert_eq!(block, b"\n".to_vec());
+    });
+}
@@ -0,0 +1,211 @@
+use std::cmp;
+
+use ::error::LimitViolation;
+
+use super::HeaderMap;
+
+/// How `HeaderMap::combine_with` handles "max one" headers present in both maps.
+///
+/// Headers which are not "max one" (e.g. `Comments`) are always appended.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MergePolicy {
+    /// Appends all headers, which can leave multiple "max one" headers in the map.
+    ///
+    /// Such a map fails `HeaderMap::validate`.
+    AppendAll,
+    /// The "max one" headers of the other map replace the ones in this map.
+    ///
+    /// This is what `insert_all` does.
+    OtherWins,
+    /// The "max one" headers of the other map are dropped if this map already has one.
+    SelfWins
+}
+
+impl HeaderMap {
+
+    /// Inserts all headers of the other map, resolving conflicts with given policy.
+    ///
+    /// Whether or not a header is "max one" is decided by `is_max_one` of
+    /// the header in `other`. The headers are inserted in the order of
+    /// `other`, for `SelfWins` a header is dropped if this map contains a
+    /// header with the same name at the time it is inserted. Like with
+    /// `insert_all` the inserted headers keep their sequence numbers (and
+    /// labels) and the validators of `other` are added to this map.
+    ///
+    /// Like with `insert_all` no header is inserted if this would exceed
+    /// the limits of the map (see `take_limit_violation`). Use
+    /// `try_combine_with` to get the `LimitViolation` directly.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    /// use mail_headers::map::MergePolicy;
+    ///
+    /// let mut map = headers! {
+    ///     Subject: "mine",
+    ///     Comments: "a"
+    /// }.unwrap();
+    ///
+    /// map.combine_with(headers! {
+    ///     Subject: "theirs",
+    ///     Comments: "b"
+    /// }.unwrap(), MergePolicy::SelfWins);
+    ///
+    /// assert_eq!(map.get_single(Subject).unwrap().unwrap().as_str(), "mine");
+    /// assert_eq!(map.get(Comments).count(), 2);
+    /// # }
+    /// ```
+    pub fn combine_with(&mut self, other: HeaderMap, policy: MergePolicy) {
+        let res = self.insert_atomically(|map| map.combine_until_violation(other, policy));
+        self.record_violation(res);
+    }
+
+    /// Like `combine_with` but fails instead of exceeding the limits of this map.
+    ///
+    /// This behaves like `try_insert_all`: headers are inserted until one
+    /// would exceed a limit, or none are inserted if the limits have
+    /// `atomic_insert_all` set. Dropped headers (see `SelfWins`) are not
+    /// counted in the returned number of inserted headers.
+    pub fn try_combine_with(&mut self, other: HeaderMap, policy: MergePolicy)
+        -> Result<usize, LimitViolation>
+    {
+        if self.limits.atomic_insert_all {
+            self.insert_atomically(|map| map.combine_until_violation(other, policy))
+        } else {
+            self.combine_until_violation(other, policy)
+        }
+    }
+
+    /// The non atomic `try_combine_with`.
+    fn combine_until_violation(&mut self, other: HeaderMap, policy: MergePolicy)
+        -> Result<usize, LimitViolation>
+    {
+        if policy == MergePolicy::OtherWins {
+            return self.insert_all_until_violation(other);
+        }
+
+        let HeaderMap { inner_map, seqs, max_seq, validators, validator_stages, labels, .. } = other;
+        for (validator, stages) in validators.into_iter().zip(validator_stages) {
+            self.add_staged_validator(validator, stages);
+        }
+        let mut accepted = 0;
+        for ((name, header), seq) in inner_map.into_iter().zip(seqs) {
+            let max_one = header.is_max_one();
+            if max_one && policy == MergePolicy::SelfWins && self.contains(name) {
+                continue;
+            }
+            // `AppendAll` adds "max one" headers without replacing
+            self._try_insert_with_seq(name, false, header, seq, accepted)?;
+            self.copy_label(&labels, seq);
+            accepted += 1;
+        }
+        self.max_seq = cmp::max(self.max_seq, max_seq);
+        Ok(accepted)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::headers::*;
+    use super::*;
+
+    fn base() -> HeaderMap {
+        headers! {
+            Subject: "mine",
+            Comments: "a"
+        }.unwrap()
+    }
+
+    fn other() -> HeaderMap {
+        headers! {
+            Comments: "b",
+            Subject: "theirs"
+        }.unwrap()
+    }
+
+    fn subjects(map: &HeaderMap) -> Vec<&str> {
+        map.get(Subject).map(|subject| subject.unwrap().as_str()).collect()
+    }
+
+    fn comments(map: &HeaderMap) -> Vec<&str> {
+        map.get(Comments).map(|comment| comment.unwrap().as_str()).collect()
+    }
+
+    test!(append_all {
+        let mut map = base();
+        map.combine_with(other(), MergePolicy::AppendAll);
+
+        assert_eq!(subjects(&map), vec!["mine", "theirs"]);
+        assert_eq!(comments(&map), vec!["a", "b"]);
+        assert_err!(map.validate());
+    });
+
+    test!(other_wins {
+        let mut map = base();
+        map.combine_with(other(), MergePolicy::OtherWins);
+
+        assert_eq!(subjects(&map), vec!["theirs"]);
+        assert_eq!(comments(&map), vec!["a", "b"]);
+        assert_ok!(map.validate());
+    });
+
+    test!(self_wins {
+        let mut map = base();
+        map.combine_with(other(), MergePolicy::SelfWins);
+
+        assert_eq!(subjects(&map), vec!["mine"]);
+        assert_eq!(comments(&map), vec!["a", "b"]);
+        assert_ok!(map.validate());
+
+        // without a conflict the header is taken from the other map
+        let mut map = headers! { Comments: "a" }?;
+        map.combine_with(other(), MergePolicy::SelfWins);
+        assert_eq!(subjects(&map), vec!["theirs"]);
+    });
+
+    test!(order_is_kept {
+        let mut map = base();
+        map.combine_with(other(), MergePolicy::AppendAll);
+        let names = map.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Subject", "Comments", "Comments", "Subject"]);
+
+        let mut map = base();
+        map.combine_with(other(), MergePolicy::SelfWins);
+        let names = map.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Subject", "Comments", "Comments"]);
+    });
+
+    test!(limits_are_honored {
+        use ::map::MapLimits;
+        use ::error::LimitKind;
+
+        let limits = MapLimits { max_headers: Some(3), ..Default::default() };
+        let mut map = HeaderMap::with_limits(limits);
+        map.try_combine_with(base(), MergePolicy::AppendAll)?;
+        let err = assert_err!(map.try_combine_with(other(), MergePolicy::AppendAll));
+        assert_eq!(err.kind(), LimitKind::HeaderCount);
+        assert_eq!(err.accepted(), 1);
+        assert_eq!(comments(&map), vec!["a", "b"]);
+
+        let mut map = HeaderMap::with_limits(MapLimits { atomic_insert_all: true, ..limits });
+        map.try_combine_with(base(), MergePolicy::AppendAll)?;
+        let err = assert_err!(map.try_combine_with(other(), MergePolicy::AppendAll));
+        assert_eq!(err.accepted(), 0);
+        assert_eq!(comments(&map), vec!["a"]);
+
+        // the dropped subject is not counted
+        assert_eq!(map.try_combine_with(other(), MergePolicy::SelfWins)?, 1);
+        assert_eq!(map.len(), 3);
+
+        // `combine_with` inserts nothing instead of panicking
+        let mut map = HeaderMap::with_limits(limits);
+        map.combine_with(base(), MergePolicy::AppendAll);
+        map.combine_with(other(), MergePolicy::AppendAll);
+        assert_eq!(comments(&map), vec!["a"]);
+        assert_eq!(map.take_limit_violation().unwrap().accepted(), 0);
+    });
+}
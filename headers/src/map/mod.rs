@@ -3,10 +3,11 @@
 //! It also contains some helper types like iterator types
 //! for the HeaderMap etc.
 use std::marker::PhantomData;
+use std::mem;
 use std::iter::ExactSizeIterator;
 use std::fmt::{self, Debug};
-use std::collections::HashSet;
-use std::cmp::PartialEq;
+use std::collections::{HashMap, HashSet};
+use std::cmp::{self, PartialEq};
 use std::hash::{Hash, Hasher};
 
 use total_order_multi_map::{
@@ -16,8 +17,13 @@ use total_order_multi_map::{
     EntryValuesMut
 };
 
+use internals::MailType;
+use internals::error::EncodingError;
+
 use ::error::{
+    LimitViolation,
     HeaderTypeError,
+    HeaderExpectationError,
     HeaderValidationError,
     BuildInValidationError
 };
@@ -29,12 +35,217 @@ use ::name::{
 use ::header::{
     Header, HeaderKind,
     HeaderObj, HeaderObjTrait,
-    MaxOneMarker
+    MaxOneMarker, uncached_value
 };
 
+// defined before the submodules so that they can use it, too
+/// Create a header map from a list of header's with ther fields
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate mail_headers;
+/// # use mail_headers::headers::*;
+/// # use mail_headers::error::ComponentCreationError;
+/// # fn main() { (|| -> Result<(), ComponentCreationError> {
+/// let map = headers! {
+///     _From: ["bobo@nana.test"],
+///     Subject: "hy there"
+/// }?;
+/// # Ok(()) })(); }
+/// ```
+///
+/// A trailing comma after the last header is allowed. To add headers to an
+/// existing map use `headers_into!`.
+#[macro_export]
+macro_rules! headers {
+    ($($header:ty : $val:expr),* $(,)*) => ({
+        //FIXME[rust/catch block] use catch block once available
+        (|| -> Result<$crate::HeaderMap, $crate::error::ComponentCreationError>
+        {
+            let mut map = $crate::HeaderMap::new();
+            $(
+                map.insert(<$header as $crate::HeaderKind>::auto_body($val)?);
+            )*
+            Ok(map)
+        })()
+    });
+}
+
+/// Adds a list of headers to an existing header map.
+///
+/// Takes a `&mut HeaderMap` followed by the headers in the same syntax
+/// as `headers!` and returns a `Result<(), ComponentCreationError>`. The
+/// headers are added with `HeaderMap::try_insert`, i.e. a "max one" header
+/// replaces one already in the map.
+///
+/// If the map was created with `HeaderMap::with_limits` and adding a header
+/// would exceed a limit this fails with a `ComponentCreationError` (for the
+/// component `"HeaderMap"`) caused by the `LimitViolation`.
+///
+/// If creating or adding a header fails the headers listed before it have
+/// already been added to the map.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate mail_headers;
+/// # use mail_headers::headers::*;
+/// # use mail_headers::error::ComponentCreationError;
+/// # fn main() { (|| -> Result<(), ComponentCreationError> {
+/// let mut map = headers! {
+///     _From: ["bobo@nana.test"],
+///     Subject: "template"
+/// }?;
+///
+/// headers_into!(&mut map, {
+///     _To: ["kiki@nana.test"],
+///     Subject: "hy there",
+/// })?;
+///
+/// assert_eq!(map.len(), 3);
+/// # Ok(()) })(); }
+/// ```
+#[macro_export]
+macro_rules! headers_into {
+    ($map:expr, { $($header:ty : $val:expr),* $(,)* }) => ({
+        let map: &mut $crate::HeaderMap = $map;
+        //FIXME[rust/catch block] use catch block once available
+        (|| -> Result<(), $crate::error::ComponentCreationError>
+        {
+            $(
+                map.try_insert(<$header as $crate::HeaderKind>::auto_body($val)?)
+                    .map_err(|violation| {
+                        $crate::error::ComponentCreationError::from_parent(violation, "HeaderMap")
+                    })?;
+            )*
+            Ok(())
+        })()
+    });
+}
+
 mod into_iter;
 pub use self::into_iter::*;
 
+mod name_index;
+pub use self::name_index::NameIndex;
+
+mod name_queries;
+
+mod redaction;
+pub use self::redaction::{RedactionPolicy, RedactionMode};
+
+mod pseudonymize;
+pub use self::pseudonymize::PseudonymizeOptions;
+
+mod date_sanity;
+pub use self::date_sanity::{validate_date_sanity, DateSanityOptions};
+
+mod string_pairs;
+pub use self::string_pairs::{PairParser, PairRegistry};
+
+mod raw_header;
+pub use self::raw_header::{RawHeader, MAX_RUNTIME_NAMES};
+
+mod validation_inputs;
+pub use self::validation_inputs::{ValidationInputSpec, ValidationInputs};
+
+mod display_name_policy;
+pub use self::display_name_policy::{
+    make_display_name_policy_validator, DisplayNamePolicy, DomainNamePolicy, NamePattern, SenderNames
+};
+
+mod mbox;
+pub use self::mbox::MboxOptions;
+
+mod injection_guard;
+pub use self::injection_guard::InjectionGuard;
+
+mod encode;
+pub use self::encode::{EncodeIssue, EncodeIssueKind};
+
+mod block;
+pub use self::block::{BlockTerminator, TrailingBytes, BlockParseOptions};
+
+#[cfg(feature="compress-values")]
+mod compress;
+#[cfg(feature="compress-values")]
+pub use self::compress::CompressedRaw;
+
+mod downgrade;
+pub use self::downgrade::{DowngradeReport, DowngradeEntry, HeaderDowngrade, DowngradeMechanism};
+
+mod encode_session;
+pub use self::encode_session::EncodeSession;
+
+mod display;
+pub use self::display::HeaderMapDisplay;
+
+mod public_headers;
+pub use self::public_headers::{is_builtin_name, PublicHeadersOptions};
+
+mod seq;
+pub use self::seq::{HeaderSeq, IterWithSeq};
+use self::seq::next_seq;
+
+mod limits;
+pub use self::limits::MapLimits;
+
+mod validator_options;
+pub use self::validator_options::ValidatorOptions;
+
+mod validator_carry;
+pub use self::validator_carry::ValidatorCarryPolicy;
+
+mod validation_stage;
+pub use self::validation_stage::{ValidationStage, ValidatorStages};
+
+mod ordering;
+pub use self::ordering::OrderingPolicy;
+
+mod repair;
+pub use self::repair::{RepairStrategy, RepairAction, RepairProblem, RepairReport};
+
+mod subset;
+pub use self::subset::{SubsetMode, SubsetViolation};
+
+mod retain;
+
+mod grouped;
+pub use self::grouped::{Grouped, GroupedValues, IterGrouped};
+
+mod drain;
+pub use self::drain::Drain;
+
+mod take;
+
+mod merge;
+pub use self::merge::MergePolicy;
+
+mod labels;
+pub use self::labels::IterWithLabels;
+
+mod resent;
+pub use self::resent::{ResentBlocks, ResentBlock};
+
+mod recipients;
+pub use self::recipients::{RecipientCountMode, AddressDedupRules};
+
+mod view;
+pub use self::view::{
+    HeaderMapView, HeaderQuery, HeaderQueryValidator, ViewBodies, ViewIter
+};
+
+mod legacy;
+pub use self::legacy::LegacyValue;
+
+mod list_rewrite;
+pub use self::list_rewrite::{
+    rewrite_from_for_list, ListRewriteOptions, ReplyToMode, RewriteReport, RewriteChange
+};
+
 /// The type of an validator used to check more complex header contraints.
 ///
 /// An example constraint would be if a `From` header field contains more than
@@ -134,10 +345,61 @@ pub type HeaderMapValidator = fn(&HeaderMap) -> Result<(), ::error::HeaderValida
 /// **So yes, you can not relay on the "max one" constraints
 ///   to be uphold without running the validators**
 ///
+/// # Ordering
+///
+/// The map keeps the headers in a total order, which is the order in
+/// which they are iterated and encoded:
+///
+/// - `insert` appends the header at the end of the map, for "max one"
+///   headers all other headers with the same name are removed first (so
+///   a replaced header does *not* keep its position)
+/// - `insert_at` and `prepend` behave like `insert` but place the header at
+///   the given position
+/// - `insert_all` inserts the headers of the other map in order, each like `insert`
+///   (`combine_with` appends them in order, too, but might keep or drop "max one"
+///   headers instead of replacing them, see `MergePolicy`)
+/// - `remove`, `remove_at`, `retain`, `take` and `drain_name` only remove headers
+///
+/// No operation changes the relative order of the headers it doesn't remove,
+/// and all methods returning the bodies of a header name (e.g. `get_untyped`)
+/// return them in map order. This contract is checked against a simple
+/// reference model in `tests/ordering.rs`.
+///
+/// # Equality
 ///
+/// The header bodies are trait objects which can not be compared with
+/// each other, so `HeaderMap` does not implement `PartialEq`. Use
+/// `eq_by_encoding` to compare two maps instead:
+///
+/// ```compile_fail
+/// # extern crate mail_headers;
+/// use mail_headers::HeaderMap;
+///
+/// # fn main() {
+/// assert!(HeaderMap::new() == HeaderMap::new());
+/// # }
+/// ```
 #[derive(Clone)]
 pub struct HeaderMap {
     inner_map: TotalOrderMultiMap<HeaderName, Box<HeaderObj>>,
+    /// the sequence numbers of the headers in `inner_map` (in the same order)
+    seqs: Vec<HeaderSeq>,
+    /// the highest sequence number ever inserted into this map
+    max_seq: HeaderSeq,
+    limits: MapLimits,
+    validator_options: ValidatorOptions,
+    /// which validators of the headers are run, see `ValidatorCarryPolicy`
+    validator_carry: ValidatorCarryPolicy,
+    /// validators added with `add_validator` (without duplicates)
+    validators: Vec<HeaderMapValidator>,
+    /// the stages the validators in `validators` are run in (in the same order)
+    validator_stages: Vec<ValidatorStages>,
+    /// the (encoded) size of all headers, only tracked if `limits.max_bytes` is set
+    used_bytes: usize,
+    /// the labels given to headers by `combine_labeled` (keyed by sequence number)
+    labels: HashMap<HeaderSeq, &'static str>,
+    /// the last violation of a insertion method which can not fail, see `take_limit_violation`
+    rejected: Option<LimitViolation>
 }
 
 pub type Iter<'a> = total_order_multi_map::Iter<'a, HeaderName, Box<HeaderObj>>;
@@ -145,11 +407,87 @@ pub type IterMut<'a> = total_order_multi_map::IterMut<'a, HeaderName, Box<Header
 pub type Values<'a> = total_order_multi_map::Values<'a, HeaderName, Box<HeaderObj>>;
 pub type ValuesMut<'a> = total_order_multi_map::ValuesMut<'a, HeaderName, Box<HeaderObj>>;
 
+/// Values longer than this many chars are truncated in the alternate `Debug` form.
+const DEBUG_MAX_VALUE_CHARS: usize = 120;
+
+/// Names with more headers than this are elided in the alternate `Debug` form.
+const DEBUG_MAX_HEADERS_PER_NAME: usize = 5;
+
+/// The compact form (`{:?}`) only lists the number of headers and the
+/// header names with their count, e.g.
+/// `HeaderMap { 12 headers: From(1), To(1), Received(10) }`, which
+/// keeps it short even for large maps.
+///
+/// With the alternate flag (`{:#?}`) one header is printed per line.
+///
+/// In the alternate form the (unfolded) encoded value of the headers is
+/// printed, encoded for a internationalized mail so that non us-ascii
+/// text stays readable. Headers which can not be encoded are printed
+/// with the `Debug` form of their component instead. Use `display` to
+/// get the encoded header block for a specific mail type.
+///
+/// Values longer than 120 chars are cut off after 120 chars followed by
+/// `... (<n> chars)` where `<n>` is the length of the full value. If there
+/// are more than 5 headers with the same name only the first two and the
+/// last one are printed, the other ones are replaced by a single
+/// `<name>: ... (<k> more)` line at the place of the first elided header.
+///
+/// In both forms "max one" headers appearing more than once (see
+/// `duplicate_max_one_names`) are flagged with `[duplicate max one]`
+/// after their name.
 impl Debug for HeaderMap {
     fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
-        write!(fter, "HeaderMap {{ ")?;
-        for (key, val_cont) in self.iter() {
-            write!(fter, "{}: {:?},", key.as_str(), val_cont)?;
+        let duplicates = self.duplicate_max_one_names();
+        let flag = |name: &HeaderName| {
+            if duplicates.contains(name) { " [duplicate max one]" } else { "" }
+        };
+
+        let mut order = Vec::new();
+        let mut counts = HashMap::new();
+        for (name, _) in self.iter() {
+            let count = counts.entry(name).or_insert(0);
+            if *count == 0 {
+                order.push(name);
+            }
+            *count += 1;
+        }
+
+        if fter.alternate() {
+            fter.write_str("HeaderMap {\n")?;
+            let mut seen = HashMap::new();
+            for (name, obj) in self.iter() {
+                let total = counts[&name];
+                let nth = {
+                    let nth = seen.entry(name).or_insert(0);
+                    *nth += 1;
+                    *nth
+                };
+                if total > DEBUG_MAX_HEADERS_PER_NAME && nth > 2 && nth < total {
+                    if nth == 3 {
+                        writeln!(fter, "    {}{}: ... ({} more)", name.as_str(), flag(&name), total - 3)?;
+                    }
+                    continue;
+                }
+
+                // not `encoded_value` to not fill the cache of the header
+                let value = match uncached_value(&**obj, MailType::Internationalized) {
+                    Ok(value) => String::from_utf8_lossy(&value).replace("\r\n", ""),
+                    Err(_) => format!("{:?}", obj)
+                };
+                write!(fter, "    {}{}: ", name.as_str(), flag(&name))?;
+                match value.char_indices().nth(DEBUG_MAX_VALUE_CHARS) {
+                    Some((end, _)) => writeln!(fter, "{}... ({} chars)", &value[..end], value.chars().count())?,
+                    None => writeln!(fter, "{}", value)?
+                }
+            }
+            return fter.write_str("}");
+        }
+
+        let len = self.len();
+        write!(fter, "HeaderMap {{ {} header{}", len, if len == 1 { "" } else { "s" })?;
+        for (idx, name) in order.iter().enumerate() {
+            let sep = if idx == 0 { ":" } else { "," };
+            write!(fter, "{} {}({}){}", sep, name.as_str(), counts[name], flag(name))?;
         }
         write!(fter, " }}")
     }
@@ -158,7 +496,17 @@ impl Debug for HeaderMap {
 impl Default for HeaderMap {
     fn default() -> Self {
         HeaderMap {
-            inner_map: Default::default()
+            inner_map: Default::default(),
+            seqs: Vec::new(),
+            max_seq: 0,
+            limits: MapLimits::default(),
+            validator_options: ValidatorOptions::default(),
+            validator_carry: ValidatorCarryPolicy::default(),
+            validators: Vec::new(),
+            validator_stages: Vec::new(),
+            used_bytes: 0,
+            labels: HashMap::new(),
+            rejected: None
         }
     }
 }
@@ -170,16 +518,50 @@ impl HeaderMap {
         Default::default()
     }
 
+    /// create a new empty header map with space for at least `capacity` headers
+    ///
+    /// Inserting up to `capacity` headers does not reallocate the map,
+    /// which is useful if the number of headers is known in advance (e.g.
+    /// when creating many mails from the same template).
+    pub fn with_capacity(capacity: usize) -> Self {
+        HeaderMap {
+            inner_map: TotalOrderMultiMap::with_capacity(capacity),
+            seqs: Vec::with_capacity(capacity),
+            ..Default::default()
+        }
+    }
+
     /// returns the number of headers in this map
     pub fn len(&self) -> usize {
         self.inner_map.len()
     }
 
+    /// returns true if the map contains no headers
+    pub fn is_empty(&self) -> bool {
+        self.inner_map.len() == 0
+    }
+
+    /// returns the number of headers the map can hold without reallocating
+    pub fn capacity(&self) -> usize {
+        cmp::min(self.inner_map.capacity(), self.seqs.capacity())
+    }
+
+    /// reserves space for at least `additional` more headers
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner_map.reserve(additional);
+        self.seqs.reserve(additional);
+    }
+
     /// clears the header map
     ///
     /// This removes all headers _and_ all validators
     pub fn clear(&mut self) {
         self.inner_map.clear();
+        self.seqs.clear();
+        self.validators.clear();
+        self.validator_stages.clear();
+        self.used_bytes = 0;
+        self.labels.clear();
     }
 
     /// Iterate over all `HeaderObj` added to the map.
@@ -188,6 +570,29 @@ impl HeaderMap {
     }
 
     /// Iterate with mut refs over all `HeaderObj` added to the map.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::HeaderKind;
+    /// use mail_headers::headers::*;
+    ///
+    /// let mut map = headers! {
+    ///     Subject: "hy there",
+    ///     Comments: "a"
+    /// }.unwrap();
+    ///
+    /// for obj in map.values_mut() {
+    ///     if let Some(comment) = obj.downcast_mut::<Comments>() {
+    ///         *comment = Comments::auto_body("b").unwrap();
+    ///     }
+    /// }
+    /// assert_eq!(map.get(Comments).next().unwrap().unwrap().as_str(), "b");
+    /// # }
+    /// ```
     pub fn values_mut(&mut self) -> ValuesMut {
         self.inner_map.values_mut()
     }
@@ -197,12 +602,41 @@ impl HeaderMap {
     /// If multiple Headers provide the same contextual validator (e.g. the resent headers)
     /// it's still only called once.
     ///
+    /// The validators added with `add_validator` are called after the validators
+    /// of the headers (in the order they were added), skipping any validator
+    /// which was already called for a header.
+    ///
     /// # Max One
     ///
     /// This will also validate that for any header name for which a header
     /// was added with `MAX_ONE == true` it will be validated that it is the
     /// only header for that header name.
+    ///
+    /// # Carry Policy
+    ///
+    /// The validators of headers not carried by the maps
+    /// `ValidatorCarryPolicy` are skipped (the max one check is still done).
+    ///
+    /// # Stage
+    ///
+    /// This validates the map as a final message, i.e. it is the same as
+    /// `use_contextual_validators_staged(ValidationStage::Final)`.
     pub fn use_contextual_validators(&self) -> Result<(), HeaderValidationError> {
+        self.use_contextual_validators_staged(ValidationStage::Final)
+    }
+
+    /// Like `use_contextual_validators` but only runs the checks applying to the given stage.
+    ///
+    /// In the `Final` stage all validators are run. In the `Draft` stage
+    /// only the structural checks are done, i.e. the max one consistency
+    /// check and, instead of the validators of the headers, the check that
+    /// the headers use the component expected by them (see
+    /// `BuildInValidationError::UnexpectedComponentType`). Validators
+    /// added with `add_validator` are only run in the `Draft` stage if
+    /// they were added with `ValidatorStages::AllStages`.
+    pub fn use_contextual_validators_staged(&self, stage: ValidationStage)
+        -> Result<(), HeaderValidationError>
+    {
         let mut seen_validators = HashSet::new();
 
         let mut validate = |validator| -> Result<(), HeaderValidationError> {
@@ -214,26 +648,179 @@ impl HeaderMap {
             Ok(())
         };
 
+        let is_final = stage == ValidationStage::Final;
         for mut group in self.inner_map.group_iter() {
             let first = group.next().expect("[BUG] returned header without any headers inserted for it");
             let max_one = first.is_max_one();
-            validate(first.validator())?;
-            let header_name = group.key().as_str();
+            let carried = self.validator_carry.carries(first.name());
+            let mut has_validator = first.validator().is_some();
+            if carried && is_final {
+                validate(first.validator())?;
+            }
+            let name = *group.key();
             for other in group {
                 if max_one != other.is_max_one() {
-                     return Err(BuildInValidationError::MaxOneInconsistency { header_name }.into());
+                     return Err(BuildInValidationError::MaxOneInconsistency {
+                         header_name: name.as_str()
+                     }.into());
                 }
-                validate(other.validator())?;
+                has_validator |= other.validator().is_some();
+                if carried && is_final {
+                    validate(other.validator())?;
+                }
+            }
+            if carried && !is_final && has_validator {
+                ::headers::validators::component_type(self, name)?;
+            }
+        }
+        for (validator, stages) in self.validators.iter().zip(self.validator_stages.iter()) {
+            if stages.applies_to(stage) {
+                validate(Some(*validator))?;
             }
         }
         Ok(())
     }
 
+    /// Adds a validator to this map which is run by `use_contextual_validators`.
+    ///
+    /// This is meant for constraints which belong to the map (e.g. a application
+    /// policy like "outgoing mails need a `List-Unsubscribe` header") and not
+    /// to a header kind. Adding the same validator multiple times has no effect.
+    ///
+    /// The validators are carried over by `insert_all` and removed by `clear`.
+    /// They are only run when validating a final message, use
+    /// `add_staged_validator` for validators which also apply to drafts.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # extern crate failure;
+    /// # fn main() {
+    /// use mail_headers::HeaderMap;
+    /// use mail_headers::headers::*;
+    /// use mail_headers::error::HeaderValidationError;
+    ///
+    /// fn subject_required(map: &HeaderMap) -> Result<(), HeaderValidationError> {
+    ///     if map.contains(Subject) {
+    ///         Ok(())
+    ///     } else {
+    ///         Err(HeaderValidationError::Custom(failure::err_msg("Subject is required")))
+    ///     }
+    /// }
+    ///
+    /// let mut map = headers! {
+    ///     _From: ["me@example.com"]
+    /// }.unwrap();
+    /// map.add_validator(subject_required);
+    /// assert!(map.use_contextual_validators().is_err());
+    ///
+    /// map.insert(Subject::auto_body("hy there").unwrap());
+    /// assert!(map.use_contextual_validators().is_ok());
+    /// # }
+    /// ```
+    pub fn add_validator(&mut self, validator: HeaderMapValidator) {
+        self.add_staged_validator(validator, ValidatorStages::FinalOnly)
+    }
+
+    /// Returns the validators added with `add_validator`.
+    pub fn validators(&self) -> &[HeaderMapValidator] {
+        &self.validators
+    }
+
+    /// Checks the max one constraints and then runs the contextual validators.
+    ///
+    /// Headers are normally inserted using a replacing insert if they are
+    /// max one, but a map can still end up with multiple of them (e.g. when
+    /// created with `from_vec`). This checks for every header name for which
+    /// the headers are max one that there is only one header, independent
+    /// of whether or not a validator is registered for the header, and then
+    /// calls `use_contextual_validators`.
+    ///
+    /// # Error
+    ///
+    /// Returns the first error, i.e. `MoreThenOne` for the first header name
+    /// (in map order) violating the max one constraint or the first error of
+    /// `use_contextual_validators`.
+    pub fn validate(&self) -> Result<(), HeaderValidationError> {
+        self.validate_staged(ValidationStage::Final)
+    }
+
+    /// Like `validate` but calls `use_contextual_validators_staged` with the given stage.
+    ///
+    /// The max one constraints are checked in all stages.
+    pub fn validate_staged(&self, stage: ValidationStage) -> Result<(), HeaderValidationError> {
+        if let Some(name) = self.duplicate_max_one_names().first() {
+            return Err(BuildInValidationError::MoreThenOne {
+                header_name: name.as_str()
+            }.into());
+        }
+        self.use_contextual_validators_staged(stage)
+    }
+
+    /// Returns the names of all "max one" headers which appear more than once.
+    ///
+    /// A header name is returned if the first header with it is "max one"
+    /// (see `HeaderObjTrait::is_max_one`) and there is more than one header
+    /// with it, e.g. because the map was created with `from_vec` or by
+    /// merging parsed maps. The names are returned in map order (of their
+    /// first header). This is the check `validate` does first.
+    pub fn duplicate_max_one_names(&self) -> Vec<HeaderName> {
+        let mut duplicates = Vec::new();
+        for mut group in self.inner_map.group_iter() {
+            let first = group.next().expect("[BUG] returned header without any headers inserted for it");
+            if first.is_max_one() && group.next().is_some() {
+                duplicates.push(first.name());
+            }
+        }
+        duplicates
+    }
+
     /// Returns true if this map contains a header with the given name.
     pub fn contains<H: HasHeaderName>(&self, name: H) -> bool {
         self.inner_map.contains_key(name.get_name())
     }
 
+    /// Returns true if this map contains a header of the given kind.
+    ///
+    /// Unlike `contains` this only returns true if at least one of the
+    /// headers with the name of `H` is a `Header<H>` (and not a header of
+    /// another implementation with the same name).
+    pub fn contains_kind<H: HeaderKind>(&self) -> bool {
+        self.get_untyped(H::name()).any(|obj| obj.is::<H>())
+    }
+
+    /// Returns a `HeaderTypeError` if a header with the name of `H` is not a `Header<H>`.
+    ///
+    /// This is the error `get`, `get_single` and similar would return when
+    /// reaching such a header, so it can be used to check the map before
+    /// calling them. Returns `None` if all headers with the name of `H` are
+    /// of the given kind (including if there are none).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    ///
+    /// let map = headers! {
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// assert!(map.contains_kind::<Subject>());
+    /// assert!(map.kind_mismatch::<Subject>().is_none());
+    /// assert!(!map.contains_kind::<Comments>());
+    /// # }
+    /// ```
+    pub fn kind_mismatch<H: HeaderKind>(&self) -> Option<HeaderTypeError> {
+        self.get_untyped(H::name())
+            .find(|obj| !obj.is::<H>())
+            .map(|obj| obj.type_error::<H>())
+    }
+
     /// Returns the single header associated with the given header kind.
     ///
     /// As this uses the `MaxOneMarker` trait which _should_ only be implemented
@@ -259,6 +846,30 @@ impl HeaderMap {
     ///   in some rare situations lead to be there more then one header for
     ///   a "max one" header in the map, in which case a `HeaderTypeError`
     ///   is returned.
+    ///
+    /// # Example
+    ///
+    /// The outer `Option` tells if there is such a header, the inner
+    /// `Result` if it has the expected type:
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    ///
+    /// let map = headers! {
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// match map.get_single(Subject) {
+    ///     Some(Ok(subject)) => assert_eq!(subject.as_str(), "hy there"),
+    ///     Some(Err(type_error)) => panic!("other Subject implementation: {}", type_error),
+    ///     None => panic!("no Subject")
+    /// }
+    /// assert!(map.get_single(Sender).is_none());
+    /// # }
+    /// ```
     #[inline]
     pub fn get_single<'a, H>(&'a self, _type_hint: H)
         -> Option<Result<&'a Header<H>, HeaderTypeError>>
@@ -271,6 +882,28 @@ impl HeaderMap {
     ///
     /// Normally using `get_single` is more ergonomic, except if you write a function
     /// which abstracts over it in which case using `_get_single` can be better.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::{HeaderMap, MaxOneMarker};
+    /// use mail_headers::headers::*;
+    ///
+    /// fn has_valid<H: MaxOneMarker>(map: &HeaderMap) -> bool {
+    ///     map._get_single::<H>().map(|res| res.is_ok()).unwrap_or(false)
+    /// }
+    ///
+    /// let map = headers! {
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// assert!(has_valid::<Subject>(&map));
+    /// assert!(!has_valid::<Sender>(&map));
+    /// # }
+    /// ```
     pub fn _get_single<'a, H>(&'a self)
         -> Option<Result<&'a Header<H>, HeaderTypeError>>
         where H: MaxOneMarker
@@ -280,24 +913,84 @@ impl HeaderMap {
             return Some(Err(HeaderTypeError::new(H::name())))
         }
 
-        bodies.next().map(|untyped| {
-            untyped.downcast_ref::<H>()
-                .ok_or_else(|| HeaderTypeError::new(H::name()))
-        })
+        bodies.next().map(|untyped| untyped.try_downcast_ref::<H>())
     }
 
-    /// Returns a a mutable reference to the header associated with the given header kind.__internals
+    /// Returns the single header associated with the given header kind, which has to be in the map.
     ///
-    /// See `HeaderMap::get_single` for more details.
-    #[inline]
-    pub fn get_single_mut<H>(&mut self, _type_hint: H)
-        -> Option<Result<&mut Header<H>, HeaderTypeError>>
-        where H: MaxOneMarker
-    {
-        self._get_single_mut::<H>()
+    /// This is like `get_single` but treats a missing header as error, so
+    /// it can be used with a single `?`.
+    ///
+    /// # Error
+    ///
+    /// - `HeaderExpectationError::Missing` if there is no such header
+    /// - `HeaderExpectationError::Type` in the cases `get_single` returns a `HeaderTypeError`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    /// use mail_headers::error::HeaderExpectationError;
+    ///
+    /// let map = headers! {
+    ///     Subject: "Hi"
+    /// }.unwrap();
+    ///
+    /// assert_eq!(map.expect_single(Subject).unwrap().as_str(), "Hi");
+    /// match map.expect_single(Comments) {
+    ///     Err(HeaderExpectationError::Missing { name }) => assert_eq!(name.as_str(), "Comments"),
+    ///     other => panic!("unexpected: {:?}", other)
+    /// }
+    /// # }
+    /// ```
+    #[inline]
+    pub fn expect_single<'a, H>(&'a self, _type_hint: H)
+        -> Result<&'a Header<H>, HeaderExpectationError>
+        where H: MaxOneMarker
+    {
+        self._expect_single::<H>()
+    }
+
+    /// A variation of `expect_single` which doesn't require passing in a type hint.
+    pub fn _expect_single<'a, H>(&'a self)
+        -> Result<&'a Header<H>, HeaderExpectationError>
+        where H: MaxOneMarker
+    {
+        match self._get_single::<H>() {
+            Some(res) => Ok(res?),
+            None => Err(HeaderExpectationError::Missing { name: H::name() })
+        }
+    }
+
+    /// Returns the single header associated with the header kind of `default` or `default`.
+    ///
+    /// `default` is returned if the map contains no such header.
+    ///
+    /// # Error
+    ///
+    /// A `HeaderTypeError` is returned in the same cases as for `get_single`.
+    pub fn get_single_or<'a, H>(&'a self, default: &'a Header<H>)
+        -> Result<&'a Header<H>, HeaderTypeError>
+        where H: MaxOneMarker
+    {
+        self._get_single::<H>().unwrap_or(Ok(default))
     }
 
-    /// Returns a a mutable reference to the header associated with the given header kind.__internals
+    /// Returns a a mutable reference to the header associated with the given header kind.
+    ///
+    /// See `HeaderMap::get_single` for more details.
+    #[inline]
+    pub fn get_single_mut<H>(&mut self, _type_hint: H)
+        -> Option<Result<&mut Header<H>, HeaderTypeError>>
+        where H: MaxOneMarker
+    {
+        self._get_single_mut::<H>()
+    }
+
+    /// Returns a a mutable reference to the header associated with the given header kind.
     ///
     /// See `HeaderMap::_get_single` for more details.
     pub fn _get_single_mut<H>(&mut self)
@@ -309,10 +1002,7 @@ impl HeaderMap {
             return Some(Err(HeaderTypeError::new(H::name())))
         }
 
-        bodies.next().map(|untyped| {
-            untyped.downcast_mut::<H>()
-                .ok_or_else(|| HeaderTypeError::new(H::name()))
-        })
+        bodies.next().map(|untyped| untyped.try_downcast_mut::<H>())
     }
 
     /// Returns all header bodies for a given header name, without trying to cast them to a concrete type
@@ -328,11 +1018,88 @@ impl HeaderMap {
     ///
     /// Accepts both `HeaderName` or a type implementing `HeaderKind`.
     ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::HeaderKind;
+    /// use mail_headers::headers::*;
+    ///
+    /// let mut map = headers! {
+    ///     Comments: "a",
+    ///     Comments: "b"
+    /// }.unwrap();
+    ///
+    /// for obj in map.get_untyped_mut(Comments) {
+    ///     let comment = obj.downcast_mut::<Comments>().unwrap();
+    ///     *comment = Comments::auto_body("changed").unwrap();
+    /// }
+    /// assert!(map.get(Comments).all(|comment| comment.unwrap().as_str() == "changed"));
+    /// # }
+    /// ```
     #[inline]
     pub fn get_untyped_mut<H: HasHeaderName>(&mut self, name: H) -> UntypedBodiesMut {
         self.inner_map.get_mut(name.get_name())
     }
 
+    /// Returns the single header body for a given header name, without trying to cast it to a concrete type
+    ///
+    /// Accepts both `HeaderName` or a type implementing `HeaderKind`.
+    ///
+    /// Returns `Ok(None)` if there is no header with the given name.
+    ///
+    /// # Error
+    ///
+    /// Fails with `MoreThenOne` if there are multiple headers with the given
+    /// name, independent of whether or not the header is a "max one" header.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    ///
+    /// let map = headers! {
+    ///     Subject: "hy there",
+    ///     Comments: "a",
+    ///     Comments: "b"
+    /// }.unwrap();
+    ///
+    /// let subject = map.get_untyped_single(Subject).unwrap().unwrap();
+    /// assert_eq!(subject.name().as_str(), "Subject");
+    /// assert!(map.get_untyped_single(_To).unwrap().is_none());
+    /// assert!(map.get_untyped_single(Comments).is_err());
+    /// # }
+    /// ```
+    pub fn get_untyped_single<H: HasHeaderName>(&self, name: H)
+        -> Result<Option<&HeaderObj>, HeaderValidationError>
+    {
+        let name = name.get_name();
+        let mut bodies = self.get_untyped(name);
+        if bodies.len() > 1 {
+            return Err(more_then_one(name));
+        }
+        Ok(bodies.next().map(|obj| &**obj))
+    }
+
+    /// Returns the single header body for a given header name, without trying to cast it to a concrete type
+    ///
+    /// See `HeaderMap::get_untyped_single` for more details.
+    pub fn get_untyped_single_mut<H: HasHeaderName>(&mut self, name: H)
+        -> Result<Option<&mut HeaderObj>, HeaderValidationError>
+    {
+        let name = name.get_name();
+        let mut bodies = self.get_untyped_mut(name);
+        if bodies.len() > 1 {
+            return Err(more_then_one(name));
+        }
+        Ok(bodies.next().map(|obj| &mut **obj))
+    }
+
     /// Returns all header bodies for a given header
     #[inline(always)]
     pub fn get<H>(&self, _type_hint: H) -> TypedBodies<H>
@@ -349,6 +1116,31 @@ impl HeaderMap {
     }
 
     /// Returns all header bodies for a given header
+    ///
+    /// Each item is a `Result` as the header can have a different type if
+    /// multiple implementations for the same header name are used.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::HeaderKind;
+    /// use mail_headers::headers::*;
+    ///
+    /// let mut map = headers! {
+    ///     Comments: "a",
+    ///     Subject: "hy there",
+    ///     Comments: "b"
+    /// }.unwrap();
+    ///
+    /// for comment in map.get_mut(Comments) {
+    ///     *comment.unwrap() = Comments::auto_body("c").unwrap();
+    /// }
+    /// assert_eq!(map.get(Comments).filter(|comment| comment.is_ok()).count(), 2);
+    /// # }
+    /// ```
     #[inline(always)]
     pub fn get_mut<H>(&mut self, _type_hint: H) -> TypedBodiesMut<H>
         where H: HeaderKind
@@ -378,6 +1170,9 @@ impl HeaderMap {
     ///   that it will add the header to all headers previously associated with the given
     ///   header name.
     ///
+    /// If the map was created with `with_limits` and inserting the header would
+    /// exceed a limit the header is dropped, see `take_limit_violation`. Use
+    /// `try_insert` to get the `LimitViolation` directly.
     pub fn insert<H>(&mut self, header: Header<H>)
         where H: HeaderKind
     {
@@ -386,6 +1181,135 @@ impl HeaderMap {
         self._insert(name, H::MAX_ONE, obj)
     }
 
+    /// Inserts the given header at the given position of the map.
+    ///
+    /// This behaves like `insert` (e.g. a existing header with the same
+    /// name is removed if `H::MAX_ONE == true`), but the header is placed
+    /// at `index` (or at the end if `index` is larger than the number of
+    /// other headers) instead of at the end of the map.
+    ///
+    /// Like `insert` the header is dropped if inserting it would exceed
+    /// the limits of the map. Use `try_insert_at` to get the `LimitViolation`
+    /// directly.
+    pub fn insert_at<H>(&mut self, index: usize, header: Header<H>)
+        where H: HeaderKind
+    {
+        let res = self.try_insert_at(index, header);
+        self.record_violation(res);
+    }
+
+    /// Like `insert_at` but fails instead of exceeding the limits of this map.
+    pub fn try_insert_at<H>(&mut self, index: usize, header: Header<H>) -> Result<(), LimitViolation>
+        where H: HeaderKind
+    {
+        self.try_insert(header)?;
+        let last = self.len() - 1;
+        self.move_header(last, index);
+        Ok(())
+    }
+
+    /// Inserts the given header at the front of the map.
+    ///
+    /// This is the same as `insert_at(0, header)`. It is e.g. used to add
+    /// trace headers (`Received`, `Return-Path`) which by convention are
+    /// prepended to the header section.
+    ///
+    /// Like `insert` the header is dropped if inserting it would exceed
+    /// the limits of the map. Use `try_prepend` to get the `LimitViolation`
+    /// directly.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::HeaderKind;
+    /// use mail_headers::headers::*;
+    ///
+    /// let mut map = headers! {
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// map.prepend(Comments::auto_body("first").unwrap());
+    /// let names = map.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+    /// assert_eq!(names, vec!["Comments", "Subject"]);
+    /// # }
+    /// ```
+    pub fn prepend<H>(&mut self, header: Header<H>)
+        where H: HeaderKind
+    {
+        self.insert_at(0, header)
+    }
+
+    /// Like `prepend` but fails instead of exceeding the limits of this map.
+    pub fn try_prepend<H>(&mut self, header: Header<H>) -> Result<(), LimitViolation>
+        where H: HeaderKind
+    {
+        self.try_insert_at(0, header)
+    }
+
+    /// Moves the header at position `from` to position `to` (clamped to the last position).
+    fn move_header(&mut self, from: usize, to: usize) {
+        let to = cmp::min(to, self.len() - 1);
+        if from == to {
+            return;
+        }
+        let inner_map = mem::replace(&mut self.inner_map, Default::default());
+        let seqs = mem::replace(&mut self.seqs, Vec::new());
+        let mut entries = inner_map.into_iter().zip(seqs).collect::<Vec<_>>();
+        let entry = entries.remove(from);
+        entries.insert(to, entry);
+        for ((name, obj), seq) in entries {
+            self.inner_map.add(name, obj);
+            self.seqs.push(seq);
+        }
+    }
+
+    /// Adds the given header to the map, failing if this would replace a header.
+    ///
+    /// For headers with `H::MAX_ONE == false` this is the same as `insert`.
+    /// For headers with `H::MAX_ONE == true` this fails with `MoreThenOne` if
+    /// the map already contains a header with the same name, instead of
+    /// replacing it like `insert` does. Use `insert` to explicitly replace
+    /// such headers.
+    ///
+    /// If the map was created with `with_limits` and inserting the header
+    /// would exceed a limit this fails with a `Custom` error wrapping the
+    /// `LimitViolation`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::HeaderKind;
+    /// use mail_headers::headers::*;
+    ///
+    /// let mut map = headers! {
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// assert!(map.try_add(Subject::auto_body("hy you").unwrap()).is_err());
+    /// assert_eq!(map.get_single(Subject).unwrap().unwrap().as_str(), "hy there");
+    /// # }
+    /// ```
+    pub fn try_add<H>(&mut self, header: Header<H>) -> Result<(), HeaderValidationError>
+        where H: HeaderKind
+    {
+        let name = header.name();
+        if H::MAX_ONE && self.contains(name) {
+            return Err(HeaderValidationError::from(
+                BuildInValidationError::MoreThenOne {
+                    header_name: name.as_str()
+                }
+            ));
+        }
+        self.try_insert(header)
+            .map_err(|violation| HeaderValidationError::Custom(violation.into()))
+    }
+
     /// Insert a HeaderObj into the header map.
     #[doc(hidden)]
     pub fn insert_untyped(&mut self, obj: Box<HeaderObj>) {
@@ -394,10 +1318,42 @@ impl HeaderMap {
 
     #[inline(always)]
     fn _insert(&mut self, name: HeaderName, max_one: bool, obj: Box<HeaderObj>) {
+        let res = self._try_insert_with_seq(name, max_one, obj, next_seq(), 0);
+        self.record_violation(res);
+    }
+
+    fn _try_insert_with_seq(
+        &mut self, name: HeaderName, max_one: bool, obj: Box<HeaderObj>,
+        seq: HeaderSeq, accepted: usize
+    ) -> Result<(), LimitViolation> {
+        let used_bytes = limits::check_insert(self, name, max_one, &*obj, accepted)?;
+        self._insert_with_seq(name, max_one, obj, seq);
+        self.used_bytes = used_bytes;
+        Ok(())
+    }
+
+    fn _insert_with_seq(&mut self, name: HeaderName, max_one: bool, obj: Box<HeaderObj>, seq: HeaderSeq) {
         if max_one {
-            self.inner_map.set(name, obj);
-        } else {
-            self.inner_map.add(name, obj);
+            // don't use `set`, the ordering contract must not depend on the
+            // position at which the multimap places a replacing value
+            self.remove_seqs_of(name);
+            self.inner_map.remove_all(name);
+        }
+        self.inner_map.add(name, obj);
+        self.seqs.push(seq);
+        self.max_seq = cmp::max(self.max_seq, seq);
+    }
+
+    fn remove_seqs_of(&mut self, name: HeaderName) {
+        let positions = self.inner_map.iter()
+            .enumerate()
+            .filter(|&(_, (other, _))| other == name)
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+
+        for idx in positions.into_iter().rev() {
+            let seq = self.seqs.remove(idx);
+            self.labels.remove(&seq);
         }
     }
 
@@ -412,6 +1368,16 @@ impl HeaderMap {
     /// `MAX_ONE == true` in the headers to insert can replace
     /// existing headers associated with the same header name.
     ///
+    /// The inserted headers keep the sequence numbers and labels
+    /// they had in `other` (see `iter_with_seq`, `combine_labeled`)
+    /// and the validators added to `other` (see `add_validator`)
+    /// are added to this map.
+    ///
+    /// If this map was created with `with_limits` and inserting the
+    /// headers would exceed a limit no header is inserted, see
+    /// `take_limit_violation`. Use `try_insert_all` to get the
+    /// `LimitViolation` directly (or to insert headers up to the limit).
+    ///
     /// # Example
     ///
     /// ```
@@ -442,16 +1408,75 @@ impl HeaderMap {
     /// ```
     ///
     pub fn insert_all(&mut self, other: HeaderMap) {
-        for (_name, header) in other.into_iter() {
-            self.insert_untyped(header);
-        }
+        let res = self.insert_atomically(|map| map.insert_all_until_violation(other));
+        self.record_violation(res);
     }
 
     /// Remove all headers with the given header name.
     ///
     /// Returns true, if at last one header was removed.
     pub fn remove<H: HasHeaderName>(&mut self, name: H) -> bool {
-        self.inner_map.remove_all(name.get_name())
+        let name = name.get_name();
+        if self.limits.max_bytes.is_some() {
+            self.used_bytes = self.used_bytes.saturating_sub(limits::bytes_of(self, name));
+        }
+        self.remove_seqs_of(name);
+        self.inner_map.remove_all(name)
+    }
+
+    /// Removes the `index`-th header with the given name and returns it.
+    ///
+    /// The index is the position among the headers with the given name
+    /// (i.e. the position in `get_untyped`), not the position in the map.
+    /// All other headers keep their order.
+    ///
+    /// Returns `None` if there are not more than `index` headers with the given name.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    ///
+    /// let mut map = headers! {
+    ///     Comments: "a",
+    ///     Comments: "b",
+    ///     Comments: "c"
+    /// }.unwrap();
+    ///
+    /// let removed = map.remove_at(Comments, 1).unwrap();
+    /// assert_eq!(removed.downcast_ref::<Comments>().unwrap().as_str(), "b");
+    /// assert!(map.remove_at(Comments, 2).is_none());
+    /// # }
+    /// ```
+    pub fn remove_at<H: HasHeaderName>(&mut self, name: H, index: usize) -> Option<Box<HeaderObj>> {
+        let name = name.get_name();
+        if self.get_untyped(name).len() <= index {
+            return None;
+        }
+
+        let inner_map = mem::replace(&mut self.inner_map, Default::default());
+        let seqs = mem::replace(&mut self.seqs, Vec::new());
+        let mut seen = 0;
+        let mut removed = None;
+        for ((other, obj), seq) in inner_map.into_iter().zip(seqs) {
+            if other == name {
+                seen += 1;
+                if seen == index + 1 {
+                    removed = Some(obj);
+                    self.labels.remove(&seq);
+                    continue;
+                }
+            }
+            self.inner_map.add(other, obj);
+            self.seqs.push(seq);
+        }
+        if self.limits.max_bytes.is_some() {
+            self.used_bytes = limits::total_bytes(self);
+        }
+        removed
     }
 
     /// iterate over all (header name, boxed body) pairs in this map
@@ -459,6 +1484,99 @@ impl HeaderMap {
         self.inner_map.iter()
     }
 
+    /// Iterates over the distinct header names in this map.
+    ///
+    /// Unlike `iter` each name is only yielded once (even if there are
+    /// multiple bodies for it), in the order in which it first appears
+    /// in the map.
+    pub fn names(&self) -> Names {
+        Names {
+            iter: self.iter(),
+            seen: HashSet::new()
+        }
+    }
+
+    /// Returns the number of bodies with given header name.
+    ///
+    /// Accepts both `HeaderName` or a type implementing `HeaderKind`.
+    pub fn count_of<H: HasHeaderName>(&self, name: H) -> usize {
+        self.get_untyped(name).len()
+    }
+
+    /// Returns true if both maps contain the same headers when encoded.
+    ///
+    /// The maps are equal if they contain the same header names in the same
+    /// order and the bodies encode to the same text with given mail type. The
+    /// type of the bodies is not compared, i.e. a `Subject` using a different
+    /// component which encodes to the same text is equal. Sequence numbers,
+    /// labels and limits are ignored, too.
+    ///
+    /// `HeaderMap` does not implement `PartialEq` as encoding can fail and
+    /// the result depends on the mail type.
+    ///
+    /// # Error
+    ///
+    /// Fails if any header (of either map) can not be encoded, except if the
+    /// maps already differ in the header names.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # extern crate mail_internals;
+    /// # fn main() {
+    /// use mail_internals::MailType;
+    /// use mail_headers::headers::*;
+    ///
+    /// let map = headers! {
+    ///     _From: ["me@example.com"],
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    /// let mut other = headers! {
+    ///     _From: ["me@example.com"],
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    /// assert!(map.eq_by_encoding(&other, MailType::Ascii).unwrap());
+    ///
+    /// other.insert(Subject::auto_body("hy here").unwrap());
+    /// assert!(!map.eq_by_encoding(&other, MailType::Ascii).unwrap());
+    /// # }
+    /// ```
+    pub fn eq_by_encoding(&self, other: &HeaderMap, mail_type: MailType)
+        -> Result<bool, EncodingError>
+    {
+        if self.len() != other.len()
+            || self.iter().zip(other.iter()).any(|((name, _), (other_name, _))| name != other_name)
+        {
+            return Ok(false);
+        }
+        for ((_, obj), (_, other_obj)) in self.iter().zip(other.iter()) {
+            if obj.encoded_value(mail_type)? != other_obj.encoded_value(mail_type)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+}
+
+/// Iterator over the distinct header names of a map, see `HeaderMap::names`.
+#[derive(Debug)]
+pub struct Names<'a> {
+    iter: Iter<'a>,
+    seen: HashSet<HeaderName>
+}
+
+impl<'a> Iterator for Names<'a> {
+    type Item = HeaderName;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let seen = &mut self.seen;
+        self.iter
+            .find(|&(name, _)| seen.insert(name))
+            .map(|(name, _)| name)
+    }
 }
 
 /// Iterator over all boxed bodies for a given header name
@@ -503,10 +1621,7 @@ impl<'a, H> Iterator for TypedBodies<'a, H>
 
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next()
-            .map( |tobj| {
-                tobj.downcast_ref::<H>()
-                    .ok_or_else(|| HeaderTypeError::new(H::name()))
-            } )
+            .map(|tobj| tobj.try_downcast_ref::<H>())
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -577,10 +1692,7 @@ impl<'a, H> Iterator for TypedBodiesMut<'a, H>
 
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next()
-            .map(|tobj| {
-                tobj.downcast_mut::<H>()
-                    .ok_or_else(|| HeaderTypeError::new(H::name()))
-            })
+            .map(|tobj| tobj.try_downcast_mut::<H>())
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -600,41 +1712,15 @@ impl<'a, H> Debug for TypedBodiesMut<'a, H>
     where H: HeaderKind
 {
     fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
-        fter.write_str("TypedBodiesMut { .. }")
+        // `UntypedBodiesMut` does not implement `Debug`, so the remaining
+        // bodies are only counted
+        fter.debug_struct("TypedBodiesMut")
+            .field("name", &H::name())
+            .field("remaining", &self.inner.len())
+            .finish()
     }
 }
 
-/// Create a header map from a list of header's with ther fields
-///
-/// # Example
-///
-/// ```
-/// # #[macro_use]
-/// # extern crate mail_headers;
-/// # use mail_headers::headers::*;
-/// # use mail_headers::error::ComponentCreationError;
-/// # fn main() { (|| -> Result<(), ComponentCreationError> {
-/// let map = headers! {
-///     _From: ["bobo@nana.test"],
-///     Subject: "hy there"
-/// }?;
-/// # Ok(()) })(); }
-/// ```
-#[macro_export]
-macro_rules! headers {
-    ($($header:ty : $val:expr),*) => ({
-        //FIXME[rust/catch block] use catch block once available
-        (|| -> Result<$crate::HeaderMap, $crate::error::ComponentCreationError>
-        {
-            let mut map = $crate::HeaderMap::new();
-            $(
-                map.insert(<$header as $crate::HeaderKind>::auto_body($val)?);
-            )*
-            Ok(map)
-        })()
-    });
-}
-
 /// HeaderMapValidator is just a function pointer,
 /// but it does not implement Hash so we wrap it
 /// and implement Hash on it. Note that some function
@@ -672,6 +1758,14 @@ impl Hash for ValidatorHashWrapper {
 }
 
 
+fn more_then_one(name: HeaderName) -> HeaderValidationError {
+    HeaderValidationError::from(
+        BuildInValidationError::MoreThenOne {
+            header_name: name.as_str()
+        }
+    )
+}
+
 pub fn check_header_count_max_one(name: HeaderName, map: &HeaderMap)
     -> Result<(), HeaderValidationError>
 {
@@ -690,8 +1784,9 @@ pub fn check_header_count_max_one(name: HeaderName, map: &HeaderMap)
 #[cfg(test)]
 mod test {
     use failure::Context;
-    use soft_ascii_string::SoftAsciiStr;
+    use soft_ascii::SoftAsciiStr;
 
+    use internals::MailType;
     use internals::error::{EncodingError, EncodingErrorKind};
     use internals::encoder::{EncodableInHeader, EncodingWriter};
 
@@ -704,7 +1799,9 @@ mod test {
     use self::good_headers::*;
     use self::bad_headers::{
         Subject as BadSubject,
-        Comments as BadComments
+        Comments as BadComments,
+        _From as BadFrom,
+        ResentFrom as BadResentFrom
     };
     use self::bad_headers2::{
         Comments2 as BadComments2
@@ -718,6 +1815,7 @@ mod test {
             Ok(OtherComponent)
         }
     }
+
     impl EncodableInHeader for OtherComponent {
         fn encode(&self, _encoder:  &mut EncodingWriter) -> Result<(), EncodingError> {
             Err(EncodingError::from(
@@ -741,11 +1839,17 @@ mod test {
     }
 
     mod bad_headers {
+        use ::headers::validators::{
+            from as validator_from,
+            resent_any as validator_resent_any
+        };
         def_headers! {
             test_name: validate_header_names,
             scope: super,
             Subject, unchecked { "Subject" },  OtherComponent, maxOne, None,
-            Comments, unchecked { "Comments" }, OtherComponent, multi, None
+            Comments, unchecked { "Comments" }, OtherComponent, multi, None,
+            _From, unchecked { "From" }, OtherComponent, maxOne, validator_from,
+            ResentFrom, unchecked { "Resent-From" }, OtherComponent, multi, validator_resent_any
         }
     }
     mod bad_headers2 {
@@ -775,6 +1879,92 @@ mod test {
         panic!("unexpected result: {:?}", res);
     });
 
+    fn assert_unexpected_component_type(map: &HeaderMap, name: &str) {
+        match map.use_contextual_validators() {
+            Err(HeaderValidationError::BuildIn(berr)) => {
+                match *berr.get_context() {
+                    BuildInValidationError::UnexpectedComponentType { header_name } => {
+                        assert_eq!(header_name, name)
+                    },
+                    ref other => panic!("unexpected error: {:?}", other)
+                }
+            },
+            res => panic!("unexpected result: {:?}", res)
+        }
+    }
+
+    test!(wrong_typed_from_fails_validation {
+        let map = headers! {
+            BadFrom: ()
+        }?;
+        assert_unexpected_component_type(&map, "From");
+    });
+
+    test!(wrong_typed_resent_from_fails_validation {
+        let map = headers! {
+            ::headers::ResentDate: ::header_components::DateTime::now(),
+            BadResentFrom: ()
+        }?;
+        assert_unexpected_component_type(&map, "Resent-From");
+    });
+
+    test!(wrong_typed_unrelated_header_is_ignored {
+        let map = headers! {
+            ::headers::_From: ["a@example.com", "b@example.com"],
+            ::headers::Sender: "a@example.com",
+            BadComments: ()
+        }?;
+        assert_ok!(map.use_contextual_validators());
+    });
+
+    test!(lenient_options_skip_wrong_typed_headers {
+        let mut map = headers! {
+            ::headers::ResentDate: ::header_components::DateTime::now(),
+            BadResentFrom: ()
+        }?;
+        map.set_validator_options(ValidatorOptions::lenient());
+        assert_ok!(map.use_contextual_validators());
+
+        let mut map = headers! {
+            BadFrom: ()
+        }?;
+        map.set_validator_options(ValidatorOptions::lenient());
+        assert_ok!(map.use_contextual_validators());
+    });
+
+    test!(try_add_rejects_second_max_one_header {
+        let mut map = HeaderMap::new();
+        map.try_add(Subject::auto_body(TEXT_1)?)?;
+
+        let err = assert_err!(map.try_add(Subject::auto_body(TEXT_2)?));
+        if let HeaderValidationError::BuildIn(berr) = err {
+            assert_eq!(*berr.get_context(), BuildInValidationError::MoreThenOne {
+                header_name: "Subject"
+            });
+        } else {
+            panic!("unexpected error: {:?}", err);
+        }
+
+        assert_eq!(map.len(), 1);
+        let subject = map.get_single(Subject).unwrap()?;
+        assert_eq!(subject.as_str(), TEXT_1);
+
+        // `insert` is still the explicit way to replace it
+        map.insert(Subject::auto_body(TEXT_2)?);
+        assert_eq!(map.get_single(Subject).unwrap()?.as_str(), TEXT_2);
+    });
+
+    test!(try_add_adds_multi_headers {
+        let mut map = HeaderMap::new();
+        map.try_add(Comments::auto_body(TEXT_1)?)?;
+        map.try_add(Comments::auto_body(TEXT_2)?)?;
+
+        let values = map.get(Comments)
+            .map(|comp| comp.unwrap().as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![TEXT_1, TEXT_2]);
+    });
+
     #[test]
     fn headers_macro() {
         let headers = headers! {
@@ -804,6 +1994,62 @@ mod test {
         assert_eq!(1, count);
     }
 
+    test!(headers_macro_trailing_comma {
+        let headers = headers! {
+            Comments: TEXT_1,
+            Subject: TEXT_2,
+        }?;
+        assert_eq!(headers.len(), 2);
+    });
+
+    test!(alternate_debug_prints_one_header_per_line {
+        let map = headers! {
+            ::headers::_From: [("Jane Doe", "jane@example.com")],
+            Subject: TEXT_1,
+            Comments: TEXT_2
+        }?;
+
+        let expected = format!(
+            "HeaderMap {{\n    From: Jane Doe <jane@example.com>\n    Subject: {}\n    Comments: {}\n}}",
+            TEXT_1, TEXT_2);
+        assert_eq!(format!("{:#?}", map), expected);
+        assert_eq!(format!("{:#?}", HeaderMap::new()), "HeaderMap {\n}");
+
+        // the single line form is unchanged
+        assert!(!format!("{:?}", map).contains('\n'));
+    });
+
+    test!(headers_into_macro {
+        let mut headers = headers! {
+            Comments: TEXT_1,
+            Subject: "template"
+        }?;
+
+        headers_into!(&mut headers, {
+            Subject: TEXT_2,
+            Comments: TEXT_2,
+        })?;
+
+        let names = headers.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Comments", "Subject", "Comments"]);
+        assert_eq!(headers.get_single(Subject).unwrap()?.as_str(), TEXT_2);
+    });
+
+    test!(headers_into_macro_propagates_errors {
+        let mut headers = headers! {
+            Subject: TEXT_1
+        }?;
+
+        let res = headers_into!(&mut headers, {
+            Comments: TEXT_2,
+            _From: ["not an email"]
+        });
+
+        assert_err!(res);
+        // headers before the failing one are already added
+        assert_eq!(headers.len(), 2);
+    });
+
     #[test]
     fn get_single() {
         let headers = headers! {
@@ -829,6 +2075,22 @@ mod test {
         assert_err!( res.expect("where did the header go?") );
     }
 
+    test!(get_single_mut {
+        let mut headers = headers! {
+            Subject: "abc",
+            Comments: "1st"
+        }?;
+
+        *headers.get_single_mut(Subject).unwrap()?.body_mut() = RawUnstructured::from("def");
+        assert_eq!(headers.get_single(Subject).unwrap()?.as_str(), "def");
+
+        *headers._get_single_mut::<Subject>().unwrap()?.body_mut() = RawUnstructured::from("ghi");
+        assert_eq!(headers.get_single(Subject).unwrap()?.as_str(), "ghi");
+
+        assert_err!(headers.get_single_mut(BadSubject).unwrap());
+        assert_eq!(headers.len(), 2);
+    });
+
     #[test]
     fn get() {
         let headers = headers! {
@@ -890,6 +2152,58 @@ mod test {
         assert!(res.next().is_none());
     }
 
+    fn duplicated_headers() -> Result<HeaderMap, ComponentCreationError> {
+        Ok(HeaderMap::from_vec(vec![
+            (Subject::name(), Box::new(Subject::auto_body("a")?) as Box<HeaderObj>),
+            (Comments::name(), Box::new(Comments::auto_body("b")?)),
+            (Subject::name(), Box::new(Subject::auto_body("c")?)),
+            (Comments::name(), Box::new(Comments::auto_body("d")?))
+        ]))
+    }
+
+    test!(is_max_one_is_available_on_header_objects {
+        let max_one = duplicated_headers()?.into_iter()
+            .map(|(name, obj)| (name.as_str(), obj.is_max_one()))
+            .collect::<Vec<_>>();
+        assert_eq!(max_one, vec![
+            ("Subject", true), ("Comments", false), ("Subject", true), ("Comments", false)
+        ]);
+    });
+
+    test!(duplicate_max_one_names_only_contains_max_one_headers {
+        let map = duplicated_headers()?;
+        assert_eq!(map.duplicate_max_one_names(), vec![Subject::name()]);
+        let err = assert_err!(map.validate());
+        if let HeaderValidationError::BuildIn(berr) = err {
+            assert_eq!(*berr.get_context(), BuildInValidationError::MoreThenOne {
+                header_name: "Subject"
+            });
+        } else {
+            panic!("unexpected error: {:?}", err);
+        }
+
+        let map = headers! {
+            Subject: "a",
+            Comments: "b",
+            Comments: "c"
+        }?;
+        assert!(map.duplicate_max_one_names().is_empty());
+        assert_ok!(map.validate());
+    });
+
+    test!(debug_flags_duplicate_max_one_headers {
+        let map = duplicated_headers()?;
+
+        assert_eq!(
+            format!("{:?}", map),
+            "HeaderMap { 4 headers: Subject(2) [duplicate max one], Comments(2) }"
+        );
+
+        let expected = "HeaderMap {\n    Subject [duplicate max one]: a\n    Comments: b\n    \
+            Subject [duplicate max one]: c\n    Comments: d\n}";
+        assert_eq!(format!("{:#?}", map), expected);
+    });
+
     #[test]
     fn fmt_debug() {
         let headers = headers! {
@@ -897,11 +2211,104 @@ mod test {
         }.unwrap();
 
         let res = format!("{:?}", headers);
+        assert_eq!("HeaderMap { 1 header: Subject(1) }", res.as_str());
+        assert_eq!("HeaderMap { 0 headers }", format!("{:?}", HeaderMap::new()));
+    }
+
+    test!(debug_of_small_map {
+        let map = headers! {
+            ::headers::_From: ["a@example.com"],
+            ::headers::_To: ["b@example.com"],
+            Comments: "first",
+            Subject: "hy there",
+            Comments: "second"
+        }?;
+
         assert_eq!(
-            "HeaderMap { Subject: RawUnstructured { text: Input(Owned(\"hy there\")) }, }",
-            res.as_str()
+            format!("{:?}", map),
+            "HeaderMap { 5 headers: From(1), To(1), Comments(2), Subject(1) }"
         );
-    }
+        assert_eq!(
+            format!("{:#?}", map),
+            "HeaderMap {\n    From: <a@example.com>\n    To: <b@example.com>\n    \
+            Comments: first\n    Subject: hy there\n    Comments: second\n}"
+        );
+    });
+
+    test!(debug_elides_names_with_many_headers {
+        use ::header_components::{ReceivedToken, Domain};
+        let mut map = headers! {
+            ::headers::_From: ["a@example.com"],
+            Subject: "hy there"
+        }?;
+        for idx in 0..10 {
+            let domain = Domain::try_from(format!("mx{}.example.com", idx).as_str())?;
+            map.insert(::headers::Received::body(ReceivedToken::Domain(domain)));
+        }
+
+        assert_eq!(
+            format!("{:?}", map),
+            "HeaderMap { 12 headers: From(1), Subject(1), Received(10) }"
+        );
+        assert_eq!(
+            format!("{:#?}", map),
+            "HeaderMap {\n    From: <a@example.com>\n    Subject: hy there\n    \
+            Received: mx0.example.com\n    Received: mx1.example.com\n    \
+            Received: ... (7 more)\n    Received: mx9.example.com\n}"
+        );
+    });
+
+    test!(debug_does_not_elide_up_to_the_limit {
+        let mut map = HeaderMap::new();
+        for idx in 0..DEBUG_MAX_HEADERS_PER_NAME {
+            map.insert(Comments::auto_body(format!("c{}", idx).as_str())?);
+        }
+        let debug = format!("{:#?}", map);
+        assert!(!debug.contains("more)"));
+        assert_eq!(debug.lines().count(), DEBUG_MAX_HEADERS_PER_NAME + 2);
+    });
+
+    test!(debug_truncates_long_values {
+        let subject = "abcd ".repeat(1000);
+        let subject = subject.trim_end();
+        assert_eq!(subject.len(), 4999);
+        // unlike the raw test header this one is folded when encoded
+        let map = headers! {
+            ::headers::Subject: subject
+        }?;
+
+        assert_eq!(format!("{:?}", map), "HeaderMap { 1 header: Subject(1) }");
+        assert_eq!(
+            format!("{:#?}", map),
+            format!("HeaderMap {{\n    Subject: {}... (4999 chars)\n}}", &subject[..120])
+        );
+    });
+
+    test!(debug_does_not_fill_the_encoded_value_cache {
+        let map = headers! {
+            ::headers::Subject: "hy there"
+        }?;
+        assert!(format!("{:#?}", map).contains("Subject: hy there"));
+
+        // the cache is filled for the first requested mail type
+        let subject = map.get_single(::headers::Subject).unwrap()?;
+        subject.encoded_value(MailType::Ascii)?;
+        match subject.encoded_value(MailType::Ascii)? {
+            ::std::borrow::Cow::Borrowed(_) => {},
+            ::std::borrow::Cow::Owned(_) => panic!("Debug filled the cache")
+        }
+    });
+
+    test!(debug_truncates_at_char_boundaries {
+        let subject = "\u{e4}".repeat(121);
+        let map = headers! {
+            Subject: subject.as_str()
+        }?;
+        assert_eq!(
+            format!("{:#?}", map),
+            format!("HeaderMap {{\n    Subject: {}... (121 chars)\n}}", "\u{e4}".repeat(120))
+        );
+    });
 
     test!(combine_keeps_order {
         let mut headers = headers! {
@@ -973,6 +2380,191 @@ mod test {
         );
     });
 
+    test!(eq_by_encoding_ignores_component_types {
+        let map = headers! {
+            Subject: "hy there",
+            Comments: "a"
+        }?;
+        let other = headers! {
+            ::headers::Subject: "hy there",
+            ::headers::Comments: "a"
+        }?;
+
+        assert!(map.get_single(::headers::Subject).unwrap().is_err());
+        assert!(map.eq_by_encoding(&other, MailType::Ascii)?);
+        assert!(other.eq_by_encoding(&map, MailType::Internationalized)?);
+    });
+
+    test!(eq_by_encoding_compares_names_order_and_bodies {
+        let map = headers! {
+            Subject: "hy there",
+            Comments: "a"
+        }?;
+
+        let reordered = headers! {
+            Comments: "a",
+            Subject: "hy there"
+        }?;
+        assert!(!map.eq_by_encoding(&reordered, MailType::Ascii)?);
+
+        let other_body = headers! {
+            Subject: "hy there",
+            Comments: "b"
+        }?;
+        assert!(!map.eq_by_encoding(&other_body, MailType::Ascii)?);
+
+        let other_name = headers! {
+            Subject: "hy there",
+            XComment: "a"
+        }?;
+        assert!(!map.eq_by_encoding(&other_name, MailType::Ascii)?);
+
+        let more = headers! {
+            Subject: "hy there",
+            Comments: "a",
+            Comments: "a"
+        }?;
+        assert!(!map.eq_by_encoding(&more, MailType::Ascii)?);
+        assert!(map.eq_by_encoding(&map.clone(), MailType::Ascii)?);
+    });
+
+    test!(names_yields_each_name_once {
+        let map = headers! {
+            Comments: "a",
+            Subject: "hy there",
+            Comments: "b",
+            ::headers::_From: ["me@example.com"],
+            Comments: "c"
+        }?;
+
+        let names = map.names().map(|name| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Comments", "Subject", "From"]);
+        assert_eq!(HeaderMap::new().names().count(), 0);
+    });
+
+    test!(count_of_counts_interleaved_bodies {
+        let map = headers! {
+            Comments: "a",
+            Subject: "hy there",
+            Comments: "b",
+            Comments: "c"
+        }?;
+
+        assert_eq!(map.count_of(Comments), 3);
+        assert_eq!(map.count_of(Subject::name()), 1);
+        assert_eq!(map.count_of(::headers::Sender), 0);
+    });
+
+    fn received(domain: &str) -> Result<Header<::headers::Received>, ComponentCreationError> {
+        use ::header_components::{ReceivedToken, Domain};
+        let domain = Domain::try_from(domain)?;
+        Ok(::headers::Received::body(ReceivedToken::Domain(domain)))
+    }
+
+    test!(prepend_puts_header_in_front {
+        let mut map = headers! {
+            Subject: "hy there",
+            Comments: "a"
+        }?;
+
+        map.prepend(received("mail.example.com")?);
+        map.prepend(received("relay.example.com")?);
+
+        let names = map.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Received", "Received", "Subject", "Comments"]);
+        let names = map.into_iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Received", "Received", "Subject", "Comments"]);
+    });
+
+    test!(insert_at_position {
+        let mut map = headers! {
+            Comments: "a",
+            Comments: "c"
+        }?;
+
+        map.insert_at(1, Comments::auto_body("b")?);
+        map.insert_at(100, Comments::auto_body("d")?);
+        let texts = map.get(Comments)
+            .map(|res| res.unwrap().as_str().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, vec!["a", "b", "c", "d"]);
+        assert_eq!(map.iter_with_seq().count(), 4);
+    });
+
+    test!(insert_at_replaces_max_one {
+        let mut map = headers! {
+            Comments: "a",
+            Subject: "old",
+            Comments: "b"
+        }?;
+
+        map.insert_at(0, Subject::auto_body("new")?);
+        let names = map.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Subject", "Comments", "Comments"]);
+        assert_eq!(map.get_single(Subject).unwrap()?.as_str(), "new");
+    });
+
+    test!(limited_maps_fail_instead_of_panicking {
+        use ::error::{LimitKind, LimitViolation};
+
+        let mut map = HeaderMap::with_limits(MapLimits { max_headers: Some(2), ..Default::default() });
+        map.try_prepend(Comments::auto_body("a")?)?;
+        map.try_insert_at(0, Subject::auto_body("b")?)?;
+        let err = assert_err!(map.try_insert_at(1, Comments::auto_body("c")?));
+        assert_eq!(err.kind(), LimitKind::HeaderCount);
+        assert_err!(map.try_prepend(Comments::auto_body("c")?));
+
+        // replacing a "max one" header stays within the limit
+        map.try_prepend(Subject::auto_body("d")?)?;
+
+        match assert_err!(map.try_add(Comments::auto_body("c")?)) {
+            HeaderValidationError::Custom(err) => assert!(err.downcast_ref::<LimitViolation>().is_some()),
+            err => panic!("unexpected error: {:?}", err)
+        }
+
+        assert_err!(headers_into!(&mut map, { Comments: "c" }));
+        map.prepend(Comments::auto_body("c")?);
+        map.insert_at(1, Comments::auto_body("c")?);
+        assert_eq!(map.take_limit_violation().unwrap().kind(), LimitKind::HeaderCount);
+        let names = map.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Subject", "Comments"]);
+    });
+
+    test!(remove_at_keeps_order {
+        let mut headers = headers!{
+            Comments: "a",
+            Subject: "b",
+            Comments: "c",
+            Comments: "d",
+            XComment: "e"
+        }?;
+
+        let removed = headers.remove_at(Comments, 1).unwrap();
+        assert_eq!(removed.downcast_ref::<Comments>().unwrap().as_str(), "c");
+
+        let mut values = Vec::new();
+        for (name, obj) in headers.iter() {
+            let value = obj.encoded_value(MailType::Ascii)?;
+            values.push(format!("{}: {}", name, String::from_utf8_lossy(&value)));
+        }
+        assert_eq!(values, vec!["Comments: a", "Subject: b", "Comments: d", "X-Comment: e"]);
+    });
+
+    test!(remove_at_out_of_range {
+        let mut headers = headers!{
+            Comments: "a",
+            Subject: "b"
+        }?;
+
+        assert!(headers.remove_at(Comments, 1).is_none());
+        assert!(headers.remove_at(XComment, 0).is_none());
+        assert_eq!(2, headers.len());
+
+        assert!(headers.remove_at(Comments, 0).is_some());
+        assert!(!headers.contains(Comments));
+        assert_eq!(1, headers.len());
+    });
+
     #[derive(Default, Copy, Clone)]
     struct XComment;
     impl HeaderKind for XComment {
@@ -1011,6 +2603,38 @@ mod test {
         assert_eq!( false, map.contains( Comments         ));
     });
 
+    test!(contains_kind_checks_the_type {
+        let map = headers! {
+            Subject: "soso",
+            BadComments: ()
+        }?;
+
+        assert!(map.contains_kind::<Subject>());
+        assert!(!map.contains_kind::<BadSubject>());
+        assert!(map.contains(BadSubject));
+        assert!(map.contains_kind::<BadComments>());
+        assert!(!map.contains_kind::<Comments>());
+
+        assert!(map.kind_mismatch::<Subject>().is_none());
+        assert!(map.kind_mismatch::<BadComments>().is_none());
+        let err = map.kind_mismatch::<BadSubject>().unwrap();
+        assert_eq!(err.to_string(), HeaderTypeError::new(Subject::name()).to_string());
+        assert_err!(map.get_single(BadSubject).unwrap());
+    });
+
+    test!(kind_mismatch_with_mixed_implementations {
+        let mut map = headers! {
+            Comments: "a"
+        }?;
+        map.insert(BadComments::auto_body(())?);
+
+        assert!(map.contains_kind::<Comments>());
+        assert!(map.contains_kind::<BadComments>());
+        assert!(map.kind_mismatch::<Comments>().is_some());
+        assert!(map.kind_mismatch::<BadComments>().is_some());
+        assert!(map.kind_mismatch::<Subject>().is_none());
+    });
+
     test!(use_validator_ok {
         let map = headers! {
             XComment: "yay",
@@ -1030,6 +2654,106 @@ mod test {
         assert_err!(map.use_contextual_validators());
     });
 
+    test!(validate_rejects_duplicated_max_one_headers {
+        let map = HeaderMap::from_vec(vec![
+            (Subject::name(), Box::new(Subject::auto_body("a")?) as Box<HeaderObj>),
+            (Comments::name(), Box::new(Comments::auto_body("b")?)),
+            (Subject::name(), Box::new(Subject::auto_body("c")?))
+        ]);
+
+        // there is no validator for `Subject`
+        assert_ok!(map.use_contextual_validators());
+        let err = assert_err!(map.validate());
+        match err {
+            HeaderValidationError::BuildIn(ref ctx) => assert_eq!(
+                *ctx.get_context(),
+                BuildInValidationError::MoreThenOne { header_name: "Subject" }
+            ),
+            other => panic!("unexpected error: {:?}", other)
+        }
+    });
+
+    test!(validate_runs_contextual_validators {
+        let map = headers! {
+            XComment: "yay",
+            Comments: "oh no",
+            Subject: "soso"
+        }?;
+        assert_err!(map.validate());
+
+        let map = headers! {
+            XComment: "yay",
+            Subject: "soso"
+        }?;
+        assert_ok!(map.validate());
+    });
+
+    fn subject_required(map: &HeaderMap) -> Result<(), HeaderValidationError> {
+        if map.contains(Subject) {
+            Ok(())
+        } else {
+            Err(HeaderValidationError::Custom(::failure::err_msg("Subject is required")))
+        }
+    }
+
+    fn comments_forbidden(map: &HeaderMap) -> Result<(), HeaderValidationError> {
+        if map.contains(Comments) {
+            Err(HeaderValidationError::Custom(::failure::err_msg("Comments are forbidden")))
+        } else {
+            Ok(())
+        }
+    }
+
+    test!(added_validators_are_run {
+        let mut map = headers! {
+            XComment: "yay"
+        }?;
+        assert_ok!(map.use_contextual_validators());
+
+        map.add_validator(subject_required);
+        assert_err!(map.use_contextual_validators());
+        assert_err!(map.validate());
+
+        map.insert(Subject::auto_body("soso")?);
+        assert_ok!(map.use_contextual_validators());
+    });
+
+    test!(added_validators_are_deduplicated {
+        let mut map = HeaderMap::new();
+        map.add_validator(subject_required);
+        map.add_validator(comments_forbidden);
+        map.add_validator(subject_required);
+        assert_eq!(map.validators().len(), 2);
+    });
+
+    test!(added_validators_survive_insert_all_and_clone {
+        let mut other = headers! {
+            Subject: "soso"
+        }?;
+        other.add_validator(comments_forbidden);
+
+        let mut map = headers! {
+            Comments: "oh no"
+        }?;
+        map.add_validator(comments_forbidden);
+        map.insert_all(other);
+        assert_eq!(map.validators().len(), 1);
+        assert_err!(map.clone().use_contextual_validators());
+
+        map.remove(Comments);
+        assert_ok!(map.use_contextual_validators());
+    });
+
+    test!(clear_removes_added_validators {
+        let mut map = HeaderMap::new();
+        map.add_validator(subject_required);
+        assert_err!(map.use_contextual_validators());
+
+        map.clear();
+        assert!(map.validators().is_empty());
+        assert_ok!(map.use_contextual_validators());
+    });
+
     test!(has_len {
         let map = headers! {
             XComment: "yay",
@@ -1039,4 +2763,141 @@ mod test {
 
         assert_eq!(3, map.len());
     });
+
+    test!(get_untyped_single {
+        let map = headers! {
+            Subject: "hy there",
+            Comments: "a",
+            Comments: "b"
+        }?;
+
+        let subject = map.get_untyped_single(Subject)?.unwrap();
+        assert_eq!(subject.downcast_ref::<Subject>().unwrap().as_str(), "hy there");
+        assert!(map.get_untyped_single(Subject::name())?.is_some());
+        assert!(map.get_untyped_single(XComment)?.is_none());
+
+        let err = assert_err!(map.get_untyped_single(Comments));
+        match err {
+            HeaderValidationError::BuildIn(ref ctx) => assert_eq!(
+                *ctx.get_context(),
+                BuildInValidationError::MoreThenOne { header_name: "Comments" }
+            ),
+            other => panic!("unexpected error: {:?}", other)
+        }
+    });
+
+    test!(get_untyped_single_mut {
+        let mut map = headers! {
+            Subject: "hy there",
+            Comments: "a",
+            Comments: "b"
+        }?;
+
+        {
+            let subject = map.get_untyped_single_mut(Subject)?.unwrap();
+            *subject.downcast_mut::<Subject>().unwrap() = Subject::auto_body("changed")?;
+        }
+        assert_eq!(map.get_single(Subject).unwrap()?.as_str(), "changed");
+        assert!(map.get_untyped_single_mut(XComment)?.is_none());
+        assert_err!(map.get_untyped_single_mut(Comments));
+    });
+
+    test!(expect_single {
+        let map = headers! {
+            Subject: "hy there"
+        }?;
+
+        assert_eq!(map.expect_single(Subject)?.as_str(), "hy there");
+        assert_eq!(map._expect_single::<Subject>()?.as_str(), "hy there");
+
+        match map.expect_single(BadSubject) {
+            Err(HeaderExpectationError::Type(_)) => {},
+            other => panic!("unexpected result: {:?}", other)
+        }
+
+        let empty = HeaderMap::new();
+        let err = assert_err!(empty.expect_single(Subject));
+        assert_eq!(err.to_string(), "expected Subject header is missing");
+        match err {
+            HeaderExpectationError::Missing { name } => assert_eq!(name, Subject::name()),
+            other => panic!("unexpected error: {:?}", other)
+        }
+    });
+
+    test!(get_single_or {
+        let default = Subject::auto_body("default")?;
+        let map = headers! {
+            Subject: "hy there"
+        }?;
+
+        assert_eq!(map.get_single_or(&default)?.as_str(), "hy there");
+        assert_eq!(HeaderMap::new().get_single_or(&default)?.as_str(), "default");
+        assert_err!(map.get_single_or(&BadSubject::body(OtherComponent)));
+    });
+
+    test!(clone_is_copy_on_write {
+        let template = headers! {
+            Subject: "template subject",
+            Comments: "a"
+        }?;
+        let mut map = template.clone();
+        {
+            let original = template.get_single(Subject).unwrap()?;
+            let cloned = map.get_single(Subject).unwrap()?;
+            assert!(cloned.shares_body_with(original));
+        }
+
+        *map.get_single_mut(Subject).unwrap()?.body_mut() = RawUnstructured::from("changed");
+        for comment in map.get_mut(Comments) {
+            *comment?.body_mut() = RawUnstructured::from("b");
+        }
+
+        assert_eq!(template.get_single(Subject).unwrap()?.as_str(), "template subject");
+        assert_eq!(template.get(Comments).next().unwrap()?.as_str(), "a");
+        assert_eq!(map.get_single(Subject).unwrap()?.as_str(), "changed");
+        assert_eq!(map.get(Comments).next().unwrap()?.as_str(), "b");
+        let original = template.get_single(Subject).unwrap()?;
+        let changed = map.get_single(Subject).unwrap()?;
+        assert!(!changed.shares_body_with(original));
+    });
+
+    test!(is_empty {
+        let mut map = HeaderMap::new();
+        assert!(map.is_empty());
+
+        map.insert(Subject::auto_body("soso")?);
+        assert!(!map.is_empty());
+
+        map.clear();
+        assert!(map.is_empty());
+    });
+
+    test!(with_capacity_does_not_reallocate {
+        let mut map = HeaderMap::with_capacity(30);
+        let capacity = map.capacity();
+        assert!(capacity >= 30);
+
+        for idx in 0..30 {
+            map.insert(Comments::auto_body(format!("comment {}", idx))?);
+        }
+
+        assert_eq!(map.len(), 30);
+        assert_eq!(map.capacity(), capacity);
+    });
+
+    test!(reserve_makes_room_for_more_headers {
+        let mut map = headers! {
+            Subject: "soso"
+        }?;
+
+        map.reserve(10);
+        let capacity = map.capacity();
+        assert!(capacity >= 11);
+
+        for idx in 0..10 {
+            map.insert(Comments::auto_body(format!("comment {}", idx))?);
+        }
+
+        assert_eq!(map.capacity(), capacity);
+    });
 }
\ No newline at end of file
@@ -0,0 +1,166 @@
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+
+use ::name::HeaderName;
+
+use super::HeaderMap;
+
+/// A prebuilt, read-only index over the header names contained in a `HeaderMap`.
+///
+/// The index is created through `HeaderMap::build_name_index` and answers
+/// `contains` queries (case insensitive) without touching the map. It is
+/// tied to a shared borrow of the map it was build from, so the map can not
+/// be mutated as long as the index is alive, which means the index can never
+/// get out of sync with the map.
+///
+/// This is meant for hot paths which check a larger number of names
+/// against the same map, for a single check `HeaderMap::contains` is
+/// the better choice.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate mail_headers;
+/// # fn main() {
+/// use mail_headers::HeaderKind;
+/// use mail_headers::headers::*;
+///
+/// let map = headers! {
+///     Subject: "hy there",
+///     Comments: "some text"
+/// }.unwrap();
+///
+/// let index = map.build_name_index();
+/// assert!(index.contains(&Subject::name()));
+/// assert!(index.contains_str_ignore_case("comments"));
+/// assert!(!index.contains_str_ignore_case("X-Mailer"));
+/// # }
+/// ```
+///
+/// The map can not be mutated while the index is alive:
+///
+/// ```compile_fail
+/// # #[macro_use]
+/// # extern crate mail_headers;
+/// # fn main() {
+/// use mail_headers::HeaderKind;
+/// use mail_headers::headers::*;
+///
+/// let mut map = headers! {
+///     Subject: "hy there"
+/// }.unwrap();
+///
+/// let index = map.build_name_index();
+/// map.remove(Subject);
+/// assert!(index.contains(&Subject::name()));
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct NameIndex<'a> {
+    /// lowercased names, sorted and without duplicates
+    names: Vec<Box<[u8]>>,
+    _map: PhantomData<&'a HeaderMap>
+}
+
+impl HeaderMap {
+
+    /// Builds a `NameIndex` for all header names currently in this map.
+    ///
+    /// The index borrows the map, i.e. the map can not be mutated
+    /// as long as the index is in use.
+    pub fn build_name_index(&self) -> NameIndex {
+        let mut names = self.inner_map.iter()
+            .map(|(name, _)| {
+                name.as_str().to_ascii_lowercase().into_bytes().into_boxed_slice()
+            })
+            .collect::<Vec<_>>();
+
+        names.sort();
+        names.dedup();
+
+        NameIndex {
+            names,
+            _map: PhantomData
+        }
+    }
+}
+
+impl<'a> NameIndex<'a> {
+
+    /// Returns true if the map this index was build from contains a header with given name.
+    #[inline]
+    pub fn contains(&self, name: &HeaderName) -> bool {
+        self.contains_str_ignore_case(name.as_str())
+    }
+
+    /// Returns true if the map contains a header which name matches given name ignoring case.
+    ///
+    /// The comparison is done on ascii level and is independent of any locale.
+    pub fn contains_str_ignore_case(&self, name: &str) -> bool {
+        let name = name.as_bytes();
+        self.names
+            .binary_search_by(|probe| cmp_lowercased(probe, name))
+            .is_ok()
+    }
+
+    /// Returns the number of unique header names in the index.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns true if the index does not contain any names.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// Compares a already lowercased name with a name of arbitrary case.
+///
+/// This does not allocate but lowercases the bytes of `other` on the fly.
+fn cmp_lowercased(lowercased: &[u8], other: &[u8]) -> Ordering {
+    let other = other.iter().map(|bch| bch.to_ascii_lowercase());
+    lowercased.iter().cloned().cmp(other)
+}
+
+#[cfg(test)]
+mod test {
+    use ::headers::{Subject, Comments, _To, Sender};
+    use ::{HeaderKind, HeaderMap};
+
+    test!(agrees_with_map {
+        let map = headers! {
+            Subject: "abc",
+            Comments: "1st",
+            Comments: "2nd"
+        }?;
+
+        let index = map.build_name_index();
+        assert_eq!(index.len(), 2);
+
+        for name in &[Subject::name(), Comments::name(), _To::name(), Sender::name()] {
+            assert_eq!(index.contains(name), map.contains(*name));
+        }
+    });
+
+    test!(ignores_case {
+        let map = headers! {
+            Subject: "abc"
+        }?;
+
+        let index = map.build_name_index();
+        assert!(index.contains_str_ignore_case("Subject"));
+        assert!(index.contains_str_ignore_case("SUBJECT"));
+        assert!(index.contains_str_ignore_case("sUbJeCt"));
+        assert!(!index.contains_str_ignore_case("Subjec"));
+        assert!(!index.contains_str_ignore_case("Subjects"));
+        assert!(!index.contains_str_ignore_case(""));
+    });
+
+    test!(empty_map {
+        let map = HeaderMap::new();
+        let index = map.build_name_index();
+        assert!(index.is_empty());
+        assert!(!index.contains_str_ignore_case("Subject"));
+    });
+}
@@ -0,0 +1,179 @@
+use ::name::HeaderName;
+
+use super::HeaderMap;
+
+impl HeaderMap {
+
+    /// Returns true if this map contains a header with any of the given names.
+    ///
+    /// This is the same as calling `contains` for each name, so it returns
+    /// false for an empty slice.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::HeaderKind;
+    /// use mail_headers::headers::*;
+    ///
+    /// let map = headers! {
+    ///     _From: ["a@example.com"],
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// assert!(map.contains_any(&[Comments::name(), Subject::name()]));
+    /// assert!(!map.contains_any(&[Comments::name(), Keywords::name()]));
+    /// assert!(!map.contains_any(&[]));
+    /// # }
+    /// ```
+    pub fn contains_any(&self, names: &[HeaderName]) -> bool {
+        names.iter().any(|&name| self.contains(name))
+    }
+
+    /// Returns true if this map contains a header for each of the given names.
+    ///
+    /// This is the same as calling `contains` for each name, so it returns
+    /// true for an empty slice.
+    pub fn contains_all(&self, names: &[HeaderName]) -> bool {
+        names.iter().all(|&name| self.contains(name))
+    }
+
+    /// Returns for each of the given names if this map contains a header with it.
+    ///
+    /// The returned vector has the same length and order as `names`.
+    pub fn which_present(&self, names: &[HeaderName]) -> Vec<bool> {
+        names.iter().map(|&name| self.contains(name)).collect()
+    }
+
+    /// Like `contains_any` but compares the names ignoring (ascii) case.
+    ///
+    /// No `HeaderName` is created for the queried names, instead the names
+    /// of the headers in this map are compared with them, which is done
+    /// once per distinct header name in the map. A name which is not a
+    /// valid header name is never contained.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    ///
+    /// let map = headers! {
+    ///     _From: ["a@example.com"],
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// assert!(map.contains_any_str(&["List-Id", "SUBJECT"]));
+    /// assert!(map.contains_all_str(&["from", "Subject"]));
+    /// assert!(!map.contains_all_str(&["from", "Date"]));
+    /// # }
+    /// ```
+    pub fn contains_any_str(&self, names: &[&str]) -> bool {
+        self.any_name(|name| names.iter().any(|query| name.eq_ignore_ascii_case(query)))
+    }
+
+    /// Like `contains_all` but compares the names ignoring (ascii) case.
+    ///
+    /// See `contains_any_str`.
+    pub fn contains_all_str(&self, names: &[&str]) -> bool {
+        names.iter().all(|query| self.any_name(|name| name.eq_ignore_ascii_case(query)))
+    }
+
+    /// Like `which_present` but compares the names ignoring (ascii) case.
+    ///
+    /// See `contains_any_str`.
+    pub fn which_present_str(&self, names: &[&str]) -> Vec<bool> {
+        names.iter()
+            .map(|query| self.any_name(|name| name.eq_ignore_ascii_case(query)))
+            .collect()
+    }
+
+    /// Returns true if `pred` returns true for the name of any distinct header name in the map.
+    fn any_name<F>(&self, mut pred: F) -> bool
+        where F: FnMut(&str) -> bool
+    {
+        for mut group in self.inner_map.group_iter() {
+            let first = group.next().expect("[BUG] returned header without any headers inserted for it");
+            if pred(first.name().as_str()) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderKind;
+    use ::headers::*;
+    use super::*;
+
+    test!(empty_slices {
+        let map = headers! {
+            Subject: "abc"
+        }?;
+        assert!(!map.contains_any(&[]));
+        assert!(map.contains_all(&[]));
+        assert!(map.which_present(&[]).is_empty());
+        assert!(!map.contains_any_str(&[]));
+        assert!(map.contains_all_str(&[]));
+        assert!(map.which_present_str(&[]).is_empty());
+
+        let empty = HeaderMap::new();
+        assert!(!empty.contains_any(&[]));
+        assert!(empty.contains_all(&[]));
+        assert!(!empty.contains_any_str(&[]));
+        assert!(empty.contains_all_str(&[]));
+    });
+
+    test!(absent_names {
+        let map = headers! {
+            _From: ["a@example.com"],
+            Comments: "1st",
+            Comments: "2nd"
+        }?;
+
+        let names = &[_From::name(), Subject::name(), Comments::name(), Sender::name()];
+        assert!(map.contains_any(names));
+        assert!(!map.contains_all(names));
+        assert_eq!(map.which_present(names), vec![true, false, true, false]);
+
+        assert!(!map.contains_any(&[Subject::name(), Sender::name()]));
+        assert!(map.contains_all(&[Comments::name(), _From::name()]));
+
+        let queries = &["From", "Subject", "Comments", "Sender", "", "From "];
+        assert!(map.contains_any_str(queries));
+        assert!(!map.contains_all_str(queries));
+        assert_eq!(map.which_present_str(queries), vec![true, false, true, false, false, false]);
+        assert!(!map.contains_any_str(&["Subject", "Sender", "Comment"]));
+    });
+
+    test!(str_queries_ignore_case {
+        let map = headers! {
+            MessageId: "a@example.com",
+            Subject: "abc"
+        }?;
+
+        assert!(map.contains_all_str(&["message-id", "MESSAGE-ID", "Message-ID", "sUbJeCt"]));
+        assert!(map.contains_any_str(&["x-mailer", "SUBJECT"]));
+        assert_eq!(map.which_present_str(&["subject", "X-Mailer", "MeSSaGe-iD"]), vec![true, false, true]);
+    });
+
+    test!(str_queries_agree_with_names {
+        let map = headers! {
+            _From: ["a@example.com"],
+            _To: ["b@example.com"],
+            Subject: "abc"
+        }?;
+
+        let names = &[_From::name(), _To::name(), Cc::name(), Subject::name()];
+        let queries = names.iter().map(|name| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(map.which_present(names), map.which_present_str(&queries));
+        assert_eq!(map.contains_any(names), map.contains_any_str(&queries));
+        assert_eq!(map.contains_all(names), map.contains_all_str(&queries));
+    });
+}
@@ -0,0 +1,277 @@
+use std::mem;
+
+use ::name::HeaderName;
+use ::header::HeaderKind;
+use ::headers::*;
+
+use super::HeaderMap;
+
+/// A canonical order of header names, see `HeaderMap::canonicalize_order`.
+///
+/// Headers with a trace name (see `with_trace_names`) are placed before all
+/// other headers, keeping their relative order (i.e. they are not ordered by
+/// name). The other headers are ordered by the position of their name in the
+/// policy, headers with the same name (e.g. multiple `Comments` headers) keep
+/// their relative order. Headers whose name is not part of the policy are
+/// placed after all other headers, keeping their relative order, too.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OrderingPolicy {
+    trace: Vec<HeaderName>,
+    order: Vec<HeaderName>
+}
+
+impl OrderingPolicy {
+
+    /// Creates a policy ordering headers in the order of the given names.
+    pub fn new(order: Vec<HeaderName>) -> Self {
+        OrderingPolicy { trace: Vec::new(), order }
+    }
+
+    /// Sets the names of the headers which are kept in their order at the top.
+    ///
+    /// This is meant for headers whose order relative to each other is
+    /// semantically relevant, like the trace and resent blocks of rfc5322.
+    pub fn with_trace_names(mut self, trace: Vec<HeaderName>) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// The order of header fields as specified by rfc5322, followed by the MIME headers.
+    ///
+    /// The trace (`Return-Path`, `Received`) and resent (`Resent-*`) headers
+    /// are kept together in their order at the top (rfc5322 section 3.6), as
+    /// the order of the trace and resent blocks is semantically relevant. The
+    /// other fields are placed after them in the order in which rfc5322 lists
+    /// them.
+    pub fn rfc5322() -> Self {
+        OrderingPolicy::new(vec![
+            Date::name(), _From::name(), Sender::name(), ReplyTo::name(),
+            _To::name(), Cc::name(), Bcc::name(),
+            MessageId::name(), InReplyTo::name(), References::name(),
+            Subject::name(), Comments::name(), Keywords::name(),
+            ContentType::name(), ContentTransferEncoding::name(), ContentId::name(),
+            ContentDescription::name(), ContentDisposition::name()
+        ]).with_trace_names(vec![
+            ReturnPath::name(), Received::name(),
+            ResentDate::name(), ResentFrom::name(), ResentSender::name(),
+            ResentTo::name(), ResentCc::name(), ResentBcc::name(), ResentMsgId::name()
+        ])
+    }
+
+    /// Returns the names in the order defined by this policy (excluding the trace names).
+    pub fn names(&self) -> &[HeaderName] {
+        &self.order
+    }
+
+    /// Returns the names of the headers which are kept in their order at the top.
+    pub fn trace_names(&self) -> &[HeaderName] {
+        &self.trace
+    }
+
+    fn rank(&self, name: HeaderName) -> usize {
+        if self.trace.contains(&name) {
+            return 0;
+        }
+        self.order.iter()
+            .position(|other| *other == name)
+            .map(|idx| idx + 1)
+            .unwrap_or_else(|| self.order.len() + 1)
+    }
+}
+
+impl Default for OrderingPolicy {
+    fn default() -> Self {
+        OrderingPolicy::rfc5322()
+    }
+}
+
+impl HeaderMap {
+
+    /// Reorders the headers in this map according to given policy.
+    ///
+    /// This changes the order in which the headers are stored, so everything
+    /// (iteration, encoding etc.) sees the new order afterwards. The order is
+    /// stable, i.e. headers with the same rank in the policy keep their relative
+    /// order and canonicalizing an already canonical map changes nothing. The
+    /// sequence numbers of the headers (see `iter_with_seq`) move with the headers.
+    ///
+    /// Returns true if any header was moved.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    /// use mail_headers::map::OrderingPolicy;
+    ///
+    /// let mut map = headers! {
+    ///     Subject: "hy there",
+    ///     _From: ["me@example.com"]
+    /// }.unwrap();
+    ///
+    /// assert!(map.canonicalize_order(&OrderingPolicy::rfc5322()));
+    /// let names = map.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+    /// assert_eq!(names, vec!["From", "Subject"]);
+    /// assert!(!map.canonicalize_order(&OrderingPolicy::rfc5322()));
+    /// # }
+    /// ```
+    pub fn canonicalize_order(&mut self, policy: &OrderingPolicy) -> bool {
+        let ranks = self.iter()
+            .map(|(name, _)| policy.rank(name))
+            .collect::<Vec<_>>();
+
+        if ranks.windows(2).all(|pair| pair[0] <= pair[1]) {
+            return false;
+        }
+
+        let inner_map = mem::replace(&mut self.inner_map, Default::default());
+        let seqs = mem::replace(&mut self.seqs, Vec::new());
+        let mut entries = ranks.into_iter()
+            .zip(inner_map.into_iter().zip(seqs))
+            .collect::<Vec<_>>();
+
+        // `sort_by_key` is stable
+        entries.sort_by_key(|&(rank, _)| rank);
+
+        for (_rank, ((name, obj), seq)) in entries {
+            self.inner_map.add(name, obj);
+            self.seqs.push(seq);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use internals::MailType;
+    use ::HeaderTryFrom;
+    use ::header_components::{ReceivedToken, Domain, DateTime};
+    use super::*;
+
+    fn received(domain: &str) -> ReceivedToken {
+        ReceivedToken::Domain(Domain::try_from(domain).unwrap())
+    }
+
+    fn names(map: &HeaderMap) -> Vec<&'static str> {
+        map.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    fn encoded(map: &HeaderMap) -> String {
+        map.display(MailType::Ascii).to_string()
+    }
+
+    test!(shuffled_map_equals_canonical_map {
+        let mut shuffled = headers! {
+            Subject: "hy there",
+            Received: received("mx1.example.com"),
+            _To: ["you@example.com"],
+            Precedence: "bulk",
+            _From: ["me@example.com"],
+            Received: received("mx2.example.com")
+        }?;
+
+        let canonical = headers! {
+            Received: received("mx1.example.com"),
+            Received: received("mx2.example.com"),
+            _From: ["me@example.com"],
+            _To: ["you@example.com"],
+            Subject: "hy there",
+            Precedence: "bulk"
+        }?;
+
+        assert!(shuffled.canonicalize_order(&OrderingPolicy::rfc5322()));
+        assert_eq!(names(&shuffled), names(&canonical));
+        assert_eq!(encoded(&shuffled), encoded(&canonical));
+    });
+
+    test!(canonicalizing_is_idempotent {
+        let mut map = headers! {
+            Subject: "hy there",
+            _From: ["me@example.com"]
+        }?;
+        let policy = OrderingPolicy::default();
+
+        assert!(map.canonicalize_order(&policy));
+        let first = encoded(&map);
+        assert!(!map.canonicalize_order(&policy));
+        assert_eq!(encoded(&map), first);
+    });
+
+    test!(duplicates_keep_their_order {
+        let mut map = headers! {
+            Received: received("c.example.com"),
+            Subject: "hy there",
+            Received: received("a.example.com"),
+            Received: received("b.example.com")
+        }?;
+
+        map.canonicalize_order(&OrderingPolicy::rfc5322());
+        let text = encoded(&map);
+        let first = text.find("c.example.com").unwrap();
+        let second = text.find("a.example.com").unwrap();
+        let third = text.find("b.example.com").unwrap();
+        assert!(first < second && second < third);
+        assert_eq!(names(&map), vec!["Received", "Received", "Received", "Subject"]);
+    });
+
+    test!(seqs_move_with_headers {
+        let mut map = headers! {
+            Subject: "hy there",
+            _From: ["me@example.com"]
+        }?;
+        let subject_seq = map.seq_of(0).unwrap();
+        let from_seq = map.seq_of(1).unwrap();
+        let max_seq = map.max_seq();
+
+        map.canonicalize_order(&OrderingPolicy::rfc5322());
+        assert_eq!(map.seq_of(0), Some(from_seq));
+        assert_eq!(map.seq_of(1), Some(subject_seq));
+        assert_eq!(map.max_seq(), max_seq);
+    });
+
+    test!(trace_and_resent_blocks_stay_together_at_the_top {
+        let mut map = headers! {
+            Subject: "hy there",
+            ReturnPath: "bounce@example.com",
+            Received: received("mx2.example.com"),
+            _From: ["me@example.com"],
+            ResentDate: DateTime::test_time(2),
+            ResentFrom: ["relay@example.com"],
+            Received: received("mx1.example.com"),
+            ResentDate: DateTime::test_time(1),
+            ResentFrom: ["origin@example.com"],
+            Date: DateTime::test_time(0)
+        }?;
+
+        assert!(map.canonicalize_order(&OrderingPolicy::rfc5322()));
+        assert_eq!(names(&map), vec![
+            "Return-Path", "Received", "Resent-Date", "Resent-From",
+            "Received", "Resent-Date", "Resent-From",
+            "Date", "From", "Subject"
+        ]);
+
+        let text = encoded(&map);
+        assert!(text.find("mx2.example.com").unwrap() < text.find("relay@example.com").unwrap());
+        assert!(text.find("relay@example.com").unwrap() < text.find("mx1.example.com").unwrap());
+        assert!(text.find("mx1.example.com").unwrap() < text.find("origin@example.com").unwrap());
+        assert_eq!(map.resent_blocks().count(), 2);
+        assert!(!map.canonicalize_order(&OrderingPolicy::rfc5322()));
+    });
+
+    test!(custom_trace_names {
+        let policy = OrderingPolicy::new(vec![Subject::name()])
+            .with_trace_names(vec![Comments::name()]);
+        assert_eq!(policy.trace_names(), &[Comments::name()]);
+
+        let mut map = headers! {
+            Subject: "hy there",
+            Comments: "first",
+            Precedence: "bulk",
+            Comments: "second"
+        }?;
+        assert!(map.canonicalize_order(&policy));
+        assert_eq!(names(&map), vec!["Comments", "Comments", "Subject", "Precedence"]);
+    });
+}
@@ -0,0 +1,401 @@
+#[allow(deprecated)]
+use std::hash::{Hasher, SipHasher};
+
+use ::header::{Header, HeaderKind, HeaderObj, HeaderObjTrait};
+use ::header_components::{
+    Domain, Email, LocalPart, Mailbox, MailboxList, MessageId,
+    MessageIdList, OptMailboxList, Phrase, Unstructured
+};
+use ::headers::{
+    _From, Sender, ReplyTo, _To, Cc, Bcc,
+    ResentFrom, ResentSender, ResentTo, ResentCc, ResentBcc,
+    ReturnPath, MessageId as MessageIdHeader, InReplyTo, References, ResentMsgId,
+    Subject
+};
+use ::HeaderTryFrom;
+
+use super::{HeaderMap, ValidatorCarryPolicy};
+
+/// Options for `HeaderMap::pseudonymize_with`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PseudonymizeOptions {
+    /// Also pseudonymizes the message ids (`Message-Id`, `In-Reply-To`, `References`
+    /// and `Resent-Msg-Id`).
+    ///
+    /// As equal ids are mapped to equal pseudonyms the threading information
+    /// is kept. Default: `true`.
+    pub message_ids: bool,
+
+    /// Replaces the `Subject` with a pseudonym.
+    ///
+    /// Default: `false`.
+    pub subject: bool
+}
+
+impl Default for PseudonymizeOptions {
+    fn default() -> Self {
+        PseudonymizeOptions {
+            message_ids: true,
+            subject: false
+        }
+    }
+}
+
+impl HeaderMap {
+
+    /// Creates a copy of this map with all addresses and display names replaced by pseudonyms.
+    ///
+    /// This uses the default `PseudonymizeOptions`, see `pseudonymize_with`.
+    pub fn pseudonymize(&self, key: &[u8]) -> HeaderMap {
+        self.pseudonymize_with(key, PseudonymizeOptions::default())
+    }
+
+    /// Creates a copy of this map with all addresses and display names replaced by pseudonyms.
+    ///
+    /// This is meant to turn real header maps into shareable test fixtures.
+    /// In the build-in address headers (`From`, `Sender`, `Reply-To`, `To`,
+    /// `Cc`, `Bcc`, the `Resent-*` address headers and `Return-Path`):
+    ///
+    /// - local parts are replaced by `u` followed by hex digits
+    /// - display names are replaced by `Person` followed by hex digits
+    /// - domains are mapped label by label to a domain under `.example`,
+    ///   e.g. `mail.example.com` to `l….l….example`, keeping the number of
+    ///   labels, shared parent domains and whether a label is internationalized
+    ///
+    /// Depending on the options message ids (both parts) and the `Subject` are
+    /// replaced, too. All other headers (including e.g. `Received`) are cloned
+    /// as they are, the order and the number of addresses in each header is kept.
+    ///
+    /// The pseudonyms are derived from the original value with a keyed hash
+    /// (SipHash-2-4 with a key derived from `key`), so the same value is mapped
+    /// to the same pseudonym when using the same key (keeping e.g. threading
+    /// and deduplication intact) while different keys produce unrelated
+    /// pseudonyms. Domains are lowercased before hashing, other values are
+    /// hashed as they are. SipHash is a keyed pseudo random function but a
+    /// short key or a small set of possible inputs (e.g. known employee names)
+    /// still allows reverting pseudonyms by trying all candidates, so use a
+    /// long random key which is not shared with the fixtures.
+    ///
+    /// The copy contains the same headers and carries their validators like
+    /// this map (see `ValidatorCarryPolicy`). This map is not modified.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # extern crate mail_internals;
+    /// # fn main() {
+    /// use mail_internals::MailType;
+    /// use mail_headers::headers::*;
+    ///
+    /// let map = headers! {
+    ///     _From: [("Jane Doe", "jane.doe@example.com")],
+    ///     _To: ["bob@example.com"],
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// let fixture = map.pseudonymize(b"some long random key");
+    /// let encoded = fixture.to_string(MailType::Ascii).unwrap();
+    /// assert!(!encoded.contains("jane"));
+    /// assert!(!encoded.contains("bob"));
+    /// assert!(encoded.contains("Subject: hy there"));
+    ///
+    /// assert!(fixture.eq_by_encoding(&map.pseudonymize(b"some long random key"), MailType::Ascii).unwrap());
+    /// # }
+    /// ```
+    pub fn pseudonymize_with(&self, key: &[u8], options: PseudonymizeOptions) -> HeaderMap {
+        let pseudonyms = Pseudonyms::new(key);
+        let mut out = HeaderMap::new();
+        for ((_name, obj), &seq) in self.inner_map.iter().zip(self.seqs.iter()) {
+            let obj = pseudonyms.header(&**obj, options)
+                .unwrap_or_else(|| obj.boxed_clone());
+            out.inner_map.add(obj.name(), obj);
+            out.seqs.push(seq);
+        }
+        out.max_seq = self.max_seq;
+        self.carry_validators_into(&mut out, &ValidatorCarryPolicy::CarryAll);
+        out
+    }
+}
+
+/// Derives the pseudonyms from a key.
+struct Pseudonyms {
+    k0: u64,
+    k1: u64
+}
+
+impl Pseudonyms {
+
+    fn new(key: &[u8]) -> Self {
+        Pseudonyms {
+            k0: sip_hash(0, 0, "key0", key),
+            k1: sip_hash(0, 0, "key1", key)
+        }
+    }
+
+    /// Returns `len` (max 16) hex digits derived from the (tagged) input.
+    fn token(&self, tag: &str, input: &str, len: usize) -> String {
+        let hash = sip_hash(self.k0, self.k1, tag, input.as_bytes());
+        let mut hex = format!("{:016x}", hash);
+        hex.truncate(len);
+        hex
+    }
+
+    /// Returns the pseudonymized header or `None` if the header is kept as it is.
+    fn header(&self, obj: &HeaderObj, options: PseudonymizeOptions) -> Option<Box<HeaderObj>> {
+        macro_rules! try_kinds {
+            ($method:ident: $($kind:ident),*) => ($(
+                if let Some(header) = obj.downcast_ref::<$kind>() {
+                    let mut header = header.clone();
+                    self.$method(header.body_mut());
+                    return Some(boxed(header));
+                }
+            )*);
+        }
+
+        try_kinds!(mailbox_list: _From, ReplyTo, _To, Cc, Bcc, ResentFrom, ResentTo, ResentCc);
+        try_kinds!(opt_mailbox_list: ResentBcc);
+        try_kinds!(mailbox: Sender, ResentSender);
+        if let Some(header) = obj.downcast_ref::<ReturnPath>() {
+            let mut header = header.clone();
+            if let Some(ref mut email) = header.body_mut().0 {
+                self.email(email);
+            }
+            return Some(boxed(header));
+        }
+        if options.message_ids {
+            try_kinds!(message_id: MessageIdHeader, ResentMsgId);
+            try_kinds!(message_id_list: InReplyTo, References);
+        }
+        if options.subject {
+            try_kinds!(unstructured: Subject);
+        }
+        None
+    }
+
+    fn mailbox_list(&self, list: &mut MailboxList) {
+        for mailbox in list.0.iter_mut() {
+            self.mailbox(mailbox);
+        }
+    }
+
+    fn opt_mailbox_list(&self, list: &mut OptMailboxList) {
+        for mailbox in list.0.iter_mut() {
+            self.mailbox(mailbox);
+        }
+    }
+
+    fn mailbox(&self, mailbox: &mut Mailbox) {
+        if mailbox.display_name.is_some() {
+            let name = mailbox.sort_key().name().to_owned();
+            let pseudonym = format!("Person {}", self.token("name", &name, 8));
+            mailbox.display_name = Some(Phrase::try_from(pseudonym)
+                .expect("[BUG] pseudonym is a valid phrase"));
+        }
+        self.email(&mut mailbox.email);
+    }
+
+    fn email(&self, email: &mut Email) {
+        let local = format!("u{}", self.token("local-part", email.local_part.as_str(), 12));
+        email.local_part = LocalPart::try_from(local)
+            .expect("[BUG] pseudonym is a valid local part");
+        email.domain = Domain::try_from(self.domain(email.domain.as_str()))
+            .expect("[BUG] pseudonym is a valid domain");
+    }
+
+    fn message_id_list(&self, list: &mut MessageIdList) {
+        for id in list.0.iter_mut() {
+            self.message_id(id);
+        }
+    }
+
+    fn message_id(&self, id: &mut MessageId) {
+        let pseudonym = {
+            let text = id.as_str();
+            let (left, right) = match text.rfind('@') {
+                Some(idx) => (&text[..idx], &text[idx+1..]),
+                None => (text, "")
+            };
+            format!("m{}@{}", self.token("id-left", left, 16), self.domain(right))
+        };
+        *id = MessageId::from_unchecked(pseudonym);
+    }
+
+    fn unstructured(&self, text: &mut Unstructured) {
+        let pseudonym = format!("Subject {}", self.token("unstructured", text.as_str(), 12));
+        *text = Unstructured::from(pseudonym);
+    }
+
+    /// Maps each label (but the last) to a label derived from the domain it names.
+    ///
+    /// The last label is replaced by `example`, domain literals are mapped to
+    /// a domain with a single (non top level) label.
+    fn domain(&self, domain: &str) -> String {
+        let domain = domain.to_lowercase();
+        if domain.starts_with('[') || domain.is_empty() {
+            return format!("d{}.example", self.token("domain", &domain, 10));
+        }
+
+        let labels = domain.split('.').collect::<Vec<_>>();
+        let mapped_count = if labels.len() > 1 { labels.len() - 1 } else { 1 };
+        let mut out = String::new();
+        for idx in 0..mapped_count {
+            let name = labels[idx..].join(".");
+            let prefix = if labels[idx].is_ascii() { "l" } else { "ü" };
+            out.push_str(prefix);
+            out.push_str(&self.token("domain", &name, 10));
+            out.push('.');
+        }
+        out.push_str("example");
+        out
+    }
+}
+
+#[allow(deprecated)]
+fn sip_hash(k0: u64, k1: u64, tag: &str, input: &[u8]) -> u64 {
+    // `SipHasher` is deprecated as it might be removed in the future, but unlike
+    // `DefaultHasher` it is guaranteed to stay SipHash-2-4 while it's there
+    let mut hasher = SipHasher::new_with_keys(k0, k1);
+    hasher.write(tag.as_bytes());
+    hasher.write_u8(0xff);
+    hasher.write(input);
+    hasher.finish()
+}
+
+fn boxed<H: HeaderKind>(header: Header<H>) -> Box<HeaderObj> {
+    Box::new(header)
+}
+
+#[cfg(test)]
+mod test {
+    use internals::MailType;
+
+    use ::HeaderKind;
+    use ::headers::*;
+    use ::headers::MessageId;
+    use ::header_components::{MessageIdList, MessageId as MessageIdComponent};
+    use super::*;
+
+    const KEY: &[u8] = b"a key only used for tests";
+
+    fn base() -> HeaderMap {
+        let mut map = headers! {
+            _From: [("Jane Doe", "jane.doe@mail.example.com"), "bob@example.com"],
+            Sender: "jane.doe@mail.example.com",
+            _To: ["carl@bücher.de"],
+            Cc: [("Dora Dot", "dora@example.com")],
+            MessageId: "abc.123@mail.example.com",
+            Subject: "secret plans",
+            Comments: "kept as it is"
+        }.unwrap();
+        map.insert(Header::<InReplyTo>::new(MessageIdList(vec1![
+            MessageIdComponent::try_from("parent@example.com").unwrap()
+        ])));
+        map.insert(Header::<References>::new(MessageIdList(vec1![
+            MessageIdComponent::try_from("root@example.com").unwrap(),
+            MessageIdComponent::try_from("parent@example.com").unwrap()
+        ])));
+        map
+    }
+
+    fn ids(map: &HeaderMap, name: HeaderName) -> Vec<String> {
+        let obj = map.get_untyped(name).next().unwrap();
+        if let Some(list) = obj.downcast_ref::<References>() {
+            list.body().iter().map(|id| id.as_str().to_owned()).collect()
+        } else if let Some(list) = obj.downcast_ref::<InReplyTo>() {
+            list.body().iter().map(|id| id.as_str().to_owned()).collect()
+        } else {
+            let id = obj.downcast_ref::<MessageId>().unwrap();
+            vec![id.body().as_str().to_owned()]
+        }
+    }
+
+    test!(same_key_same_output {
+        let map = base();
+        let first = map.pseudonymize(KEY);
+        let second = map.pseudonymize(KEY);
+        assert!(first.eq_by_encoding(&second, MailType::Internationalized)?);
+        assert!(!first.eq_by_encoding(&map, MailType::Internationalized)?);
+    });
+
+    test!(different_keys_differ {
+        let map = base();
+        let first = map.pseudonymize(KEY);
+        let second = map.pseudonymize(b"another key");
+        assert!(!first.eq_by_encoding(&second, MailType::Internationalized)?);
+    });
+
+    test!(original_values_are_gone {
+        let map = base();
+        let encoded = map.pseudonymize(KEY).to_string(MailType::Internationalized)?;
+        for original in &["jane", "Jane", "bob", "carl", "dora", "Dora", "bücher", "abc.123", "parent", "root"] {
+            assert!(!encoded.contains(original), "{} in {}", original, encoded);
+        }
+        // not pseudonymized by default
+        assert!(encoded.contains("Subject: secret plans"));
+        assert!(encoded.contains("Comments: kept as it is"));
+    });
+
+    test!(structure_is_kept {
+        let map = base();
+        let fixture = map.pseudonymize(KEY);
+        let names = |map: &HeaderMap| map.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names(&fixture), names(&map));
+
+        let from = fixture.get_single(_From).unwrap()?;
+        assert_eq!(from.len(), 2);
+        assert!(from[0].display_name.is_some());
+        assert!(from[1].display_name.is_none());
+
+        // shared parent domains stay shared
+        let first_domain = from[0].email.domain.as_str();
+        let second_domain = from[1].email.domain.as_str();
+        assert_eq!(first_domain.split('.').count(), 3);
+        assert!(first_domain.ends_with(&format!(".{}", second_domain)));
+        assert!(second_domain.ends_with(".example"));
+
+        // the same address is mapped to the same pseudonym
+        let sender = fixture.get_single(Sender).unwrap()?;
+        assert_eq!(sender.email, from[0].email);
+
+        // internationalized domains stay internationalized
+        let to = fixture.get_single(_To).unwrap()?;
+        assert!(!to[0].email.domain.as_str().is_ascii());
+        assert!(to[0].email.local_part.as_str().is_ascii());
+    });
+
+    test!(threading_is_kept {
+        let fixture = base().pseudonymize(KEY);
+        let in_reply_to = ids(&fixture, InReplyTo::name());
+        let references = ids(&fixture, References::name());
+        assert_eq!(references.len(), 2);
+        assert_eq!(in_reply_to, vec![references[1].clone()]);
+        assert_ne!(references[0], references[1]);
+
+        // replying to the pseudonymized mail with the pseudonymized original id fits
+        let parent = headers! {
+            MessageId: "parent@example.com"
+        }?.pseudonymize(KEY);
+        assert_eq!(ids(&parent, MessageId::name()), in_reply_to);
+    });
+
+    test!(message_ids_and_subject_are_optional {
+        let map = base();
+        let options = PseudonymizeOptions { message_ids: false, subject: true };
+        let fixture = map.pseudonymize_with(KEY, options);
+        assert_eq!(ids(&fixture, MessageId::name()), vec!["abc.123@mail.example.com".to_owned()]);
+        assert_eq!(ids(&fixture, References::name()), ids(&map, References::name()));
+
+        let subject = fixture.get_single(Subject).unwrap()?;
+        assert!(subject.as_str().starts_with("Subject "));
+        assert!(!subject.as_str().contains("secret"));
+    });
+
+    test!(output_validates_and_encodes {
+        let fixture = base().pseudonymize(KEY);
+        assert_ok!(fixture.validate());
+        assert_ok!(fixture.to_string(MailType::Internationalized));
+        assert_ok!(fixture.to_string(MailType::Ascii));
+    });
+}
@@ -0,0 +1,235 @@
+use std::any::TypeId;
+
+use ::name::HeaderName;
+use ::header::{Header, HeaderKind, HeaderObjTrait};
+use ::headers::*;
+
+use super::{HeaderMap, ValidatorCarryPolicy};
+
+/// Options for `HeaderMap::public_headers_with`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub struct PublicHeadersOptions {
+    /// Also include the trace headers `Received` and `Return-Path`.
+    pub include_trace: bool,
+
+    /// Only include headers which are of the built-in header kind.
+    ///
+    /// By default only the name is checked, i.e. a custom header kind using
+    /// e.g. the name `Subject` (with whatever component) is included. With
+    /// this option it has to be the `headers::Subject` header kind.
+    pub strict_types: bool
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Tier {
+    /// always included
+    General,
+    /// only included with `include_trace`
+    Trace,
+    /// never included (but still built-in)
+    Private
+}
+
+struct Entry {
+    name: fn() -> HeaderName,
+    type_id: fn() -> TypeId,
+    tier: Tier
+}
+
+macro_rules! entries {
+    ($($tier:ident: [$($header:ident),*]),*) => (&[$($(
+        Entry {
+            name: <$header as HeaderKind>::name,
+            type_id: TypeId::of::<Header<$header>>,
+            tier: Tier::$tier
+        },
+    )*)*]);
+}
+
+/// All headers defined by rfc5322/2045/2183 which are defined by this crate.
+///
+/// Non standard headers (e.g. `Precedence` or `X-Redacted`) are not included.
+static BUILTIN: &[Entry] = entries! {
+    General: [
+        Date, _From, Sender, ReplyTo, _To, Cc, MessageId, InReplyTo, References,
+        Subject, Comments, Keywords, ResentDate, ResentFrom, ResentSender, ResentTo,
+        ResentCc, ResentMsgId, ContentType, ContentId, ContentTransferEncoding,
+        ContentDescription, ContentDisposition
+    ],
+    Trace: [ReturnPath, Received],
+    Private: [Bcc, ResentBcc]
+};
+
+fn builtin_entry(name: &HeaderName) -> Option<&'static Entry> {
+    BUILTIN.iter().find(|entry| (entry.name)() == *name)
+}
+
+/// Returns true if given name is the name of a header defined by rfc5322/2045/2183
+/// which this crate defines in `headers`.
+///
+/// Non standard headers defined by this crate (like `Precedence`) are not
+/// considered built-in.
+pub fn is_builtin_name(name: &HeaderName) -> bool {
+    builtin_entry(name).is_some()
+}
+
+impl HeaderMap {
+
+    /// Returns a copy of this map only containing headers which are safe to make public.
+    ///
+    /// This is the same as `public_headers_with(Default::default())`.
+    pub fn public_headers(&self) -> HeaderMap {
+        self.public_headers_with(Default::default())
+    }
+
+    /// Returns a copy of this map only containing headers which are safe to make public.
+    ///
+    /// This uses a conservative allowlist of built-in headers (see
+    /// `is_builtin_name`) from which following headers are excluded:
+    ///
+    /// - `Bcc` and `Resent-Bcc`, always
+    /// - the trace headers `Received` and `Return-Path`, unless
+    ///   `include_trace` is set
+    ///
+    /// Any other header, e.g. all `X-` headers, is never included. The order
+    /// (and the sequence numbers) of the included headers is preserved.
+    ///
+    /// **Note that by default only the name of a header is checked**, i.e. if
+    /// a custom header kind uses an allowlisted name like `Subject` it is
+    /// included independent of its component. Use `strict_types` to only
+    /// include headers of the built-in header kinds.
+    ///
+    /// The returned map carries the validators of its headers like this map,
+    /// use `public_headers_carrying` to change this.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    ///
+    /// let map = headers! {
+    ///     _From: ["me@example.com"],
+    ///     Bcc: ["hidden@example.com"],
+    ///     Subject: "hy there",
+    ///     Precedence: "bulk"
+    /// }.unwrap();
+    ///
+    /// let public = map.public_headers();
+    /// assert_eq!(public.len(), 2);
+    /// assert!(public.contains(_From));
+    /// assert!(public.contains(Subject));
+    /// # }
+    /// ```
+    pub fn public_headers_with(&self, opts: PublicHeadersOptions) -> HeaderMap {
+        self.public_headers_carrying(opts, ValidatorCarryPolicy::CarryAll)
+    }
+
+    /// Like `public_headers_with` but the validators of the returned map's headers are run as given by `carry`.
+    pub fn public_headers_carrying(&self, opts: PublicHeadersOptions, carry: ValidatorCarryPolicy) -> HeaderMap {
+        let mut public = HeaderMap::new();
+        for ((name, obj), &seq) in self.inner_map.iter().zip(self.seqs.iter()) {
+            let entry = match builtin_entry(&name) {
+                Some(entry) => entry,
+                None => continue
+            };
+            let allowed = match entry.tier {
+                Tier::General => true,
+                Tier::Trace => opts.include_trace,
+                Tier::Private => false
+            };
+            if !allowed {
+                continue;
+            }
+            if opts.strict_types && HeaderObjTrait::type_id(&**obj) != (entry.type_id)() {
+                continue;
+            }
+            public.inner_map.add(name, obj.boxed_clone());
+            public.seqs.push(seq);
+        }
+        public.max_seq = self.max_seq;
+        self.carry_validators_into(&mut public, &carry);
+        public
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::{HeaderKind, HeaderMap, HeaderTryFrom};
+    use ::header_components::{self as components, ReceivedToken, Domain};
+    use ::headers::{_From, Bcc, Subject, Received, ReturnPath, Precedence};
+    use super::*;
+
+    def_headers! {
+        test_name: validate_header_names,
+        scope: components,
+        XInternalId, unchecked { "X-Internal-Id" }, Unstructured, maxOne, None,
+        SquattingSubject, unchecked { "Subject" }, RawUnstructured, maxOne, None
+    }
+
+    fn names(map: &HeaderMap) -> Vec<&'static str> {
+        map.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    fn internal_map() -> HeaderMap {
+        let mut map = headers! {
+            Received: ReceivedToken::Domain(Domain::try_from("mx.example.com").unwrap()),
+            ReturnPath: "bounce@example.com",
+            _From: ["me@example.com"],
+            XInternalId: "1234",
+            Bcc: ["hidden@example.com"],
+            Precedence: "bulk"
+        }.unwrap();
+        map.insert(Subject::auto_body("hy there").unwrap());
+        map
+    }
+
+    #[test]
+    fn builtin_names() {
+        assert!(is_builtin_name(&Subject::name()));
+        assert!(is_builtin_name(&Received::name()));
+        assert!(is_builtin_name(&Bcc::name()));
+        assert!(!is_builtin_name(&Precedence::name()));
+        assert!(!is_builtin_name(&XInternalId::name()));
+        assert!(is_builtin_name(&SquattingSubject::name()));
+    }
+
+    #[test]
+    fn default_excludes_internal_and_trace_headers() {
+        let public = internal_map().public_headers();
+        assert_eq!(names(&public), vec!["From", "Subject"]);
+    }
+
+    #[test]
+    fn trace_headers_can_be_included() {
+        let opts = PublicHeadersOptions { include_trace: true, ..Default::default() };
+        let public = internal_map().public_headers_with(opts);
+        assert_eq!(names(&public), vec!["Received", "Return-Path", "From", "Subject"]);
+    }
+
+    test!(strict_mode_checks_the_header_kind {
+        let mut map = internal_map();
+        map.insert(SquattingSubject::auto_body("squatting")?);
+
+        let lenient = map.public_headers();
+        assert_eq!(names(&lenient), vec!["From", "Subject"]);
+        assert!(lenient.get_single(SquattingSubject).unwrap().is_ok());
+
+        let opts = PublicHeadersOptions { strict_types: true, ..Default::default() };
+        let strict = map.public_headers_with(opts);
+        assert_eq!(names(&strict), vec!["From"]);
+
+        let strict = internal_map().public_headers_with(opts);
+        assert_eq!(names(&strict), vec!["From", "Subject"]);
+    });
+
+    #[test]
+    fn seqs_are_preserved() {
+        let map = internal_map();
+        let public = map.public_headers();
+        assert_eq!(public.seq_of(0), map.seq_of(2));
+        assert_eq!(public.seq_of(1), map.seq_of(6));
+    }
+}
@@ -0,0 +1,191 @@
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use soft_ascii::SoftAsciiStr;
+
+use internals::error::EncodingError;
+use internals::encoder::{EncodingWriter, EncodableInHeader};
+
+use ::name::HeaderName;
+use ::header::{HeaderObj, HeaderObjTrait};
+use ::error::ComponentCreationError;
+use ::header_components::RawFieldBody;
+use ::mail_type::{HasMailTypeRequirement, MailTypeRequirement};
+
+use super::HeaderMapValidator;
+
+/// The maximal number of distinct header names `RawHeader` creates at runtime.
+///
+/// The names have to live as long as the program (like the names of
+/// header kinds), so each distinct spelling is allocated once and never
+/// freed. This limit stops input with arbitrary names from growing the
+/// used memory unboundedly.
+pub const MAX_RUNTIME_NAMES: usize = 4096;
+
+static RUNTIME_NAMES: Lazy<Mutex<HashMap<String, HeaderName>>> = Lazy::new(Default::default);
+
+/// A header whose name is only known at runtime with a raw body.
+///
+/// This is what a `PairRegistry` with the raw fallback (the default, see
+/// `PairRegistry::set_raw_fallback`) creates for header names no parser
+/// is registered for, e.g. `X-Mailer` or `DKIM-Signature` when parsing a
+/// header block. The (unfolded) value is kept as a `RawValidation::Checked`
+/// `RawFieldBody` and encoded as it is. The header is not max one and
+/// has no validator.
+///
+/// As there is no header kind for it, it can only be accessed untyped,
+/// e.g. through `HeaderMap::get_untyped` and `RawHeader::from_obj`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RawHeader {
+    name: HeaderName,
+    body: RawFieldBody
+}
+
+impl RawHeader {
+
+    /// Creates a header with given name (in any letter case) and value.
+    ///
+    /// The name is converted into the capitalization scheme of `HeaderName`
+    /// (e.g. `Dkim-Signature`), the given spelling is kept as wire spelling.
+    ///
+    /// # Error
+    ///
+    /// Fails if the name is not a valid header field name, if there are
+    /// already `MAX_RUNTIME_NAMES` names or if the value is not valid for
+    /// a checked `RawFieldBody`.
+    pub fn new(name: &str, value: &str) -> Result<Self, ComponentCreationError> {
+        let name = runtime_name(name)
+            .ok_or_else(|| ComponentCreationError::new_with_str("HeaderName", name))?;
+        RawHeader::with_name(name, value)
+    }
+
+    pub(crate) fn with_name(name: HeaderName, value: &str) -> Result<Self, ComponentCreationError> {
+        let body = RawFieldBody::checked(value)?;
+        Ok(RawHeader { name, body })
+    }
+
+    /// Returns the raw header if the header object is one.
+    pub fn from_obj(obj: &HeaderObj) -> Option<&RawHeader> {
+        if HeaderObjTrait::type_id(obj) == TypeId::of::<RawHeader>() {
+            Some(unsafe { &*(obj as *const HeaderObj as *const RawHeader) })
+        } else {
+            None
+        }
+    }
+
+    pub fn body(&self) -> &RawFieldBody {
+        &self.body
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.body.as_str()
+    }
+}
+
+/// Returns the header name for the given spelling, creating it on first use.
+///
+/// Returns `None` if the name is invalid or there are already `MAX_RUNTIME_NAMES` names.
+pub(crate) fn runtime_name(spelling: &str) -> Option<HeaderName> {
+    let mut names = RUNTIME_NAMES.lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(&name) = names.get(spelling) {
+        return Some(name);
+    }
+    if names.len() >= MAX_RUNTIME_NAMES {
+        return None;
+    }
+
+    let mut begin_of_word = true;
+    let canonical = spelling.chars()
+        .map(|ch| {
+            let ch = if begin_of_word { ch.to_ascii_uppercase() } else { ch.to_ascii_lowercase() };
+            begin_of_word = !ch.is_ascii_alphanumeric();
+            ch
+        })
+        .collect::<String>();
+    let wire_name = SoftAsciiStr::from_str(spelling).ok()?;
+    HeaderName::new(SoftAsciiStr::from_str(&canonical).ok()?).ok()?
+        .with_wire_case(wire_name).ok()?;
+
+    // both are valid (checked above) and have to life as long as the program
+    let wire_name: &'static str = Box::leak(spelling.to_owned().into_boxed_str());
+    let canonical: &'static str =
+        if canonical == wire_name { wire_name } else { Box::leak(canonical.into_boxed_str()) };
+    let name = HeaderName::from_ascii_unchecked_with_wire_case(canonical, wire_name);
+    names.insert(spelling.to_owned(), name);
+    Some(name)
+}
+
+impl HeaderObjTrait for RawHeader {
+    fn name(&self) -> HeaderName {
+        self.name
+    }
+
+    fn is_max_one(&self) -> bool {
+        false
+    }
+
+    fn validator(&self) -> Option<HeaderMapValidator> {
+        None
+    }
+
+    fn encode(&self, handle: &mut EncodingWriter) -> Result<(), EncodingError> {
+        self.body.encode(handle)
+    }
+
+    fn mail_type_requirement(&self) -> MailTypeRequirement {
+        self.body.mail_type_requirement()
+    }
+
+    fn component_type_name(&self) -> &'static str {
+        ::std::any::type_name::<RawFieldBody>()
+    }
+
+    fn boxed_clone(&self) -> Box<HeaderObj> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use internals::MailType;
+    use ::HeaderMap;
+    use super::*;
+
+    test!(names_use_the_capitalization_scheme {
+        let header = RawHeader::new("DKIM-Signature", "v=1; a=rsa-sha256")?;
+        assert_eq!(header.name().as_str(), "Dkim-Signature");
+        assert_eq!(header.name().as_wire_str(), "DKIM-Signature");
+
+        let other = RawHeader::new("dkim-signature", "v=1")?;
+        assert_eq!(other.name(), header.name());
+        assert_eq!(other.name().as_wire_str(), "dkim-signature");
+
+        let mailer = RawHeader::new("X-Mailer", "mailer 1.0")?;
+        assert_eq!(mailer.name().as_str(), "X-Mailer");
+        assert_eq!(mailer.name().as_wire_str(), "X-Mailer");
+    });
+
+    #[test]
+    fn invalid_names_and_values_fail() {
+        assert_err!(RawHeader::new("", "v"));
+        assert_err!(RawHeader::new("X Mailer", "v"));
+        assert_err!(RawHeader::new("X:Mailer", "v"));
+        assert_err!(RawHeader::new("X-Mäiler", "v"));
+        assert_err!(RawHeader::new("X-Mailer", "a\r\nb"));
+    }
+
+    test!(encodes_the_raw_value {
+        let mut map = HeaderMap::new();
+        let header = RawHeader::new("X-Mailer", "some  mailer; v=1")?;
+        let name = header.name();
+        map.insert_untyped(Box::new(header));
+
+        let encoded = map.encode_checked(MailType::Ascii)?;
+        assert_eq!(String::from_utf8(encoded)?, "X-Mailer: some  mailer; v=1\r\n");
+        let obj = map.get_untyped(name).next().unwrap();
+        assert_eq!(RawHeader::from_obj(obj).unwrap().as_str(), "some  mailer; v=1");
+    });
+}
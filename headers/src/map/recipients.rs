@@ -0,0 +1,283 @@
+use std::collections::HashSet;
+
+use ::error::{HeaderTypeError, RecipientLimitError};
+use ::header_components::{Email, Mailbox};
+use ::headers::{_To, Cc, Bcc};
+
+use super::HeaderMap;
+
+/// Which address headers are counted by `HeaderMap::recipient_count`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum RecipientCountMode {
+    /// The `To`, `Cc` and `Bcc` headers.
+    Normal,
+    /// The `Resent-To`, `Resent-Cc` and `Resent-Bcc` headers of the newest resent block.
+    ///
+    /// Resent blocks are prepended, so this is the first block returned
+    /// by `HeaderMap::resent_blocks`.
+    Resent,
+    /// The headers of both `Normal` and `Resent`.
+    Both
+}
+
+/// How addresses are normalized before comparing them in `HeaderMap::recipient_count_deduped`.
+///
+/// Domains are always compared case insensitive and in their punycode form.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct AddressDedupRules {
+    /// Compare local parts case insensitive.
+    ///
+    /// Local parts are case sensitive by the standard, but treated case
+    /// insensitive by nearly all providers. Default: `true`.
+    pub ignore_local_part_case: bool,
+
+    /// Ignore the part of the local part starting with this separator (sub addressing).
+    ///
+    /// E.g. with `Some('+')` `john+news@example.com` is the same as
+    /// `john@example.com`. Default: `None`.
+    pub subaddress_separator: Option<char>
+}
+
+impl Default for AddressDedupRules {
+    fn default() -> Self {
+        AddressDedupRules {
+            ignore_local_part_case: true,
+            subaddress_separator: None
+        }
+    }
+}
+
+impl AddressDedupRules {
+
+    /// Returns the normalized form of the address, addresses are duplicates if it is equal.
+    pub fn normalize(&self, email: &Email) -> String {
+        let mut local_part = email.local_part.as_str();
+        if let Some(separator) = self.subaddress_separator {
+            if let Some(idx) = local_part.find(separator) {
+                local_part = local_part.get(..idx).unwrap_or(local_part);
+            }
+        }
+        let domain = match email.domain.to_ascii_string() {
+            Ok(ascii) => ascii.as_str().to_lowercase(),
+            Err(_) => email.domain.as_str().to_lowercase()
+        };
+        if self.ignore_local_part_case {
+            format!("{}@{}", local_part.to_lowercase(), domain)
+        } else {
+            format!("{}@{}", local_part, domain)
+        }
+    }
+}
+
+impl HeaderMap {
+
+    /// Counts the recipients in the address headers selected by `mode`.
+    ///
+    /// Each mailbox is counted, an address appearing in multiple headers
+    /// (or multiple times in the same header) is counted multiple times,
+    /// see `recipient_count_deduped`. This is meant as cheap check before
+    /// any more expensive processing of the recipients (e.g. deriving the
+    /// envelope), see `guard_recipient_limit`.
+    ///
+    /// # Error
+    ///
+    /// If one of the counted headers is not of the kind of this crate
+    /// (e.g. a custom `To` header using another component) a
+    /// `HeaderTypeError` is returned instead of ignoring its recipients.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    /// use mail_headers::map::RecipientCountMode;
+    ///
+    /// let map = headers! {
+    ///     _From: ["me@example.com"],
+    ///     _To: ["a@example.com", "b@example.com"],
+    ///     Bcc: ["a@example.com"]
+    /// }.unwrap();
+    ///
+    /// assert_eq!(map.recipient_count(RecipientCountMode::Normal).unwrap(), 3);
+    /// assert_eq!(map.recipient_count(RecipientCountMode::Resent).unwrap(), 0);
+    /// # }
+    /// ```
+    pub fn recipient_count(&self, mode: RecipientCountMode) -> Result<usize, HeaderTypeError> {
+        let mut count = 0;
+        self.for_each_recipient(mode, |_| count += 1)?;
+        Ok(count)
+    }
+
+    /// Like `recipient_count` but counts addresses which are equal when normalized only once.
+    pub fn recipient_count_deduped(&self, mode: RecipientCountMode, rules: &AddressDedupRules)
+        -> Result<usize, HeaderTypeError>
+    {
+        let mut seen = HashSet::new();
+        self.for_each_recipient(mode, |mailbox| {
+            seen.insert(rules.normalize(&mailbox.email));
+        })?;
+        Ok(seen.len())
+    }
+
+    /// Fails if the map has more than `max` recipients (as counted by `recipient_count`).
+    ///
+    /// # Error
+    ///
+    /// Returns `RecipientLimitError::Exceeded` if there are more than `max`
+    /// recipients and `RecipientLimitError::Type` if they can not be counted.
+    pub fn guard_recipient_limit(&self, max: usize, mode: RecipientCountMode)
+        -> Result<(), RecipientLimitError>
+    {
+        let count = self.recipient_count(mode)?;
+        if count > max {
+            Err(RecipientLimitError::Exceeded { count, limit: max })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn for_each_recipient<F>(&self, mode: RecipientCountMode, mut func: F) -> Result<(), HeaderTypeError>
+        where F: FnMut(&Mailbox)
+    {
+        if mode != RecipientCountMode::Resent {
+            for header in self.get(_To) {
+                for mailbox in header?.body().0.iter() {
+                    func(mailbox);
+                }
+            }
+            for header in self.get(Cc) {
+                for mailbox in header?.body().0.iter() {
+                    func(mailbox);
+                }
+            }
+            for header in self.get(Bcc) {
+                for mailbox in header?.body().0.iter() {
+                    func(mailbox);
+                }
+            }
+        }
+
+        if mode != RecipientCountMode::Normal {
+            if let Some(block) = self.resent_blocks().next() {
+                if let Some(header) = block.resent_to() {
+                    for mailbox in header?.body().0.iter() {
+                        func(mailbox);
+                    }
+                }
+                if let Some(header) = block.resent_cc() {
+                    for mailbox in header?.body().0.iter() {
+                        func(mailbox);
+                    }
+                }
+                if let Some(header) = block.resent_bcc() {
+                    for mailbox in header?.body().0.iter() {
+                        func(mailbox);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::headers::*;
+    use ::header_components::{self, DateTime};
+    use ::error::LimitKind;
+    use super::*;
+    use super::RecipientCountMode::*;
+
+    def_headers! {
+        test_name: validate_header_names,
+        scope: header_components,
+        OtherCc, unchecked { "Cc" }, Unstructured, maxOne, None
+    }
+
+    test!(counts_to_cc_and_bcc {
+        let map = headers! {
+            _From: ["me@example.com"],
+            _To: ["a@example.com", "b@example.com"],
+            Cc: ["c@example.com"],
+            Bcc: ["a@example.com", "d@example.com"],
+            ReplyTo: ["e@example.com"]
+        }?;
+        assert_eq!(map.recipient_count(Normal)?, 5);
+        assert_eq!(map.recipient_count(Resent)?, 0);
+        assert_eq!(map.recipient_count(Both)?, 5);
+        assert_eq!(HeaderMap::new().recipient_count(Both)?, 0);
+    });
+
+    test!(resent_mode_only_counts_the_newest_block {
+        let map = headers! {
+            ResentDate: DateTime::now(),
+            ResentTo: ["a@example.com"],
+            ResentBcc: ["b@example.com", "c@example.com"],
+            ResentDate: DateTime::now(),
+            ResentTo: ["old1@example.com", "old2@example.com", "old3@example.com"],
+            ResentCc: ["old4@example.com"],
+            _To: ["d@example.com"]
+        }?;
+        assert_eq!(map.recipient_count(Resent)?, 3);
+        assert_eq!(map.recipient_count(Normal)?, 1);
+        assert_eq!(map.recipient_count(Both)?, 4);
+    });
+
+    test!(deduped_count_normalizes_addresses {
+        let map = headers! {
+            _To: ["a@example.com", "A@Example.COM"],
+            Cc: ["a+news@example.com", "b@xn--tda.example"],
+            Bcc: ["b@ü.example", "a@example.com"]
+        }?;
+        assert_eq!(map.recipient_count(Normal)?, 6);
+
+        let rules = AddressDedupRules::default();
+        assert_eq!(map.recipient_count_deduped(Normal, &rules)?, 3);
+
+        let rules = AddressDedupRules { subaddress_separator: Some('+'), ..Default::default() };
+        assert_eq!(map.recipient_count_deduped(Normal, &rules)?, 2);
+
+        let rules = AddressDedupRules { ignore_local_part_case: false, ..Default::default() };
+        assert_eq!(map.recipient_count_deduped(Normal, &rules)?, 4);
+    });
+
+    test!(mismatched_implementations_are_errors {
+        let map = headers! {
+            _To: ["a@example.com"],
+            OtherCc: "not a mailbox list"
+        }?;
+        assert_err!(map.recipient_count(Normal));
+        assert_err!(map.recipient_count_deduped(Both, &AddressDedupRules::default()));
+        assert_ok!(map.recipient_count(Resent));
+
+        match assert_err!(map.guard_recipient_limit(10, Normal)) {
+            RecipientLimitError::Type(_) => {},
+            other => panic!("unexpected error: {:?}", other)
+        }
+    });
+
+    test!(guard_triggers_above_the_limit {
+        let map = headers! {
+            _To: ["a@example.com", "b@example.com"],
+            Cc: ["c@example.com"]
+        }?;
+        assert_ok!(map.guard_recipient_limit(4, Normal));
+        assert_ok!(map.guard_recipient_limit(3, Normal));
+
+        let err = assert_err!(map.guard_recipient_limit(2, Normal));
+        match err {
+            RecipientLimitError::Exceeded { count, limit } => {
+                assert_eq!(count, 3);
+                assert_eq!(limit, 2);
+            },
+            ref other => panic!("unexpected error: {:?}", other)
+        }
+        let violation = err.limit_violation().unwrap();
+        assert_eq!(violation.kind(), LimitKind::Recipients);
+        assert_eq!(violation.limit(), 2);
+
+        assert_ok!(map.guard_recipient_limit(0, Resent));
+    });
+}
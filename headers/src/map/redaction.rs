@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use ::name::{HeaderName, HasHeaderName};
+use ::header::{Header, HeaderKind, HeaderObj, HeaderObjTrait};
+use ::header_components::{Mailbox, Unstructured};
+use ::headers::{
+    XRedacted,
+    _From, Sender, ReplyTo, _To, Cc, Bcc,
+    ResentFrom, ResentSender, ResentTo, ResentCc, ResentBcc
+};
+
+use super::{HeaderMap, ValidatorCarryPolicy};
+
+/// How a header listed in a `RedactionPolicy` is redacted.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum RedactionMode {
+    /// Replace the header with a marker only naming the header and the number of addresses.
+    Remove,
+    /// Like `Remove` but the marker additionally lists the domains of all addresses.
+    ///
+    /// Headers which are not known to contain addresses are treated like `Remove`.
+    DomainsOnly
+}
+
+/// Describes which headers `HeaderMap::redact_for_storage` redacts and how.
+///
+/// By default the redacted headers are replaced by a `X-Redacted` header,
+/// a different marker header can be set using `RedactionPolicy::with_marker`.
+#[derive(Clone)]
+pub struct RedactionPolicy {
+    redacted: HashMap<HeaderName, RedactionMode>,
+    create_marker: fn(Unstructured) -> Box<HeaderObj>,
+    validator_carry: ValidatorCarryPolicy
+}
+
+impl RedactionPolicy {
+
+    /// Creates a policy which doesn't redact any header.
+    pub fn new() -> Self {
+        RedactionPolicy {
+            redacted: HashMap::new(),
+            create_marker: create_marker::<XRedacted>,
+            validator_carry: ValidatorCarryPolicy::CarryAll
+        }
+    }
+
+    /// Uses `H` instead of `XRedacted` for the marker headers.
+    pub fn with_marker<H>(mut self) -> Self
+        where H: HeaderKind<Component=Unstructured>
+    {
+        self.create_marker = create_marker::<H>;
+        self
+    }
+
+    /// Redacts all headers with given name, see `RedactionMode::Remove`.
+    pub fn redact<N: HasHeaderName>(mut self, name: N) -> Self {
+        self.redacted.insert(name.get_name(), RedactionMode::Remove);
+        self
+    }
+
+    /// Redacts all headers with given name but keeps the domains, see `RedactionMode::DomainsOnly`.
+    pub fn keep_domains_only<N: HasHeaderName>(mut self, name: N) -> Self {
+        self.redacted.insert(name.get_name(), RedactionMode::DomainsOnly);
+        self
+    }
+
+    /// Sets which validators of the headers the redacted map runs.
+    ///
+    /// By default (`CarryAll`) the redacted map carries the validators like
+    /// the original map. A redacted map often misses headers the validators
+    /// require, e.g. a redacted `Sender` makes a `From` with multiple
+    /// mailboxes invalid.
+    pub fn carry_validators(mut self, carry: ValidatorCarryPolicy) -> Self {
+        self.validator_carry = carry;
+        self
+    }
+
+    /// Returns how headers with given name are redacted, or `None` if they are kept.
+    pub fn mode_for<N: HasHeaderName>(&self, name: N) -> Option<RedactionMode> {
+        self.redacted.get(&name.get_name()).cloned()
+    }
+}
+
+impl fmt::Debug for RedactionPolicy {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        // the marker constructor is a fn pointer, printing it's address is of no use
+        fter.debug_struct("RedactionPolicy")
+            .field("redacted", &self.redacted)
+            .field("validator_carry", &self.validator_carry)
+            .finish()
+    }
+}
+
+impl Default for RedactionPolicy {
+    /// Creates a policy redacting `Bcc` and `Resent-Bcc`.
+    fn default() -> Self {
+        RedactionPolicy::new()
+            .redact(Bcc)
+            .redact(ResentBcc)
+    }
+}
+
+fn create_marker<H>(text: Unstructured) -> Box<HeaderObj>
+    where H: HeaderKind<Component=Unstructured>
+{
+    Box::new(Header::<H>::new(text))
+}
+
+impl HeaderMap {
+
+    /// Creates a copy of this map with all headers listed in the policy redacted.
+    ///
+    /// Each redacted header is replaced (in place) by a marker header, which
+    /// names the redacted header and contains a short summary like e.g.
+    /// `X-Redacted: Bcc (2 recipients)`. All other headers are cloned as they are.
+    /// The validators of the headers are carried as set with
+    /// `RedactionPolicy::carry_validators` (by default all of them).
+    ///
+    /// This map is not modified.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    /// use mail_headers::map::RedactionPolicy;
+    ///
+    /// let map = headers! {
+    ///     _From: ["me@example.com"],
+    ///     Bcc: ["secret@example.com", "hidden@example.com"]
+    /// }.unwrap();
+    ///
+    /// let redacted = map.redact_for_storage(&RedactionPolicy::default());
+    /// assert!(!redacted.contains(Bcc));
+    ///
+    /// let marker = redacted.get(XRedacted).next().unwrap().unwrap();
+    /// assert_eq!(marker.as_str(), "Bcc (2 recipients)");
+    /// # }
+    /// ```
+    pub fn redact_for_storage(&self, policy: &RedactionPolicy) -> HeaderMap {
+        let mut redacted = HeaderMap::new();
+        for ((name, obj), &seq) in self.inner_map.iter().zip(self.seqs.iter()) {
+            let obj = match policy.mode_for(name) {
+                Some(mode) => {
+                    let summary = redaction_summary(name, obj, mode);
+                    (policy.create_marker)(Unstructured::from(summary))
+                },
+                None => obj.boxed_clone()
+            };
+            redacted.inner_map.add(obj.name(), obj);
+            redacted.seqs.push(seq);
+        }
+        redacted.max_seq = self.max_seq;
+        self.carry_validators_into(&mut redacted, &policy.validator_carry);
+        redacted
+    }
+}
+
+fn redaction_summary(name: HeaderName, obj: &HeaderObj, mode: RedactionMode) -> String {
+    let mailboxes = match known_mailboxes(obj) {
+        Some(mailboxes) => mailboxes,
+        None => return name.as_str().to_owned()
+    };
+
+    let count = mailboxes.len();
+    let plural = if count == 1 { "" } else { "s" };
+    let mut summary = format!("{} ({} recipient{}", name, count, plural);
+
+    if mode == RedactionMode::DomainsOnly && count > 0 {
+        let mut domains = Vec::new();
+        for mailbox in mailboxes {
+            let domain = mailbox.email.domain.as_str();
+            if !domains.contains(&domain) {
+                domains.push(domain);
+            }
+        }
+        summary.push_str("; domains: ");
+        summary.push_str(&domains.join(", "));
+    }
+
+    summary.push(')');
+    summary
+}
+
+/// Returns the mailboxes of all build-in address headers, or `None` for any other header.
+fn known_mailboxes(obj: &HeaderObj) -> Option<Vec<&Mailbox>> {
+    macro_rules! try_mailbox_lists {
+        ($($kind:ident),*) => ($(
+            if let Some(list) = obj.downcast_ref::<$kind>() {
+                return Some(list.iter().collect());
+            }
+        )*);
+    }
+    macro_rules! try_mailboxes {
+        ($($kind:ident),*) => ($(
+            if let Some(mailbox) = obj.downcast_ref::<$kind>() {
+                return Some(vec![mailbox.body()]);
+            }
+        )*);
+    }
+
+    try_mailbox_lists!(_From, ReplyTo, _To, Cc, Bcc, ResentFrom, ResentTo, ResentCc, ResentBcc);
+    try_mailboxes!(Sender, ResentSender);
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderKind;
+    use ::headers::{_From, _To, Sender, Bcc, Subject, XRedacted, Comments};
+    use super::*;
+
+    test!(bcc_becomes_marker_with_count {
+        let map = headers! {
+            _From: ["me@example.com"],
+            Bcc: ["a@example.com", "b@example.com"],
+            Subject: "hy"
+        }?;
+
+        let redacted = map.redact_for_storage(&RedactionPolicy::default());
+
+        assert_eq!(
+            redacted.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(),
+            vec!["From", "X-Redacted", "Subject"]
+        );
+
+        let marker = redacted.get(XRedacted).next().unwrap()?;
+        assert_eq!(marker.as_str(), "Bcc (2 recipients)");
+
+        // the source map is untouched
+        assert_eq!(map.get_single(Bcc).unwrap()?.len(), 2);
+    });
+
+    test!(to_domains_only {
+        let map = headers! {
+            _From: ["me@example.com"],
+            _To: ["a@example.com", "b@other.test", "c@example.com"]
+        }?;
+
+        let policy = RedactionPolicy::new().keep_domains_only(_To);
+        let redacted = map.redact_for_storage(&policy);
+
+        assert!(!redacted.contains(_To));
+        let marker = redacted.get(XRedacted).next().unwrap()?;
+        assert_eq!(marker.as_str(), "To (3 recipients; domains: example.com, other.test)");
+    });
+
+    test!(non_address_headers_only_keep_the_name {
+        let map = headers! {
+            Comments: "internal routing"
+        }?;
+
+        let policy = RedactionPolicy::new().keep_domains_only(Comments);
+        let redacted = map.redact_for_storage(&policy);
+
+        let marker = redacted.get(XRedacted).next().unwrap()?;
+        assert_eq!(marker.as_str(), "Comments");
+    });
+
+    test!(custom_marker {
+        let map = headers! {
+            Bcc: ["a@example.com"]
+        }?;
+
+        let policy = RedactionPolicy::default().with_marker::<Comments>();
+        let redacted = map.redact_for_storage(&policy);
+
+        let marker = redacted.get(Comments).next().unwrap()?;
+        assert_eq!(marker.as_str(), "Bcc (1 recipient)");
+        assert_eq!(redacted.get_untyped(XRedacted::name()).len(), 0);
+    });
+
+    test!(redacted_map_passes_validation {
+        let map = headers! {
+            _From: ["me@example.com", "you@example.com"],
+            Sender: "me@example.com",
+            Bcc: ["a@example.com", "b@example.com"]
+        }?;
+
+        let redacted = map.redact_for_storage(&RedactionPolicy::default());
+        assert_ok!(redacted.use_contextual_validators());
+    });
+}
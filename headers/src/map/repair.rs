@@ -0,0 +1,488 @@
+use std::{cmp, mem};
+
+use ::name::HeaderName;
+use ::header::{HeaderKind, HeaderObj};
+use ::header_components::{Mailbox, MailboxList};
+use ::headers::{Date, _From, Sender, ResentDate};
+
+use super::{HeaderMap, HeaderSeq};
+use super::seq::next_seq;
+use super::limits::total_bytes;
+
+/// Which fixes `HeaderMap::repair` applies.
+///
+/// The default enables all fixes but has no `From` placeholder.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RepairStrategy {
+    /// Remove all but the first `Date` header.
+    pub dedup_date: bool,
+
+    /// If there is no `From` header insert one with this mailbox.
+    pub from_placeholder: Option<Mailbox>,
+
+    /// If the `From` header has multiple mailboxes but there is no `Sender`
+    /// header, add a `Sender` header with the first mailbox of the `From` header.
+    pub promote_sender: bool,
+
+    /// Add a `Resent-Date` (with the value of the `Date` header) to resent
+    /// blocks without one, or drop the block if there is no `Date` header.
+    pub fix_resent_date: bool
+}
+
+impl RepairStrategy {
+
+    /// A strategy which does not fix anything, i.e. only reports problems.
+    pub fn none() -> Self {
+        RepairStrategy {
+            dedup_date: false,
+            from_placeholder: None,
+            promote_sender: false,
+            fix_resent_date: false
+        }
+    }
+}
+
+impl Default for RepairStrategy {
+    fn default() -> Self {
+        RepairStrategy {
+            dedup_date: true,
+            from_placeholder: None,
+            promote_sender: true,
+            fix_resent_date: true
+        }
+    }
+}
+
+/// A fix applied by `HeaderMap::repair`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RepairAction {
+    /// All but the first `Date` header were removed.
+    RemovedDuplicateDates { removed: usize },
+    /// The `From` placeholder was inserted.
+    InsertedPlaceholderFrom { placeholder: Mailbox },
+    /// A `Sender` header with the first mailbox of the `From` header was added.
+    PromotedSender { sender: Mailbox },
+    /// A `Resent-Date` was added to the resent block with given index.
+    SynthesizedResentDate { block: usize },
+    /// The resent block with given index was removed.
+    DroppedResentBlock { block: usize, removed: usize }
+}
+
+/// A problem `HeaderMap::repair` found but did not fix.
+///
+/// This is either because the fix is disabled in the `RepairStrategy`
+/// or because there is no sensible way to fix it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RepairProblem {
+    /// There are multiple `Date` headers.
+    DuplicateDates { count: usize },
+    /// There is no `From` header (and no placeholder).
+    MissingFrom,
+    /// The `From` header has multiple mailboxes but there is no `Sender` header.
+    MultiMailboxFromWithoutSender,
+    /// The resent block with given index has no `Resent-Date`.
+    ResentBlockWithoutDate { block: usize },
+    /// `use_contextual_validators` still fails after the repair.
+    ///
+    /// This happens e.g. if headers have unexpected types or for problems
+    /// not covered by the repair (like a `Resent-From` with multiple mailboxes
+    /// but without `Resent-Sender`).
+    StillInvalid { reason: String }
+}
+
+/// Describes what `HeaderMap::repair` did and what it could not fix.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct RepairReport {
+    /// The applied fixes in the order they were applied.
+    pub actions: Vec<RepairAction>,
+    /// The remaining problems.
+    pub problems: Vec<RepairProblem>
+}
+
+impl RepairReport {
+
+    /// Returns true if there are no remaining problems.
+    pub fn is_repaired(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+type Entry = (HeaderName, Box<HeaderObj>, HeaderSeq);
+
+impl HeaderMap {
+
+    /// Tries to fix common problems of maps created from (slightly) broken input.
+    ///
+    /// The fixes are applied in following order, each can be disabled
+    /// through the `strategy`:
+    ///
+    /// 1. If there are multiple `Date` headers the first one is kept.
+    /// 2. If there is no `From` header the `from_placeholder` is inserted (if set).
+    /// 3. If the `From` header has multiple mailboxes and there is no `Sender`
+    ///    header the first mailbox is added as `Sender`.
+    /// 4. Resent blocks without `Resent-Date` get a `Resent-Date` with the value
+    ///    of the `Date` header (placed at the start of the block) or are removed
+    ///    if there is no `Date` header.
+    ///
+    /// Added headers are placed at the end of the map (except the `Resent-Date`),
+    /// other headers keep their relative order and sequence numbers. Headers of
+    /// types other then the ones defined in `headers` are not looked into. The
+    /// repair does not check the limits of the map (see `with_limits`).
+    ///
+    /// The returned report lists all applied fixes and all problems which
+    /// remain, including a failing `use_contextual_validators`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    /// use mail_headers::map::{RepairStrategy, RepairAction};
+    ///
+    /// let mut map = headers! {
+    ///     _From: ["a@example.com", "b@example.com"]
+    /// }.unwrap();
+    /// assert!(map.use_contextual_validators().is_err());
+    ///
+    /// let report = map.repair(&RepairStrategy::default());
+    /// assert!(report.is_repaired());
+    /// assert!(map.contains(Sender));
+    /// assert!(map.use_contextual_validators().is_ok());
+    /// # }
+    /// ```
+    pub fn repair(&mut self, strategy: &RepairStrategy) -> RepairReport {
+        let mut report = RepairReport::default();
+        let mut entries = self.take_entries();
+
+        repair_dates(&mut entries, strategy, &mut report);
+        repair_from(&mut entries, strategy, &mut report);
+        repair_sender(&mut entries, strategy, &mut report);
+        repair_resent_blocks(&mut entries, strategy, &mut report);
+
+        self.put_entries(entries);
+
+        if let Err(err) = self.use_contextual_validators() {
+            report.problems.push(RepairProblem::StillInvalid { reason: err.to_string() });
+        }
+        report
+    }
+
+    fn take_entries(&mut self) -> Vec<Entry> {
+        let inner_map = mem::replace(&mut self.inner_map, Default::default());
+        let seqs = mem::replace(&mut self.seqs, Vec::new());
+        inner_map.into_iter()
+            .zip(seqs)
+            .map(|((name, obj), seq)| (name, obj, seq))
+            .collect()
+    }
+
+    fn put_entries(&mut self, entries: Vec<Entry>) {
+        for (name, obj, seq) in entries {
+            self.inner_map.add(name, obj);
+            self.seqs.push(seq);
+            self.max_seq = cmp::max(self.max_seq, seq);
+        }
+        if self.limits.max_bytes.is_some() {
+            self.used_bytes = total_bytes(self);
+        }
+        // the repairs can drop headers
+        self.prune_labels();
+    }
+}
+
+fn positions_of(entries: &[Entry], name: HeaderName) -> Vec<usize> {
+    entries.iter()
+        .enumerate()
+        .filter(|&(_, entry)| entry.0 == name)
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+fn new_entry<H: HeaderKind>(body: H::Component) -> Entry {
+    let obj: Box<HeaderObj> = Box::new(H::body(body));
+    (H::name(), obj, next_seq())
+}
+
+fn repair_dates(entries: &mut Vec<Entry>, strategy: &RepairStrategy, report: &mut RepairReport) {
+    let positions = positions_of(entries, Date::name());
+    if positions.len() < 2 {
+        return;
+    }
+    if !strategy.dedup_date {
+        report.problems.push(RepairProblem::DuplicateDates { count: positions.len() });
+        return;
+    }
+    for &idx in positions[1..].iter().rev() {
+        entries.remove(idx);
+    }
+    report.actions.push(RepairAction::RemovedDuplicateDates { removed: positions.len() - 1 });
+}
+
+fn repair_from(entries: &mut Vec<Entry>, strategy: &RepairStrategy, report: &mut RepairReport) {
+    if !positions_of(entries, _From::name()).is_empty() {
+        return;
+    }
+    match strategy.from_placeholder {
+        Some(ref placeholder) => {
+            entries.push(new_entry::<_From>(MailboxList::from_single(placeholder.clone())));
+            report.actions.push(RepairAction::InsertedPlaceholderFrom {
+                placeholder: placeholder.clone()
+            });
+        },
+        None => report.problems.push(RepairProblem::MissingFrom)
+    }
+}
+
+fn repair_sender(entries: &mut Vec<Entry>, strategy: &RepairStrategy, report: &mut RepairReport) {
+    if !positions_of(entries, Sender::name()).is_empty() {
+        return;
+    }
+    let first_of_multi = entries.iter()
+        .filter_map(|entry| entry.1.downcast_ref::<_From>())
+        .find(|from| from.body().len() > 1)
+        .and_then(|from| from.body().iter().next().cloned());
+
+    let sender = match first_of_multi {
+        Some(sender) => sender,
+        None => return
+    };
+    if strategy.promote_sender {
+        entries.push(new_entry::<Sender>(sender.clone()));
+        report.actions.push(RepairAction::PromotedSender { sender });
+    } else {
+        report.problems.push(RepairProblem::MultiMailboxFromWithoutSender);
+    }
+}
+
+/// Returns the positions of the headers of each resent block.
+///
+/// The blocks are detected the same way as done by the resent validator,
+/// i.e. a new block starts if a header name repeats.
+fn resent_blocks(entries: &[Entry]) -> Vec<Vec<usize>> {
+    let mut blocks: Vec<Vec<usize>> = Vec::new();
+    let mut current: Vec<usize> = Vec::new();
+    for (idx, entry) in entries.iter().enumerate() {
+        if !entry.0.as_str().starts_with("Resent-") {
+            continue;
+        }
+        if current.iter().any(|&other| entries[other].0 == entry.0) {
+            blocks.push(mem::replace(&mut current, Vec::new()));
+        }
+        current.push(idx);
+    }
+    if !current.is_empty() {
+        blocks.push(current);
+    }
+    blocks
+}
+
+fn repair_resent_blocks(entries: &mut Vec<Entry>, strategy: &RepairStrategy, report: &mut RepairReport) {
+    let resent_date = ResentDate::name();
+    let broken = resent_blocks(entries).into_iter()
+        .enumerate()
+        .filter(|&(_, ref block)| !block.iter().any(|&idx| entries[idx].0 == resent_date))
+        .collect::<Vec<_>>();
+
+    if !strategy.fix_resent_date {
+        for (block, _) in broken {
+            report.problems.push(RepairProblem::ResentBlockWithoutDate { block });
+        }
+        return;
+    }
+
+    let date = entries.iter()
+        .filter_map(|entry| entry.1.downcast_ref::<Date>())
+        .map(|date| date.body().clone())
+        .next();
+
+    // go backwards so that the positions of the blocks before stay valid
+    let mut actions = Vec::new();
+    for (block, positions) in broken.into_iter().rev() {
+        match date {
+            Some(ref date) => {
+                entries.insert(positions[0], new_entry::<ResentDate>(date.clone()));
+                actions.push(RepairAction::SynthesizedResentDate { block });
+            },
+            None => {
+                for &idx in positions.iter().rev() {
+                    entries.remove(idx);
+                }
+                actions.push(RepairAction::DroppedResentBlock { block, removed: positions.len() });
+            }
+        }
+    }
+    actions.reverse();
+    report.actions.extend(actions);
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderTryFrom;
+    use ::header_components::{self as components, DateTime};
+    use ::headers::{Subject, ResentFrom, ResentTo};
+    use super::*;
+
+    def_headers! {
+        test_name: validate_header_names,
+        scope: components,
+        // what a lenient parser would produce for duplicate `Date` fields
+        LenientDate, unchecked { "Date" }, DateTime, multi, None
+    }
+
+    fn date(secs: i64) -> DateTime {
+        use chrono::{TimeZone, Utc};
+        DateTime::new(Utc.timestamp(secs, 0))
+    }
+
+    fn mailbox(email: &str) -> Mailbox {
+        Mailbox::try_from(email).unwrap()
+    }
+
+    fn assert_minimal_rfc5322(map: &HeaderMap) {
+        assert_ok!(map.use_contextual_validators());
+        assert_eq!(map.get_untyped(Date).len(), 1);
+        assert!(map.contains(_From));
+    }
+
+    fn names(map: &HeaderMap) -> Vec<&'static str> {
+        map.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    test!(removes_duplicate_dates {
+        let mut map = headers! {
+            LenientDate: date(1000),
+            _From: ["me@example.com"],
+            LenientDate: date(2000)
+        }?;
+
+        let report = map.repair(&RepairStrategy::default());
+        assert_eq!(report.actions, vec![RepairAction::RemovedDuplicateDates { removed: 1 }]);
+        assert!(report.is_repaired());
+        assert_eq!(names(&map), vec!["Date", "From"]);
+        let kept = map.get(LenientDate).next().unwrap()?;
+        assert_eq!(kept.body(), &date(1000));
+    });
+
+    test!(promotes_sender {
+        let mut map = headers! {
+            Date: date(1000),
+            _From: ["a@example.com", "b@example.com"]
+        }?;
+
+        let report = map.repair(&RepairStrategy::default());
+        assert_eq!(report.actions, vec![
+            RepairAction::PromotedSender { sender: mailbox("a@example.com") }
+        ]);
+        assert_eq!(map.get_single(Sender).unwrap()?.body(), &mailbox("a@example.com"));
+        assert_minimal_rfc5322(&map);
+    });
+
+    test!(inserts_from_placeholder {
+        let mut map = headers! {
+            Date: date(1000),
+            Subject: "hy there"
+        }?;
+        let strategy = RepairStrategy {
+            from_placeholder: Some(mailbox("unknown@example.com")),
+            ..Default::default()
+        };
+
+        let report = map.repair(&strategy);
+        assert_eq!(report.actions, vec![
+            RepairAction::InsertedPlaceholderFrom { placeholder: mailbox("unknown@example.com") }
+        ]);
+        assert_eq!(names(&map), vec!["Date", "Subject", "From"]);
+        assert_minimal_rfc5322(&map);
+    });
+
+    test!(refuses_to_guess_missing_from {
+        let mut map = headers! {
+            Date: date(1000),
+            Subject: "hy there"
+        }?;
+
+        let report = map.repair(&RepairStrategy::default());
+        assert!(report.actions.is_empty());
+        assert_eq!(report.problems, vec![RepairProblem::MissingFrom]);
+        assert!(!report.is_repaired());
+        assert!(!map.contains(_From));
+    });
+
+    test!(synthesizes_resent_date {
+        let mut map = headers! {
+            ResentFrom: ["relay@example.com"],
+            ResentTo: ["you@example.com"],
+            Date: date(1000),
+            _From: ["me@example.com"]
+        }?;
+
+        let report = map.repair(&RepairStrategy::default());
+        assert_eq!(report.actions, vec![RepairAction::SynthesizedResentDate { block: 0 }]);
+        assert_eq!(names(&map), vec!["Resent-Date", "Resent-From", "Resent-To", "Date", "From"]);
+        assert_eq!(map.get(ResentDate).next().unwrap()?.body(), &date(1000));
+        assert_minimal_rfc5322(&map);
+    });
+
+    test!(drops_resent_block_without_date {
+        let mut map = headers! {
+            ResentDate: date(2000),
+            ResentFrom: ["relay@example.com"],
+            ResentFrom: ["other-relay@example.com"],
+            ResentTo: ["you@example.com"],
+            _From: ["me@example.com"]
+        }?;
+
+        let report = map.repair(&RepairStrategy::default());
+        assert_eq!(report.actions, vec![RepairAction::DroppedResentBlock { block: 1, removed: 2 }]);
+        assert_eq!(names(&map), vec!["Resent-Date", "Resent-From", "From"]);
+        assert_ok!(map.use_contextual_validators());
+    });
+
+    test!(disabled_fixes_are_reported {
+        let mut map = headers! {
+            LenientDate: date(1000),
+            LenientDate: date(2000),
+            _From: ["a@example.com", "b@example.com"],
+            ResentTo: ["you@example.com"]
+        }?;
+        let before = names(&map);
+
+        let report = map.repair(&RepairStrategy::none());
+        assert!(report.actions.is_empty());
+        assert_eq!(&report.problems[..3], &[
+            RepairProblem::DuplicateDates { count: 2 },
+            RepairProblem::MultiMailboxFromWithoutSender,
+            RepairProblem::ResentBlockWithoutDate { block: 0 }
+        ]);
+        match report.problems[3] {
+            RepairProblem::StillInvalid { .. } => {},
+            ref other => panic!("unexpected problem: {:?}", other)
+        }
+        assert_eq!(names(&map), before);
+        assert!(!report.is_repaired());
+    });
+
+    test!(repairs_messy_map {
+        let mut map = headers! {
+            ResentTo: ["you@example.com"],
+            Date: date(1000),
+            Subject: "hy there",
+            _From: ["a@example.com", "b@example.com"],
+            LenientDate: date(2000),
+            ResentTo: ["someone@example.com"],
+            ResentDate: date(1500)
+        }?;
+        assert_err!(map.use_contextual_validators());
+
+        let report = map.repair(&RepairStrategy::default());
+        assert_eq!(report.actions, vec![
+            RepairAction::RemovedDuplicateDates { removed: 1 },
+            RepairAction::PromotedSender { sender: mailbox("a@example.com") },
+            RepairAction::SynthesizedResentDate { block: 0 }
+        ]);
+        assert!(report.is_repaired(), "{:?}", report.problems);
+        assert_minimal_rfc5322(&map);
+    });
+}
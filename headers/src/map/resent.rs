@@ -0,0 +1,248 @@
+use ::name::HeaderName;
+use ::header::{Header, HeaderKind, HeaderObj};
+use ::headers::{
+    ResentDate, ResentFrom, ResentSender, ResentTo,
+    ResentCc, ResentBcc, ResentMsgId
+};
+use ::error::HeaderTypeError;
+
+use super::{HeaderMap, Iter};
+
+impl HeaderMap {
+
+    /// Iterates over the resent blocks of the map (in map order).
+    ///
+    /// Each time a mail is resent a block of `Resent-*` headers is
+    /// added to it (rfc5322 section 3.6.6). A block consists of
+    /// consecutive (ignoring non `Resent-*` headers) resent headers, a
+    /// resent header whose name already appeared in the current block
+    /// starts a new block. This is the same splitting used by the
+    /// validator of the resent headers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    /// use mail_headers::header_components::DateTime;
+    ///
+    /// let map = headers! {
+    ///     ResentDate: DateTime::now(),
+    ///     ResentFrom: ["first@example.com"],
+    ///     ResentDate: DateTime::now(),
+    ///     ResentFrom: ["second@example.com"],
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// assert_eq!(map.resent_blocks().count(), 2);
+    /// let last = map.resent_blocks().last().unwrap();
+    /// let from = last.resent_from().unwrap().unwrap();
+    /// assert_eq!(from[0].email.to_string(), "second@example.com");
+    /// # }
+    /// ```
+    pub fn resent_blocks(&self) -> ResentBlocks {
+        ResentBlocks {
+            inner: self.iter(),
+            next_header: None
+        }
+    }
+}
+
+/// Iterator returned by `HeaderMap::resent_blocks`.
+#[derive(Debug)]
+pub struct ResentBlocks<'a> {
+    inner: Iter<'a>,
+    /// The header starting the next block.
+    next_header: Option<(HeaderName, &'a HeaderObj)>
+}
+
+impl<'a> Iterator for ResentBlocks<'a> {
+    type Item = ResentBlock<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut headers = Vec::new();
+        headers.extend(self.next_header.take());
+        while let Some((name, obj)) = self.inner.next() {
+            if !name.as_str().starts_with("Resent-") {
+                continue;
+            }
+            if headers.iter().any(|&(other, _)| other == name) {
+                self.next_header = Some((name, obj));
+                break;
+            }
+            headers.push((name, obj));
+        }
+
+        if headers.is_empty() {
+            None
+        } else {
+            Some(ResentBlock { headers })
+        }
+    }
+}
+
+/// The headers of one resent block, see `HeaderMap::resent_blocks`.
+///
+/// A block contains each header name at most once, the typed accessors
+/// return `None` if the block has no such header and a `HeaderTypeError`
+/// if the header has a different type.
+#[derive(Debug, Clone)]
+pub struct ResentBlock<'a> {
+    headers: Vec<(HeaderName, &'a HeaderObj)>
+}
+
+impl<'a> ResentBlock<'a> {
+
+    /// The number of headers in the block.
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    /// Returns true if the block contains a header with the name.
+    pub fn contains(&self, name: HeaderName) -> bool {
+        self.headers.iter().any(|&(other, _)| other == name)
+    }
+
+    /// Iterates over the headers of the block in map order.
+    pub fn iter<'b>(&'b self) -> impl Iterator<Item=(HeaderName, &'a HeaderObj)> + 'b {
+        self.headers.iter().cloned()
+    }
+
+    /// Returns the header associated with the given header kind.
+    #[inline]
+    pub fn get<H>(&self, _type_hint: H) -> Option<Result<&'a Header<H>, HeaderTypeError>>
+        where H: HeaderKind
+    {
+        self._get::<H>()
+    }
+
+    /// A variation of `get` which doesn't require passing in a type hint.
+    pub fn _get<H>(&self) -> Option<Result<&'a Header<H>, HeaderTypeError>>
+        where H: HeaderKind
+    {
+        self.headers.iter()
+            .find(|&&(name, _)| name == H::name())
+            .map(|&(_, obj)| obj.try_downcast_ref::<H>())
+    }
+
+    pub fn resent_date(&self) -> Option<Result<&'a Header<ResentDate>, HeaderTypeError>> {
+        self._get::<ResentDate>()
+    }
+
+    pub fn resent_from(&self) -> Option<Result<&'a Header<ResentFrom>, HeaderTypeError>> {
+        self._get::<ResentFrom>()
+    }
+
+    pub fn resent_sender(&self) -> Option<Result<&'a Header<ResentSender>, HeaderTypeError>> {
+        self._get::<ResentSender>()
+    }
+
+    pub fn resent_to(&self) -> Option<Result<&'a Header<ResentTo>, HeaderTypeError>> {
+        self._get::<ResentTo>()
+    }
+
+    pub fn resent_cc(&self) -> Option<Result<&'a Header<ResentCc>, HeaderTypeError>> {
+        self._get::<ResentCc>()
+    }
+
+    pub fn resent_bcc(&self) -> Option<Result<&'a Header<ResentBcc>, HeaderTypeError>> {
+        self._get::<ResentBcc>()
+    }
+
+    pub fn resent_msg_id(&self) -> Option<Result<&'a Header<ResentMsgId>, HeaderTypeError>> {
+        self._get::<ResentMsgId>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderKind;
+    use ::headers::*;
+    use ::header_components::DateTime;
+    use super::*;
+
+    mod other_headers {
+        use ::header_components;
+        def_headers! {
+            test_name: validate_header_names,
+            scope: header_components,
+            ResentDate, unchecked { "Resent-Date" }, Unstructured, multi, None
+        }
+    }
+
+    fn names(block: &ResentBlock) -> Vec<&'static str> {
+        block.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    test!(no_resent_headers_no_blocks {
+        let map = headers! {
+            Subject: "hy there"
+        }?;
+        assert_eq!(map.resent_blocks().count(), 0);
+        assert_eq!(HeaderMap::new().resent_blocks().count(), 0);
+    });
+
+    test!(repeated_name_starts_new_block {
+        let map = headers! {
+            ResentDate: DateTime::now(),
+            ResentFrom: ["a@b.c"],
+            Subject: "hy there",
+            ResentTo: ["e@f.d"],
+            ResentFrom: ["ee@ee.e"],
+            ResentSender: "ee@ee.e",
+            ResentDate: DateTime::now(),
+            ResentMsgId: "a@b.c",
+            ResentDate: DateTime::now()
+        }?;
+
+        let blocks = map.resent_blocks().collect::<Vec<_>>();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(names(&blocks[0]), vec!["Resent-Date", "Resent-From", "Resent-To"]);
+        assert_eq!(names(&blocks[1]), vec![
+            "Resent-From", "Resent-Sender", "Resent-Date", "Resent-Msg-Id"
+        ]);
+        assert_eq!(names(&blocks[2]), vec!["Resent-Date"]);
+        assert_eq!(blocks[2].len(), 1);
+    });
+
+    test!(typed_accessors {
+        let map = headers! {
+            ResentDate: DateTime::now(),
+            ResentFrom: ["a@b.c", "d@e.f"],
+            ResentSender: "a@b.c",
+            ResentTo: ["e@f.d"],
+            ResentCc: ["cc@f.d"],
+            ResentBcc: ["bcc@f.d"],
+            ResentMsgId: "id@b.c"
+        }?;
+
+        let block = map.resent_blocks().next().unwrap();
+        assert!(block.resent_date().unwrap().is_ok());
+        assert_eq!(block.resent_from().unwrap()?.len(), 2);
+        assert_eq!(block.resent_sender().unwrap()?.email.to_string(), "a@b.c");
+        assert_eq!(block.resent_to().unwrap()?.len(), 1);
+        assert_eq!(block.resent_cc().unwrap()?.len(), 1);
+        assert_eq!(block.resent_bcc().unwrap()?.0.len(), 1);
+        assert_eq!(block.resent_msg_id().unwrap()?.as_str(), "id@b.c");
+        assert!(block.get(ResentTo).is_some());
+        assert!(block.contains(ResentCc::name()));
+
+        let map = headers! {
+            ResentDate: DateTime::now()
+        }?;
+        let block = map.resent_blocks().next().unwrap();
+        assert!(block.resent_from().is_none());
+        assert!(!block.contains(ResentFrom::name()));
+    });
+
+    test!(other_type_is_a_type_error {
+        let map = headers! {
+            other_headers::ResentDate: "yesterday"
+        }?;
+        let block = map.resent_blocks().next().unwrap();
+        assert_err!(block.resent_date().unwrap());
+        assert!(block._get::<other_headers::ResentDate>().unwrap().is_ok());
+    });
+}
@@ -0,0 +1,349 @@
+use std::mem;
+
+use internals::MailType;
+use internals::error::EncodingError;
+
+use ::name::HeaderName;
+use ::header::{HeaderObj, HeaderObjTrait};
+
+use super::{HeaderMap, ValidatorCarryPolicy};
+use super::limits::total_bytes;
+
+impl HeaderMap {
+
+    /// Removes all headers for which the predicate returns false.
+    ///
+    /// The predicate is called once for each header, in the order of the
+    /// map, and the retained headers keep their order (and sequence numbers).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    ///
+    /// let mut map = headers! {
+    ///     _From: ["me@example.com"],
+    ///     Comments: "internal",
+    ///     Subject: "hy there",
+    ///     Comments: "also internal"
+    /// }.unwrap();
+    ///
+    /// map.retain(|name, _| name.as_str() != "Comments");
+    /// let names = map.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+    /// assert_eq!(names, vec!["From", "Subject"]);
+    /// # }
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F)
+        where F: FnMut(HeaderName, &HeaderObj) -> bool
+    {
+        self.retain_mut(|name, obj| f(name, &*obj))
+    }
+
+    /// Like `retain` but the predicate can modify the headers it retains.
+    ///
+    /// Modifying a header (e.g. through `HeaderObj::downcast_mut`) clears
+    /// its cached encoded value as usual.
+    pub fn retain_mut<F>(&mut self, mut f: F)
+        where F: FnMut(HeaderName, &mut HeaderObj) -> bool
+    {
+        let inner_map = mem::replace(&mut self.inner_map, Default::default());
+        let seqs = mem::replace(&mut self.seqs, Vec::new());
+        for ((name, mut obj), seq) in inner_map.into_iter().zip(seqs) {
+            if f(name, &mut *obj) {
+                self.inner_map.add(name, obj);
+                self.seqs.push(seq);
+            } else {
+                self.labels.remove(&seq);
+            }
+        }
+        if self.limits.max_bytes.is_some() {
+            self.used_bytes = total_bytes(self);
+        }
+    }
+
+    /// Removes all headers for which the predicate returns true for their encoded value.
+    ///
+    /// The predicate is called once for each header, in the order of the
+    /// map, with the name and the encoded value of the header (as returned
+    /// by `HeaderObj::encoded_value`), i.e. the value exactly as it would be
+    /// written into the mail including quoting, encoded words and the CRLF
+    /// added by folding. This makes it possible to remove headers
+    /// independent of their type, e.g. to scrub secrets before logging.
+    ///
+    /// Returns the number of removed headers.
+    ///
+    /// # Error
+    ///
+    /// If any header can not be encoded with the given mail type the error
+    /// is returned and the map is not changed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # extern crate mail_internals;
+    /// # fn main() {
+    /// use mail_internals::MailType;
+    /// use mail_headers::headers::*;
+    ///
+    /// let mut map = headers! {
+    ///     _From: ["me@example.com"],
+    ///     Comments: "token=s3cr3t",
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// let removed = map.remove_if_encoded(MailType::Ascii, |_, value| value.contains("token="));
+    /// assert_eq!(removed.unwrap(), 1);
+    /// assert!(!map.contains(Comments));
+    /// # }
+    /// ```
+    pub fn remove_if_encoded<F>(&mut self, mail_type: MailType, mut pred: F) -> Result<usize, EncodingError>
+        where F: FnMut(HeaderName, &str) -> bool
+    {
+        let mut remove = Vec::with_capacity(self.len());
+        for (name, obj) in self.iter() {
+            let value = obj.encoded_value(mail_type)?;
+            remove.push(pred(name, &String::from_utf8_lossy(&value)));
+        }
+
+        let removed = remove.iter().filter(|&&flag| flag).count();
+        let mut remove = remove.into_iter();
+        self.retain(|_, _| !remove.next().expect("[BUG] header count changed while retaining"));
+        Ok(removed)
+    }
+
+    /// Moves all headers for which the predicate returns true into a new map.
+    ///
+    /// This is the moving counterpart of `retain`: the predicate is called
+    /// once for each header, in the order of the map, the matching headers
+    /// are returned in a new map and all other headers stay in this map.
+    /// Both maps keep the relative order of their headers and the headers
+    /// keep their sequence numbers (and labels). The returned map has no
+    /// limits and no validators added to it, it carries the validators of
+    /// its headers like this map (see `split_off_carrying`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    ///
+    /// let mut map = headers! {
+    ///     Received: "mx1.example.com",
+    ///     Subject: "hy there",
+    ///     ReturnPath: "bounces@example.com"
+    /// }.unwrap();
+    ///
+    /// let trace = map.split_off(|name, _| {
+    ///     name.as_str() == "Received" || name.as_str() == "Return-Path"
+    /// });
+    /// assert_eq!(trace.len(), 2);
+    /// assert_eq!(map.len(), 1);
+    /// # }
+    /// ```
+    pub fn split_off<F>(&mut self, f: F) -> HeaderMap
+        where F: FnMut(HeaderName, &HeaderObj) -> bool
+    {
+        self.split_off_carrying(f, ValidatorCarryPolicy::CarryAll)
+    }
+
+    /// Like `split_off` but the validators of the returned map's headers are run as given by `carry`.
+    pub fn split_off_carrying<F>(&mut self, mut f: F, carry: ValidatorCarryPolicy) -> HeaderMap
+        where F: FnMut(HeaderName, &HeaderObj) -> bool
+    {
+        let mut split = HeaderMap::new();
+        let inner_map = mem::replace(&mut self.inner_map, Default::default());
+        let seqs = mem::replace(&mut self.seqs, Vec::new());
+        for ((name, obj), seq) in inner_map.into_iter().zip(seqs) {
+            if f(name, &*obj) {
+                split._insert_with_seq(name, false, obj, seq);
+                if let Some(label) = self.labels.remove(&seq) {
+                    split.labels.insert(seq, label);
+                }
+            } else {
+                self.inner_map.add(name, obj);
+                self.seqs.push(seq);
+            }
+        }
+        if self.limits.max_bytes.is_some() {
+            self.used_bytes = total_bytes(self);
+        }
+        self.carry_validators_into(&mut split, &carry);
+        split
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderKind;
+    use ::headers::*;
+    use super::*;
+
+    fn names(map: &HeaderMap) -> Vec<&'static str> {
+        map.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    fn comments(map: &HeaderMap) -> Vec<String> {
+        map.get(Comments)
+            .map(|comment| comment.unwrap().as_str().to_owned())
+            .collect()
+    }
+
+    test!(removes_interleaved_multi_headers {
+        let mut map = headers! {
+            Comments: "a",
+            _From: ["me@example.com"],
+            Comments: "b",
+            Subject: "hy there",
+            Comments: "c",
+            _To: ["you@example.com"]
+        }?;
+
+        map.retain(|name, _| name != Comments::name());
+        assert_eq!(names(&map), vec!["From", "Subject", "To"]);
+    });
+
+    test!(keeps_order_of_retained_multi_headers {
+        let mut map = headers! {
+            Comments: "keep 1",
+            _From: ["me@example.com"],
+            Comments: "drop",
+            Subject: "hy there",
+            Comments: "keep 2"
+        }?;
+
+        map.retain(|name, obj| {
+            name != Comments::name()
+                || obj.downcast_ref::<Comments>().unwrap().as_str() != "drop"
+        });
+        assert_eq!(names(&map), vec!["Comments", "From", "Subject", "Comments"]);
+        assert_eq!(comments(&map), vec!["keep 1", "keep 2"]);
+    });
+
+    test!(retained_headers_keep_their_seqs {
+        let mut map = headers! {
+            Subject: "hy there",
+            Comments: "drop",
+            _From: ["me@example.com"]
+        }?;
+        let subject_seq = map.seq_of(0).unwrap();
+        let from_seq = map.seq_of(2).unwrap();
+        let max_seq = map.max_seq();
+
+        map.retain(|name, _| name != Comments::name());
+        assert_eq!(map.seq_of(0), Some(subject_seq));
+        assert_eq!(map.seq_of(1), Some(from_seq));
+        assert_eq!(map.max_seq(), max_seq);
+    });
+
+    test!(retain_mut_can_modify_bodies {
+        let mut map = headers! {
+            Comments: "a",
+            Subject: "hy there",
+            Comments: "b"
+        }?;
+
+        map.retain_mut(|name, obj| {
+            if name == Comments::name() {
+                let comment = obj.downcast_mut::<Comments>().unwrap();
+                let text = format!("{}!", comment.as_str());
+                *comment.body_mut() = text.into();
+            }
+            true
+        });
+        assert_eq!(names(&map), vec!["Comments", "Subject", "Comments"]);
+        assert_eq!(comments(&map), vec!["a!", "b!"]);
+    });
+
+    test!(remove_if_encoded_sees_encoded_values {
+        let mut map = headers! {
+            _From: [("Jörn", "me@example.com")],
+            Comments: "token=1234",
+            Subject: "hy there",
+            Comments: "no secret here"
+        }?;
+
+        let mut seen = Vec::new();
+        let removed = map.remove_if_encoded(MailType::Ascii, |name, value| {
+            seen.push((name.as_str(), value.to_owned()));
+            value.contains("token=")
+        })?;
+
+        assert_eq!(removed, 1);
+        assert_eq!(seen.len(), 4);
+        // the display name is written as encoded word in an ascii mail
+        assert_eq!(seen[0].0, "From");
+        assert!(seen[0].1.starts_with("=?utf8?"), "{:?}", seen[0]);
+        assert!(seen[0].1.ends_with("<me@example.com>"), "{:?}", seen[0]);
+        assert_eq!(seen[1..].to_vec(), vec![
+            ("Comments", "token=1234".to_owned()),
+            ("Subject", "hy there".to_owned()),
+            ("Comments", "no secret here".to_owned())
+        ]);
+        assert_eq!(names(&map), vec!["From", "Subject", "Comments"]);
+        assert_eq!(comments(&map), vec!["no secret here"]);
+    });
+
+    test!(remove_if_encoded_sees_folded_values {
+        let mut map = headers! {
+            Subject: "a subject which is long enough to be folded when it is encoded into a mail",
+            Comments: "short"
+        }?;
+
+        let removed = map.remove_if_encoded(MailType::Ascii, |_, value| value.contains("\r\n "))?;
+        assert_eq!(removed, 1);
+        assert_eq!(names(&map), vec!["Comments"]);
+    });
+
+    test!(remove_if_encoded_reports_encoding_errors {
+        let mut map = headers! {
+            Comments: "token=1234",
+            _From: ["jörn@example.com"]
+        }?;
+
+        assert_err!(map.remove_if_encoded(MailType::Ascii, |_, _| true));
+        assert_eq!(names(&map), vec!["Comments", "From"]);
+
+        let removed = map.remove_if_encoded(MailType::Internationalized, |_, _| true)?;
+        assert_eq!(removed, 2);
+        assert!(map.is_empty());
+    });
+
+    test!(split_off_keeps_order_of_both_halves {
+        let mut map = headers! {
+            Received: "mx1.example.com",
+            Subject: "hy there",
+            Received: "mx2.example.com",
+            Comments: "a comment"
+        }?;
+        let seqs = map.iter_with_seq().map(|(seq, _, _)| seq).collect::<Vec<_>>();
+
+        let trace = map.split_off(|name, _| name == Received::name());
+
+        assert_eq!(names(&trace), vec!["Received", "Received"]);
+        assert_eq!(trace.seq_of(0), Some(seqs[0]));
+        assert_eq!(trace.seq_of(1), Some(seqs[2]));
+        assert_eq!(trace.max_seq(), seqs[2]);
+
+        assert_eq!(names(&map), vec!["Subject", "Comments"]);
+        assert_eq!(map.seq_of(0), Some(seqs[1]));
+        assert_eq!(map.seq_of(1), Some(seqs[3]));
+    });
+
+    test!(split_off_without_match_returns_empty_map {
+        let mut map = headers! {
+            Subject: "hy there",
+            Comments: "a comment"
+        }?;
+
+        let split = map.split_off(|name, _| name == Received::name());
+        assert!(split.is_empty());
+        assert_eq!(names(&map), vec!["Subject", "Comments"]);
+    });
+}
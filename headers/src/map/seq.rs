@@ -0,0 +1,174 @@
+use std::slice;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ::name::HeaderName;
+use ::header::HeaderObj;
+
+use super::{HeaderMap, Iter};
+
+/// The sequence number assigned to a header when it is inserted into a `HeaderMap`.
+///
+/// Sequence numbers come from a process wide counter, so they are unique and
+/// monotonically increasing across all maps (but not necessarily continuous
+/// for any specific map). The first sequence number is `1`.
+pub type HeaderSeq = usize;
+
+static NEXT_SEQ: AtomicUsize = AtomicUsize::new(1);
+
+/// Returns the next sequence number.
+pub fn next_seq() -> HeaderSeq {
+    NEXT_SEQ.fetch_add(1, Ordering::Relaxed)
+}
+
+impl HeaderMap {
+
+    /// Iterates over all headers in map order together with their sequence numbers.
+    ///
+    /// While the map order tells at which position a header is, the sequence
+    /// number tells when it was inserted. They differ e.g. if maps are combined
+    /// with `insert_all` as the inserted headers keep their sequence numbers.
+    /// Cloning a map preserves the sequence numbers, replacing a header of a
+    /// `MAX_ONE` header kind assigns a new sequence number to the new header.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::HeaderKind;
+    /// use mail_headers::headers::*;
+    ///
+    /// let mut map = headers! { _From: ["me@example.com"] }.unwrap();
+    /// let template = headers! { Subject: "from template" }.unwrap();
+    /// map.insert(Comments::auto_body("added after the template was created").unwrap());
+    /// map.insert_all(template);
+    ///
+    /// let seqs = map.iter_with_seq().map(|(seq, _, _)| seq).collect::<Vec<_>>();
+    /// // the subject was inserted (into the template) before the comment
+    /// assert!(seqs[2] < seqs[1]);
+    /// # }
+    /// ```
+    pub fn iter_with_seq(&self) -> IterWithSeq {
+        IterWithSeq {
+            inner: self.iter(),
+            seqs: self.seqs.iter()
+        }
+    }
+
+    /// Returns the sequence number of the header at given position (in map order).
+    pub fn seq_of(&self, position: usize) -> Option<HeaderSeq> {
+        self.seqs.get(position).cloned()
+    }
+
+    /// Returns the highest sequence number ever inserted into this map.
+    ///
+    /// This includes the sequence numbers of headers which have been removed
+    /// and the high-water marks of maps inserted with `insert_all`. For a map
+    /// to which nothing was ever inserted this is `0`.
+    pub fn max_seq(&self) -> HeaderSeq {
+        self.max_seq
+    }
+}
+
+/// Iterator returned by `HeaderMap::iter_with_seq`.
+#[derive(Debug)]
+pub struct IterWithSeq<'a> {
+    inner: Iter<'a>,
+    seqs: slice::Iter<'a, HeaderSeq>
+}
+
+impl<'a> Iterator for IterWithSeq<'a> {
+    type Item = (HeaderSeq, HeaderName, &'a Box<HeaderObj>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (name, obj) = self.inner.next()?;
+        let seq = *self.seqs.next().expect("[BUG] sequence numbers out of sync with headers");
+        Some((seq, name, obj))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.seqs.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for IterWithSeq<'a> {
+    fn len(&self) -> usize {
+        self.seqs.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::{HeaderKind, HeaderMap};
+    use ::headers::{_From, _To, Subject, Comments};
+
+    fn seqs(map: &HeaderMap) -> Vec<usize> {
+        map.iter_with_seq().map(|(seq, _, _)| seq).collect()
+    }
+
+    test!(combine_keeps_original_seqs {
+        let mut map = HeaderMap::new();
+        map.insert(_From::auto_body(["me@example.com"])?);
+        let mut other = HeaderMap::new();
+        other.insert(_To::auto_body(["you@example.com"])?);
+        map.insert(Subject::auto_body("hy")?);
+        let other_max = other.max_seq();
+
+        map.insert_all(other);
+
+        let names = map.iter_with_seq().map(|(_, name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["From", "Subject", "To"]);
+
+        let seqs = seqs(&map);
+        assert!(seqs[0] < seqs[2]);
+        assert!(seqs[2] < seqs[1]);
+        assert_eq!(seqs[2], other_max);
+        assert_eq!(map.max_seq(), seqs[1]);
+        assert_eq!(map.seq_of(2), Some(seqs[2]));
+        assert_eq!(map.seq_of(3), None);
+    });
+
+    test!(set_assigns_higher_seq {
+        let mut map = HeaderMap::new();
+        map.insert(Subject::auto_body("first")?);
+        map.insert(Comments::auto_body("a comment")?);
+        let first = map.seq_of(0).unwrap();
+
+        map.insert(Subject::auto_body("second")?);
+
+        assert_eq!(map.len(), 2);
+        let subject_seq = map.iter_with_seq()
+            .find(|&(_, name, _)| name == Subject::name())
+            .map(|(seq, _, _)| seq)
+            .unwrap();
+        assert!(subject_seq > first);
+        assert_eq!(map.max_seq(), subject_seq);
+        assert_eq!(seqs(&map).len(), map.len());
+    });
+
+    test!(clone_preserves_seqs {
+        let mut map = HeaderMap::new();
+        map.insert(_From::auto_body(["me@example.com"])?);
+        map.insert(Comments::auto_body("one")?);
+        map.insert(Comments::auto_body("two")?);
+
+        let cloned = map.clone();
+        assert_eq!(seqs(&cloned), seqs(&map));
+        assert_eq!(cloned.max_seq(), map.max_seq());
+    });
+
+    test!(remove_drops_seqs {
+        let mut map = HeaderMap::new();
+        map.insert(Comments::auto_body("one")?);
+        map.insert(_From::auto_body(["me@example.com"])?);
+        map.insert(Comments::auto_body("two")?);
+        let from_seq = map.seq_of(1).unwrap();
+        let max = map.max_seq();
+
+        map.remove(Comments);
+
+        assert_eq!(seqs(&map), vec![from_seq]);
+        assert_eq!(map.max_seq(), max);
+    });
+}
@@ -0,0 +1,753 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono;
+use vec1::Vec1;
+
+use internals::MailType;
+use internals::error::EncodingError;
+
+use ::{HeaderKind, HeaderTryFrom};
+use ::name::HeaderName;
+use ::header::HeaderObj;
+use ::error::{ComponentCreationError, StringPairError};
+use ::header_components as components;
+use ::header_components::utils::encoded_word::decode_encoded_words;
+use ::headers::*;
+
+use super::{HeaderMap, MapLimits};
+use super::raw_header::{RawHeader, runtime_name};
+
+/// Creates a header from the unfolded value of a `(name, value)` pair.
+pub type PairParser = fn(&str) -> Result<Box<HeaderObj>, ComponentCreationError>;
+
+/// The parsers used by `HeaderMap::from_string_pairs` to create typed headers.
+///
+/// `PairRegistry::builtin()` (which is also the `Default`) knows all headers
+/// defined in `headers`. Custom headers can be added with `register` or
+/// `register_str`.
+///
+/// Headers with a name no parser is registered for are kept as `RawHeader`
+/// (with their value as it is) if the raw fallback is enabled, which it is
+/// for `builtin()` but not for `new()`, see `set_raw_fallback`.
+///
+/// Note that the built-in `Received` header only models a single token
+/// (a address or a domain). To keep full `Received` lines register a
+/// header kind with a `RawFieldBody` component under the `Received` name.
+#[derive(Clone)]
+pub struct PairRegistry {
+    parsers: HashMap<HeaderName, PairParser>,
+    raw_fallback: bool
+}
+
+impl PairRegistry {
+
+    /// Creates a registry without any header and without the raw fallback.
+    pub fn new() -> Self {
+        PairRegistry { parsers: HashMap::new(), raw_fallback: false }
+    }
+
+    /// Creates a registry knowing the headers defined in `headers` with the raw fallback.
+    pub fn builtin() -> Self {
+        let mut registry = PairRegistry::new();
+        registry.set_raw_fallback(true);
+
+        macro_rules! builtin {
+            ($($header:ident => $parse:ident),*) => ($({
+                fn parse(value: &str) -> Result<Box<HeaderObj>, ComponentCreationError> {
+                    Ok(Box::new($header::body($parse(value)?)))
+                }
+                registry.register($header::name(), parse);
+            })*);
+        }
+
+        builtin! {
+            Date => date_time,
+            _From => mailbox_list,
+            Sender => mailbox,
+            ReplyTo => mailbox_list,
+            _To => mailbox_list,
+            Cc => mailbox_list,
+            Bcc => mailbox_list,
+            MessageId => message_id,
+            InReplyTo => message_id_list,
+            References => message_id_list,
+            Subject => unstructured,
+            Comments => unstructured,
+            Keywords => phrase_list,
+            ResentDate => date_time,
+            ResentFrom => mailbox_list,
+            ResentSender => mailbox,
+            ResentTo => mailbox_list,
+            ResentCc => mailbox_list,
+            ResentBcc => opt_mailbox_list,
+            ResentMsgId => message_id,
+            ReturnPath => path,
+            Received => received_token,
+            ContentType => media_type,
+            ContentId => message_id,
+            ContentTransferEncoding => transfer_encoding,
+            ContentDescription => unstructured,
+            ContentDisposition => disposition,
+            Precedence => precedence,
+            XAutoResponseSuppress => auto_response_suppress,
+            ListId => list_id,
+            ListPost => uri_list,
+            ListArchive => uri_list,
+            ListHelp => uri_list,
+            ListSubscribe => uri_list,
+            FeedbackId => feedback_id,
+            XReportAbuse => uri_list,
+            XRedacted => unstructured,
+            XOriginalFrom => mailbox,
+            XSpamStatus => spam_status
+        }
+
+        registry
+    }
+
+    /// Registers (or replaces) the parser for given header name.
+    pub fn register(&mut self, name: HeaderName, parser: PairParser) {
+        self.parsers.insert(name, parser);
+    }
+
+    /// Registers a header whose component is created from the value as it is.
+    ///
+    /// This is meant for raw components like `RawFieldBody`, which encode
+    /// the value exactly as given and as such always round-trip.
+    pub fn register_str<H>(&mut self)
+        where H: HeaderKind, H::Component: HeaderTryFrom<String>
+    {
+        self.register(H::name(), str_parser::<H>);
+    }
+
+    /// Sets if headers with unknown names are created as `RawHeader` or fail.
+    ///
+    /// Without the fallback `from_string_pairs` (and `from_block_bytes`)
+    /// fail with `StringPairError::UnknownHeader` for such headers.
+    pub fn set_raw_fallback(&mut self, enabled: bool) {
+        self.raw_fallback = enabled;
+    }
+
+    /// Returns true if headers with unknown names are created as `RawHeader`.
+    pub fn has_raw_fallback(&self) -> bool {
+        self.raw_fallback
+    }
+
+    /// Returns the parser for given header name.
+    ///
+    /// This does not include the raw fallback.
+    pub fn get(&self, name: HeaderName) -> Option<PairParser> {
+        self.parsers.get(&name).cloned()
+    }
+
+    /// Returns the names of all headers a parser is registered for (in no particular order).
+    pub fn names<'a>(&'a self) -> impl Iterator<Item=HeaderName> + 'a {
+        self.parsers.keys().cloned()
+    }
+
+    /// Finds the parser for the header name given as string (ignoring case).
+    fn find(&self, name: &str) -> Option<(HeaderName, PairParser)> {
+        self.parsers.iter()
+            .find(|&(known, _)| known.as_str().eq_ignore_ascii_case(name))
+            .map(|(&known, &parser)| (known, parser))
+    }
+
+    /// Creates the header for given `(name, value)` pair.
+    ///
+    /// Used by `from_string_pairs` and `from_block_bytes`.
+    pub(super) fn parse_pair(&self, name: &str, value: &str)
+        -> Result<(HeaderName, Box<HeaderObj>), StringPairError>
+    {
+        let unknown = || StringPairError::UnknownHeader { name: name.to_owned() };
+        let (name, parser) = match self.find(name) {
+            Some(found) => found,
+            None if self.raw_fallback => {
+                let name = runtime_name(name).ok_or_else(unknown)?;
+                let raw = RawHeader::with_name(name, value)
+                    .map_err(|error| StringPairError::Component { header_name: name, error })?;
+                return Ok((name, Box::new(raw)));
+            },
+            None => return Err(unknown())
+        };
+        let obj = parser(value)
+            .map_err(|error| StringPairError::Component { header_name: name, error })?;
+        Ok((name, obj))
+    }
+}
+
+impl Default for PairRegistry {
+    fn default() -> Self {
+        PairRegistry::builtin()
+    }
+}
+
+impl fmt::Debug for PairRegistry {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        let mut names = self.parsers.keys().map(|name| name.as_str()).collect::<Vec<_>>();
+        names.sort();
+        fter.debug_struct("PairRegistry")
+            .field("headers", &names)
+            .field("raw_fallback", &self.raw_fallback)
+            .finish()
+    }
+}
+
+impl HeaderMap {
+
+    /// Converts the map into `(name, value)` string pairs, e.g. for a key-value store.
+    ///
+    /// The value is the encoded header body for given mail type, unfolded
+    /// into a single line: as the encoder only folds by inserting a CRLF
+    /// before whitespace, unfolding removes each CRLF and keeps the
+    /// whitespace (like described in RFC 5322, section 2.2.3). The pairs
+    /// are in map order and use the (canonical) header name.
+    ///
+    /// # Round trip
+    ///
+    /// `from_string_pairs` creates a map from the pairs again. The first
+    /// round trip can normalize the headers, e.g. whitespace in a display
+    /// name is collapsed, the whitespace between two adjacent encoded words
+    /// is dropped (as required when decoding them) and text which looks
+    /// like a encoded word is decoded. After this one normalization pass
+    /// the conversion is idempotent: for pairs returned by a map restored
+    /// with `from_string_pairs` (using the same mail type and registry)
+    /// restoring and converting them again returns exactly the same pairs.
+    /// So storing and loading a map which was not edited in between does
+    /// not produce spurious changes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # extern crate mail_internals;
+    /// # fn main() {
+    /// use mail_internals::MailType;
+    /// use mail_headers::HeaderMap;
+    /// use mail_headers::headers::*;
+    /// use mail_headers::map::PairRegistry;
+    ///
+    /// let map = headers! {
+    ///     _From: [("Jane Doe", "jane@example.com")],
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// let pairs = map.to_string_pairs(MailType::Ascii).unwrap();
+    /// assert_eq!(pairs, vec![
+    ///     ("From".to_owned(), "Jane Doe <jane@example.com>".to_owned()),
+    ///     ("Subject".to_owned(), "hy there".to_owned())
+    /// ]);
+    ///
+    /// let restored = HeaderMap::from_string_pairs(pairs.clone(), &PairRegistry::builtin()).unwrap();
+    /// assert_eq!(restored.to_string_pairs(MailType::Ascii).unwrap(), pairs);
+    /// # }
+    /// ```
+    pub fn to_string_pairs(&self, mail_type: MailType) -> Result<Vec<(String, String)>, EncodingError> {
+        self.iter()
+            .map(|(name, obj)| {
+                let value = obj.encoded_value(mail_type)?;
+                let value = String::from_utf8_lossy(&value).replace("\r\n", "");
+                Ok((name.as_str().to_owned(), value))
+            })
+            .collect()
+    }
+
+    /// Creates a map from `(name, value)` string pairs created by `to_string_pairs`.
+    ///
+    /// The header names are looked up in the registry ignoring case and
+    /// the values are parsed by the registered parser. The headers are
+    /// added in the order of the pairs without replacing "max one" headers
+    /// (like `from_vec`), so a stored map is restored as it was even if it
+    /// does not validate.
+    ///
+    /// # Error
+    ///
+    /// Returns the error for the first pair which can not be converted.
+    pub fn from_string_pairs<I, N, V>(pairs: I, registry: &PairRegistry) -> Result<HeaderMap, StringPairError>
+        where I: IntoIterator<Item=(N, V)>, N: AsRef<str>, V: AsRef<str>
+    {
+        HeaderMap::from_string_pairs_with_limits(pairs, registry, MapLimits::default())
+    }
+
+    /// Like `from_string_pairs` but creates a map with given limits.
+    ///
+    /// Fails with `StringPairError::Limit` as soon as a header would exceed
+    /// the limits, so that untrusted input doesn't have to be converted
+    /// completely before its size can be checked.
+    pub fn from_string_pairs_with_limits<I, N, V>(pairs: I, registry: &PairRegistry, limits: MapLimits)
+        -> Result<HeaderMap, StringPairError>
+        where I: IntoIterator<Item=(N, V)>, N: AsRef<str>, V: AsRef<str>
+    {
+        let mut map = HeaderMap::with_limits(limits);
+        for (name, value) in pairs {
+            let (header_name, obj) = registry.parse_pair(name.as_ref(), value.as_ref())?;
+            map.try_push(header_name, obj)
+                .map_err(|violation| StringPairError::Limit { header_name, violation })?;
+        }
+        Ok(map)
+    }
+}
+
+fn str_parser<H>(value: &str) -> Result<Box<HeaderObj>, ComponentCreationError>
+    where H: HeaderKind, H::Component: HeaderTryFrom<String>
+{
+    Ok(Box::new(H::body(H::Component::try_from(value.to_owned())?)))
+}
+
+/// Returns the text inside of `<..>`, or the (trimmed) text if it's not in angle brackets.
+fn strip_angle_brackets(value: &str) -> &str {
+    let value = value.trim();
+    if value.len() >= 2 && value.starts_with('<') && value.ends_with('>') {
+        &value[1..value.len()-1]
+    } else {
+        value
+    }
+}
+
+/// Splits a address list at all `','` which are not in a quoted string or a angle address.
+fn split_list(value: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut in_angle = false;
+    for (idx, ch) in value.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match ch {
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '<' if !quoted => in_angle = true,
+            '>' if !quoted => in_angle = false,
+            ',' if !quoted && !in_angle => {
+                items.push(&value[start..idx]);
+                start = idx + 1;
+            },
+            _ => {}
+        }
+    }
+    items.push(&value[start..]);
+    items.into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Removes the quotes and quoted-pair escapes of all quoted strings in the text.
+fn unquote(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut quoted = false;
+    let mut escaped = false;
+    for ch in text.chars() {
+        if escaped {
+            out.push(ch);
+            escaped = false;
+        } else if quoted && ch == '\\' {
+            escaped = true;
+        } else if ch == '"' {
+            quoted = !quoted;
+        } else {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+fn unstructured(value: &str) -> Result<components::Unstructured, ComponentCreationError> {
+    components::Unstructured::try_from(decode_encoded_words(value))
+}
+
+fn date_time(value: &str) -> Result<components::DateTime, ComponentCreationError> {
+    chrono::DateTime::parse_from_rfc2822(value.trim())
+        .map(components::DateTime::new)
+        .map_err(|err| ComponentCreationError::from_parent(err, "DateTime")
+            .with_str_context(value))
+}
+
+fn mailbox(value: &str) -> Result<components::Mailbox, ComponentCreationError> {
+    let value = value.trim();
+    if !value.ends_with('>') {
+        return components::Mailbox::try_from(value.to_owned());
+    }
+    let start = value.rfind('<')
+        .ok_or_else(|| ComponentCreationError::new_with_str("Mailbox", value))?;
+    let email = value[start+1..value.len()-1].to_owned();
+    let display_name = value[..start].trim();
+    if display_name.is_empty() {
+        components::Mailbox::try_from(email)
+    } else {
+        let display_name = decode_encoded_words(&unquote(display_name));
+        components::Mailbox::try_from((display_name, email))
+    }
+}
+
+fn mailbox_list(value: &str) -> Result<components::MailboxList, ComponentCreationError> {
+    let mailboxes = split_list(value).into_iter()
+        .map(mailbox)
+        .collect::<Result<Vec<_>, _>>()?;
+    Vec1::from_vec(mailboxes)
+        .map(components::MailboxList)
+        .map_err(|_| ComponentCreationError::new_with_str("MailboxList", value))
+}
+
+fn opt_mailbox_list(value: &str) -> Result<components::OptMailboxList, ComponentCreationError> {
+    let mailboxes = split_list(value).into_iter()
+        .map(mailbox)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(components::OptMailboxList(mailboxes))
+}
+
+fn message_id(value: &str) -> Result<components::MessageId, ComponentCreationError> {
+    components::MessageId::try_from(strip_angle_brackets(value).to_owned())
+}
+
+fn message_id_list(value: &str) -> Result<components::MessageIdList, ComponentCreationError> {
+    let ids = value.split('>')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| message_id(part.trim_start_matches('<')))
+        .collect::<Result<Vec<_>, _>>()?;
+    Vec1::from_vec(ids)
+        .map(components::MessageIdList)
+        .map_err(|_| ComponentCreationError::new_with_str("MessageIdList", value))
+}
+
+fn path(value: &str) -> Result<components::Path, ComponentCreationError> {
+    match strip_angle_brackets(value) {
+        "" => Ok(components::Path(None)),
+        email => components::Path::try_from(email.to_owned())
+    }
+}
+
+fn received_token(value: &str) -> Result<components::ReceivedToken, ComponentCreationError> {
+    use self::components::ReceivedToken::*;
+    let token = strip_angle_brackets(value);
+    if token.contains('@') {
+        components::Email::try_from(token.to_owned()).map(Address)
+    } else {
+        components::Domain::try_from(token.to_owned()).map(Domain)
+    }
+}
+
+fn media_type(value: &str) -> Result<components::MediaType, ComponentCreationError> {
+    components::MediaType::parse(value.trim())
+}
+
+fn transfer_encoding(value: &str) -> Result<components::TransferEncoding, ComponentCreationError> {
+    use self::components::TransferEncoding::*;
+    let value = value.trim();
+    [_7Bit, _8Bit, Binary, QuotedPrintable, Base64].iter()
+        .find(|candidate| candidate.repr().as_str().eq_ignore_ascii_case(value))
+        .cloned()
+        .ok_or_else(|| ComponentCreationError::new_with_str("TransferEncoding", value))
+}
+
+fn precedence(value: &str) -> Result<components::Precedence, ComponentCreationError> {
+    components::Precedence::parse(value.trim())
+}
+
+fn auto_response_suppress(value: &str) -> Result<components::AutoResponseSuppress, ComponentCreationError> {
+    components::AutoResponseSuppress::parse(value.trim())
+}
+
+fn list_id(value: &str) -> Result<components::ListId, ComponentCreationError> {
+    components::ListId::parse(value.trim())
+}
+
+fn feedback_id(value: &str) -> Result<components::FeedbackId, ComponentCreationError> {
+    components::FeedbackId::parse(value.trim())
+}
+
+fn spam_status(value: &str) -> Result<components::SpamStatus, ComponentCreationError> {
+    components::SpamStatus::parse(value)
+}
+
+fn phrase_list(value: &str) -> Result<components::PhraseList, ComponentCreationError> {
+    let phrases = split_list(value).into_iter()
+        .map(|phrase| components::Phrase::try_from(decode_encoded_words(&unquote(phrase))))
+        .collect::<Result<Vec<_>, _>>()?;
+    Vec1::from_vec(phrases)
+        .map(components::PhraseList)
+        .map_err(|_| ComponentCreationError::new_with_str("PhraseList", value))
+}
+
+fn disposition(value: &str) -> Result<components::Disposition, ComponentCreationError> {
+    components::Disposition::parse(value.trim())
+}
+
+fn uri_list(value: &str) -> Result<components::UriList, ComponentCreationError> {
+    components::UriList::parse(value.trim())
+}
+
+#[cfg(test)]
+mod test {
+    use internals::MailType;
+    use ::header_components as components;
+    use ::error::StringPairError;
+    use ::headers::*;
+    use ::HeaderMap;
+    use ::map::RawHeader;
+    use super::*;
+
+    mod raw_headers {
+        use ::header_components as components;
+
+        def_headers! {
+            test_name: validate_header_names,
+            scope: components,
+            XCustom, unchecked { "X-Custom" }, RawFieldBody, multi, None,
+            RawReceived, unchecked { "Received" }, RawFieldBody, multi, None
+        }
+    }
+    use self::raw_headers::{XCustom, RawReceived};
+
+    /// Checks that a map created from the pairs converts back into the same pairs.
+    fn assert_round_trip(pairs: &[(String, String)], registry: &PairRegistry, mail_type: MailType) {
+        let map = HeaderMap::from_string_pairs(pairs.to_vec(), registry).unwrap();
+        assert_eq!(map.to_string_pairs(mail_type).unwrap(), pairs);
+    }
+
+    test!(folded_subject_is_unfolded {
+        let subject = "a subject with a lot of words so that it needs to be folded to not exceed the line limit";
+        let map = headers! {
+            Subject: subject
+        }?;
+
+        let folded = map.get_untyped(Subject).next().unwrap().encoded_value(MailType::Ascii)?.into_owned();
+        assert!(String::from_utf8(folded)?.contains("\r\n "));
+
+        let pairs = map.to_string_pairs(MailType::Ascii)?;
+        assert_eq!(pairs, vec![("Subject".to_owned(), subject.to_owned())]);
+        assert_round_trip(&pairs, &PairRegistry::builtin(), MailType::Ascii);
+
+        let restored = HeaderMap::from_string_pairs(pairs, &PairRegistry::builtin())?;
+        assert_eq!(restored.get_single(Subject).unwrap()?.as_str(), subject);
+    });
+
+    test!(encoded_words_are_decoded {
+        let map = headers! {
+            _From: [("Jäne Doe", "jane@example.com"), ("Doe, John", "john@example.com")],
+            Subject: "hällo world"
+        }?;
+
+        let pairs = map.to_string_pairs(MailType::Ascii)?;
+        assert!(pairs.iter().all(|&(_, ref value)| value.is_ascii()));
+        assert_round_trip(&pairs, &PairRegistry::builtin(), MailType::Ascii);
+
+        let restored = HeaderMap::from_string_pairs(pairs, &PairRegistry::builtin())?;
+        assert_eq!(restored.get_single(Subject).unwrap()?.as_str(), "hällo world");
+        let from = restored.get_single(_From).unwrap()?;
+        let names = from.0.iter()
+            .map(|mailbox| mailbox.display_name.as_ref().unwrap().to_display_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Jäne Doe", "Doe, John"]);
+    });
+
+    test!(first_round_trip_can_normalize {
+        // the whitespace between adjacent encoded words is removed when decoding
+        let map = headers! {
+            Subject: "hällo wörld"
+        }?;
+        let registry = PairRegistry::builtin();
+
+        let pairs = map.to_string_pairs(MailType::Ascii)?;
+        let normalized = HeaderMap::from_string_pairs(pairs, &registry)?
+            .to_string_pairs(MailType::Ascii)?;
+        assert_round_trip(&normalized, &registry, MailType::Ascii);
+
+        let restored = HeaderMap::from_string_pairs(normalized, &registry)?;
+        assert_eq!(restored.get_single(Subject).unwrap()?.as_str(), "hällowörld");
+    });
+
+    test!(received_line_survives {
+        // the built-in component only models a single token
+        let map = headers! {
+            Received: components::ReceivedToken::Domain(components::Domain::try_from("mx.example.com")?)
+        }?;
+        let pairs = map.to_string_pairs(MailType::Ascii)?;
+        assert_round_trip(&pairs, &PairRegistry::builtin(), MailType::Ascii);
+
+        let line = "from mx.example.com (mx.example.com [192.0.2.1]) by mail.example.org \
+            with ESMTPS id 4a2b; Mon, 1 Oct 2018 12:00:00 +0000";
+        let pairs = vec![("Received".to_owned(), line.to_owned())];
+        assert_err!(HeaderMap::from_string_pairs(pairs.clone(), &PairRegistry::builtin()));
+
+        let mut registry = PairRegistry::builtin();
+        registry.register_str::<RawReceived>();
+        assert_round_trip(&pairs, &registry, MailType::Ascii);
+    });
+
+    test!(unknown_header_survives_as_raw_header {
+        let pairs = vec![
+            ("X-Custom".to_owned(), "some  value; with=params".to_owned()),
+            ("Subject".to_owned(), "hy".to_owned()),
+            ("X-Custom".to_owned(), "second".to_owned())
+        ];
+        assert_round_trip(&pairs, &PairRegistry::builtin(), MailType::Ascii);
+
+        let map = HeaderMap::from_string_pairs(pairs.clone(), &PairRegistry::builtin())?;
+        let values = map.get_untyped(XCustom::name())
+            .map(|obj| RawHeader::from_obj(obj).unwrap().as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec!["some  value; with=params", "second"]);
+        let encoded = String::from_utf8(map.encode_checked(MailType::Ascii)?)?;
+        assert_eq!(encoded, "X-Custom: some  value; with=params\r\nSubject: hy\r\nX-Custom: second\r\n");
+
+        // invalid values of unknown headers still fail
+        match assert_err!(HeaderMap::from_string_pairs(vec![("X-Custom", "a\rb")], &PairRegistry::builtin())) {
+            StringPairError::Component { header_name, .. } => assert_eq!(header_name, XCustom::name()),
+            other => panic!("unexpected error: {:?}", other)
+        }
+        assert_err!(HeaderMap::from_string_pairs(vec![("X Custom", "v")], &PairRegistry::builtin()));
+    });
+
+    test!(unknown_header_fails_without_raw_fallback {
+        let pairs = vec![("X-Custom", "some value")];
+        let mut registry = PairRegistry::builtin();
+        registry.set_raw_fallback(false);
+        assert!(!PairRegistry::new().has_raw_fallback());
+
+        match assert_err!(HeaderMap::from_string_pairs(pairs.clone(), &registry)) {
+            StringPairError::UnknownHeader { name } => assert_eq!(name, "X-Custom"),
+            other => panic!("unexpected error: {:?}", other)
+        }
+
+        registry.register_str::<XCustom>();
+        let map = HeaderMap::from_string_pairs(pairs, &registry)?;
+        assert_eq!(map.get_single(XCustom).unwrap()?.as_str(), "some value");
+
+        // names are looked up ignoring case
+        let map = HeaderMap::from_string_pairs(vec![("x-custom", "v")], &registry)?;
+        assert_eq!(map.get_single(XCustom).unwrap()?.as_str(), "v");
+    });
+
+    test!(keywords_and_content_disposition_round_trip {
+        let mut map = headers! {
+            Keywords: ["first", "zweites Wört", "third one"]
+        }?;
+        map.insert(ContentDisposition::body(components::Disposition::new(
+            components::DispositionKind::Attachment,
+            components::FileMeta {
+                file_name: Some("hälö datei.png".to_owned()),
+                size: Some(4096),
+                ..Default::default()
+            }
+        )));
+
+        let pairs = map.to_string_pairs(MailType::Ascii)?;
+        assert_round_trip(&pairs, &PairRegistry::builtin(), MailType::Ascii);
+
+        let restored = HeaderMap::from_string_pairs(pairs, &PairRegistry::builtin())?;
+        let keywords = restored.get_single(Keywords).unwrap()?.0.iter()
+            .map(|phrase| phrase.to_display_string())
+            .collect::<Vec<_>>();
+        assert_eq!(keywords, vec!["first", "zweites Wört", "third one"]);
+        let disposition = restored.get_single(ContentDisposition).unwrap()?;
+        assert_eq!(disposition.file_meta().file_name.as_ref().unwrap(), "hälö datei.png");
+        assert_eq!(disposition.file_meta().size, Some(4096));
+    });
+
+    test!(full_cycle_with_all_kinds_of_headers {
+        let pairs = vec![
+            ("Date", "Wed, 10 Oct 2018 12:00:00 +0000"),
+            ("From", "Jane Doe <jane@example.com>"),
+            ("To", "<a@example.com>, <b@example.com>"),
+            ("Subject", "a subject"),
+            ("Keywords", "a, b"),
+            ("Content-Disposition", "inline; filename=logo.png"),
+            ("X-Mailer", "some mailer 1.0"),
+            ("DKIM-Signature", "v=1; a=rsa-sha256; d=example.com; b=Zm9v")
+        ].into_iter()
+            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+            .collect::<Vec<_>>();
+        let registry = PairRegistry::builtin();
+
+        let map = HeaderMap::from_string_pairs(pairs.clone(), &registry)?;
+        let normalized = map.to_string_pairs(MailType::Ascii)?;
+        assert_round_trip(&normalized, &registry, MailType::Ascii);
+        // only the name of the unknown header is normalized
+        assert_eq!(normalized[..7], pairs[..7]);
+        assert_eq!(normalized[7], ("Dkim-Signature".to_owned(), pairs[7].1.clone()));
+    });
+
+    test!(structured_headers_round_trip {
+        let mut map = headers! {
+            Date: chrono::DateTime::parse_from_rfc2822("Mon, 1 Oct 2018 12:00:00 +0000")?,
+            Sender: ("Mailer", "mailer@example.com"),
+            _To: ["a@example.com", "b@example.com"],
+            MessageId: "abc.123@example.com",
+            ReturnPath: "bounces@example.com",
+            Comments: "some comment"
+        }?;
+        map.insert(InReplyTo::body(components::MessageIdList(vec1![
+            components::MessageId::try_from("a@example.com")?,
+            components::MessageId::try_from("b@example.com")?
+        ])));
+
+        let pairs = map.to_string_pairs(MailType::Ascii)?;
+        assert_eq!(pairs.len(), 7);
+        assert_round_trip(&pairs, &PairRegistry::builtin(), MailType::Ascii);
+
+        let restored = HeaderMap::from_string_pairs(pairs, &PairRegistry::builtin())?;
+        assert_eq!(restored.get_single(InReplyTo).unwrap()?.len(), 2);
+    });
+
+    test!(max_one_duplicates_are_kept {
+        let pairs = vec![
+            ("Subject".to_owned(), "a".to_owned()),
+            ("Subject".to_owned(), "b".to_owned())
+        ];
+        let map = HeaderMap::from_string_pairs(pairs.clone(), &PairRegistry::builtin())?;
+        assert_eq!(map.len(), 2);
+        assert_err!(map.validate());
+        assert_eq!(map.to_string_pairs(MailType::Ascii)?, pairs);
+    });
+
+    test!(invalid_value_fails {
+        let res = HeaderMap::from_string_pairs(vec![("To", "not an address")], &PairRegistry::builtin());
+        match assert_err!(res) {
+            StringPairError::Component { header_name, .. } => assert_eq!(header_name, _To::name()),
+            other => panic!("unexpected error: {:?}", other)
+        }
+    });
+
+    test!(limits_stop_the_conversion {
+        use ::error::LimitKind;
+
+        let pairs = vec![("Subject", "a"), ("Comments", "b"), ("Comments", "c")];
+        let limits = MapLimits { max_headers: Some(2), ..Default::default() };
+        let res = HeaderMap::from_string_pairs_with_limits(pairs.clone(), &PairRegistry::builtin(), limits);
+        match assert_err!(res) {
+            StringPairError::Limit { header_name, violation } => {
+                assert_eq!(header_name, Comments::name());
+                assert_eq!(violation.kind(), LimitKind::HeaderCount);
+                assert_eq!(violation.accepted(), 2);
+            },
+            other => panic!("unexpected error: {:?}", other)
+        }
+
+        let limits = MapLimits { max_headers: Some(3), ..Default::default() };
+        let map = HeaderMap::from_string_pairs_with_limits(pairs, &PairRegistry::builtin(), limits)?;
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.limits(), limits);
+    });
+
+    #[test]
+    fn split_list_respects_quotes_and_angles() {
+        assert_eq!(
+            split_list("\"Doe, John\" <a@b>, c@d, <e,f@g>, "),
+            vec!["\"Doe, John\" <a@b>", "c@d", "<e,f@g>"]
+        );
+        assert_eq!(split_list("  "), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn unquote_removes_quoting() {
+        assert_eq!(unquote("\"Doe, John\""), "Doe, John");
+        assert_eq!(unquote("\"Doe,\" John"), "Doe, John");
+        assert_eq!(unquote("\"a \\\"b\\\" c\""), "a \"b\" c");
+    }
+}
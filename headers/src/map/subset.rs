@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+
+use internals::MailType;
+
+use ::name::HeaderName;
+
+use super::HeaderMap;
+
+/// How `HeaderMap::is_subset_of` compares headers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SubsetMode {
+    /// Every header name in the map has to appear in the other map.
+    NamesOnly,
+    /// Every header in the map has to appear in the other map with the same
+    /// name and encoded value, at least as often as in the map.
+    ///
+    /// Values are compared by their encoding for an internationalized mail
+    /// (see `HeaderObjTrait::encoded_value`).
+    Exact
+}
+
+/// Something which makes a map not a subset of another map, see `HeaderMap::subset_violations`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SubsetViolation {
+    /// There is no header with this name in the other map.
+    MissingName { name: HeaderName },
+    /// The header value appears less often in the other map than in the map.
+    ///
+    /// `found` is `0` if the other map only has headers with this name but different values.
+    MissingValue { name: HeaderName, value: String, required: usize, found: usize },
+    /// A header with this name can not be encoded, so it can't be compared.
+    Unencodable { name: HeaderName }
+}
+
+impl HeaderMap {
+
+    /// Returns true if all headers of this map appear in the other map.
+    ///
+    /// What "appear" means depends on the `mode`, see `SubsetMode`. This can
+    /// be used e.g. to check that a map of user provided headers only adds
+    /// headers to a base map but never changes any of them.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    /// use mail_headers::map::SubsetMode;
+    ///
+    /// let base = headers! {
+    ///     _From: ["me@example.com"],
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    /// let overrides = headers! {
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    /// let changed = headers! {
+    ///     Subject: "hy you"
+    /// }.unwrap();
+    ///
+    /// assert!(overrides.is_subset_of(&base, SubsetMode::Exact));
+    /// assert!(changed.is_subset_of(&base, SubsetMode::NamesOnly));
+    /// assert!(!changed.is_subset_of(&base, SubsetMode::Exact));
+    /// # }
+    /// ```
+    pub fn is_subset_of(&self, other: &HeaderMap, mode: SubsetMode) -> bool {
+        self.subset_violations(other, mode).is_empty()
+    }
+
+    /// Lists everything which makes this map not a subset of the other map.
+    ///
+    /// Violations are listed in the order in which the headers appear in this
+    /// map, each missing name or value is only listed once.
+    pub fn subset_violations(&self, other: &HeaderMap, mode: SubsetMode) -> Vec<SubsetViolation> {
+        let mut violations = Vec::new();
+
+        let mut seen_names = Vec::new();
+        for (name, _) in self.iter() {
+            if seen_names.contains(&name) {
+                continue;
+            }
+            seen_names.push(name);
+            if !other.contains(name) {
+                violations.push(SubsetViolation::MissingName { name });
+            }
+        }
+
+        if mode == SubsetMode::NamesOnly {
+            return violations;
+        }
+
+        let mut other_counts = HashMap::new();
+        for (name, obj) in other.iter() {
+            if let Ok(value) = obj.encoded_value(MailType::Internationalized) {
+                *other_counts.entry((name, value.into_owned())).or_insert(0) += 1;
+            }
+        }
+
+        let mut required: Vec<(HeaderName, Vec<u8>, usize)> = Vec::new();
+        for (name, obj) in self.iter() {
+            if !other.contains(name) {
+                continue;
+            }
+            let value = match obj.encoded_value(MailType::Internationalized) {
+                Ok(value) => value.into_owned(),
+                Err(_) => {
+                    let violation = SubsetViolation::Unencodable { name };
+                    if !violations.contains(&violation) {
+                        violations.push(violation);
+                    }
+                    continue;
+                }
+            };
+            let pos = required.iter()
+                .position(|&(other_name, ref other_value, _)| other_name == name && *other_value == value);
+            match pos {
+                Some(pos) => required[pos].2 += 1,
+                None => required.push((name, value, 1))
+            }
+        }
+
+        for (name, value, count) in required {
+            let found = other_counts.get(&(name, value.clone())).cloned().unwrap_or(0);
+            if found < count {
+                violations.push(SubsetViolation::MissingValue {
+                    name,
+                    value: String::from_utf8_lossy(&value).into_owned(),
+                    required: count,
+                    found
+                });
+            }
+        }
+
+        violations
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderKind;
+    use ::headers::*;
+    use super::*;
+
+    fn base() -> HeaderMap {
+        headers! {
+            _From: ["me@example.com"],
+            Subject: "hy there",
+            Comments: "first",
+            Comments: "first"
+        }.unwrap()
+    }
+
+    test!(names_only {
+        let map = headers! {
+            Subject: "something else",
+            Comments: "other"
+        }?;
+        assert!(map.is_subset_of(&base(), SubsetMode::NamesOnly));
+
+        let map = headers! {
+            Subject: "hy there",
+            _To: ["you@example.com"]
+        }?;
+        assert!(!map.is_subset_of(&base(), SubsetMode::NamesOnly));
+        assert_eq!(map.subset_violations(&base(), SubsetMode::NamesOnly), vec![
+            SubsetViolation::MissingName { name: _To::name() }
+        ]);
+    });
+
+    test!(exact {
+        let map = headers! {
+            Subject: "hy there",
+            _From: ["me@example.com"]
+        }?;
+        assert!(map.is_subset_of(&base(), SubsetMode::Exact));
+
+        let map = headers! {
+            Subject: "hy you"
+        }?;
+        assert!(!map.is_subset_of(&base(), SubsetMode::Exact));
+        assert_eq!(map.subset_violations(&base(), SubsetMode::Exact), vec![
+            SubsetViolation::MissingValue {
+                name: Subject::name(),
+                value: "hy you".to_owned(),
+                required: 1,
+                found: 0
+            }
+        ]);
+    });
+
+    test!(exact_respects_multiplicity {
+        let twice = headers! {
+            Comments: "first",
+            Comments: "first"
+        }?;
+        assert!(twice.is_subset_of(&base(), SubsetMode::Exact));
+
+        let thrice = headers! {
+            Comments: "first",
+            Comments: "first",
+            Comments: "first"
+        }?;
+        assert!(thrice.is_subset_of(&base(), SubsetMode::NamesOnly));
+        assert_eq!(thrice.subset_violations(&base(), SubsetMode::Exact), vec![
+            SubsetViolation::MissingValue {
+                name: Comments::name(),
+                value: "first".to_owned(),
+                required: 3,
+                found: 2
+            }
+        ]);
+
+        // the other direction needs two in the other map, too
+        let once = headers! {
+            Comments: "first"
+        }?;
+        assert!(once.is_subset_of(&twice, SubsetMode::Exact));
+        assert!(!twice.is_subset_of(&once, SubsetMode::Exact));
+    });
+
+    test!(diagnostics_list_everything_once {
+        let map = headers! {
+            _To: ["you@example.com"],
+            Subject: "hy you",
+            _To: ["them@example.com"]
+        }?;
+        assert_eq!(map.subset_violations(&base(), SubsetMode::Exact), vec![
+            SubsetViolation::MissingName { name: _To::name() },
+            SubsetViolation::MissingValue {
+                name: Subject::name(),
+                value: "hy you".to_owned(),
+                required: 1,
+                found: 0
+            }
+        ]);
+    });
+
+    test!(empty_map_is_subset_of_everything {
+        let empty = HeaderMap::new();
+        assert!(empty.is_subset_of(&base(), SubsetMode::Exact));
+        assert!(empty.is_subset_of(&empty, SubsetMode::Exact));
+        assert!(!base().is_subset_of(&empty, SubsetMode::NamesOnly));
+    });
+}
@@ -0,0 +1,244 @@
+use ::header::{Header, HeaderKind, MaxOneMarker, HeaderObjTraitBoxExt};
+use ::error::HeaderTypeError;
+
+use super::HeaderMap;
+
+impl HeaderMap {
+
+    /// Removes all headers of the given kind and returns them typed.
+    ///
+    /// Unlike `remove` this hands ownership of the headers back, so they
+    /// can e.g. be moved into another map without cloning them. The
+    /// headers are returned in the order in which they appear in the map.
+    ///
+    /// # Error
+    ///
+    /// A `HeaderTypeError` is returned if any of the headers with the
+    /// name of `H` has a body of a different type. In that case the map
+    /// is not changed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::HeaderMap;
+    /// use mail_headers::headers::*;
+    ///
+    /// let mut map = headers! {
+    ///     Comments: "a",
+    ///     Subject: "hy there",
+    ///     Comments: "b"
+    /// }.unwrap();
+    ///
+    /// let mut other = HeaderMap::new();
+    /// for comment in map.take::<Comments>().unwrap() {
+    ///     other.insert(comment);
+    /// }
+    /// assert!(!map.contains(Comments));
+    /// assert_eq!(other.get(Comments).count(), 2);
+    /// # }
+    /// ```
+    pub fn take<H>(&mut self) -> Result<Vec<Header<H>>, HeaderTypeError>
+        where H: HeaderKind
+    {
+        if let Some(err) = self.kind_mismatch::<H>() {
+            return Err(err);
+        }
+
+        let taken = self.drain_name(H::name())
+            .map(|(_, obj)| {
+                let header = obj.downcast::<H>()
+                    .unwrap_or_else(|_| panic!("[BUG] header type changed while taking it"));
+                *header
+            })
+            .collect();
+        Ok(taken)
+    }
+
+    /// Splits the map into the typed headers of the given kind and all other headers.
+    ///
+    /// This is the consuming version of `take`, e.g. to pull all `Received`
+    /// headers out of a parsed map while continuing to use the rest of it.
+    /// The remaining map keeps the order, sequence numbers, limits and
+    /// validators of this map.
+    ///
+    /// # Error
+    ///
+    /// A `HeaderTypeError` is returned if any of the headers with the name
+    /// of `H` has a body of a different type. As the map is consumed use
+    /// `take` instead if the map is still needed in that case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::headers::*;
+    ///
+    /// let map = headers! {
+    ///     Comments: "a",
+    ///     Subject: "hy there",
+    ///     Comments: "b"
+    /// }.unwrap();
+    ///
+    /// let (comments, rest) = map.into_typed::<Comments>().unwrap();
+    /// assert_eq!(comments.len(), 2);
+    /// assert_eq!(rest.len(), 1);
+    /// assert!(rest.contains(Subject));
+    /// # }
+    /// ```
+    pub fn into_typed<H>(mut self) -> Result<(Vec<Header<H>>, HeaderMap), HeaderTypeError>
+        where H: HeaderKind
+    {
+        let taken = self.take::<H>()?;
+        Ok((taken, self))
+    }
+
+    /// Removes the header of the given kind and returns it typed.
+    ///
+    /// Returns `None` if the map doesn't contain the header.
+    ///
+    /// # Error
+    ///
+    /// Like `_get_single` a `HeaderTypeError` is returned if the map
+    /// contains more than one header with the name of `H` or if the
+    /// body has a different type. In that case the map is not changed.
+    pub fn take_single<H>(&mut self) -> Option<Result<Header<H>, HeaderTypeError>>
+        where H: MaxOneMarker
+    {
+        if self.get_untyped(H::name()).len() > 1 {
+            return Some(Err(HeaderTypeError::new(H::name())));
+        }
+
+        match self.take::<H>() {
+            Ok(mut taken) => taken.pop().map(Ok),
+            Err(err) => Some(Err(err))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderKind;
+    use ::headers::*;
+    use ::map::MapLimits;
+    use super::*;
+
+    use self::raw_headers::{
+        Comments as RawComments,
+        Subject as RawSubject
+    };
+
+    mod raw_headers {
+        use ::header_components;
+        def_headers! {
+            test_name: validate_header_names,
+            scope: header_components,
+            Comments, unchecked { "Comments" }, RawUnstructured, multi, None,
+            // multi so that it can be added to a map which already contains a `Subject`
+            Subject, unchecked { "Subject" }, RawUnstructured, multi, None
+        }
+    }
+
+    test!(take_moves_headers_into_other_map {
+        let mut map = headers! {
+            Comments: "a",
+            _From: ["me@example.com"],
+            Comments: "b"
+        }?;
+
+        let comments = map.take::<Comments>()?;
+        let texts = comments.iter().map(|header| header.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, vec!["a", "b"]);
+        assert!(!map.contains(Comments));
+        assert_eq!(map.len(), 1);
+
+        let mut other = HeaderMap::new();
+        for comment in comments {
+            other.insert(comment);
+        }
+        assert_eq!(other.get(Comments).count(), 2);
+
+        assert_eq!(map.take::<Comments>()?.len(), 0);
+    });
+
+    test!(take_single_pop_and_reinsert_modified {
+        let mut map = headers! {
+            _From: ["me@example.com"],
+            Subject: "hy there"
+        }?;
+
+        let mut subject = map.take_single::<Subject>().unwrap()?;
+        assert!(!map.contains(Subject));
+        *subject.body_mut() = "Re: hy there".into();
+        map.insert(subject);
+
+        assert_eq!(map.get_single(Subject).unwrap()?.as_str(), "Re: hy there");
+        assert!(map.take_single::<Sender>().is_none());
+    });
+
+    test!(mismatching_type_is_an_error_and_keeps_map {
+        let mut map = headers! {
+            Comments: "a",
+            RawComments: "b"
+        }?;
+
+        let err = assert_err!(map.take::<Comments>());
+        assert_eq!(err.to_string(), HeaderTypeError::new(Comments::name()).to_string());
+        assert_eq!(map.len(), 2);
+    });
+
+    test!(take_single_rejects_multiple_headers {
+        let mut map = HeaderMap::new();
+        map.insert(Subject::auto_body("a")?);
+        map.insert(RawSubject::auto_body("b")?);
+        assert_eq!(map.len(), 2);
+
+        assert_err!(map.take_single::<Subject>().unwrap());
+        assert_eq!(map.len(), 2);
+    });
+
+    test!(into_typed_splits_the_map {
+        let map = headers! {
+            Comments: "a",
+            _From: ["me@example.com"],
+            Comments: "b",
+            Subject: "hy there"
+        }?;
+
+        let (comments, rest) = map.into_typed::<Comments>()?;
+        let texts = comments.iter().map(|header| header.as_str()).collect::<Vec<_>>();
+        assert_eq!(texts, vec!["a", "b"]);
+        let names = rest.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["From", "Subject"]);
+
+        let (senders, rest) = rest.into_typed::<Sender>()?;
+        assert!(senders.is_empty());
+        assert_eq!(rest.len(), 2);
+    });
+
+    test!(into_typed_rejects_mismatching_type {
+        let map = headers! {
+            Comments: "a",
+            RawComments: "b"
+        }?;
+
+        let err = assert_err!(map.into_typed::<Comments>());
+        assert_eq!(err.to_string(), HeaderTypeError::new(Comments::name()).to_string());
+    });
+
+    test!(take_frees_limited_bytes {
+        let mut map = HeaderMap::with_limits(MapLimits {
+            max_bytes: Some(40),
+            ..Default::default()
+        });
+        map.try_insert(Comments::auto_body("some comment")?)?;
+        assert_err!(map.try_insert(Comments::auto_body("some comment")?));
+
+        assert_eq!(map.take::<Comments>()?.len(), 1);
+        map.try_insert(Comments::auto_body("some comment")?)?;
+    });
+}
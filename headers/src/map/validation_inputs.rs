@@ -0,0 +1,373 @@
+use ::error::{HeaderValidationError, ValidationErrors};
+use ::header::HeaderObj;
+use ::header_components::{Email, MessageId};
+use ::headers::{
+    _From, Sender, ReplyTo, _To, Cc, Bcc,
+    ResentFrom, ResentSender, ResentTo, ResentCc, ResentBcc,
+    ReturnPath, MessageId as MessageIdHeader, InReplyTo, References, ResentMsgId,
+    Subject
+};
+use ::name::{HeaderName, HasHeaderName};
+
+use super::HeaderMap;
+
+/// Describes which data `HeaderMap::extract_validation_inputs` extracts.
+///
+/// Only headers of the build-in header kinds of this crate are considered,
+/// headers with a listed name but a different implementation are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ValidationInputSpec {
+    /// Extract the addresses of the headers with this names.
+    ///
+    /// Supported are the mailbox (list) headers (e.g. `To`, `Sender`,
+    /// `Resent-Bcc`) and `Return-Path`.
+    pub addresses: Vec<HeaderName>,
+
+    /// Extract the message ids of the headers with this names.
+    ///
+    /// Supported are `Message-Id`, `In-Reply-To`, `References` and `Resent-Msg-Id`.
+    pub message_ids: Vec<HeaderName>,
+
+    /// Extract the text of the `Subject` header.
+    pub subject: bool
+}
+
+impl ValidationInputSpec {
+
+    /// Creates a spec which doesn't extract anything.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates a spec extracting the addresses of `To`, `Cc` and `Bcc`.
+    pub fn recipients() -> Self {
+        ValidationInputSpec::new()
+            .with_addresses(_To)
+            .with_addresses(Cc)
+            .with_addresses(Bcc)
+    }
+
+    /// Also extract the addresses of the header with given name.
+    pub fn with_addresses<H: HasHeaderName>(mut self, name: H) -> Self {
+        let name = name.get_name();
+        if !self.addresses.contains(&name) {
+            self.addresses.push(name);
+        }
+        self
+    }
+
+    /// Also extract the message ids of the header with given name.
+    pub fn with_message_ids<H: HasHeaderName>(mut self, name: H) -> Self {
+        let name = name.get_name();
+        if !self.message_ids.contains(&name) {
+            self.message_ids.push(name);
+        }
+        self
+    }
+
+    /// Also extract the text of the `Subject` header.
+    pub fn with_subject(mut self) -> Self {
+        self.subject = true;
+        self
+    }
+}
+
+/// The data extracted by `HeaderMap::extract_validation_inputs`.
+///
+/// All data is owned so it can be moved into a (async) task independent of the map.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ValidationInputs {
+    /// The extracted addresses with the name of the header they appear in (in map order).
+    pub addresses: Vec<(HeaderName, Email)>,
+
+    /// The extracted message ids with the name of the header they appear in (in map order).
+    pub message_ids: Vec<(HeaderName, MessageId)>,
+
+    /// The text of the `Subject` header, if requested and present.
+    pub subject: Option<String>
+}
+
+impl ValidationInputs {
+
+    /// Returns the addresses extracted from headers with given name.
+    pub fn addresses_of<'a, H: HasHeaderName>(&'a self, name: H)
+        -> impl Iterator<Item=&'a Email> + 'a
+    {
+        let name = name.get_name();
+        self.addresses.iter()
+            .filter(move |&&(other, _)| other == name)
+            .map(|&(_, ref email)| email)
+    }
+
+    /// Returns the message ids extracted from headers with given name.
+    pub fn message_ids_of<'a, H: HasHeaderName>(&'a self, name: H)
+        -> impl Iterator<Item=&'a MessageId> + 'a
+    {
+        let name = name.get_name();
+        self.message_ids.iter()
+            .filter(move |&&(other, _)| other == name)
+            .map(|&(_, ref id)| id)
+    }
+}
+
+impl HeaderMap {
+
+    /// Extracts the data described by the spec from this map.
+    ///
+    /// Validators are plain functions (`HeaderMapValidator`), so they can not
+    /// do any (async) I/O. If a check needs external data, e.g. a lookup of
+    /// the recipients in a suppression list, the recommended pattern is to:
+    ///
+    /// 1. extract the needed data with this method (the result is owned and
+    ///    can be moved into a async task),
+    /// 2. do the I/O outside of the map, e.g. in the async task,
+    /// 3. turn the result into a synchronous check and run it together with
+    ///    the validators of the map using `validate_with`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # extern crate failure;
+    /// # fn main() {
+    /// use mail_headers::HeaderMap;
+    /// use mail_headers::headers::*;
+    /// use mail_headers::error::HeaderValidationError;
+    /// use mail_headers::map::ValidationInputSpec;
+    ///
+    /// let map = headers! {
+    ///     _From: ["me@example.com"],
+    ///     _To: ["bounced@example.com", "ok@example.com"]
+    /// }.unwrap();
+    ///
+    /// let inputs = map.extract_validation_inputs(&ValidationInputSpec::recipients());
+    ///
+    /// // e.g. `let suppressed = suppression_list.lookup(inputs).await;`
+    /// let suppressed = inputs.addresses_of(_To)
+    ///     .any(|email| email.to_string() == "bounced@example.com");
+    ///
+    /// let suppression_check = |_: &HeaderMap| if suppressed {
+    ///     Err(HeaderValidationError::Custom(failure::err_msg("recipient is suppressed")))
+    /// } else {
+    ///     Ok(())
+    /// };
+    /// assert!(map.validate_with(&[&suppression_check]).is_err());
+    /// # }
+    /// ```
+    pub fn extract_validation_inputs(&self, spec: &ValidationInputSpec) -> ValidationInputs {
+        let mut inputs = ValidationInputs::default();
+        for (name, obj) in self.iter() {
+            if spec.addresses.contains(&name) {
+                extract_addresses(name, obj, &mut inputs.addresses);
+            }
+            if spec.message_ids.contains(&name) {
+                extract_message_ids(name, obj, &mut inputs.message_ids);
+            }
+        }
+
+        if spec.subject {
+            if let Some(Ok(subject)) = self.get_single(Subject) {
+                inputs.subject = Some(subject.as_str().to_owned());
+            }
+        }
+        inputs
+    }
+
+    /// Runs `validate` and then each of the given (synchronous) checks.
+    ///
+    /// This allows running ad-hoc checks which can not be added with
+    /// `add_validator` (e.g. closures capturing the result of a async lookup,
+    /// see `extract_validation_inputs`) together with the validators of
+    /// the map.
+    ///
+    /// # Error
+    ///
+    /// All checks are run, even if `validate` or a previous check failed.
+    /// If exactly one of them fails its error is returned, if multiple of
+    /// them fail a `HeaderValidationError::Custom` with a `ValidationErrors`
+    /// containing all errors (in the order the checks were run) is returned.
+    pub fn validate_with(&self, extra: &[&Fn(&HeaderMap) -> Result<(), HeaderValidationError>])
+        -> Result<(), HeaderValidationError>
+    {
+        let mut errors = Vec::new();
+        if let Err(err) = self.validate() {
+            errors.push(err);
+        }
+        for check in extra {
+            if let Err(err) = check(self) {
+                errors.push(err);
+            }
+        }
+
+        if errors.len() > 1 {
+            Err(HeaderValidationError::Custom(ValidationErrors::new(errors).into()))
+        } else {
+            errors.pop().map_or(Ok(()), Err)
+        }
+    }
+}
+
+fn extract_addresses(name: HeaderName, obj: &HeaderObj, out: &mut Vec<(HeaderName, Email)>) {
+    macro_rules! try_kinds {
+        ($($kind:ident),*) => ($(
+            if let Some(header) = obj.downcast_ref::<$kind>() {
+                out.extend(header.body().0.iter().map(|mailbox| (name, mailbox.email.clone())));
+                return;
+            }
+        )*);
+    }
+
+    try_kinds!(_From, ReplyTo, _To, Cc, Bcc, ResentFrom, ResentTo, ResentCc, ResentBcc);
+    if let Some(header) = obj.downcast_ref::<Sender>() {
+        out.push((name, header.body().email.clone()));
+    } else if let Some(header) = obj.downcast_ref::<ResentSender>() {
+        out.push((name, header.body().email.clone()));
+    } else if let Some(header) = obj.downcast_ref::<ReturnPath>() {
+        if let Some(ref email) = header.body().0 {
+            out.push((name, email.clone()));
+        }
+    }
+}
+
+fn extract_message_ids(name: HeaderName, obj: &HeaderObj, out: &mut Vec<(HeaderName, MessageId)>) {
+    if let Some(header) = obj.downcast_ref::<MessageIdHeader>() {
+        out.push((name, header.body().clone()));
+    } else if let Some(header) = obj.downcast_ref::<ResentMsgId>() {
+        out.push((name, header.body().clone()));
+    } else if let Some(header) = obj.downcast_ref::<InReplyTo>() {
+        out.extend(header.body().0.iter().map(|id| (name, id.clone())));
+    } else if let Some(header) = obj.downcast_ref::<References>() {
+        out.extend(header.body().0.iter().map(|id| (name, id.clone())));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use failure;
+
+    use ::error::{HeaderValidationError, BuildInValidationError, ValidationErrors};
+    use ::header::Header;
+    use ::header_components::{MessageIdList, MessageId as MessageIdComponent};
+    use ::headers::*;
+    use ::headers::MessageId;
+    use ::{HeaderMap, HeaderTryFrom};
+    use super::*;
+
+    fn emails<'a, I: Iterator<Item=&'a Email>>(iter: I) -> Vec<String> {
+        iter.map(|email| email.to_string()).collect()
+    }
+
+    fn fails(message: &'static str) -> impl Fn(&HeaderMap) -> Result<(), HeaderValidationError> {
+        move |_| Err(HeaderValidationError::Custom(failure::err_msg(message)))
+    }
+
+    fn succeeds(_: &HeaderMap) -> Result<(), HeaderValidationError> {
+        Ok(())
+    }
+
+    test!(extract_addresses {
+        let map = headers! {
+            _From: ["me@example.com"],
+            _To: [("Bob", "bob@example.com"), "carl@example.com"],
+            Sender: "sender@example.com",
+            Cc: ["dan@example.com"],
+            Subject: "hy"
+        }?;
+
+        let inputs = map.extract_validation_inputs(&ValidationInputSpec::recipients());
+        assert_eq!(emails(inputs.addresses_of(_To)), vec!["bob@example.com", "carl@example.com"]);
+        assert_eq!(emails(inputs.addresses_of(Cc)), vec!["dan@example.com"]);
+        assert_eq!(inputs.addresses.len(), 3);
+        assert!(inputs.message_ids.is_empty());
+        assert_eq!(inputs.subject, None);
+
+        let spec = ValidationInputSpec::new().with_addresses(Sender).with_subject();
+        let inputs = map.extract_validation_inputs(&spec);
+        assert_eq!(emails(inputs.addresses_of(Sender)), vec!["sender@example.com"]);
+        assert_eq!(inputs.addresses.len(), 1);
+        assert_eq!(inputs.subject, Some("hy".to_owned()));
+    });
+
+    test!(extract_message_ids {
+        let mut map = headers! {
+            MessageId: "a@example.com"
+        }?;
+        map.insert(Header::<InReplyTo>::new(MessageIdList(vec1![
+            MessageIdComponent::try_from("b@example.com")?,
+            MessageIdComponent::try_from("c@example.com")?
+        ])));
+        map.insert(Header::<References>::new(MessageIdList(vec1![
+            MessageIdComponent::try_from("b@example.com")?
+        ])));
+
+        let spec = ValidationInputSpec::new()
+            .with_message_ids(MessageId)
+            .with_message_ids(InReplyTo);
+        let inputs = map.extract_validation_inputs(&spec);
+
+        let ids = inputs.message_ids.iter()
+            .map(|&(name, ref id)| (name.as_str(), id.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![
+            ("Message-Id", "a@example.com"),
+            ("In-Reply-To", "b@example.com"),
+            ("In-Reply-To", "c@example.com")
+        ]);
+        assert_eq!(inputs.message_ids_of(References).count(), 0);
+        assert!(inputs.addresses.is_empty());
+    });
+
+    test!(validate_with_runs_validators_and_checks {
+        let map = headers! {
+            _From: ["me@example.com"],
+            Subject: "hy"
+        }?;
+
+        let has_subject = |map: &HeaderMap| if map.contains(Subject) {
+            Ok(())
+        } else {
+            Err(HeaderValidationError::Custom(failure::err_msg("no subject")))
+        };
+        assert_ok!(map.validate_with(&[&has_subject, &succeeds]));
+        assert_ok!(map.validate_with(&[]));
+
+        let err = assert_err!(map.validate_with(&[&succeeds, &fails("second")]));
+        assert_eq!(err.to_string(), "second");
+
+        // the build-in validators are run, too
+        let map = headers! {
+            _From: ["a@example.com", "b@example.com"]
+        }?;
+        match assert_err!(map.validate_with(&[&has_subject, &succeeds])) {
+            HeaderValidationError::Custom(err) => {
+                let errors = err.downcast::<ValidationErrors>().expect("unexpected custom error");
+                assert_eq!(errors.errors().len(), 2);
+            },
+            other => panic!("unexpected error: {:?}", other)
+        }
+        match assert_err!(map.validate_with(&[&succeeds])) {
+            HeaderValidationError::BuildIn(ctx) => {
+                assert_eq!(*ctx.get_context(), BuildInValidationError::MultiMailboxFromWithoutSender);
+            },
+            other => panic!("unexpected error: {:?}", other)
+        }
+    });
+
+    test!(validate_with_aggregates_errors {
+        let map = headers! {
+            _From: ["me@example.com"]
+        }?;
+
+        let err = assert_err!(map.validate_with(&[&fails("first"), &succeeds, &fails("second")]));
+        let errors = match err {
+            HeaderValidationError::Custom(err) => {
+                err.downcast::<ValidationErrors>().expect("unexpected custom error")
+            },
+            other => panic!("unexpected error: {:?}", other)
+        };
+        let messages = errors.errors().iter().map(|err| err.to_string()).collect::<Vec<_>>();
+        assert_eq!(messages, vec!["first", "second"]);
+        assert_eq!(errors.to_string(), "2 validation errors: first; second");
+    });
+}
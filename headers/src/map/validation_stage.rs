@@ -0,0 +1,283 @@
+use super::{HeaderMap, HeaderMapValidator, ValidatorHashWrapper};
+
+/// The stage of a message a `HeaderMap` is validated for.
+///
+/// A map is often build up step by step (e.g. from a template, by a
+/// editor or by multiple services) and only complete once it is about to
+/// be send. Checks which make sure a map is *consistent* (e.g. no "max one"
+/// header appearing twice, no header using an unexpected component) apply
+/// to such a draft, but checks which make sure a map is *complete* (e.g.
+/// a `Sender` for a `From` with multiple mailboxes or a `Resent-Date` in
+/// each resent block) only apply to the final message.
+///
+/// See `HeaderMap::use_contextual_validators_staged` and
+/// `HeaderMap::validate_staged`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ValidationStage {
+    /// A message which is still build up, only structural checks are done.
+    Draft,
+    /// A message which is about to be send, all checks are done (the default).
+    Final
+}
+
+impl Default for ValidationStage {
+    fn default() -> Self {
+        ValidationStage::Final
+    }
+}
+
+/// The stages a validator added with `HeaderMap::add_staged_validator` is run in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ValidatorStages {
+    /// Only run the validator for final messages (the default, used by `add_validator`).
+    FinalOnly,
+    /// Run the validator for drafts and final messages.
+    AllStages
+}
+
+impl Default for ValidatorStages {
+    fn default() -> Self {
+        ValidatorStages::FinalOnly
+    }
+}
+
+impl ValidatorStages {
+
+    /// Returns true if a validator with this stages is run in the given stage.
+    pub fn applies_to(&self, stage: ValidationStage) -> bool {
+        match *self {
+            ValidatorStages::FinalOnly => stage == ValidationStage::Final,
+            ValidatorStages::AllStages => true
+        }
+    }
+}
+
+impl HeaderMap {
+
+    /// Adds a validator to this map which is run in the given stages.
+    ///
+    /// This is `add_validator` for validators which (also) check the
+    /// consistency of drafts. Adding a validator which was already added
+    /// has no effect, except that it is run in all stages if it is added
+    /// with `AllStages` once.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # extern crate failure;
+    /// # fn main() {
+    /// use mail_headers::HeaderMap;
+    /// use mail_headers::headers::*;
+    /// use mail_headers::error::HeaderValidationError;
+    /// use mail_headers::map::{ValidationStage, ValidatorStages};
+    ///
+    /// fn no_comments(map: &HeaderMap) -> Result<(), HeaderValidationError> {
+    ///     if map.contains(Comments) {
+    ///         Err(HeaderValidationError::Custom(failure::err_msg("Comments are not allowed")))
+    ///     } else {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// // the `Sender` is still missing, which is fine for a draft
+    /// let mut map = headers! {
+    ///     _From: ["a@example.com", "b@example.com"],
+    ///     Comments: "to be removed"
+    /// }.unwrap();
+    /// map.add_staged_validator(no_comments, ValidatorStages::AllStages);
+    /// assert!(map.validate_staged(ValidationStage::Draft).is_err());
+    ///
+    /// map.remove(Comments);
+    /// assert!(map.validate_staged(ValidationStage::Draft).is_ok());
+    /// assert!(map.validate_staged(ValidationStage::Final).is_err());
+    /// # }
+    /// ```
+    pub fn add_staged_validator(&mut self, validator: HeaderMapValidator, stages: ValidatorStages) {
+        let wrapped = ValidatorHashWrapper(validator);
+        let existing = self.validators.iter()
+            .position(|&other| ValidatorHashWrapper(other) == wrapped);
+
+        match existing {
+            Some(idx) => {
+                if stages == ValidatorStages::AllStages {
+                    self.validator_stages[idx] = stages;
+                }
+            },
+            None => {
+                self.validators.push(validator);
+                self.validator_stages.push(stages);
+            }
+        }
+    }
+
+    /// Returns the stages the given validator (added to this map) is run in.
+    ///
+    /// Returns `None` if the validator was not added with `add_validator`
+    /// or `add_staged_validator`.
+    pub fn validator_stages(&self, validator: HeaderMapValidator) -> Option<ValidatorStages> {
+        let wrapped = ValidatorHashWrapper(validator);
+        self.validators.iter()
+            .position(|&other| ValidatorHashWrapper(other) == wrapped)
+            .map(|idx| self.validator_stages[idx])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderKind;
+    use ::headers::*;
+    use ::header_components::DateTime;
+    use ::error::{HeaderValidationError, BuildInValidationError};
+    use ::map::ValidatorOptions;
+    use super::*;
+    use super::ValidationStage::*;
+
+    mod bad_headers {
+        use ::header_components;
+        use ::headers::validators::from as validator_from;
+
+        def_headers! {
+            test_name: validate_header_names,
+            scope: header_components,
+            BadFrom, unchecked { "From" }, Unstructured, maxOne, validator_from,
+            MultiSubject, unchecked { "Subject" }, Unstructured, multi, None
+        }
+    }
+    use self::bad_headers::{BadFrom, MultiSubject};
+
+    fn build_in_error(res: Result<(), HeaderValidationError>) -> BuildInValidationError {
+        match assert_err!(res) {
+            HeaderValidationError::BuildIn(ctx) => *ctx.get_context(),
+            other => panic!("unexpected error: {:?}", other)
+        }
+    }
+
+    fn subject_required(map: &HeaderMap) -> Result<(), HeaderValidationError> {
+        if map.contains(Subject) {
+            Ok(())
+        } else {
+            Err(HeaderValidationError::Custom(::failure::err_msg("Subject is required")))
+        }
+    }
+
+    fn comments_forbidden(map: &HeaderMap) -> Result<(), HeaderValidationError> {
+        if map.contains(Comments) {
+            Err(HeaderValidationError::Custom(::failure::err_msg("Comments are forbidden")))
+        } else {
+            Ok(())
+        }
+    }
+
+    test!(incomplete_draft_only_fails_final {
+        let mut map = headers! {
+            _From: ["a@example.com", "b@example.com"]
+        }?;
+        map.add_validator(subject_required);
+
+        assert_ok!(map.use_contextual_validators_staged(Draft));
+        assert_ok!(map.validate_staged(Draft));
+        assert_eq!(
+            build_in_error(map.validate_staged(Final)),
+            BuildInValidationError::MultiMailboxFromWithoutSender
+        );
+
+        map.insert(Sender::auto_body("a@example.com")?);
+        match assert_err!(map.validate()) {
+            HeaderValidationError::Custom(_) => {},
+            other => panic!("unexpected error: {:?}", other)
+        }
+
+        map.insert(Subject::auto_body("hy there")?);
+        assert_ok!(map.validate_staged(Final));
+    });
+
+    test!(resent_block_without_date_only_fails_final {
+        let mut map = headers! {
+            ResentFrom: ["a@example.com"],
+            Subject: "hy there"
+        }?;
+        assert_ok!(map.validate_staged(Draft));
+        assert_eq!(
+            build_in_error(map.validate_staged(Final)),
+            BuildInValidationError::ResentDateFieldMissing
+        );
+
+        map.prepend(ResentDate::auto_body(DateTime::now())?);
+        assert_ok!(map.validate_staged(Final));
+    });
+
+    test!(broken_maps_fail_all_stages {
+        let mut map = headers! {
+            Subject: "one",
+            MultiSubject: "two"
+        }?;
+        for &stage in &[Draft, Final] {
+            assert_eq!(
+                build_in_error(map.use_contextual_validators_staged(stage)),
+                BuildInValidationError::MaxOneInconsistency { header_name: "Subject" }
+            );
+        }
+
+        let first = headers! { Subject: "one" }?;
+        map = headers! { Subject: "two" }?;
+        map.combine_with(first, ::map::MergePolicy::AppendAll);
+        for &stage in &[Draft, Final] {
+            assert_eq!(
+                build_in_error(map.validate_staged(stage)),
+                BuildInValidationError::MoreThenOne { header_name: "Subject" }
+            );
+        }
+
+        map = headers! { BadFrom: "not a mailbox list" }?;
+        for &stage in &[Draft, Final] {
+            assert_eq!(
+                build_in_error(map.validate_staged(stage)),
+                BuildInValidationError::UnexpectedComponentType { header_name: "From" }
+            );
+        }
+
+        map.set_validator_options(ValidatorOptions::lenient());
+        assert_ok!(map.validate_staged(Draft));
+        assert_ok!(map.validate_staged(Final));
+    });
+
+    test!(all_stages_validators_run_for_drafts {
+        let mut map = headers! {
+            Comments: "oh no"
+        }?;
+        map.add_validator(comments_forbidden);
+        assert_ok!(map.validate_staged(Draft));
+        assert_err!(map.validate_staged(Final));
+
+        map.add_staged_validator(comments_forbidden, ValidatorStages::AllStages);
+        assert_eq!(map.validators().len(), 1);
+        assert_eq!(map.validator_stages(comments_forbidden), Some(ValidatorStages::AllStages));
+        assert_err!(map.validate_staged(Draft));
+        assert_err!(map.validate_staged(Final));
+
+        // adding it again without the opt-in keeps it in all stages
+        map.add_validator(comments_forbidden);
+        assert_eq!(map.validator_stages(comments_forbidden), Some(ValidatorStages::AllStages));
+        assert_eq!(map.validator_stages(subject_required), None);
+    });
+
+    test!(validator_stages_survive_insert_all {
+        let mut other = HeaderMap::new();
+        other.add_staged_validator(comments_forbidden, ValidatorStages::AllStages);
+        other.add_validator(subject_required);
+
+        let mut map = headers! {
+            Comments: "oh no"
+        }?;
+        map.insert_all(other);
+        assert_eq!(map.validator_stages(comments_forbidden), Some(ValidatorStages::AllStages));
+        assert_eq!(map.validator_stages(subject_required), Some(ValidatorStages::FinalOnly));
+        assert_err!(map.clone().validate_staged(Draft));
+
+        map.clear();
+        assert_eq!(map.validator_stages(comments_forbidden), None);
+        assert_ok!(map.validate_staged(Draft));
+    });
+}
@@ -0,0 +1,246 @@
+use ::name::HeaderName;
+use ::header::HeaderObjTrait;
+
+use super::HeaderMap;
+
+/// Which contextual validators of the headers apply to a map derived from another map.
+///
+/// Each header kind can provide a contextual validator (e.g. `_From`
+/// requires a `Sender` if it has multiple mailboxes), which is run by
+/// `use_contextual_validators` for every map containing such a header.
+/// This is not always wanted for maps derived from a mail's header map
+/// which are never encoded as a mail themselves (e.g. a map of metadata
+/// only containing the `From` header).
+///
+/// The policy is set on a map with `HeaderMap::set_validator_carry_policy`
+/// or passed to the operations deriving a map from another map, like
+/// `clone_only`, `split_off_carrying`, `public_headers_carrying` and
+/// `RedactionPolicy::carry_validators`. It only affects the validators
+/// of the headers, the validators added with `add_validator` and the
+/// max one checks are not affected.
+///
+/// A validator provided by multiple header kinds (e.g. the one of the
+/// `Resent-*` headers) is run if at least one of the headers providing it
+/// is carried.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ValidatorCarryPolicy {
+    /// Run the validators of all headers (the default).
+    CarryAll,
+    /// Run the validators of no header.
+    CarryNone,
+    /// Only run the validators of headers with one of the given names.
+    CarryOnlyFor(Vec<HeaderName>)
+}
+
+impl Default for ValidatorCarryPolicy {
+    fn default() -> Self {
+        ValidatorCarryPolicy::CarryAll
+    }
+}
+
+impl ValidatorCarryPolicy {
+
+    /// Returns true if the validator of headers with given name is run.
+    pub fn carries(&self, name: HeaderName) -> bool {
+        use self::ValidatorCarryPolicy::*;
+        match *self {
+            CarryAll => true,
+            CarryNone => false,
+            CarryOnlyFor(ref names) => names.contains(&name)
+        }
+    }
+
+    /// Returns a policy only carrying validators carried by both policies.
+    ///
+    /// A map derived from a map which doesn't carry some validators
+    /// doesn't carry them either, independent of the policy passed to
+    /// the deriving operation.
+    pub fn restrict(&self, other: &ValidatorCarryPolicy) -> ValidatorCarryPolicy {
+        use self::ValidatorCarryPolicy::*;
+        match (self, other) {
+            (&CarryNone, _) | (_, &CarryNone) => CarryNone,
+            (&CarryAll, policy) | (policy, &CarryAll) => policy.clone(),
+            (&CarryOnlyFor(ref names), &CarryOnlyFor(ref other_names)) => {
+                CarryOnlyFor(names.iter()
+                    .filter(|name| other_names.contains(name))
+                    .cloned()
+                    .collect())
+            }
+        }
+    }
+}
+
+impl HeaderMap {
+
+    /// Returns the policy deciding which validators of the headers are run.
+    pub fn validator_carry_policy(&self) -> &ValidatorCarryPolicy {
+        &self.validator_carry
+    }
+
+    /// Sets the policy deciding which validators of the headers are run.
+    ///
+    /// The policy is used by `use_contextual_validators` (and `validate`)
+    /// and is kept by `clone`. Maps derived from this map using e.g.
+    /// `clone_only` carry at most the validators carried by this map.
+    pub fn set_validator_carry_policy(&mut self, policy: ValidatorCarryPolicy) {
+        self.validator_carry = policy;
+    }
+
+    /// Sets the policy of a map derived from this map.
+    ///
+    /// Used by all operations deriving a map from another map.
+    pub(super) fn carry_validators_into(&self, derived: &mut HeaderMap, policy: &ValidatorCarryPolicy) {
+        derived.validator_carry = self.validator_carry.restrict(policy);
+    }
+
+    /// Returns a copy of this map only containing the headers with one of the given names.
+    ///
+    /// The headers keep their order, sequence numbers and labels. The
+    /// returned map has no limits and no validators added to it, the
+    /// validators of its headers are run as given by `carry`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::HeaderKind;
+    /// use mail_headers::headers::*;
+    /// use mail_headers::map::ValidatorCarryPolicy;
+    ///
+    /// let map = headers! {
+    ///     _From: ["a@example.com", "b@example.com"],
+    ///     Sender: "a@example.com",
+    ///     Subject: "hy there"
+    /// }.unwrap();
+    ///
+    /// // the `From` header needs a `Sender`, which is not in the metadata
+    /// let metadata = map.clone_only(&[_From::name()], ValidatorCarryPolicy::CarryNone);
+    /// assert_eq!(metadata.len(), 1);
+    /// assert!(metadata.use_contextual_validators().is_ok());
+    /// # }
+    /// ```
+    pub fn clone_only(&self, names: &[HeaderName], carry: ValidatorCarryPolicy) -> HeaderMap {
+        let mut clone = HeaderMap::new();
+        for ((name, obj), &seq) in self.inner_map.iter().zip(self.seqs.iter()) {
+            if names.contains(&name) {
+                clone.inner_map.add(name, obj.boxed_clone());
+                clone.seqs.push(seq);
+                clone.copy_label(&self.labels, seq);
+            }
+        }
+        clone.max_seq = self.max_seq;
+        self.carry_validators_into(&mut clone, &carry);
+        clone
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderKind;
+    use ::headers::*;
+    use ::header_components::DateTime;
+    use ::map::{RedactionPolicy, PublicHeadersOptions};
+    use super::*;
+    use super::ValidatorCarryPolicy::*;
+
+    fn multi_from_map() -> HeaderMap {
+        headers! {
+            _From: ["a@example.com", "b@example.com"],
+            Sender: "a@example.com",
+            ResentDate: DateTime::now(),
+            ResentFrom: ["c@example.com", "d@example.com"],
+            ResentSender: "c@example.com",
+            Subject: "hy there"
+        }.unwrap()
+    }
+
+    test!(clone_only_with_carry_none_skips_validators {
+        let map = multi_from_map();
+        assert_ok!(map.use_contextual_validators());
+
+        let metadata = map.clone_only(&[_From::name()], CarryNone);
+        assert_eq!(metadata.len(), 1);
+        assert_ok!(metadata.use_contextual_validators());
+
+        let metadata = map.clone_only(&[_From::name()], CarryAll);
+        assert_err!(metadata.use_contextual_validators());
+    });
+
+    test!(carry_only_for_given_names {
+        let map = multi_from_map();
+        let names = &[_From::name(), ResentFrom::name(), Subject::name()];
+
+        // `From` lacks its `Sender` and the resent block its `Resent-Date`
+        let only_from = CarryOnlyFor(vec![_From::name()]);
+        let only_resent = CarryOnlyFor(vec![ResentFrom::name()]);
+        let only_subject = CarryOnlyFor(vec![Subject::name()]);
+
+        assert_err!(map.clone_only(names, only_from.clone()).use_contextual_validators());
+        assert_err!(map.clone_only(names, only_resent.clone()).use_contextual_validators());
+        assert_ok!(map.clone_only(names, only_subject.clone()).use_contextual_validators());
+
+        let split_off = |policy| {
+            let mut map = multi_from_map();
+            map.split_off_carrying(|name, _| names.contains(&name), policy)
+        };
+        assert_err!(split_off(only_from).use_contextual_validators());
+        assert_err!(split_off(only_resent).use_contextual_validators());
+        assert_ok!(split_off(only_subject).use_contextual_validators());
+    });
+
+    test!(redact_for_storage_carries_validators_by_policy {
+        let map = multi_from_map();
+        let policy = RedactionPolicy::new().redact(Sender);
+        assert_err!(map.redact_for_storage(&policy).use_contextual_validators());
+
+        let policy = policy.carry_validators(CarryOnlyFor(vec![ResentFrom::name()]));
+        let redacted = map.redact_for_storage(&policy);
+        assert!(!redacted.contains(Sender));
+        assert_ok!(redacted.use_contextual_validators());
+    });
+
+    test!(public_headers_carries_validators_by_policy {
+        let map = headers! {
+            _From: ["a@example.com", "b@example.com"],
+            Bcc: ["hidden@example.com"]
+        }?;
+        let opts = PublicHeadersOptions::default();
+        assert_err!(map.public_headers().use_contextual_validators());
+        let public = map.public_headers_carrying(opts, CarryNone);
+        assert_eq!(public.len(), 1);
+        assert_ok!(public.use_contextual_validators());
+    });
+
+    test!(derived_maps_do_not_carry_more_than_the_source {
+        let mut map = multi_from_map();
+        map.set_validator_carry_policy(CarryOnlyFor(vec![ResentFrom::name(), Subject::name()]));
+        let names = &[_From::name(), Subject::name()];
+
+        let derived = map.clone_only(names, CarryAll);
+        assert_eq!(derived.validator_carry_policy(), &CarryOnlyFor(vec![ResentFrom::name(), Subject::name()]));
+        assert_ok!(derived.use_contextual_validators());
+
+        let derived = map.clone_only(names, CarryOnlyFor(vec![_From::name(), Subject::name()]));
+        assert_eq!(derived.validator_carry_policy(), &CarryOnlyFor(vec![Subject::name()]));
+
+        map.set_validator_carry_policy(CarryNone);
+        assert_eq!(map.clone_only(names, CarryAll).validator_carry_policy(), &CarryNone);
+        assert_eq!(map.clone().validator_carry_policy(), &CarryNone);
+    });
+
+    test!(map_level_policy_skips_flagged_headers {
+        let mut map = headers! {
+            _From: ["a@example.com", "b@example.com"]
+        }?;
+        assert_err!(map.use_contextual_validators());
+
+        map.set_validator_carry_policy(CarryOnlyFor(vec![Subject::name()]));
+        assert_ok!(map.use_contextual_validators());
+        assert_ok!(map.validate());
+
+        map.set_validator_carry_policy(CarryAll);
+        assert_err!(map.validate());
+    });
+}
@@ -0,0 +1,66 @@
+use ::error::HeaderValidationError;
+use ::header::{Header, HeaderKind};
+
+use super::HeaderMap;
+
+/// Options for the build-in contextual validators, see `HeaderMap::set_validator_options`.
+///
+/// # Migration
+///
+/// The build-in validators used to silently skip headers they check (e.g.
+/// `From` for the validator of `_From`) if the header did not use the
+/// expected component, which let such maps pass validation only to fail
+/// (or encode something unexpected) later on. They now fail with
+/// `BuildInValidationError::UnexpectedComponentType` instead. Custom headers
+/// using the name of a build-in header with a different component can
+/// set `lenient_component_types` to restore the old behavior.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ValidatorOptions {
+    /// Skip headers with an unexpected component instead of failing.
+    ///
+    /// Default: `false`.
+    pub lenient_component_types: bool
+}
+
+impl ValidatorOptions {
+
+    /// Options which skip headers with an unexpected component (the old behavior).
+    pub fn lenient() -> Self {
+        ValidatorOptions { lenient_component_types: true }
+    }
+}
+
+impl HeaderMap {
+
+    /// Returns the options used by the build-in validators for this map.
+    pub fn validator_options(&self) -> ValidatorOptions {
+        self.validator_options
+    }
+
+    /// Sets the options used by the build-in validators for this map.
+    ///
+    /// The options are used by `use_contextual_validators`.
+    pub fn set_validator_options(&mut self, options: ValidatorOptions) {
+        self.validator_options = options;
+    }
+}
+
+/// Returns the headers with the name of `H` which use the component of `H`.
+///
+/// Headers with another component fail with `UnexpectedComponentType`, or
+/// are skipped if the map has `lenient_component_types` set.
+pub(crate) fn typed_headers<H>(map: &HeaderMap) -> Result<Vec<&Header<H>>, HeaderValidationError>
+    where H: HeaderKind
+{
+    let lenient = map.validator_options().lenient_component_types;
+    let mut headers = Vec::new();
+    for res in map._get::<H>() {
+        match res {
+            Ok(header) => headers.push(header),
+            Err(_) if lenient => {},
+            Err(_) => header_validation_bail!(
+                kind: UnexpectedComponentType { header_name: H::name().as_str() })
+        }
+    }
+    Ok(headers)
+}
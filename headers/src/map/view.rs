@@ -0,0 +1,290 @@
+use ::name::{HeaderName, HasHeaderName};
+use ::header::{Header, HeaderObj, MaxOneMarker};
+use ::error::{HeaderTypeError, HeaderValidationError};
+
+use super::{HeaderMap, Iter, UntypedBodies};
+
+/// The type of a validator which can run against a `HeaderMap` or a `HeaderMapView`.
+///
+/// Unlike `HeaderMapValidator` (which is used by the header kinds and
+/// always sees the whole map) this can e.g. be used for policy hooks which
+/// should only see a restricted view of the map.
+pub type HeaderQueryValidator = fn(&dyn HeaderQuery) -> Result<(), HeaderValidationError>;
+
+/// Read-only access to the headers of a `HeaderMap` or a `HeaderMapView`.
+///
+/// The typed accessors (`get_single`, `_get_single`) are implemented on
+/// `dyn HeaderQuery`, so they can be used on a `&dyn HeaderQuery`.
+pub trait HeaderQuery {
+
+    /// Returns true if a header with the given name is visible.
+    fn contains_name(&self, name: HeaderName) -> bool;
+
+    /// Returns all visible (header name, body) pairs in the order of the map.
+    fn untyped_iter<'a>(&'a self) -> Box<Iterator<Item=(HeaderName, &'a HeaderObj)> + 'a>;
+
+    /// Returns the bodies of all visible headers with the given name.
+    fn untyped_bodies<'a>(&'a self, name: HeaderName) -> Box<Iterator<Item=&'a HeaderObj> + 'a> {
+        Box::new(self.untyped_iter()
+            .filter(move |&(other, _)| other == name)
+            .map(|(_, obj)| obj))
+    }
+}
+
+impl<'q> dyn HeaderQuery + 'q {
+
+    /// Returns the header of the given kind, see `HeaderMap::get_single`.
+    pub fn get_single<'a, H>(&'a self, _type_hint: H)
+        -> Option<Result<&'a Header<H>, HeaderTypeError>>
+        where H: MaxOneMarker
+    {
+        self._get_single::<H>()
+    }
+
+    /// A variation of `get_single` which doesn't require passing in a type hint.
+    pub fn _get_single<'a, H>(&'a self)
+        -> Option<Result<&'a Header<H>, HeaderTypeError>>
+        where H: MaxOneMarker
+    {
+        single(self.untyped_bodies(H::name()))
+    }
+}
+
+impl HeaderQuery for HeaderMap {
+
+    fn contains_name(&self, name: HeaderName) -> bool {
+        self.contains(name)
+    }
+
+    fn untyped_iter<'a>(&'a self) -> Box<Iterator<Item=(HeaderName, &'a HeaderObj)> + 'a> {
+        Box::new(self.iter().map(|(name, obj)| (name, &**obj)))
+    }
+
+    fn untyped_bodies<'a>(&'a self, name: HeaderName) -> Box<Iterator<Item=&'a HeaderObj> + 'a> {
+        Box::new(self.get_untyped(name))
+    }
+}
+
+/// A read-only view of a `HeaderMap` only containing the allowed header names.
+///
+/// Headers with other names are treated as if they wouldn't be in the map.
+/// The view only borrows the map (and the allowed names), so it's cheap
+/// to copy and pass around. See `HeaderMap::restricted_view`.
+#[derive(Debug, Copy, Clone)]
+pub struct HeaderMapView<'a> {
+    map: &'a HeaderMap,
+    allowed: &'a [HeaderName]
+}
+
+impl HeaderMap {
+
+    /// Returns a read-only view of this map which only contains the `allowed` header names.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate mail_headers;
+    /// # fn main() {
+    /// use mail_headers::HeaderKind;
+    /// use mail_headers::headers::*;
+    ///
+    /// let map = headers! {
+    ///     Subject: "hy there",
+    ///     Bcc: ["secret@example.com"]
+    /// }.unwrap();
+    ///
+    /// let allowed = [Subject::name()];
+    /// let view = map.restricted_view(&allowed);
+    /// assert!(view.contains(Subject));
+    /// assert!(!view.contains(Bcc));
+    /// assert!(map.contains(Bcc));
+    /// # }
+    /// ```
+    pub fn restricted_view<'a>(&'a self, allowed: &'a [HeaderName]) -> HeaderMapView<'a> {
+        HeaderMapView { map: self, allowed }
+    }
+}
+
+impl<'a> HeaderMapView<'a> {
+
+    /// Returns the header names allowed by this view.
+    pub fn allowed(&self) -> &'a [HeaderName] {
+        self.allowed
+    }
+
+    fn is_allowed(&self, name: HeaderName) -> bool {
+        self.allowed.contains(&name)
+    }
+
+    /// Returns true if the view contains a header with the given name.
+    pub fn contains<H: HasHeaderName>(&self, name: H) -> bool {
+        let name = name.get_name();
+        self.is_allowed(name) && self.map.contains(name)
+    }
+
+    /// Returns all bodies with the given name, see `HeaderMap::get_untyped`.
+    pub fn get_untyped<H: HasHeaderName>(&self, name: H) -> ViewBodies<'a> {
+        let name = name.get_name();
+        if self.is_allowed(name) {
+            ViewBodies(Some(self.map.get_untyped(name)))
+        } else {
+            ViewBodies(None)
+        }
+    }
+
+    /// Returns the header of the given kind, see `HeaderMap::get_single`.
+    pub fn get_single<H>(&self, _type_hint: H) -> Option<Result<&'a Header<H>, HeaderTypeError>>
+        where H: MaxOneMarker
+    {
+        self._get_single::<H>()
+    }
+
+    /// A variation of `get_single` which doesn't require passing in a type hint.
+    pub fn _get_single<H>(&self) -> Option<Result<&'a Header<H>, HeaderTypeError>>
+        where H: MaxOneMarker
+    {
+        single(self.get_untyped(H::name()))
+    }
+
+    /// Iterates over all (header name, boxed body) pairs in the view.
+    pub fn iter(&self) -> ViewIter<'a> {
+        ViewIter {
+            iter: self.map.iter(),
+            allowed: self.allowed
+        }
+    }
+}
+
+impl<'v> HeaderQuery for HeaderMapView<'v> {
+
+    fn contains_name(&self, name: HeaderName) -> bool {
+        self.contains(name)
+    }
+
+    fn untyped_iter<'a>(&'a self) -> Box<Iterator<Item=(HeaderName, &'a HeaderObj)> + 'a> {
+        Box::new(self.iter().map(|(name, obj)| (name, &**obj)))
+    }
+
+    fn untyped_bodies<'a>(&'a self, name: HeaderName) -> Box<Iterator<Item=&'a HeaderObj> + 'a> {
+        Box::new(self.get_untyped(name))
+    }
+}
+
+fn single<'a, H, I>(mut bodies: I) -> Option<Result<&'a Header<H>, HeaderTypeError>>
+    where H: MaxOneMarker, I: Iterator<Item=&'a HeaderObj>
+{
+    let first = bodies.next();
+    if first.is_some() && bodies.next().is_some() {
+        return Some(Err(HeaderTypeError::new(H::name())));
+    }
+    first.map(|untyped| untyped.try_downcast_ref::<H>())
+}
+
+/// Iterator over the bodies of a name in a `HeaderMapView`, see `HeaderMapView::get_untyped`.
+#[derive(Debug)]
+pub struct ViewBodies<'a>(Option<UntypedBodies<'a>>);
+
+impl<'a> Iterator for ViewBodies<'a> {
+    type Item = &'a HeaderObj;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.as_mut().and_then(|bodies| bodies.next())
+    }
+}
+
+/// Iterator over the headers in a `HeaderMapView`, see `HeaderMapView::iter`.
+#[derive(Debug)]
+pub struct ViewIter<'a> {
+    iter: Iter<'a>,
+    allowed: &'a [HeaderName]
+}
+
+impl<'a> Iterator for ViewIter<'a> {
+    type Item = (HeaderName, &'a Box<HeaderObj>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let allowed = self.allowed;
+        self.iter.find(|&(name, _)| allowed.contains(&name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderKind;
+    use ::headers::*;
+    use super::*;
+
+    fn address_headers() -> Vec<HeaderName> {
+        vec![Subject::name(), _From::name(), _To::name()]
+    }
+
+    /// A validator written against `HeaderQuery` requiring a `From` header.
+    fn needs_from(query: &dyn HeaderQuery) -> Result<(), HeaderValidationError> {
+        match query.get_single(_From) {
+            Some(Ok(_)) => Ok(()),
+            Some(Err(err)) => Err(HeaderValidationError::Custom(err.into())),
+            None => Err(HeaderValidationError::Custom(format_err!("missing From")))
+        }
+    }
+
+    test!(view_hides_not_allowed_headers {
+        let map = headers! {
+            _From: ["me@example.com"],
+            Bcc: ["secret@example.com"],
+            Subject: "hy there"
+        }?;
+        let allowed = address_headers();
+        let view = map.restricted_view(&allowed);
+
+        assert!(!view.contains(Bcc));
+        assert_eq!(view.get_untyped(Bcc).count(), 0);
+        let names = view.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["From", "Subject"]);
+        assert_eq!(view.get_single(Subject).unwrap()?.as_str(), "hy there");
+
+        // the underlying map is unchanged
+        assert!(map.contains(Bcc));
+        assert_eq!(map.len(), 3);
+    });
+
+    test!(view_is_copy {
+        let map = headers! {
+            Subject: "hy there"
+        }?;
+        let allowed = address_headers();
+        let view = map.restricted_view(&allowed);
+        let other = view;
+        assert!(view.contains(Subject));
+        assert!(other.contains(Subject));
+    });
+
+    test!(query_validator_runs_against_map_and_view {
+        let map = headers! {
+            _From: ["me@example.com"],
+            Bcc: ["secret@example.com"]
+        }?;
+        let validator: HeaderQueryValidator = needs_from;
+
+        validator(&map)?;
+        let allowed = address_headers();
+        validator(&map.restricted_view(&allowed))?;
+
+        let no_from = [Subject::name()];
+        assert_err!(validator(&map.restricted_view(&no_from)));
+    });
+
+    test!(query_hides_not_allowed_headers {
+        let map = headers! {
+            Subject: "hy there",
+            Bcc: ["secret@example.com"]
+        }?;
+        let allowed = address_headers();
+        let view = map.restricted_view(&allowed);
+        let query: &dyn HeaderQuery = &view;
+
+        assert!(!query.contains_name(Bcc::name()));
+        assert_eq!(query.untyped_iter().count(), 1);
+        assert!((&map as &dyn HeaderQuery).contains_name(Bcc::name()));
+    });
+}
@@ -1,5 +1,6 @@
 use std::fmt;
-use soft_ascii_string::SoftAsciiStr;
+use std::hash::{Hash, Hasher};
+use soft_ascii::SoftAsciiStr;
 
 use internals::grammar::is_ftext;
 
@@ -8,9 +9,18 @@ use internals::grammar::is_ftext;
 /// yourself (except maybe for testing). At last as long as you use `def_header!`
 /// for defining custom Headers, which is highly recommended
 ///
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+/// A header name consists of the name used for comparison (and hashing),
+/// which has to follow the capitalization scheme described in `HeaderName::new`,
+/// and the name used when encoding the header (the "wire spelling"). Normally
+/// both are the same, but some systems expect a specific spelling like
+/// `Message-ID` or `MIME-Version` which can be set with `with_wire_case`.
+/// The wire spelling is ignored for equality and hashing, i.e. two names
+/// only differing in the wire spelling are the same (map key).
+///
+#[derive(Debug, Copy, Clone)]
 pub struct HeaderName {
-    name: &'static SoftAsciiStr
+    name: &'static SoftAsciiStr,
+    wire_name: &'static SoftAsciiStr
 }
 
 impl HeaderName {
@@ -22,25 +32,83 @@ impl HeaderName {
     /// This frees us from doing either case insensitive comparison/hash wrt. hash map
     /// lookups, or converting all names to upper/lower case.
     ///
+    /// To encode the name with a different capitalization use `with_wire_case`.
+    ///
     pub fn new( name: &'static SoftAsciiStr ) -> Result<Self, InvalidHeaderName> {
         HeaderName::validate_name( name )?;
-        Ok( HeaderName { name } )
+        Ok( HeaderName { name, wire_name: name } )
     }
 
     pub fn from_ascii_unchecked<B: ?Sized>( name: &'static B ) -> HeaderName
         where B: AsRef<str>
     {
-        HeaderName { name: SoftAsciiStr::from_unchecked( name.as_ref() ) }
+        let name = SoftAsciiStr::from_unchecked( name.as_ref() );
+        HeaderName { name, wire_name: name }
+    }
+
+    /// Like `from_ascii_unchecked` but with a different wire spelling.
+    pub fn from_ascii_unchecked_with_wire_case<B: ?Sized, W: ?Sized>(
+        name: &'static B,
+        wire_name: &'static W
+    ) -> HeaderName
+        where B: AsRef<str>, W: AsRef<str>
+    {
+        HeaderName {
+            name: SoftAsciiStr::from_unchecked( name.as_ref() ),
+            wire_name: SoftAsciiStr::from_unchecked( wire_name.as_ref() )
+        }
     }
 
+    /// Returns a header name with the same comparison name but given wire spelling.
+    ///
+    /// The wire spelling is used when encoding the header, it has to be a valid
+    /// header field name and has to match this name ignoring (ascii) case, but
+    /// does not need to follow the capitalization scheme, e.g. `Message-ID`
+    /// is a valid wire spelling for `Message-Id`.
+    pub fn with_wire_case( self, wire_name: &'static SoftAsciiStr )
+        -> Result<Self, InvalidHeaderName>
+    {
+        HeaderName::validate_wire_name( self.name, wire_name )?;
+        Ok( HeaderName { name: self.name, wire_name } )
+    }
+
+    /// Returns the name used for comparison, e.g. `Message-Id`.
     #[inline(always)]
     pub fn as_ascii_str( &self ) -> &'static SoftAsciiStr {
         self.name
     }
+
+    /// Returns the name used for comparison, e.g. `Message-Id`.
     #[inline(always)]
     pub fn as_str( &self ) -> &'static str {
         self.name.as_str()
     }
+
+    /// Returns the name used when encoding the header, e.g. `Message-ID`.
+    #[inline(always)]
+    pub fn as_wire_ascii_str( &self ) -> &'static SoftAsciiStr {
+        self.wire_name
+    }
+
+    /// Returns the name used when encoding the header, e.g. `Message-ID`.
+    #[inline(always)]
+    pub fn as_wire_str( &self ) -> &'static str {
+        self.wire_name.as_str()
+    }
+}
+
+impl PartialEq for HeaderName {
+    fn eq(&self, other: &HeaderName) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for HeaderName {}
+
+impl Hash for HeaderName {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state)
+    }
 }
 
 impl fmt::Display for HeaderName {
@@ -106,9 +174,26 @@ impl HeaderName {
         }
         Ok( () )
     }
+
+    /// validates if the wire name is a valid header field name matching given name
+    ///
+    /// the wire name does not need to follow the capitalization scheme
+    fn validate_wire_name(name: &SoftAsciiStr, wire_name: &SoftAsciiStr)
+        -> Result<(), InvalidHeaderName>
+    {
+        let is_valid = wire_name.len() > 0
+            && wire_name.as_str().chars().all(|ch| is_ftext(ch) && ch != ':')
+            && wire_name.as_str().eq_ignore_ascii_case(name.as_str());
+
+        if is_valid {
+            Ok( () )
+        } else {
+            Err(InvalidHeaderName { invalid_name: wire_name.to_owned().into() })
+        }
+    }
 }
 
-#[derive(Clone, Debug, Fail)]
+#[derive(Clone, Debug, Fail, PartialEq, Eq, Hash)]
 #[fail(display = "given name is not a valid header name: {:?}", invalid_name)]
 pub struct InvalidHeaderName {
     invalid_name: String
@@ -129,8 +214,14 @@ impl HasHeaderName for HeaderName {
 
 #[cfg(test)]
 mod test {
+    use std::collections::hash_map::DefaultHasher;
     use super::*;
 
+    fn hash_of(name: HeaderName) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        hasher.finish()
+    }
 
     #[test]
     fn valide_header_names() {
@@ -177,6 +268,29 @@ mod test {
             assert_err!( HeaderName::validate_name( SoftAsciiStr::from_str( case ).unwrap() ), case );
         }
     }
-}
 
+    #[test]
+    fn wire_case_has_to_match_ignoring_case() {
+        let name = HeaderName::new(SoftAsciiStr::from_unchecked("Message-Id")).unwrap();
+        let valid_cases = &["Message-ID", "MESSAGE-ID", "message-id", "Message-Id"];
+        for case in valid_cases.iter() {
+            assert_ok!(name.with_wire_case(SoftAsciiStr::from_unchecked(case)), case);
+        }
+
+        let invalid_cases = &["Message-Ids", "Message_ID", "Message ID", "Message:ID", ""];
+        for case in invalid_cases.iter() {
+            assert_err!(name.with_wire_case(SoftAsciiStr::from_unchecked(case)), case);
+        }
+    }
 
+    #[test]
+    fn wire_case_is_ignored_for_eq_and_hash() {
+        let name = HeaderName::from_ascii_unchecked_with_wire_case("Mime-Version", "MIME-Version");
+        let other = HeaderName::from_ascii_unchecked("Mime-Version");
+        assert_eq!(name.as_str(), "Mime-Version");
+        assert_eq!(name.as_wire_str(), "MIME-Version");
+        assert_eq!(other.as_wire_str(), "Mime-Version");
+        assert_eq!(name, other);
+        assert_eq!(hash_of(name), hash_of(other));
+    }
+}
@@ -0,0 +1,268 @@
+//! Utilities for parameter values of headers like `Content-Type` or `Content-Disposition`.
+//!
+//! Parameter values (rfc2045) are either a `token` or a `quoted-string` (rfc5322).
+//! This module decides which of both is needed for a value, quotes values
+//! (escaping `"` and `\`) and unquotes them again.
+//!
+//! Values which contain non us-ascii chars can only be represented in a
+//! internationalized mail (rfc6532), in a ascii mail they would need to be
+//! encoded (rfc2231) which is not done by this module.
+use std::borrow::Cow;
+
+use internals::MailType;
+
+use ::error::ComponentCreationError;
+
+const COMPONENT: &str = "ParameterValue";
+
+/// Returns true if the char is a `tspecial` (rfc2045).
+fn is_tspecial(ch: char) -> bool {
+    match ch {
+        '(' | ')' | '<' | '>' | '@' | ',' | ';' | ':' |
+        '\\' | '"' | '/' | '[' | ']' | '?' | '=' => true,
+        _ => false
+    }
+}
+
+/// Returns true if the char can be part of a `token` (rfc2045).
+pub fn is_token_char(ch: char) -> bool {
+    ch.is_ascii() && !ch.is_ascii_control() && ch != ' ' && !is_tspecial(ch)
+}
+
+/// Returns true if the char can appear in a quoted string without being escaped.
+fn is_unescaped_char(ch: char, mail_type: MailType) -> bool {
+    match ch {
+        '"' | '\\' => false,
+        ' ' | '\t' => true,
+        ch if ch.is_ascii() => !ch.is_ascii_control(),
+        _ => mail_type.is_internationalized()
+    }
+}
+
+/// Returns true if given value can not be used as parameter value as-is.
+///
+/// This is the case if it's not a valid `token`, i.e. if it is empty or
+/// contains spaces, control chars, `tspecials` (e.g. `"`, `\` or `;`) or
+/// non us-ascii chars (independent of the mail type, as internationalized
+/// mails only extend `quoted-string` but not `token`).
+pub fn needs_quoting(value: &str, _mail_type: MailType) -> bool {
+    value.is_empty() || !value.chars().all(is_token_char)
+}
+
+/// Quotes given value if necessary so that it can be used as parameter value.
+///
+/// Values which are valid tokens are returned as-is, all other values are
+/// turned into a quoted string, escaping `"` and `\`.
+///
+/// # Error
+///
+/// Fails if the value can not be represented even as a quoted string, i.e.
+/// if it contains control chars (except tab) like CR or LF, or if it contains
+/// non us-ascii chars and the mail type is not internationalized. The
+/// error's span points to the first char which can not be represented.
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_headers;
+/// # extern crate mail_internals;
+/// # fn main() {
+/// use mail_internals::MailType;
+/// use mail_headers::params::{quote_value, unquote_value};
+///
+/// let quoted = quote_value(r#"say "hy""#, MailType::Ascii).unwrap();
+/// assert_eq!(quoted, r#""say \"hy\"""#);
+/// assert_eq!(unquote_value(&quoted).unwrap(), r#"say "hy""#);
+/// assert_eq!(quote_value("logo.png", MailType::Ascii).unwrap(), "logo.png");
+/// # }
+/// ```
+pub fn quote_value(value: &str, mail_type: MailType) -> Result<String, ComponentCreationError> {
+    if !needs_quoting(value, mail_type) {
+        return Ok(value.to_owned());
+    }
+
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for (idx, ch) in value.char_indices() {
+        if is_unescaped_char(ch, mail_type) {
+            out.push(ch);
+        } else if ch == '"' || ch == '\\' {
+            out.push('\\');
+            out.push(ch);
+        } else {
+            let expected = if mail_type.is_internationalized() {
+                "qtext, WSP or quoted-pair"
+            } else {
+                "us-ascii qtext, WSP or quoted-pair"
+            };
+            return Err(
+                ComponentCreationError::new_with_str(COMPONENT, value)
+                    .with_span(idx..idx + ch.len_utf8(), Some(expected))
+            );
+        }
+    }
+    out.push('"');
+    Ok(out)
+}
+
+/// Unquotes a parameter value, i.e. removes the quotes and unescapes quoted-pairs.
+///
+/// Values which are not quoted (i.e. tokens) are returned as-is. No new
+/// string is allocated if the value does not contain any quoted-pairs.
+///
+/// # Error
+///
+/// Fails if the value starts with a `"` but is not a complete quoted
+/// string, e.g. if the closing quote is missing, a unescaped `"` appears
+/// in the middle or the value ends in a lone `\`.
+pub fn unquote_value(value: &str) -> Result<Cow<str>, ComponentCreationError> {
+    if !value.starts_with('"') {
+        return Ok(Cow::Borrowed(value));
+    }
+
+    let error = |idx: usize, expected| {
+        ComponentCreationError::new_with_str(COMPONENT, value)
+            .with_span(idx..value.len(), Some(expected))
+    };
+
+    let mut unescaped: Option<String> = None;
+    let mut chars = value.char_indices().skip(1);
+    while let Some((idx, ch)) = chars.next() {
+        match ch {
+            '"' => {
+                if idx + 1 != value.len() {
+                    return Err(error(idx + 1, "end of quoted string"));
+                }
+                return Ok(match unescaped {
+                    Some(owned) => Cow::Owned(owned),
+                    None => Cow::Borrowed(&value[1..idx])
+                });
+            },
+            '\\' => {
+                let (_, escaped) = chars.next()
+                    .ok_or_else(|| error(idx, "quoted-pair"))?;
+                unescaped.get_or_insert_with(|| value[1..idx].to_owned()).push(escaped);
+            },
+            ch => {
+                if let Some(owned) = unescaped.as_mut() {
+                    owned.push(ch);
+                }
+            }
+        }
+    }
+
+    Err(error(value.len(), "'\"'"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn both_types() -> Vec<MailType> {
+        vec![MailType::Ascii, MailType::Internationalized]
+    }
+
+    #[test]
+    fn tokens_are_not_quoted() {
+        for mail_type in both_types() {
+            assert!(!needs_quoting("logo.png", mail_type));
+            assert!(!needs_quoting("a-b_c+1", mail_type));
+            assert_eq!(quote_value("logo.png", mail_type).unwrap(), "logo.png");
+        }
+    }
+
+    #[test]
+    fn values_with_spaces_are_quoted() {
+        for mail_type in both_types() {
+            assert!(needs_quoting("this is nice", mail_type));
+            assert_eq!(quote_value("this is nice", mail_type).unwrap(), "\"this is nice\"");
+            assert_eq!(quote_value("tab\there", mail_type).unwrap(), "\"tab\there\"");
+        }
+    }
+
+    #[test]
+    fn empty_value_is_quoted() {
+        assert!(needs_quoting("", MailType::Ascii));
+        assert_eq!(quote_value("", MailType::Ascii).unwrap(), "\"\"");
+        assert_eq!(unquote_value("\"\"").unwrap(), "");
+    }
+
+    #[test]
+    fn quotes_and_backslashes_are_escaped() {
+        for mail_type in both_types() {
+            assert_eq!(quote_value("a\"b", mail_type).unwrap(), r#""a\"b""#);
+            assert_eq!(quote_value(r"C:\dir", mail_type).unwrap(), r#""C:\\dir""#);
+        }
+    }
+
+    #[test]
+    fn tspecials_are_quoted() {
+        assert_eq!(quote_value("a;b", MailType::Ascii).unwrap(), "\"a;b\"");
+        assert_eq!(quote_value("a=b", MailType::Ascii).unwrap(), "\"a=b\"");
+    }
+
+    #[test]
+    fn non_ascii_needs_internationalized_mail() {
+        assert!(needs_quoting("hälö", MailType::Ascii));
+        assert!(needs_quoting("hälö", MailType::Internationalized));
+
+        assert_eq!(quote_value("hälö", MailType::Internationalized).unwrap(), "\"hälö\"");
+
+        let err = quote_value("hälö", MailType::Ascii).unwrap_err();
+        assert_eq!(err.span(), Some(1..3));
+    }
+
+    #[test]
+    fn cr_and_lf_can_not_be_quoted() {
+        for mail_type in both_types() {
+            let err = quote_value("a\r\nb", mail_type).unwrap_err();
+            assert_eq!(err.span(), Some(1..2));
+            assert_err!(quote_value("a\nb", mail_type));
+            assert_err!(quote_value("a\0b", mail_type));
+        }
+    }
+
+    #[test]
+    fn unquote_borrows_if_possible() {
+        match unquote_value("\"this is nice\"").unwrap() {
+            Cow::Borrowed(value) => assert_eq!(value, "this is nice"),
+            Cow::Owned(_) => panic!("unexpected allocation")
+        }
+        match unquote_value("logo.png").unwrap() {
+            Cow::Borrowed(value) => assert_eq!(value, "logo.png"),
+            Cow::Owned(_) => panic!("unexpected allocation")
+        }
+    }
+
+    #[test]
+    fn unquote_unescapes_quoted_pairs() {
+        assert_eq!(unquote_value(r#""a\"b\\c\d""#).unwrap(), "a\"b\\cd");
+    }
+
+    #[test]
+    fn unquote_rejects_malformed_quoted_strings() {
+        assert_err!(unquote_value("\"abc"));
+        assert_err!(unquote_value("\"abc\\\""));
+        assert_err!(unquote_value("\"ab\"c\""));
+        let err = unquote_value("\"ab\"cd").unwrap_err();
+        assert_eq!(err.span(), Some(4..6));
+    }
+
+    #[test]
+    fn quote_unquote_round_trip() {
+        let values = &[
+            "logo.png", "this is nice", "", "a\"b", r"C:\dir\file", "\"\"", "\\",
+            "Tue,  6 Aug 2013 07:11:01 +0000", "hälö wörld"
+        ];
+        for value in values {
+            for mail_type in both_types() {
+                let quoted = match quote_value(value, mail_type) {
+                    Ok(quoted) => quoted,
+                    // non ascii in ascii mails
+                    Err(_) => continue
+                };
+                assert_eq!(unquote_value(&quoted).unwrap(), *value);
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Re-exports the most commonly used parts of this crate.
+//!
+//! This includes the `HeaderMap`, the traits and types needed to
+//! define and use headers, all predefined headers, the error types
+//! and the most common components.
+//!
+//! Components which have the same name as a predefined header
+//! (e.g. `MessageId`) are not re-exported, use the
+//! `header_components` module for them.
+//!
+//! As macros are not placed in modules the `headers!` and
+//! `def_headers!` macros still need to be imported using
+//! `#[macro_use] extern crate mail_headers;`.
+//!
+//! # Example
+//!
+//! ```
+//! #[macro_use]
+//! extern crate mail_headers;
+//!
+//! use mail_headers::prelude::*;
+//!
+//! fn create_headers() -> Result<HeaderMap, ComponentCreationError> {
+//!     headers! {
+//!         _From: ["me@example.com"],
+//!         Subject: "Hy there"
+//!     }
+//! }
+//!
+//! fn main() {
+//!     let map = create_headers().unwrap();
+//!     assert_eq!(map.len(), 2);
+//! }
+//! ```
+//!
+//! Custom headers can be defined from outside of this crate without
+//! depending on any of its (hidden) internals:
+//!
+//! ```
+//! #[macro_use]
+//! extern crate mail_headers;
+//!
+//! use mail_headers::prelude::*;
+//! use mail_headers::header_components as components;
+//!
+//! def_headers! {
+//!     test_name: validate_header_names,
+//!     scope: components,
+//!     XMailer, unchecked { "X-Mailer" }, Unstructured, maxOne, None
+//! }
+//!
+//! fn main() {
+//!     let map = headers! {
+//!         XMailer: "my mailer"
+//!     }.unwrap();
+//!     assert_eq!(map.get_single(XMailer).unwrap().unwrap().as_str(), "my mailer");
+//! }
+//! ```
+
+pub use ::map::HeaderMap;
+pub use ::name::{HeaderName, HasHeaderName};
+pub use ::header::{HeaderKind, Header, MaxOneMarker, HeaderObj, HeaderObjTrait};
+pub use ::convert::{HeaderTryFrom, HeaderTryInto};
+
+pub use ::error::{
+    ComponentCreationError,
+    HeaderTypeError,
+    HeaderValidationError,
+    BuildInValidationError
+};
+
+pub use ::headers::*;
+
+pub use ::header_components::{
+    DateTime,
+    Email, Domain, LocalPart,
+    Mailbox, MailboxList, OptMailboxList,
+    Phrase, PhraseList,
+    Unstructured,
+    MessageIdList,
+    MediaType,
+    TransferEncoding,
+    Disposition, DispositionKind,
+    FileMeta
+};
@@ -0,0 +1,304 @@
+//! Injectable sources of randomness for generated header values.
+//!
+//! Generated values like multipart boundaries (see
+//! `MediaType::multipart_mixed_with_boundary`) or message ids (see
+//! `MessageId::generate`) need some randomness. By default it is taken
+//! from `HashRandSource`, but to produce reproducible mails (e.g. for byte
+//! exact golden tests) a deterministic `SeededRandSource` can be used
+//! instead, either explicitly through the `*_using` constructors or
+//! for all constructors called in a closure through `with_rand_source`.
+//!
+//! Generated values are always grammar-valid independent of the bytes a
+//! source produces (e.g. a source only producing zeros still works, it
+//! just always produces the same values).
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// A source of random bytes.
+pub trait RandSource {
+    /// Fills given buffer with random bytes.
+    fn fill(&mut self, buf: &mut [u8]);
+}
+
+/// The default source of randomness.
+///
+/// This hashes a counter with the std `RandomState` (SipHash) hasher, which is
+/// keyed with random keys the std library obtains once per thread, so it does
+/// not need any additional dependency. It does *not* read from the OS for each
+/// call and it is **not** a CSPRNG: it is good enough to make generated
+/// boundaries and message ids unique, but must not be used for anything
+/// security related.
+#[derive(Debug, Clone, Default)]
+pub struct HashRandSource {
+    counter: u64
+}
+
+impl HashRandSource {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl RandSource for HashRandSource {
+    fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            // each `RandomState::new` on the same thread uses different keys
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u64(self.counter);
+            self.counter = self.counter.wrapping_add(1);
+            let bytes = u64_to_le_bytes(hasher.finish());
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+/// A deterministic source of randomness, producing the same bytes for the same seed.
+///
+/// This is meant for tests and reproducible builds, it is **not** suitable
+/// for anything security related.
+#[derive(Debug, Clone)]
+pub struct SeededRandSource {
+    state: u64
+}
+
+impl SeededRandSource {
+    pub fn new(seed: u64) -> Self {
+        SeededRandSource { state: seed }
+    }
+
+    /// splitmix64, works with any seed (including 0)
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl RandSource for SeededRandSource {
+    fn fill(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = u64_to_le_bytes(self.next_u64());
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
+fn u64_to_le_bytes(val: u64) -> [u8; 8] {
+    let mut bytes = [0u8; 8];
+    for (idx, byte) in bytes.iter_mut().enumerate() {
+        *byte = (val >> (idx * 8)) as u8;
+    }
+    bytes
+}
+
+thread_local! {
+    static OVERRIDE: RefCell<Option<Box<RandSource>>> = RefCell::new(None);
+}
+
+/// Runs `func` with `source` as the source of randomness of the current thread.
+///
+/// All constructors which do not take an explicit `RandSource` (e.g.
+/// `MessageId::generate`) will use `source` while `func` runs, which
+/// allows making them deterministic without changing their call sites.
+/// Calls can be nested, the previous source is restored afterwards (even
+/// if `func` panics).
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_headers;
+/// # extern crate soft_ascii_string;
+/// # fn main() {
+/// use soft_ascii_string::SoftAsciiStr;
+/// use mail_headers::header_components::MessageId;
+/// use mail_headers::rand_source::{with_rand_source, SeededRandSource};
+///
+/// let domain = SoftAsciiStr::from_unchecked("example.com");
+/// let first = with_rand_source(SeededRandSource::new(42), || MessageId::generate(domain).unwrap());
+/// let second = with_rand_source(SeededRandSource::new(42), || MessageId::generate(domain).unwrap());
+/// assert_eq!(first, second);
+/// # }
+/// ```
+pub fn with_rand_source<S, F, R>(source: S, func: F) -> R
+    where S: RandSource + 'static, F: FnOnce() -> R
+{
+    struct Restore(Option<Box<RandSource>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            OVERRIDE.with(|cell| *cell.borrow_mut() = previous);
+        }
+    }
+
+    let previous = OVERRIDE.with(|cell| {
+        cell.borrow_mut().replace(Box::new(source))
+    });
+    let _restore = Restore(previous);
+    func()
+}
+
+/// Calls `func` with the current source of randomness of this thread.
+///
+/// This is the source set by `with_rand_source` or a `HashRandSource` if none is set.
+pub fn use_current_source<F, R>(func: F) -> R
+    where F: FnOnce(&mut RandSource) -> R
+{
+    // the source is taken out while in use (and put back afterwards even if `func`
+    // panics), so calling this function from `func` uses a `HashRandSource`
+    struct PutBack(Option<Box<RandSource>>);
+
+    impl Drop for PutBack {
+        fn drop(&mut self) {
+            let source = self.0.take();
+            OVERRIDE.with(|cell| {
+                let mut current = cell.borrow_mut();
+                if current.is_none() {
+                    *current = source;
+                }
+            });
+        }
+    }
+
+    let mut guard = PutBack(OVERRIDE.with(|cell| cell.borrow_mut().take()));
+    match guard.0 {
+        Some(ref mut source) => func(&mut **source),
+        None => func(&mut HashRandSource::new())
+    }
+}
+
+/// Creates a string of `len` chars from given alphabet using given source.
+///
+/// The alphabet has exactly 64 (ascii) chars, so that every byte maps to
+/// a char without any bias or rejection sampling.
+pub fn random_string(source: &mut RandSource, alphabet: &[u8; 64], len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    source.fill(&mut bytes);
+    bytes.iter()
+        .map(|byte| alphabet[(byte & 0x3f) as usize] as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use soft_ascii::SoftAsciiStr;
+
+    use ::HeaderTryFrom;
+    use ::header_components::{MessageId, MediaType};
+    use super::*;
+
+    struct ZeroSource;
+
+    impl RandSource for ZeroSource {
+        fn fill(&mut self, buf: &mut [u8]) {
+            for byte in buf.iter_mut() {
+                *byte = 0;
+            }
+        }
+    }
+
+    fn domain() -> &'static SoftAsciiStr {
+        SoftAsciiStr::from_unchecked("example.com")
+    }
+
+    fn boundary_of(media_type: &MediaType) -> String {
+        for (name, value) in media_type.params() {
+            let name: &str = name.as_ref();
+            if name == "boundary" {
+                return value.as_str_repr().to_owned();
+            }
+        }
+        panic!("boundary param missing")
+    }
+
+    #[test]
+    fn same_seed_same_output() {
+        let mut first = SeededRandSource::new(7);
+        let mut second = SeededRandSource::new(7);
+        assert_eq!(
+            MessageId::generate_using(domain(), &mut first).unwrap(),
+            MessageId::generate_using(domain(), &mut second).unwrap()
+        );
+        assert_eq!(
+            boundary_of(&MediaType::multipart_mixed_with_boundary_using(&mut first)),
+            boundary_of(&MediaType::multipart_mixed_with_boundary_using(&mut second))
+        );
+    }
+
+    #[test]
+    fn different_seeds_differ() {
+        let mut first = SeededRandSource::new(1);
+        let mut second = SeededRandSource::new(2);
+        assert_ne!(
+            MessageId::generate_using(domain(), &mut first).unwrap(),
+            MessageId::generate_using(domain(), &mut second).unwrap()
+        );
+        assert_ne!(
+            boundary_of(&MediaType::multipart_mixed_with_boundary_using(&mut first)),
+            boundary_of(&MediaType::multipart_mixed_with_boundary_using(&mut second))
+        );
+    }
+
+    #[test]
+    fn zero_source_produces_valid_output() {
+        let id = MessageId::generate_using(domain(), &mut ZeroSource).unwrap();
+        assert_eq!(id.as_str(), "AAAAAAAAAAAAAAAAAAAAAAAA@example.com");
+        assert_ok!(MessageId::try_from(id.as_str()));
+
+        let media_type = MediaType::multipart_mixed_with_boundary_using(&mut ZeroSource);
+        assert_eq!(boundary_of(&media_type), "A".repeat(40));
+    }
+
+    #[test]
+    fn all_bytes_map_to_valid_chars() {
+        struct CountingSource(u8);
+        impl RandSource for CountingSource {
+            fn fill(&mut self, buf: &mut [u8]) {
+                for byte in buf.iter_mut() {
+                    *byte = self.0;
+                    self.0 = self.0.wrapping_add(1);
+                }
+            }
+        }
+
+        let mut source = CountingSource(0);
+        for _ in 0..11 {
+            let id = MessageId::generate_using(domain(), &mut source).unwrap();
+            assert_ok!(MessageId::try_from(id.as_str()));
+        }
+    }
+
+    #[test]
+    fn seeded_source_accepts_zero_seed() {
+        let mut source = SeededRandSource::new(0);
+        let mut buf = [0u8; 16];
+        source.fill(&mut buf);
+        assert_ne!(buf, [0u8; 16]);
+    }
+
+    #[test]
+    fn override_makes_convenience_constructors_deterministic() {
+        let first = with_rand_source(SeededRandSource::new(3), || {
+            (MessageId::generate(domain()).unwrap(), boundary_of(&MediaType::multipart_mixed_with_boundary()))
+        });
+        let second = with_rand_source(SeededRandSource::new(3), || {
+            (MessageId::generate(domain()).unwrap(), boundary_of(&MediaType::multipart_mixed_with_boundary()))
+        });
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn override_is_restored() {
+        let zero_id = MessageId::generate_using(domain(), &mut ZeroSource).unwrap();
+        with_rand_source(SeededRandSource::new(3), || {
+            with_rand_source(ZeroSource, || {
+                assert_eq!(MessageId::generate(domain()).unwrap(), zero_id);
+            });
+            assert_ne!(MessageId::generate(domain()).unwrap(), zero_id);
+        });
+        assert_ne!(MessageId::generate(domain()).unwrap(), zero_id);
+    }
+}
@@ -0,0 +1,195 @@
+//! Helpers for the headers of inline parts of a `multipart/related` body.
+//!
+//! A html mail embedding images needs a part per image with a `Content-Id`,
+//! a inline `Content-Disposition`, a `Content-Type` and a
+//! `Content-Transfer-Encoding`, while the html refers to the image through
+//! a `cid:` url which has to match the `Content-Id` exactly. Creating both
+//! the headers (`inline_part_headers`) and the url (`cid_reference`) from
+//! the same `ContentId` makes sure they can not drift apart.
+use ::HeaderKind;
+use ::error::ComponentCreationError;
+use ::header_components::{
+    ContentId, MediaType, Disposition, DispositionKind, FileMeta, TransferEncoding
+};
+use ::headers;
+use ::map::HeaderMap;
+use ::params::quote_value;
+use internals::MailType;
+
+/// Options for `inline_part_headers_with`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct InlinePartOptions {
+    /// The transfer encoding to use, `Base64` if not set.
+    pub transfer_encoding: Option<TransferEncoding>,
+
+    /// The parameters of the `Content-Disposition` header.
+    ///
+    /// The `filename` passed to `inline_part_headers_with` (if any)
+    /// overrides the file name set here.
+    pub file_meta: FileMeta
+}
+
+/// The `image/png` media type.
+pub fn image_png() -> MediaType {
+    MediaType::new("image", "png")
+        .expect("[BUG] image/png is a valid media type")
+}
+
+/// The `image/jpeg` media type.
+pub fn image_jpeg() -> MediaType {
+    MediaType::new("image", "jpeg")
+        .expect("[BUG] image/jpeg is a valid media type")
+}
+
+/// Creates the headers for a inline part with given media type and content id.
+///
+/// The returned map contains a `Content-Type`, `Content-Id`, a inline
+/// `Content-Disposition` (with given file name, if any) and a `Base64`
+/// `Content-Transfer-Encoding`. Use `cid_reference` to refer to the part.
+///
+/// # Error
+///
+/// Fails if the file name can not be used as parameter value, e.g. because
+/// it contains a line break.
+///
+/// # Example
+///
+/// ```
+/// # extern crate mail_headers;
+/// # fn main() {
+/// use mail_headers::HeaderTryFrom;
+/// use mail_headers::header_components::ContentId;
+/// use mail_headers::related::{inline_part_headers, cid_reference, image_png};
+///
+/// let id = ContentId::try_from("logo.1234@example.com").unwrap();
+/// let headers = inline_part_headers(image_png(), id.clone(), Some("logo.png")).unwrap();
+/// assert_eq!(headers.len(), 4);
+/// assert_eq!(cid_reference(&id), "cid:logo.1234@example.com");
+/// # }
+/// ```
+pub fn inline_part_headers(media_type: MediaType, id: ContentId, filename: Option<&str>)
+    -> Result<HeaderMap, ComponentCreationError>
+{
+    inline_part_headers_with(media_type, id, filename, InlinePartOptions::default())
+}
+
+/// Like `inline_part_headers` but with explicit options.
+pub fn inline_part_headers_with(
+    media_type: MediaType,
+    id: ContentId,
+    filename: Option<&str>,
+    options: InlinePartOptions
+) -> Result<HeaderMap, ComponentCreationError> {
+    let InlinePartOptions { transfer_encoding, mut file_meta } = options;
+    if let Some(filename) = filename {
+        file_meta.file_name = Some(filename.to_owned());
+    }
+    if let Some(file_name) = file_meta.file_name.as_ref() {
+        quote_value(file_name, MailType::Internationalized)?;
+    }
+
+    let mut map = HeaderMap::new();
+    map.insert(headers::ContentType::body(media_type));
+    map.insert(headers::ContentId::body(id));
+    map.insert(headers::ContentDisposition::body(
+        Disposition::new(DispositionKind::Inline, file_meta)));
+    map.insert(headers::ContentTransferEncoding::body(
+        transfer_encoding.unwrap_or(TransferEncoding::Base64)));
+    Ok(map)
+}
+
+/// Returns the `cid:` url referring to the part with given content id (rfc2392).
+///
+/// Chars which are not allowed (or could cause problems when used in a
+/// html attribute) are percent encoded.
+pub fn cid_reference(id: &ContentId) -> String {
+    let mut url = String::from("cid:");
+    for bch in id.as_str().bytes() {
+        match bch {
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' |
+            b'-' | b'.' | b'_' | b'~' | b'@' | b'!' | b'$' | b'*' | b'+' | b'=' => {
+                url.push(bch as char)
+            },
+            _ => url.push_str(&format!("%{:02X}", bch))
+        }
+    }
+    url
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderTryFrom;
+    use super::*;
+
+    fn id() -> ContentId {
+        ContentId::try_from("part1.abc@example.com").unwrap()
+    }
+
+    fn encoded_id(map: &HeaderMap) -> String {
+        let obj = map.get_untyped(headers::ContentId).next().unwrap();
+        let value = obj.encoded_value(MailType::Ascii).unwrap();
+        String::from_utf8(value.into_owned()).unwrap()
+    }
+
+    test!(generated_map_validates {
+        let map = inline_part_headers(image_png(), id(), Some("logo.png"))?;
+        map.use_contextual_validators()?;
+        assert_eq!(map.len(), 4);
+        assert!(map.contains(headers::ContentType));
+        assert!(map.contains(headers::ContentDisposition));
+        let encoding = map.get_single(headers::ContentTransferEncoding).unwrap()?;
+        assert_eq!(**encoding, TransferEncoding::Base64);
+        let disposition = map.get_single(headers::ContentDisposition).unwrap()?;
+        assert_eq!(disposition.kind(), DispositionKind::Inline);
+        assert_eq!(disposition.file_meta().file_name, Some("logo.png".to_owned()));
+    });
+
+    test!(cid_reference_matches_content_id {
+        let map = inline_part_headers(image_jpeg(), id(), None)?;
+        let encoded = encoded_id(&map);
+        let reference = cid_reference(&id());
+        assert_eq!(&reference[..4], "cid:");
+        assert_eq!(format!("<{}>", &reference[4..]), encoded);
+    });
+
+    #[test]
+    fn cid_reference_escapes_special_chars() {
+        let id = ContentId::from_unchecked("a/b%c&d@example.com".to_owned());
+        assert_eq!(cid_reference(&id), "cid:a%2Fb%25c%26d@example.com");
+    }
+
+    test!(options_override_defaults {
+        let options = InlinePartOptions {
+            transfer_encoding: Some(TransferEncoding::QuotedPrintable),
+            file_meta: FileMeta {
+                file_name: Some("other.png".to_owned()),
+                size: Some(42),
+                ..Default::default()
+            }
+        };
+        let map = inline_part_headers_with(image_png(), id(), Some("logo.png"), options)?;
+        let encoding = map.get_single(headers::ContentTransferEncoding).unwrap()?;
+        assert_eq!(**encoding, TransferEncoding::QuotedPrintable);
+        let disposition = map.get_single(headers::ContentDisposition).unwrap()?;
+        assert_eq!(disposition.file_meta().file_name, Some("logo.png".to_owned()));
+        assert_eq!(disposition.file_meta().size, Some(42));
+    });
+
+    #[test]
+    fn invalid_file_name_is_rejected() {
+        assert_err!(inline_part_headers(image_png(), id(), Some("logo\r\n.png")));
+    }
+
+    test!(presets_round_trip {
+        for (preset, subtype) in vec![(image_png(), "png"), (image_jpeg(), "jpeg")] {
+            let map = inline_part_headers(preset, id(), Some("image"))?;
+            let text = map.display(MailType::Ascii).to_string();
+            let line = text.lines()
+                .find(|line| line.starts_with("Content-Type: "))
+                .unwrap();
+            let parsed = MediaType::parse(&line["Content-Type: ".len()..])?;
+            assert_eq!(parsed.type_().as_ref() as &str, "image");
+            assert_eq!(parsed.subtype().as_ref() as &str, subtype);
+        }
+    });
+}
@@ -0,0 +1,328 @@
+//! Helpers for testing code which creates `HeaderMap`s.
+//!
+//! This module (and the macros using it) is only available if the
+//! `test-helpers` feature is enabled, which is meant to be only
+//! enabled for the `dev-dependencies` of a crate:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! mail-headers = { version = "...", features = ["test-helpers"] }
+//! ```
+//!
+//! It provides following macros (exported at crate level):
+//!
+//! - `assert_headers_eq!(map, ["From", "Subject"])` asserts that the map contains
+//!   exactly headers with given names in given order
+//! - `assert_header_encodes!(map, Subject, "=?utf8?Q?...?=", MailType::Ascii)`
+//!   asserts that the (first) header with given type encodes to given body
+//! - `assert_validation_err_contains!(map, "Sender")` asserts that the
+//!   contextual validation of the map fails and that the error mentions
+//!   given text
+//!
+//! Additionally `HeaderMapSnapshot` can be used to create a stable,
+//! diff-friendly representation of a map for snapshot testing.
+use std::fmt::Write;
+
+use soft_ascii::SoftAsciiChar;
+
+use internals::MailType;
+use internals::error::EncodingError;
+use internals::encoder::{EncodingBuffer, EncodingWriter};
+
+use ::name::HeaderName;
+use ::header::HeaderObj;
+use ::map::{HeaderMap, RedactionPolicy};
+
+/// Returns the names of all headers in the map in order.
+pub fn header_names(map: &HeaderMap) -> Vec<&'static str> {
+    map.iter().map(|(name, _)| name.as_str()).collect()
+}
+
+/// Encodes given header into a `"Name: body"` string (without the final CRLF).
+///
+/// If the header is folded the string contains the CRLF of the folding.
+pub fn encode_header_line(name: HeaderName, obj: &HeaderObj, mail_type: MailType)
+    -> Result<String, EncodingError>
+{
+    fn write_line(handle: &mut EncodingWriter, name: HeaderName, obj: &HeaderObj)
+        -> Result<(), EncodingError>
+    {
+        handle.write_str(name.as_wire_ascii_str())?;
+        handle.write_char(SoftAsciiChar::from_unchecked(':'))?;
+        handle.write_fws();
+        obj.encode(handle)
+    }
+
+    let mut encoder = EncodingBuffer::new(mail_type);
+    {
+        let mut handle = encoder.writer();
+        match write_line(&mut handle, name, obj) {
+            Ok(()) => handle.finish_header(),
+            Err(err) => {
+                handle.undo_header();
+                return Err(err);
+            }
+        }
+    }
+    let line = String::from_utf8_lossy(encoder.as_slice());
+    Ok(line.trim_end_matches("\r\n").to_owned())
+}
+
+/// Implementation of `assert_headers_eq!`.
+pub fn check_headers_eq(map: &HeaderMap, expected: &[&str]) {
+    let got = header_names(map);
+    if got != expected {
+        panic!("header names differ\n     got: {:?}\nexpected: {:?}", got, expected);
+    }
+}
+
+/// Implementation of `assert_header_encodes!`.
+pub fn check_header_encodes(map: &HeaderMap, name: HeaderName, expected: &str, mail_type: MailType) {
+    let obj = match map.get_untyped(name).next() {
+        Some(obj) => obj,
+        None => panic!("map does not contain a {} header, it contains: {:?}",
+            name, header_names(map))
+    };
+
+    let line = match encode_header_line(name, obj, mail_type) {
+        Ok(line) => line,
+        Err(err) => panic!("encoding the {} header failed: {}", name, err)
+    };
+
+    let prefix_len = name.as_str().len() + 2;
+    let got = &line[prefix_len..];
+    if got != expected {
+        panic!("{} header encodes differently\n     got: {:?}\nexpected: {:?}",
+            name, got, expected);
+    }
+}
+
+/// Implementation of `assert_validation_err_contains!`.
+pub fn check_validation_err_contains(map: &HeaderMap, expected: &str) {
+    match map.use_contextual_validators() {
+        Ok(()) => panic!("expected validation to fail mentioning {:?}, but it succeeded", expected),
+        Err(err) => {
+            let message = err.to_string();
+            if !message.contains(expected) {
+                panic!("validation error does not mention {:?}\n   error: {:?}",
+                    expected, message);
+            }
+        }
+    }
+}
+
+/// Asserts that the map contains exactly headers with the given names in the given order.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate mail_headers;
+/// # use mail_headers::headers::*;
+/// # fn main() {
+/// let map = headers! {
+///     _From: ["me@example.com"],
+///     Subject: "hy"
+/// }.unwrap();
+///
+/// assert_headers_eq!(map, ["From", "Subject"]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_headers_eq {
+    ($map:expr, [$($name:expr),*]) => ({
+        $crate::testing::check_headers_eq(&$map, &[$($name),*]);
+    });
+}
+
+/// Asserts that the (first) header of given type encodes to the given body.
+///
+/// The expected body does not include the header name, but does include
+/// the CRLF's of folding (if the header is folded).
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate mail_headers;
+/// # extern crate mail_internals;
+/// # use mail_internals::MailType;
+/// # use mail_headers::headers::*;
+/// # fn main() {
+/// let map = headers! {
+///     Subject: "hy there"
+/// }.unwrap();
+///
+/// assert_header_encodes!(map, Subject, "hy there", MailType::Ascii);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_header_encodes {
+    ($map:expr, $header:ty, $expected:expr, $mail_type:expr) => ({
+        $crate::testing::check_header_encodes(
+            &$map, <$header as $crate::HeaderKind>::name(), $expected, $mail_type);
+    });
+}
+
+/// Asserts that running the contextual validators fails with an error mentioning given text.
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate mail_headers;
+/// # use mail_headers::headers::*;
+/// # fn main() {
+/// let map = headers! {
+///     _From: ["a@example.com", "b@example.com"]
+/// }.unwrap();
+///
+/// assert_validation_err_contains!(map, "Sender");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_validation_err_contains {
+    ($map:expr, $expected:expr) => ({
+        $crate::testing::check_validation_err_contains(&$map, $expected);
+    });
+}
+
+/// Creates a stable, diff-friendly multi-line representation of a `HeaderMap`.
+///
+/// Each header is encoded on its own (LF terminated) line(s), folded
+/// headers keep their folding. Headers which fail to encode are rendered
+/// as `Name: <encoding failed: ...>` instead of failing the whole snapshot.
+///
+/// The snapshot can optionally be created from a redacted copy of the map
+/// (see `HeaderMap::redact_for_storage`), e.g. to not include generated
+/// addresses in it.
+#[derive(Debug, Clone)]
+pub struct HeaderMapSnapshot {
+    mail_type: MailType,
+    redaction: Option<RedactionPolicy>
+}
+
+impl HeaderMapSnapshot {
+
+    /// Creates a snapshot helper encoding with given mail type.
+    pub fn new(mail_type: MailType) -> Self {
+        HeaderMapSnapshot {
+            mail_type,
+            redaction: None
+        }
+    }
+
+    /// Redacts the map with given policy before rendering it.
+    pub fn with_redaction(mut self, policy: RedactionPolicy) -> Self {
+        self.redaction = Some(policy);
+        self
+    }
+
+    /// Renders the given map.
+    pub fn render(&self, map: &HeaderMap) -> String {
+        let redacted;
+        let map = match self.redaction {
+            Some(ref policy) => {
+                redacted = map.redact_for_storage(policy);
+                &redacted
+            },
+            None => map
+        };
+
+        let mut out = String::new();
+        for (name, obj) in map.iter() {
+            match encode_header_line(name, &**obj, self.mail_type) {
+                Ok(line) => out.push_str(&line.replace("\r\n", "\n")),
+                Err(err) => {
+                    // writing to a String can not fail
+                    let _ = write!(out, "{}: <encoding failed: {}>", name, err);
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use internals::MailType;
+    use ::map::RedactionPolicy;
+    use ::headers::{_From, Sender, Subject, Bcc, Comments};
+    use super::HeaderMapSnapshot;
+
+    fn valid_map() -> ::HeaderMap {
+        headers! {
+            _From: ["me@example.com"],
+            Subject: "hy there"
+        }.unwrap()
+    }
+
+    #[test]
+    fn headers_eq_passes() {
+        assert_headers_eq!(valid_map(), ["From", "Subject"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "header names differ")]
+    fn headers_eq_fails_on_wrong_order() {
+        assert_headers_eq!(valid_map(), ["Subject", "From"]);
+    }
+
+    #[test]
+    fn header_encodes_passes() {
+        assert_header_encodes!(valid_map(), Subject, "hy there", MailType::Ascii);
+        assert_header_encodes!(valid_map(), _From, "<me@example.com>", MailType::Ascii);
+    }
+
+    #[test]
+    #[should_panic(expected = "Subject header encodes differently")]
+    fn header_encodes_fails_on_different_body() {
+        assert_header_encodes!(valid_map(), Subject, "hy", MailType::Ascii);
+    }
+
+    #[test]
+    #[should_panic(expected = "map does not contain a Sender header")]
+    fn header_encodes_fails_on_missing_header() {
+        assert_header_encodes!(valid_map(), Sender, "<me@example.com>", MailType::Ascii);
+    }
+
+    #[test]
+    fn validation_err_contains_passes() {
+        let map = headers! {
+            _From: ["a@example.com", "b@example.com"]
+        }.unwrap();
+        assert_validation_err_contains!(map, "Sender");
+    }
+
+    #[test]
+    #[should_panic(expected = "but it succeeded")]
+    fn validation_err_contains_fails_on_valid_map() {
+        assert_validation_err_contains!(valid_map(), "Sender");
+    }
+
+    #[test]
+    #[should_panic(expected = "validation error does not mention \"Resent\"")]
+    fn validation_err_contains_fails_on_other_error() {
+        let map = headers! {
+            _From: ["a@example.com", "b@example.com"]
+        }.unwrap();
+        assert_validation_err_contains!(map, "Resent");
+    }
+
+    #[test]
+    fn snapshot_is_stable_and_multi_line() {
+        let map = headers! {
+            _From: ["me@example.com"],
+            Bcc: ["hidden@example.com"],
+            Subject: "hy there",
+            Comments: "a comment"
+        }.unwrap();
+
+        let snapshot = HeaderMapSnapshot::new(MailType::Ascii)
+            .with_redaction(RedactionPolicy::default());
+
+        let rendered = snapshot.render(&map);
+        assert_eq!(rendered, snapshot.render(&map));
+        assert_eq!(rendered, concat!(
+            "From: <me@example.com>\n",
+            "X-Redacted: Bcc (1 recipient)\n",
+            "Subject: hy there\n",
+            "Comments: a comment\n"
+        ));
+    }
+}
@@ -0,0 +1,308 @@
+//! Compile time checks of the standard traits implemented by the public types.
+//!
+//! Types intentionally not implementing a trait document it (including a
+//! `compile_fail` doc test) on the type itself, e.g. `HeaderMap` (`PartialEq`),
+//! `MediaType` (`PartialEq`), `HeaderMapDisplay` (`Sync`) and `TransformPipeline`
+//! (`Clone`, `Send`, `Sync`).
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use ::name::{HeaderName, InvalidHeaderName};
+use ::header::{Header, HeaderObj};
+use ::headers::Subject;
+use ::header_components::*;
+use ::header_components::utils::text_partition::{Partition, PartitionError};
+use ::data::{Input, InnerAscii, InnerUtf8, SimpleItem};
+use ::error::*;
+use ::map::*;
+use ::mail_type::{MailTypeRequirement, MailTypeOptions};
+use ::interner::{HeaderInterner, InternerStats};
+use ::rand_source::{HashRandSource, SeededRandSource};
+use ::related::InlinePartOptions;
+use ::transform::*;
+use ::capabilities::Capabilities;
+
+/// Asserts that each type implements all of the given traits.
+macro_rules! assert_impl {
+    ($($ty:ty: $($bound:path),+;)*) => ({
+        $({
+            fn check<T: ?Sized $(+ $bound)+>() {}
+            check::<$ty>();
+        })*
+    });
+}
+
+trait Value: Debug + Clone + Eq + Hash + Send + Sync {}
+impl<T> Value for T where T: Debug + Clone + Eq + Hash + Send + Sync {}
+
+#[test]
+fn components() {
+    assert_impl! {
+        AddressPart: Value, Copy;
+        AddressProfile: Value, Copy, Default;
+        AutoResponseSuppress: Value, Default;
+        AutoResponseSuppressToken: Value, Copy, Ord;
+        CFWS: Value;
+        ConfusableFinding: Value, Copy;
+        ConfusableReport: Value, Default;
+        ConfusableSeverity: Value, Copy, Ord;
+        DateTime: Value;
+        Disposition: Value;
+        DispositionKind: Value, Copy;
+        Domain: Value;
+        Email: Value;
+        FileMeta: Value, Default;
+        FeedbackId: Value;
+        FWS: Value;
+        ListId: Value;
+        LocalPart: Value;
+        Mailbox: Value;
+        MailboxList: Value;
+        MailboxSortKey: Value, Ord;
+        MessageId: Value;
+        MessageIdList: Value;
+        NoDisplayName: Value, Copy;
+        OptMailboxList: Value;
+        ParamIssue: Value;
+        Path: Value;
+        Phrase: Value;
+        PhraseList: Value;
+        Precedence: Value;
+        RawFieldBody: Value;
+        RawParam: Value;
+        RawUnstructured: Value;
+        RawValidation: Value, Copy;
+        ReceivedToken: Value;
+        Shared<Unstructured>: Value;
+        Substitution: Value, Copy;
+        TransferEncoding: Value, Copy;
+        Unstructured: Value;
+        UriList: Value, Default;
+        Word: Value;
+        Partition<'static>: Value, Copy;
+        PartitionError: Value, Copy;
+    }
+}
+
+#[test]
+fn components_without_equality() {
+    assert_impl! {
+        MediaType: Debug, Clone, Send, Sync;
+    }
+}
+
+#[test]
+fn data() {
+    assert_impl! {
+        Input: Value;
+        InnerAscii: Value;
+        InnerUtf8: Value;
+        SimpleItem: Value;
+    }
+}
+
+#[test]
+fn names_and_headers() {
+    assert_impl! {
+        HeaderName: Value, Copy;
+        InvalidHeaderName: Value;
+        Subject: Debug, Default, Copy, Send, Sync;
+        Header<Subject>: Debug, Clone, Send, Sync;
+        Box<HeaderObj>: Debug, Clone, Send, Sync;
+    }
+}
+
+#[test]
+fn map_types() {
+    assert_impl! {
+        HeaderMap: Debug, Clone, Default, Send, Sync;
+        HeaderMapView<'static>: Debug, Copy, Send, Sync;
+        NameIndex<'static>: Debug, Clone, Send, Sync;
+        EncodeSession: Debug, Clone, Send, Sync;
+        RedactionPolicy: Debug, Clone, Default, Send, Sync;
+        HeaderMapDisplay<'static>: Debug, Send;
+        HeaderInterner: Debug, Default, Send, Sync;
+    }
+}
+
+#[test]
+fn map_options_and_reports() {
+    assert_impl! {
+        AddressDedupRules: Value, Copy, Default;
+        BlockParseOptions: Debug, Clone, Default, Send, Sync;
+        BlockTerminator: Value, Copy, Default;
+        Capabilities: Value, Copy;
+        DateSanityOptions: Value, Copy;
+        DisplayNamePolicy: Value, Default;
+        DomainNamePolicy: Value, Default;
+        DowngradeEntry: Value;
+        DowngradeMechanism: Value, Copy;
+        DowngradeReport: Debug, Clone, Eq, Send, Sync;
+        EncodeIssue: Value;
+        EncodeIssueKind: Value;
+        HeaderDowngrade: Value;
+        InjectionGuard: Value, Copy;
+        InlinePartOptions: Value, Default;
+        InternerStats: Value, Copy, Default;
+        LegacyValue: Value;
+        ListRewriteOptions: Value;
+        MailTypeOptions: Value, Copy;
+        MailTypeRequirement: Debug, Copy, Eq, Send, Sync;
+        MapLimits: Value, Copy, Default;
+        MboxOptions: Value, Copy;
+        MergePolicy: Value, Copy;
+        NamePattern: Value;
+        OrderingPolicy: Value;
+        PairRegistry: Debug, Clone, Default, Send, Sync;
+        PseudonymizeOptions: Value, Copy, Default;
+        PublicHeadersOptions: Value, Copy, Default;
+        RawHeader: Value;
+        RecipientCountMode: Value, Copy;
+        RedactionMode: Value, Copy;
+        RepairAction: Value;
+        RepairProblem: Value;
+        RepairReport: Value, Default;
+        RepairStrategy: Value;
+        ReplyToMode: Value, Copy;
+        SenderNames: Value;
+        RewriteChange: Value;
+        RewriteReport: Value, Default;
+        SubsetMode: Value, Copy;
+        SubsetViolation: Value;
+        TrailingBytes: Value, Copy;
+        ValidationInputSpec: Value, Default;
+        ValidationInputs: Value, Default;
+        ValidationStage: Value, Copy, Default;
+        ValidatorOptions: Value, Copy, Default;
+        ValidatorCarryPolicy: Value, Default;
+        ValidatorStages: Value, Copy, Default;
+    }
+}
+
+#[test]
+fn iterators() {
+    assert_impl! {
+        Drain<'static>: Debug, Send, Sync;
+        Grouped<'static>: Debug, Send, Sync;
+        GroupedValues<'static>: Debug, Send, Sync;
+        IterGrouped<'static>: Debug, Send, Sync;
+        IterWithLabels<'static>: Debug, Send, Sync;
+        IterWithSeq<'static>: Debug, Send, Sync;
+        Names<'static>: Debug, Send, Sync;
+        ResentBlock<'static>: Debug, Clone, Send, Sync;
+        ResentBlocks<'static>: Debug, Send, Sync;
+        TypedBodies<'static, Subject>: Debug, Clone, Send, Sync;
+        TypedBodiesMut<'static, Subject>: Debug, Send, Sync;
+        ViewBodies<'static>: Debug, Send, Sync;
+        ViewIter<'static>: Debug, Send, Sync;
+        IntoIter: Debug, Send, Sync;
+    }
+}
+
+#[test]
+fn errors() {
+    assert_impl! {
+        AddressViolation: Value, Copy;
+        BlockParseError: Debug, Send, Sync;
+        BuildInValidationError: Value, Copy;
+        ChainTail: Debug, Send, Sync;
+        ComponentCreationError: Debug, Send, Sync;
+        DisplayNameViolation: Value;
+        DisplayNameViolationKind: Value, Copy;
+        DomainViolation: Value;
+        HeaderExpectationError: Debug, Send, Sync;
+        HeaderInjectionDetected: Value, Copy;
+        HeaderTooLong: Value, Copy;
+        HeaderTypeError: Debug, Send, Sync;
+        HeaderValidationError: Debug, Send, Sync;
+        InvalidDomainName: Value;
+        InvalidFeedbackIdSegment: Value, Copy;
+        LimitKind: Value, Copy;
+        LimitViolation: Value, Copy;
+        MigrationError: Debug, Send, Sync;
+        MissingCapability: Value;
+        RecipientLimitError: Debug, Send, Sync;
+        RequirementConflict: Value, Copy;
+        StringPairError: Debug, Send, Sync;
+        TransformError: Debug, Send, Sync;
+        ValidationErrors: Debug, Send, Sync;
+    }
+}
+
+#[cfg(feature="compress-values")]
+#[test]
+fn compressed_values() {
+    assert_impl! {
+        CompressedRaw: Debug, Clone, Send, Sync;
+        DecompressError: Debug, Send, Sync;
+    }
+}
+
+#[test]
+fn transforms() {
+    assert_impl! {
+        CanonicalizeOrder: Value;
+        FailurePolicy: Value, Copy;
+        PipelineReport: Debug, Default, Send, Sync;
+        Repair: Value, Default;
+        RewriteFromForList: Value;
+        StepReport: Debug, Send, Sync;
+        StepResult: Debug, Send, Sync;
+        TransformOutcome: Value, Copy;
+        TransformPipeline: Debug, Default;
+        Validate: Value, Copy, Default;
+    }
+}
+
+#[test]
+fn rand_sources() {
+    assert_impl! {
+        HashRandSource: Debug, Clone, Default, Send, Sync;
+        SeededRandSource: Debug, Clone, Send, Sync;
+    }
+}
+
+#[cfg(feature="test-helpers")]
+#[test]
+fn testing_helpers() {
+    use ::testing::HeaderMapSnapshot;
+
+    assert_impl! {
+        HeaderMapSnapshot: Debug, Clone, Send, Sync;
+    }
+}
+
+#[cfg(feature="serde-impl")]
+#[test]
+fn from_json() {
+    use ::from_json::HeaderRegistry;
+    use ::header_group::{HeaderGroupSpec, HeaderGroupEntry, EntryKind};
+
+    assert_impl! {
+        HeaderRegistry: Debug, Clone, Default, Send, Sync;
+        JsonHeaderError: Debug, Send, Sync;
+        HeaderGroupSpec: Debug, Clone, PartialEq, Default, Send, Sync;
+        HeaderGroupEntry: Debug, Clone, PartialEq, Send, Sync;
+        EntryKind: Value, Copy, Default;
+        SpecError: Debug, Send, Sync;
+        SpecErrorKind: Debug, Send, Sync;
+    }
+}
+
+#[test]
+fn lint() {
+    use ::lint::*;
+
+    assert_impl! {
+        BulkWithoutListUnsubscribe: Value, Copy;
+        LintFinding: Value;
+        LintReport: Value, Default;
+        LintSet: Debug, Default, Send, Sync;
+        ReceivedChainTooLong: Value, Copy, Default;
+        RedundantReplyTo: Value, Copy;
+        Severity: Value, Copy;
+        SubjectAllCaps: Value, Copy;
+        SubjectTooLong: Value, Copy, Default;
+        TooManyToRecipients: Value, Copy, Default;
+    }
+}
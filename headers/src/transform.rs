@@ -0,0 +1,462 @@
+//! Composable transformations of a `HeaderMap`, see `TransformPipeline`.
+//!
+//! Outgoing maps often go through a fixed sequence of steps (e.g. repair,
+//! rewrite the `From` for mailing lists, reorder, validate). Each step
+//! is a `HeaderTransform`, so the sequence can be configured as data
+//! (e.g. per tenant) and run through a `TransformPipeline`, which also
+//! decides what happens if a step fails (see `FailurePolicy`).
+use std::fmt;
+
+use ::error::TransformError;
+use ::header_components::Mailbox;
+use ::map::{
+    HeaderMap, RepairStrategy, OrderingPolicy, ListRewriteOptions,
+    rewrite_from_for_list
+};
+
+/// Whether a `HeaderTransform` changed the map.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TransformOutcome {
+    Unchanged,
+    Changed
+}
+
+impl TransformOutcome {
+
+    /// Returns `Changed` if `changed` is true, `Unchanged` else wise.
+    pub fn from_changed(changed: bool) -> Self {
+        if changed { TransformOutcome::Changed } else { TransformOutcome::Unchanged }
+    }
+
+    /// Returns true if the map was changed.
+    pub fn is_changed(&self) -> bool {
+        *self == TransformOutcome::Changed
+    }
+}
+
+/// A single step of a `TransformPipeline`.
+pub trait HeaderTransform {
+
+    /// Applies the transformation to the map.
+    ///
+    /// Transforms should not leave the map half modified if they fail,
+    /// through the pipeline does not rely on it (see `FailurePolicy`).
+    fn apply(&self, map: &mut HeaderMap) -> Result<TransformOutcome, TransformError>;
+
+    /// A short name for the transform used in reports and errors.
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+}
+
+/// Applies `HeaderMap::repair`, changes the map if any fix was applied.
+///
+/// This never fails, remaining problems are found by a later `Validate` step.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Repair(pub RepairStrategy);
+
+impl HeaderTransform for Repair {
+    fn apply(&self, map: &mut HeaderMap) -> Result<TransformOutcome, TransformError> {
+        let report = map.repair(&self.0);
+        Ok(TransformOutcome::from_changed(!report.actions.is_empty()))
+    }
+
+    fn name(&self) -> &'static str {
+        "repair"
+    }
+}
+
+/// Applies `map::rewrite_from_for_list` with given list address and options.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RewriteFromForList {
+    pub list_addr: Mailbox,
+    pub options: ListRewriteOptions
+}
+
+impl HeaderTransform for RewriteFromForList {
+    fn apply(&self, map: &mut HeaderMap) -> Result<TransformOutcome, TransformError> {
+        let report = rewrite_from_for_list(map, self.list_addr.clone(), self.options.clone())
+            .map_err(|err| TransformError::new(self.name(), err))?;
+        Ok(TransformOutcome::from_changed(!report.is_empty()))
+    }
+
+    fn name(&self) -> &'static str {
+        "rewrite-from-for-list"
+    }
+}
+
+/// Applies `HeaderMap::canonicalize_order` with given policy.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CanonicalizeOrder(pub OrderingPolicy);
+
+impl HeaderTransform for CanonicalizeOrder {
+    fn apply(&self, map: &mut HeaderMap) -> Result<TransformOutcome, TransformError> {
+        Ok(TransformOutcome::from_changed(map.canonicalize_order(&self.0)))
+    }
+
+    fn name(&self) -> &'static str {
+        "canonicalize-order"
+    }
+}
+
+/// Runs `HeaderMap::use_contextual_validators`, never changes the map.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Validate;
+
+impl HeaderTransform for Validate {
+    fn apply(&self, map: &mut HeaderMap) -> Result<TransformOutcome, TransformError> {
+        map.use_contextual_validators()
+            .map_err(|err| TransformError::new(self.name(), err))?;
+        Ok(TransformOutcome::Unchanged)
+    }
+
+    fn name(&self) -> &'static str {
+        "validate"
+    }
+}
+
+/// What a `TransformPipeline` does if a step fails.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum FailurePolicy {
+    /// Stop the pipeline and restore the map to the state it had before the pipeline run.
+    AbortAndRollback,
+    /// Undo the changes of the failed step, record the failure and continue with the next step.
+    SkipAndRecord,
+    /// Stop the pipeline, keeping all changes done so far (including the ones of the failed step).
+    AbortKeepingChanges
+}
+
+/// What happened with a single step of a pipeline run.
+#[derive(Debug)]
+pub enum StepResult {
+    /// The step was applied successfully.
+    Applied(TransformOutcome),
+    /// The step failed and was skipped (`FailurePolicy::SkipAndRecord`).
+    Skipped(TransformError),
+    /// The step failed and aborted the pipeline.
+    Failed(TransformError),
+    /// The step was not run as the pipeline was aborted before.
+    NotRun
+}
+
+/// The report of a single step, see `PipelineReport`.
+#[derive(Debug)]
+pub struct StepReport {
+    /// The name of the transform (see `HeaderTransform::name`).
+    pub transform: &'static str,
+    pub result: StepResult
+}
+
+/// The result of `TransformPipeline::run`, with one `StepReport` per step.
+#[derive(Debug, Default)]
+pub struct PipelineReport {
+    /// The reports of all steps in the order of the pipeline.
+    pub steps: Vec<StepReport>,
+    /// True if a step failed with a policy which aborts the pipeline.
+    pub aborted: bool,
+    /// True if the map was restored to the state before the pipeline run.
+    pub rolled_back: bool
+}
+
+impl PipelineReport {
+
+    /// Returns true if all steps were applied successfully.
+    pub fn is_success(&self) -> bool {
+        self.steps.iter().all(|step| match step.result {
+            StepResult::Applied(_) => true,
+            _ => false
+        })
+    }
+
+    /// Returns true if the map was changed by the pipeline run.
+    ///
+    /// A failed step kept by `AbortKeepingChanges` is assumed to have changed the map.
+    pub fn is_changed(&self) -> bool {
+        !self.rolled_back && self.steps.iter().any(|step| match step.result {
+            StepResult::Applied(outcome) => outcome.is_changed(),
+            StepResult::Failed(_) => true,
+            _ => false
+        })
+    }
+}
+
+/// A sequence of `HeaderTransform`s with a `FailurePolicy` for each step.
+///
+/// # Example
+///
+/// ```
+/// # #[macro_use]
+/// # extern crate mail_headers;
+/// # fn main() {
+/// use mail_headers::headers::*;
+/// use mail_headers::map::{RepairStrategy, OrderingPolicy};
+/// use mail_headers::transform::{
+///     TransformPipeline, FailurePolicy, Repair, CanonicalizeOrder, Validate
+/// };
+///
+/// let pipeline = TransformPipeline::new()
+///     .with_step(Repair(RepairStrategy::default()), FailurePolicy::SkipAndRecord)
+///     .with_step(CanonicalizeOrder(OrderingPolicy::rfc5322()), FailurePolicy::AbortKeepingChanges)
+///     .with_step(Validate, FailurePolicy::AbortAndRollback);
+///
+/// let mut map = headers! {
+///     Subject: "hy there",
+///     _From: ["me@example.com"]
+/// }.unwrap();
+///
+/// let report = pipeline.run(&mut map);
+/// assert!(report.is_success());
+/// assert!(report.is_changed());
+/// # }
+/// ```
+///
+/// As `HeaderTransform` does not require `Send` or `Sync` (e.g. to allow
+/// transforms caching state in a `RefCell`) a pipeline is neither `Send`
+/// nor `Sync`, and as transforms can not be cloned it's not `Clone`:
+///
+/// ```compile_fail
+/// # extern crate mail_headers;
+/// use mail_headers::transform::TransformPipeline;
+///
+/// fn assert_send<T: Send>() {}
+///
+/// # fn main() {
+/// assert_send::<TransformPipeline>();
+/// # }
+/// ```
+#[derive(Default)]
+pub struct TransformPipeline {
+    steps: Vec<(Box<dyn HeaderTransform>, FailurePolicy)>
+}
+
+impl fmt::Debug for TransformPipeline {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        let steps = self.steps.iter()
+            .map(|&(ref transform, on_failure)| (transform.name(), on_failure))
+            .collect::<Vec<_>>();
+
+        fter.debug_struct("TransformPipeline")
+            .field("steps", &steps)
+            .finish()
+    }
+}
+
+impl TransformPipeline {
+
+    /// Creates a pipeline without any steps.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a step at the end of the pipeline.
+    pub fn with_step<T>(mut self, transform: T, on_failure: FailurePolicy) -> Self
+        where T: HeaderTransform + 'static
+    {
+        self.push(Box::new(transform), on_failure);
+        self
+    }
+
+    /// Adds a (boxed) step at the end of the pipeline.
+    pub fn push(&mut self, transform: Box<dyn HeaderTransform>, on_failure: FailurePolicy) {
+        self.steps.push((transform, on_failure));
+    }
+
+    /// Returns the number of steps.
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Returns true if the pipeline has no steps.
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Applies all steps in order to the map.
+    ///
+    /// If a step fails its `FailurePolicy` decides how to continue.
+    /// Rolling back (or undoing a skipped step) restores a clone of the map
+    /// made before the pipeline run (or step), so the clones are only made
+    /// if a step uses the corresponding policy.
+    pub fn run(&self, map: &mut HeaderMap) -> PipelineReport {
+        let mut report = PipelineReport::default();
+        let needs_rollback = self.steps.iter()
+            .any(|&(_, policy)| policy == FailurePolicy::AbortAndRollback);
+        let mut snapshot = if needs_rollback { Some(map.clone()) } else { None };
+
+        let mut steps = self.steps.iter();
+        while let Some(&(ref transform, policy)) = steps.next() {
+            let step_snapshot =
+                if policy == FailurePolicy::SkipAndRecord { Some(map.clone()) } else { None };
+
+            let result = match transform.apply(map) {
+                Ok(outcome) => StepResult::Applied(outcome),
+                Err(err) => match policy {
+                    FailurePolicy::SkipAndRecord => {
+                        if let Some(step_snapshot) = step_snapshot {
+                            *map = step_snapshot;
+                        }
+                        StepResult::Skipped(err)
+                    },
+                    FailurePolicy::AbortKeepingChanges => {
+                        report.aborted = true;
+                        StepResult::Failed(err)
+                    },
+                    FailurePolicy::AbortAndRollback => {
+                        if let Some(snapshot) = snapshot.take() {
+                            *map = snapshot;
+                        }
+                        report.aborted = true;
+                        report.rolled_back = true;
+                        StepResult::Failed(err)
+                    }
+                }
+            };
+            report.steps.push(StepReport { transform: transform.name(), result });
+            if report.aborted {
+                break;
+            }
+        }
+
+        for &(ref transform, _) in steps {
+            report.steps.push(StepReport { transform: transform.name(), result: StepResult::NotRun });
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::HeaderKind;
+    use ::headers::*;
+    use super::*;
+
+    /// Adds a `Comments` header.
+    struct AddComment(&'static str);
+
+    impl HeaderTransform for AddComment {
+        fn apply(&self, map: &mut HeaderMap) -> Result<TransformOutcome, TransformError> {
+            let comment = Comments::auto_body(self.0)
+                .map_err(|err| TransformError::new(self.name(), err))?;
+            map.insert(comment);
+            Ok(TransformOutcome::Changed)
+        }
+
+        fn name(&self) -> &'static str {
+            "add-comment"
+        }
+    }
+
+    /// Adds a `Comments` header and then fails.
+    struct Broken;
+
+    impl HeaderTransform for Broken {
+        fn apply(&self, map: &mut HeaderMap) -> Result<TransformOutcome, TransformError> {
+            map.insert(Comments::auto_body("broken").unwrap());
+            Err(TransformError::new(self.name(), ::failure::err_msg("it broke")))
+        }
+
+        fn name(&self) -> &'static str {
+            "broken"
+        }
+    }
+
+    fn pipeline(policy: FailurePolicy) -> TransformPipeline {
+        TransformPipeline::new()
+            .with_step(AddComment("first"), FailurePolicy::AbortAndRollback)
+            .with_step(Broken, policy)
+            .with_step(AddComment("third"), FailurePolicy::AbortAndRollback)
+    }
+
+    fn comments(map: &HeaderMap) -> Vec<String> {
+        map.get(Comments)
+            .map(|comment| comment.unwrap().as_str().to_owned())
+            .collect()
+    }
+
+    fn base() -> HeaderMap {
+        headers! {
+            Subject: "hy there"
+        }.unwrap()
+    }
+
+    test!(abort_and_rollback {
+        let mut map = base();
+        let report = pipeline(FailurePolicy::AbortAndRollback).run(&mut map);
+
+        assert!(report.aborted);
+        assert!(report.rolled_back);
+        assert!(!report.is_success());
+        assert!(!report.is_changed());
+        assert_eq!(map.len(), 1);
+        assert!(comments(&map).is_empty());
+
+        assert_eq!(report.steps.len(), 3);
+        assert_eq!(report.steps[0].transform, "add-comment");
+        assert!(match report.steps[0].result {
+            StepResult::Applied(TransformOutcome::Changed) => true,
+            _ => false
+        });
+        match report.steps[1].result {
+            StepResult::Failed(ref err) => {
+                assert_eq!(err.transform(), "broken");
+                assert_eq!(err.error().to_string(), "it broke");
+            },
+            ref other => panic!("unexpected result: {:?}", other)
+        }
+        assert!(match report.steps[2].result { StepResult::NotRun => true, _ => false });
+    });
+
+    test!(skip_and_record {
+        let mut map = base();
+        let report = pipeline(FailurePolicy::SkipAndRecord).run(&mut map);
+
+        assert!(!report.aborted);
+        assert!(!report.rolled_back);
+        assert!(!report.is_success());
+        assert!(report.is_changed());
+        // the changes of the failed step are undone
+        assert_eq!(comments(&map), vec!["first", "third"]);
+
+        assert_eq!(report.steps[1].transform, "broken");
+        assert!(match report.steps[1].result { StepResult::Skipped(_) => true, _ => false });
+        assert!(match report.steps[2].result { StepResult::Applied(_) => true, _ => false });
+    });
+
+    test!(abort_keeping_changes {
+        let mut map = base();
+        let report = pipeline(FailurePolicy::AbortKeepingChanges).run(&mut map);
+
+        assert!(report.aborted);
+        assert!(!report.rolled_back);
+        assert!(report.is_changed());
+        assert_eq!(comments(&map), vec!["first", "broken"]);
+
+        assert!(match report.steps[1].result { StepResult::Failed(_) => true, _ => false });
+        assert!(match report.steps[2].result { StepResult::NotRun => true, _ => false });
+    });
+
+    test!(build_in_transforms {
+        let mut map = headers! {
+            Subject: "hy there",
+            _From: ["a@example.com", "b@example.com"]
+        }?;
+        let pipeline = TransformPipeline::new()
+            .with_step(Validate, FailurePolicy::SkipAndRecord)
+            .with_step(Repair(RepairStrategy::default()), FailurePolicy::AbortAndRollback)
+            .with_step(CanonicalizeOrder(OrderingPolicy::rfc5322()), FailurePolicy::AbortAndRollback)
+            .with_step(Validate, FailurePolicy::AbortAndRollback);
+
+        let report = pipeline.run(&mut map);
+        let names = report.steps.iter().map(|step| step.transform).collect::<Vec<_>>();
+        assert_eq!(names, vec!["validate", "repair", "canonicalize-order", "validate"]);
+        // the multi mailbox From needs a Sender, which is added by the repair
+        assert!(match report.steps[0].result { StepResult::Skipped(_) => true, _ => false });
+        assert!(match report.steps[1].result {
+            StepResult::Applied(TransformOutcome::Changed) => true,
+            _ => false
+        });
+        assert!(match report.steps[3].result { StepResult::Applied(_) => true, _ => false });
+        assert!(map.contains(Sender));
+        assert_ok!(map.use_contextual_validators());
+    });
+}
@@ -2,6 +2,7 @@
 
 set -eu
 
+cargo test --features traceing "$@"
 cargo test --all-features "$@"
 cargo clean -p mail-headers
 cargo build "$@"
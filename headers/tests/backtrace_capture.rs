@@ -0,0 +1,33 @@
+//! Toggling the global backtrace capture setting.
+//!
+//! The setting is process wide, so this is a separate test binary with a
+//! single test, which means no other test can observe (or race with) the
+//! changes made here.
+extern crate mail_headers;
+extern crate failure;
+
+use failure::Fail;
+use mail_headers::HeaderKind;
+use mail_headers::headers::Subject;
+use mail_headers::error::{
+    set_backtrace_capture, backtrace_capture,
+    ComponentCreationError, HeaderTypeError
+};
+
+#[test]
+fn backtrace_capture_can_be_toggled() {
+    set_backtrace_capture(false);
+    assert!(!backtrace_capture());
+    assert!(ComponentCreationError::new("Email").backtrace().is_none());
+    assert!(ComponentCreationError::new_with_str("Email", "abc").backtrace().is_none());
+    assert!(HeaderTypeError::new(Subject::name()).backtrace().is_none());
+
+    set_backtrace_capture(true);
+    assert!(backtrace_capture());
+    assert!(ComponentCreationError::new("Email").backtrace().is_some());
+    assert!(ComponentCreationError::new_with_str("Email", "abc").backtrace().is_some());
+    assert!(HeaderTypeError::new(Subject::name()).backtrace().is_some());
+
+    set_backtrace_capture(false);
+    assert!(HeaderTypeError::new(Subject::name()).backtrace().is_none());
+}
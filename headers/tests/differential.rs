@@ -0,0 +1,193 @@
+//! Differential tests comparing the different parsing/construction paths
+//! of components against each other.
+//!
+//! Each component has it's own module containing the invariants which have
+//! to hold between its parsing paths. To add a new component add a module
+//! with a `proptest!` block using the strategies from `strategies` (or new
+//! ones) and, if needed, `encode_to_string` to check that encoded values
+//! can be parsed again.
+//!
+//! Any invariant violation found should be fixed in the component and
+//! pinned with a normal unit test in the components module.
+extern crate mail_headers;
+extern crate mail_internals;
+extern crate soft_ascii_string;
+#[macro_use]
+extern crate proptest;
+
+use mail_internals::MailType;
+use mail_internals::encoder::{EncodingBuffer, EncodableInHeader};
+
+/// Encodes given value as (internationalized) header body, returns `None` if encoding failed.
+fn encode_to_string<E: EncodableInHeader>(value: &E) -> Option<String> {
+    let mut encoder = EncodingBuffer::new(MailType::Internationalized);
+    {
+        let mut handle = encoder.writer();
+        if value.encode(&mut handle).is_err() {
+            handle.undo_header();
+            return None;
+        }
+        handle.finish_header();
+    }
+    let encoded = String::from_utf8(encoder.as_slice().to_owned())
+        .expect("[BUG] encoder produced non utf8 output");
+    Some(encoded.trim_end_matches("\r\n").to_owned())
+}
+
+mod strategies {
+    //! Strategies used by the differential tests.
+    //!
+    //! The grammar-ish strategies are kept short so that the encoder
+    //! never needs to fold the output.
+
+    /// any string (including control chars and non us-ascii chars)
+    pub const ANY: &str = "(?s).{0,24}";
+
+    /// a mix of atext, specials and some non us-ascii chars
+    pub const MIXED: &str = "[a-zA-Z0-9.@\\[\\]<>\"\\\\ äö!#$%&'*+/=?^_`{|}~-]{0,24}";
+
+    /// (mostly) valid dot-atom-text
+    pub const DOT_ATOM: &str = "[a-zA-Z0-9!#$%&'*+/=?^_`{|}~-]{1,6}(\\.[a-z0-9ä-]{1,6}){0,3}\\.?";
+
+    /// (mostly) valid domain literal
+    pub const LITERAL: &str = "\\[[0-9a-z.: @]{0,12}\\]";
+}
+
+mod message_id {
+    use mail_headers::HeaderTryFrom;
+    use mail_headers::header_components::MessageId;
+    use soft_ascii_string::SoftAsciiStr;
+    use super::strategies::*;
+    use super::encode_to_string;
+
+    /// splits a message id at the first `'@'` (id-left can not contain a `'@'`)
+    fn split(id: &str) -> Option<(&str, &str)> {
+        id.find('@').map(|idx| (&id[..idx], &id[idx+1..]))
+    }
+
+    fn check_parse_and_new_agree(id: &str) {
+        let parsed = MessageId::try_from(id);
+        if let Ok(ref parsed) = parsed {
+            let (left, right) = split(id).expect("accepted message id without '@'");
+            // `MessageId::new` only accepts ascii, `try_from` also internationalized ids
+            if let Ok(left) = SoftAsciiStr::from_str(left) {
+                if let Ok(right) = SoftAsciiStr::from_str(right) {
+                    let created = MessageId::new(left, right)
+                        .expect("MessageId::new rejected id accepted by MessageId::try_from");
+                    assert_eq!(parsed.as_str(), created.as_str());
+                }
+            }
+        }
+
+        if let Some((left, right)) = split(id) {
+            if let (Ok(left), Ok(right)) = (SoftAsciiStr::from_str(left), SoftAsciiStr::from_str(right)) {
+                if let Ok(created) = MessageId::new(left, right) {
+                    assert!(parsed.is_ok(),
+                        "MessageId::try_from rejected id accepted by MessageId::new: {:?}", id);
+                    assert_eq!(created.as_str(), id);
+                }
+            }
+        }
+    }
+
+    fn check_encoded_can_be_parsed(id: &str) {
+        if let Ok(parsed) = MessageId::try_from(id) {
+            let encoded = encode_to_string(&parsed).expect("failed to encode valid message id");
+            assert!(encoded.starts_with('<') && encoded.ends_with('>'), "{:?}", encoded);
+            let reparsed = MessageId::try_from(&encoded[1..encoded.len()-1])
+                .expect("failed to reparse encoded message id");
+            assert_eq!(parsed, reparsed);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn parse_and_new_agree_any(ref id in ANY) {
+            check_parse_and_new_agree(id);
+        }
+
+        #[test]
+        fn parse_and_new_agree_mixed(ref id in MIXED) {
+            check_parse_and_new_agree(id);
+        }
+
+        #[test]
+        fn parse_and_new_agree_grammar(ref left in DOT_ATOM, ref right in DOT_ATOM, ref lit in LITERAL) {
+            check_parse_and_new_agree(&format!("{}@{}", left, right));
+            check_parse_and_new_agree(&format!("{}@{}", left, lit));
+        }
+
+        #[test]
+        fn encoded_can_be_parsed(ref left in DOT_ATOM, ref right in DOT_ATOM) {
+            check_encoded_can_be_parsed(&format!("{}@{}", left, right));
+        }
+    }
+}
+
+mod email {
+    use mail_headers::HeaderTryFrom;
+    use mail_headers::header_components::{Email, Domain, MessageId};
+    use soft_ascii_string::SoftAsciiStr;
+    use super::strategies::*;
+    use super::encode_to_string;
+
+    fn check_domain_is_valid(email: &str) {
+        if let Ok(email) = Email::try_from(email) {
+            let domain = email.domain.as_str();
+            assert!(!domain.is_empty());
+            Domain::try_from(domain).expect("Email contains invalid Domain");
+        }
+    }
+
+    /// Email domains and id-right of message ids are both dot-atom-text (or a literal)
+    fn check_domain_agrees_with_message_id(domain: &str) {
+        let as_domain = Domain::try_from(domain);
+        if let Ok(ascii) = SoftAsciiStr::from_str(domain) {
+            let as_id_right = MessageId::new(SoftAsciiStr::from_unchecked("left"), ascii);
+            if as_id_right.is_ok() {
+                assert!(as_domain.is_ok(), "Domain rejected valid id-right: {:?}", domain);
+            }
+            // id-right literals can not contain whitespace, but domain literals can
+            if as_domain.is_ok() && !domain.starts_with('[') {
+                assert!(as_id_right.is_ok(), "id-right rejected valid Domain: {:?}", domain);
+            }
+        }
+    }
+
+    fn check_encoded_can_be_parsed(email: &str) {
+        if let Ok(email) = Email::try_from(email) {
+            // some local parts can not be encoded, e.g. if they contain control chars
+            if let Some(encoded) = encode_to_string(&email) {
+                let reparsed = Email::try_from(encoded.as_str())
+                    .expect("failed to reparse encoded email");
+                assert_eq!(email.domain, reparsed.domain);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn domain_is_valid_any(ref email in ANY) {
+            check_domain_is_valid(email);
+            check_encoded_can_be_parsed(email);
+        }
+
+        #[test]
+        fn domain_is_valid_mixed(ref email in MIXED) {
+            check_domain_is_valid(email);
+            check_encoded_can_be_parsed(email);
+        }
+
+        #[test]
+        fn domain_agrees_with_message_id(ref domain in DOT_ATOM, ref lit in LITERAL, ref any in MIXED) {
+            check_domain_agrees_with_message_id(domain);
+            check_domain_agrees_with_message_id(lit);
+            check_domain_agrees_with_message_id(any);
+        }
+
+        #[test]
+        fn encoded_can_be_parsed(ref local in MIXED, ref domain in DOT_ATOM) {
+            check_encoded_can_be_parsed(&format!("{}@{}", local, domain));
+        }
+    }
+}
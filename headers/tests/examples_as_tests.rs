@@ -0,0 +1,1304 @@
+//! Small, runnable scenarios for every public `HeaderMap` method and the major components.
+//!
+//! Each scenario shows how a group of related methods is used, including
+//! the less obvious outcomes (a missing header, a header of an unexpected
+//! type, multiple bodies for one name). `CHECKLIST` maps every public
+//! method of `HeaderMap` to the scenario using it and
+//! `every_public_method_has_an_example` checks it against the sources,
+//! so adding a public method without adding an example fails the tests.
+#[macro_use]
+extern crate mail_headers;
+extern crate mail_internals;
+extern crate failure;
+
+use std::fs;
+use std::path::Path;
+
+use mail_internals::MailType;
+use mail_internals::encoder::EncodingBuffer;
+use mail_headers::{HeaderMap, HeaderKind, HeaderName, HeaderTryFrom};
+use mail_headers::headers::*;
+use mail_headers::header_components::{
+    self as components,
+    Email, Domain, Mailbox, MailboxList, Phrase, Unstructured, MessageId,
+    MediaType, DateTime, Disposition, DispositionKind, Precedence
+};
+use mail_headers::error::{
+    HeaderValidationError, HeaderExpectationError, LimitKind, RecipientLimitError
+};
+use mail_headers::map::*;
+use mail_headers::mail_type::MailTypeOptions;
+
+/// The public `HeaderMap` methods and the scenario (function in this file) showing them.
+///
+/// Extend an existing scenario or add a new one when adding a method.
+const CHECKLIST: &[(&str, &str)] = &[
+    ("new", "construction_and_capacity"),
+    ("with_capacity", "construction_and_capacity"),
+    ("len", "construction_and_capacity"),
+    ("is_empty", "construction_and_capacity"),
+    ("capacity", "construction_and_capacity"),
+    ("reserve", "construction_and_capacity"),
+    ("clear", "construction_and_capacity"),
+    ("values", "values_of_all_headers"),
+    ("values_mut", "values_of_all_headers"),
+    ("use_contextual_validators", "contextual_validation"),
+    ("add_validator", "contextual_validation"),
+    ("validators", "contextual_validation"),
+    ("validate", "contextual_validation"),
+    ("duplicate_max_one_names", "multiple_bodies_for_max_one_header"),
+    ("use_contextual_validators_staged", "staged_validation"),
+    ("validate_staged", "staged_validation"),
+    ("add_staged_validator", "staged_validation"),
+    ("validator_stages", "staged_validation"),
+    ("contains", "single_header_lookup"),
+    ("contains_kind", "header_of_unexpected_type"),
+    ("kind_mismatch", "header_of_unexpected_type"),
+    ("get_single", "single_header_lookup"),
+    ("_get_single", "single_header_lookup"),
+    ("expect_single", "single_header_lookup"),
+    ("_expect_single", "single_header_lookup"),
+    ("get_single_or", "single_header_lookup"),
+    ("get_single_mut", "single_header_mutation"),
+    ("_get_single_mut", "single_header_mutation"),
+    ("get_untyped", "untyped_lookup"),
+    ("get_untyped_mut", "untyped_lookup"),
+    ("get_untyped_single", "untyped_lookup"),
+    ("get_untyped_single_mut", "untyped_lookup"),
+    ("get", "multi_header_lookup"),
+    ("_get", "multi_header_lookup"),
+    ("get_mut", "multi_header_lookup"),
+    ("_get_mut", "multi_header_lookup"),
+    ("count_of", "multi_header_lookup"),
+    ("insert", "insertion"),
+    ("insert_at", "insertion"),
+    ("prepend", "insertion"),
+    ("try_add", "insertion"),
+    ("insert_untyped", "insertion"),
+    ("insert_all", "insert_all_and_removal"),
+    ("remove", "insert_all_and_removal"),
+    ("remove_at", "insert_all_and_removal"),
+    ("iter", "iteration_and_comparison"),
+    ("names", "iteration_and_comparison"),
+    ("eq_by_encoding", "iteration_and_comparison"),
+    ("validator_options", "lenient_validator_options"),
+    ("set_validator_options", "lenient_validator_options"),
+    ("validator_carry_policy", "validator_carry"),
+    ("set_validator_carry_policy", "validator_carry"),
+    ("clone_only", "validator_carry"),
+    ("extract_validation_inputs", "external_validation_inputs"),
+    ("validate_with", "external_validation_inputs"),
+    ("with_limits", "limited_maps"),
+    ("limits", "limited_maps"),
+    ("into_unlimited", "limited_maps"),
+    ("try_insert", "limited_maps"),
+    ("try_insert_untyped", "limited_maps"),
+    ("try_insert_all", "limited_maps"),
+    ("oversized_headers", "oversized_headers"),
+    ("encode", "encoding"),
+    ("encode_unchecked", "encoding"),
+    ("to_string", "encoding"),
+    ("display", "displaying"),
+    ("try_display", "displaying"),
+    ("encode_checked", "injection_guarded_encoding"),
+    ("encode_with_guard", "injection_guarded_encoding"),
+    ("encode_for_mbox", "mbox_encoding"),
+    ("to_block_bytes", "header_blocks"),
+    ("from_block_bytes", "header_blocks"),
+    ("to_string_pairs", "string_pairs"),
+    ("from_string_pairs", "string_pairs"),
+    ("from_legacy_pairs", "legacy_pairs"),
+    ("to_vec", "vec_snapshots"),
+    ("from_vec", "vec_snapshots"),
+    ("drain", "draining"),
+    ("drain_name", "draining"),
+    ("grouped", "grouping"),
+    ("iter_grouped", "grouping"),
+    ("combine_labeled", "labels"),
+    ("label_of", "labels"),
+    ("iter_with_labels", "labels"),
+    ("iter_with_seq", "sequence_numbers"),
+    ("seq_of", "sequence_numbers"),
+    ("max_seq", "sequence_numbers"),
+    ("combine_with", "multiple_bodies_for_max_one_header"),
+    ("canonicalize_order", "canonical_order"),
+    ("retain", "retaining"),
+    ("retain_mut", "retaining"),
+    ("remove_if_encoded", "retaining"),
+    ("split_off", "splitting"),
+    ("split_off_carrying", "splitting"),
+    ("take", "taking"),
+    ("into_typed", "taking"),
+    ("take_single", "taking"),
+    ("is_subset_of", "subsets"),
+    ("subset_violations", "subsets"),
+    ("public_headers", "public_headers"),
+    ("public_headers_with", "public_headers"),
+    ("public_headers_carrying", "public_headers"),
+    ("redact_for_storage", "redaction"),
+    ("pseudonymize", "pseudonymization"),
+    ("pseudonymize_with", "pseudonymization"),
+    ("repair", "repairing"),
+    ("resent_blocks", "resent_blocks"),
+    ("recipient_count", "recipients"),
+    ("recipient_count_deduped", "recipients"),
+    ("guard_recipient_limit", "recipients"),
+    ("build_name_index", "name_index"),
+    ("contains_any", "multi_name_queries"),
+    ("contains_all", "multi_name_queries"),
+    ("which_present", "multi_name_queries"),
+    ("contains_any_str", "multi_name_queries"),
+    ("contains_all_str", "multi_name_queries"),
+    ("which_present_str", "multi_name_queries"),
+    ("restricted_view", "restricted_view"),
+    ("downgrade_report", "downgrade_report"),
+    ("required_mail_type", "required_mail_type"),
+    ("compress_large_values", "compression"),
+    ("compress_large_values_with", "compression"),
+    ("decompress_values", "compression"),
+];
+
+/// Returns the names of all `pub fn` in `impl HeaderMap` blocks of the given source.
+fn header_map_methods(source: &str) -> Vec<String> {
+    let mut methods = Vec::new();
+    let mut in_impl = false;
+    for line in source.lines() {
+        if line.starts_with("impl HeaderMap {") {
+            in_impl = true;
+        } else if line.starts_with('}') {
+            in_impl = false;
+        } else if in_impl && line.starts_with("    pub fn ") {
+            let name = line["    pub fn ".len()..]
+                .split(|ch: char| !(ch.is_alphanumeric() || ch == '_'))
+                .next()
+                .unwrap_or("");
+            methods.push(name.to_owned());
+        }
+    }
+    methods
+}
+
+#[test]
+fn every_public_method_has_an_example() {
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut files = fs::read_dir(src.join("map"))
+        .expect("src/map to be readable")
+        .map(|entry| entry.expect("src/map entry to be readable").path())
+        .filter(|path| path.extension().map(|ext| ext == "rs").unwrap_or(false))
+        .collect::<Vec<_>>();
+    files.push(src.join("mail_type.rs"));
+
+    let mut methods = Vec::new();
+    for path in files {
+        let source = fs::read_to_string(&path)
+            .unwrap_or_else(|err| panic!("can not read {}: {}", path.display(), err));
+        methods.extend(header_map_methods(&source));
+    }
+    assert!(methods.len() > 100, "found only {} methods, did the impl layout change?", methods.len());
+
+    let missing = methods.iter()
+        .filter(|method| !CHECKLIST.iter().any(|&(listed, _)| listed == method.as_str()))
+        .collect::<Vec<_>>();
+    assert!(missing.is_empty(), "public HeaderMap methods without example: {:?}", missing);
+
+    let stale = CHECKLIST.iter()
+        .filter(|&&(listed, _)| !methods.iter().any(|method| method == listed))
+        .collect::<Vec<_>>();
+    assert!(stale.is_empty(), "CHECKLIST lists methods which do not exist: {:?}", stale);
+
+    let this_file = include_str!("examples_as_tests.rs");
+    for &(method, scenario) in CHECKLIST {
+        assert!(this_file.contains(&format!("fn {}()", scenario)),
+            "scenario {} for {} does not exist", scenario, method);
+    }
+}
+
+mod other_headers {
+    use super::components;
+
+    def_headers! {
+        test_name: validate_header_names,
+        scope: components,
+        OtherSubject, unchecked { "Subject" }, Unstructured, maxOne, None,
+        OtherResentFrom, unchecked { "Resent-From" }, Unstructured, multi, None
+    }
+}
+use self::other_headers::{OtherSubject, OtherResentFrom};
+
+fn custom_error(map: &HeaderMap) -> Result<(), HeaderValidationError> {
+    if map.contains(Comments) {
+        Err(HeaderValidationError::Custom(failure::err_msg("Comments are not allowed")))
+    } else {
+        Ok(())
+    }
+}
+
+fn names_of(map: &HeaderMap) -> Vec<&'static str> {
+    map.iter().map(|(name, _)| name.as_str()).collect()
+}
+
+#[test]
+fn construction_and_capacity() {
+    let mut map = HeaderMap::new();
+    assert!(map.is_empty());
+    assert_eq!(map.len(), 0);
+
+    map.reserve(10);
+    assert!(map.capacity() >= 10);
+
+    let mut map = HeaderMap::with_capacity(4);
+    assert!(map.capacity() >= 4);
+    map.insert(Subject::auto_body("hy there").unwrap());
+    map.insert(Comments::auto_body("a comment").unwrap());
+    assert_eq!(map.len(), 2);
+    assert!(!map.is_empty());
+
+    map.clear();
+    assert!(map.is_empty());
+    assert!(!map.contains(Subject));
+}
+
+#[test]
+fn values_of_all_headers() {
+    let mut map = headers! {
+        Subject: "hy there",
+        Comments: "a"
+    }.unwrap();
+
+    let names = map.values().map(|obj| obj.name().as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["Subject", "Comments"]);
+
+    for obj in map.values_mut() {
+        if let Some(comment) = obj.downcast_mut::<Comments>() {
+            *comment = Comments::auto_body("b").unwrap();
+        }
+    }
+    assert_eq!(map.get(Comments).next().unwrap().unwrap().as_str(), "b");
+}
+
+#[test]
+fn single_header_lookup() {
+    let map = headers! {
+        Subject: "hy there"
+    }.unwrap();
+
+    assert!(map.contains(Subject));
+    assert!(map.contains(Subject::name()));
+    assert!(!map.contains(Sender));
+
+    // `Option` for "is there such a header", `Result` for "is it of the expected type"
+    assert_eq!(map.get_single(Subject).unwrap().unwrap().as_str(), "hy there");
+    assert_eq!(map._get_single::<Subject>().unwrap().unwrap().as_str(), "hy there");
+    assert!(map.get_single(Sender).is_none());
+
+    assert_eq!(map.expect_single(Subject).unwrap().as_str(), "hy there");
+    assert_eq!(map._expect_single::<Subject>().unwrap().as_str(), "hy there");
+    match map.expect_single(Sender) {
+        Err(HeaderExpectationError::Missing { name }) => assert_eq!(name, Sender::name()),
+        other => panic!("unexpected: {:?}", other)
+    }
+
+    let default = Subject::auto_body("(no subject)").unwrap();
+    assert_eq!(map.get_single_or(&default).unwrap().as_str(), "hy there");
+    let empty = HeaderMap::new();
+    assert_eq!(empty.get_single_or(&default).unwrap().as_str(), "(no subject)");
+}
+
+#[test]
+fn header_of_unexpected_type() {
+    let map = headers! {
+        OtherSubject: "not the built-in subject"
+    }.unwrap();
+
+    assert!(map.contains(Subject));
+    assert!(map.contains_kind::<OtherSubject>());
+    assert!(!map.contains_kind::<Subject>());
+    assert!(map.kind_mismatch::<Subject>().is_some());
+    assert!(map.kind_mismatch::<OtherSubject>().is_none());
+    assert!(map.kind_mismatch::<Comments>().is_none());
+
+    assert!(map.get_single(Subject).unwrap().is_err());
+    assert!(map.get(Subject).next().unwrap().is_err());
+    match map.expect_single(Subject) {
+        Err(HeaderExpectationError::Type(_)) => {},
+        other => panic!("unexpected: {:?}", other)
+    }
+    assert_eq!(map.get_single(OtherSubject).unwrap().unwrap().as_str(), "not the built-in subject");
+}
+
+#[test]
+fn multiple_bodies_for_max_one_header() {
+    let mut map = headers! {
+        Subject: "first",
+        Comments: "a"
+    }.unwrap();
+    assert!(map.duplicate_max_one_names().is_empty());
+
+    map.combine_with(headers! {
+        Subject: "second",
+        Comments: "b"
+    }.unwrap(), MergePolicy::AppendAll);
+
+    assert_eq!(map.count_of(Subject), 2);
+    assert_eq!(map.count_of(Comments), 2);
+    assert_eq!(map.duplicate_max_one_names(), vec![Subject::name()]);
+    assert!(map.get_single(Subject).unwrap().is_err());
+    assert!(map.get_untyped_single(Subject).is_err());
+    assert!(map.validate().is_err());
+
+    let mut map = headers! { Subject: "mine" }.unwrap();
+    map.combine_with(headers! { Subject: "theirs" }.unwrap(), MergePolicy::OtherWins);
+    assert_eq!(map.get_single(Subject).unwrap().unwrap().as_str(), "theirs");
+}
+
+#[test]
+fn single_header_mutation() {
+    let mut map = headers! {
+        Subject: "hy there"
+    }.unwrap();
+
+    {
+        let subject = map.get_single_mut(Subject).unwrap().unwrap();
+        *subject = Subject::auto_body("hy you").unwrap();
+    }
+    assert_eq!(map.get_single(Subject).unwrap().unwrap().as_str(), "hy you");
+
+    if let Some(Ok(subject)) = map._get_single_mut::<Subject>() {
+        *subject = Subject::auto_body("hy all").unwrap();
+    }
+    assert_eq!(map.get_single(Subject).unwrap().unwrap().as_str(), "hy all");
+    assert!(map.get_single_mut(Sender).is_none());
+}
+
+#[test]
+fn untyped_lookup() {
+    let mut map = headers! {
+        Subject: "hy there",
+        Comments: "a",
+        Comments: "b"
+    }.unwrap();
+
+    assert_eq!(map.get_untyped(Comments).len(), 2);
+    assert_eq!(map.get_untyped(HeaderName::from_ascii_unchecked("Comments")).len(), 2);
+    assert_eq!(map.get_untyped(Sender).len(), 0);
+
+    for obj in map.get_untyped_mut(Comments) {
+        let comment = obj.downcast_mut::<Comments>().unwrap();
+        *comment = Comments::auto_body("changed").unwrap();
+    }
+    assert!(map.get(Comments).all(|comment| comment.unwrap().as_str() == "changed"));
+
+    let subject = map.get_untyped_single(Subject).unwrap().unwrap();
+    assert!(subject.is::<Subject>());
+    assert!(map.get_untyped_single(Sender).unwrap().is_none());
+    assert!(map.get_untyped_single(Comments).is_err());
+
+    {
+        let subject = map.get_untyped_single_mut(Subject).unwrap().unwrap();
+        *subject.downcast_mut::<Subject>().unwrap() = Subject::auto_body("hy you").unwrap();
+    }
+    assert_eq!(map.get_single(Subject).unwrap().unwrap().as_str(), "hy you");
+    assert!(map.get_untyped_single_mut(Comments).is_err());
+}
+
+#[test]
+fn multi_header_lookup() {
+    let mut map = headers! {
+        Comments: "a",
+        Subject: "hy there",
+        Comments: "b"
+    }.unwrap();
+
+    let comments = map.get(Comments)
+        .map(|comment| comment.unwrap().as_str().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(comments, vec!["a", "b"]);
+    assert_eq!(map._get::<Comments>().count(), 2);
+    assert_eq!(map.get(Sender).count(), 0);
+    assert_eq!(map.count_of(Comments), 2);
+    assert_eq!(map.count_of(Sender), 0);
+
+    for comment in map.get_mut(Comments) {
+        *comment.unwrap() = Comments::auto_body("c").unwrap();
+    }
+    for comment in map._get_mut::<Comments>() {
+        assert_eq!(comment.unwrap().as_str(), "c");
+    }
+}
+
+#[test]
+fn insertion() {
+    let mut map = headers! {
+        _From: ["me@example.com"],
+        Subject: "hy there"
+    }.unwrap();
+
+    // max one headers are replaced, multi headers added
+    map.insert(Subject::auto_body("hy you").unwrap());
+    map.insert(Comments::auto_body("a").unwrap());
+    map.insert(Comments::auto_body("b").unwrap());
+    assert_eq!(names_of(&map), vec!["From", "Subject", "Comments", "Comments"]);
+    assert_eq!(map.get_single(Subject).unwrap().unwrap().as_str(), "hy you");
+
+    map.insert_at(1, Sender::auto_body("me@example.com").unwrap());
+    map.prepend(Received::auto_body("mx.example.com").unwrap());
+    map.insert_at(100, ContentDescription::auto_body("at the end").unwrap());
+    assert_eq!(names_of(&map), vec![
+        "Received", "From", "Sender", "Subject", "Comments", "Comments", "Content-Description"
+    ]);
+
+    assert!(map.try_add(Subject::auto_body("not replaced").unwrap()).is_err());
+    assert!(map.try_add(Comments::auto_body("c").unwrap()).is_ok());
+    assert_eq!(map.get_single(Subject).unwrap().unwrap().as_str(), "hy you");
+
+    map.insert_untyped(Box::new(XRedacted::auto_body("untyped").unwrap()));
+    assert_eq!(map.get(XRedacted).next().unwrap().unwrap().as_str(), "untyped");
+}
+
+#[test]
+fn insert_all_and_removal() {
+    let mut map = headers! {
+        _From: ["me@example.com"],
+        Subject: "...",
+        Comments: "a"
+    }.unwrap();
+
+    map.insert_all(headers! {
+        Subject: "expected subject",
+        Comments: "b",
+        Comments: "c"
+    }.unwrap());
+    assert_eq!(map.len(), 5);
+    assert_eq!(map.get_single(Subject).unwrap().unwrap().as_str(), "expected subject");
+
+    let removed = map.remove_at(Comments, 1).unwrap();
+    assert_eq!(removed.downcast_ref::<Comments>().unwrap().as_str(), "b");
+    assert!(map.remove_at(Comments, 2).is_none());
+    assert_eq!(map.count_of(Comments), 2);
+
+    assert!(map.remove(Comments));
+    assert!(!map.remove(Comments));
+    assert_eq!(names_of(&map), vec!["From", "Subject"]);
+}
+
+#[test]
+fn iteration_and_comparison() {
+    let map = headers! {
+        Comments: "a",
+        Subject: "hy there",
+        Comments: "b"
+    }.unwrap();
+
+    assert_eq!(names_of(&map), vec!["Comments", "Subject", "Comments"]);
+    let names = map.names().map(|name| name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["Comments", "Subject"]);
+
+    let same = headers! {
+        Comments: "a",
+        OtherSubject: "hy there",
+        Comments: "b"
+    }.unwrap();
+    assert!(map.eq_by_encoding(&same, MailType::Ascii).unwrap());
+
+    let reordered = headers! {
+        Subject: "hy there",
+        Comments: "a",
+        Comments: "b"
+    }.unwrap();
+    assert!(!map.eq_by_encoding(&reordered, MailType::Ascii).unwrap());
+}
+
+#[test]
+fn contextual_validation() {
+    let mut map = headers! {
+        _From: ["a@example.com", "b@example.com"],
+        Comments: "oh no"
+    }.unwrap();
+
+    // multiple mailboxes in `From` require a `Sender`
+    assert!(map.use_contextual_validators().is_err());
+    map.insert(Sender::auto_body("a@example.com").unwrap());
+    assert!(map.use_contextual_validators().is_ok());
+
+    map.add_validator(custom_error);
+    map.add_validator(custom_error);
+    assert_eq!(map.validators().len(), 1);
+    match map.validate() {
+        Err(HeaderValidationError::Custom(_)) => {},
+        other => panic!("unexpected: {:?}", other)
+    }
+
+    map.remove(Comments);
+    assert!(map.validate().is_ok());
+}
+
+#[test]
+fn staged_validation() {
+    let mut map = headers! {
+        _From: ["a@example.com", "b@example.com"],
+        Comments: "oh no"
+    }.unwrap();
+
+    // the missing `Sender` only matters for the final message
+    assert!(map.use_contextual_validators_staged(ValidationStage::Draft).is_ok());
+    assert!(map.use_contextual_validators_staged(ValidationStage::Final).is_err());
+
+    map.add_validator(custom_error);
+    assert_eq!(map.validator_stages(custom_error), Some(ValidatorStages::FinalOnly));
+    assert!(map.validate_staged(ValidationStage::Draft).is_ok());
+
+    map.add_staged_validator(custom_error, ValidatorStages::AllStages);
+    assert_eq!(map.validator_stages(custom_error), Some(ValidatorStages::AllStages));
+    assert!(map.validate_staged(ValidationStage::Draft).is_err());
+}
+
+#[test]
+fn lenient_validator_options() {
+    let mut map = headers! {
+        ResentDate: DateTime::now(),
+        OtherResentFrom: "not a mailbox list"
+    }.unwrap();
+
+    assert_eq!(map.validator_options(), ValidatorOptions::default());
+    assert!(map.use_contextual_validators().is_err());
+
+    map.set_validator_options(ValidatorOptions::lenient());
+    assert!(map.validator_options().lenient_component_types);
+    assert!(map.use_contextual_validators().is_ok());
+}
+
+#[test]
+fn validator_carry() {
+    let mut map = headers! {
+        _From: ["a@example.com", "b@example.com"],
+        Sender: "a@example.com",
+        Subject: "hy there"
+    }.unwrap();
+    assert_eq!(*map.validator_carry_policy(), ValidatorCarryPolicy::CarryAll);
+
+    // the `From` header needs a `Sender`, which is not in the copy
+    let metadata = map.clone_only(&[_From::name()], ValidatorCarryPolicy::CarryNone);
+    assert_eq!(metadata.len(), 1);
+    assert!(metadata.use_contextual_validators().is_ok());
+    let checked = map.clone_only(&[_From::name()], ValidatorCarryPolicy::CarryAll);
+    assert!(checked.use_contextual_validators().is_err());
+
+    map.remove(Sender);
+    map.set_validator_carry_policy(ValidatorCarryPolicy::CarryOnlyFor(vec![Subject::name()]));
+    assert!(map.use_contextual_validators().is_ok());
+}
+
+#[test]
+fn external_validation_inputs() {
+    let map = headers! {
+        _From: ["me@example.com"],
+        _To: ["bounced@example.com", "ok@example.com"],
+        Subject: "hy there"
+    }.unwrap();
+
+    let spec = ValidationInputSpec::recipients().with_subject();
+    let inputs = map.extract_validation_inputs(&spec);
+    assert_eq!(inputs.addresses_of(_To).count(), 2);
+    assert_eq!(inputs.addresses_of(_From).count(), 0);
+    assert_eq!(inputs.subject, Some("hy there".to_owned()));
+
+    let suppressed = inputs.addresses_of(_To)
+        .any(|email| email.to_string() == "bounced@example.com");
+    let suppression_check = |_: &HeaderMap| if suppressed {
+        Err(HeaderValidationError::Custom(failure::err_msg("recipient is suppressed")))
+    } else {
+        Ok(())
+    };
+    assert!(map.validate_with(&[&suppression_check]).is_err());
+    assert!(map.validate_with(&[]).is_ok());
+}
+
+#[test]
+fn limited_maps() {
+    let limits = MapLimits { max_headers: Some(2), ..Default::default() };
+    let mut map = HeaderMap::with_limits(limits);
+    assert_eq!(map.limits(), limits);
+
+    map.try_insert(Comments::auto_body("one").unwrap()).unwrap();
+    map.try_insert_untyped(Box::new(Comments::auto_body("two").unwrap())).unwrap();
+    let err = map.try_insert(Comments::auto_body("three").unwrap()).unwrap_err();
+    assert_eq!(err.kind(), LimitKind::HeaderCount);
+    assert_eq!(map.len(), 2);
+
+    let mut other = HeaderMap::with_limits(limits);
+    let err = other.try_insert_all(headers! {
+        Comments: "a",
+        Comments: "b",
+        Comments: "c"
+    }.unwrap()).unwrap_err();
+    assert_eq!(err.kind(), LimitKind::HeaderCount);
+    assert_eq!(other.len(), 2);
+
+    let mut map = map.into_unlimited();
+    assert!(map.limits().is_unlimited());
+    assert_eq!(map.try_insert_all(headers! { Comments: "three" }.unwrap()).unwrap(), 1);
+    assert_eq!(map.len(), 3);
+}
+
+#[test]
+fn oversized_headers() {
+    let map = headers! {
+        Subject: "hy there",
+        Comments: "a comment which is quite a bit longer than the subject"
+    }.unwrap();
+
+    let oversized = map.oversized_headers(30);
+    assert_eq!(oversized.len(), 1);
+    assert_eq!(oversized[0].name(), Comments::name());
+    assert_eq!(oversized[0].limit(), 30);
+    assert!(oversized[0].encoded_len() > 30);
+    assert!(map.oversized_headers(998).is_empty());
+}
+
+#[test]
+fn encoding() {
+    let map = headers! {
+        _From: ["me@example.com"],
+        Subject: "hy there"
+    }.unwrap();
+
+    let mut buffer = EncodingBuffer::new(MailType::Ascii);
+    map.encode(&mut buffer).unwrap();
+    assert_eq!(buffer.as_slice(), &b"From: <me@example.com>\r\nSubject: hy there\r\n"[..]);
+    assert_eq!(map.to_string(MailType::Ascii).unwrap(), "From: <me@example.com>\r\nSubject: hy there\r\n");
+
+    // `encode` runs the contextual validators, `encode_unchecked` does not
+    let draft = headers! {
+        _From: ["a@example.com", "b@example.com"]
+    }.unwrap();
+    let mut buffer = EncodingBuffer::new(MailType::Ascii);
+    assert!(draft.encode(&mut buffer).is_err());
+    let mut buffer = EncodingBuffer::new(MailType::Ascii);
+    assert!(draft.encode_unchecked(&mut buffer).is_ok());
+}
+
+#[test]
+fn displaying() {
+    let map = headers! {
+        Subject: "hy there",
+        Comments: "some comment"
+    }.unwrap();
+
+    let display = map.display(MailType::Ascii);
+    assert_eq!(format!("{}", display), "Subject: hy there\r\nComments: some comment\r\n");
+    assert!(display.errors().is_empty());
+
+    let display = map.try_display(MailType::Internationalized).unwrap();
+    assert_eq!(display.mail_type(), MailType::Internationalized);
+}
+
+#[test]
+fn injection_guarded_encoding() {
+    let map = headers! {
+        _From: ["me@example.com"],
+        Subject: "hy there"
+    }.unwrap();
+
+    let block = map.encode_checked(MailType::Ascii).unwrap();
+    assert_eq!(block, b"From: <me@example.com>\r\nSubject: hy there\r\n".to_vec());
+    let unguarded = map.encode_with_guard(MailType::Ascii, InjectionGuard::Disabled).unwrap();
+    assert_eq!(unguarded, block);
+    let guarded = map.encode_with_guard(MailType::Ascii, InjectionGuard::Enabled).unwrap();
+    assert_eq!(guarded, block);
+}
+
+#[test]
+fn mbox_encoding() {
+    let map = headers! {
+        _From: ["me@example.com"],
+        Subject: "hy there"
+    }.unwrap();
+
+    let block = map.encode_for_mbox(MailType::Ascii, MboxOptions::default()).unwrap();
+    assert_eq!(block, b"From: <me@example.com>\nSubject: hy there\n\n".to_vec());
+}
+
+#[test]
+fn header_blocks() {
+    let map = headers! {
+        _From: ["me@example.com"],
+        Subject: "hy there"
+    }.unwrap();
+
+    let mut mail = map.to_block_bytes(MailType::Ascii, BlockTerminator::CrlfBlankLine).unwrap();
+    assert_eq!(mail, b"From: <me@example.com>\r\nSubject: hy there\r\n\r\n".to_vec());
+
+    mail.extend_from_slice(b"the body");
+    let opts = BlockParseOptions { trailing: TrailingBytes::Return, ..Default::default() };
+    let (parsed, consumed) = HeaderMap::from_block_bytes(&mail, &opts).unwrap();
+    assert_eq!(parsed.len(), 2);
+    assert_eq!(&mail[consumed..], b"the body");
+    assert_eq!(parsed.get_single(Subject).unwrap().unwrap().as_str(), "hy there");
+}
+
+#[test]
+fn string_pairs() {
+    let map = headers! {
+        _From: [("Jane Doe", "jane@example.com")],
+        Subject: "hy there"
+    }.unwrap();
+
+    let pairs = map.to_string_pairs(MailType::Ascii).unwrap();
+    assert_eq!(pairs, vec![
+        ("From".to_owned(), "Jane Doe <jane@example.com>".to_owned()),
+        ("Subject".to_owned(), "hy there".to_owned())
+    ]);
+
+    let restored = HeaderMap::from_string_pairs(pairs.clone(), &PairRegistry::builtin()).unwrap();
+    assert_eq!(restored.to_string_pairs(MailType::Ascii).unwrap(), pairs);
+
+    // names without a parser are errors instead of being dropped
+    assert!(HeaderMap::from_string_pairs(vec![("From", "Jane")], &PairRegistry::new()).is_err());
+}
+
+#[test]
+fn legacy_pairs() {
+    let map = HeaderMap::from_legacy_pairs(vec![
+        (_From::name(), LegacyValue::Pair("Me".to_owned(), "me@example.com".to_owned())),
+        (_To::name(), LegacyValue::List(vec!["a@example.com".to_owned(), "b@example.com".to_owned()])),
+        (Subject::name(), LegacyValue::Str("hy there".to_owned()))
+    ]).unwrap();
+    assert_eq!(map.len(), 3);
+    assert_eq!(map.get_single(_To).unwrap().unwrap().len(), 2);
+
+    let err = HeaderMap::from_legacy_pairs(vec![
+        (Subject::name(), LegacyValue::Pair("Me".to_owned(), "me@example.com".to_owned()))
+    ]).unwrap_err();
+    assert_eq!(err.header_name(), Subject::name());
+}
+
+#[test]
+fn vec_snapshots() {
+    let map = headers! {
+        _From: ["me@example.com"],
+        Subject: "hy there"
+    }.unwrap();
+
+    let snapshot = map.to_vec();
+    assert_eq!(snapshot.len(), 2);
+    assert_eq!(snapshot[0].0, _From::name());
+
+    let restored = HeaderMap::from_vec(snapshot);
+    assert!(restored.eq_by_encoding(&map, MailType::Ascii).unwrap());
+}
+
+#[test]
+fn draining() {
+    let mut map = headers! {
+        Comments: "a",
+        Subject: "hy there",
+        Comments: "b"
+    }.unwrap();
+
+    let comments = map.drain_name(Comments)
+        .map(|(_, obj)| obj.downcast_ref::<Comments>().unwrap().as_str().to_owned())
+        .collect::<Vec<_>>();
+    assert_eq!(comments, vec!["a", "b"]);
+    assert_eq!(names_of(&map), vec!["Subject"]);
+    assert_eq!(map.drain_name(Comments).count(), 0);
+
+    let names = map.drain().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["Subject"]);
+    assert!(map.is_empty());
+}
+
+#[test]
+fn grouping() {
+    let map = headers! {
+        Comments: "a",
+        Subject: "hy there",
+        Comments: "b"
+    }.unwrap();
+
+    let grouped = map.grouped()
+        .map(|(name, values)| (name.as_str(), values.len()))
+        .collect::<Vec<_>>();
+    assert_eq!(grouped, vec![("Comments", 2), ("Subject", 1)]);
+
+    let grouped = map.iter_grouped()
+        .map(|(name, bodies)| (name.as_str(), bodies.count()))
+        .collect::<Vec<_>>();
+    assert_eq!(grouped, vec![("Comments", 2), ("Subject", 1)]);
+}
+
+#[test]
+fn labels() {
+    let mut map = HeaderMap::new();
+    map.combine_labeled(headers! {
+        _From: ["news@example.com"],
+        Subject: "default subject"
+    }.unwrap(), "global");
+    map.combine_labeled(headers! {
+        Subject: "campaign subject"
+    }.unwrap(), "campaign");
+    map.insert(Comments::auto_body("added directly").unwrap());
+
+    let labels = map.iter_with_labels()
+        .map(|(label, name, _)| (name.as_str(), label))
+        .collect::<Vec<_>>();
+    assert_eq!(labels, vec![
+        ("From", Some("global")),
+        ("Subject", Some("campaign")),
+        ("Comments", None)
+    ]);
+    assert_eq!(map.label_of(0), Some("global"));
+    assert_eq!(map.label_of(2), None);
+    assert_eq!(map.label_of(3), None);
+}
+
+#[test]
+fn sequence_numbers() {
+    assert_eq!(HeaderMap::new().max_seq(), 0);
+
+    let mut map = headers! { _From: ["me@example.com"] }.unwrap();
+    let template = headers! { Subject: "from template" }.unwrap();
+    map.insert(Comments::auto_body("added after the template was created").unwrap());
+    map.insert_all(template);
+
+    let seqs = map.iter_with_seq().map(|(seq, _, _)| seq).collect::<Vec<_>>();
+    // the subject was inserted (into the template) before the comment
+    assert!(seqs[2] < seqs[1]);
+    assert_eq!(map.seq_of(1), Some(seqs[1]));
+    assert_eq!(map.seq_of(3), None);
+    assert_eq!(map.max_seq(), seqs[1]);
+
+    // removing a header does not lower the high-water mark
+    map.remove(Comments);
+    assert_eq!(map.max_seq(), seqs[1]);
+}
+
+#[test]
+fn canonical_order() {
+    let mut map = headers! {
+        Subject: "hy there",
+        _From: ["me@example.com"]
+    }.unwrap();
+
+    assert!(map.canonicalize_order(&OrderingPolicy::rfc5322()));
+    assert_eq!(names_of(&map), vec!["From", "Subject"]);
+    assert!(!map.canonicalize_order(&OrderingPolicy::rfc5322()));
+
+    let policy = OrderingPolicy::new(vec![Subject::name()]);
+    assert!(map.canonicalize_order(&policy));
+    assert_eq!(names_of(&map), vec!["Subject", "From"]);
+}
+
+#[test]
+fn retaining() {
+    let mut map = headers! {
+        _From: ["me@example.com"],
+        Comments: "internal",
+        Subject: "hy there",
+        Comments: "token=s3cr3t"
+    }.unwrap();
+
+    let removed = map.remove_if_encoded(MailType::Ascii, |_, value| value.contains("token="));
+    assert_eq!(removed.unwrap(), 1);
+    assert_eq!(map.count_of(Comments), 1);
+
+    map.retain_mut(|_, obj| {
+        if let Some(subject) = obj.downcast_mut::<Subject>() {
+            *subject = Subject::auto_body("[external] hy there").unwrap();
+        }
+        true
+    });
+    assert_eq!(map.get_single(Subject).unwrap().unwrap().as_str(), "[external] hy there");
+
+    map.retain(|name, _| name != Comments::name());
+    assert_eq!(names_of(&map), vec!["From", "Subject"]);
+}
+
+#[test]
+fn splitting() {
+    let mut map = headers! {
+        Received: "mx1.example.com",
+        _From: ["a@example.com", "b@example.com"],
+        Sender: "a@example.com",
+        ReturnPath: "bounces@example.com"
+    }.unwrap();
+
+    let trace = map.split_off(|name, _| {
+        name == Received::name() || name == ReturnPath::name()
+    });
+    assert_eq!(names_of(&trace), vec!["Received", "Return-Path"]);
+    assert_eq!(names_of(&map), vec!["From", "Sender"]);
+
+    // the `From` header needs a `Sender`, which stays in the map
+    let from = map.split_off_carrying(|name, _| name == _From::name(), ValidatorCarryPolicy::CarryNone);
+    assert!(from.use_contextual_validators().is_ok());
+    assert_eq!(names_of(&map), vec!["Sender"]);
+}
+
+#[test]
+fn taking() {
+    let mut map = headers! {
+        Comments: "a",
+        Subject: "hy there",
+        Comments: "b",
+        OtherResentFrom: "unexpected type"
+    }.unwrap();
+
+    let comments = map.take::<Comments>().unwrap();
+    assert_eq!(comments.len(), 2);
+    assert!(!map.contains(Comments));
+    // a mismatched type does not remove anything
+    assert!(map.take::<ResentFrom>().is_err());
+    assert!(map.contains(ResentFrom));
+
+    let subject = map.take_single::<Subject>().unwrap().unwrap();
+    assert_eq!(subject.as_str(), "hy there");
+    assert!(map.take_single::<Subject>().is_none());
+
+    let (others, rest) = map.into_typed::<OtherResentFrom>().unwrap();
+    assert_eq!(others.len(), 1);
+    assert!(rest.is_empty());
+}
+
+#[test]
+fn subsets() {
+    let base = headers! {
+        _From: ["me@example.com"],
+        Subject: "hy there"
+    }.unwrap();
+    let overrides = headers! {
+        Subject: "hy there"
+    }.unwrap();
+    let changed = headers! {
+        Subject: "hy you"
+    }.unwrap();
+
+    assert!(overrides.is_subset_of(&base, SubsetMode::Exact));
+    assert!(changed.is_subset_of(&base, SubsetMode::NamesOnly));
+    assert!(!changed.is_subset_of(&base, SubsetMode::Exact));
+
+    assert!(overrides.subset_violations(&base, SubsetMode::Exact).is_empty());
+    match changed.subset_violations(&base, SubsetMode::Exact).as_slice() {
+        &[SubsetViolation::MissingValue { name, found, .. }] => {
+            assert_eq!(name, Subject::name());
+            assert_eq!(found, 0);
+        },
+        other => panic!("unexpected: {:?}", other)
+    }
+}
+
+#[test]
+fn public_headers() {
+    let map = headers! {
+        Received: "mx1.example.com",
+        _From: ["a@example.com", "b@example.com"],
+        Sender: "a@example.com",
+        Bcc: ["hidden@example.com"],
+        Subject: "hy there"
+    }.unwrap();
+
+    let public = map.public_headers();
+    assert_eq!(names_of(&public), vec!["From", "Sender", "Subject"]);
+
+    let opts = PublicHeadersOptions { include_trace: true, ..Default::default() };
+    let with_trace = map.public_headers_with(opts);
+    assert_eq!(names_of(&with_trace), vec!["Received", "From", "Sender", "Subject"]);
+
+    let carried = map.public_headers_carrying(opts, ValidatorCarryPolicy::CarryNone);
+    assert_eq!(carried.len(), 4);
+    assert_eq!(*carried.validator_carry_policy(), ValidatorCarryPolicy::CarryNone);
+}
+
+#[test]
+fn redaction() {
+    let map = headers! {
+        _From: ["me@example.com"],
+        Bcc: ["secret@example.com", "hidden@example.com"]
+    }.unwrap();
+
+    let redacted = map.redact_for_storage(&RedactionPolicy::default());
+    assert!(!redacted.contains(Bcc));
+    assert!(redacted.contains(_From));
+    let marker = redacted.get(XRedacted).next().unwrap().unwrap();
+    assert_eq!(marker.as_str(), "Bcc (2 recipients)");
+
+    // the map itself is not changed
+    assert!(map.contains(Bcc));
+}
+
+#[test]
+fn pseudonymization() {
+    let map = headers! {
+        _From: [("Jane Doe", "jane.doe@example.com")],
+        _To: ["bob@example.com"],
+        Subject: "hy there"
+    }.unwrap();
+
+    let fixture = map.pseudonymize(b"some long random key");
+    let encoded = fixture.to_string(MailType::Ascii).unwrap();
+    assert!(!encoded.contains("jane"));
+    assert!(!encoded.contains("bob"));
+    assert!(encoded.contains("Subject: hy there"));
+    assert!(fixture.eq_by_encoding(&map.pseudonymize(b"some long random key"), MailType::Ascii).unwrap());
+
+    let opts = PseudonymizeOptions { subject: true, ..Default::default() };
+    let fixture = map.pseudonymize_with(b"some long random key", opts);
+    assert!(!fixture.to_string(MailType::Ascii).unwrap().contains("hy there"));
+}
+
+#[test]
+fn repairing() {
+    let mut map = headers! {
+        _From: ["a@example.com", "b@example.com"]
+    }.unwrap();
+    assert!(map.use_contextual_validators().is_err());
+
+    let report = map.repair(&RepairStrategy::none());
+    assert!(!report.is_repaired());
+    assert!(report.problems.contains(&RepairProblem::MultiMailboxFromWithoutSender));
+    assert!(!map.contains(Sender));
+
+    let report = map.repair(&RepairStrategy::default());
+    assert!(report.is_repaired());
+    match report.actions.as_slice() {
+        &[RepairAction::PromotedSender { ref sender }] => {
+            assert_eq!(sender.email.to_string(), "a@example.com")
+        },
+        other => panic!("unexpected: {:?}", other)
+    }
+    assert!(map.use_contextual_validators().is_ok());
+}
+
+#[test]
+fn resent_blocks() {
+    let map = headers! {
+        ResentDate: DateTime::now(),
+        ResentFrom: ["newest@example.com"],
+        ResentDate: DateTime::now(),
+        ResentFrom: ["oldest@example.com"],
+        Subject: "hy there"
+    }.unwrap();
+
+    assert_eq!(map.resent_blocks().count(), 2);
+    let newest = map.resent_blocks().next().unwrap();
+    let from = newest.resent_from().unwrap().unwrap();
+    assert_eq!(from[0].email.to_string(), "newest@example.com");
+    assert!(newest.resent_sender().is_none());
+
+    assert_eq!(headers! { Subject: "hy there" }.unwrap().resent_blocks().count(), 0);
+}
+
+#[test]
+fn recipients() {
+    let map = headers! {
+        _From: ["me@example.com"],
+        _To: ["a@example.com", "b@example.com"],
+        Bcc: ["A@example.com"]
+    }.unwrap();
+
+    assert_eq!(map.recipient_count(RecipientCountMode::Normal).unwrap(), 3);
+    assert_eq!(map.recipient_count(RecipientCountMode::Resent).unwrap(), 0);
+    let rules = AddressDedupRules::default();
+    assert_eq!(map.recipient_count_deduped(RecipientCountMode::Normal, &rules).unwrap(), 2);
+
+    assert!(map.guard_recipient_limit(3, RecipientCountMode::Both).is_ok());
+    match map.guard_recipient_limit(2, RecipientCountMode::Both) {
+        Err(RecipientLimitError::Exceeded { count, limit }) => assert_eq!((count, limit), (3, 2)),
+        other => panic!("unexpected: {:?}", other)
+    }
+}
+
+#[test]
+fn name_index() {
+    let mut map = headers! {
+        Subject: "hy there",
+        Comments: "some text"
+    }.unwrap();
+
+    {
+        let index = map.build_name_index();
+        assert_eq!(index.len(), 2);
+        assert!(index.contains(&Subject::name()));
+        assert!(index.contains_str_ignore_case("comments"));
+        assert!(!index.contains_str_ignore_case("X-Mailer"));
+    }
+
+    map.remove(Subject);
+    assert!(!map.build_name_index().contains(&Subject::name()));
+}
+
+#[test]
+fn multi_name_queries() {
+    let map = headers! {
+        _From: ["a@example.com"],
+        Subject: "hy there"
+    }.unwrap();
+
+    let names = &[_From::name(), Date::name(), Subject::name()];
+    assert!(map.contains_any(names));
+    assert!(!map.contains_all(names));
+    assert!(map.contains_all(&[_From::name(), Subject::name()]));
+    assert_eq!(map.which_present(names), vec![true, false, true]);
+
+    assert!(map.contains_any_str(&["list-id", "SUBJECT"]));
+    assert!(!map.contains_all_str(&["from", "date"]));
+    assert_eq!(map.which_present_str(&["FROM", "Date", "subject"]), vec![true, false, true]);
+}
+
+#[test]
+fn restricted_view() {
+    let map = headers! {
+        Subject: "hy there",
+        Bcc: ["secret@example.com"]
+    }.unwrap();
+
+    let allowed = [Subject::name()];
+    let view = map.restricted_view(&allowed);
+    assert!(view.contains(Subject));
+    assert!(!view.contains(Bcc));
+    assert_eq!(view.get_single(Subject).unwrap().unwrap().as_str(), "hy there");
+    assert!(view.get_single(Bcc).is_none());
+    assert_eq!(view.iter().count(), 1);
+    assert!(map.contains(Bcc));
+}
+
+#[test]
+fn downgrade_report() {
+    let map = headers! {
+        _From: ["me@example.com"],
+        Subject: "hö there"
+    }.unwrap();
+
+    let report = map.downgrade_report(MailType::Internationalized, MailType::Ascii);
+    assert!(report.is_lossless());
+    assert_eq!(report.entries()[0].outcome, HeaderDowngrade::Unchanged);
+    assert_eq!(report.altered().count(), 1);
+
+    let map = headers! {
+        _From: ["thé@example.com"]
+    }.unwrap();
+    let report = map.downgrade_report(MailType::Internationalized, MailType::Ascii);
+    assert!(!report.is_lossless());
+    assert_eq!(report.lost().count(), 1);
+}
+
+#[test]
+fn required_mail_type() {
+    let map = headers! {
+        _From: ["me@example.com"],
+        Subject: "hy there"
+    }.unwrap();
+    assert_eq!(map.required_mail_type(MailTypeOptions::default()).unwrap(), MailType::Ascii);
+
+    let map = headers! {
+        _From: ["thé@example.com"]
+    }.unwrap();
+    assert_eq!(map.required_mail_type(MailTypeOptions::default()).unwrap(), MailType::Internationalized);
+}
+
+#[cfg(feature="compress-values")]
+#[test]
+fn compression() {
+    let mut map = headers! {
+        Subject: "hy there",
+        Comments: "a comment which is long enough to be compressed"
+    }.unwrap();
+    let encoded = map.encode_checked(MailType::Ascii).unwrap();
+
+    assert_eq!(map.compress_large_values(20), 1);
+    assert_eq!(map.encode_checked(MailType::Ascii).unwrap(), encoded);
+    assert_eq!(map.compress_large_values_with(20, &PairRegistry::builtin()), 0);
+
+    assert_eq!(map.decompress_values().unwrap(), 1);
+    assert_eq!(map.decompress_values().unwrap(), 0);
+    assert_eq!(map.encode_checked(MailType::Ascii).unwrap(), encoded);
+}
+
+#[cfg(not(feature="compress-values"))]
+#[test]
+fn compression() {
+    // the compression methods only exist with the `compress-values` feature
+}
+
+#[test]
+fn email_and_domain_constructors() {
+    let email = Email::new("jane@example.com").unwrap();
+    assert_eq!(email.local_part.as_str(), "jane");
+    assert_eq!(email.domain.as_str(), "example.com");
+    assert_eq!(email.to_string(), "jane@example.com");
+
+    let email = Email::new("jane@bücher.example").unwrap();
+    assert_eq!(email.to_ascii_string().unwrap().as_str(), "jane@xn--bcher-kva.example");
+
+    let literal = Email::new("jane@[127.0.0.1]").unwrap();
+    assert!(literal.domain.is_literal());
+    assert!(literal.domain.as_ip_addr().is_some());
+
+    assert!(Email::new("no-at-sign").is_err());
+    assert!(Email::new("jane@-example.com").is_err());
+    assert!(Email::new("jane@[not an ip]").is_err());
+
+    let domain = Domain::try_from("example.com").unwrap();
+    assert!(!domain.is_literal());
+    assert!(Domain::try_from("exa mple.com").is_err());
+}
+
+#[test]
+fn mailbox_constructors() {
+    let mailbox = Mailbox::from(Email::new("jane@example.com").unwrap());
+    assert!(mailbox.display_name.is_none());
+
+    let mailbox = Mailbox::try_from(("Jane Doe", "jane@example.com")).unwrap();
+    assert_eq!(mailbox.display_name.as_ref().unwrap().to_display_string(), "Jane Doe");
+    assert_eq!(mailbox.email.to_string(), "jane@example.com");
+    assert!(Mailbox::try_from(("Jane Doe", "not an address")).is_err());
+
+    let list = MailboxList::from_single(mailbox.clone());
+    assert_eq!(list.len(), 1);
+    let list = MailboxList::try_from(vec!["a@example.com", "b@example.com"]).unwrap();
+    assert_eq!(list.len(), 2);
+}
+
+#[test]
+fn text_constructors() {
+    let phrase = Phrase::new("Jane Doe").unwrap();
+    assert_eq!(phrase.to_display_string(), "Jane Doe");
+
+    let text = Unstructured::try_from("hy there").unwrap();
+    assert_eq!(text.as_str(), "hy there");
+    assert_eq!(text.to_string(), "hy there");
+}
+
+#[test]
+fn message_id_constructors() {
+    let id = MessageId::try_from("abc.123@example.com").unwrap();
+    assert_eq!(id.as_str(), "abc.123@example.com");
+    assert_eq!(id.to_string(), "abc.123@example.com");
+    assert!(MessageId::try_from("no at sign").is_err());
+
+    let unchecked = MessageId::from_unchecked("abc.123@example.com".to_owned());
+    assert_eq!(unchecked, id);
+}
+
+#[test]
+fn media_type_constructors() {
+    let media_type = MediaType::parse("text/plain; charset=utf-8").unwrap();
+    assert_eq!(media_type.get_param("charset"), Some("utf-8".to_owned()));
+    assert!(media_type.get_param("boundary").is_none());
+    assert!(MediaType::parse("not a media type").is_err());
+
+    let media_type = MediaType::new("text", "html").unwrap();
+    assert_eq!(media_type.get_param("charset"), None);
+}
+
+#[test]
+fn other_component_constructors() {
+    let date = DateTime::now();
+    let map = headers! { Date: date }.unwrap();
+    assert!(map.contains(Date));
+
+    assert_eq!(Disposition::inline().kind(), DispositionKind::Inline);
+    assert_eq!(Disposition::attachment().kind(), DispositionKind::Attachment);
+
+    assert_eq!(Precedence::parse("bulk").unwrap(), Precedence::Bulk);
+    assert_eq!(Precedence::try_from("list").unwrap(), Precedence::List);
+}
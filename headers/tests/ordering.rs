@@ -0,0 +1,263 @@
+//! The ordering contract of the mutating `HeaderMap` operations.
+//!
+//! The contract (also documented on `HeaderMap`) is:
+//!
+//! 1. `insert` of a "multi" header appends it at the end of the map.
+//! 2. `insert` of a "max one" header removes all headers with the same
+//!    name and then appends it at the end of the map.
+//! 3. `insert_at`/`prepend` behave like `insert` but place the header at
+//!    the given position (clamped to the end).
+//! 4. `remove` removes all headers with the given name, `remove_at` the
+//!    `index`-th header with the given name.
+//! 5. `insert_all` inserts the headers of the other map in their order,
+//!    each one like `insert`.
+//! 6. No operation changes the relative order of the headers it doesn't
+//!    remove, and `get_untyped` yields the headers of a name in map order.
+//!
+//! The contract is checked by applying random sequences of operations both
+//! to a `HeaderMap` and to `Model`, a plain `Vec` based reference
+//! implementation of the contract, and comparing the results. Any
+//! divergence is a bug in `HeaderMap` (and not in the model).
+#[macro_use]
+extern crate mail_headers;
+#[macro_use]
+extern crate proptest;
+
+use mail_headers::{HeaderMap, HeaderKind, HeaderName, Header};
+use mail_headers::headers::{Subject, ContentDescription, Comments, XRedacted};
+use mail_headers::header_components::Unstructured;
+
+/// The header kinds used by the tests, two "max one" and two "multi" ones.
+#[derive(Debug, Copy, Clone)]
+enum Kind {
+    Subject,
+    ContentDescription,
+    Comments,
+    XRedacted
+}
+
+const KINDS: &[Kind] = &[Kind::Subject, Kind::ContentDescription, Kind::Comments, Kind::XRedacted];
+
+impl Kind {
+
+    fn name(self) -> HeaderName {
+        match self {
+            Kind::Subject => Subject::name(),
+            Kind::ContentDescription => ContentDescription::name(),
+            Kind::Comments => Comments::name(),
+            Kind::XRedacted => XRedacted::name()
+        }
+    }
+
+    fn max_one(self) -> bool {
+        match self {
+            Kind::Subject | Kind::ContentDescription => true,
+            Kind::Comments | Kind::XRedacted => false
+        }
+    }
+
+    fn insert(self, map: &mut HeaderMap, text: &str) {
+        let text = Unstructured::from(text);
+        match self {
+            Kind::Subject => map.insert(Header::<Subject>::new(text)),
+            Kind::ContentDescription => map.insert(Header::<ContentDescription>::new(text)),
+            Kind::Comments => map.insert(Header::<Comments>::new(text)),
+            Kind::XRedacted => map.insert(Header::<XRedacted>::new(text))
+        }
+    }
+
+    fn insert_at(self, map: &mut HeaderMap, index: usize, text: &str) {
+        let text = Unstructured::from(text);
+        match self {
+            Kind::Subject => map.insert_at(index, Header::<Subject>::new(text)),
+            Kind::ContentDescription => map.insert_at(index, Header::<ContentDescription>::new(text)),
+            Kind::Comments => map.insert_at(index, Header::<Comments>::new(text)),
+            Kind::XRedacted => map.insert_at(index, Header::<XRedacted>::new(text))
+        }
+    }
+}
+
+/// Returns the text of given body (all used kinds have a `Unstructured` body).
+fn text_of(map: &HeaderMap, name: HeaderName, index: usize) -> String {
+    let obj = map.get_untyped(name).nth(index).expect("missing body");
+    let text = if let Some(header) = obj.downcast_ref::<Subject>() {
+        header.as_str()
+    } else if let Some(header) = obj.downcast_ref::<ContentDescription>() {
+        header.as_str()
+    } else if let Some(header) = obj.downcast_ref::<Comments>() {
+        header.as_str()
+    } else if let Some(header) = obj.downcast_ref::<XRedacted>() {
+        header.as_str()
+    } else {
+        panic!("unexpected header kind")
+    };
+    text.to_owned()
+}
+
+/// The reference implementation of the ordering contract.
+#[derive(Debug, Default, Clone)]
+struct Model {
+    entries: Vec<(HeaderName, String)>
+}
+
+impl Model {
+
+    fn insert(&mut self, kind: Kind, text: &str) {
+        if kind.max_one() {
+            self.remove(kind.name());
+        }
+        self.entries.push((kind.name(), text.to_owned()));
+    }
+
+    fn insert_at(&mut self, kind: Kind, index: usize, text: &str) {
+        self.insert(kind, text);
+        let entry = self.entries.pop().unwrap();
+        let index = std::cmp::min(index, self.entries.len());
+        self.entries.insert(index, entry);
+    }
+
+    fn remove(&mut self, name: HeaderName) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|&(other, _)| other != name);
+        len != self.entries.len()
+    }
+
+    fn remove_at(&mut self, name: HeaderName, index: usize) -> Option<String> {
+        let position = self.entries.iter()
+            .enumerate()
+            .filter(|&(_, &(other, _))| other == name)
+            .map(|(position, _)| position)
+            .nth(index)?;
+        Some(self.entries.remove(position).1)
+    }
+
+    fn get(&self, name: HeaderName) -> Vec<String> {
+        self.entries.iter()
+            .filter(|&&(other, _)| other == name)
+            .map(|&(_, ref text)| text.clone())
+            .collect()
+    }
+}
+
+/// A operation applied to both the map and the model.
+#[derive(Debug, Clone)]
+enum Op {
+    Insert(usize),
+    InsertAt(usize, usize),
+    Remove(usize),
+    RemoveAt(usize, usize),
+    InsertAll(Vec<usize>)
+}
+
+fn op() -> impl proptest::strategy::Strategy<Value=Op> {
+    use proptest::prelude::*;
+    let kind = 0..KINDS.len();
+    prop_oneof![
+        kind.clone().prop_map(Op::Insert),
+        (kind.clone(), 0..8usize).prop_map(|(kind, index)| Op::InsertAt(kind, index)),
+        kind.clone().prop_map(Op::Remove),
+        (kind.clone(), 0..3usize).prop_map(|(kind, index)| Op::RemoveAt(kind, index)),
+        prop::collection::vec(kind, 0..5).prop_map(Op::InsertAll)
+    ]
+}
+
+/// Applies the operations to a map and the model, comparing them after each step.
+fn check_ops(ops: &[Op]) {
+    let mut map = HeaderMap::new();
+    let mut model = Model::default();
+    let mut counter = 0;
+    let mut next_text = || {
+        counter += 1;
+        format!("text {}", counter)
+    };
+
+    for op in ops {
+        match *op {
+            Op::Insert(kind) => {
+                let text = next_text();
+                KINDS[kind].insert(&mut map, &text);
+                model.insert(KINDS[kind], &text);
+            },
+            Op::InsertAt(kind, index) => {
+                let text = next_text();
+                KINDS[kind].insert_at(&mut map, index, &text);
+                model.insert_at(KINDS[kind], index, &text);
+            },
+            Op::Remove(kind) => {
+                let name = KINDS[kind].name();
+                assert_eq!(map.remove(name), model.remove(name), "{:?}", op);
+            },
+            Op::RemoveAt(kind, index) => {
+                let name = KINDS[kind].name();
+                let removed = map.remove_at(name, index).map(|obj| {
+                    let mut single = HeaderMap::new();
+                    single.insert_untyped(obj);
+                    text_of(&single, name, 0)
+                });
+                assert_eq!(removed, model.remove_at(name, index), "{:?}", op);
+            },
+            Op::InsertAll(ref kinds) => {
+                let mut other = HeaderMap::new();
+                for &kind in kinds {
+                    let text = next_text();
+                    KINDS[kind].insert(&mut other, &text);
+                    model.insert(KINDS[kind], &text);
+                }
+                map.insert_all(other);
+            }
+        }
+        assert_agree(&map, &model, op);
+    }
+}
+
+fn assert_agree(map: &HeaderMap, model: &Model, op: &Op) {
+    let map_names = map.iter().map(|(name, _)| name).collect::<Vec<_>>();
+    let model_names = model.entries.iter().map(|&(name, _)| name).collect::<Vec<_>>();
+    assert_eq!(map_names, model_names, "iteration order differs after {:?}", op);
+
+    for kind in KINDS {
+        let name = kind.name();
+        let expected = model.get(name);
+        let count = map.get_untyped(name).len();
+        let texts = (0..count).map(|idx| text_of(map, name, idx)).collect::<Vec<_>>();
+        assert_eq!(texts, expected, "bodies of {} differ after {:?}", name, op);
+        assert_eq!(map.contains(name), !expected.is_empty());
+    }
+
+    let seqs = map.iter_with_seq().map(|(seq, _, _)| seq).collect::<Vec<_>>();
+    assert_eq!(seqs.len(), map.len(), "sequence numbers out of sync after {:?}", op);
+}
+
+proptest! {
+    #[test]
+    fn operations_agree_with_model(ref ops in proptest::collection::vec(op(), 0..40)) {
+        check_ops(ops);
+    }
+}
+
+#[test]
+fn max_one_insert_moves_to_end() {
+    let mut map = headers! {
+        Subject: "a",
+        Comments: "b"
+    }.unwrap();
+    map.insert(Subject::auto_body("c").unwrap());
+
+    let names = map.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, vec!["Comments", "Subject"]);
+}
+
+#[test]
+fn fixed_sequence_agrees_with_model() {
+    check_ops(&[
+        Op::Insert(2),
+        Op::Insert(0),
+        Op::Insert(2),
+        Op::InsertAll(vec![3, 0, 2]),
+        Op::InsertAt(1, 1),
+        Op::RemoveAt(2, 1),
+        Op::Insert(1),
+        Op::Remove(3),
+        Op::InsertAt(3, 100)
+    ]);
+}
@@ -0,0 +1,227 @@
+//! Panic hunting tests for parsing and encoding arbitrary input.
+//!
+//! Components are created from untrusted input, so any panic while
+//! creating or encoding them is a bug (a DoS for anyone running this
+//! crate on received data). Each string is fed through every public
+//! string based constructor and every created component is encoded with
+//! both mail types. Errors are fine, panics are not.
+//!
+//! If a panic is found, fix it in the component and add the exact input
+//! to `regressions::INPUTS` (and a unit test in the components module).
+extern crate mail_headers;
+extern crate mail_internals;
+extern crate soft_ascii_string;
+#[macro_use]
+extern crate proptest;
+
+use mail_internals::MailType;
+use mail_internals::encoder::{EncodingBuffer, EncodableInHeader};
+use soft_ascii_string::SoftAsciiStr;
+
+use mail_headers::HeaderTryFrom;
+use mail_headers::header_components::*;
+use mail_headers::header_components::utils::text_partition::partition;
+use mail_headers::params::{quote_value, unquote_value};
+
+/// Encodes given value with given mail type ignoring any errors.
+fn encode_with<E: EncodableInHeader>(value: &E, mail_type: MailType) {
+    let mut encoder = EncodingBuffer::new(mail_type);
+    let mut handle = encoder.writer();
+    if value.encode(&mut handle).is_ok() {
+        handle.finish_header();
+    } else {
+        handle.undo_header();
+    }
+}
+
+/// Encodes given value under both mail types.
+fn encode_both<E: EncodableInHeader>(value: &E) {
+    encode_with(value, MailType::Ascii);
+    encode_with(value, MailType::Internationalized);
+}
+
+/// Encodes given value under both mail types, if it could be created.
+fn encode<E, R>(value: Result<E, R>) -> Option<E>
+    where E: EncodableInHeader
+{
+    value.ok().map(|value| {
+        encode_both(&value);
+        value
+    })
+}
+
+/// Feeds `text` through all string based constructors and encodes the results.
+fn check_no_panic(text: &str) {
+    let _ = partition(text);
+    let _ = unquote_value(text);
+    for &mail_type in &[MailType::Ascii, MailType::Internationalized] {
+        let _ = quote_value(text, mail_type);
+    }
+
+    encode(LocalPart::try_from(text));
+    if let Some(domain) = encode(Domain::try_from(text)) {
+        let _ = domain.skeleton();
+        let _ = domain.as_ip_addr();
+        encode_both(&ReceivedToken::Domain(domain));
+    }
+    if let Some(email) = encode(Email::try_from(text)) {
+        let _ = email.confusable_with(&email);
+        encode_both(&ReceivedToken::Address(email));
+    }
+    encode(Email::parse_lenient(text));
+    encode(Email::new_with_profile(text, AddressProfile::Rfc5322));
+    encode(Email::new_with_profile(text, AddressProfile::provider_strict()));
+    if let Ok(word) = Word::try_from(text) {
+        encode_both(&ReceivedToken::Word(word));
+    }
+    encode(Path::try_from(text));
+
+    encode(Mailbox::try_from(text));
+    encode(Mailbox::try_from((text, text)));
+    encode(Mailbox::try_from((text, "a@b.example")));
+    encode(Mailbox::try_from_with_profile(text, AddressProfile::provider_strict()));
+    encode(MailboxList::try_from(vec![text, text]));
+    encode(MailboxList::try_from_with_profile(vec![text], AddressProfile::provider_strict()));
+    encode(OptMailboxList::try_from(vec![text]));
+
+    if let Some(phrase) = encode(Phrase::try_from(text)) {
+        let _ = phrase.to_display_string();
+        let _ = phrase.to_string();
+        encode(ListId::new(Some(phrase), text));
+    }
+    encode(PhraseList::try_from(vec![text, text]));
+    if let Some(unstructured) = encode(Unstructured::try_from(text)) {
+        let _ = unstructured.to_string();
+    }
+    encode(RawUnstructured::try_from(text));
+    encode(RawFieldBody::checked(text));
+    encode(RawFieldBody::pre_folded(text));
+
+    encode(MessageId::try_from(text));
+    if let Ok(ascii) = SoftAsciiStr::from_str(text) {
+        encode(MessageId::new(ascii, ascii));
+        encode(MessageId::new(SoftAsciiStr::from_unchecked("left"), ascii));
+    }
+
+    encode(MediaType::parse(text));
+    encode(MediaType::new(text, text));
+    encode(MediaType::new("text", text));
+    encode(Disposition::try_from(text));
+    encode(ListId::parse(text));
+    encode(ListId::new(None, text));
+    encode(UriList::parse(text));
+    encode(UriList::new(vec![text]));
+    encode(Precedence::parse(text));
+    encode(AutoResponseSuppress::parse(text));
+    encode(FeedbackId::parse(text));
+    encode(FeedbackId::new(text).and_then(|id| id.with_sender_id(text)));
+}
+
+mod strategies {
+    //! Strategies used by the panic hunting tests.
+
+    /// any string (including control chars and non us-ascii chars)
+    pub const ANY: &str = "(?s).{0,32}";
+
+    /// specials, whitespace, control chars and multi byte chars
+    ///
+    /// Multi byte chars of all lengths (2, 3, 4 bytes) are mixed with the
+    /// chars input is split at, to find slicing at non char boundaries.
+    pub const ADVERSARIAL: &str =
+        "[a-z\"\\\\@<>()\\[\\].,;:=?_ \\t\\r\\näé€𝄞\\x00-\\x1f\\x7f-]{0,32}";
+
+    /// (broken) encoded words
+    pub const ENCODED_WORD: &str =
+        "=\\?[a-zA-Z0-9*-]{0,8}\\?[bBqQx]?\\?[a-zA-Z0-9+/=_?ä ]{0,16}\\?=";
+}
+
+mod hunt {
+    use proptest::prelude::ProptestConfig;
+    use super::strategies::*;
+    use super::check_no_panic;
+
+    proptest! {
+        #![proptest_config(ProptestConfig { cases: 64, .. ProptestConfig::default() })]
+
+        #[test]
+        fn any_input(ref text in ANY) {
+            check_no_panic(text);
+        }
+
+        #[test]
+        fn adversarial_input(ref text in ADVERSARIAL) {
+            check_no_panic(text);
+        }
+
+        #[test]
+        fn adversarial_emails(ref local in ADVERSARIAL, ref domain in ADVERSARIAL) {
+            check_no_panic(&format!("{}@{}", local, domain));
+            check_no_panic(&format!("<{}@[{}]>", local, domain));
+        }
+
+        #[test]
+        fn encoded_words(ref word in ENCODED_WORD, ref text in ADVERSARIAL) {
+            check_no_panic(word);
+            check_no_panic(&format!("{} {}", word, text));
+            check_no_panic(&format!("{}{}", word, word));
+        }
+    }
+}
+
+mod regressions {
+    use super::check_no_panic;
+
+    /// Inputs at the edges of the index arithmetic of the parsers.
+    const INPUTS: &[&str] = &[
+        "",
+        "@",
+        "@@",
+        "a@",
+        "@b",
+        "ä@",
+        "@ä",
+        "𝄞@𝄞",
+        "a@[",
+        "a@]",
+        "a@[]",
+        "[",
+        "]",
+        "<",
+        ">",
+        "<>",
+        "<@>",
+        "\"",
+        "\"\\",
+        "\\",
+        "=?",
+        "?=",
+        "=?=",
+        "=??=",
+        "=???=",
+        "=?utf-8?b?=",
+        "=?utf-8?q?=",
+        "=?utf-8?b?ä?=",
+        "xn--",
+        "a@xn--",
+        "a@xn--ä",
+        "a\u{0}b",
+        "\r\n",
+        "a\r\n b",
+        "\r\n\r\n",
+        " ",
+        "\t€",
+        "text/",
+        "/plain",
+        "text/plain;",
+        "text/plain; a=\"",
+        "<mailto:",
+        "list <a.b",
+    ];
+
+    #[test]
+    fn edge_case_inputs_do_not_panic() {
+        for input in INPUTS {
+            check_no_panic(input);
+        }
+    }
+}
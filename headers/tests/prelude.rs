@@ -0,0 +1,102 @@
+//! Using the crate like a downstream crate does, only through the prelude.
+//!
+//! This checks that the prelude contains everything needed to create and
+//! use header maps and that the exported macros expand correctly in an
+//! external crate (i.e. don't depend on items which are only reachable
+//! from inside of this crate).
+#[macro_use]
+extern crate mail_headers;
+
+use mail_headers::prelude::{self, *};
+
+def_headers! {
+    test_name: validate_header_names,
+    scope: prelude,
+    XMailer, unchecked { "X-Mailer" }, Unstructured, maxOne, None,
+    XTag, unchecked { "X-Tag" }, Unstructured, multi, None
+}
+
+mod with_requirement {
+    use mail_headers::prelude;
+
+    def_headers! {
+        test_name: validate_header_names,
+        scope: prelude,
+        mail_type_requirement: component,
+        XContact, unchecked { "X-Contact", wire: "X-CONTACT" }, Mailbox, maxOne, None
+    }
+}
+use self::with_requirement::XContact;
+
+fn create_headers() -> Result<HeaderMap, ComponentCreationError> {
+    headers! {
+        _From: [("Me", "me@example.com")],
+        _To: ["you@example.com"],
+        Subject: "hy there",
+        XMailer: "my mailer",
+        XTag: "a",
+        XContact: "contact@example.com",
+    }
+}
+
+#[test]
+fn headers_macro_expands_with_custom_headers() {
+    let mut map = create_headers().unwrap();
+    assert_eq!(map.len(), 6);
+    map.use_contextual_validators().unwrap();
+
+    let mailer = map.get_single(XMailer).unwrap().unwrap();
+    assert_eq!(mailer.as_str(), "my mailer");
+    assert_eq!(map.get(XTag).count(), 1);
+    assert_eq!(XContact::name().as_str(), "X-Contact");
+    assert_eq!(XContact::name().as_wire_str(), "X-CONTACT");
+
+    headers_into!(&mut map, {
+        XMailer: "other mailer",
+        XTag: "b"
+    }).unwrap();
+    assert_eq!(map.get_single(XMailer).unwrap().unwrap().as_str(), "other mailer");
+    assert_eq!(map.get(XTag).count(), 2);
+}
+
+#[test]
+fn prelude_covers_the_common_api() {
+    let subject: Header<Subject> = Subject::auto_body("hy there").unwrap();
+    assert_eq!(subject.name(), Subject.get_name());
+
+    let mut map = HeaderMap::new();
+    map.insert(subject);
+    map.insert(_From::body(MailboxList::from_single(
+        Mailbox::try_from(("Me", "me@example.com")).unwrap()
+    )));
+    map.insert(ContentType::body(MediaType::parse("text/plain; charset=utf-8").unwrap()));
+    map.insert(ContentTransferEncoding::body(TransferEncoding::QuotedPrintable));
+    map.insert(Comments::body(Unstructured::try_from("a comment").unwrap()));
+
+    let obj: &HeaderObj = map.get_untyped_single(Subject).unwrap().unwrap();
+    assert_eq!(HeaderObjTrait::name(obj), Subject::name());
+
+    let email: Email = "me@example.com".try_into().unwrap();
+    assert_eq!(email.domain.as_str(), "example.com");
+
+    // a second `From` mailbox without a `Sender` fails the validation
+    map.insert(_From::auto_body(["a@example.com", "b@example.com"]).unwrap());
+    match map.use_contextual_validators() {
+        Err(HeaderValidationError::BuildIn(err)) => {
+            assert_eq!(*err.get_context(), BuildInValidationError::MultiMailboxFromWithoutSender);
+        },
+        other => panic!("unexpected result: {:?}", other)
+    }
+}
+
+#[test]
+#[allow(deprecated)]
+fn deprecated_paths_still_work() {
+    let text = mail_headers::_SoftAsciiStr::from_str("X-Mailer").unwrap();
+    assert_eq!(HeaderName::new(text).unwrap(), XMailer::name());
+
+    let text = mail_headers::soft_ascii_string::SoftAsciiStr::from_str("X-Tag").unwrap();
+    assert_eq!(HeaderName::new(text).unwrap(), XTag::name());
+
+    let _mail_type = mail_headers::__internals::MailType::Ascii;
+}
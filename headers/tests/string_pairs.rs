@@ -0,0 +1,128 @@
+//! The round-trip guarantee of `HeaderMap::to_string_pairs`/`from_string_pairs`.
+//!
+//! The first round trip (`to_string_pairs` -> `from_string_pairs` ->
+//! `to_string_pairs`) may normalize the pairs, but any further round trip
+//! has to return exactly the pairs it started with. This is checked for a
+//! fixed corpus of maps and for generated maps, using both mail types.
+#[macro_use]
+extern crate mail_headers;
+extern crate mail_internals;
+#[macro_use]
+extern crate proptest;
+
+use mail_internals::MailType;
+use mail_headers::{HeaderMap, HeaderKind, HeaderTryFrom};
+use mail_headers::headers::*;
+use mail_headers::header_components::{ReceivedToken, Domain, Disposition, DispositionKind, FileMeta};
+use mail_headers::map::{PairRegistry, RawHeader};
+
+const MAIL_TYPES: &[MailType] = &[MailType::Ascii, MailType::Internationalized];
+
+/// Restores a map from the pairs and converts it back.
+fn round_trip(pairs: &[(String, String)], registry: &PairRegistry, mail_type: MailType)
+    -> Vec<(String, String)>
+{
+    let restored = HeaderMap::from_string_pairs(pairs.to_vec(), registry)
+        .unwrap_or_else(|err| panic!("can not restore {:?}: {}", pairs, err));
+    assert_eq!(restored.len(), pairs.len());
+    restored.to_string_pairs(mail_type).expect("restored map can not be encoded")
+}
+
+fn check_round_trip(map: &HeaderMap) {
+    let registry = PairRegistry::builtin();
+    for &mail_type in MAIL_TYPES {
+        let pairs = match map.to_string_pairs(mail_type) {
+            Ok(pairs) => pairs,
+            // e.g. a non us-ascii address in a ascii mail
+            Err(_) => continue
+        };
+        for &(_, ref value) in &pairs {
+            assert!(!value.contains('\r') && !value.contains('\n'), "not unfolded: {:?}", value);
+        }
+
+        let normalized = round_trip(&pairs, &registry, mail_type);
+        assert_eq!(round_trip(&normalized, &registry, mail_type), normalized, "{:?}", mail_type);
+    }
+}
+
+fn corpus() -> Vec<HeaderMap> {
+    vec![
+        headers! {
+            _From: [("Jane Doe", "jane@example.com")],
+            _To: ("bob@example.com", ("Doe, John", "john@example.com")),
+            Subject: "hy there"
+        }.unwrap(),
+        headers! {
+            _From: [("Jäne Döe", "jane@example.com")],
+            Sender: "relay@example.com",
+            Cc: [("\"Quoted\" Name", "cc@example.com")],
+            Subject: "ünicode and a rather long subject which will have to be folded by the encoder",
+            Comments: "a comment",
+            Comments: "another comment"
+        }.unwrap(),
+        headers! {
+            MessageId: "abc.123@example.com",
+            ReturnPath: "bounces@example.com",
+            Received: ReceivedToken::Domain(Domain::try_from("mx.example.com").unwrap()),
+            ContentDescription: "=?utf-8?q?looks_encoded?= but isn't"
+        }.unwrap(),
+        {
+            let mut map = headers! {
+                Keywords: ["first", "zweites Wört"]
+            }.unwrap();
+            map.insert(ContentDisposition::body(Disposition::new(DispositionKind::Attachment, FileMeta {
+                file_name: Some("hälö datei.png".to_owned()),
+                size: Some(4096),
+                ..Default::default()
+            })));
+            map.insert_untyped(Box::new(RawHeader::new("X-Mailer", "some  mailer; v=1.0").unwrap()));
+            map.insert_untyped(Box::new(RawHeader::new("DKIM-Signature", "v=1; d=example.com; b=Zm9v").unwrap()));
+            map
+        },
+        HeaderMap::new()
+    ]
+}
+
+#[test]
+fn corpus_round_trips() {
+    for map in corpus() {
+        check_round_trip(&map);
+    }
+}
+
+#[test]
+fn plain_maps_do_not_normalize() {
+    let map = headers! {
+        _From: [("Jane Doe", "jane@example.com")],
+        Subject: "hy there"
+    }.unwrap();
+
+    let pairs = map.to_string_pairs(MailType::Ascii).unwrap();
+    assert_eq!(pairs[0], (_From::name().as_str().to_owned(), "Jane Doe <jane@example.com>".to_owned()));
+    assert_eq!(pairs[1], ("Subject".to_owned(), "hy there".to_owned()));
+}
+
+fn generated_map(subject: &str, name: &str, local: &str, comments: &[String]) -> Option<HeaderMap> {
+    let mut map = headers! {
+        _From: [(name, format!("{}@example.com", local))],
+        Subject: subject
+    }.ok()?;
+    for comment in comments {
+        map.insert(Comments::auto_body(comment.as_str()).ok()?);
+    }
+    Some(map)
+}
+
+proptest! {
+    #[test]
+    fn generated_maps_round_trip(
+        ref subject in "[a-zA-Z0-9äöü]([a-zA-Z0-9 äöü.,:;!?()\"=_-]{0,118}[a-zA-Z0-9äöü])?",
+        ref name in "[a-zA-Z äöü.,\"]{1,30}",
+        ref local in "[a-z0-9]{1,10}",
+        ref comments in proptest::collection::vec("[a-z äö]{0,40}", 0..3)
+    ) {
+        if let Some(map) = generated_map(subject, name, local, comments) {
+            check_round_trip(&map);
+        }
+    }
+}